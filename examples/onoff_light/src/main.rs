@@ -18,20 +18,32 @@
 use core::borrow::Borrow;
 use core::pin::pin;
 
-use embassy_futures::select::select3;
+use embassy_futures::select::{select, select4};
 use log::info;
 use rs_matter::core::{CommissioningData, Matter};
 use rs_matter::data_model::cluster_basic_information::BasicInfoConfig;
-use rs_matter::data_model::cluster_on_off;
+use rs_matter::data_model::cluster_identify::{
+    self, EffectIdentifier, IdentifyCluster, IdentifyIndicator, IdentifyType,
+};
+use rs_matter::data_model::cluster_localization::LocalizationConfig;
+use rs_matter::data_model::cluster_on_off::{self, OnOffCluster};
 use rs_matter::data_model::device_types::DEV_TYPE_ON_OFF_LIGHT;
 use rs_matter::data_model::objects::*;
-use rs_matter::data_model::root_endpoint;
+use rs_matter::data_model::root_endpoint::{self, RootEndpointHandler};
+use rs_matter::data_model::sdm::ethernet_nw_diagnostics::EthNwDiagCluster;
+use rs_matter::data_model::sdm::general_commissioning::RegLocationType;
+use rs_matter::data_model::sdm::general_diagnostics::{DiagnosticsPlatform, GenDiagCluster};
+use rs_matter::data_model::sdm::nw_commissioning::NwCommCluster;
+use rs_matter::data_model::sdm::sw_diagnostics::{SwDiagCluster, SwDiagnostics};
 use rs_matter::data_model::system_model::descriptor;
 use rs_matter::error::Error;
+use rs_matter::handler_chain_type;
 use rs_matter::mdns::{MdnsRunBuffers, MdnsService};
 use rs_matter::secure_channel::spake2p::VerifierData;
 use rs_matter::transport::core::RunBuffers;
-use rs_matter::transport::network::{Ipv4Addr, Ipv6Addr, NetworkStack};
+use rs_matter::transport::network::{Ipv4Addr, Ipv6Addr, NetworkStack, PacketCounters};
+use rs_matter::utils::epoch::Epoch;
+use rs_matter::utils::rand::Rand;
 use rs_matter::utils::select::EitherUnwrap;
 
 mod dev_att;
@@ -74,6 +86,14 @@ fn run() -> Result<(), Error> {
         device_name: "OnOff Light",
         product_name: "Light123",
         vendor_name: "Vendor PQR",
+        unique_id: "onoff-light-aabbccdd",
+        manufacturing_date: "20230101",
+        part_number: "",
+        product_url: "",
+        product_label: "",
+        product_appearance: Default::default(),
+        icd: None,
+        location_capability: RegLocationType::IndoorOutdoor,
     };
 
     let (ipv4_addr, ipv6_addr, interface) = initialize_network()?;
@@ -120,7 +140,42 @@ fn run() -> Result<(), Error> {
     #[cfg(all(feature = "std", not(target_os = "espidf")))]
     let mut psm = rs_matter::persist::Psm::new(&matter, std::env::temp_dir().join("rs-matter"))?;
 
-    let handler = HandlerCompat(handler(&matter));
+    #[cfg(all(feature = "std", not(target_os = "espidf")))]
+    let diag = rs_matter::data_model::sdm::general_diagnostics::sys::SysDiagnostics::new(
+        rs_matter::data_model::sdm::general_diagnostics::BootReason::Unspecified,
+        &std::env::temp_dir().join("rs-matter").join("reboot_count"),
+    )?;
+
+    // NOTE (no_std): For no_std, provide the diagnostics of your platform here
+    #[cfg(not(all(feature = "std", not(target_os = "espidf"))))]
+    let diag = rs_matter::data_model::sdm::general_diagnostics::NoDiagnostics;
+
+    #[cfg(all(feature = "std", target_os = "linux"))]
+    let sw_diag = rs_matter::data_model::sdm::sw_diagnostics::sys::SysSwDiagnostics;
+
+    #[cfg(not(all(feature = "std", target_os = "linux")))]
+    let sw_diag = rs_matter::data_model::sdm::sw_diagnostics::NoSwDiagnostics;
+
+    let on_off = OnOffCluster::new(rand);
+    let identify = IdentifyCluster::new(IdentifyType::LightOutput, rand);
+
+    identify.set_indicator(Some(&Blinker));
+
+    let handler = HandlerCompat(handler(&matter, &diag, &sw_diag, &on_off, &identify));
+
+    #[cfg(all(feature = "std", not(target_os = "espidf")))]
+    let mut attrs = rs_matter::data_model::persist::AttrPersist::new();
+
+    #[cfg(all(feature = "std", not(target_os = "espidf")))]
+    async_io::block_on(psm.load_attrs(&mut attrs, &handler))?;
+
+    // The country code of the regulatory config is not an attribute, so it is persisted separately
+    #[cfg(all(feature = "std", not(target_os = "espidf")))]
+    let gen_comm: &rs_matter::data_model::sdm::general_commissioning::GenCommCluster =
+        handler.0 .1.get();
+
+    #[cfg(all(feature = "std", not(target_os = "espidf")))]
+    psm.load_state("gen_comm", gen_comm)?;
 
     // When using a custom UDP stack, remove the network stack initialization below
     // and call `Matter::run_piped()` instead, by utilizing the TX & RX `Pipe` structs
@@ -154,12 +209,19 @@ fn run() -> Result<(), Error> {
     let mut runner = pin!(runner);
 
     #[cfg(all(feature = "std", not(target_os = "espidf")))]
-    let mut psm_runner = pin!(psm.run());
+    let mut psm_runner = pin!(psm.run_attrs(&mut attrs, &handler, &[("gen_comm", gen_comm)]));
 
     #[cfg(not(all(feature = "std", not(target_os = "espidf"))))]
     let mut psm_runner = pin!(core::future::pending());
 
-    let runner = select3(&mut runner, &mut mdns_runner, &mut psm_runner);
+    let mut light_runner = pin!(async { select(on_off.run(), identify.run()).await.unwrap() });
+
+    let runner = select4(
+        &mut runner,
+        &mut mdns_runner,
+        &mut psm_runner,
+        &mut light_runner,
+    );
 
     #[cfg(feature = "std")]
     async_io::block_on(runner).unwrap()?;
@@ -179,28 +241,73 @@ const NODE: Node<'static> = Node {
         Endpoint {
             id: 1,
             device_type: DEV_TYPE_ON_OFF_LIGHT,
-            clusters: &[descriptor::CLUSTER, cluster_on_off::CLUSTER],
+            extra_device_types: &[],
+            clusters: &[
+                descriptor::CLUSTER,
+                cluster_identify::CLUSTER,
+                cluster_on_off::CLUSTER,
+            ],
+            client_clusters: &[],
         },
     ],
 };
 
-fn handler<'a>(matter: &'a Matter<'a>) -> impl Metadata + NonBlockingHandler + 'a {
+type LightHandler<'a> = handler_chain_type!(
+    &'a OnOffCluster<'a>,
+    &'a IdentifyCluster<'a>,
+    descriptor::DescriptorCluster<'static>
+    | RootEndpointHandler<'a>
+);
+
+fn handler<'a>(
+    matter: &'a Matter<'a>,
+    diag: &'a dyn DiagnosticsPlatform,
+    sw_diag: &'a dyn SwDiagnostics,
+    on_off: &'a OnOffCluster<'a>,
+    identify: &'a IdentifyCluster<'a>,
+) -> (Node<'static>, LightHandler<'a>) {
+    let counters: &PacketCounters = matter.borrow();
+    let epoch: Epoch = *matter.borrow();
+    let rand: Rand = *matter.borrow();
+
     (
         NODE,
-        root_endpoint::handler(0, matter)
-            .chain(
-                1,
-                descriptor::ID,
-                descriptor::DescriptorCluster::new(*matter.borrow()),
-            )
-            .chain(
-                1,
-                cluster_on_off::ID,
-                cluster_on_off::OnOffCluster::new(*matter.borrow()),
-            ),
+        root_endpoint::handler_with(
+            0,
+            matter,
+            NwCommCluster::new(rand),
+            GenDiagCluster::new(diag, None, epoch, rand),
+            EthNwDiagCluster::new(counters, epoch, rand),
+            SwDiagCluster::new(sw_diag, rand),
+            &LocalizationConfig::DEFAULT,
+        )
+        .chain(
+            1,
+            descriptor::ID,
+            descriptor::DescriptorCluster::new(*matter.borrow()),
+        )
+        .chain(1, cluster_identify::ID, identify)
+        .chain(1, cluster_on_off::ID, on_off),
     )
 }
 
+// Only logs; a real light would start and stop blinking here
+struct Blinker;
+
+impl IdentifyIndicator for Blinker {
+    fn identify_start(&self) {
+        info!("Identify: start blinking");
+    }
+
+    fn identify_stop(&self) {
+        info!("Identify: stop blinking");
+    }
+
+    fn trigger_effect(&self, effect: EffectIdentifier, _variant: u8) {
+        info!("Identify: effect {:?}", effect);
+    }
+}
+
 // NOTE (no_std): For no_std, implement here your own way of initializing the logger
 #[cfg(all(not(feature = "std"), not(target_os = "espidf")))]
 #[inline(never)]
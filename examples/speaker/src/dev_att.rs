@@ -16,7 +16,7 @@
  */
 
 use rs_matter::data_model::sdm::dev_att::{DataType, DevAttDataFetcher};
-use rs_matter::error::Error;
+use rs_matter::error::{Error, ErrorCode};
 
 pub struct HardCodedDevAtt {}
 
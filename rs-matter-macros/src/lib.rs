@@ -26,6 +26,8 @@ use syn::{
     MetaList, MetaNameValue, Type,
 };
 
+mod zap;
+
 struct TlvArgs {
     start: u8,
     datatype: String,
@@ -468,3 +470,46 @@ pub fn derive_fromtlv(item: TokenStream) -> TokenStream {
         )
     }
 }
+
+/// Generate the cluster definitions of a Matter data model XML file
+///
+/// The argument is the path of the XML file (in the format used by ZAP and the
+/// C++ SDK), relative to the directory of the crate's `Cargo.toml`.
+///
+/// For every cluster in the file, a module named after the cluster (i.e. `on_off`
+/// for the On/Off cluster) is generated, containing:
+/// - the `ID` of the cluster and its `CLUSTER` metadata
+/// - the `Attributes`, `Commands` and `RespCommands` enums
+/// - the enums, bitmaps and structs used by the cluster, as well as the
+///   request and response structs of its commands
+/// - a `ClusterHandler` trait with one method per attribute and command, and a
+///   `HandlerAdaptor` turning any `ClusterHandler` into a `Handler`
+///
+/// For example:
+///  rs_matter::data_model::import!("zcl/onoff-cluster.xml");
+#[proc_macro]
+pub fn import(input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(input as syn::LitStr);
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = std::path::Path::new(&manifest_dir).join(path.value());
+
+    let xml = std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Cannot read {}: {}", path.display(), err));
+
+    let model = zap::Model::parse(&xml)
+        .unwrap_or_else(|err| panic!("Cannot parse {}: {}", path.display(), err));
+
+    let krate = Ident::new(&get_crate_name(), Span::call_site());
+    let clusters = zap::generate(&model, &krate);
+
+    // Make sure the crate is rebuilt when the XML file changes
+    let path = path.to_string_lossy();
+
+    quote! {
+        const _: &[u8] = include_bytes!(#path);
+
+        #clusters
+    }
+    .into()
+}
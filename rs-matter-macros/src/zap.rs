@@ -0,0 +1,1449 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Code generation from the Matter data model XML files, in the format used by
+//! ZAP and the C++ SDK (`src/app/zap-templates/zcl/data-model/chip/*.xml`)
+
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::{format_ident, quote};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Privilege {
+    View,
+    Operate,
+    Manage,
+    Administer,
+}
+
+impl Privilege {
+    fn parse(privilege: &str) -> Option<Self> {
+        match privilege {
+            "view" => Some(Self::View),
+            "operate" => Some(Self::Operate),
+            "manage" => Some(Self::Manage),
+            "administer" | "admin" => Some(Self::Administer),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Quality {
+    pub scene: bool,
+    pub persistent: bool,
+    pub fixed: bool,
+    pub nullable: bool,
+}
+
+/// A struct field or a command argument
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub id: u8,
+    pub name: String,
+    pub ty: String,
+    pub array: bool,
+    pub optional: bool,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub code: u16,
+    pub name: String,
+    pub ty: String,
+    pub array: bool,
+    pub optional: bool,
+    pub writable: bool,
+    pub timed_write: bool,
+    pub read_privilege: Privilege,
+    pub write_privilege: Privilege,
+    pub quality: Quality,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub code: u32,
+    pub name: String,
+    /// `true` for requests, `false` for responses
+    pub client: bool,
+    pub optional: bool,
+    pub response: Option<String>,
+    pub args: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enum {
+    pub name: String,
+    pub ty: String,
+    pub clusters: Vec<u32>,
+    pub items: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    pub name: String,
+    pub ty: String,
+    pub clusters: Vec<u32>,
+    pub fields: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Struct {
+    pub name: String,
+    pub clusters: Vec<u32>,
+    pub fabric_scoped: bool,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    pub name: String,
+    pub code: u32,
+    pub attributes: Vec<Attribute>,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Default)]
+pub struct Model {
+    pub clusters: Vec<Cluster>,
+    pub enums: Vec<Enum>,
+    pub bitmaps: Vec<Bitmap>,
+    pub structs: Vec<Struct>,
+}
+
+impl Model {
+    pub fn parse(xml: &str) -> Result<Self, String> {
+        let doc = roxmltree::Document::parse(xml).map_err(|err| err.to_string())?;
+
+        let mut model = Self::default();
+
+        for node in doc.root_element().children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "cluster" => model.clusters.push(parse_cluster(node)?),
+                "enum" => model.enums.push(Enum {
+                    name: required(node, "name")?.to_string(),
+                    ty: node.attribute("type").unwrap_or("enum8").to_string(),
+                    clusters: parse_cluster_codes(node)?,
+                    items: parse_values(node, "item", "value")?,
+                }),
+                "bitmap" => model.bitmaps.push(Bitmap {
+                    name: required(node, "name")?.to_string(),
+                    ty: node.attribute("type").unwrap_or("bitmap8").to_string(),
+                    clusters: parse_cluster_codes(node)?,
+                    fields: parse_values(node, "field", "mask")?,
+                }),
+                "struct" => model.structs.push(Struct {
+                    name: required(node, "name")?.to_string(),
+                    clusters: parse_cluster_codes(node)?,
+                    fabric_scoped: flag(node, "isFabricScoped"),
+                    fields: parse_fields(node, "item")?,
+                }),
+                _ => (),
+            }
+        }
+
+        Ok(model)
+    }
+
+    fn enum_(&self, cluster: &Cluster, name: &str) -> Option<&Enum> {
+        find_scoped(&self.enums, cluster, name, |e| (&e.name, &e.clusters))
+    }
+
+    fn bitmap(&self, cluster: &Cluster, name: &str) -> Option<&Bitmap> {
+        find_scoped(&self.bitmaps, cluster, name, |b| (&b.name, &b.clusters))
+    }
+
+    fn struct_(&self, cluster: &Cluster, name: &str) -> Option<&Struct> {
+        find_scoped(&self.structs, cluster, name, |s| (&s.name, &s.clusters))
+    }
+}
+
+/// Cluster-scoped definitions take precedence over global ones with the same name
+fn find_scoped<'a, T, F>(items: &'a [T], cluster: &Cluster, name: &str, f: F) -> Option<&'a T>
+where
+    F: Fn(&T) -> (&String, &Vec<u32>),
+{
+    items
+        .iter()
+        .find(|item| {
+            let (item_name, clusters) = f(item);
+            item_name == name && clusters.contains(&cluster.code)
+        })
+        .or_else(|| {
+            items.iter().find(|item| {
+                let (item_name, clusters) = f(item);
+                item_name == name && clusters.is_empty()
+            })
+        })
+}
+
+fn parse_cluster(node: roxmltree::Node) -> Result<Cluster, String> {
+    let name = child_text(node, "name").ok_or("Cluster without a name")?;
+    let code = parse_int(&child_text(node, "code").ok_or("Cluster without a code")?)? as u32;
+
+    let mut attributes = Vec::new();
+    let mut commands = Vec::new();
+
+    for child in node.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "attribute" if child.attribute("side") != Some("client") => {
+                let code = parse_int(required(child, "code")?)? as u16;
+
+                // Global attributes are handled by `Cluster` itself
+                if code >= 0xF000 || child.attribute("manufacturerCode").is_some() {
+                    continue;
+                }
+
+                let (ty, array) = parse_type(child)?;
+
+                let mut quality = Quality {
+                    nullable: flag(child, "isNullable"),
+                    ..Default::default()
+                };
+
+                if let Some(q) = child.children().find(|n| n.has_tag_name("quality")) {
+                    quality.nullable |= flag(q, "nullable");
+                    quality.scene = flag(q, "scene");
+                    quality.persistent = q.attribute("persistence") == Some("nonVolatile");
+                    quality.fixed = q.attribute("persistence") == Some("fixed");
+                }
+
+                attributes.push(Attribute {
+                    code,
+                    name: attribute_name(child).ok_or("Attribute without a name")?,
+                    ty,
+                    array,
+                    optional: flag(child, "optional"),
+                    writable: flag(child, "writable"),
+                    timed_write: flag(child, "mustUseTimedWrite"),
+                    read_privilege: access_privilege(child, "read").unwrap_or(Privilege::View),
+                    write_privilege: access_privilege(child, "write").unwrap_or(Privilege::Operate),
+                    quality,
+                });
+            }
+            "command" => commands.push(Command {
+                code: parse_int(required(child, "code")?)? as u32,
+                name: required(child, "name")?.to_string(),
+                client: child.attribute("source") != Some("server"),
+                optional: flag(child, "optional"),
+                response: child.attribute("response").map(str::to_string),
+                args: parse_fields(child, "arg")?,
+            }),
+            _ => (),
+        }
+    }
+
+    Ok(Cluster {
+        name,
+        code,
+        attributes,
+        commands,
+    })
+}
+
+fn parse_fields(node: roxmltree::Node, tag: &str) -> Result<Vec<Field>, String> {
+    node.children()
+        .filter(|n| n.has_tag_name(tag))
+        .enumerate()
+        .map(|(index, child)| -> Result<Field, String> {
+            let (ty, array) = parse_type(child)?;
+
+            Ok(Field {
+                id: match child.attribute("fieldId") {
+                    Some(id) => parse_int(id)? as u8,
+                    None => index as u8,
+                },
+                name: required(child, "name")?.to_string(),
+                ty,
+                array,
+                optional: flag(child, "optional"),
+                nullable: flag(child, "isNullable"),
+            })
+        })
+        .collect()
+}
+
+fn parse_values(
+    node: roxmltree::Node,
+    tag: &str,
+    value: &str,
+) -> Result<Vec<(String, u64)>, String> {
+    node.children()
+        .filter(|n| n.has_tag_name(tag))
+        .map(|child| -> Result<(String, u64), String> {
+            Ok((
+                required(child, "name")?.to_string(),
+                parse_int(required(child, value)?)?,
+            ))
+        })
+        .collect()
+}
+
+fn parse_cluster_codes(node: roxmltree::Node) -> Result<Vec<u32>, String> {
+    node.children()
+        .filter(|n| n.has_tag_name("cluster"))
+        .map(|child| -> Result<u32, String> { Ok(parse_int(required(child, "code")?)? as u32) })
+        .collect()
+}
+
+/// Lists are described either with `array="true"` or with `type="array" entryType="..."`
+fn parse_type(node: roxmltree::Node) -> Result<(String, bool), String> {
+    let ty = required(node, "type")?;
+
+    if ty.eq_ignore_ascii_case("array") {
+        Ok((required(node, "entryType")?.to_string(), true))
+    } else {
+        Ok((ty.to_string(), flag(node, "array")))
+    }
+}
+
+fn attribute_name(node: roxmltree::Node) -> Option<String> {
+    node.attribute("name")
+        .map(str::to_string)
+        .or_else(|| child_text(node, "description"))
+        .or_else(|| {
+            node.text()
+                .map(str::trim)
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        })
+}
+
+fn access_privilege(node: roxmltree::Node, op: &str) -> Option<Privilege> {
+    node.children()
+        .filter(|n| n.has_tag_name("access") && n.attribute("op") == Some(op))
+        .find_map(|n| {
+            n.attribute("privilege")
+                .or_else(|| n.attribute("role"))
+                .and_then(Privilege::parse)
+        })
+}
+
+fn child_text(node: roxmltree::Node, tag: &str) -> Option<String> {
+    node.children()
+        .find(|n| n.has_tag_name(tag))
+        .and_then(|n| n.text())
+        .map(|text| text.trim().to_string())
+}
+
+fn required<'a>(node: roxmltree::Node<'a, '_>, attr: &str) -> Result<&'a str, String> {
+    node.attribute(attr).ok_or_else(|| {
+        format!(
+            "Element <{}> without the `{}` attribute",
+            node.tag_name().name(),
+            attr
+        )
+    })
+}
+
+fn flag(node: roxmltree::Node, attr: &str) -> bool {
+    node.attribute(attr) == Some("true")
+}
+
+fn parse_int(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+
+    let result = if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16)
+    } else {
+        value.parse()
+    };
+
+    result.map_err(|_| format!("Invalid integer `{}`", value))
+}
+
+/// How a data model type maps to Rust
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Primitive(&'static str),
+    Enum(String),
+    Bitmap(String),
+    String,
+    Octets,
+    Struct(String, bool),
+    Unknown,
+}
+
+impl Kind {
+    fn is_scalar(&self) -> bool {
+        matches!(self, Self::Primitive(_) | Self::Enum(_) | Self::Bitmap(_))
+    }
+
+    fn needs_lifetime(&self) -> bool {
+        match self {
+            Self::String | Self::Octets | Self::Unknown => true,
+            Self::Struct(_, lifetime) => *lifetime,
+            _ => false,
+        }
+    }
+}
+
+fn primitive(ty: &str) -> Option<&'static str> {
+    let rust = match ty.to_ascii_lowercase().as_str() {
+        "boolean" | "bool" => "bool",
+        "int8u" | "enum8" | "bitmap8" | "percent" | "fabric_idx" | "action_id" | "status"
+        | "priority" => "u8",
+        "int16u" | "enum16" | "bitmap16" | "percent100ths" | "vendor_id" | "group_id"
+        | "endpoint_no" | "entry_idx" => "u16",
+        "int24u" | "int32u" | "bitmap32" | "epoch_s" | "elapsed_s" | "utc" | "cluster_id"
+        | "attrib_id" | "command_id" | "devtype_id" | "field_id" | "data_ver" | "event_id"
+        | "trans_id" => "u32",
+        "int40u" | "int48u" | "int56u" | "int64u" | "bitmap64" | "epoch_us" | "posix_ms"
+        | "systime_us" | "systime_ms" | "fabric_id" | "node_id" | "event_no" => "u64",
+        "int8s" => "i8",
+        "int16s" | "temperature" => "i16",
+        "int24s" | "int32s" => "i32",
+        "int40s" | "int48s" | "int56s" | "int64s" | "amperage_ma" | "voltage_mv" | "energy_mwh"
+        | "power_mw" => "i64",
+        _ => return None,
+    };
+
+    Some(rust)
+}
+
+fn kind(model: &Model, cluster: &Cluster, lifetimes: &[(String, bool)], ty: &str) -> Kind {
+    if let Some(e) = model.enum_(cluster, ty) {
+        if e.items.is_empty() {
+            Kind::Primitive(primitive(&e.ty).unwrap_or("u8"))
+        } else {
+            Kind::Enum(e.name.clone())
+        }
+    } else if let Some(b) = model.bitmap(cluster, ty) {
+        Kind::Bitmap(b.name.clone())
+    } else if let Some(s) = model.struct_(cluster, ty) {
+        let lifetime = lifetimes
+            .iter()
+            .find(|(name, _)| *name == s.name)
+            .map(|(_, lifetime)| *lifetime)
+            .unwrap_or(true);
+
+        Kind::Struct(s.name.clone(), lifetime)
+    } else if let Some(rust) = primitive(ty) {
+        Kind::Primitive(rust)
+    } else {
+        match ty.to_ascii_lowercase().as_str() {
+            "char_string" | "long_char_string" => Kind::String,
+            "octet_string" | "long_octet_string" | "ipadr" | "ipv4adr" | "ipv6adr" | "ipv6pre"
+            | "hwadr" => Kind::Octets,
+            _ => Kind::Unknown,
+        }
+    }
+}
+
+/// Figure out which of the structs used by `cluster` need a lifetime parameter
+fn struct_lifetimes(model: &Model, cluster: &Cluster) -> Vec<(String, bool)> {
+    let structs = cluster_structs(model, cluster);
+
+    let mut lifetimes: Vec<(String, bool)> =
+        structs.iter().map(|s| (s.name.clone(), false)).collect();
+
+    loop {
+        let mut changed = false;
+
+        for (index, s) in structs.iter().enumerate() {
+            if !lifetimes[index].1
+                && s.fields.iter().any(|field| {
+                    field.array || kind(model, cluster, &lifetimes, &field.ty).needs_lifetime()
+                })
+            {
+                lifetimes[index].1 = true;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break lifetimes;
+        }
+    }
+}
+
+fn cluster_structs<'a>(model: &'a Model, cluster: &Cluster) -> Vec<&'a Struct> {
+    model
+        .structs
+        .iter()
+        .filter(|s| s.clusters.contains(&cluster.code) || s.clusters.is_empty())
+        .collect()
+}
+
+pub fn snake_case(name: &str) -> String {
+    let mut result = String::new();
+
+    for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let chars: Vec<char> = word.chars().collect();
+
+        for (index, c) in chars.iter().enumerate() {
+            let boundary = index > 0
+                && c.is_ascii_uppercase()
+                && (!chars[index - 1].is_ascii_uppercase()
+                    || chars
+                        .get(index + 1)
+                        .map(|next| next.is_ascii_lowercase())
+                        .unwrap_or(false));
+
+            if (boundary || index == 0) && !result.is_empty() && !result.ends_with('_') {
+                result.push('_');
+            }
+
+            result.push(c.to_ascii_lowercase());
+        }
+    }
+
+    result
+}
+
+pub fn pascal_case(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect()
+}
+
+fn ident(name: &str) -> Ident {
+    let name = if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    };
+
+    if syn::parse_str::<Ident>(&name).is_ok() {
+        Ident::new(&name, Span::call_site())
+    } else {
+        format_ident!("{}_", name)
+    }
+}
+
+fn literal(value: u64) -> Literal {
+    Literal::u64_unsuffixed(value)
+}
+
+/// Generate the Rust code for all clusters in `model`
+pub fn generate(model: &Model, krate: &Ident) -> TokenStream {
+    let clusters = model
+        .clusters
+        .iter()
+        .map(|cluster| cluster_module(model, cluster, krate));
+
+    quote! {
+        #(#clusters)*
+    }
+}
+
+fn imports(krate: &Ident) -> TokenStream {
+    quote! {
+        #[allow(unused_imports)]
+        use core::convert::TryInto;
+
+        #[allow(unused_imports)]
+        use #krate::data_model::objects::*;
+        #[allow(unused_imports)]
+        use #krate::error::{Error, ErrorCode};
+        #[allow(unused_imports)]
+        use #krate::tlv::{
+            FromTLV, Nullable, OctetStr, TLVArray, TLVElement, TLVWriter, TagType, ToTLV, UtfStr,
+        };
+        #[allow(unused_imports)]
+        use #krate::transport::exchange::Exchange;
+    }
+}
+
+fn cluster_module(model: &Model, cluster: &Cluster, krate: &Ident) -> TokenStream {
+    let module = ident(&snake_case(&cluster.name));
+    let doc = format!("The {} cluster (0x{:04X})", cluster.name, cluster.code);
+    let id = literal(cluster.code as _);
+
+    let imports = imports(krate);
+    let lifetimes = struct_lifetimes(model, cluster);
+
+    let enums = model
+        .enums
+        .iter()
+        .filter(|e| e.clusters.contains(&cluster.code) || e.clusters.is_empty())
+        .map(enum_type);
+    let bitmaps = model
+        .bitmaps
+        .iter()
+        .filter(|b| b.clusters.contains(&cluster.code) || b.clusters.is_empty())
+        .map(bitmap_type);
+    let structs = cluster_structs(model, cluster).into_iter().map(|s| {
+        struct_type(
+            model,
+            cluster,
+            &lifetimes,
+            &s.name,
+            s.fabric_scoped,
+            &s.fields,
+        )
+    });
+    let command_structs = cluster
+        .commands
+        .iter()
+        .filter(|cmd| !cmd.args.is_empty())
+        .map(|cmd| {
+            struct_type(
+                model,
+                cluster,
+                &lifetimes,
+                &command_struct(cmd),
+                false,
+                &cmd.args,
+            )
+        });
+
+    let attributes = attributes(model, cluster);
+    let commands = commands(cluster);
+    let cluster_const = cluster_const(cluster);
+    let handler = handler(model, cluster, &lifetimes);
+
+    quote! {
+        #[doc = #doc]
+        pub mod #module {
+            #imports
+
+            pub const ID: u32 = #id;
+
+            #attributes
+
+            #commands
+
+            #cluster_const
+
+            #(#enums)*
+
+            #(#bitmaps)*
+
+            #(#structs)*
+
+            #(#command_structs)*
+
+            #handler
+        }
+    }
+}
+
+fn command_struct(cmd: &Command) -> String {
+    if cmd.client {
+        format!("{}Request", pascal_case(&cmd.name))
+    } else {
+        pascal_case(&cmd.name)
+    }
+}
+
+fn attributes(model: &Model, cluster: &Cluster) -> TokenStream {
+    if cluster.attributes.is_empty() {
+        return quote!();
+    }
+
+    let names: Vec<_> = cluster
+        .attributes
+        .iter()
+        .map(|attr| ident(&pascal_case(&attr.name)))
+        .collect();
+    let codes: Vec<_> = cluster
+        .attributes
+        .iter()
+        .map(|attr| literal(attr.code as _))
+        .collect();
+    let metadata = cluster.attributes.iter().map(|attr| {
+        let fabric_scoped = attr.array
+            && model
+                .struct_(cluster, &attr.ty)
+                .map(|s| s.fabric_scoped)
+                .unwrap_or(false);
+
+        attribute_metadata(attr, fabric_scoped)
+    });
+
+    quote! {
+        #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+        #[repr(u16)]
+        pub enum Attributes {
+            #(#names = #codes,)*
+        }
+
+        impl Attributes {
+            pub const fn from_repr(id: u16) -> Option<Self> {
+                match id {
+                    #(#codes => Some(Self::#names),)*
+                    _ => None,
+                }
+            }
+
+            /// The metadata of this attribute, for building a `Cluster` with only
+            /// a subset of the optional attributes
+            pub const fn attribute(self) -> Attribute {
+                match self {
+                    #(Self::#names => #metadata,)*
+                }
+            }
+        }
+
+        impl core::convert::TryFrom<AttrId> for Attributes {
+            type Error = Error;
+
+            fn try_from(id: AttrId) -> Result<Self, Self::Error> {
+                Self::from_repr(id).ok_or_else(|| ErrorCode::AttributeNotFound.into())
+            }
+        }
+    }
+}
+
+fn attribute_metadata(attr: &Attribute, fabric_scoped: bool) -> TokenStream {
+    let privilege = |privilege| match privilege {
+        Privilege::View => quote!(Access::NEED_VIEW),
+        Privilege::Operate => quote!(Access::NEED_OPERATE),
+        Privilege::Manage => quote!(Access::NEED_MANAGE),
+        Privilege::Administer => quote!(Access::NEED_ADMIN),
+    };
+
+    let mut access = vec![quote!(Access::READ), privilege(attr.read_privilege)];
+
+    if attr.writable {
+        access.push(quote!(Access::WRITE));
+        access.push(privilege(attr.write_privilege));
+    }
+
+    if fabric_scoped {
+        access.push(quote!(Access::FAB_SCOPED));
+    }
+
+    if attr.timed_write {
+        access.push(quote!(Access::TIMED_ONLY));
+    }
+
+    let mut quality = vec![quote!(Quality::NONE)];
+
+    if attr.quality.scene {
+        quality.push(quote!(Quality::SCENE));
+    }
+
+    if attr.quality.persistent {
+        quality.push(quote!(Quality::PERSISTENT));
+    }
+
+    if attr.quality.fixed {
+        quality.push(quote!(Quality::FIXED));
+    }
+
+    if attr.quality.nullable {
+        quality.push(quote!(Quality::NULLABLE));
+    }
+
+    let code = literal(attr.code as _);
+    let first_access = &access[0];
+    let rest_access = &access[1..];
+    let first_quality = &quality[0];
+    let rest_quality = &quality[1..];
+
+    quote! {
+        Attribute::new(
+            #code,
+            #first_access #(.union(#rest_access))*,
+            #first_quality #(.union(#rest_quality))*,
+        )
+    }
+}
+
+fn commands(cluster: &Cluster) -> TokenStream {
+    let enum_for = |name: &str, client: bool| {
+        let commands: Vec<_> = cluster
+            .commands
+            .iter()
+            .filter(|cmd| cmd.client == client)
+            .collect();
+
+        if commands.is_empty() {
+            return quote!();
+        }
+
+        let enum_name = ident(name);
+        let names: Vec<_> = commands
+            .iter()
+            .map(|cmd| ident(&pascal_case(&cmd.name)))
+            .collect();
+        let codes: Vec<_> = commands.iter().map(|cmd| literal(cmd.code as _)).collect();
+
+        quote! {
+            #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+            #[repr(u32)]
+            pub enum #enum_name {
+                #(#names = #codes,)*
+            }
+
+            impl #enum_name {
+                pub const fn from_repr(id: u32) -> Option<Self> {
+                    match id {
+                        #(#codes => Some(Self::#names),)*
+                        _ => None,
+                    }
+                }
+            }
+
+            impl core::convert::TryFrom<CmdId> for #enum_name {
+                type Error = Error;
+
+                fn try_from(id: CmdId) -> Result<Self, Self::Error> {
+                    Self::from_repr(id).ok_or_else(|| ErrorCode::CommandNotFound.into())
+                }
+            }
+        }
+    };
+
+    let requests = enum_for("Commands", true);
+    let responses = enum_for("RespCommands", false);
+
+    quote! {
+        #requests
+
+        #responses
+    }
+}
+
+fn cluster_const(cluster: &Cluster) -> TokenStream {
+    let attributes = cluster
+        .attributes
+        .iter()
+        .map(|attr| ident(&pascal_case(&attr.name)));
+    let commands = cluster
+        .commands
+        .iter()
+        .filter(|cmd| cmd.client)
+        .map(|cmd| ident(&pascal_case(&cmd.name)));
+
+    quote! {
+        /// The metadata of the cluster with all its attributes and commands, including the optional ones
+        pub const CLUSTER: Cluster<'static> = Cluster {
+            id: ID as _,
+            feature_map: 0,
+            attributes: &[
+                FEATURE_MAP,
+                ATTRIBUTE_LIST,
+                #(Attributes::#attributes.attribute(),)*
+            ],
+            commands: &[
+                #(Commands::#commands as _,)*
+            ],
+        };
+    }
+}
+
+fn enum_type(e: &Enum) -> TokenStream {
+    let name = ident(&pascal_case(&e.name));
+    let repr = ident(primitive(&e.ty).unwrap_or("u8"));
+
+    if e.items.is_empty() {
+        return quote! {
+            pub type #name = #repr;
+        };
+    }
+
+    // Skip items with duplicate values, as these cannot be discriminants
+    let mut items: Vec<&(String, u64)> = Vec::new();
+    for item in &e.items {
+        if !items.iter().any(|other| other.1 == item.1) {
+            items.push(item);
+        }
+    }
+
+    let names: Vec<_> = items
+        .iter()
+        .map(|(name, _)| ident(&pascal_case(name)))
+        .collect();
+    let values: Vec<_> = items.iter().map(|(_, value)| literal(*value)).collect();
+
+    quote! {
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        #[repr(#repr)]
+        pub enum #name {
+            #(#names = #values,)*
+        }
+
+        impl #name {
+            pub const fn from_repr(value: #repr) -> Option<Self> {
+                match value {
+                    #(#values => Some(Self::#names),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl ToTLV for #name {
+            fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+                tw.#repr(tag, *self as _)
+            }
+        }
+
+        impl<'a> FromTLV<'a> for #name {
+            fn from_tlv(t: &TLVElement<'a>) -> Result<Self, Error> {
+                Self::from_repr(t.#repr()?).ok_or_else(|| ErrorCode::InvalidData.into())
+            }
+        }
+    }
+}
+
+fn bitmap_type(b: &Bitmap) -> TokenStream {
+    let name = ident(&pascal_case(&b.name));
+    let repr = ident(primitive(&b.ty).unwrap_or("u32"));
+
+    let mut fields: Vec<&(String, u64)> = Vec::new();
+    for field in &b.fields {
+        if !fields.iter().any(|other| other.0 == field.0) {
+            fields.push(field);
+        }
+    }
+
+    let names = fields
+        .iter()
+        .map(|(name, _)| ident(&snake_case(name).to_ascii_uppercase()));
+    let masks = fields.iter().map(|(_, mask)| literal(*mask));
+
+    quote! {
+        #[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+        pub struct #name(pub #repr);
+
+        impl #name {
+            #(pub const #names: Self = Self(#masks);)*
+
+            pub const fn bits(&self) -> #repr {
+                self.0
+            }
+
+            pub const fn contains(&self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            pub const fn union(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+        }
+
+        impl core::ops::BitOr for #name {
+            type Output = Self;
+
+            fn bitor(self, other: Self) -> Self {
+                self.union(other)
+            }
+        }
+
+        impl ToTLV for #name {
+            fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+                tw.#repr(tag, self.0)
+            }
+        }
+
+        impl<'a> FromTLV<'a> for #name {
+            fn from_tlv(t: &TLVElement<'a>) -> Result<Self, Error> {
+                Ok(Self(t.#repr()?))
+            }
+        }
+    }
+}
+
+fn base_type(kind: &Kind) -> TokenStream {
+    match kind {
+        Kind::Primitive(rust) => {
+            let rust = ident(rust);
+            quote!(#rust)
+        }
+        Kind::Enum(name) | Kind::Bitmap(name) => {
+            let name = ident(&pascal_case(name));
+            quote!(#name)
+        }
+        Kind::String => quote!(UtfStr<'a>),
+        Kind::Octets => quote!(OctetStr<'a>),
+        Kind::Struct(name, lifetime) => {
+            let name = ident(&pascal_case(name));
+            if *lifetime {
+                quote!(#name<'a>)
+            } else {
+                quote!(#name)
+            }
+        }
+        Kind::Unknown => quote!(TLVElement<'a>),
+    }
+}
+
+fn field_type(
+    model: &Model,
+    cluster: &Cluster,
+    lifetimes: &[(String, bool)],
+    field: &Field,
+) -> TokenStream {
+    let kind = kind(model, cluster, lifetimes, &field.ty);
+
+    let mut ty = base_type(&kind);
+
+    if field.array {
+        ty = quote!(TLVArray<'a, #ty>);
+    }
+
+    if field.nullable {
+        ty = quote!(Nullable<#ty>);
+    }
+
+    if field.optional {
+        ty = quote!(Option<#ty>);
+    }
+
+    ty
+}
+
+fn struct_type(
+    model: &Model,
+    cluster: &Cluster,
+    lifetimes: &[(String, bool)],
+    name: &str,
+    fabric_scoped: bool,
+    fields: &[Field],
+) -> TokenStream {
+    let struct_name = ident(&pascal_case(name));
+
+    let lifetime = fields
+        .iter()
+        .any(|field| field.array || kind(model, cluster, lifetimes, &field.ty).needs_lifetime());
+
+    let names: Vec<_> = fields
+        .iter()
+        .map(|field| ident(&snake_case(&field.name)))
+        .collect();
+    let tags: Vec<_> = fields.iter().map(|field| field.id).collect();
+    let types: Vec<_> = fields
+        .iter()
+        .map(|field| field_type(model, cluster, lifetimes, field))
+        .collect();
+
+    // The implicit FabricIndex field of fabric-scoped structs
+    let fabric_index = if fabric_scoped && !fields.iter().any(|field| field.id == 254) {
+        quote! {
+            #[tagval(254)]
+            pub fabric_index: Option<u8>,
+        }
+    } else {
+        quote!()
+    };
+
+    if lifetime {
+        quote! {
+            #[derive(Debug, Clone, FromTLV, ToTLV)]
+            #[tlvargs(lifetime = "'a")]
+            pub struct #struct_name<'a> {
+                #(
+                    #[tagval(#tags)]
+                    pub #names: #types,
+                )*
+                #fabric_index
+            }
+        }
+    } else {
+        quote! {
+            #[derive(Debug, Clone, FromTLV, ToTLV)]
+            pub struct #struct_name {
+                #(
+                    #[tagval(#tags)]
+                    pub #names: #types,
+                )*
+                #fabric_index
+            }
+        }
+    }
+}
+
+/// The `ClusterHandler` trait to be implemented by the application, and the
+/// `HandlerAdaptor` which turns it into a `Handler`
+fn handler(model: &Model, cluster: &Cluster, lifetimes: &[(String, bool)]) -> TokenStream {
+    let mut methods = Vec::new();
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    let mut invokes = Vec::new();
+
+    for attr in &cluster.attributes {
+        let variant = ident(&pascal_case(&attr.name));
+        let getter = ident(&snake_case(&attr.name));
+        let setter = format_ident!("set_{}", snake_case(&attr.name));
+
+        let kind = kind(model, cluster, lifetimes, &attr.ty);
+        let scalar = kind.is_scalar() && !attr.array;
+
+        let default_read = attr.optional.then(|| {
+            quote! {
+                { Err(ErrorCode::AttributeNotFound.into()) }
+            }
+        });
+        let default_write = attr.optional.then(|| {
+            quote! {
+                { Err(ErrorCode::AttributeNotFound.into()) }
+            }
+        });
+
+        let semi = |default: &Option<TokenStream>| {
+            if default.is_some() {
+                quote!()
+            } else {
+                quote!(;)
+            }
+        };
+
+        if scalar {
+            let mut ty = base_type(&kind);
+            if attr.quality.nullable {
+                ty = quote!(Nullable<#ty>);
+            }
+
+            let read_semi = semi(&default_read);
+            methods.push(quote! {
+                fn #getter(&self) -> Result<#ty, Error> #default_read #read_semi
+            });
+            reads.push(quote! {
+                Attributes::#variant => writer.set(self.0.#getter()?),
+            });
+
+            if attr.writable {
+                let write_semi = semi(&default_write);
+                methods.push(quote! {
+                    fn #setter(&self, value: #ty) -> Result<(), Error> #default_write #write_semi
+                });
+                writes.push(quote! {
+                    Attributes::#variant => self.0.#setter(FromTLV::from_tlv(data)?)?,
+                });
+            }
+        } else {
+            let read_semi = semi(&default_read);
+            methods.push(quote! {
+                fn #getter(&self, writer: AttrDataWriter<'_, '_, '_>) -> Result<(), Error> #default_read #read_semi
+            });
+            reads.push(quote! {
+                Attributes::#variant => self.0.#getter(writer),
+            });
+
+            if attr.writable {
+                let write_semi = semi(&default_write);
+                methods.push(quote! {
+                    fn #setter(&self, data: &TLVElement<'_>) -> Result<(), Error> #default_write #write_semi
+                });
+                writes.push(quote! {
+                    Attributes::#variant => self.0.#setter(data)?,
+                });
+            }
+        }
+    }
+
+    for cmd in cluster.commands.iter().filter(|cmd| cmd.client) {
+        let variant = ident(&pascal_case(&cmd.name));
+        let method = format_ident!("handle_{}", snake_case(&cmd.name));
+
+        let response = cmd.response.as_ref().and_then(|response| {
+            cluster
+                .commands
+                .iter()
+                .find(|other| !other.client && other.name == *response)
+        });
+
+        let default = cmd.optional.then(|| {
+            quote! {
+                { Err(ErrorCode::CommandNotFound.into()) }
+            }
+        });
+        let semi = if default.is_some() {
+            quote!()
+        } else {
+            quote!(;)
+        };
+
+        let (request_param, request_arg) = if cmd.args.is_empty() {
+            (quote!(), quote!())
+        } else {
+            let request = ident(&command_struct(cmd));
+            let request_ty = if cmd.args.iter().any(|field| {
+                field.array || kind(model, cluster, lifetimes, &field.ty).needs_lifetime()
+            }) {
+                quote!(#request<'_>)
+            } else {
+                quote!(#request)
+            };
+
+            (
+                quote!(, request: &#request_ty),
+                quote!(, &#request::from_tlv(data)?),
+            )
+        };
+
+        if let Some(response) = response {
+            let response_variant = ident(&pascal_case(&response.name));
+
+            let response_ty = if response.args.is_empty() {
+                quote!(())
+            } else {
+                let name = ident(&command_struct(response));
+                if response.args.iter().any(|field| {
+                    field.array || kind(model, cluster, lifetimes, &field.ty).needs_lifetime()
+                }) {
+                    quote!(#name<'_>)
+                } else {
+                    quote!(#name)
+                }
+            };
+
+            methods.push(quote! {
+                fn #method(&self, exchange: &Exchange #request_param) -> Result<#response_ty, Error> #default #semi
+            });
+
+            if response.args.is_empty() {
+                invokes.push(quote! {
+                    Commands::#variant => {
+                        self.0.#method(exchange #request_arg)?;
+                        encoder.with_command(RespCommands::#response_variant as _)?.complete()?;
+                    }
+                });
+            } else {
+                invokes.push(quote! {
+                    Commands::#variant => {
+                        let response = self.0.#method(exchange #request_arg)?;
+                        encoder.with_command(RespCommands::#response_variant as _)?.set(response)?;
+                    }
+                });
+            }
+        } else {
+            methods.push(quote! {
+                fn #method(&self, exchange: &Exchange #request_param) -> Result<(), Error> #default #semi
+            });
+            invokes.push(quote! {
+                Commands::#variant => self.0.#method(exchange #request_arg)?,
+            });
+        }
+    }
+
+    let read = if reads.is_empty() {
+        quote! {
+            Err(ErrorCode::AttributeNotFound.into())
+        }
+    } else {
+        quote! {
+            match attr.attr_id.try_into()? {
+                #(#reads)*
+            }
+        }
+    };
+
+    let write = if writes.is_empty() {
+        quote! {
+            Err(ErrorCode::AttributeNotFound.into())
+        }
+    } else {
+        quote! {
+            let data = data.with_dataver(self.0.dataver())?;
+
+            #[allow(unreachable_patterns)]
+            match attr.attr_id.try_into()? {
+                #(#writes)*
+                _ => return Err(ErrorCode::InvalidAction.into()),
+            }
+
+            self.0.dataver_changed();
+
+            Ok(())
+        }
+    };
+
+    let invoke = if invokes.is_empty() {
+        quote! {
+            Err(ErrorCode::CommandNotFound.into())
+        }
+    } else {
+        quote! {
+            match cmd.cmd_id.try_into()? {
+                #(#invokes)*
+            }
+
+            Ok(())
+        }
+    };
+
+    quote! {
+        /// The application side of the cluster
+        ///
+        /// Optional attributes and commands have default implementations, which
+        /// report them as not supported.
+        pub trait ClusterHandler {
+            /// The metadata of the cluster as served by this handler
+            const CLUSTER: Cluster<'static> = CLUSTER;
+
+            /// The current data version of the cluster
+            fn dataver(&self) -> u32;
+
+            /// Mark the attributes of the cluster as changed
+            fn dataver_changed(&self);
+
+            #(#methods)*
+        }
+
+        /// Wraps a `ClusterHandler` implementation into a `Handler`
+        pub struct HandlerAdaptor<T>(pub T);
+
+        impl<T> Handler for HandlerAdaptor<T>
+        where
+            T: ClusterHandler,
+        {
+            #[allow(unreachable_code)]
+            fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+                if let Some(writer) = encoder.with_dataver(self.0.dataver())? {
+                    if attr.is_system() {
+                        T::CLUSTER.read(attr.attr_id, writer)
+                    } else {
+                        #read
+                    }
+                } else {
+                    Ok(())
+                }
+            }
+
+            #[allow(unused_variables, unreachable_code)]
+            fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+                #write
+            }
+
+            #[allow(unused_variables, unreachable_code)]
+            fn invoke(
+                &self,
+                exchange: &Exchange,
+                cmd: &CmdDetails,
+                data: &TLVElement,
+                encoder: CmdDataEncoder,
+            ) -> Result<(), Error> {
+                #invoke
+            }
+
+            fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+                Some(self.0.dataver())
+            }
+        }
+
+        impl<T> NonBlockingHandler for HandlerAdaptor<T> where T: ClusterHandler {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::{Ident, Span};
+
+    use super::{generate, pascal_case, snake_case, Model, Privilege, Quality};
+
+    const XML: &str = r#"<?xml version="1.0"?>
+<configurator>
+  <domain name="General"/>
+  <enum name="StartUpOnOffEnum" type="enum8">
+    <cluster code="0x0006"/>
+    <item name="Off" value="0x00"/>
+    <item name="On" value="0x01"/>
+    <item name="Toggle" value="0x02"/>
+  </enum>
+  <bitmap name="Feature" type="bitmap32">
+    <cluster code="0x0006"/>
+    <field name="Lighting" mask="0x1"/>
+  </bitmap>
+  <cluster>
+    <domain>General</domain>
+    <name>On/Off</name>
+    <code>0x0006</code>
+    <attribute side="server" code="0x0000" define="ON_OFF" type="boolean" writable="false" optional="false">OnOff</attribute>
+    <attribute side="server" code="0x4003" define="START_UP_ON_OFF" type="StartUpOnOffEnum" writable="true" isNullable="true" optional="true">
+      <description>StartUpOnOff</description>
+      <access op="read" privilege="view"/>
+      <access op="write" privilege="manage"/>
+    </attribute>
+    <attribute side="server" code="0xFFFD" define="CLUSTER_REVISION" type="int16u">ClusterRevision</attribute>
+    <command source="client" code="0x00" name="Off" optional="false"/>
+    <command source="client" code="0x40" name="OffWithEffect" optional="true">
+      <arg name="EffectIdentifier" type="enum8"/>
+      <arg name="EffectVariant" type="enum8"/>
+    </command>
+  </cluster>
+</configurator>
+"#;
+
+    #[test]
+    fn test_parse() {
+        let model = Model::parse(XML).unwrap();
+
+        assert_eq!(model.enums.len(), 1);
+        assert_eq!(model.enums[0].clusters, [6]);
+        assert_eq!(model.enums[0].items.len(), 3);
+        assert_eq!(model.bitmaps[0].fields, [("Lighting".to_string(), 1)]);
+
+        let cluster = &model.clusters[0];
+        assert_eq!(cluster.name, "On/Off");
+        assert_eq!(cluster.code, 6);
+
+        // The global ClusterRevision attribute is skipped
+        assert_eq!(cluster.attributes.len(), 2);
+
+        let on_off = &cluster.attributes[0];
+        assert_eq!(on_off.name, "OnOff");
+        assert!(!on_off.writable);
+        assert!(!on_off.optional);
+        assert_eq!(on_off.read_privilege, Privilege::View);
+
+        let start_up = &cluster.attributes[1];
+        assert_eq!(start_up.name, "StartUpOnOff");
+        assert_eq!(start_up.code, 0x4003);
+        assert!(start_up.writable);
+        assert_eq!(start_up.write_privilege, Privilege::Manage);
+        assert_eq!(
+            start_up.quality,
+            Quality {
+                nullable: true,
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(cluster.commands.len(), 2);
+        assert!(cluster.commands[1].client);
+        assert_eq!(cluster.commands[1].args[1].id, 1);
+        assert_eq!(cluster.commands[1].args[1].name, "EffectVariant");
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(snake_case("On/Off"), "on_off");
+        assert_eq!(snake_case("StartUpOnOff"), "start_up_on_off");
+        assert_eq!(snake_case("HWVersion"), "hw_version");
+        assert_eq!(snake_case("Level Control"), "level_control");
+        assert_eq!(pascal_case("On/Off"), "OnOff");
+        assert_eq!(pascal_case("Level Control"), "LevelControl");
+    }
+
+    #[test]
+    fn test_generate() {
+        let model = Model::parse(XML).unwrap();
+
+        let tokens = generate(&model, &Ident::new("rs_matter", Span::call_site()));
+
+        let code = tokens.to_string();
+
+        assert!(code.contains("pub mod on_off"));
+        assert!(code.contains("pub enum StartUpOnOffEnum"));
+        assert!(code.contains("pub struct OffWithEffectRequest"));
+        assert!(code.contains("fn set_start_up_on_off"));
+    }
+}
@@ -0,0 +1,357 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Encoding and decoding of the BDX messages
+//!
+//! Unlike most other Matter messages, BDX messages are not TLV-encoded, but use a
+//! fixed little-endian layout.
+
+use bitflags::bitflags;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    error::{Error, ErrorCode},
+    utils::writebuf::WriteBuf,
+};
+
+bitflags! {
+    #[derive(Default)]
+    pub struct TransferControl: u8 {
+        const SENDER_DRIVE = 0x10;
+        const RECEIVER_DRIVE = 0x20;
+        const ASYNC = 0x40;
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct RangeControl: u8 {
+        const DEFLEN = 0x01;
+        const START_OFFSET = 0x02;
+        const WIDERANGE = 0x10;
+    }
+}
+
+const VERSION_MASK: u8 = 0x0F;
+
+/// The SendInit and ReceiveInit messages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferInit<'a> {
+    pub version: u8,
+    pub transfer_control: TransferControl,
+    pub max_block_size: u16,
+    pub start_offset: Option<u64>,
+    pub max_length: Option<u64>,
+    pub file_designator: &'a [u8],
+    pub metadata: &'a [u8],
+}
+
+impl<'a> TransferInit<'a> {
+    pub fn encode(&self, wb: &mut WriteBuf) -> Result<(), Error> {
+        let wide = self
+            .start_offset
+            .iter()
+            .chain(self.max_length.iter())
+            .any(|value| *value > u32::MAX as u64);
+
+        let mut range_control = RangeControl::empty();
+        range_control.set(RangeControl::DEFLEN, self.max_length.is_some());
+        range_control.set(RangeControl::START_OFFSET, self.start_offset.is_some());
+        range_control.set(RangeControl::WIDERANGE, wide);
+
+        let file_designator_len: u16 = self
+            .file_designator
+            .len()
+            .try_into()
+            .map_err(|_| ErrorCode::InvalidData)?;
+
+        wb.le_u8(encode_control(self.version, self.transfer_control))?;
+        wb.le_u8(range_control.bits())?;
+        wb.le_u16(self.max_block_size)?;
+        if let Some(start_offset) = self.start_offset {
+            encode_range(wb, start_offset, wide)?;
+        }
+        if let Some(max_length) = self.max_length {
+            encode_range(wb, max_length, wide)?;
+        }
+        wb.le_u16(file_designator_len)?;
+        wb.copy_from_slice(self.file_designator)?;
+        wb.copy_from_slice(self.metadata)
+    }
+
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        let mut reader = Reader(data);
+
+        let (version, transfer_control) = decode_control(reader.u8()?);
+        let range_control = RangeControl::from_bits_truncate(reader.u8()?);
+        let wide = range_control.contains(RangeControl::WIDERANGE);
+        let max_block_size = reader.u16()?;
+
+        let start_offset = if range_control.contains(RangeControl::START_OFFSET) {
+            Some(reader.range(wide)?)
+        } else {
+            None
+        };
+
+        let max_length = if range_control.contains(RangeControl::DEFLEN) {
+            Some(reader.range(wide)?)
+        } else {
+            None
+        };
+
+        let file_designator_len = reader.u16()? as usize;
+        let file_designator = reader.take(file_designator_len)?;
+
+        Ok(Self {
+            version,
+            transfer_control,
+            max_block_size,
+            start_offset,
+            max_length,
+            file_designator,
+            metadata: reader.rest(),
+        })
+    }
+}
+
+/// The ReceiveAccept message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiveAccept<'a> {
+    pub version: u8,
+    pub transfer_control: TransferControl,
+    pub max_block_size: u16,
+    pub start_offset: Option<u64>,
+    pub length: Option<u64>,
+    pub metadata: &'a [u8],
+}
+
+impl<'a> ReceiveAccept<'a> {
+    pub fn encode(&self, wb: &mut WriteBuf) -> Result<(), Error> {
+        let wide = self
+            .start_offset
+            .iter()
+            .chain(self.length.iter())
+            .any(|value| *value > u32::MAX as u64);
+
+        let mut range_control = RangeControl::empty();
+        range_control.set(RangeControl::DEFLEN, self.length.is_some());
+        range_control.set(RangeControl::START_OFFSET, self.start_offset.is_some());
+        range_control.set(RangeControl::WIDERANGE, wide);
+
+        wb.le_u8(encode_control(self.version, self.transfer_control))?;
+        wb.le_u8(range_control.bits())?;
+        wb.le_u16(self.max_block_size)?;
+        if let Some(start_offset) = self.start_offset {
+            encode_range(wb, start_offset, wide)?;
+        }
+        if let Some(length) = self.length {
+            encode_range(wb, length, wide)?;
+        }
+        wb.copy_from_slice(self.metadata)
+    }
+
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        let mut reader = Reader(data);
+
+        let (version, transfer_control) = decode_control(reader.u8()?);
+        let range_control = RangeControl::from_bits_truncate(reader.u8()?);
+        let wide = range_control.contains(RangeControl::WIDERANGE);
+        let max_block_size = reader.u16()?;
+
+        let start_offset = if range_control.contains(RangeControl::START_OFFSET) {
+            Some(reader.range(wide)?)
+        } else {
+            None
+        };
+
+        let length = if range_control.contains(RangeControl::DEFLEN) {
+            Some(reader.range(wide)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            version,
+            transfer_control,
+            max_block_size,
+            start_offset,
+            length,
+            metadata: reader.rest(),
+        })
+    }
+}
+
+/// The Block and BlockEOF messages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block<'a> {
+    pub counter: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Block<'a> {
+    pub fn encode(&self, wb: &mut WriteBuf) -> Result<(), Error> {
+        wb.le_u32(self.counter)?;
+        wb.copy_from_slice(self.data)
+    }
+
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        let mut reader = Reader(data);
+
+        let counter = reader.u32()?;
+
+        Ok(Self {
+            counter,
+            data: reader.rest(),
+        })
+    }
+}
+
+/// The BlockQuery, BlockAck and BlockAckEOF messages, which only carry a block counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockCounter {
+    pub counter: u32,
+}
+
+impl BlockCounter {
+    pub fn encode(&self, wb: &mut WriteBuf) -> Result<(), Error> {
+        wb.le_u32(self.counter)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(data);
+
+        Ok(Self {
+            counter: reader.u32()?,
+        })
+    }
+}
+
+fn encode_control(version: u8, transfer_control: TransferControl) -> u8 {
+    (version & VERSION_MASK) | transfer_control.bits()
+}
+
+fn decode_control(control: u8) -> (u8, TransferControl) {
+    (
+        control & VERSION_MASK,
+        TransferControl::from_bits_truncate(control),
+    )
+}
+
+fn encode_range(wb: &mut WriteBuf, value: u64, wide: bool) -> Result<(), Error> {
+    if wide {
+        wb.le_u64(value)
+    } else {
+        wb.le_u32(value as u32)
+    }
+}
+
+// Unlike `ParseBuf`, hands out slices which live as long as the parsed data
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            Err(ErrorCode::TruncatedPacket)?;
+        }
+
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+
+        Ok(head)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        core::mem::take(&mut self.0)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(LittleEndian::read_u16(self.take(2)?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    fn range(&mut self, wide: bool) -> Result<u64, Error> {
+        if wide {
+            Ok(LittleEndian::read_u64(self.take(8)?))
+        } else {
+            Ok(self.u32()? as u64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::writebuf::WriteBuf;
+
+    use super::{Block, BlockCounter, ReceiveAccept, TransferControl, TransferInit};
+
+    #[test]
+    fn test_transfer_init_roundtrip() {
+        let init = TransferInit {
+            version: 0,
+            transfer_control: TransferControl::RECEIVER_DRIVE,
+            max_block_size: 1024,
+            start_offset: None,
+            max_length: Some(0x1_0000_0000),
+            file_designator: b"image.ota",
+            metadata: &[],
+        };
+
+        let mut buf = [0; 64];
+        let mut wb = WriteBuf::new(&mut buf);
+        init.encode(&mut wb).unwrap();
+
+        // Control bytes, block size, 8-byte length, designator length and designator
+        assert_eq!(&wb.as_slice()[..4], &[0x20, 0x11, 0x00, 0x04]);
+        assert_eq!(wb.as_slice().len(), 4 + 8 + 2 + 9);
+
+        assert_eq!(TransferInit::decode(wb.as_slice()).unwrap(), init);
+    }
+
+    #[test]
+    fn test_receive_accept_decode() {
+        let data = [0x20, 0x01, 0x00, 0x02, 0x10, 0x27, 0x00, 0x00];
+
+        let accept = ReceiveAccept::decode(&data).unwrap();
+        assert_eq!(accept.version, 0);
+        assert_eq!(accept.transfer_control, TransferControl::RECEIVER_DRIVE);
+        assert_eq!(accept.max_block_size, 512);
+        assert_eq!(accept.length, Some(10000));
+        assert!(accept.metadata.is_empty());
+
+        // Length is missing
+        assert!(ReceiveAccept::decode(&data[..6]).is_err());
+    }
+
+    #[test]
+    fn test_block_decode() {
+        let data = [0x02, 0x00, 0x00, 0x00, 0xaa, 0xbb];
+
+        let block = Block::decode(&data).unwrap();
+        assert_eq!(block.counter, 2);
+        assert_eq!(block.data, &[0xaa, 0xbb]);
+
+        assert_eq!(BlockCounter::decode(&data).unwrap().counter, 2);
+        assert!(BlockCounter::decode(&data[..3]).is_err());
+    }
+}
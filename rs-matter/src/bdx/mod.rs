@@ -0,0 +1,66 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The Bulk Data Exchange (BDX) protocol
+//!
+//! Only the receiving side of a synchronous, receiver-driven transfer is implemented,
+//! which is what an OTA Requestor needs to download an image from an OTA Provider.
+
+use num_derive::FromPrimitive;
+
+pub mod messages;
+pub mod receiver;
+
+/* Bulk Data Exchange Protocol ID as per the Matter Spec */
+pub const PROTO_ID_BDX: u16 = 0x02;
+
+/// The version of the BDX protocol implemented
+pub const BDX_VERSION: u8 = 0;
+
+#[derive(FromPrimitive, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OpCode {
+    SendInit = 0x01,
+    SendAccept = 0x02,
+    ReceiveInit = 0x04,
+    ReceiveAccept = 0x05,
+    BlockQuery = 0x10,
+    Block = 0x11,
+    BlockEOF = 0x12,
+    BlockAck = 0x13,
+    BlockAckEOF = 0x14,
+    BlockQueryWithSkip = 0x15,
+}
+
+/// The protocol-specific codes of the Status Reports of the BDX protocol
+#[derive(FromPrimitive, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StatusCode {
+    Overflow = 0x0011,
+    LengthTooLarge = 0x0012,
+    LengthTooShort = 0x0013,
+    LengthMismatch = 0x0014,
+    LengthRequired = 0x0015,
+    BadMessageContents = 0x0016,
+    BadBlockCounter = 0x0017,
+    UnexpectedMessage = 0x0018,
+    ResponderBusy = 0x0019,
+    TransferFailedUnknownError = 0x001F,
+    TransferMethodNotSupported = 0x0050,
+    FileDesignatorUnknown = 0x0051,
+    StartOffsetNotSupported = 0x0052,
+    VersionNotSupported = 0x0053,
+    Unknown = 0x005F,
+}
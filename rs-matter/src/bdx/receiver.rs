@@ -0,0 +1,653 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use log::{error, info};
+
+use crate::{
+    error::{Error, ErrorCode},
+    secure_channel::status_report::{create_status_report, GeneralCode},
+    transport::{exchange::Exchange, packet::Packet},
+    utils::writebuf::WriteBuf,
+};
+
+use super::{
+    messages::{Block, BlockCounter, ReceiveAccept, TransferControl, TransferInit},
+    OpCode, StatusCode, BDX_VERSION, PROTO_ID_BDX,
+};
+
+#[cfg(feature = "std")]
+pub use fileio::*;
+
+/// Where the received data ends up
+///
+/// On std this would typically be a file, on embedded targets the inactive flash partition.
+pub trait ImageSink {
+    /// A transfer is about to start; `length` is the total size, if the sender knows it
+    fn begin(&mut self, length: Option<u64>) -> Result<(), Error>;
+
+    /// Write a block of data at `offset`
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Error>;
+
+    /// All data was received
+    fn finish(&mut self) -> Result<(), Error>;
+
+    /// The transfer failed, and whatever was written so far should be discarded
+    fn abort(&mut self);
+}
+
+impl<T> ImageSink for &mut T
+where
+    T: ImageSink,
+{
+    fn begin(&mut self, length: Option<u64>) -> Result<(), Error> {
+        (**self).begin(length)
+    }
+
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Error> {
+        (**self).write(offset, data)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        (**self).finish()
+    }
+
+    fn abort(&mut self) {
+        (**self).abort()
+    }
+}
+
+/// What to do after a message of the sender was handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Send the reply and wait for the next message of the sender
+    Continue(OpCode),
+    /// Send the reply, which completes the transfer
+    Done(OpCode),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Initiated,
+    Receiving,
+    Done,
+    Failed,
+}
+
+/// The receiving side of a synchronous, receiver-driven BDX transfer
+///
+/// The receiver only encodes and decodes messages, so that it can be driven over any
+/// exchange, see `receive`.
+pub struct BdxReceiver<S> {
+    sink: S,
+    max_block_size: u16,
+    state: State,
+    counter: u32,
+    received: u64,
+    length: Option<u64>,
+    status: Option<StatusCode>,
+}
+
+impl<S> BdxReceiver<S>
+where
+    S: ImageSink,
+{
+    pub fn new(sink: S, max_block_size: u16) -> Self {
+        Self {
+            sink,
+            max_block_size,
+            state: State::Idle,
+            counter: 0,
+            received: 0,
+            length: None,
+            status: None,
+        }
+    }
+
+    /// The number of bytes received so far
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// The total number of bytes to receive, if the sender announced it
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+
+    /// Why the transfer failed, as reported to the sender
+    pub fn status(&self) -> Option<StatusCode> {
+        self.status
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Write the ReceiveInit message, asking for the file `file_designator`, into `wb`
+    pub fn start(&mut self, file_designator: &[u8], wb: &mut WriteBuf) -> Result<OpCode, Error> {
+        if self.state != State::Idle {
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        TransferInit {
+            version: BDX_VERSION,
+            transfer_control: TransferControl::RECEIVER_DRIVE,
+            max_block_size: self.max_block_size,
+            start_offset: None,
+            max_length: None,
+            file_designator,
+            metadata: &[],
+        }
+        .encode(wb)?;
+
+        self.state = State::Initiated;
+
+        Ok(OpCode::ReceiveInit)
+    }
+
+    /// Handle a message of the sender, writing the reply into `wb`
+    ///
+    /// On error, the transfer is aborted and `status` tells what to report to the sender.
+    pub fn handle(&mut self, opcode: u8, data: &[u8], wb: &mut WriteBuf) -> Result<Step, Error> {
+        match self.process(opcode, data, wb) {
+            Ok(step) => Ok(step),
+            Err((status, err)) => {
+                error!("BDX transfer failed: {:?}", status);
+                self.status = Some(status);
+                self.abort();
+
+                Err(err)
+            }
+        }
+    }
+
+    /// Abort the transfer, e.g. because the sender reported an error
+    pub fn abort(&mut self) {
+        if !matches!(self.state, State::Done | State::Failed) {
+            self.sink.abort();
+            self.state = State::Failed;
+        }
+    }
+
+    fn process(
+        &mut self,
+        opcode: u8,
+        data: &[u8],
+        wb: &mut WriteBuf,
+    ) -> Result<Step, (StatusCode, Error)> {
+        let opcode = num::FromPrimitive::from_u8(opcode)
+            .ok_or_else(|| fail(StatusCode::UnexpectedMessage))?;
+
+        match (self.state, opcode) {
+            (State::Initiated, OpCode::ReceiveAccept) => {
+                let accept = ReceiveAccept::decode(data)
+                    .map_err(|err| (StatusCode::BadMessageContents, err))?;
+
+                if accept.version > BDX_VERSION {
+                    Err(fail(StatusCode::VersionNotSupported))?;
+                }
+
+                if !accept
+                    .transfer_control
+                    .contains(TransferControl::RECEIVER_DRIVE)
+                {
+                    Err(fail(StatusCode::TransferMethodNotSupported))?;
+                }
+
+                if accept.start_offset.is_some() {
+                    Err(fail(StatusCode::StartOffsetNotSupported))?;
+                }
+
+                if accept.max_block_size == 0 || accept.max_block_size > self.max_block_size {
+                    Err(fail(StatusCode::BadMessageContents))?;
+                }
+
+                info!(
+                    "BDX transfer accepted, length {:?}, block size {}",
+                    accept.length, accept.max_block_size
+                );
+
+                self.max_block_size = accept.max_block_size;
+                self.length = accept.length;
+                self.sink
+                    .begin(self.length)
+                    .map_err(|err| (StatusCode::TransferFailedUnknownError, err))?;
+                self.state = State::Receiving;
+
+                BlockCounter {
+                    counter: self.counter,
+                }
+                .encode(wb)
+                .map_err(|err| (StatusCode::TransferFailedUnknownError, err))?;
+
+                Ok(Step::Continue(OpCode::BlockQuery))
+            }
+            (State::Receiving, OpCode::Block | OpCode::BlockEOF) => {
+                let eof = opcode == OpCode::BlockEOF;
+
+                let block =
+                    Block::decode(data).map_err(|err| (StatusCode::BadMessageContents, err))?;
+
+                if block.counter != self.counter {
+                    Err(fail(StatusCode::BadBlockCounter))?;
+                }
+
+                if block.data.len() > self.max_block_size as usize
+                    || (!eof && block.data.is_empty())
+                {
+                    Err(fail(StatusCode::BadMessageContents))?;
+                }
+
+                let received = self.received + block.data.len() as u64;
+
+                if let Some(length) = self.length {
+                    if received > length {
+                        Err(fail(StatusCode::LengthTooLarge))?;
+                    } else if eof && received < length {
+                        Err(fail(StatusCode::LengthTooShort))?;
+                    }
+                }
+
+                self.sink
+                    .write(self.received, block.data)
+                    .map_err(|err| (StatusCode::TransferFailedUnknownError, err))?;
+                self.received = received;
+
+                if eof {
+                    self.sink
+                        .finish()
+                        .map_err(|err| (StatusCode::TransferFailedUnknownError, err))?;
+                    self.state = State::Done;
+
+                    BlockCounter {
+                        counter: self.counter,
+                    }
+                    .encode(wb)
+                    .map_err(|err| (StatusCode::TransferFailedUnknownError, err))?;
+
+                    Ok(Step::Done(OpCode::BlockAckEOF))
+                } else {
+                    // Querying the next block implicitly acknowledges the current one
+                    self.counter = self.counter.wrapping_add(1);
+
+                    BlockCounter {
+                        counter: self.counter,
+                    }
+                    .encode(wb)
+                    .map_err(|err| (StatusCode::TransferFailedUnknownError, err))?;
+
+                    Ok(Step::Continue(OpCode::BlockQuery))
+                }
+            }
+            _ => Err(fail(StatusCode::UnexpectedMessage)),
+        }
+    }
+}
+
+// Terminate the transfer, reporting `status` to the sender
+fn fail(status: StatusCode) -> (StatusCode, Error) {
+    (status, ErrorCode::Invalid.into())
+}
+
+/// Receive the file `file_designator` from the sender at the other end of `exchange`
+///
+/// The exchange - and the session beneath it - has to be established with the sender
+/// by the caller. Returns the number of bytes received.
+pub async fn receive<S>(
+    exchange: &mut Exchange<'_>,
+    tx: &mut Packet<'_>,
+    rx: &mut Packet<'_>,
+    receiver: &mut BdxReceiver<S>,
+    file_designator: &[u8],
+) -> Result<u64, Error>
+where
+    S: ImageSink,
+{
+    tx.reset();
+    tx.set_proto_id(PROTO_ID_BDX);
+    let opcode = receiver.start(file_designator, tx.get_writebuf()?)?;
+    tx.set_proto_opcode(opcode as u8);
+
+    loop {
+        if let Err(err) = exchange.exchange(tx, rx).await {
+            receiver.abort();
+            return Err(err);
+        }
+
+        if rx.get_proto_id() != PROTO_ID_BDX {
+            // Most likely a Status Report of the sender, which terminates the transfer
+            error!(
+                "BDX transfer terminated by the sender, Proto-ID {:x}",
+                rx.get_proto_id()
+            );
+            receiver.abort();
+            Err(ErrorCode::Invalid)?;
+        }
+
+        tx.reset();
+        tx.set_proto_id(PROTO_ID_BDX);
+
+        let result = receiver.handle(rx.get_proto_raw_opcode(), rx.as_slice(), tx.get_writebuf()?);
+
+        match result {
+            Ok(Step::Continue(opcode)) => tx.set_proto_opcode(opcode as u8),
+            Ok(Step::Done(opcode)) => {
+                tx.set_proto_opcode(opcode as u8);
+                exchange.send_complete(tx).await?;
+
+                break Ok(receiver.received());
+            }
+            Err(err) => {
+                let status = receiver.status().unwrap_or(StatusCode::Unknown);
+
+                create_status_report(
+                    tx,
+                    GeneralCode::Failure,
+                    PROTO_ID_BDX as u32,
+                    status as u16,
+                    None,
+                )?;
+                exchange.send_complete(tx).await?;
+
+                break Err(err);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub mod fileio {
+    use std::fs::{self, File};
+    use std::io::{Seek, SeekFrom, Write};
+    use std::path::PathBuf;
+
+    use crate::error::{Error, ErrorCode};
+
+    use super::ImageSink;
+
+    /// An image sink writing into a file
+    pub struct FileImageSink {
+        path: PathBuf,
+        file: Option<File>,
+    }
+
+    impl FileImageSink {
+        pub fn new(path: PathBuf) -> Self {
+            Self { path, file: None }
+        }
+    }
+
+    impl ImageSink for FileImageSink {
+        fn begin(&mut self, _length: Option<u64>) -> Result<(), Error> {
+            self.file = Some(File::create(&self.path)?);
+
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Error> {
+            let file = self.file.as_mut().ok_or(ErrorCode::InvalidState)?;
+
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(data)?;
+
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<(), Error> {
+            let file = self.file.take().ok_or(ErrorCode::InvalidState)?;
+
+            file.sync_all()?;
+
+            Ok(())
+        }
+
+        fn abort(&mut self) {
+            if self.file.take().is_some() {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bdx::{
+            messages::{Block, BlockCounter, ReceiveAccept, TransferControl, TransferInit},
+            OpCode, StatusCode,
+        },
+        error::Error,
+        utils::writebuf::WriteBuf,
+    };
+
+    use super::{BdxReceiver, ImageSink, Step};
+
+    const BLOCK_SIZE: u16 = 8;
+
+    #[derive(Default)]
+    struct TestSink {
+        data: heapless::Vec<u8, 64>,
+        length: Option<u64>,
+        finished: bool,
+        aborted: bool,
+    }
+
+    impl ImageSink for TestSink {
+        fn begin(&mut self, length: Option<u64>) -> Result<(), Error> {
+            self.length = length;
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Error> {
+            assert_eq!(offset, self.data.len() as u64);
+            self.data.extend_from_slice(data).unwrap();
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<(), Error> {
+            self.finished = true;
+            Ok(())
+        }
+
+        fn abort(&mut self) {
+            self.aborted = true;
+        }
+    }
+
+    /// The sending side, as an OTA Provider would run it
+    struct TestSender<'a> {
+        image: &'a [u8],
+        block_size: usize,
+    }
+
+    impl<'a> TestSender<'a> {
+        fn handle(&mut self, opcode: OpCode, data: &[u8], wb: &mut WriteBuf) -> OpCode {
+            match opcode {
+                OpCode::ReceiveInit => {
+                    let init = TransferInit::decode(data).unwrap();
+                    assert_eq!(init.file_designator, b"image.ota");
+                    assert!(init
+                        .transfer_control
+                        .contains(TransferControl::RECEIVER_DRIVE));
+
+                    self.block_size = self.block_size.min(init.max_block_size as usize);
+
+                    ReceiveAccept {
+                        version: 0,
+                        transfer_control: TransferControl::RECEIVER_DRIVE,
+                        max_block_size: self.block_size as u16,
+                        start_offset: None,
+                        length: Some(self.image.len() as u64),
+                        metadata: &[],
+                    }
+                    .encode(wb)
+                    .unwrap();
+
+                    OpCode::ReceiveAccept
+                }
+                OpCode::BlockQuery => {
+                    let counter = BlockCounter::decode(data).unwrap().counter;
+                    let start = counter as usize * self.block_size;
+                    let end = self.image.len().min(start + self.block_size);
+
+                    Block {
+                        counter,
+                        data: &self.image[start..end],
+                    }
+                    .encode(wb)
+                    .unwrap();
+
+                    if end == self.image.len() {
+                        OpCode::BlockEOF
+                    } else {
+                        OpCode::Block
+                    }
+                }
+                _ => panic!("Unexpected opcode {:?}", opcode),
+            }
+        }
+    }
+
+    #[test]
+    fn test_receive() {
+        let image: [u8; 20] = core::array::from_fn(|i| i as u8);
+        let mut sender = TestSender {
+            image: &image,
+            block_size: 16,
+        };
+
+        let mut sink = TestSink::default();
+        let mut receiver = BdxReceiver::new(&mut sink, BLOCK_SIZE);
+
+        let mut tx_buf = [0; 64];
+        let mut rx_buf = [0; 64];
+
+        let mut tx = WriteBuf::new(&mut tx_buf);
+        let mut opcode = receiver.start(b"image.ota", &mut tx).unwrap();
+
+        let mut blocks = 0;
+        loop {
+            let mut rx = WriteBuf::new(&mut rx_buf);
+            let reply = sender.handle(opcode, tx.as_slice(), &mut rx);
+            if reply != OpCode::ReceiveAccept {
+                blocks += 1;
+            }
+
+            tx = WriteBuf::new(&mut tx_buf);
+            match receiver
+                .handle(reply as u8, rx.as_slice(), &mut tx)
+                .unwrap()
+            {
+                Step::Continue(next) => opcode = next,
+                Step::Done(last) => {
+                    assert_eq!(last, OpCode::BlockAckEOF);
+                    assert_eq!(BlockCounter::decode(tx.as_slice()).unwrap().counter, 2);
+                    break;
+                }
+            }
+        }
+
+        // The sender's block size was capped to the one of the receiver
+        assert_eq!(blocks, 3);
+        assert!(receiver.is_done());
+        assert_eq!(receiver.received(), 20);
+
+        assert!(sink.finished);
+        assert_eq!(sink.length, Some(20));
+        assert_eq!(sink.data.as_slice(), &image);
+    }
+
+    #[test]
+    fn test_receive_bad_block() {
+        let mut sink = TestSink::default();
+        let mut receiver = BdxReceiver::new(&mut sink, BLOCK_SIZE);
+
+        let mut tx_buf = [0; 64];
+        let mut rx_buf = [0; 64];
+
+        let mut tx = WriteBuf::new(&mut tx_buf);
+        receiver.start(b"image.ota", &mut tx).unwrap();
+
+        // A block before the transfer was accepted
+        let mut rx = WriteBuf::new(&mut rx_buf);
+        Block {
+            counter: 0,
+            data: &[1, 2, 3],
+        }
+        .encode(&mut rx)
+        .unwrap();
+
+        let mut tx = WriteBuf::new(&mut tx_buf);
+        assert!(receiver
+            .handle(OpCode::Block as u8, rx.as_slice(), &mut tx)
+            .is_err());
+        assert_eq!(receiver.status(), Some(StatusCode::UnexpectedMessage));
+        assert!(!receiver.is_done());
+
+        assert!(sink.aborted);
+        assert!(sink.data.is_empty());
+    }
+
+    #[test]
+    fn test_receive_bad_counter() {
+        let mut sink = TestSink::default();
+        let mut receiver = BdxReceiver::new(&mut sink, BLOCK_SIZE);
+
+        let mut tx_buf = [0; 64];
+        let mut rx_buf = [0; 64];
+
+        let mut tx = WriteBuf::new(&mut tx_buf);
+        receiver.start(b"image.ota", &mut tx).unwrap();
+
+        let mut rx = WriteBuf::new(&mut rx_buf);
+        ReceiveAccept {
+            version: 0,
+            transfer_control: TransferControl::RECEIVER_DRIVE,
+            max_block_size: BLOCK_SIZE,
+            start_offset: None,
+            length: None,
+            metadata: &[],
+        }
+        .encode(&mut rx)
+        .unwrap();
+
+        let mut tx = WriteBuf::new(&mut tx_buf);
+        assert_eq!(
+            receiver
+                .handle(OpCode::ReceiveAccept as u8, rx.as_slice(), &mut tx)
+                .unwrap(),
+            Step::Continue(OpCode::BlockQuery)
+        );
+
+        let mut rx = WriteBuf::new(&mut rx_buf);
+        Block {
+            counter: 1,
+            data: &[1, 2, 3],
+        }
+        .encode(&mut rx)
+        .unwrap();
+
+        let mut tx = WriteBuf::new(&mut tx_buf);
+        assert!(receiver
+            .handle(OpCode::Block as u8, rx.as_slice(), &mut tx)
+            .is_err());
+        assert_eq!(receiver.status(), Some(StatusCode::BadBlockCounter));
+
+        assert!(sink.aborted);
+        assert!(!sink.finished);
+    }
+}
@@ -627,6 +627,11 @@ impl<'a> Cert<'a> {
         Ok(w.as_slice().len())
     }
 
+    /// Whether `now`, in seconds since the Matter epoch, is within the validity period
+    pub fn is_valid_at(&self, now: u64) -> bool {
+        now >= self.not_before as u64 && (self.not_after == 0 || now <= self.not_after as u64)
+    }
+
     pub fn verify_chain_start(&self) -> CertVerifier {
         CertVerifier::new(self)
     }
@@ -698,17 +703,37 @@ impl<'a> fmt::Display for Cert<'a> {
 
 pub struct CertVerifier<'a> {
     cert: &'a Cert<'a>,
+    now: Option<u64>,
 }
 
 impl<'a> CertVerifier<'a> {
     pub fn new(cert: &'a Cert) -> Self {
-        Self { cert }
+        Self { cert, now: None }
+    }
+
+    /// Also verify that the certificates of the chain are valid at `now`, in seconds
+    /// since the Matter epoch
+    ///
+    /// The validity periods are not checked if the time is not known.
+    pub fn valid_at(self, now: Option<u64>) -> Self {
+        Self { now, ..self }
     }
 
     pub fn add_cert(self, parent: &'a Cert) -> Result<CertVerifier<'a>, Error> {
         if !self.cert.is_authority(parent)? {
             Err(ErrorCode::InvalidAuthKey)?;
         }
+
+        if let Some(now) = self.now {
+            if !self.cert.is_valid_at(now) {
+                error!(
+                    "Certificate {:x?} is not valid at {}",
+                    self.cert.get_subject_key_id(),
+                    now
+                );
+                Err(ErrorCode::InvalidTime)?;
+            }
+        }
         let mut asn1 = [0u8; MAX_ASN1_CERT_SIZE];
         let len = self.cert.as_asn1(&mut asn1)?;
         let asn1 = &asn1[..len];
@@ -724,9 +749,12 @@ impl<'a> CertVerifier<'a> {
         })?;
 
         // TODO: other validation checks
-        Ok(CertVerifier::new(parent))
+        Ok(CertVerifier::new(parent).valid_at(self.now))
     }
 
+    /// Complete the chain with its root, which has to be self-signed
+    ///
+    /// Like the other certificates of the chain, the root has to be valid at `now`.
     pub fn finalise(self) -> Result<(), Error> {
         let cert = self.cert;
         self.add_cert(cert)?;
@@ -833,6 +861,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_verify_chain_validity_period() {
+        use crate::error::ErrorCode;
+
+        let noc = Cert::new(&test_vectors::NOC1_SUCCESS).unwrap();
+        let icac = Cert::new(&test_vectors::ICAC1_SUCCESS).unwrap();
+        let rca = Cert::new(&test_vectors::RCA1_SUCCESS).unwrap();
+
+        let now = noc.not_before.max(icac.not_before).max(rca.not_before) as u64;
+        noc.verify_chain_start()
+            .valid_at(Some(now))
+            .add_cert(&icac)
+            .unwrap()
+            .add_cert(&rca)
+            .unwrap()
+            .finalise()
+            .unwrap();
+
+        // Before the NOC was issued
+        assert_eq!(
+            Err(ErrorCode::InvalidTime),
+            noc.verify_chain_start()
+                .valid_at(Some(noc.not_before as u64 - 1))
+                .add_cert(&icac)
+                .map(|_| ())
+                .map_err(|e| e.code())
+        );
+    }
+
+    #[test]
+    fn test_verify_chain_root_validity_period() {
+        // The root is checked as well, when finalising the chain
+        use crate::error::ErrorCode;
+
+        let rca = Cert::new(&test_vectors::RCA1_SUCCESS).unwrap();
+
+        rca.verify_chain_start()
+            .valid_at(Some(rca.not_before as u64))
+            .finalise()
+            .unwrap();
+
+        // Before the root was issued
+        assert_eq!(
+            Err(ErrorCode::InvalidTime),
+            rca.verify_chain_start()
+                .valid_at(Some(rca.not_before as u64 - 1))
+                .finalise()
+                .map_err(|e| e.code())
+        );
+    }
+
     #[test]
     fn test_zero_value_of_not_after_field() {
         let noc = Cert::new(&test_vectors::NOC_NOT_AFTER_ZERO).unwrap();
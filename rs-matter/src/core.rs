@@ -15,24 +15,31 @@
  *    limitations under the License.
  */
 
-use core::{borrow::Borrow, cell::RefCell};
+use core::{borrow::Borrow, cell::RefCell, time::Duration};
 
 use crate::{
     acl::AclMgr,
     data_model::{
-        cluster_basic_information::BasicInfoConfig,
+        cluster_basic_information::{self, BasicInfoConfig},
         sdm::{dev_att::DevAttDataFetcher, failsafe::FailSafe},
     },
     error::*,
     fabric::FabricMgr,
+    icd::IcdManager,
+    interaction_model::{events::EventLog, subscriptions::Subscriptions},
     mdns::Mdns,
     pairing::{print_pairing_code_and_qr, DiscoveryCapabilities},
     secure_channel::{pake::PaseMgr, spake2p::VerifierData},
     transport::{
         exchange::{ExchangeCtx, MAX_EXCHANGES},
+        network::PacketCounters,
         session::SessionMgr,
     },
-    utils::{epoch::Epoch, rand::Rand, select::Notification},
+    utils::{
+        epoch::{Epoch, UtcClock},
+        rand::Rand,
+        select::Notification,
+    },
 };
 
 /* The Matter Port */
@@ -52,14 +59,20 @@ pub struct Matter<'a> {
     pub acl_mgr: RefCell<AclMgr>, // Public for tests
     pase_mgr: RefCell<PaseMgr>,
     failsafe: RefCell<FailSafe>,
+    pub(crate) icd: RefCell<IcdManager>,
+    pub(crate) subscriptions: RefCell<Subscriptions>,
+    pub(crate) events: RefCell<EventLog>,
     persist_notification: Notification,
     pub(crate) send_notification: Notification,
+    timers_notification: Notification,
     mdns: &'a dyn Mdns,
     pub(crate) epoch: Epoch,
+    pub(crate) utc_clock: UtcClock,
     pub(crate) rand: Rand,
     dev_det: &'a BasicInfoConfig<'a>,
     dev_att: &'a dyn DevAttDataFetcher,
     pub(crate) port: u16,
+    pub(crate) packet_counters: PacketCounters,
     pub(crate) exchanges: RefCell<heapless::Vec<ExchangeCtx, MAX_EXCHANGES>>,
     pub session_mgr: RefCell<SessionMgr>, // Public for tests
 }
@@ -99,14 +112,20 @@ impl<'a> Matter<'a> {
             acl_mgr: RefCell::new(AclMgr::new()),
             pase_mgr: RefCell::new(PaseMgr::new(epoch, rand)),
             failsafe: RefCell::new(FailSafe::new()),
+            icd: RefCell::new(IcdManager::new(dev_det.icd)),
+            subscriptions: RefCell::new(Subscriptions::new()),
+            events: RefCell::new(EventLog::new(epoch)),
             persist_notification: Notification::new(),
             send_notification: Notification::new(),
+            timers_notification: Notification::new(),
             mdns,
             epoch,
+            utc_clock: UtcClock::new(epoch),
             rand,
             dev_det,
             dev_att,
             port,
+            packet_counters: PacketCounters::new(),
             exchanges: RefCell::new(heapless::Vec::new()),
             session_mgr: RefCell::new(SessionMgr::new(epoch, rand)),
         }
@@ -149,6 +168,11 @@ impl<'a> Matter<'a> {
         dev_comm: CommissioningData,
         buf: &mut [u8],
     ) -> Result<bool, Error> {
+        // Needed for the basic commissioning windows opened later on by administrators
+        self.pase_mgr
+            .borrow_mut()
+            .set_onboarding_data(dev_comm.verifier.clone(), dev_comm.discriminator);
+
         if !self.pase_mgr.borrow().is_pase_session_enabled() && self.fabric_mgr.borrow().is_empty()
         {
             print_pairing_code_and_qr(
@@ -170,12 +194,70 @@ impl<'a> Matter<'a> {
         }
     }
 
+    pub(crate) fn expire_comm_window(&self) -> Result<(), Error> {
+        self.pase_mgr.borrow_mut().expire_comm_window(self.mdns)
+    }
+
+    pub(crate) fn expire_failsafe(&self) {
+        self.failsafe.borrow_mut().expire((self.epoch)());
+    }
+
+    pub(crate) fn take_failsafe_expired(&self) -> bool {
+        self.failsafe.borrow_mut().take_expired()
+    }
+
+    /// The earliest of the expiry of the commissioning window and of the fail-safe,
+    /// as per the epoch
+    pub(crate) fn next_expiry(&self) -> Option<Duration> {
+        let comm_window = self
+            .pase_mgr
+            .borrow()
+            .comm_window()
+            .map(|window| window.expires_at);
+        let failsafe = self.failsafe.borrow().expires_at();
+
+        match (comm_window, failsafe) {
+            (Some(comm_window), Some(failsafe)) => Some(comm_window.min(failsafe)),
+            (comm_window, failsafe) => comm_window.or(failsafe),
+        }
+    }
+
+    /// Notify the timers that the commissioning window or the fail-safe might have been
+    /// opened or armed
+    pub(crate) fn notify_timers_changed(&self) {
+        self.timers_notification.signal(());
+    }
+
+    pub(crate) async fn wait_timers_changed(&self) {
+        self.timers_notification.wait().await
+    }
+
+    pub(crate) fn take_removed_fabric(&self) -> Option<u8> {
+        self.fabric_mgr.borrow_mut().take_removed()
+    }
+
+    /// Record the ShutDown event of the node
+    ///
+    /// Call before stopping the node, e.g. ahead of a reboot.
+    pub fn shut_down(&self) -> Result<(), Error> {
+        cluster_basic_information::shut_down(&self.events)
+    }
+
     pub fn notify_changed(&self) {
         if self.is_changed() {
             self.persist_notification.signal(());
         }
     }
 
+    /// Notify the persistence runner that persistent attributes or state might have changed
+    ///
+    /// Called by the stack after a write which changed the data version of a cluster with
+    /// persistent attributes, and after every invoke. Applications should call it as well after
+    /// changing a persistent attribute or state locally, i.e. on a physical button press.
+    pub fn notify_attrs_changed(&self) {
+        self.persist_notification.signal(());
+    }
+
     pub async fn wait_changed(&self) {
         self.persist_notification.wait().await
     }
@@ -205,6 +287,18 @@ impl<'a> Borrow<RefCell<FailSafe>> for Matter<'a> {
     }
 }
 
+impl<'a> Borrow<RefCell<IcdManager>> for Matter<'a> {
+    fn borrow(&self) -> &RefCell<IcdManager> {
+        &self.icd
+    }
+}
+
+impl<'a> Borrow<RefCell<EventLog>> for Matter<'a> {
+    fn borrow(&self) -> &RefCell<EventLog> {
+        &self.events
+    }
+}
+
 impl<'a> Borrow<BasicInfoConfig<'a>> for Matter<'a> {
     fn borrow(&self) -> &BasicInfoConfig<'a> {
         self.dev_det
@@ -223,6 +317,18 @@ impl<'a> Borrow<dyn Mdns + 'a> for Matter<'a> {
     }
 }
 
+impl<'a> Borrow<PacketCounters> for Matter<'a> {
+    fn borrow(&self) -> &PacketCounters {
+        &self.packet_counters
+    }
+}
+
+impl<'a> Borrow<UtcClock> for Matter<'a> {
+    fn borrow(&self) -> &UtcClock {
+        &self.utc_clock
+    }
+}
+
 impl<'a> Borrow<Epoch> for Matter<'a> {
     fn borrow(&self) -> &Epoch {
         &self.epoch
@@ -0,0 +1,88 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Helpers for building the endpoint tree of a bridge
+//!
+//! A bridge exposes an Aggregator endpoint, whose `PartsList` contains one
+//! endpoint per bridged device. Every bridged endpoint has the Bridged Node device
+//! type and serves the Bridged Device Basic Information cluster, in addition to the
+//! device type and the clusters of the bridged device.
+
+use crate::{error::Error, handler_chain_type, utils::rand::Rand};
+
+use super::{
+    cluster_bridged_device_basic_information::{self, BridgedDeviceBasicInfoCluster},
+    device_types::DEV_TYPE_AGGREGATOR,
+    dynamic::DynamicHandler,
+    objects::{Cluster, EmptyHandler, Endpoint, EndptId},
+    system_model::descriptor::{self, DescriptorCluster},
+};
+
+pub type AggregatorHandler = handler_chain_type!(DescriptorCluster<'static>);
+
+pub const CLUSTERS: [Cluster<'static>; 1] = [descriptor::CLUSTER];
+
+pub const fn endpoint(id: EndptId) -> Endpoint<'static> {
+    Endpoint {
+        id,
+        device_type: DEV_TYPE_AGGREGATOR,
+        extra_device_types: &[],
+        clusters: &CLUSTERS,
+        client_clusters: &[],
+    }
+}
+
+/// The handler of an Aggregator endpoint in a static endpoint tree, where all
+/// endpoints other than the root one are bridged endpoints
+///
+/// Not needed with `DynamicHandler`, which serves the Descriptor clusters itself.
+pub fn handler(endpoint_id: EndptId, rand: Rand) -> AggregatorHandler {
+    EmptyHandler.chain(
+        endpoint_id,
+        descriptor::ID,
+        DescriptorCluster::new_aggregator(rand),
+    )
+}
+
+/// Add a bridged device to `handler`, as a part of the Aggregator endpoint `aggregator_id`
+///
+/// The clusters of `endpoint` should include the Descriptor and the Bridged Device
+/// Basic Information clusters, and its `extra_device_types` should include
+/// `DEV_TYPE_BRIDGED_NODE`. The handlers of the other clusters of the bridged
+/// device need to be registered with `DynamicHandler::add_handler`.
+pub fn add_bridged<'a, T, const N: usize, const H: usize>(
+    handler: &DynamicHandler<'a, T, N, H>,
+    aggregator_id: EndptId,
+    endpoint: Endpoint<'a>,
+    basic_info: &'a BridgedDeviceBasicInfoCluster<'a>,
+) -> Result<(), Error> {
+    let endpoint_id = endpoint.id;
+
+    handler.add_endpoint(endpoint, Some(aggregator_id))?;
+
+    if let Err(err) = handler.add_handler(
+        endpoint_id,
+        cluster_bridged_device_basic_information::ID,
+        basic_info,
+    ) {
+        handler.remove_endpoint(endpoint_id)?;
+
+        return Err(err);
+    }
+
+    Ok(())
+}
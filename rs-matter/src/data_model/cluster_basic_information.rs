@@ -15,12 +15,22 @@
  *    limitations under the License.
  */
 
-use core::{cell::RefCell, convert::TryInto};
+use core::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+};
 
 use super::objects::*;
 use crate::{
     attribute_enum,
+    data_model::sdm::general_commissioning::RegLocationType,
     error::{Error, ErrorCode},
+    icd::IcdConfig,
+    interaction_model::{
+        events::{EventLog, EventPriority},
+        subscriptions::SUBSCRIPTIONS_PER_FABRIC,
+    },
+    tlv::{Nullable, TLVWriter, TagType, ToTLV},
     utils::rand::Rand,
 };
 use heapless::String;
@@ -28,6 +38,22 @@ use strum::FromRepr;
 
 pub const ID: u32 = 0x0028;
 
+/// The revision of this cluster, as per Matter 1.3
+pub const CLUSTER_REVISION: u16 = 3;
+
+/// The version of the Matter specification the node implements, encoded as in the
+/// SpecificationVersion attribute, i.e. `0xMMmmdd00` for version MM.mm.dd
+pub const SPECIFICATION_VERSION: u32 = 0x0103_0000;
+
+/// The minimum number of CASE sessions per fabric, as per the spec
+const CASE_SESSIONS_PER_FABRIC: u16 = 3;
+
+/// The Interaction Model processes the paths of an Invoke Request one at a time
+const MAX_PATHS_PER_INVOKE: u16 = 1;
+
+/// The Location of a node whose country is unknown
+const UNKNOWN_LOCATION: &str = "XX";
+
 #[derive(Clone, Copy, Debug, FromRepr)]
 #[repr(u16)]
 pub enum Attributes {
@@ -37,10 +63,22 @@ pub enum Attributes {
     ProductName(AttrUtfType) = 3,
     ProductId(AttrType<u16>) = 4,
     NodeLabel(AttrUtfType) = 5,
+    Location(AttrUtfType) = 6,
     HwVer(AttrType<u16>) = 7,
     SwVer(AttrType<u32>) = 9,
     SwVerString(AttrUtfType) = 0xa,
+    ManufacturingDate(AttrUtfType) = 0x0b,
+    PartNumber(AttrUtfType) = 0x0c,
+    ProductUrl(AttrUtfType) = 0x0d,
+    ProductLabel(AttrUtfType) = 0x0e,
     SerialNo(AttrUtfType) = 0x0f,
+    LocalConfigDisabled(AttrType<bool>) = 0x10,
+    Reachable(AttrType<bool>) = 0x11,
+    UniqueId(AttrUtfType) = 0x12,
+    CapabilityMinima(()) = 0x13,
+    ProductAppearance(()) = 0x14,
+    SpecificationVersion(AttrType<u32>) = 0x15,
+    MaxPathsPerInvoke(AttrType<u16>) = 0x16,
 }
 
 attribute_enum!(Attributes);
@@ -52,10 +90,153 @@ pub enum AttributesDiscriminants {
     ProductName = 3,
     ProductId = 4,
     NodeLabel = 5,
+    Location = 6,
     HwVer = 7,
     SwVer = 9,
     SwVerString = 0xa,
+    ManufacturingDate = 0x0b,
+    PartNumber = 0x0c,
+    ProductUrl = 0x0d,
+    ProductLabel = 0x0e,
     SerialNo = 0x0f,
+    LocalConfigDisabled = 0x10,
+    Reachable = 0x11,
+    UniqueId = 0x12,
+    CapabilityMinima = 0x13,
+    ProductAppearance = 0x14,
+    SpecificationVersion = 0x15,
+    MaxPathsPerInvoke = 0x16,
+}
+
+/// Events of this cluster
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u32)]
+pub enum Events {
+    StartUp = 0x00,
+    ShutDown = 0x01,
+    Leave = 0x02,
+    ReachableChanged = 0x03,
+}
+
+/// The finish of the exterior of the product
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum ProductFinish {
+    #[default]
+    Other = 0,
+    Matte = 1,
+    Satin = 2,
+    Polished = 3,
+    Rugged = 4,
+    Fabric = 5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum ProductColor {
+    Black = 0,
+    Navy = 1,
+    Green = 2,
+    Teal = 3,
+    Maroon = 4,
+    Purple = 5,
+    Olive = 6,
+    Gray = 7,
+    Blue = 8,
+    Lime = 9,
+    Aqua = 10,
+    Red = 11,
+    Fuchsia = 12,
+    Yellow = 13,
+    White = 14,
+    Nickel = 15,
+    Chrome = 16,
+    Brass = 17,
+    Copper = 18,
+    Silver = 19,
+    Gold = 20,
+}
+
+/// The appearance of the product, so that controllers can show a matching picture
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProductAppearance {
+    pub finish: ProductFinish,
+    /// `None` if the product has no primary color
+    pub primary_color: Option<ProductColor>,
+}
+
+#[derive(ToTLV)]
+struct ProductAppearanceStruct {
+    finish: u8,
+    primary_color: Nullable<u8>,
+}
+
+#[derive(ToTLV)]
+struct CapabilityMinimaStruct {
+    case_sessions_per_fabric: u16,
+    subscriptions_per_fabric: u16,
+}
+
+#[derive(ToTLV)]
+struct StartUpEvent {
+    software_version: u32,
+}
+
+#[derive(ToTLV)]
+struct ShutDownEvent {}
+
+#[derive(ToTLV)]
+struct LeaveEvent {
+    fabric_index: u8,
+}
+
+// The cluster is on the root endpoint
+const ROOT_ENDPOINT_ID: EndptId = 0;
+
+/// Record the StartUp event, once the node starts operating with software version `sw_ver`
+pub(crate) fn start_up(events: &RefCell<EventLog>, sw_ver: u32) -> Result<(), Error> {
+    events.borrow_mut().push(
+        ROOT_ENDPOINT_ID,
+        ID,
+        Events::StartUp as _,
+        EventPriority::Critical,
+        None,
+        &StartUpEvent {
+            software_version: sw_ver,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Record the ShutDown event, before the node stops operating
+pub(crate) fn shut_down(events: &RefCell<EventLog>) -> Result<(), Error> {
+    events.borrow_mut().push(
+        ROOT_ENDPOINT_ID,
+        ID,
+        Events::ShutDown as _,
+        EventPriority::Critical,
+        None,
+        &ShutDownEvent {},
+    )?;
+
+    Ok(())
+}
+
+/// Record the Leave event, once the fabric `fab_idx` was removed
+pub(crate) fn leave(events: &RefCell<EventLog>, fab_idx: u8) -> Result<(), Error> {
+    events.borrow_mut().push(
+        ROOT_ENDPOINT_ID,
+        ID,
+        Events::Leave as _,
+        EventPriority::Info,
+        None,
+        &LeaveEvent {
+            fabric_index: fab_idx,
+        },
+    )?;
+
+    Ok(())
 }
 
 #[derive(Default)]
@@ -70,6 +251,25 @@ pub struct BasicInfoConfig<'a> {
     pub device_name: &'a str,
     pub vendor_name: &'a str,
     pub product_name: &'a str,
+    /// Persistent, unique identifier of the device, which should change on a factory
+    /// reset; up to 32 characters
+    pub unique_id: &'a str,
+    /// Manufacturing date, as YYYYMMDD optionally followed by vendor-specific
+    /// characters; up to 16 characters
+    pub manufacturing_date: &'a str,
+    /// Part number of the product; up to 32 characters
+    pub part_number: &'a str,
+    /// Link to the product page of the manufacturer; up to 256 characters
+    pub product_url: &'a str,
+    /// Marketing name of the product, without the vendor name; up to 64 characters
+    pub product_label: &'a str,
+    pub product_appearance: ProductAppearance,
+    /// The parameters of an Intermittently Connected Device; `None` if the device is
+    /// always reachable
+    pub icd: Option<IcdConfig>,
+    /// The locations the device can operate in, which limit the regulatory config
+    /// that can be set during commissioning
+    pub location_capability: RegLocationType,
 }
 
 pub const CLUSTER: Cluster<'static> = Cluster {
@@ -78,6 +278,11 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        Attribute::new(
+            GlobalElements::ClusterRevision as _,
+            Access::RV,
+            Quality::NONE,
+        ),
         Attribute::new(
             AttributesDiscriminants::DMRevision as u16,
             Access::RV,
@@ -108,6 +313,11 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             Access::RWVM,
             Quality::N,
         ),
+        Attribute::new(
+            AttributesDiscriminants::Location as u16,
+            Access::RWVA,
+            Quality::N,
+        ),
         Attribute::new(
             AttributesDiscriminants::HwVer as u16,
             Access::RV,
@@ -123,11 +333,66 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             Access::RV,
             Quality::FIXED,
         ),
+        Attribute::new(
+            AttributesDiscriminants::ManufacturingDate as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PartNumber as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ProductUrl as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ProductLabel as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
         Attribute::new(
             AttributesDiscriminants::SerialNo as u16,
             Access::RV,
             Quality::FIXED,
         ),
+        Attribute::new(
+            AttributesDiscriminants::LocalConfigDisabled as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Reachable as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::UniqueId as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CapabilityMinima as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ProductAppearance as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SpecificationVersion as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MaxPathsPerInvoke as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
     ],
     commands: &[],
 };
@@ -136,6 +401,8 @@ pub struct BasicInfoCluster<'a> {
     data_ver: Dataver,
     cfg: &'a BasicInfoConfig<'a>,
     node_label: RefCell<String<32>>, // Max node-label as per the spec
+    location: RefCell<String<2>>,
+    local_config_disabled: Cell<bool>,
 }
 
 impl<'a> BasicInfoCluster<'a> {
@@ -145,12 +412,22 @@ impl<'a> BasicInfoCluster<'a> {
             data_ver: Dataver::new(rand),
             cfg,
             node_label,
+            location: RefCell::new(String::from(UNKNOWN_LOCATION)),
+            local_config_disabled: Cell::new(false),
         }
     }
 
+    /// Whether the local configuration of the node, i.e. through its own user interface,
+    /// was disabled by an administrator
+    pub fn local_config_disabled(&self) -> bool {
+        self.local_config_disabled.get()
+    }
+
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
-        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
-            if attr.is_system() {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.attr_id == GlobalElements::ClusterRevision as AttrId {
+                writer.set(CLUSTER_REVISION)
+            } else if attr.is_system() {
                 CLUSTER.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
@@ -162,10 +439,54 @@ impl<'a> BasicInfoCluster<'a> {
                     Attributes::NodeLabel(codec) => {
                         codec.encode(writer, self.node_label.borrow().as_str())
                     }
+                    Attributes::Location(codec) => {
+                        codec.encode(writer, self.location.borrow().as_str())
+                    }
                     Attributes::HwVer(codec) => codec.encode(writer, self.cfg.hw_ver),
                     Attributes::SwVer(codec) => codec.encode(writer, self.cfg.sw_ver),
                     Attributes::SwVerString(codec) => codec.encode(writer, self.cfg.sw_ver_str),
+                    Attributes::ManufacturingDate(codec) => {
+                        codec.encode(writer, self.cfg.manufacturing_date)
+                    }
+                    Attributes::PartNumber(codec) => codec.encode(writer, self.cfg.part_number),
+                    Attributes::ProductUrl(codec) => codec.encode(writer, self.cfg.product_url),
+                    Attributes::ProductLabel(codec) => codec.encode(writer, self.cfg.product_label),
                     Attributes::SerialNo(codec) => codec.encode(writer, self.cfg.serial_no),
+                    Attributes::LocalConfigDisabled(codec) => {
+                        codec.encode(writer, self.local_config_disabled.get())
+                    }
+                    // The node is reachable whenever it answers
+                    Attributes::Reachable(codec) => codec.encode(writer, true),
+                    Attributes::UniqueId(codec) => codec.encode(writer, self.cfg.unique_id),
+                    Attributes::CapabilityMinima(_) => {
+                        CapabilityMinimaStruct {
+                            case_sessions_per_fabric: CASE_SESSIONS_PER_FABRIC,
+                            subscriptions_per_fabric: SUBSCRIPTIONS_PER_FABRIC as _,
+                        }
+                        .to_tlv(&mut writer, AttrDataWriter::TAG)?;
+
+                        writer.complete()
+                    }
+                    Attributes::ProductAppearance(_) => {
+                        let appearance = &self.cfg.product_appearance;
+
+                        ProductAppearanceStruct {
+                            finish: appearance.finish as _,
+                            primary_color: match appearance.primary_color {
+                                Some(color) => Nullable::NotNull(color as _),
+                                None => Nullable::Null,
+                            },
+                        }
+                        .to_tlv(&mut writer, AttrDataWriter::TAG)?;
+
+                        writer.complete()
+                    }
+                    Attributes::SpecificationVersion(codec) => {
+                        codec.encode(writer, SPECIFICATION_VERSION)
+                    }
+                    Attributes::MaxPathsPerInvoke(codec) => {
+                        codec.encode(writer, MAX_PATHS_PER_INVOKE)
+                    }
                 }
             }
         } else {
@@ -178,11 +499,31 @@ impl<'a> BasicInfoCluster<'a> {
 
         match attr.attr_id.try_into()? {
             Attributes::NodeLabel(codec) => {
-                *self.node_label.borrow_mut() = String::from(
-                    codec
-                        .decode(data)
-                        .map_err(|_| Error::new(ErrorCode::InvalidAction))?,
-                );
+                let label = codec
+                    .decode(data)
+                    .map_err(|_| Error::new(ErrorCode::InvalidAction))?;
+
+                let mut node_label = String::new();
+                node_label
+                    .push_str(label)
+                    .map_err(|_| ErrorCode::ConstraintError)?;
+
+                *self.node_label.borrow_mut() = node_label;
+            }
+            Attributes::Location(codec) => {
+                let location = codec
+                    .decode(data)
+                    .map_err(|_| Error::new(ErrorCode::InvalidAction))?;
+
+                // An ISO 3166-1 alpha-2 country code, or XX if unknown
+                if location.len() != 2 || !location.bytes().all(|b| b.is_ascii_uppercase()) {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                *self.location.borrow_mut() = String::from(location);
+            }
+            Attributes::LocalConfigDisabled(codec) => {
+                self.local_config_disabled.set(codec.decode(data)?)
             }
             _ => return Err(Error::new(ErrorCode::InvalidAction)),
         }
@@ -201,6 +542,10 @@ impl<'a> Handler for BasicInfoCluster<'a> {
     fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
         BasicInfoCluster::write(self, attr, data)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 impl<'a> NonBlockingHandler for BasicInfoCluster<'a> {}
@@ -0,0 +1,215 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+};
+
+use super::objects::*;
+use crate::{
+    attribute_enum,
+    error::{Error, ErrorCode},
+    interaction_model::events::{EventLog, EventPriority},
+    tlv::{TLVWriter, TagType, ToTLV},
+    utils::rand::Rand,
+};
+use heapless::String;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0039;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    VendorName(AttrUtfType) = 0x01,
+    NodeLabel(AttrUtfType) = 0x05,
+    Reachable(AttrType<bool>) = 0x11,
+    UniqueId(AttrUtfType) = 0x12,
+}
+
+attribute_enum!(Attributes);
+
+/// Events of this cluster
+#[derive(FromRepr)]
+#[repr(u32)]
+pub enum Events {
+    ReachableChanged = 0x03,
+}
+
+#[derive(ToTLV)]
+struct ReachableChangedEvent {
+    reachable_new_value: bool,
+}
+
+#[derive(Default)]
+pub struct BridgedDeviceBasicInfoConfig<'a> {
+    /// Vendor name of the bridged device; up to 32 characters
+    pub vendor_name: &'a str,
+    /// Persistent, unique identifier of the bridged device; up to 32 characters
+    pub unique_id: &'a str,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::VendorName as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NodeLabel as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Reachable as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::UniqueId as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[],
+};
+
+pub struct BridgedDeviceBasicInfoCluster<'a> {
+    data_ver: Dataver,
+    endpoint_id: EndptId,
+    cfg: &'a BridgedDeviceBasicInfoConfig<'a>,
+    events: &'a RefCell<EventLog>,
+    node_label: RefCell<String<32>>, // Max node-label as per the spec
+    reachable: Cell<bool>,
+}
+
+impl<'a> BridgedDeviceBasicInfoCluster<'a> {
+    /// Create the cluster of the bridged device on endpoint `endpoint_id`, which emits its
+    /// ReachableChanged events to `events`
+    pub fn new(
+        endpoint_id: EndptId,
+        cfg: &'a BridgedDeviceBasicInfoConfig<'a>,
+        events: &'a RefCell<EventLog>,
+        rand: Rand,
+    ) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            endpoint_id,
+            cfg,
+            events,
+            node_label: RefCell::new(String::from("")),
+            reachable: Cell::new(true),
+        }
+    }
+
+    pub fn reachable(&self) -> bool {
+        self.reachable.get()
+    }
+
+    /// Update the reachability of the bridged device, i.e. when the bridge
+    /// loses or regains the connection to it
+    pub fn set_reachable(&self, reachable: bool) -> Result<(), Error> {
+        if self.reachable.get() != reachable {
+            self.reachable.set(reachable);
+            self.data_ver.changed();
+
+            self.events.borrow_mut().push(
+                self.endpoint_id,
+                ID,
+                Events::ReachableChanged as _,
+                EventPriority::Info,
+                None,
+                &ReachableChangedEvent {
+                    reachable_new_value: reachable,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::VendorName(codec) => codec.encode(writer, self.cfg.vendor_name),
+                    Attributes::NodeLabel(codec) => {
+                        codec.encode(writer, self.node_label.borrow().as_str())
+                    }
+                    Attributes::Reachable(codec) => codec.encode(writer, self.reachable.get()),
+                    Attributes::UniqueId(codec) => codec.encode(writer, self.cfg.unique_id),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::NodeLabel(codec) => {
+                let mut node_label = String::new();
+                node_label
+                    .push_str(
+                        codec
+                            .decode(data)
+                            .map_err(|_| Error::new(ErrorCode::InvalidAction))?,
+                    )
+                    .map_err(|_| Error::new(ErrorCode::InvalidAction))?;
+
+                *self.node_label.borrow_mut() = node_label;
+            }
+            _ => return Err(Error::new(ErrorCode::InvalidAction)),
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+}
+
+impl<'a> Handler for BridgedDeviceBasicInfoCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        BridgedDeviceBasicInfoCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        BridgedDeviceBasicInfoCluster::write(self, attr, data)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for BridgedDeviceBasicInfoCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for BridgedDeviceBasicInfoCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
@@ -0,0 +1,1387 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::{
+    cluster_level_control::{LevelCoupling, MAX_LEVEL, MIN_LEVEL},
+    cluster_on_off::OnOffCluster,
+    cluster_scenes_management::{AttributeValuePair, SceneParticipant},
+    objects::*,
+    transition::{Transition, TransitionTimer},
+};
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, Nullable, TLVElement},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+use embassy_time::{Duration, Instant};
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0300;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    CurrentHue(AttrType<u8>) = 0x0,
+    CurrentSaturation(AttrType<u8>) = 0x1,
+    RemainingTime(AttrType<u16>) = 0x2,
+    CurrentX(AttrType<u16>) = 0x3,
+    CurrentY(AttrType<u16>) = 0x4,
+    ColorTemperatureMireds(AttrType<u16>) = 0x7,
+    ColorMode(AttrType<u8>) = 0x8,
+    Options(AttrType<u8>) = 0xf,
+    NumberOfPrimaries(AttrType<Nullable<u8>>) = 0x10,
+    EnhancedColorMode(AttrType<u8>) = 0x4001,
+    ColorCapabilities(AttrType<u16>) = 0x400a,
+    ColorTempPhysicalMinMireds(AttrType<u16>) = 0x400b,
+    ColorTempPhysicalMaxMireds(AttrType<u16>) = 0x400c,
+    CoupleColorTempToLevelMinMireds(AttrType<u16>) = 0x400d,
+    StartUpColorTemperatureMireds(AttrType<Nullable<u16>>) = 0x4010,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    MoveToHue = 0x0,
+    MoveHue = 0x1,
+    StepHue = 0x2,
+    MoveToSaturation = 0x3,
+    MoveSaturation = 0x4,
+    StepSaturation = 0x5,
+    MoveToHueAndSaturation = 0x6,
+    MoveToColor = 0x7,
+    MoveColor = 0x8,
+    StepColor = 0x9,
+    MoveToColorTemperature = 0xa,
+    StopMoveStep = 0x47,
+    MoveColorTemperature = 0x4b,
+    StepColorTemperature = 0x4c,
+}
+
+command_enum!(Commands);
+
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    HueSaturation = 0x01,
+    EnhancedHue = 0x02,
+    ColorLoop = 0x04,
+    Xy = 0x08,
+    ColorTemperature = 0x10,
+}
+
+const FEATURES: u32 =
+    Feature::HueSaturation as u32 | Feature::Xy as u32 | Feature::ColorTemperature as u32;
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FEATURES,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::CurrentHue as u16,
+            Access::RV,
+            Quality::SN,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentSaturation as u16,
+            Access::RV,
+            Quality::SN,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::RemainingTime as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentX as u16,
+            Access::RV,
+            Quality::SN,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentY as u16,
+            Access::RV,
+            Quality::SN,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ColorTemperatureMireds as u16,
+            Access::RV,
+            Quality::SN,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ColorMode as u16,
+            Access::RV,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Options as u16,
+            Access::RWVO,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NumberOfPrimaries as u16,
+            Access::RV,
+            Quality::F.union(Quality::X),
+        ),
+        Attribute::new(
+            AttributesDiscriminants::EnhancedColorMode as u16,
+            Access::RV,
+            Quality::SN,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ColorCapabilities as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ColorTempPhysicalMinMireds as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ColorTempPhysicalMaxMireds as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CoupleColorTempToLevelMinMireds as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::StartUpColorTemperatureMireds as u16,
+            Access::RWVM,
+            Quality::N.union(Quality::X),
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::MoveToHue as _,
+        CommandsDiscriminants::MoveHue as _,
+        CommandsDiscriminants::StepHue as _,
+        CommandsDiscriminants::MoveToSaturation as _,
+        CommandsDiscriminants::MoveSaturation as _,
+        CommandsDiscriminants::StepSaturation as _,
+        CommandsDiscriminants::MoveToHueAndSaturation as _,
+        CommandsDiscriminants::MoveToColor as _,
+        CommandsDiscriminants::MoveColor as _,
+        CommandsDiscriminants::StepColor as _,
+        CommandsDiscriminants::MoveToColorTemperature as _,
+        CommandsDiscriminants::StopMoveStep as _,
+        CommandsDiscriminants::MoveColorTemperature as _,
+        CommandsDiscriminants::StepColorTemperature as _,
+    ],
+};
+
+pub const MAX_HUE: u8 = 254;
+pub const MAX_SATURATION: u8 = 254;
+pub const MAX_XY: u16 = 0xfeff;
+pub const MAX_MIREDS: u16 = 0xfeff;
+
+/// The color temperature range used unless the application sets its own
+pub const DEFAULT_MIN_MIREDS: u16 = 153;
+pub const DEFAULT_MAX_MIREDS: u16 = 500;
+
+const OPTION_EXECUTE_IF_OFF: u8 = 0x01;
+
+const MOVE_MODE_STOP: u8 = 0;
+const MOVE_MODE_UP: u8 = 1;
+const MOVE_MODE_DOWN: u8 = 3;
+
+const DIRECTION_SHORTEST: u8 = 0;
+const DIRECTION_LONGEST: u8 = 1;
+const DIRECTION_UP: u8 = 2;
+const DIRECTION_DOWN: u8 = 3;
+
+// Hue wraps around after the maximum
+const HUE_RANGE: i32 = MAX_HUE as i32 + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum ColorMode {
+    HueSaturation = 0,
+    Xy = 1,
+    Temperature = 2,
+}
+
+/// The color of the light, in the color mode last used by a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    HueSaturation {
+        hue: u8,
+        saturation: u8,
+    },
+    Xy {
+        x: u16,
+        y: u16,
+    },
+    /// The color temperature in mireds
+    Temperature(u16),
+}
+
+impl Color {
+    pub fn mode(&self) -> ColorMode {
+        match self {
+            Self::HueSaturation { .. } => ColorMode::HueSaturation,
+            Self::Xy { .. } => ColorMode::Xy,
+            Self::Temperature(_) => ColorMode::Temperature,
+        }
+    }
+}
+
+/// The application side of the Color Control cluster
+///
+/// The cluster calls the light whenever its color changes, including at every step of
+/// a transition, so the light only needs to apply the color it is given.
+pub trait ColorLight {
+    fn set_color(&self, color: Color);
+}
+
+impl<T> ColorLight for &T
+where
+    T: ColorLight,
+{
+    fn set_color(&self, color: Color) {
+        (**self).set_color(color)
+    }
+}
+
+#[derive(FromTLV)]
+struct MoveToHueReq {
+    hue: u8,
+    direction: u8,
+    transition_time: u16,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct MoveReq {
+    move_mode: u8,
+    rate: u8,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct StepReq {
+    step_mode: u8,
+    step_size: u8,
+    transition_time: u8,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct MoveToSaturationReq {
+    saturation: u8,
+    transition_time: u16,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct MoveToHueAndSaturationReq {
+    hue: u8,
+    saturation: u8,
+    transition_time: u16,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct MoveToColorReq {
+    color_x: u16,
+    color_y: u16,
+    transition_time: u16,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct MoveColorReq {
+    rate_x: i16,
+    rate_y: i16,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct StepColorReq {
+    step_x: i16,
+    step_y: i16,
+    transition_time: u16,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct MoveToColorTemperatureReq {
+    color_temperature_mireds: u16,
+    transition_time: u16,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct StopMoveStepReq {
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct MoveColorTemperatureReq {
+    move_mode: u8,
+    rate: u16,
+    color_temperature_minimum_mireds: u16,
+    color_temperature_maximum_mireds: u16,
+    options_mask: u8,
+    options_override: u8,
+}
+
+#[derive(FromTLV)]
+struct StepColorTemperatureReq {
+    step_mode: u8,
+    step_size: u16,
+    transition_time: u16,
+    color_temperature_minimum_mireds: u16,
+    color_temperature_maximum_mireds: u16,
+    options_mask: u8,
+    options_override: u8,
+}
+
+/// A linear change of the two values of a color mode over time
+///
+/// The values are hue and saturation, x and y, or the color temperature and 0. Hue is
+/// not wrapped, so that the direction of the transition is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ColorTransition {
+    mode: ColorMode,
+    values: Transition<2>,
+    // The hue change per second of MoveHue, which has no end and goes on until stopped
+    hue_rate: Option<i32>,
+}
+
+impl ColorTransition {
+    fn values_at(&self, now: Instant) -> (i32, i32) {
+        if let Some(rate) = self.hue_rate {
+            let [hue, saturation] = self.values.from;
+            let elapsed = now.saturating_duration_since(self.values.start).as_millis() as i64;
+
+            let hue = (hue as i64 + rate as i64 * elapsed / 1000).rem_euclid(HUE_RANGE as _);
+
+            (hue as _, saturation)
+        } else {
+            let [first, second] = self.values.values(now);
+
+            (first, second)
+        }
+    }
+
+    fn is_complete(&self, now: Instant) -> bool {
+        self.hue_rate.is_none() && self.values.is_complete(now)
+    }
+
+    fn remaining_time(&self, now: Instant) -> u16 {
+        if self.hue_rate.is_some() {
+            u16::MAX
+        } else {
+            self.values.remaining_time(now)
+        }
+    }
+}
+
+// The change of hue for getting from `from` to `to` in the given direction
+fn hue_delta(from: i32, to: i32, direction: u8) -> Result<i32, Error> {
+    let up = (to - from).rem_euclid(HUE_RANGE);
+    let down = if up == 0 { 0 } else { up - HUE_RANGE };
+
+    let delta = match direction {
+        DIRECTION_SHORTEST if up <= HUE_RANGE / 2 => up,
+        DIRECTION_SHORTEST => down,
+        DIRECTION_LONGEST if up > HUE_RANGE / 2 => up,
+        DIRECTION_LONGEST => down,
+        DIRECTION_UP => up,
+        DIRECTION_DOWN => down,
+        _ => Err(ErrorCode::ConstraintError)?,
+    };
+
+    Ok(delta)
+}
+
+pub struct ColorControlCluster<'a, T> {
+    data_ver: Dataver,
+    light: T,
+    on_off: Option<&'a OnOffCluster<'a>>,
+    hue: Cell<u8>,
+    saturation: Cell<u8>,
+    x: Cell<u16>,
+    y: Cell<u16>,
+    temperature: Cell<u16>,
+    color_mode: Cell<ColorMode>,
+    options: Cell<u8>,
+    min_mireds: Cell<u16>,
+    max_mireds: Cell<u16>,
+    couple_min_mireds: Cell<u16>,
+    start_up_temperature: Cell<Option<u16>>,
+    transition: Cell<Option<ColorTransition>>,
+    timer: TransitionTimer,
+}
+
+impl<'a, T> ColorControlCluster<'a, T>
+where
+    T: ColorLight,
+{
+    /// Create the cluster, optionally checking the On/Off cluster of the same endpoint
+    /// for the `ExecuteIfOff` option
+    ///
+    /// To follow the current level, the cluster also needs to be registered with
+    /// `LevelControlCluster::set_coupling`.
+    pub fn new(light: T, on_off: Option<&'a OnOffCluster<'a>>, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            light,
+            on_off,
+            hue: Cell::new(0),
+            saturation: Cell::new(0),
+            x: Cell::new(0x616b),
+            y: Cell::new(0x607d),
+            temperature: Cell::new(0x00fa),
+            color_mode: Cell::new(ColorMode::Xy),
+            options: Cell::new(0),
+            min_mireds: Cell::new(DEFAULT_MIN_MIREDS),
+            max_mireds: Cell::new(DEFAULT_MAX_MIREDS),
+            couple_min_mireds: Cell::new(DEFAULT_MIN_MIREDS),
+            start_up_temperature: Cell::new(None),
+            transition: Cell::new(None),
+            timer: TransitionTimer::new(),
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self.color_mode.get() {
+            ColorMode::HueSaturation => Color::HueSaturation {
+                hue: self.hue.get(),
+                saturation: self.saturation.get(),
+            },
+            ColorMode::Xy => Color::Xy {
+                x: self.x.get(),
+                y: self.y.get(),
+            },
+            ColorMode::Temperature => Color::Temperature(self.temperature.get()),
+        }
+    }
+
+    /// Set the color right away, cancelling any ongoing transition
+    pub fn set_color(&self, color: Color) {
+        self.transition.set(None);
+
+        let values = match color {
+            Color::HueSaturation { hue, saturation } => (hue as i32, saturation as i32),
+            Color::Xy { x, y } => (x as i32, y as i32),
+            Color::Temperature(mireds) => (mireds as i32, 0),
+        };
+
+        self.apply(color.mode(), values);
+    }
+
+    /// Set the physical color temperature range of the light, in mireds
+    pub fn set_temperature_range(&self, min: u16, max: u16) {
+        self.min_mireds.set(min);
+        self.max_mireds.set(max.max(min));
+
+        self.temperature
+            .set(self.temperature.get().clamp(min, max.max(min)));
+        self.couple_min_mireds
+            .set(self.couple_min_mireds.get().clamp(min, max.max(min)));
+        self.data_ver.changed();
+    }
+
+    /// Set the coolest color temperature the light goes to when following the level,
+    /// within the physical range
+    pub fn set_couple_min_mireds(&self, mireds: u16) {
+        self.couple_min_mireds
+            .set(mireds.clamp(self.min_mireds.get(), self.max_mireds.get()));
+        self.data_ver.changed();
+    }
+
+    /// Apply the `StartUpColorTemperatureMireds` attribute
+    ///
+    /// Should be called once on power up, after the persisted attributes were loaded.
+    pub fn start_up(&self) {
+        if let Some(mireds) = self.start_up_temperature.get() {
+            self.set_color(Color::Temperature(mireds));
+        }
+    }
+
+    /// Drive the color transitions, as per `TransitionTimer::run`
+    pub async fn run(&self) -> Result<(), Error> {
+        self.timer
+            .run(|| self.transition.get().is_some(), |now| self.update(now))
+            .await
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::CurrentHue(codec) => codec.encode(writer, self.hue.get()),
+                    Attributes::CurrentSaturation(codec) => {
+                        codec.encode(writer, self.saturation.get())
+                    }
+                    Attributes::RemainingTime(codec) => codec.encode(
+                        writer,
+                        self.transition
+                            .get()
+                            .map(|transition| transition.remaining_time(Instant::now()))
+                            .unwrap_or(0),
+                    ),
+                    Attributes::CurrentX(codec) => codec.encode(writer, self.x.get()),
+                    Attributes::CurrentY(codec) => codec.encode(writer, self.y.get()),
+                    Attributes::ColorTemperatureMireds(codec) => {
+                        codec.encode(writer, self.temperature.get())
+                    }
+                    Attributes::ColorMode(codec) => {
+                        codec.encode(writer, self.color_mode.get() as _)
+                    }
+                    Attributes::Options(codec) => codec.encode(writer, self.options.get()),
+                    Attributes::NumberOfPrimaries(codec) => {
+                        codec.encode(writer, Nullable::NotNull(0))
+                    }
+                    // Enhanced hue is not supported, so this is the same as `ColorMode`
+                    Attributes::EnhancedColorMode(codec) => {
+                        codec.encode(writer, self.color_mode.get() as _)
+                    }
+                    Attributes::ColorCapabilities(codec) => codec.encode(writer, FEATURES as _),
+                    Attributes::ColorTempPhysicalMinMireds(codec) => {
+                        codec.encode(writer, self.min_mireds.get())
+                    }
+                    Attributes::ColorTempPhysicalMaxMireds(codec) => {
+                        codec.encode(writer, self.max_mireds.get())
+                    }
+                    Attributes::CoupleColorTempToLevelMinMireds(codec) => {
+                        codec.encode(writer, self.couple_min_mireds.get())
+                    }
+                    Attributes::StartUpColorTemperatureMireds(codec) => codec.encode(
+                        writer,
+                        match self.start_up_temperature.get() {
+                            Some(mireds) => Nullable::NotNull(mireds),
+                            None => Nullable::Null,
+                        },
+                    ),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            // The current color and mode are only writable internally, when restoring
+            // the persisted color
+            Attributes::CurrentHue(codec) => self.hue.set(codec.decode(data)?),
+            Attributes::CurrentSaturation(codec) => self.saturation.set(codec.decode(data)?),
+            Attributes::CurrentX(codec) => self.x.set(codec.decode(data)?),
+            Attributes::CurrentY(codec) => self.y.set(codec.decode(data)?),
+            Attributes::ColorTemperatureMireds(codec) => self.temperature.set(codec.decode(data)?),
+            Attributes::ColorMode(codec) | Attributes::EnhancedColorMode(codec) => {
+                let mode =
+                    ColorMode::from_repr(codec.decode(data)?).ok_or(ErrorCode::ConstraintError)?;
+
+                self.color_mode.set(mode);
+                self.light.set_color(self.color());
+            }
+            Attributes::Options(codec) => self
+                .options
+                .set(codec.decode(data)? & OPTION_EXECUTE_IF_OFF),
+            Attributes::StartUpColorTemperatureMireds(codec) => {
+                let mireds = match codec.decode(data)? {
+                    Nullable::NotNull(mireds) if mireds > MAX_MIREDS => {
+                        Err(ErrorCode::ConstraintError)?
+                    }
+                    Nullable::NotNull(mireds) => Some(mireds),
+                    Nullable::Null => None,
+                };
+
+                self.start_up_temperature.set(mireds);
+            }
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        _encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::MoveToHue => {
+                cmd_enter!("MoveToHue");
+                let req = MoveToHueReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_to_hue(&req)?;
+                }
+            }
+            Commands::MoveHue => {
+                cmd_enter!("MoveHue");
+                let req = MoveReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_hue(&req)?;
+                }
+            }
+            Commands::StepHue => {
+                cmd_enter!("StepHue");
+                let req = StepReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.step_hue(&req)?;
+                }
+            }
+            Commands::MoveToSaturation => {
+                cmd_enter!("MoveToSaturation");
+                let req = MoveToSaturationReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_to_saturation(&req)?;
+                }
+            }
+            Commands::MoveSaturation => {
+                cmd_enter!("MoveSaturation");
+                let req = MoveReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_saturation(&req)?;
+                }
+            }
+            Commands::StepSaturation => {
+                cmd_enter!("StepSaturation");
+                let req = StepReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.step_saturation(&req)?;
+                }
+            }
+            Commands::MoveToHueAndSaturation => {
+                cmd_enter!("MoveToHueAndSaturation");
+                let req = MoveToHueAndSaturationReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_to_hue_and_saturation(&req)?;
+                }
+            }
+            Commands::MoveToColor => {
+                cmd_enter!("MoveToColor");
+                let req = MoveToColorReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_to_color(&req)?;
+                }
+            }
+            Commands::MoveColor => {
+                cmd_enter!("MoveColor");
+                let req = MoveColorReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_color(&req);
+                }
+            }
+            Commands::StepColor => {
+                cmd_enter!("StepColor");
+                let req = StepColorReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.step_color(&req);
+                }
+            }
+            Commands::MoveToColorTemperature => {
+                cmd_enter!("MoveToColorTemperature");
+                let req = MoveToColorTemperatureReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_to_color_temperature(&req)?;
+                }
+            }
+            Commands::StopMoveStep => {
+                cmd_enter!("StopMoveStep");
+                let req = StopMoveStepReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.stop();
+                }
+            }
+            Commands::MoveColorTemperature => {
+                cmd_enter!("MoveColorTemperature");
+                let req = MoveColorTemperatureReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_color_temperature(&req)?;
+                }
+            }
+            Commands::StepColorTemperature => {
+                cmd_enter!("StepColorTemperature");
+                let req = StepColorTemperatureReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.step_color_temperature(&req)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Commands are ignored while the light is off, unless the ExecuteIfOff option
+    // says otherwise
+    fn execute_if_off(&self, options_mask: u8, options_override: u8) -> bool {
+        let options = (self.options.get() & !options_mask) | (options_override & options_mask);
+
+        options & OPTION_EXECUTE_IF_OFF != 0
+            || self.on_off.map(|on_off| on_off.get()).unwrap_or(true)
+    }
+
+    fn move_to_hue(&self, req: &MoveToHueReq) -> Result<(), Error> {
+        if req.hue > MAX_HUE {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let (hue, saturation) = self.values(ColorMode::HueSaturation);
+        let delta = hue_delta(hue, req.hue as i32, req.direction)?;
+
+        self.start(
+            ColorMode::HueSaturation,
+            (hue + delta, saturation),
+            Self::tenths(req.transition_time),
+        );
+
+        Ok(())
+    }
+
+    fn move_hue(&self, req: &MoveReq) -> Result<(), Error> {
+        let (hue, saturation) = self.values(ColorMode::HueSaturation);
+
+        let rate = match Self::move_rate(req.move_mode, req.rate as i32)? {
+            Some(rate) => rate,
+            None => {
+                self.stop();
+                return Ok(());
+            }
+        };
+
+        info!(
+            "Color transition ({:?}) {:?} moving by {}/s",
+            ColorMode::HueSaturation,
+            (hue, saturation),
+            rate
+        );
+
+        self.begin(ColorTransition {
+            mode: ColorMode::HueSaturation,
+            values: Transition::new(
+                [hue, saturation],
+                [hue, saturation],
+                Duration::from_ticks(0),
+            ),
+            hue_rate: Some(rate),
+        });
+
+        Ok(())
+    }
+
+    fn step_hue(&self, req: &StepReq) -> Result<(), Error> {
+        let (hue, saturation) = self.values(ColorMode::HueSaturation);
+        let step = Self::step(req.step_mode, req.step_size as i32)?;
+
+        self.start(
+            ColorMode::HueSaturation,
+            (hue + step, saturation),
+            Self::tenths(req.transition_time as _),
+        );
+
+        Ok(())
+    }
+
+    fn move_to_saturation(&self, req: &MoveToSaturationReq) -> Result<(), Error> {
+        if req.saturation > MAX_SATURATION {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let (hue, _) = self.values(ColorMode::HueSaturation);
+
+        self.start(
+            ColorMode::HueSaturation,
+            (hue, req.saturation as _),
+            Self::tenths(req.transition_time),
+        );
+
+        Ok(())
+    }
+
+    fn move_saturation(&self, req: &MoveReq) -> Result<(), Error> {
+        let (hue, saturation) = self.values(ColorMode::HueSaturation);
+
+        let rate = match Self::move_rate(req.move_mode, req.rate as i32)? {
+            Some(rate) => rate,
+            None => {
+                self.stop();
+                return Ok(());
+            }
+        };
+
+        let to = if rate > 0 { MAX_SATURATION as i32 } else { 0 };
+
+        self.start(
+            ColorMode::HueSaturation,
+            (hue, to),
+            Self::moving(saturation, to, rate),
+        );
+
+        Ok(())
+    }
+
+    fn step_saturation(&self, req: &StepReq) -> Result<(), Error> {
+        let (hue, saturation) = self.values(ColorMode::HueSaturation);
+        let step = Self::step(req.step_mode, req.step_size as i32)?;
+
+        self.start(
+            ColorMode::HueSaturation,
+            (hue, (saturation + step).clamp(0, MAX_SATURATION as _)),
+            Self::tenths(req.transition_time as _),
+        );
+
+        Ok(())
+    }
+
+    fn move_to_hue_and_saturation(&self, req: &MoveToHueAndSaturationReq) -> Result<(), Error> {
+        if req.hue > MAX_HUE || req.saturation > MAX_SATURATION {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let (hue, _) = self.values(ColorMode::HueSaturation);
+        let delta = hue_delta(hue, req.hue as i32, DIRECTION_SHORTEST)?;
+
+        self.start(
+            ColorMode::HueSaturation,
+            (hue + delta, req.saturation as _),
+            Self::tenths(req.transition_time),
+        );
+
+        Ok(())
+    }
+
+    fn move_to_color(&self, req: &MoveToColorReq) -> Result<(), Error> {
+        if req.color_x > MAX_XY || req.color_y > MAX_XY {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        self.start(
+            ColorMode::Xy,
+            (req.color_x as _, req.color_y as _),
+            Self::tenths(req.transition_time),
+        );
+
+        Ok(())
+    }
+
+    fn move_color(&self, req: &MoveColorReq) {
+        if req.rate_x == 0 && req.rate_y == 0 {
+            self.stop();
+            return;
+        }
+
+        let (x, y) = self.values(ColorMode::Xy);
+
+        // Move until the first of x and y reaches its bound
+        let duration = [(x, req.rate_x as i32), (y, req.rate_y as i32)]
+            .into_iter()
+            .filter(|(_, rate)| *rate != 0)
+            .map(|(value, rate)| {
+                let to = if rate > 0 { MAX_XY as i32 } else { 0 };
+
+                Self::moving(value, to, rate)
+            })
+            .min()
+            .unwrap_or(Duration::from_ticks(0));
+
+        let distance = |rate: i16| (rate as i64 * duration.as_millis() as i64 / 1000) as i32;
+
+        self.start(
+            ColorMode::Xy,
+            (x + distance(req.rate_x), y + distance(req.rate_y)),
+            duration,
+        );
+    }
+
+    fn step_color(&self, req: &StepColorReq) {
+        let (x, y) = self.values(ColorMode::Xy);
+
+        self.start(
+            ColorMode::Xy,
+            (
+                (x + req.step_x as i32).clamp(0, MAX_XY as _),
+                (y + req.step_y as i32).clamp(0, MAX_XY as _),
+            ),
+            Self::tenths(req.transition_time),
+        );
+    }
+
+    fn move_to_color_temperature(&self, req: &MoveToColorTemperatureReq) -> Result<(), Error> {
+        if req.color_temperature_mireds > MAX_MIREDS {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let mireds = req
+            .color_temperature_mireds
+            .clamp(self.min_mireds.get(), self.max_mireds.get());
+
+        self.start(
+            ColorMode::Temperature,
+            (mireds as _, 0),
+            Self::tenths(req.transition_time),
+        );
+
+        Ok(())
+    }
+
+    fn move_color_temperature(&self, req: &MoveColorTemperatureReq) -> Result<(), Error> {
+        let (temperature, _) = self.values(ColorMode::Temperature);
+
+        let rate = match Self::move_rate(req.move_mode, req.rate as i32)? {
+            Some(rate) => rate,
+            None => {
+                self.stop();
+                return Ok(());
+            }
+        };
+
+        let (min, max) = self.temperature_bounds(
+            req.color_temperature_minimum_mireds,
+            req.color_temperature_maximum_mireds,
+        );
+
+        let to = if rate > 0 { max } else { min };
+
+        self.start(
+            ColorMode::Temperature,
+            (to, 0),
+            Self::moving(temperature, to, rate),
+        );
+
+        Ok(())
+    }
+
+    fn step_color_temperature(&self, req: &StepColorTemperatureReq) -> Result<(), Error> {
+        let (temperature, _) = self.values(ColorMode::Temperature);
+        let step = Self::step(req.step_mode, req.step_size as i32)?;
+
+        let (min, max) = self.temperature_bounds(
+            req.color_temperature_minimum_mireds,
+            req.color_temperature_maximum_mireds,
+        );
+
+        self.start(
+            ColorMode::Temperature,
+            ((temperature + step).clamp(min, max), 0),
+            Self::tenths(req.transition_time),
+        );
+
+        Ok(())
+    }
+
+    fn stop(&self) {
+        if let Some(transition) = self.transition.get() {
+            self.apply(transition.mode, transition.values_at(Instant::now()));
+            self.transition.set(None);
+            self.data_ver.changed();
+        }
+    }
+
+    fn start(&self, mode: ColorMode, to: (i32, i32), duration: Duration) {
+        let from = self.values(mode);
+
+        info!(
+            "Color transition ({:?}) {:?} -> {:?} in {}ms",
+            mode,
+            from,
+            to,
+            duration.as_millis()
+        );
+
+        self.begin(ColorTransition {
+            mode,
+            values: Transition::new([from.0, from.1], [to.0, to.1], duration),
+            hue_rate: None,
+        });
+    }
+
+    fn begin(&self, transition: ColorTransition) {
+        // Switches the color mode right away
+        self.apply(
+            transition.mode,
+            transition.values_at(transition.values.start),
+        );
+
+        self.transition.set(Some(transition));
+
+        self.timer.started(Instant::now());
+        self.data_ver.changed();
+
+        // Transitions with no duration complete right away
+        self.update(Instant::now());
+    }
+
+    fn update(&self, now: Instant) {
+        if let Some(transition) = self.transition.get() {
+            if transition.is_complete(now) {
+                self.apply(transition.mode, transition.values_at(now));
+                self.transition.set(None);
+
+                // RemainingTime is now 0
+                self.data_ver.changed();
+            } else if self.store(transition.mode, transition.values_at(now))
+                && self.timer.report(now)
+            {
+                self.data_ver.changed();
+            }
+        }
+    }
+
+    // The current values of a color mode, as used by transitions
+    fn values(&self, mode: ColorMode) -> (i32, i32) {
+        match mode {
+            ColorMode::HueSaturation => (self.hue.get() as _, self.saturation.get() as _),
+            ColorMode::Xy => (self.x.get() as _, self.y.get() as _),
+            ColorMode::Temperature => (self.temperature.get() as _, 0),
+        }
+    }
+
+    fn apply(&self, mode: ColorMode, values: (i32, i32)) {
+        if self.store(mode, values) {
+            self.data_ver.changed();
+        }
+    }
+
+    // Change the color without reporting it, returning `true` if it changed
+    fn store(&self, mode: ColorMode, values: (i32, i32)) -> bool {
+        let changed = match mode {
+            ColorMode::HueSaturation => {
+                Self::update_value(&self.hue, values.0.rem_euclid(HUE_RANGE) as _)
+                    | Self::update_value(
+                        &self.saturation,
+                        values.1.clamp(0, MAX_SATURATION as _) as _,
+                    )
+            }
+            ColorMode::Xy => {
+                Self::update_value(&self.x, values.0.clamp(0, MAX_XY as _) as _)
+                    | Self::update_value(&self.y, values.1.clamp(0, MAX_XY as _) as _)
+            }
+            ColorMode::Temperature => Self::update_value(
+                &self.temperature,
+                values
+                    .0
+                    .clamp(self.min_mireds.get() as _, self.max_mireds.get() as _)
+                    as _,
+            ),
+        };
+
+        let changed = Self::update_value(&self.color_mode, mode) || changed;
+
+        if changed {
+            self.light.set_color(self.color());
+        }
+
+        changed
+    }
+
+    fn update_value<V>(cell: &Cell<V>, value: V) -> bool
+    where
+        V: Copy + PartialEq,
+    {
+        let changed = cell.get() != value;
+
+        cell.set(value);
+
+        changed
+    }
+
+    // The bounds of color temperature moves and steps, within the physical bounds
+    fn temperature_bounds(&self, min: u16, max: u16) -> (i32, i32) {
+        let physical_min = self.min_mireds.get();
+        let physical_max = self.max_mireds.get();
+
+        let min = if min == 0 {
+            physical_min
+        } else {
+            min.max(physical_min)
+        };
+        let max = if max == 0 {
+            physical_max
+        } else {
+            max.min(physical_max)
+        };
+
+        (min as _, max.max(min) as _)
+    }
+
+    // The signed rate of a move command, or `None` for stopping
+    fn move_rate(move_mode: u8, rate: i32) -> Result<Option<i32>, Error> {
+        match move_mode {
+            MOVE_MODE_STOP => Ok(None),
+            _ if rate == 0 => Err(ErrorCode::InvalidCommand.into()),
+            MOVE_MODE_UP => Ok(Some(rate)),
+            MOVE_MODE_DOWN => Ok(Some(-rate)),
+            _ => Err(ErrorCode::ConstraintError.into()),
+        }
+    }
+
+    fn step(step_mode: u8, step_size: i32) -> Result<i32, Error> {
+        match step_mode {
+            MOVE_MODE_UP => Ok(step_size),
+            MOVE_MODE_DOWN => Ok(-step_size),
+            _ => Err(ErrorCode::ConstraintError.into()),
+        }
+    }
+
+    // The time it takes to get from `from` to `to` with `rate` units per second
+    fn moving(from: i32, to: i32, rate: i32) -> Duration {
+        Duration::from_millis(from.abs_diff(to) as u64 * 1000 / rate.unsigned_abs() as u64)
+    }
+
+    fn tenths(time: u16) -> Duration {
+        Duration::from_millis(time as u64 * 100)
+    }
+}
+
+// Follows the current level with the color temperature, warmer as the light dims
+impl<'a, T> LevelCoupling for ColorControlCluster<'a, T>
+where
+    T: ColorLight,
+{
+    fn level_changed(&self, level: u8) {
+        if self.color_mode.get() == ColorMode::Temperature {
+            let min = self.min_mireds.get() as u32;
+            let max = self.max_mireds.get() as u32;
+
+            let dimmed = (MAX_LEVEL - level.clamp(MIN_LEVEL, MAX_LEVEL)) as u32;
+            let mireds = min + (max - min) * dimmed / (MAX_LEVEL - MIN_LEVEL) as u32;
+
+            // Never cooler than CoupleColorTempToLevelMinMireds
+            let mireds = mireds.max(self.couple_min_mireds.get() as u32);
+
+            self.set_color(Color::Temperature(mireds as _));
+        }
+    }
+}
+
+// Scenes store the values of all color modes, and `EnhancedColorMode` for the one to
+// transition in when recalled
+impl<'a, T> SceneParticipant for ColorControlCluster<'a, T>
+where
+    T: ColorLight,
+{
+    fn scene_value(&self, attr_id: AttrId) -> Option<u32> {
+        let value = match Attributes::from_repr(attr_id)? {
+            Attributes::CurrentHue(_) => self.hue.get() as _,
+            Attributes::CurrentSaturation(_) => self.saturation.get() as _,
+            Attributes::CurrentX(_) => self.x.get() as _,
+            Attributes::CurrentY(_) => self.y.get() as _,
+            Attributes::ColorTemperatureMireds(_) => self.temperature.get() as _,
+            Attributes::EnhancedColorMode(_) => self.color_mode.get() as _,
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    fn recall_scene(&self, values: &[AttributeValuePair], transition_time: Duration) {
+        let value = |attr: AttributesDiscriminants| {
+            values
+                .iter()
+                .find(|value| value.attribute_id == attr as u32)
+                .map(|value| value.attribute_value as i32)
+        };
+
+        let mode = value(AttributesDiscriminants::EnhancedColorMode)
+            .and_then(|mode| ColorMode::from_repr(mode as _))
+            .unwrap_or(self.color_mode.get());
+
+        let current = self.values(mode);
+
+        let to = match mode {
+            ColorMode::HueSaturation => (
+                value(AttributesDiscriminants::CurrentHue).unwrap_or(current.0),
+                value(AttributesDiscriminants::CurrentSaturation).unwrap_or(current.1),
+            ),
+            ColorMode::Xy => (
+                value(AttributesDiscriminants::CurrentX).unwrap_or(current.0),
+                value(AttributesDiscriminants::CurrentY).unwrap_or(current.1),
+            ),
+            ColorMode::Temperature => (
+                value(AttributesDiscriminants::ColorTemperatureMireds).unwrap_or(current.0),
+                0,
+            ),
+        };
+
+        self.start(mode, to, transition_time);
+    }
+}
+
+impl<'a, T> Handler for ColorControlCluster<'a, T>
+where
+    T: ColorLight,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        ColorControlCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        ColorControlCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        ColorControlCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a, T> NonBlockingHandler for ColorControlCluster<'a, T> where T: ColorLight {}
+
+impl<'a, T> ChangeNotifier<()> for ColorControlCluster<'a, T> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_time::{Duration, Instant};
+
+    use crate::{
+        data_model::{cluster_level_control::LevelCoupling, transition::Transition},
+        utils::rand::dummy_rand,
+    };
+
+    use super::{
+        hue_delta, Color, ColorControlCluster, ColorLight, ColorMode, ColorTransition,
+        MoveToColorTemperatureReq,
+    };
+
+    struct Light;
+
+    impl ColorLight for Light {
+        fn set_color(&self, _color: Color) {}
+    }
+
+    #[test]
+    fn test_hue_delta() {
+        // Shortest
+        assert_eq!(hue_delta(10, 20, 0).unwrap(), 10);
+        assert_eq!(hue_delta(10, 200, 0).unwrap(), -65);
+        assert_eq!(hue_delta(200, 10, 0).unwrap(), 65);
+
+        // Longest
+        assert_eq!(hue_delta(10, 20, 1).unwrap(), -245);
+        assert_eq!(hue_delta(10, 200, 1).unwrap(), 190);
+
+        // Up and down
+        assert_eq!(hue_delta(200, 10, 2).unwrap(), 65);
+        assert_eq!(hue_delta(10, 200, 3).unwrap(), -65);
+        assert_eq!(hue_delta(10, 10, 3).unwrap(), 0);
+
+        assert!(hue_delta(10, 20, 4).is_err());
+    }
+
+    #[test]
+    fn test_move_hue() {
+        // Goes on until stopped, wrapping around
+        let transition = ColorTransition {
+            mode: ColorMode::HueSaturation,
+            values: Transition {
+                from: [250, 100],
+                to: [250, 100],
+                start: Instant::from_millis(1000),
+                duration: Duration::from_ticks(0),
+            },
+            hue_rate: Some(10),
+        };
+
+        assert_eq!(transition.values_at(Instant::from_millis(1500)), (255, 100));
+        assert_eq!(transition.values_at(Instant::from_millis(3000)), (14, 100));
+        assert_eq!(
+            transition.values_at(Instant::from_millis(3_601_000)),
+            (154, 100)
+        );
+        assert!(!transition.is_complete(Instant::from_millis(3_601_000)));
+
+        let down = ColorTransition {
+            hue_rate: Some(-10),
+            ..transition
+        };
+
+        assert_eq!(down.values_at(Instant::from_millis(2000)), (240, 100));
+    }
+
+    #[test]
+    fn test_temperature_limits() {
+        let color = ColorControlCluster::new(Light, None, dummy_rand);
+        color.set_temperature_range(200, 400);
+
+        color
+            .move_to_color_temperature(&MoveToColorTemperatureReq {
+                color_temperature_mireds: 100,
+                transition_time: 0,
+                options_mask: 0,
+                options_override: 0,
+            })
+            .unwrap();
+        assert_eq!(color.color(), Color::Temperature(200));
+
+        color.set_couple_min_mireds(300);
+        color.level_changed(254);
+        assert_eq!(color.color(), Color::Temperature(300));
+
+        color.level_changed(1);
+        assert_eq!(color.color(), Color::Temperature(400));
+    }
+}
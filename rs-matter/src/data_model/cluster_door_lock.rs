@@ -0,0 +1,1796 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+};
+
+use super::objects::*;
+use super::persist::StatePersist;
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    interaction_model::events::{EventLog, EventPriority},
+    tlv::{
+        self, FromTLV, Nullable, OctetStr, TLVElement, TLVList, TLVWriter, TagType, ToTLV, UtfStr,
+    },
+    transport::exchange::Exchange,
+    utils::{rand::Rand, writebuf::WriteBuf},
+};
+use embassy_time::{Duration, Instant};
+use log::{info, warn};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0101;
+
+/// Number of users in the user table
+pub const NUM_USERS: u16 = 10;
+/// Number of PIN credentials, shared by all users
+pub const NUM_PIN_CREDENTIALS: u16 = 10;
+pub const CREDENTIALS_PER_USER: u8 = 5;
+pub const MIN_PIN_LEN: u8 = 4;
+pub const MAX_PIN_LEN: u8 = 8;
+pub const MAX_USER_NAME_LEN: usize = 10;
+
+/// `UserIndex` of `ClearUser` clearing all users
+const ALL_USERS: u16 = 0xFFFE;
+
+/// Operating modes which are not supported have their bit set
+const SUPPORTED_OPERATING_MODES: u16 =
+    !((1 << OperatingMode::Normal as u16) | (1 << OperatingMode::NoRemoteLockUnlock as u16));
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    LockState(AttrType<Nullable<u8>>) = 0x0000,
+    LockType(AttrType<u8>) = 0x0001,
+    ActuatorEnabled(AttrType<bool>) = 0x0002,
+    NumberOfTotalUsersSupported(AttrType<u16>) = 0x0011,
+    NumberOfPINUsersSupported(AttrType<u16>) = 0x0012,
+    MaxPINCodeLength(AttrType<u8>) = 0x0017,
+    MinPINCodeLength(AttrType<u8>) = 0x0018,
+    NumberOfCredentialsSupportedPerUser(AttrType<u8>) = 0x001C,
+    OperatingMode(AttrType<u8>) = 0x0025,
+    SupportedOperatingModes(AttrType<u16>) = 0x0026,
+    WrongCodeEntryLimit(AttrType<u8>) = 0x0030,
+    UserCodeTemporaryDisableTime(AttrType<u8>) = 0x0031,
+    RequirePINforRemoteOperation(AttrType<bool>) = 0x0033,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    LockDoor = 0x00,
+    UnlockDoor = 0x01,
+    SetUser = 0x1A,
+    GetUser = 0x1B,
+    ClearUser = 0x1D,
+    SetCredential = 0x22,
+    GetCredentialStatus = 0x24,
+    ClearCredential = 0x26,
+}
+
+command_enum!(Commands);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum RespCommands {
+    GetUserResponse = 0x1C,
+    SetCredentialResponse = 0x23,
+    GetCredentialStatusResponse = 0x25,
+}
+
+/// Events of this cluster, recorded in the event log and handed to `LockActuator::event`
+#[derive(FromRepr)]
+#[repr(u32)]
+pub enum Events {
+    DoorLockAlarm = 0x00,
+    LockOperation = 0x02,
+    LockOperationError = 0x03,
+}
+
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    PINCredential = 0x0001,
+    CredentialsOverTheAirAccess = 0x0080,
+    User = 0x0100,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: Feature::PINCredential as u32
+        | Feature::CredentialsOverTheAirAccess as u32
+        | Feature::User as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::LockState as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::LockType as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActuatorEnabled as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NumberOfTotalUsersSupported as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NumberOfPINUsersSupported as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MaxPINCodeLength as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MinPINCodeLength as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NumberOfCredentialsSupportedPerUser as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OperatingMode as u16,
+            Access::RWVA,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SupportedOperatingModes as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::WrongCodeEntryLimit as u16,
+            Access::RWVA,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::UserCodeTemporaryDisableTime as u16,
+            Access::RWVA,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::RequirePINforRemoteOperation as u16,
+            Access::RWVA,
+            Quality::N,
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::LockDoor as _,
+        CommandsDiscriminants::UnlockDoor as _,
+        CommandsDiscriminants::SetUser as _,
+        CommandsDiscriminants::GetUser as _,
+        CommandsDiscriminants::ClearUser as _,
+        CommandsDiscriminants::SetCredential as _,
+        CommandsDiscriminants::GetCredentialStatus as _,
+        CommandsDiscriminants::ClearCredential as _,
+    ],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum LockState {
+    NotFullyLocked = 0,
+    Locked = 1,
+    Unlocked = 2,
+    Unlatched = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum LockType {
+    DeadBolt = 0,
+    Magnetic = 1,
+    Other = 2,
+    Mortise = 3,
+    Rim = 4,
+    LatchBolt = 5,
+    CylindricalLock = 6,
+    TubularLock = 7,
+    InterconnectedLock = 8,
+    DeadLatch = 9,
+    DoorFurniture = 10,
+    Eurocylinder = 11,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum OperatingMode {
+    Normal = 0,
+    Vacation = 1,
+    Privacy = 2,
+    NoRemoteLockUnlock = 3,
+    Passage = 4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum UserStatus {
+    Available = 0,
+    OccupiedEnabled = 1,
+    OccupiedDisabled = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum UserType {
+    UnrestrictedUser = 0,
+    YearDayScheduleUser = 1,
+    WeekDayScheduleUser = 2,
+    ProgrammingUser = 3,
+    NonAccessUser = 4,
+    ForcedUser = 5,
+    DisposableUser = 6,
+    ExpiringUser = 7,
+    ScheduleRestrictedUser = 8,
+    RemoteOnlyUser = 9,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum CredentialRule {
+    Single = 0,
+    Dual = 1,
+    Tri = 2,
+}
+
+/// Only PIN credentials are supported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum CredentialType {
+    ProgrammingPIN = 0,
+    PIN = 1,
+    RFID = 2,
+    Fingerprint = 3,
+    FingerVein = 4,
+    Face = 5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum DataOperationType {
+    Add = 0,
+    Clear = 1,
+    Modify = 2,
+}
+
+/// Status of `SetCredentialResponse`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum DlStatus {
+    Success = 0x00,
+    Failure = 0x01,
+    Duplicate = 0x02,
+    Occupied = 0x03,
+    InvalidField = 0x85,
+    ResourceExhausted = 0x89,
+    NotFound = 0x8B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum AlarmCode {
+    LockJammed = 0,
+    LockFactoryReset = 1,
+    LockRadioPowerCycled = 3,
+    WrongCodeEntryLimit = 4,
+    FrontEsceutcheonRemoved = 5,
+    DoorForcedOpen = 6,
+    DoorAjar = 7,
+    ForcedUser = 8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum LockOperationType {
+    Lock = 0,
+    Unlock = 1,
+    NonAccessUserEvent = 2,
+    ForcedUserEvent = 3,
+    Unlatch = 4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum OperationSource {
+    Unspecified = 0,
+    Manual = 1,
+    ProprietaryRemote = 2,
+    Keypad = 3,
+    Auto = 4,
+    Button = 5,
+    Schedule = 6,
+    Remote = 7,
+    RFID = 8,
+    Biometric = 9,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum OperationError {
+    Unspecified = 0,
+    InvalidCredential = 1,
+    DisabledUserDenied = 2,
+    Restricted = 3,
+    InsufficientBattery = 4,
+}
+
+/// An event of the Door Lock cluster, as handed to `LockActuator::event`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorLockEvent {
+    DoorLockAlarm(AlarmCode),
+    LockOperation {
+        operation: LockOperationType,
+        source: OperationSource,
+        user_index: Option<u16>,
+        fab_idx: Option<u8>,
+    },
+    LockOperationError {
+        operation: LockOperationType,
+        source: OperationSource,
+        error: OperationError,
+        user_index: Option<u16>,
+        fab_idx: Option<u8>,
+    },
+}
+
+/// The application side of the Door Lock cluster
+///
+/// `lock` and `unlock` drive the actuator once the cluster has validated the request;
+/// an error fails the command, e.g. when the bolt is jammed.
+pub trait LockActuator {
+    fn lock(&self) -> Result<(), Error>;
+
+    fn unlock(&self) -> Result<(), Error>;
+
+    /// Called for every event of the cluster once it is recorded in the event log, e.g. to
+    /// notify the user
+    fn event(&self, _event: &DoorLockEvent) {}
+}
+
+impl<T> LockActuator for &T
+where
+    T: LockActuator,
+{
+    fn lock(&self) -> Result<(), Error> {
+        (**self).lock()
+    }
+
+    fn unlock(&self) -> Result<(), Error> {
+        (**self).unlock()
+    }
+
+    fn event(&self, event: &DoorLockEvent) {
+        (**self).event(event)
+    }
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct LockDoorReq<'a> {
+    pub pin_code: Option<OctetStr<'a>>,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct SetUserReq<'a> {
+    pub operation_type: u8,
+    pub user_index: u16,
+    pub user_name: Nullable<UtfStr<'a>>,
+    pub user_unique_id: Nullable<u32>,
+    pub user_status: Nullable<u8>,
+    pub user_type: Nullable<u8>,
+    pub credential_rule: Nullable<u8>,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct UserIndexReq {
+    pub user_index: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromTLV, ToTLV)]
+pub struct CredentialStruct {
+    pub credential_type: u8,
+    pub credential_index: u16,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct SetCredentialReq<'a> {
+    pub operation_type: u8,
+    pub credential: CredentialStruct,
+    pub credential_data: OctetStr<'a>,
+    pub user_index: Nullable<u16>,
+    pub user_status: Nullable<u8>,
+    pub user_type: Nullable<u8>,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct CredentialReq {
+    pub credential: CredentialStruct,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct ClearCredentialReq {
+    pub credential: Nullable<CredentialStruct>,
+}
+
+#[derive(ToTLV)]
+#[tlvargs(lifetime = "'a")]
+struct GetUserResp<'a> {
+    user_index: u16,
+    user_name: Nullable<UtfStr<'a>>,
+    user_unique_id: Nullable<u32>,
+    user_status: Nullable<u8>,
+    user_type: Nullable<u8>,
+    credential_rule: Nullable<u8>,
+    credentials: Nullable<&'a [CredentialStruct]>,
+    creator_fabric_index: Nullable<u8>,
+    last_modified_fabric_index: Nullable<u8>,
+    next_user_index: Nullable<u16>,
+}
+
+#[derive(Debug, PartialEq, ToTLV)]
+pub struct SetCredentialResp {
+    pub status: u8,
+    pub user_index: Nullable<u16>,
+    pub next_credential_index: Nullable<u16>,
+}
+
+#[derive(Debug, PartialEq, ToTLV)]
+pub struct CredentialStatusResp {
+    pub credential_exists: bool,
+    pub user_index: Nullable<u16>,
+    pub creator_fabric_index: Nullable<u8>,
+    pub last_modified_fabric_index: Nullable<u8>,
+    pub next_credential_index: Nullable<u16>,
+}
+
+#[derive(ToTLV)]
+struct DoorLockAlarmEvent {
+    alarm_code: u8,
+}
+
+#[derive(ToTLV)]
+struct LockOperationEvent {
+    lock_operation_type: u8,
+    operation_source: u8,
+    user_index: Nullable<u16>,
+    fabric_index: Nullable<u8>,
+    source_node: Nullable<u64>,
+}
+
+#[derive(ToTLV)]
+struct LockOperationErrorEvent {
+    lock_operation_type: u8,
+    operation_source: u8,
+    operation_error: u8,
+    user_index: Nullable<u16>,
+    fabric_index: Nullable<u8>,
+    source_node: Nullable<u64>,
+}
+
+#[derive(Debug, Clone, FromTLV, ToTLV)]
+struct User {
+    user_index: u16,
+    name: heapless::String<MAX_USER_NAME_LEN>,
+    unique_id: Option<u32>,
+    status: u8,
+    user_type: u8,
+    credential_rule: u8,
+    creator: u8,
+    last_modified: u8,
+}
+
+/// A PIN credential, always owned by a user
+#[derive(Debug, Clone, FromTLV, ToTLV)]
+struct Credential {
+    credential_index: u16,
+    data: heapless::Vec<u8, { MAX_PIN_LEN as usize }>,
+    user_index: u16,
+    creator: u8,
+    last_modified: u8,
+}
+
+pub struct DoorLockCluster<'a, T> {
+    data_ver: Dataver,
+    endpoint_id: EndptId,
+    actuator: T,
+    events: &'a RefCell<EventLog>,
+    lock_type: LockType,
+    lock_state: Cell<Option<LockState>>,
+    actuator_enabled: Cell<bool>,
+    operating_mode: Cell<OperatingMode>,
+    wrong_code_entry_limit: Cell<u8>,
+    user_code_temporary_disable_time: Cell<u8>,
+    require_pin_for_remote_operation: Cell<bool>,
+    wrong_code_entries: Cell<u8>,
+    disabled_until: Cell<Option<Instant>>,
+    users: RefCell<heapless::Vec<User, { NUM_USERS as usize }>>,
+    credentials: RefCell<heapless::Vec<Credential, { NUM_PIN_CREDENTIALS as usize }>>,
+    changed: Cell<bool>,
+}
+
+impl<'a, T> DoorLockCluster<'a, T>
+where
+    T: LockActuator,
+{
+    /// Create the cluster of the lock on endpoint `endpoint_id`, which records its events
+    /// in `events`
+    pub fn new(
+        endpoint_id: EndptId,
+        lock_type: LockType,
+        actuator: T,
+        events: &'a RefCell<EventLog>,
+        rand: Rand,
+    ) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            endpoint_id,
+            actuator,
+            events,
+            lock_type,
+            lock_state: Cell::new(None),
+            actuator_enabled: Cell::new(true),
+            operating_mode: Cell::new(OperatingMode::Normal),
+            wrong_code_entry_limit: Cell::new(3),
+            user_code_temporary_disable_time: Cell::new(10),
+            require_pin_for_remote_operation: Cell::new(false),
+            wrong_code_entries: Cell::new(0),
+            disabled_until: Cell::new(None),
+            users: RefCell::new(heapless::Vec::new()),
+            credentials: RefCell::new(heapless::Vec::new()),
+            changed: Cell::new(false),
+        }
+    }
+
+    /// The lock state, `None` if it is not known, e.g. while the bolt is moving
+    pub fn lock_state(&self) -> Option<LockState> {
+        self.lock_state.get()
+    }
+
+    /// Update the lock state after a local operation, e.g. with the thumb turn
+    pub fn set_lock_state(&self, state: Option<LockState>) {
+        if self.lock_state.replace(state) != state {
+            self.data_ver.changed();
+        }
+    }
+
+    pub fn set_actuator_enabled(&self, enabled: bool) {
+        if self.actuator_enabled.replace(enabled) != enabled {
+            self.data_ver.changed();
+        }
+    }
+
+    /// Check a PIN entered locally, e.g. on a keypad
+    ///
+    /// Wrong codes count towards `WrongCodeEntryLimit` just like the ones of remote
+    /// operations. Returns the index of the user owning the PIN.
+    pub fn verify_pin(&self, pin: &[u8], operation: LockOperationType) -> Result<u16, Error> {
+        self.check_pin(pin, operation, OperationSource::Keypad, None)
+    }
+
+    /// Remove the references to a fabric which was removed
+    ///
+    /// Users and credentials stay, as they still give access to the door.
+    pub fn remove_fabric(&self, fab_idx: u8) {
+        for user in self.users.borrow_mut().iter_mut() {
+            if user.creator == fab_idx {
+                user.creator = 0;
+            }
+            if user.last_modified == fab_idx {
+                user.last_modified = 0;
+            }
+        }
+
+        for credential in self.credentials.borrow_mut().iter_mut() {
+            if credential.creator == fab_idx {
+                credential.creator = 0;
+            }
+            if credential.last_modified == fab_idx {
+                credential.last_modified = 0;
+            }
+        }
+
+        self.changed.set(true);
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::LockState(codec) => codec.encode(
+                        writer,
+                        Self::nullable(self.lock_state.get().map(|state| state as _)),
+                    ),
+                    Attributes::LockType(codec) => codec.encode(writer, self.lock_type as _),
+                    Attributes::ActuatorEnabled(codec) => {
+                        codec.encode(writer, self.actuator_enabled.get())
+                    }
+                    Attributes::NumberOfTotalUsersSupported(codec) => {
+                        codec.encode(writer, NUM_USERS)
+                    }
+                    Attributes::NumberOfPINUsersSupported(codec) => {
+                        codec.encode(writer, NUM_PIN_CREDENTIALS)
+                    }
+                    Attributes::MaxPINCodeLength(codec) => codec.encode(writer, MAX_PIN_LEN),
+                    Attributes::MinPINCodeLength(codec) => codec.encode(writer, MIN_PIN_LEN),
+                    Attributes::NumberOfCredentialsSupportedPerUser(codec) => {
+                        codec.encode(writer, CREDENTIALS_PER_USER)
+                    }
+                    Attributes::OperatingMode(codec) => {
+                        codec.encode(writer, self.operating_mode.get() as _)
+                    }
+                    Attributes::SupportedOperatingModes(codec) => {
+                        codec.encode(writer, SUPPORTED_OPERATING_MODES)
+                    }
+                    Attributes::WrongCodeEntryLimit(codec) => {
+                        codec.encode(writer, self.wrong_code_entry_limit.get())
+                    }
+                    Attributes::UserCodeTemporaryDisableTime(codec) => {
+                        codec.encode(writer, self.user_code_temporary_disable_time.get())
+                    }
+                    Attributes::RequirePINforRemoteOperation(codec) => {
+                        codec.encode(writer, self.require_pin_for_remote_operation.get())
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::OperatingMode(codec) => {
+                let mode = OperatingMode::from_repr(codec.decode(data)?)
+                    .ok_or(ErrorCode::ConstraintError)?;
+
+                if SUPPORTED_OPERATING_MODES & (1 << mode as u16) != 0 {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                self.operating_mode.set(mode);
+            }
+            Attributes::WrongCodeEntryLimit(codec) => {
+                let limit = codec.decode(data)?;
+
+                if limit == 0 {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                self.wrong_code_entry_limit.set(limit);
+            }
+            Attributes::UserCodeTemporaryDisableTime(codec) => {
+                let time = codec.decode(data)?;
+
+                if time == 0 {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                self.user_code_temporary_disable_time.set(time);
+            }
+            Attributes::RequirePINforRemoteOperation(codec) => {
+                self.require_pin_for_remote_operation
+                    .set(codec.decode(data)?);
+            }
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        let fab_idx = exchange.accessor()?.fab_idx;
+
+        let cmd_id: Commands = cmd.cmd_id.try_into()?;
+
+        // All commands changing the lock or its tables need to be part of a timed interaction
+        if !cmd.timed && !matches!(cmd_id, Commands::GetUser | Commands::GetCredentialStatus) {
+            Err(ErrorCode::NeedsTimedInteraction)?;
+        }
+
+        match cmd_id {
+            Commands::LockDoor => {
+                cmd_enter!("LockDoor");
+                let req = LockDoorReq::from_tlv(data)?;
+
+                self.operate(
+                    LockOperationType::Lock,
+                    req.pin_code.map(|pin| pin.0),
+                    fab_idx,
+                )?;
+            }
+            Commands::UnlockDoor => {
+                cmd_enter!("UnlockDoor");
+                let req = LockDoorReq::from_tlv(data)?;
+
+                self.operate(
+                    LockOperationType::Unlock,
+                    req.pin_code.map(|pin| pin.0),
+                    fab_idx,
+                )?;
+            }
+            Commands::SetUser => {
+                cmd_enter!("SetUser");
+                let req = SetUserReq::from_tlv(data)?;
+
+                self.set_user(fab_idx, &req)?;
+            }
+            Commands::GetUser => {
+                cmd_enter!("GetUser");
+                let req = UserIndexReq::from_tlv(data)?;
+
+                Self::check_user_index(req.user_index)?;
+
+                let users = self.users.borrow();
+                let user = users.iter().find(|user| user.user_index == req.user_index);
+
+                let credentials: heapless::Vec<_, { CREDENTIALS_PER_USER as usize }> = self
+                    .credentials
+                    .borrow()
+                    .iter()
+                    .filter(|credential| credential.user_index == req.user_index)
+                    .map(|credential| CredentialStruct {
+                        credential_type: CredentialType::PIN as _,
+                        credential_index: credential.credential_index,
+                    })
+                    .collect();
+
+                let next_user_index = users
+                    .iter()
+                    .map(|user| user.user_index)
+                    .filter(|index| *index > req.user_index)
+                    .min();
+
+                let resp = if let Some(user) = user {
+                    GetUserResp {
+                        user_index: req.user_index,
+                        user_name: Nullable::NotNull(UtfStr::new(user.name.as_bytes())),
+                        user_unique_id: Self::nullable(user.unique_id),
+                        user_status: Nullable::NotNull(user.status),
+                        user_type: Nullable::NotNull(user.user_type),
+                        credential_rule: Nullable::NotNull(user.credential_rule),
+                        credentials: if credentials.is_empty() {
+                            Nullable::Null
+                        } else {
+                            Nullable::NotNull(credentials.as_slice())
+                        },
+                        creator_fabric_index: Nullable::NotNull(user.creator),
+                        last_modified_fabric_index: Nullable::NotNull(user.last_modified),
+                        next_user_index: Self::nullable(next_user_index),
+                    }
+                } else {
+                    GetUserResp {
+                        user_index: req.user_index,
+                        user_name: Nullable::Null,
+                        user_unique_id: Nullable::Null,
+                        user_status: Nullable::Null,
+                        user_type: Nullable::Null,
+                        credential_rule: Nullable::Null,
+                        credentials: Nullable::Null,
+                        creator_fabric_index: Nullable::Null,
+                        last_modified_fabric_index: Nullable::Null,
+                        next_user_index: Self::nullable(next_user_index),
+                    }
+                };
+
+                encoder
+                    .with_command(RespCommands::GetUserResponse as _)?
+                    .set(resp)?;
+            }
+            Commands::ClearUser => {
+                cmd_enter!("ClearUser");
+                let req = UserIndexReq::from_tlv(data)?;
+
+                self.clear_user(req.user_index)?;
+            }
+            Commands::SetCredential => {
+                cmd_enter!("SetCredential");
+                let req = SetCredentialReq::from_tlv(data)?;
+
+                let resp = self.set_credential(fab_idx, &req);
+
+                encoder
+                    .with_command(RespCommands::SetCredentialResponse as _)?
+                    .set(resp)?;
+            }
+            Commands::GetCredentialStatus => {
+                cmd_enter!("GetCredentialStatus");
+                let req = CredentialReq::from_tlv(data)?;
+
+                let resp = self.credential_status(&req.credential)?;
+
+                encoder
+                    .with_command(RespCommands::GetCredentialStatusResponse as _)?
+                    .set(resp)?;
+            }
+            Commands::ClearCredential => {
+                cmd_enter!("ClearCredential");
+                let req = ClearCredentialReq::from_tlv(data)?;
+
+                self.clear_credential(req.credential.notnull())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn operate(
+        &self,
+        operation: LockOperationType,
+        pin: Option<&[u8]>,
+        fab_idx: u8,
+    ) -> Result<(), Error> {
+        let source = OperationSource::Remote;
+        let fab = (fab_idx != 0).then_some(fab_idx);
+
+        if self.operating_mode.get() == OperatingMode::NoRemoteLockUnlock {
+            self.operation_error(operation, source, OperationError::Restricted, None, fab);
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        let user_index = if let Some(pin) = pin {
+            Some(self.check_pin(pin, operation, source, fab)?)
+        } else if self.require_pin_for_remote_operation.get() {
+            self.operation_error(
+                operation,
+                source,
+                OperationError::InvalidCredential,
+                None,
+                fab,
+            );
+            Err(ErrorCode::Invalid)?
+        } else {
+            None
+        };
+
+        let result = if operation == LockOperationType::Lock {
+            self.actuator.lock()
+        } else {
+            self.actuator.unlock()
+        };
+
+        if let Err(e) = result {
+            warn!("Actuator failed: {:?}", e);
+            self.operation_error(
+                operation,
+                source,
+                OperationError::Unspecified,
+                user_index,
+                fab,
+            );
+            return Err(e);
+        }
+
+        self.set_lock_state(Some(if operation == LockOperationType::Lock {
+            LockState::Locked
+        } else {
+            LockState::Unlocked
+        }));
+
+        self.event(&DoorLockEvent::LockOperation {
+            operation,
+            source,
+            user_index,
+            fab_idx: fab,
+        });
+
+        Ok(())
+    }
+
+    fn check_pin(
+        &self,
+        pin: &[u8],
+        operation: LockOperationType,
+        source: OperationSource,
+        fab: Option<u8>,
+    ) -> Result<u16, Error> {
+        if let Some(until) = self.disabled_until.get() {
+            if Instant::now() < until {
+                info!("PIN entry is temporarily disabled");
+                self.operation_error(operation, source, OperationError::Restricted, None, fab);
+                Err(ErrorCode::InvalidState)?;
+            }
+
+            self.disabled_until.set(None);
+        }
+
+        let user_index = self
+            .credentials
+            .borrow()
+            .iter()
+            .find(|credential| credential.data.as_slice() == pin)
+            .map(|credential| credential.user_index);
+
+        let user_index = match user_index {
+            Some(user_index) => user_index,
+            None => {
+                self.wrong_code_entry();
+                self.operation_error(
+                    operation,
+                    source,
+                    OperationError::InvalidCredential,
+                    None,
+                    fab,
+                );
+                Err(ErrorCode::Invalid)?
+            }
+        };
+
+        self.wrong_code_entries.set(0);
+
+        let enabled = self.users.borrow().iter().any(|user| {
+            user.user_index == user_index && user.status == UserStatus::OccupiedEnabled as u8
+        });
+
+        if !enabled {
+            self.operation_error(
+                operation,
+                source,
+                OperationError::DisabledUserDenied,
+                Some(user_index),
+                fab,
+            );
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        Ok(user_index)
+    }
+
+    fn wrong_code_entry(&self) {
+        let entries = self.wrong_code_entries.get() + 1;
+
+        if entries >= self.wrong_code_entry_limit.get() {
+            let time = self.user_code_temporary_disable_time.get();
+
+            info!(
+                "Wrong code entry limit reached, disabling PIN entry for {}s",
+                time
+            );
+
+            self.wrong_code_entries.set(0);
+            self.disabled_until
+                .set(Some(Instant::now() + Duration::from_secs(time as _)));
+
+            self.event(&DoorLockEvent::DoorLockAlarm(
+                AlarmCode::WrongCodeEntryLimit,
+            ));
+        } else {
+            self.wrong_code_entries.set(entries);
+        }
+    }
+
+    fn operation_error(
+        &self,
+        operation: LockOperationType,
+        source: OperationSource,
+        error: OperationError,
+        user_index: Option<u16>,
+        fab_idx: Option<u8>,
+    ) {
+        self.event(&DoorLockEvent::LockOperationError {
+            operation,
+            source,
+            error,
+            user_index,
+            fab_idx,
+        });
+    }
+
+    fn event(&self, event: &DoorLockEvent) {
+        let mut events = self.events.borrow_mut();
+
+        let result = match *event {
+            DoorLockEvent::DoorLockAlarm(alarm_code) => events.push(
+                self.endpoint_id,
+                ID,
+                Events::DoorLockAlarm as _,
+                EventPriority::Critical,
+                None,
+                &DoorLockAlarmEvent {
+                    alarm_code: alarm_code as _,
+                },
+            ),
+            DoorLockEvent::LockOperation {
+                operation,
+                source,
+                user_index,
+                fab_idx,
+            } => events.push(
+                self.endpoint_id,
+                ID,
+                Events::LockOperation as _,
+                EventPriority::Info,
+                None,
+                &LockOperationEvent {
+                    lock_operation_type: operation as _,
+                    operation_source: source as _,
+                    user_index: Self::nullable(user_index),
+                    fabric_index: Self::nullable(fab_idx),
+                    source_node: Nullable::Null,
+                },
+            ),
+            DoorLockEvent::LockOperationError {
+                operation,
+                source,
+                error,
+                user_index,
+                fab_idx,
+            } => events.push(
+                self.endpoint_id,
+                ID,
+                Events::LockOperationError as _,
+                EventPriority::Info,
+                None,
+                &LockOperationErrorEvent {
+                    lock_operation_type: operation as _,
+                    operation_source: source as _,
+                    operation_error: error as _,
+                    user_index: Self::nullable(user_index),
+                    fabric_index: Self::nullable(fab_idx),
+                    source_node: Nullable::Null,
+                },
+            ),
+        };
+
+        // An event which cannot be recorded does not fail the operation it reports
+        if let Err(e) = result {
+            warn!("Failed to record event {:?}: {:?}", event, e);
+        }
+
+        drop(events);
+
+        self.actuator.event(event);
+    }
+
+    fn set_user(&self, fab_idx: u8, req: &SetUserReq) -> Result<(), Error> {
+        Self::check_user_index(req.user_index)?;
+
+        let operation =
+            DataOperationType::from_repr(req.operation_type).ok_or(ErrorCode::InvalidCommand)?;
+
+        let status = match req.user_status.as_ref().notnull() {
+            Some(status) => match UserStatus::from_repr(*status) {
+                Some(UserStatus::OccupiedEnabled) | Some(UserStatus::OccupiedDisabled) => {
+                    Some(*status)
+                }
+                _ => Err(ErrorCode::InvalidCommand)?,
+            },
+            None => None,
+        };
+
+        let user_type = match req.user_type.as_ref().notnull() {
+            Some(user_type) => {
+                Some(UserType::from_repr(*user_type).ok_or(ErrorCode::InvalidCommand)? as u8)
+            }
+            None => None,
+        };
+
+        let credential_rule = match req.credential_rule.as_ref().notnull() {
+            // Only single credentials can be validated
+            Some(rule) if *rule == CredentialRule::Single as u8 => Some(*rule),
+            Some(_) => Err(ErrorCode::InvalidCommand)?,
+            None => None,
+        };
+
+        let name = match req.user_name.as_ref().notnull() {
+            Some(name) => {
+                let mut user_name = heapless::String::<MAX_USER_NAME_LEN>::new();
+                user_name
+                    .push_str(name.as_str()?)
+                    .map_err(|_| ErrorCode::InvalidCommand)?;
+
+                Some(user_name)
+            }
+            None => None,
+        };
+
+        let unique_id = req.user_unique_id.as_ref().notnull().copied();
+
+        let mut users = self.users.borrow_mut();
+        let existing = users
+            .iter_mut()
+            .find(|user| user.user_index == req.user_index);
+
+        match operation {
+            DataOperationType::Add => {
+                if existing.is_some() {
+                    // Reported as FAILURE, as there is no cluster-specific status yet
+                    Err(ErrorCode::Duplicate)?;
+                }
+
+                users
+                    .push(User {
+                        user_index: req.user_index,
+                        name: name.unwrap_or_default(),
+                        unique_id,
+                        status: status.unwrap_or(UserStatus::OccupiedEnabled as _),
+                        user_type: user_type.unwrap_or(UserType::UnrestrictedUser as _),
+                        credential_rule: credential_rule.unwrap_or(CredentialRule::Single as _),
+                        creator: fab_idx,
+                        last_modified: fab_idx,
+                    })
+                    .map_err(|_| ErrorCode::ResourceExhausted)?;
+            }
+            DataOperationType::Modify => {
+                let user = existing.ok_or(ErrorCode::InvalidCommand)?;
+
+                if let Some(name) = name {
+                    user.name = name;
+                }
+                if unique_id.is_some() {
+                    user.unique_id = unique_id;
+                }
+                if let Some(status) = status {
+                    user.status = status;
+                }
+                if let Some(user_type) = user_type {
+                    user.user_type = user_type;
+                }
+                if let Some(credential_rule) = credential_rule {
+                    user.credential_rule = credential_rule;
+                }
+
+                user.last_modified = fab_idx;
+            }
+            DataOperationType::Clear => Err(ErrorCode::InvalidCommand)?,
+        }
+
+        self.changed.set(true);
+
+        Ok(())
+    }
+
+    fn clear_user(&self, user_index: u16) -> Result<(), Error> {
+        if user_index == ALL_USERS {
+            self.users.borrow_mut().clear();
+            self.credentials.borrow_mut().clear();
+        } else {
+            Self::check_user_index(user_index)?;
+
+            self.users
+                .borrow_mut()
+                .retain(|user| user.user_index != user_index);
+            self.credentials
+                .borrow_mut()
+                .retain(|credential| credential.user_index != user_index);
+        }
+
+        self.changed.set(true);
+
+        Ok(())
+    }
+
+    fn set_credential(&self, fab_idx: u8, req: &SetCredentialReq) -> SetCredentialResp {
+        let index = req.credential.credential_index;
+
+        let (status, user_index) = match self.do_set_credential(fab_idx, req) {
+            Ok(user_index) => (DlStatus::Success, user_index),
+            Err(status) => (status, None),
+        };
+
+        let next_credential_index = (index + 1..=NUM_PIN_CREDENTIALS).find(|index| {
+            !self
+                .credentials
+                .borrow()
+                .iter()
+                .any(|credential| credential.credential_index == *index)
+        });
+
+        SetCredentialResp {
+            status: status as _,
+            user_index: Self::nullable(user_index),
+            next_credential_index: Self::nullable(next_credential_index),
+        }
+    }
+
+    /// Add or modify a credential, returning the index of the user created for it, if any
+    fn do_set_credential(
+        &self,
+        fab_idx: u8,
+        req: &SetCredentialReq,
+    ) -> Result<Option<u16>, DlStatus> {
+        let index = req.credential.credential_index;
+        let pin = req.credential_data.0;
+
+        if req.credential.credential_type != CredentialType::PIN as u8
+            || !(1..=NUM_PIN_CREDENTIALS).contains(&index)
+            || !(MIN_PIN_LEN as usize..=MAX_PIN_LEN as usize).contains(&pin.len())
+        {
+            Err(DlStatus::InvalidField)?;
+        }
+
+        let operation =
+            DataOperationType::from_repr(req.operation_type).ok_or(DlStatus::InvalidField)?;
+
+        let mut credentials = self.credentials.borrow_mut();
+
+        if credentials.iter().any(|credential| {
+            credential.credential_index != index && credential.data.as_slice() == pin
+        }) {
+            Err(DlStatus::Duplicate)?;
+        }
+
+        let position = credentials
+            .iter()
+            .position(|credential| credential.credential_index == index);
+
+        let mut created = None;
+
+        match operation {
+            DataOperationType::Add => {
+                if position.is_some() {
+                    Err(DlStatus::Occupied)?;
+                }
+
+                let mut users = self.users.borrow_mut();
+
+                let user_index = match req.user_index.as_ref().notnull() {
+                    Some(user_index) => {
+                        if !(1..=NUM_USERS).contains(user_index) {
+                            Err(DlStatus::InvalidField)?;
+                        }
+
+                        *user_index
+                    }
+                    None => (1..=NUM_USERS)
+                        .find(|index| !users.iter().any(|user| user.user_index == *index))
+                        .ok_or(DlStatus::ResourceExhausted)?,
+                };
+
+                if !users.iter().any(|user| user.user_index == user_index) {
+                    let status = match req.user_status.as_ref().notnull() {
+                        Some(status) => *status,
+                        None => UserStatus::OccupiedEnabled as _,
+                    };
+                    let user_type = match req.user_type.as_ref().notnull() {
+                        Some(user_type) => *user_type,
+                        None => UserType::UnrestrictedUser as _,
+                    };
+
+                    users
+                        .push(User {
+                            user_index,
+                            name: heapless::String::new(),
+                            unique_id: None,
+                            status,
+                            user_type,
+                            credential_rule: CredentialRule::Single as _,
+                            creator: fab_idx,
+                            last_modified: fab_idx,
+                        })
+                        .map_err(|_| DlStatus::ResourceExhausted)?;
+
+                    created = Some(user_index);
+                } else if credentials
+                    .iter()
+                    .filter(|credential| credential.user_index == user_index)
+                    .count()
+                    >= CREDENTIALS_PER_USER as usize
+                {
+                    Err(DlStatus::ResourceExhausted)?;
+                }
+
+                credentials
+                    .push(Credential {
+                        credential_index: index,
+                        data: heapless::Vec::from_slice(pin).unwrap(),
+                        user_index,
+                        creator: fab_idx,
+                        last_modified: fab_idx,
+                    })
+                    .map_err(|_| DlStatus::ResourceExhausted)?;
+            }
+            DataOperationType::Modify => {
+                let credential = &mut credentials[position.ok_or(DlStatus::InvalidField)?];
+
+                if req.user_index.as_ref().notnull() != Some(&credential.user_index) {
+                    Err(DlStatus::InvalidField)?;
+                }
+
+                credential.data = heapless::Vec::from_slice(pin).unwrap();
+                credential.last_modified = fab_idx;
+            }
+            DataOperationType::Clear => Err(DlStatus::InvalidField)?,
+        }
+
+        self.changed.set(true);
+
+        Ok(created)
+    }
+
+    fn credential_status(
+        &self,
+        credential: &CredentialStruct,
+    ) -> Result<CredentialStatusResp, Error> {
+        let index = credential.credential_index;
+
+        if credential.credential_type != CredentialType::PIN as u8
+            || !(1..=NUM_PIN_CREDENTIALS).contains(&index)
+        {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        let credentials = self.credentials.borrow();
+
+        let next_credential_index = credentials
+            .iter()
+            .map(|credential| credential.credential_index)
+            .filter(|other| *other > index)
+            .min();
+
+        let resp = if let Some(credential) = credentials
+            .iter()
+            .find(|credential| credential.credential_index == index)
+        {
+            CredentialStatusResp {
+                credential_exists: true,
+                user_index: Nullable::NotNull(credential.user_index),
+                creator_fabric_index: Nullable::NotNull(credential.creator),
+                last_modified_fabric_index: Nullable::NotNull(credential.last_modified),
+                next_credential_index: Self::nullable(next_credential_index),
+            }
+        } else {
+            CredentialStatusResp {
+                credential_exists: false,
+                user_index: Nullable::Null,
+                creator_fabric_index: Nullable::Null,
+                last_modified_fabric_index: Nullable::Null,
+                next_credential_index: Self::nullable(next_credential_index),
+            }
+        };
+
+        Ok(resp)
+    }
+
+    /// Clear one or all PIN credentials, as well as the users left without any credential
+    fn clear_credential(&self, credential: Option<CredentialStruct>) -> Result<(), Error> {
+        let mut credentials = self.credentials.borrow_mut();
+
+        if let Some(credential) = credential {
+            let index = credential.credential_index;
+
+            if credential.credential_type != CredentialType::PIN as u8
+                || !(1..=NUM_PIN_CREDENTIALS).contains(&index)
+            {
+                Err(ErrorCode::InvalidCommand)?;
+            }
+
+            credentials.retain(|credential| credential.credential_index != index);
+        } else {
+            credentials.clear();
+        }
+
+        self.users.borrow_mut().retain(|user| {
+            credentials
+                .iter()
+                .any(|credential| credential.user_index == user.user_index)
+        });
+
+        self.changed.set(true);
+
+        Ok(())
+    }
+
+    fn check_user_index(user_index: u16) -> Result<(), Error> {
+        if (1..=NUM_USERS).contains(&user_index) {
+            Ok(())
+        } else {
+            Err(ErrorCode::InvalidCommand.into())
+        }
+    }
+
+    fn nullable<V>(value: Option<V>) -> Nullable<V> {
+        match value {
+            Some(value) => Nullable::NotNull(value),
+            None => Nullable::Null,
+        }
+    }
+}
+
+impl<'a, T> Handler for DoorLockCluster<'a, T>
+where
+    T: LockActuator,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        DoorLockCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        DoorLockCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        DoorLockCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        DoorLockCluster::remove_fabric(self, fab_idx)
+    }
+}
+
+impl<'a, T> NonBlockingHandler for DoorLockCluster<'a, T> where T: LockActuator {}
+
+/// Persists the user and credential tables, which are not exposed as attributes
+impl<'a, T> StatePersist for DoorLockCluster<'a, T> {
+    fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        tlv::from_tlv(&mut *self.users.borrow_mut(), &root.find_tag(0)?)?;
+        tlv::from_tlv(&mut *self.credentials.borrow_mut(), &root.find_tag(1)?)?;
+
+        self.changed.set(false);
+
+        Ok(())
+    }
+
+    fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        if self.changed.get() {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            tw.start_struct(TagType::Anonymous)?;
+            self.users
+                .borrow()
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Context(0))?;
+            self.credentials
+                .borrow()
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Context(1))?;
+            tw.end_container()?;
+
+            self.changed.set(false);
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a, T> ChangeNotifier<()> for DoorLockCluster<'a, T> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::{Cell, RefCell};
+
+    use crate::{
+        data_model::persist::StatePersist,
+        error::{Error, ErrorCode},
+        interaction_model::events::EventLog,
+        tlv::{Nullable, OctetStr, UtfStr},
+        utils::{epoch::dummy_epoch, rand::dummy_rand},
+    };
+
+    use super::{
+        AlarmCode, CredentialStruct, CredentialType, DlStatus, DoorLockCluster, DoorLockEvent,
+        LockActuator, LockOperationType, LockState, LockType, OperationError, SetCredentialReq,
+        SetUserReq, UserStatus,
+    };
+
+    const FAB_IDX: u8 = 1;
+
+    struct Lock {
+        locked: Cell<bool>,
+        jammed: Cell<bool>,
+        events: RefCell<heapless::Vec<DoorLockEvent, 8>>,
+    }
+
+    impl Lock {
+        fn new() -> Self {
+            Self {
+                locked: Cell::new(false),
+                jammed: Cell::new(false),
+                events: RefCell::new(heapless::Vec::new()),
+            }
+        }
+
+        fn last_event(&self) -> Option<DoorLockEvent> {
+            self.events.borrow().last().copied()
+        }
+    }
+
+    impl LockActuator for Lock {
+        fn lock(&self) -> Result<(), Error> {
+            if self.jammed.get() {
+                Err(ErrorCode::Busy)?;
+            }
+
+            self.locked.set(true);
+
+            Ok(())
+        }
+
+        fn unlock(&self) -> Result<(), Error> {
+            self.locked.set(false);
+
+            Ok(())
+        }
+
+        fn event(&self, event: &DoorLockEvent) {
+            self.events.borrow_mut().push(*event).unwrap();
+        }
+    }
+
+    fn pin_req(operation_type: u8, credential_index: u16, pin: &[u8]) -> SetCredentialReq<'_> {
+        SetCredentialReq {
+            operation_type,
+            credential: CredentialStruct {
+                credential_type: CredentialType::PIN as _,
+                credential_index,
+            },
+            credential_data: OctetStr(pin),
+            user_index: Nullable::Null,
+            user_status: Nullable::Null,
+            user_type: Nullable::Null,
+        }
+    }
+
+    #[test]
+    fn test_pin_credentials() {
+        let lock = Lock::new();
+        let events = RefCell::new(EventLog::new(dummy_epoch));
+        let cluster = DoorLockCluster::new(1, LockType::DeadBolt, &lock, &events, dummy_rand);
+
+        // Adding a credential without a user creates the user
+        let resp = cluster.set_credential(FAB_IDX, &pin_req(0, 1, b"1234"));
+        assert_eq!(resp.status, DlStatus::Success as u8);
+        assert_eq!(resp.user_index, Nullable::NotNull(1));
+        assert_eq!(resp.next_credential_index, Nullable::NotNull(2));
+
+        assert_eq!(
+            cluster
+                .set_credential(FAB_IDX, &pin_req(0, 1, b"5678"))
+                .status,
+            DlStatus::Occupied as u8
+        );
+        assert_eq!(
+            cluster
+                .set_credential(FAB_IDX, &pin_req(0, 2, b"1234"))
+                .status,
+            DlStatus::Duplicate as u8
+        );
+        assert_eq!(
+            cluster
+                .set_credential(FAB_IDX, &pin_req(0, 2, b"12"))
+                .status,
+            DlStatus::InvalidField as u8
+        );
+
+        let status = cluster
+            .credential_status(&CredentialStruct {
+                credential_type: CredentialType::PIN as _,
+                credential_index: 1,
+            })
+            .unwrap();
+        assert!(status.credential_exists);
+        assert_eq!(status.user_index, Nullable::NotNull(1));
+        assert_eq!(status.creator_fabric_index, Nullable::NotNull(FAB_IDX));
+
+        // Clearing the last credential of a user clears the user too
+        cluster
+            .clear_credential(Some(CredentialStruct {
+                credential_type: CredentialType::PIN as _,
+                credential_index: 1,
+            }))
+            .unwrap();
+        assert!(cluster.users.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_set_user() {
+        let lock = Lock::new();
+        let events = RefCell::new(EventLog::new(dummy_epoch));
+        let cluster = DoorLockCluster::new(1, LockType::DeadBolt, &lock, &events, dummy_rand);
+
+        let mut req = SetUserReq {
+            operation_type: 0,
+            user_index: 3,
+            user_name: Nullable::NotNull(UtfStr::new(b"alice")),
+            user_unique_id: Nullable::Null,
+            user_status: Nullable::Null,
+            user_type: Nullable::Null,
+            credential_rule: Nullable::Null,
+        };
+
+        cluster.set_user(FAB_IDX, &req).unwrap();
+        assert_eq!(
+            cluster.set_user(FAB_IDX, &req).map_err(|e| e.code()),
+            Err(ErrorCode::Duplicate)
+        );
+
+        req.operation_type = 2;
+        req.user_name = Nullable::Null;
+        req.user_status = Nullable::NotNull(UserStatus::OccupiedDisabled as _);
+        cluster.set_user(2, &req).unwrap();
+
+        {
+            let users = cluster.users.borrow();
+            assert_eq!(users[0].name.as_str(), "alice");
+            assert_eq!(users[0].status, UserStatus::OccupiedDisabled as u8);
+            assert_eq!(users[0].last_modified, 2);
+        }
+
+        req.user_index = 0;
+        assert_eq!(
+            cluster.set_user(FAB_IDX, &req).map_err(|e| e.code()),
+            Err(ErrorCode::InvalidCommand)
+        );
+    }
+
+    #[test]
+    fn test_lock_with_pin() {
+        let lock = Lock::new();
+        let events = RefCell::new(EventLog::new(dummy_epoch));
+        let cluster = DoorLockCluster::new(1, LockType::DeadBolt, &lock, &events, dummy_rand);
+
+        cluster.set_credential(FAB_IDX, &pin_req(0, 1, b"1234"));
+
+        cluster
+            .operate(LockOperationType::Lock, None, FAB_IDX)
+            .unwrap();
+        assert!(lock.locked.get());
+        assert_eq!(cluster.lock_state(), Some(LockState::Locked));
+
+        cluster.require_pin_for_remote_operation.set(true);
+        assert!(cluster
+            .operate(LockOperationType::Unlock, None, FAB_IDX)
+            .is_err());
+
+        cluster
+            .operate(LockOperationType::Unlock, Some(&b"1234"[..]), FAB_IDX)
+            .unwrap();
+        assert!(!lock.locked.get());
+        assert_eq!(
+            lock.last_event(),
+            Some(DoorLockEvent::LockOperation {
+                operation: LockOperationType::Unlock,
+                source: super::OperationSource::Remote,
+                user_index: Some(1),
+                fab_idx: Some(FAB_IDX),
+            })
+        );
+
+        lock.jammed.set(true);
+        assert!(cluster
+            .operate(LockOperationType::Lock, Some(&b"1234"[..]), FAB_IDX)
+            .is_err());
+        assert_eq!(cluster.lock_state(), Some(LockState::Unlocked));
+    }
+
+    #[test]
+    fn test_wrong_code_lockout() {
+        let lock = Lock::new();
+        let events = RefCell::new(EventLog::new(dummy_epoch));
+        let cluster = DoorLockCluster::new(1, LockType::DeadBolt, &lock, &events, dummy_rand);
+
+        cluster.set_credential(FAB_IDX, &pin_req(0, 1, b"1234"));
+        cluster.wrong_code_entry_limit.set(2);
+
+        assert!(cluster
+            .operate(LockOperationType::Unlock, Some(&b"0000"[..]), FAB_IDX)
+            .is_err());
+        assert!(matches!(
+            lock.last_event(),
+            Some(DoorLockEvent::LockOperationError {
+                error: OperationError::InvalidCredential,
+                ..
+            })
+        ));
+
+        assert!(cluster
+            .verify_pin(b"0000", LockOperationType::Unlock)
+            .is_err());
+        assert!(lock.events.borrow().contains(&DoorLockEvent::DoorLockAlarm(
+            AlarmCode::WrongCodeEntryLimit
+        )));
+
+        // Even the right code is refused until the disable time elapses
+        assert!(cluster
+            .operate(LockOperationType::Unlock, Some(&b"1234"[..]), FAB_IDX)
+            .is_err());
+        assert!(matches!(
+            lock.last_event(),
+            Some(DoorLockEvent::LockOperationError {
+                error: OperationError::Restricted,
+                ..
+            })
+        ));
+
+        cluster.disabled_until.set(None);
+        assert_eq!(
+            cluster
+                .verify_pin(b"1234", LockOperationType::Unlock)
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_store_load() {
+        let lock = Lock::new();
+        let events = RefCell::new(EventLog::new(dummy_epoch));
+        let cluster = DoorLockCluster::new(1, LockType::DeadBolt, &lock, &events, dummy_rand);
+
+        let mut buf = [0; 512];
+        assert!(cluster.store(&mut buf).unwrap().is_none());
+
+        cluster.set_credential(FAB_IDX, &pin_req(0, 4, b"87654321"));
+
+        let data = cluster.store(&mut buf).unwrap().unwrap();
+
+        let restored = DoorLockCluster::new(1, LockType::DeadBolt, &lock, &events, dummy_rand);
+        restored.load(data).unwrap();
+
+        assert!(restored.store(&mut buf).unwrap().is_none());
+        assert_eq!(
+            restored
+                .verify_pin(b"87654321", LockOperationType::Unlock)
+                .unwrap(),
+            1
+        );
+    }
+}
@@ -0,0 +1,544 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::objects::*;
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, Nullable, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0202;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    FanMode(AttrType<u8>) = 0x0,
+    FanModeSequence(AttrType<u8>) = 0x1,
+    PercentSetting(AttrType<Nullable<u8>>) = 0x2,
+    PercentCurrent(AttrType<u8>) = 0x3,
+    SpeedMax(AttrType<u8>) = 0x4,
+    SpeedSetting(AttrType<Nullable<u8>>) = 0x5,
+    SpeedCurrent(AttrType<u8>) = 0x6,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    Step = 0x0,
+}
+
+command_enum!(Commands);
+
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    MultiSpeed = 0x01,
+    Auto = 0x02,
+    Step = 0x10,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: Feature::MultiSpeed as u32 | Feature::Auto as u32 | Feature::Step as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::FanMode as u16,
+            Access::RWVO,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::FanModeSequence as u16,
+            Access::RWVO,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PercentSetting as u16,
+            Access::RWVO,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PercentCurrent as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SpeedMax as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SpeedSetting as u16,
+            Access::RWVO,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SpeedCurrent as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+    ],
+    commands: &[CommandsDiscriminants::Step as _],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum FanMode {
+    Off = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+    /// Deprecated, same as `High`
+    On = 4,
+    Auto = 5,
+    Smart = 6,
+}
+
+/// The fan modes a client may choose from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum FanModeSequence {
+    OffLowMedHigh = 0,
+    OffLowHigh = 1,
+    OffLowMedHighAuto = 2,
+    OffLowHighAuto = 3,
+    OffHighAuto = 4,
+    OffHigh = 5,
+}
+
+impl FanModeSequence {
+    fn supports(&self, mode: FanMode) -> bool {
+        match mode {
+            FanMode::Off | FanMode::High | FanMode::On => true,
+            FanMode::Low => !matches!(self, Self::OffHighAuto | Self::OffHigh),
+            FanMode::Medium => matches!(self, Self::OffLowMedHigh | Self::OffLowMedHighAuto),
+            FanMode::Auto => matches!(
+                self,
+                Self::OffLowMedHighAuto | Self::OffLowHighAuto | Self::OffHighAuto
+            ),
+            FanMode::Smart => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum StepDirection {
+    Increase = 0,
+    Decrease = 1,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct StepReq {
+    pub direction: u8,
+    pub wrap: Option<bool>,
+    pub lowest_off: Option<bool>,
+}
+
+/// The application side of the Fan Control cluster
+///
+/// Called whenever the setting of the fan changes, with `percent_setting` being `None`
+/// when the fan is left to control its speed by itself, as in `FanMode::Auto`.
+pub trait FanMotor {
+    fn set_speed(&self, mode: FanMode, percent_setting: Option<u8>);
+}
+
+impl<T> FanMotor for &T
+where
+    T: FanMotor,
+{
+    fn set_speed(&self, mode: FanMode, percent_setting: Option<u8>) {
+        (**self).set_speed(mode, percent_setting)
+    }
+}
+
+pub struct FanControlCluster<T> {
+    data_ver: Dataver,
+    motor: T,
+    speed_max: u8,
+    fan_mode: Cell<FanMode>,
+    fan_mode_sequence: Cell<FanModeSequence>,
+    speed_setting: Cell<Option<u8>>,
+    percent_current: Cell<u8>,
+    speed_current: Cell<u8>,
+}
+
+impl<T> FanControlCluster<T>
+where
+    T: FanMotor,
+{
+    /// Create the cluster for a fan with `speed_max` speeds, from 1 to 100
+    pub fn new(motor: T, speed_max: u8, sequence: FanModeSequence, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            motor,
+            speed_max: speed_max.clamp(1, 100),
+            fan_mode: Cell::new(FanMode::Off),
+            fan_mode_sequence: Cell::new(sequence),
+            speed_setting: Cell::new(Some(0)),
+            percent_current: Cell::new(0),
+            speed_current: Cell::new(0),
+        }
+    }
+
+    pub fn fan_mode(&self) -> FanMode {
+        self.fan_mode.get()
+    }
+
+    /// The requested percentage of the maximum speed, `None` in `FanMode::Auto`
+    pub fn percent_setting(&self) -> Option<u8> {
+        self.speed_setting.get().map(|speed| self.percent(speed))
+    }
+
+    /// Update the actual speed of the fan, as a percentage of its maximum speed
+    pub fn set_percent_current(&self, percent: u8) {
+        let percent = percent.min(100);
+        let speed = self.speed(percent);
+
+        let previous_percent = self.percent_current.replace(percent);
+        let previous_speed = self.speed_current.replace(speed);
+
+        if previous_percent != percent || previous_speed != speed {
+            self.data_ver.changed();
+        }
+    }
+
+    pub fn set_fan_mode(&self, mode: FanMode) -> Result<(), Error> {
+        let mode = if mode == FanMode::On {
+            FanMode::High
+        } else {
+            mode
+        };
+
+        if !self.fan_mode_sequence.get().supports(mode) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let speed = match mode {
+            FanMode::Off => Some(0),
+            FanMode::Low => Some((self.speed_max / 3).max(1)),
+            FanMode::Medium => Some((self.speed_max as u16 * 2 / 3).max(1) as u8),
+            FanMode::High => Some(self.speed_max),
+            _ => None,
+        };
+
+        self.apply(mode, speed);
+
+        Ok(())
+    }
+
+    /// Set the speed of the fan, switching it to the matching fan mode
+    pub fn set_speed_setting(&self, speed: u8) -> Result<(), Error> {
+        if speed > self.speed_max {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        self.apply(self.mode(speed), Some(speed));
+
+        Ok(())
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::FanMode(codec) => codec.encode(writer, self.fan_mode.get() as _),
+                    Attributes::FanModeSequence(codec) => {
+                        codec.encode(writer, self.fan_mode_sequence.get() as _)
+                    }
+                    Attributes::PercentSetting(codec) => {
+                        codec.encode(writer, Self::nullable(self.percent_setting()))
+                    }
+                    Attributes::PercentCurrent(codec) => {
+                        codec.encode(writer, self.percent_current.get())
+                    }
+                    Attributes::SpeedMax(codec) => codec.encode(writer, self.speed_max),
+                    Attributes::SpeedSetting(codec) => {
+                        codec.encode(writer, Self::nullable(self.speed_setting.get()))
+                    }
+                    Attributes::SpeedCurrent(codec) => {
+                        codec.encode(writer, self.speed_current.get())
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::FanMode(codec) => self.set_fan_mode(
+                FanMode::from_repr(codec.decode(data)?).ok_or(ErrorCode::ConstraintError)?,
+            )?,
+            Attributes::FanModeSequence(codec) => {
+                let sequence = FanModeSequence::from_repr(codec.decode(data)?)
+                    .ok_or(ErrorCode::ConstraintError)?;
+
+                self.fan_mode_sequence.set(sequence);
+                self.data_ver.changed();
+            }
+            Attributes::PercentSetting(codec) => match codec.decode(data)? {
+                Nullable::NotNull(percent) if percent <= 100 => {
+                    self.set_speed_setting(self.speed(percent))?
+                }
+                _ => Err(ErrorCode::ConstraintError)?,
+            },
+            Attributes::SpeedSetting(codec) => match codec.decode(data)? {
+                Nullable::NotNull(speed) => self.set_speed_setting(speed)?,
+                Nullable::Null => Err(ErrorCode::ConstraintError)?,
+            },
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        _encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::Step => {
+                cmd_enter!("Step");
+                let req = StepReq::from_tlv(data)?;
+
+                let direction =
+                    StepDirection::from_repr(req.direction).ok_or(ErrorCode::ConstraintError)?;
+
+                self.step(
+                    direction,
+                    req.wrap.unwrap_or(false),
+                    req.lowest_off.unwrap_or(true),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn step(&self, direction: StepDirection, wrap: bool, lowest_off: bool) -> Result<(), Error> {
+        let lowest = if lowest_off { 0 } else { 1 };
+
+        // Stepping from Auto starts from the current speed
+        let speed = self.speed_setting.get().unwrap_or(self.speed_current.get());
+
+        let speed = match direction {
+            StepDirection::Increase if speed >= self.speed_max => {
+                if wrap {
+                    lowest
+                } else {
+                    self.speed_max
+                }
+            }
+            StepDirection::Increase => (speed + 1).max(lowest),
+            StepDirection::Decrease if speed <= lowest => {
+                if wrap {
+                    self.speed_max
+                } else {
+                    lowest
+                }
+            }
+            StepDirection::Decrease => speed - 1,
+        };
+
+        info!("Stepping to speed {}", speed);
+
+        self.set_speed_setting(speed)
+    }
+
+    fn apply(&self, mode: FanMode, speed: Option<u8>) {
+        let previous_mode = self.fan_mode.replace(mode);
+        let previous_speed = self.speed_setting.replace(speed);
+
+        if previous_mode != mode || previous_speed != speed {
+            self.data_ver.changed();
+        }
+
+        self.motor
+            .set_speed(mode, speed.map(|speed| self.percent(speed)));
+    }
+
+    /// The fan mode matching a speed, amongst the modes of the fan mode sequence
+    fn mode(&self, speed: u8) -> FanMode {
+        let sequence = self.fan_mode_sequence.get();
+        let speed = speed as u16;
+        let max = self.speed_max as u16;
+
+        if speed == 0 {
+            FanMode::Off
+        } else if !sequence.supports(FanMode::Low) {
+            FanMode::High
+        } else if sequence.supports(FanMode::Medium) {
+            if speed * 3 <= max {
+                FanMode::Low
+            } else if speed * 3 <= max * 2 {
+                FanMode::Medium
+            } else {
+                FanMode::High
+            }
+        } else if speed * 2 <= max {
+            FanMode::Low
+        } else {
+            FanMode::High
+        }
+    }
+
+    fn percent(&self, speed: u8) -> u8 {
+        (speed as u16 * 100 / self.speed_max as u16) as _
+    }
+
+    fn speed(&self, percent: u8) -> u8 {
+        ((percent as u16 * self.speed_max as u16 + 99) / 100) as _
+    }
+
+    fn nullable(value: Option<u8>) -> Nullable<u8> {
+        match value {
+            Some(value) => Nullable::NotNull(value),
+            None => Nullable::Null,
+        }
+    }
+}
+
+impl<T> Handler for FanControlCluster<T>
+where
+    T: FanMotor,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        FanControlCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        FanControlCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        FanControlCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<T> NonBlockingHandler for FanControlCluster<T> where T: FanMotor {}
+
+impl<T> ChangeNotifier<()> for FanControlCluster<T> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::utils::rand::dummy_rand;
+
+    use super::{FanControlCluster, FanMode, FanModeSequence, FanMotor, StepDirection};
+
+    struct Motor(Cell<Option<(FanMode, Option<u8>)>>);
+
+    impl FanMotor for Motor {
+        fn set_speed(&self, mode: FanMode, percent_setting: Option<u8>) {
+            self.0.set(Some((mode, percent_setting)));
+        }
+    }
+
+    #[test]
+    fn test_fan_mode() {
+        let motor = Motor(Cell::new(None));
+        let fan =
+            FanControlCluster::new(&motor, 10, FanModeSequence::OffLowMedHighAuto, dummy_rand);
+
+        fan.set_fan_mode(FanMode::Medium).unwrap();
+        assert_eq!(motor.0.get(), Some((FanMode::Medium, Some(60))));
+
+        fan.set_fan_mode(FanMode::Auto).unwrap();
+        assert_eq!(fan.percent_setting(), None);
+        assert_eq!(motor.0.get(), Some((FanMode::Auto, None)));
+
+        // On is the same as High
+        fan.set_fan_mode(FanMode::On).unwrap();
+        assert_eq!(fan.fan_mode(), FanMode::High);
+        assert_eq!(fan.percent_setting(), Some(100));
+
+        let fan = FanControlCluster::new(&motor, 10, FanModeSequence::OffLowHigh, dummy_rand);
+        assert!(fan.set_fan_mode(FanMode::Medium).is_err());
+        assert!(fan.set_fan_mode(FanMode::Auto).is_err());
+    }
+
+    #[test]
+    fn test_speed_setting() {
+        let motor = Motor(Cell::new(None));
+        let fan = FanControlCluster::new(&motor, 10, FanModeSequence::OffLowMedHigh, dummy_rand);
+
+        fan.set_speed_setting(2).unwrap();
+        assert_eq!(fan.fan_mode(), FanMode::Low);
+        assert_eq!(fan.percent_setting(), Some(20));
+
+        fan.set_speed_setting(5).unwrap();
+        assert_eq!(fan.fan_mode(), FanMode::Medium);
+
+        fan.set_speed_setting(0).unwrap();
+        assert_eq!(fan.fan_mode(), FanMode::Off);
+
+        assert!(fan.set_speed_setting(11).is_err());
+    }
+
+    #[test]
+    fn test_step() {
+        let motor = Motor(Cell::new(None));
+        let fan = FanControlCluster::new(&motor, 3, FanModeSequence::OffLowMedHigh, dummy_rand);
+
+        fan.step(StepDirection::Increase, false, true).unwrap();
+        assert_eq!(fan.speed_setting.get(), Some(1));
+
+        fan.set_speed_setting(3).unwrap();
+        fan.step(StepDirection::Increase, false, true).unwrap();
+        assert_eq!(fan.speed_setting.get(), Some(3));
+
+        fan.step(StepDirection::Increase, true, false).unwrap();
+        assert_eq!(fan.speed_setting.get(), Some(1));
+
+        fan.step(StepDirection::Decrease, false, false).unwrap();
+        assert_eq!(fan.speed_setting.get(), Some(1));
+    }
+}
@@ -0,0 +1,164 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::convert::TryInto;
+
+use super::objects::*;
+use crate::{
+    attribute_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, TLVWriter, TagType, ToTLV, UtfStr},
+    utils::rand::Rand,
+};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0040;
+
+/// The maximum length of the label and of the value of a label, as per the spec
+pub const MAX_LABEL_LEN: usize = 16;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    LabelList(()) = 0x0,
+}
+
+attribute_enum!(Attributes);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::LabelList as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[],
+};
+
+/// An entry of the `LabelList` attribute of the Fixed Label and the User Label clusters
+#[derive(ToTLV, FromTLV, Debug, Clone, PartialEq, Eq)]
+#[tlvargs(lifetime = "'a")]
+pub struct LabelStruct<'a> {
+    pub label: UtfStr<'a>,
+    pub value: UtfStr<'a>,
+}
+
+impl<'a> LabelStruct<'a> {
+    pub const fn new(label: &'a str, value: &'a str) -> Self {
+        Self {
+            label: UtfStr::new(label.as_bytes()),
+            value: UtfStr::new(value.as_bytes()),
+        }
+    }
+}
+
+/// The labels of an endpoint which are set by the manufacturer, like the room or the
+/// position of the device, as `(label, value)` pairs of up to `MAX_LABEL_LEN` bytes each
+pub struct FixedLabelCluster<'a> {
+    data_ver: Dataver,
+    labels: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> FixedLabelCluster<'a> {
+    /// Create the cluster with `labels`, failing with `ConstraintError` if a label or a value
+    /// is longer than `MAX_LABEL_LEN` bytes
+    pub fn new(labels: &'a [(&'a str, &'a str)], rand: Rand) -> Result<Self, Error> {
+        if labels
+            .iter()
+            .any(|(label, value)| label.len() > MAX_LABEL_LEN || value.len() > MAX_LABEL_LEN)
+        {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        Ok(Self {
+            data_ver: Dataver::new(rand),
+            labels,
+        })
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::LabelList(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for (label, value) in self.labels {
+                            LabelStruct::new(label, value)
+                                .to_tlv(&mut writer, TagType::Anonymous)?;
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<'a> Handler for FixedLabelCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        FixedLabelCluster::read(self, attr, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for FixedLabelCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for FixedLabelCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::ErrorCode, utils::rand::dummy_rand};
+
+    use super::FixedLabelCluster;
+
+    #[test]
+    fn test_label_len() {
+        assert!(FixedLabelCluster::new(&[("room", "kitchen")], dummy_rand).is_ok());
+
+        // Labels and values of more than 16 bytes are rejected
+        assert_eq!(
+            FixedLabelCluster::new(&[("a label which is too long", "1")], dummy_rand)
+                .map(|_| ())
+                .map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(
+            FixedLabelCluster::new(&[("floor", "a value which is too long")], dummy_rand)
+                .map(|_| ())
+                .map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+    }
+}
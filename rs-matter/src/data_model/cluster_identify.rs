@@ -0,0 +1,299 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::objects::*;
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::exchange::Exchange,
+    utils::{rand::Rand, select::Notification},
+};
+use embassy_time::{Duration, Timer};
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0003;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    IdentifyTime(AttrType<u16>) = 0x0,
+    IdentifyType(AttrType<u8>) = 0x1,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    Identify = 0x0,
+    TriggerEffect = 0x40,
+}
+
+command_enum!(Commands);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::IdentifyTime as u16,
+            Access::RWVO,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::IdentifyType as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::Identify as _,
+        CommandsDiscriminants::TriggerEffect as _,
+    ],
+};
+
+/// How the device identifies itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum IdentifyType {
+    None = 0,
+    LightOutput = 1,
+    VisibleIndicator = 2,
+    AudibleBeep = 3,
+    Display = 4,
+    Actuator = 5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum EffectIdentifier {
+    Blink = 0x00,
+    Breathe = 0x01,
+    Okay = 0x02,
+    ChannelChange = 0x0b,
+    FinishEffect = 0xfe,
+    StopEffect = 0xff,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct IdentifyReq {
+    pub identify_time: u16,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct TriggerEffectReq {
+    pub effect_identifier: u8,
+    pub effect_variant: u8,
+}
+
+/// The application side of the Identify cluster
+///
+/// `identify_start` is called when `IdentifyTime` becomes non-zero, and `identify_stop`
+/// once it counts down to 0 or is reset by a client.
+pub trait IdentifyIndicator {
+    fn identify_start(&self);
+
+    fn identify_stop(&self);
+
+    /// Play a short effect, e.g. a blink, independently of `IdentifyTime`
+    ///
+    /// `FinishEffect` and `StopEffect` end the effect in progress, if any.
+    fn trigger_effect(&self, _effect: EffectIdentifier, _variant: u8) {}
+}
+
+impl<T> IdentifyIndicator for &T
+where
+    T: IdentifyIndicator,
+{
+    fn identify_start(&self) {
+        (**self).identify_start()
+    }
+
+    fn identify_stop(&self) {
+        (**self).identify_stop()
+    }
+
+    fn trigger_effect(&self, effect: EffectIdentifier, variant: u8) {
+        (**self).trigger_effect(effect, variant)
+    }
+}
+
+pub struct IdentifyCluster<'a> {
+    data_ver: Dataver,
+    identify_type: IdentifyType,
+    identify_time: Cell<u16>,
+    indicator: Cell<Option<&'a dyn IdentifyIndicator>>,
+    timer_notification: Notification,
+}
+
+impl<'a> IdentifyCluster<'a> {
+    pub fn new(identify_type: IdentifyType, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            identify_type,
+            identify_time: Cell::new(0),
+            indicator: Cell::new(None),
+            timer_notification: Notification::new(),
+        }
+    }
+
+    pub fn set_indicator(&self, indicator: Option<&'a dyn IdentifyIndicator>) {
+        self.indicator.set(indicator);
+    }
+
+    /// The remaining identification time in seconds
+    pub fn identify_time(&self) -> u16 {
+        self.identify_time.get()
+    }
+
+    pub fn set_identify_time(&self, time: u16) {
+        let previous = self.identify_time.replace(time);
+
+        if previous != time {
+            self.data_ver.changed();
+        }
+
+        if let Some(indicator) = self.indicator.get() {
+            if previous == 0 && time > 0 {
+                indicator.identify_start();
+            } else if previous > 0 && time == 0 {
+                indicator.identify_stop();
+            }
+        }
+
+        self.timer_notification.signal(());
+    }
+
+    /// Count down the `IdentifyTime` attribute
+    ///
+    /// Needs to be running for the identification to ever stop by itself.
+    pub async fn run(&self) -> Result<(), Error> {
+        loop {
+            if self.identify_time.get() > 0 {
+                Timer::after(Duration::from_secs(1)).await;
+
+                let time = self.identify_time.get();
+
+                if time > 0 {
+                    self.set_identify_time(time - 1);
+                }
+            } else {
+                self.timer_notification.wait().await;
+            }
+        }
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::IdentifyTime(codec) => {
+                        codec.encode(writer, self.identify_time.get())
+                    }
+                    Attributes::IdentifyType(codec) => {
+                        codec.encode(writer, self.identify_type as _)
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::IdentifyTime(codec) => self.set_identify_time(codec.decode(data)?),
+            Attributes::IdentifyType(_) => Err(ErrorCode::InvalidAction)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        _encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::Identify => {
+                cmd_enter!("Identify");
+                let req = IdentifyReq::from_tlv(data)?;
+
+                self.set_identify_time(req.identify_time);
+            }
+            Commands::TriggerEffect => {
+                cmd_enter!("TriggerEffect");
+                let req = TriggerEffectReq::from_tlv(data)?;
+
+                let effect = EffectIdentifier::from_repr(req.effect_identifier)
+                    .ok_or(ErrorCode::ConstraintError)?;
+
+                info!("Effect {:?}, variant {}", effect, req.effect_variant);
+
+                if let Some(indicator) = self.indicator.get() {
+                    indicator.trigger_effect(effect, req.effect_variant);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Handler for IdentifyCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        IdentifyCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        IdentifyCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        IdentifyCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for IdentifyCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for IdentifyCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
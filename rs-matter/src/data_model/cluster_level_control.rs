@@ -0,0 +1,718 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::{
+    cluster_on_off::{OnOffCluster, OnOffCoupling},
+    cluster_scenes_management::{AttributeValuePair, SceneParticipant},
+    objects::*,
+    transition::{Transition, TransitionTimer},
+};
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, Nullable, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+use embassy_time::{Duration, Instant};
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0008;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    CurrentLevel(AttrType<Nullable<u8>>) = 0x0,
+    RemainingTime(AttrType<u16>) = 0x1,
+    MinLevel(AttrType<u8>) = 0x2,
+    MaxLevel(AttrType<u8>) = 0x3,
+    Options(AttrType<u8>) = 0xf,
+    OnOffTransitionTime(AttrType<u16>) = 0x10,
+    OnLevel(AttrType<Nullable<u8>>) = 0x11,
+    StartUpCurrentLevel(AttrType<Nullable<u8>>) = 0x4000,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    MoveToLevel = 0x0,
+    Move = 0x1,
+    Step = 0x2,
+    Stop = 0x3,
+    MoveToLevelWithOnOff = 0x4,
+    MoveWithOnOff = 0x5,
+    StepWithOnOff = 0x6,
+    StopWithOnOff = 0x7,
+}
+
+command_enum!(Commands);
+
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    OnOff = 0x01,
+    Lighting = 0x02,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: Feature::OnOff as u32 | Feature::Lighting as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::CurrentLevel as u16,
+            Access::RV,
+            Quality::SN.union(Quality::X),
+        ),
+        Attribute::new(
+            AttributesDiscriminants::RemainingTime as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MinLevel as u16,
+            Access::RV,
+            Quality::F,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MaxLevel as u16,
+            Access::RV,
+            Quality::F,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Options as u16,
+            Access::RWVO,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OnOffTransitionTime as u16,
+            Access::RWVO,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OnLevel as u16,
+            Access::RWVO,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::StartUpCurrentLevel as u16,
+            Access::RWVM,
+            Quality::N.union(Quality::X),
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::MoveToLevel as _,
+        CommandsDiscriminants::Move as _,
+        CommandsDiscriminants::Step as _,
+        CommandsDiscriminants::Stop as _,
+        CommandsDiscriminants::MoveToLevelWithOnOff as _,
+        CommandsDiscriminants::MoveWithOnOff as _,
+        CommandsDiscriminants::StepWithOnOff as _,
+        CommandsDiscriminants::StopWithOnOff as _,
+    ],
+};
+
+/// The levels supported with the Lighting feature
+pub const MIN_LEVEL: u8 = 1;
+pub const MAX_LEVEL: u8 = 254;
+
+const OPTION_EXECUTE_IF_OFF: u8 = 0x01;
+const OPTION_COUPLE_COLOR_TEMP_TO_LEVEL: u8 = 0x02;
+
+pub const MOVE_MODE_UP: u8 = 0;
+pub const MOVE_MODE_DOWN: u8 = 1;
+
+#[derive(FromTLV, ToTLV)]
+pub struct MoveToLevelReq {
+    pub level: u8,
+    pub transition_time: Nullable<u16>,
+    pub options_mask: u8,
+    pub options_override: u8,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct MoveReq {
+    pub move_mode: u8,
+    pub rate: Nullable<u8>,
+    pub options_mask: u8,
+    pub options_override: u8,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct StepReq {
+    pub step_mode: u8,
+    pub step_size: u8,
+    pub transition_time: Nullable<u16>,
+    pub options_mask: u8,
+    pub options_override: u8,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct StopReq {
+    pub options_mask: u8,
+    pub options_override: u8,
+}
+
+/// Lets another cluster of the same endpoint follow the current level
+///
+/// This is how the Color Control cluster implements the `CoupleColorTempToLevel` option.
+pub trait LevelCoupling {
+    /// Called whenever the current level changes while `CoupleColorTempToLevel` is set
+    fn level_changed(&self, level: u8);
+}
+
+/// A change of the current level over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LevelTransition {
+    values: Transition<1>,
+    /// Switch the coupled On/Off cluster off once done
+    off: bool,
+    /// The level to restore once done, i.e. after switching off
+    restore: Option<u8>,
+}
+
+impl LevelTransition {
+    fn level(&self, now: Instant) -> u8 {
+        self.values.values(now)[0] as _
+    }
+}
+
+pub struct LevelControlCluster<'a> {
+    data_ver: Dataver,
+    on_off: Option<&'a OnOffCluster<'a>>,
+    coupling: Cell<Option<&'a dyn LevelCoupling>>,
+    current_level: Cell<Option<u8>>,
+    options: Cell<u8>,
+    on_off_transition_time: Cell<u16>,
+    on_level: Cell<Option<u8>>,
+    start_up_current_level: Cell<Option<u8>>,
+    transition: Cell<Option<LevelTransition>>,
+    timer: TransitionTimer,
+}
+
+impl<'a> LevelControlCluster<'a> {
+    /// Create the cluster, optionally coupled to the On/Off cluster of the same endpoint
+    ///
+    /// For the coupling to work both ways, the cluster also needs to be registered with
+    /// `OnOffCluster::set_coupling`.
+    pub fn new(on_off: Option<&'a OnOffCluster<'a>>, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            on_off,
+            coupling: Cell::new(None),
+            current_level: Cell::new(Some(MAX_LEVEL)),
+            options: Cell::new(0),
+            on_off_transition_time: Cell::new(0),
+            on_level: Cell::new(None),
+            start_up_current_level: Cell::new(None),
+            transition: Cell::new(None),
+            timer: TransitionTimer::new(),
+        }
+    }
+
+    pub fn current_level(&self) -> Option<u8> {
+        self.current_level.get()
+    }
+
+    /// Set the current level right away, cancelling any ongoing transition
+    pub fn set_current_level(&self, level: Option<u8>) {
+        self.transition.set(None);
+        self.set_level(level.map(Self::clamp));
+    }
+
+    pub fn set_coupling(&self, coupling: Option<&'a dyn LevelCoupling>) {
+        self.coupling.set(coupling);
+    }
+
+    /// Apply the `StartUpCurrentLevel` attribute
+    ///
+    /// Should be called once on power up, after the persisted attributes were loaded.
+    pub fn start_up(&self) {
+        match self.start_up_current_level.get() {
+            Some(0) => self.set_current_level(Some(MIN_LEVEL)),
+            Some(level) => self.set_current_level(Some(level)),
+            None => (),
+        }
+    }
+
+    /// Drive the transitions of the current level, as per `TransitionTimer::run`
+    pub async fn run(&self) -> Result<(), Error> {
+        self.timer
+            .run(|| self.transition.get().is_some(), |now| self.update(now))
+            .await
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::CurrentLevel(codec) => {
+                        codec.encode(writer, Self::nullable(self.current_level.get()))
+                    }
+                    Attributes::RemainingTime(codec) => codec.encode(
+                        writer,
+                        self.transition
+                            .get()
+                            .map(|transition| transition.values.remaining_time(Instant::now()))
+                            .unwrap_or(0),
+                    ),
+                    Attributes::MinLevel(codec) => codec.encode(writer, MIN_LEVEL),
+                    Attributes::MaxLevel(codec) => codec.encode(writer, MAX_LEVEL),
+                    Attributes::Options(codec) => codec.encode(writer, self.options.get()),
+                    Attributes::OnOffTransitionTime(codec) => {
+                        codec.encode(writer, self.on_off_transition_time.get())
+                    }
+                    Attributes::OnLevel(codec) => {
+                        codec.encode(writer, Self::nullable(self.on_level.get()))
+                    }
+                    Attributes::StartUpCurrentLevel(codec) => {
+                        codec.encode(writer, Self::nullable(self.start_up_current_level.get()))
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            // Only writable internally, when restoring the persisted level
+            Attributes::CurrentLevel(codec) => {
+                self.set_current_level(Self::level(codec.decode(data)?)?)
+            }
+            Attributes::Options(codec) => self.options.set(
+                codec.decode(data)? & (OPTION_EXECUTE_IF_OFF | OPTION_COUPLE_COLOR_TEMP_TO_LEVEL),
+            ),
+            Attributes::OnOffTransitionTime(codec) => {
+                self.on_off_transition_time.set(codec.decode(data)?)
+            }
+            Attributes::OnLevel(codec) => self.on_level.set(Self::level(codec.decode(data)?)?),
+            Attributes::StartUpCurrentLevel(codec) => {
+                let level = match codec.decode(data)? {
+                    // 0 means the minimum level
+                    Nullable::NotNull(0) => Some(0),
+                    level => Self::level(level)?,
+                };
+
+                self.start_up_current_level.set(level);
+            }
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        _encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::MoveToLevel => {
+                cmd_enter!("MoveToLevel");
+                let req = MoveToLevelReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_to_level(&req, false)?;
+                }
+            }
+            Commands::Move => {
+                cmd_enter!("Move");
+                let req = MoveReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.move_(&req, false)?;
+                }
+            }
+            Commands::Step => {
+                cmd_enter!("Step");
+                let req = StepReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.step(&req, false)?;
+                }
+            }
+            Commands::Stop => {
+                cmd_enter!("Stop");
+                let req = StopReq::from_tlv(data)?;
+
+                if self.execute_if_off(req.options_mask, req.options_override) {
+                    self.stop();
+                }
+            }
+            Commands::MoveToLevelWithOnOff => {
+                cmd_enter!("MoveToLevelWithOnOff");
+                self.move_to_level(&MoveToLevelReq::from_tlv(data)?, true)?;
+            }
+            Commands::MoveWithOnOff => {
+                cmd_enter!("MoveWithOnOff");
+                self.move_(&MoveReq::from_tlv(data)?, true)?;
+            }
+            Commands::StepWithOnOff => {
+                cmd_enter!("StepWithOnOff");
+                self.step(&StepReq::from_tlv(data)?, true)?;
+            }
+            Commands::StopWithOnOff => {
+                cmd_enter!("StopWithOnOff");
+                self.stop();
+            }
+        }
+
+        Ok(())
+    }
+
+    // Commands without the on/off coupling are ignored while the light is off,
+    // unless the ExecuteIfOff option says otherwise
+    fn execute_if_off(&self, options_mask: u8, options_override: u8) -> bool {
+        let options = (self.options.get() & !options_mask) | (options_override & options_mask);
+
+        options & OPTION_EXECUTE_IF_OFF != 0
+            || self.on_off.map(|on_off| on_off.get()).unwrap_or(true)
+    }
+
+    fn move_to_level(&self, req: &MoveToLevelReq, with_on_off: bool) -> Result<(), Error> {
+        if req.level > MAX_LEVEL {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let transition_time = match req.transition_time {
+            Nullable::NotNull(transition_time) => transition_time,
+            Nullable::Null => self.on_off_transition_time.get(),
+        };
+
+        self.start(
+            Self::clamp(req.level),
+            Self::tenths(transition_time),
+            with_on_off,
+            None,
+        );
+
+        Ok(())
+    }
+
+    fn move_(&self, req: &MoveReq, with_on_off: bool) -> Result<(), Error> {
+        let to = match req.move_mode {
+            MOVE_MODE_UP => MAX_LEVEL,
+            MOVE_MODE_DOWN => MIN_LEVEL,
+            _ => Err(ErrorCode::ConstraintError)?,
+        };
+
+        let duration = match req.rate {
+            Nullable::NotNull(0) => Err(ErrorCode::InvalidCommand)?,
+            Nullable::NotNull(rate) => {
+                let from = self.current_level.get().unwrap_or(MIN_LEVEL);
+
+                // The rate is in units per second
+                Duration::from_millis(from.abs_diff(to) as u64 * 1000 / rate as u64)
+            }
+            // No default move rate, so move as fast as possible
+            Nullable::Null => Duration::from_ticks(0),
+        };
+
+        self.start(to, duration, with_on_off, None);
+
+        Ok(())
+    }
+
+    fn step(&self, req: &StepReq, with_on_off: bool) -> Result<(), Error> {
+        let from = self.current_level.get().unwrap_or(MIN_LEVEL);
+
+        let to = match req.step_mode {
+            MOVE_MODE_UP => from.saturating_add(req.step_size),
+            MOVE_MODE_DOWN => from.saturating_sub(req.step_size),
+            _ => Err(ErrorCode::ConstraintError)?,
+        };
+
+        let duration = match req.transition_time {
+            Nullable::NotNull(transition_time) => Self::tenths(transition_time),
+            Nullable::Null => Duration::from_ticks(0),
+        };
+
+        self.start(Self::clamp(to), duration, with_on_off, None);
+
+        Ok(())
+    }
+
+    fn stop(&self) {
+        if let Some(transition) = self.transition.get() {
+            self.set_level(Some(transition.level(Instant::now())));
+            self.transition.set(None);
+            self.data_ver.changed();
+        }
+    }
+
+    fn start(&self, to: u8, duration: Duration, with_on_off: bool, restore: Option<u8>) {
+        let from = self.current_level.get().unwrap_or(MIN_LEVEL);
+
+        if with_on_off && to > MIN_LEVEL {
+            if let Some(on_off) = self.on_off {
+                on_off.set(true);
+            }
+        }
+
+        info!(
+            "Level transition {} -> {} in {}ms",
+            from,
+            to,
+            duration.as_millis()
+        );
+
+        self.transition.set(Some(LevelTransition {
+            values: Transition::new([from as _], [to as _], duration),
+            off: with_on_off && to == MIN_LEVEL,
+            restore,
+        }));
+
+        self.timer.started(Instant::now());
+        self.data_ver.changed();
+
+        // Transitions with no duration complete right away
+        self.update(Instant::now());
+    }
+
+    fn update(&self, now: Instant) {
+        if let Some(transition) = self.transition.get() {
+            if transition.values.is_complete(now) {
+                self.set_level(Some(transition.level(now)));
+                self.transition.set(None);
+
+                if transition.off {
+                    if let Some(on_off) = self.on_off {
+                        on_off.set(false);
+                    }
+                }
+
+                if let Some(restore) = transition.restore {
+                    self.set_level(Some(restore));
+                }
+
+                // RemainingTime is now 0
+                self.data_ver.changed();
+            } else if self.store_level(Some(transition.level(now))) && self.timer.report(now) {
+                self.data_ver.changed();
+            }
+        }
+    }
+
+    fn set_level(&self, level: Option<u8>) {
+        if self.store_level(level) {
+            self.data_ver.changed();
+        }
+    }
+
+    // Change the current level without reporting it, returning `true` if it changed
+    fn store_level(&self, level: Option<u8>) -> bool {
+        if self.current_level.get() == level {
+            return false;
+        }
+
+        self.current_level.set(level);
+
+        if let (Some(level), Some(coupling)) = (level, self.coupling.get()) {
+            if self.options.get() & OPTION_COUPLE_COLOR_TEMP_TO_LEVEL != 0 {
+                coupling.level_changed(level);
+            }
+        }
+
+        true
+    }
+
+    fn level(level: Nullable<u8>) -> Result<Option<u8>, Error> {
+        match level {
+            Nullable::NotNull(level) if !(MIN_LEVEL..=MAX_LEVEL).contains(&level) => {
+                Err(ErrorCode::ConstraintError.into())
+            }
+            Nullable::NotNull(level) => Ok(Some(level)),
+            Nullable::Null => Ok(None),
+        }
+    }
+
+    fn clamp(level: u8) -> u8 {
+        level.clamp(MIN_LEVEL, MAX_LEVEL)
+    }
+
+    fn tenths(time: u16) -> Duration {
+        Duration::from_millis(time as u64 * 100)
+    }
+
+    fn nullable(value: Option<u8>) -> Nullable<u8> {
+        match value {
+            Some(value) => Nullable::NotNull(value),
+            None => Nullable::Null,
+        }
+    }
+}
+
+// The effect of the On/Off commands on the current level, as per the Lighting feature
+impl<'a> OnOffCoupling for LevelControlCluster<'a> {
+    fn switch_on(&self) {
+        // The level before switching off, if still in the middle of switching off
+        let stored = self
+            .transition
+            .get()
+            .and_then(|transition| transition.restore)
+            .or(self.current_level.get())
+            .unwrap_or(MAX_LEVEL);
+
+        let to = self.on_level.get().unwrap_or(stored);
+
+        self.transition.set(None);
+        self.set_level(Some(MIN_LEVEL));
+        self.start(
+            to,
+            Self::tenths(self.on_off_transition_time.get()),
+            false,
+            None,
+        );
+    }
+
+    fn switch_off(&self) -> bool {
+        let current = self.current_level.get().unwrap_or(MIN_LEVEL);
+
+        // Without an OnLevel, the next On restores the level from before switching off
+        let restore = self.on_level.get().is_none().then_some(current);
+
+        self.start(
+            MIN_LEVEL,
+            Self::tenths(self.on_off_transition_time.get()),
+            true,
+            restore,
+        );
+
+        true
+    }
+}
+
+// A null current level is stored as 0xFF, the null value of a nullable u8
+impl<'a> SceneParticipant for LevelControlCluster<'a> {
+    fn scene_value(&self, attr_id: AttrId) -> Option<u32> {
+        (attr_id == AttributesDiscriminants::CurrentLevel as AttrId)
+            .then(|| self.current_level.get().unwrap_or(u8::MAX) as _)
+    }
+
+    fn recall_scene(&self, values: &[AttributeValuePair], transition_time: Duration) {
+        for value in values {
+            if value.attribute_id == AttributesDiscriminants::CurrentLevel as u32
+                && value.attribute_value < u8::MAX as u32
+            {
+                self.start(
+                    Self::clamp(value.attribute_value as _),
+                    transition_time,
+                    false,
+                    None,
+                );
+            }
+        }
+    }
+}
+
+impl<'a> Handler for LevelControlCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        LevelControlCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        LevelControlCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        LevelControlCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for LevelControlCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for LevelControlCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_time::{Duration, Instant};
+
+    use crate::{data_model::transition::Transition, utils::rand::dummy_rand};
+
+    use super::{LevelControlCluster, LevelTransition};
+
+    #[test]
+    fn test_transition_reporting() {
+        let cluster = LevelControlCluster::new(None, dummy_rand);
+        let start = Instant::from_millis(1000);
+
+        cluster.current_level.set(Some(1));
+        cluster.timer.started(start);
+        cluster.transition.set(Some(LevelTransition {
+            values: Transition {
+                from: [1],
+                to: [254],
+                start,
+                duration: Duration::from_millis(2000),
+            },
+            off: false,
+            restore: None,
+        }));
+
+        let dataver = cluster.data_ver.get();
+
+        // The level follows the transition, but is not reported on every tick
+        cluster.update(Instant::from_millis(1100));
+        assert_eq!(cluster.current_level(), Some(13));
+        assert_eq!(cluster.data_ver.get(), dataver);
+
+        cluster.update(Instant::from_millis(2000));
+        assert_eq!(cluster.current_level(), Some(127));
+        assert_ne!(cluster.data_ver.get(), dataver);
+
+        let dataver = cluster.data_ver.get();
+
+        cluster.update(Instant::from_millis(2500));
+        assert_eq!(cluster.data_ver.get(), dataver);
+
+        // The end of the transition is always reported
+        cluster.update(Instant::from_millis(3000));
+        assert_eq!(cluster.current_level(), Some(254));
+        assert!(cluster.transition.get().is_none());
+        assert_ne!(cluster.data_ver.get(), dataver);
+    }
+}
@@ -0,0 +1,242 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::{
+    cluster_time_format_localization::{CalendarType, HourFormat},
+    cluster_unit_localization::TempUnit,
+    objects::*,
+};
+use crate::{
+    attribute_enum,
+    error::{Error, ErrorCode},
+    tlv::{TagType, ToTLV, UtfStr},
+    utils::rand::Rand,
+};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x002B;
+
+/// The maximum length of a locale, as per the spec
+pub const MAX_LOCALE_LEN: usize = 35;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    ActiveLocale(AttrUtfType) = 0x0,
+    SupportedLocales(()) = 0x1,
+}
+
+attribute_enum!(Attributes);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::ActiveLocale as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SupportedLocales as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[],
+};
+
+/// The localization of the user interface of the node
+///
+/// Shared by the Localization Configuration, the Time Format Localization and the
+/// Unit Localization clusters, which take their supported values and the initial
+/// values of their writable attributes from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalizationConfig<'a> {
+    /// The supported locales, as language tags like "en-US", of up to `MAX_LOCALE_LEN`
+    /// characters each
+    pub supported_locales: &'a [&'a str],
+    /// The initial ActiveLocale, one of `supported_locales`
+    pub active_locale: &'a str,
+    pub hour_format: HourFormat,
+    pub supported_calendar_types: &'a [CalendarType],
+    /// The initial ActiveCalendarType, one of `supported_calendar_types`
+    pub active_calendar_type: CalendarType,
+    pub temperature_unit: TempUnit,
+}
+
+impl LocalizationConfig<'static> {
+    pub const DEFAULT: Self = Self {
+        supported_locales: &["en-US"],
+        active_locale: "en-US",
+        hour_format: HourFormat::TwelveHour,
+        supported_calendar_types: &[CalendarType::Gregorian],
+        active_calendar_type: CalendarType::Gregorian,
+        temperature_unit: TempUnit::Fahrenheit,
+    };
+}
+
+impl Default for LocalizationConfig<'static> {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+pub struct LocalizationCluster<'a> {
+    data_ver: Dataver,
+    conf: &'a LocalizationConfig<'a>,
+    // The index of the active locale in the supported locales
+    active_locale: Cell<usize>,
+}
+
+impl<'a> LocalizationCluster<'a> {
+    pub fn new(conf: &'a LocalizationConfig<'a>, rand: Rand) -> Self {
+        let active_locale = conf
+            .supported_locales
+            .iter()
+            .position(|locale| *locale == conf.active_locale)
+            .unwrap_or(0);
+
+        Self {
+            data_ver: Dataver::new(rand),
+            conf,
+            active_locale: Cell::new(active_locale),
+        }
+    }
+
+    pub fn active_locale(&self) -> &'a str {
+        self.conf
+            .supported_locales
+            .get(self.active_locale.get())
+            .copied()
+            .unwrap_or("")
+    }
+
+    /// Set the active locale, which needs to be one of the supported locales
+    pub fn set_active_locale(&self, locale: &str) -> Result<(), Error> {
+        if locale.len() > MAX_LOCALE_LEN {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let index = self
+            .conf
+            .supported_locales
+            .iter()
+            .position(|supported| *supported == locale)
+            .ok_or(ErrorCode::ConstraintError)?;
+
+        self.active_locale.set(index);
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::ActiveLocale(codec) => codec.encode(writer, self.active_locale()),
+                    Attributes::SupportedLocales(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for locale in self.conf.supported_locales {
+                            UtfStr::new(locale.as_bytes())
+                                .to_tlv(&mut writer, TagType::Anonymous)?;
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::ActiveLocale(codec) => {
+                self.set_active_locale(codec.decode(data).map_err(|_| ErrorCode::InvalidDataType)?)
+            }
+            _ => Err(ErrorCode::InvalidAction.into()),
+        }
+    }
+}
+
+impl<'a> Handler for LocalizationCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        LocalizationCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        LocalizationCluster::write(self, attr, data)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for LocalizationCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for LocalizationCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::ErrorCode, utils::rand::dummy_rand};
+
+    use super::{LocalizationCluster, LocalizationConfig};
+
+    #[test]
+    fn test_active_locale() {
+        let conf = LocalizationConfig {
+            supported_locales: &["en-US", "de-DE", "fr-FR"],
+            active_locale: "de-DE",
+            ..LocalizationConfig::DEFAULT
+        };
+
+        let cluster = LocalizationCluster::new(&conf, dummy_rand);
+        assert_eq!(cluster.active_locale(), "de-DE");
+
+        cluster.set_active_locale("fr-FR").unwrap();
+        assert_eq!(cluster.active_locale(), "fr-FR");
+
+        assert_eq!(
+            cluster.set_active_locale("es-ES").map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(
+            cluster
+                .set_active_locale("en-US-x-a-very-long-private-use-subtag")
+                .map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(cluster.active_locale(), "fr-FR");
+    }
+}
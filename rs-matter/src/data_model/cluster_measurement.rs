@@ -0,0 +1,177 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The clusters measuring a single quantity, e.g. temperature or pressure, which only
+//! differ in their ID and in the type of their measured value
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::objects::*;
+use crate::{
+    attribute_enum,
+    error::Error,
+    tlv::{Nullable, ToTLV},
+    utils::rand::Rand,
+};
+use strum::{EnumDiscriminants, FromRepr};
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    MeasuredValue(()) = 0x0,
+    MinMeasuredValue(()) = 0x1,
+    MaxMeasuredValue(()) = 0x2,
+    Tolerance(AttrType<u16>) = 0x3,
+}
+
+attribute_enum!(Attributes);
+
+const ATTRIBUTES: &[Attribute] = &[
+    FEATURE_MAP,
+    ATTRIBUTE_LIST,
+    Attribute::new(
+        AttributesDiscriminants::MeasuredValue as u16,
+        Access::RV,
+        Quality::X,
+    ),
+    Attribute::new(
+        AttributesDiscriminants::MinMeasuredValue as u16,
+        Access::RV,
+        Quality::X,
+    ),
+    Attribute::new(
+        AttributesDiscriminants::MaxMeasuredValue as u16,
+        Access::RV,
+        Quality::X,
+    ),
+    Attribute::new(
+        AttributesDiscriminants::Tolerance as u16,
+        Access::RV,
+        Quality::NONE,
+    ),
+];
+
+/// The metadata of the measurement cluster `id`
+pub const fn cluster(id: u32) -> Cluster<'static> {
+    Cluster {
+        id: id as _,
+        feature_map: 0,
+        attributes: ATTRIBUTES,
+        commands: &[],
+    }
+}
+
+/// A measurement cluster with ID `ID`, whose measured value is a `T`
+pub struct MeasurementCluster<T, const ID: u32> {
+    data_ver: Dataver,
+    measured_value: Cell<Option<T>>,
+    min_measured_value: Cell<Option<T>>,
+    max_measured_value: Cell<Option<T>>,
+    tolerance: Cell<u16>,
+}
+
+impl<T, const ID: u32> MeasurementCluster<T, ID>
+where
+    T: Copy + PartialEq + ToTLV,
+{
+    /// Create the cluster with the range the sensor can measure, `None` if unknown
+    pub fn new(min: Option<T>, max: Option<T>, tolerance: u16, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            measured_value: Cell::new(None),
+            min_measured_value: Cell::new(min),
+            max_measured_value: Cell::new(max),
+            tolerance: Cell::new(tolerance),
+        }
+    }
+
+    pub fn measured_value(&self) -> Option<T> {
+        self.measured_value.get()
+    }
+
+    /// Update the measured value, `None` if the measurement is invalid
+    pub fn set_measured_value(&self, value: Option<T>) {
+        if self.measured_value.replace(value) != value {
+            self.data_ver.changed();
+        }
+    }
+
+    /// Update the range of the sensor, e.g. after a recalibration
+    pub fn set_range(&self, min: Option<T>, max: Option<T>, tolerance: u16) {
+        let previous_min = self.min_measured_value.replace(min);
+        let previous_max = self.max_measured_value.replace(max);
+        let previous_tolerance = self.tolerance.replace(tolerance);
+
+        if previous_min != min || previous_max != max || previous_tolerance != tolerance {
+            self.data_ver.changed();
+        }
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                cluster(ID).read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::MeasuredValue(_) => {
+                        writer.set(Self::nullable(self.measured_value.get()))
+                    }
+                    Attributes::MinMeasuredValue(_) => {
+                        writer.set(Self::nullable(self.min_measured_value.get()))
+                    }
+                    Attributes::MaxMeasuredValue(_) => {
+                        writer.set(Self::nullable(self.max_measured_value.get()))
+                    }
+                    Attributes::Tolerance(codec) => codec.encode(writer, self.tolerance.get()),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    fn nullable(value: Option<T>) -> Nullable<T> {
+        match value {
+            Some(value) => Nullable::NotNull(value),
+            None => Nullable::Null,
+        }
+    }
+}
+
+impl<T, const ID: u32> Handler for MeasurementCluster<T, ID>
+where
+    T: Copy + PartialEq + ToTLV,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        MeasurementCluster::read(self, attr, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<T, const ID: u32> NonBlockingHandler for MeasurementCluster<T, ID> where
+    T: Copy + PartialEq + ToTLV
+{
+}
+
+impl<T, const ID: u32> ChangeNotifier<()> for MeasurementCluster<T, ID> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
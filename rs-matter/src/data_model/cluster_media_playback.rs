@@ -15,50 +15,134 @@
  *    limitations under the License.
  */
 
+use core::{cell::Cell, convert::TryInto, time::Duration};
+
 use super::objects::*;
 use crate::{
-    error::*,
-    interaction_model::{
-        command::CommandReq,
-        core::IMStatusCode,
-        messages::ib::{self},
+    attribute_enum, cmd_enter, command_enum,
+    error::Error,
+    tlv::{FromTLV, Nullable, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::exchange::Exchange,
+    utils::{
+        epoch::{Epoch, UtcClock},
+        rand::Rand,
     },
-    tlv::{TLVWriter, TagType, ToTLV},
 };
-use chrono::{DateTime, NaiveDate};
-use num_derive::FromPrimitive;
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
 
 pub const ID: u32 = 0x0506;
-#[derive(FromPrimitive)]
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
 pub enum Attributes {
-    CurrentState = 0x0,
-    StartTime = 0x1,
-    Duration = 0x2,
-    SampledPosition = 0x3,
-    PlaybackSpeed = 0x4,
-    SeekRangeEnd = 0x5,
-    SeekRangeStart = 0x6,
+    CurrentState(AttrType<u8>) = 0x0,
+    StartTime(AttrType<Nullable<u64>>) = 0x1,
+    Duration(AttrType<Nullable<u64>>) = 0x2,
+    SampledPosition(AttrType<Nullable<PlaybackPosition>>) = 0x3,
+    PlaybackSpeed(()) = 0x4,
+    SeekRangeEnd(AttrType<Nullable<u64>>) = 0x5,
+    SeekRangeStart(AttrType<Nullable<u64>>) = 0x6,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    Play = 0x0,
+    Pause = 0x1,
+    Stop = 0x2,
+    StartOver = 0x3,
+    Previous = 0x4,
+    Next = 0x5,
+    SkipForward = 0x8,
+    SkipBackward = 0x9,
+    Seek = 0x0b,
 }
 
-struct ClusterCallback {
-    name: Commands,
-    callback: Box<dyn FnMut()>,
+command_enum!(Commands);
+
+#[repr(u16)]
+pub enum RespCommands {
+    PlaybackResponse = 0x0a,
 }
 
-enum _FeatureMap {
-    AdvancedSeek = 0,
-    VariableSpeed = 1,
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    AdvancedSeek = 0x01,
+    VariableSpeed = 0x02,
 }
-#[derive(FromPrimitive)]
 
-enum PlaybackState {
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: Feature::AdvancedSeek as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::CurrentState as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::StartTime as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Duration as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SampledPosition as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PlaybackSpeed as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SeekRangeEnd as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SeekRangeStart as u16,
+            Access::RV,
+            Quality::X,
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::Play as _,
+        CommandsDiscriminants::Pause as _,
+        CommandsDiscriminants::Stop as _,
+        CommandsDiscriminants::StartOver as _,
+        CommandsDiscriminants::Previous as _,
+        CommandsDiscriminants::Next as _,
+        CommandsDiscriminants::SkipForward as _,
+        CommandsDiscriminants::SkipBackward as _,
+        CommandsDiscriminants::Seek as _,
+    ],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PlaybackState {
     Playing = 0,
     Paused = 1,
     NotPlaying = 2,
     Buffering = 3,
 }
-#[derive(FromPrimitive)]
-enum CommandStatus {
+
+/// The status reported back in the `PlaybackResponse` of every command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PlaybackStatus {
     Success = 0,
     InvalidStateForCommand = 1,
     NotAllowed = 2,
@@ -67,342 +151,570 @@ enum CommandStatus {
     SeekOutOfRange = 5,
 }
 
-impl CommandStatus {
-    fn u8(&self) -> u8 {
-        match self {
-            CommandStatus::Success => 0,
-            CommandStatus::InvalidStateForCommand => 1,
-            CommandStatus::NotAllowed => 2,
-            CommandStatus::NotActive => 3,
-            CommandStatus::SpeedOutOfRange => 4,
-            CommandStatus::SeekOutOfRange => 5,
-        }
-    }
+/// The value of the `SampledPosition` attribute
+///
+/// `updated_at` is in microseconds since the Matter epoch, `position` is in milliseconds
+/// from the start of the media.
+#[derive(Debug, Clone, Copy, PartialEq, ToTLV)]
+pub struct PlaybackPosition {
+    pub updated_at: u64,
+    pub position: Nullable<u64>,
 }
 
-#[derive(FromPrimitive, PartialEq)]
-pub enum Commands {
-    Play = 0x0,
-    Pause = 0x1,
-    Stop = 0x2,
-    StartOver = 0x3,
-    Previous = 0x4,
-    Next = 0x5,
-    Rewind = 0x6,
-    FastForward = 0x7,
-    SkipForward = 0x8,
-    SkipBackward = 0x9,
-    // Response is from us to server
-    PlaybackResponse = 0xa,
-    Seek = 0x0b,
+#[derive(FromTLV)]
+struct SeekReq {
+    position: u64,
 }
 
-struct PlaybackPosition {
-    updated_at: u64,
-    position: u64,
+#[derive(FromTLV)]
+struct SkipReq {
+    delta_position: u64,
 }
-// Get microseconds since 2000, Jan 1, 00:00:00
-pub fn get_epoch_us() -> u64 {
-    let epoch_start = NaiveDate::from_ymd_opt(2000, 1, 1)
-        .unwrap()
-        .and_hms_micro_opt(0, 0, 0, 0)
-        .unwrap()
-        .and_local_timezone(chrono::Utc)
-        .unwrap();
-    DateTime::timestamp_micros(&epoch_start) as u64
+
+#[derive(ToTLV)]
+struct PlaybackResp {
+    status: u8,
 }
 
-pub struct MediaPlaybackCluster {
-    base: Cluster,
-    sampled_position: PlaybackPosition,
-    callbacks: Vec<ClusterCallback>,
+/// The application side of the Media Playback cluster
+///
+/// The cluster calls the player before changing its own playback state. Returning
+/// an error status rejects the command, and the status is sent back to the client in
+/// the `PlaybackResponse`. Positions are in milliseconds from the start of the media.
+pub trait MediaPlayer {
+    fn play(&self) -> Result<(), PlaybackStatus>;
+
+    fn pause(&self) -> Result<(), PlaybackStatus>;
+
+    fn stop(&self) -> Result<(), PlaybackStatus>;
+
+    fn start_over(&self) -> Result<(), PlaybackStatus> {
+        Err(PlaybackStatus::NotAllowed)
+    }
+
+    fn previous(&self) -> Result<(), PlaybackStatus> {
+        Err(PlaybackStatus::NotAllowed)
+    }
+
+    fn next(&self) -> Result<(), PlaybackStatus> {
+        Err(PlaybackStatus::NotAllowed)
+    }
+
+    /// Called for `Seek`, `SkipForward` and `SkipBackward` with the new position
+    fn seek(&self, _position: u64) -> Result<(), PlaybackStatus> {
+        Err(PlaybackStatus::NotAllowed)
+    }
 }
 
-impl MediaPlaybackCluster {
-    pub fn new() -> Result<Box<Self>, Error> {
-        let mut cluster = Box::new(MediaPlaybackCluster {
-            base: Cluster::new(ID)?,
-            sampled_position: PlaybackPosition {
-                updated_at: 0,
-                position: 0,
-            },
-            callbacks: vec![],
-        });
-
-        // List should be a Vec<
-        let attrs = [
-            Attribute::new(
-                Attributes::CurrentState as u16,
-                AttrValue::Uint8(PlaybackState::NotPlaying as u8),
-                Access::RV,
-                Quality::PERSISTENT,
-            ),
-            // epoch-us
-            Attribute::new(
-                Attributes::StartTime as u16,
-                AttrValue::Uint64(0),
-                Access::RV,
-                Quality::PERSISTENT,
-            ),
-            Attribute::new(
-                Attributes::Duration as u16,
-                AttrValue::Uint64(1),
-                Access::RV,
-                Quality::PERSISTENT,
-            ),
-            // Playback-Position
-            Attribute::new(
-                Attributes::SampledPosition as u16,
-                AttrValue::Custom,
-                Access::RV,
-                Quality::PERSISTENT,
-            ),
-            // Float
-            Attribute::new(
-                Attributes::PlaybackSpeed as u16,
-                AttrValue::Custom,
-                Access::RV,
-                Quality::PERSISTENT,
-            ),
-            Attribute::new(
-                Attributes::SeekRangeEnd as u16,
-                AttrValue::Uint64(0),
-                Access::RV,
-                Quality::PERSISTENT,
-            ),
-            Attribute::new(
-                Attributes::SeekRangeStart as u16,
-                AttrValue::Uint64(0),
-                Access::RV,
-                Quality::PERSISTENT,
-            ),
-            // Options - probably want a custom type here for mapping cluster options to TLV bitmask
-        ];
-        cluster.base.add_attributes(&attrs)?;
-
-        // For now disable all features by default
-        cluster.base.set_feature_map(0)?;
-        Ok(cluster)
-    }
-
-    pub fn add_callback(&mut self, name: Commands, callback: Box<dyn FnMut()>) {
-        self.callbacks.push(ClusterCallback { name, callback });
-    }
-
-    fn run_callback(&mut self, name: Commands) {
-        for cmd in self.callbacks.iter_mut() {
-            if cmd.name == name {
-                (cmd.callback)()
+impl<T> MediaPlayer for &T
+where
+    T: MediaPlayer,
+{
+    fn play(&self) -> Result<(), PlaybackStatus> {
+        (**self).play()
+    }
+
+    fn pause(&self) -> Result<(), PlaybackStatus> {
+        (**self).pause()
+    }
+
+    fn stop(&self) -> Result<(), PlaybackStatus> {
+        (**self).stop()
+    }
+
+    fn start_over(&self) -> Result<(), PlaybackStatus> {
+        (**self).start_over()
+    }
+
+    fn previous(&self) -> Result<(), PlaybackStatus> {
+        (**self).previous()
+    }
+
+    fn next(&self) -> Result<(), PlaybackStatus> {
+        (**self).next()
+    }
+
+    fn seek(&self, position: u64) -> Result<(), PlaybackStatus> {
+        (**self).seek(position)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Playback {
+    state: PlaybackState,
+    start_time: Option<u64>,
+    duration: Option<u64>,
+    position: Option<u64>,
+    // The `Epoch` time at which the position was sampled
+    updated_at: Duration,
+}
+
+/// The Media Playback cluster
+///
+/// The position is extrapolated with the `Epoch`, while the time it was sampled at is
+/// reported in UTC, so `SampledPosition` is null as long as the `UtcClock` is not set.
+pub struct MediaPlaybackCluster<'a, T> {
+    data_ver: Dataver,
+    epoch: Epoch,
+    utc: &'a UtcClock,
+    player: T,
+    playback: Cell<Playback>,
+}
+
+impl<'a, T> MediaPlaybackCluster<'a, T>
+where
+    T: MediaPlayer,
+{
+    pub fn new(player: T, epoch: Epoch, utc: &'a UtcClock, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            epoch,
+            utc,
+            player,
+            playback: Cell::new(Playback {
+                state: PlaybackState::NotPlaying,
+                start_time: None,
+                duration: None,
+                position: None,
+                updated_at: Duration::ZERO,
+            }),
+        }
+    }
+
+    pub fn player(&self) -> &T {
+        &self.player
+    }
+
+    pub fn state(&self) -> PlaybackState {
+        self.playback.get().state
+    }
+
+    /// The current position in the media, extrapolated from the last sampled position
+    pub fn position(&self) -> Option<u64> {
+        self.current_position(&self.playback.get())
+    }
+
+    /// Set the media being played
+    ///
+    /// `start_time` is in microseconds since the Matter epoch and `duration` is in
+    /// milliseconds; `None` for either means the value is unknown (e.g. live streams).
+    pub fn set_media(&self, start_time: Option<u64>, duration: Option<u64>) {
+        let mut playback = self.playback.get();
+
+        playback.start_time = start_time;
+        playback.duration = duration;
+        playback.position = Some(0);
+        playback.updated_at = (self.epoch)();
+
+        self.playback.set(playback);
+        self.data_ver.changed();
+    }
+
+    /// The last sampled position, with the UTC time it was sampled at
+    ///
+    /// `None` if there is no media, or if the UTC time is not known.
+    pub fn sampled_position(&self) -> Option<PlaybackPosition> {
+        let playback = self.playback.get();
+
+        let position = playback.position?;
+        let elapsed = (self.epoch)().saturating_sub(playback.updated_at);
+        let now = self.utc.now_us()?;
+
+        Some(PlaybackPosition {
+            updated_at: now.saturating_sub(elapsed.as_micros() as u64),
+            position: Nullable::NotNull(position),
+        })
+    }
+
+    /// Update the playback state when it is changed by the player itself rather than by
+    /// a command (e.g. when buffering, or when the end of the media is reached)
+    pub fn set_state(&self, state: PlaybackState) {
+        self.transition(state, None);
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                let playback = self.playback.get();
+
+                match attr.attr_id.try_into()? {
+                    Attributes::CurrentState(codec) => codec.encode(writer, playback.state as _),
+                    Attributes::StartTime(codec) => {
+                        codec.encode(writer, Self::nullable(playback.start_time))
+                    }
+                    Attributes::Duration(codec) => {
+                        codec.encode(writer, Self::nullable(playback.duration))
+                    }
+                    Attributes::SampledPosition(codec) => {
+                        codec.encode(writer, self.sampled_position().into())
+                    }
+                    Attributes::PlaybackSpeed(_) => {
+                        let speed = if playback.state == PlaybackState::Playing {
+                            1.0
+                        } else {
+                            0.0
+                        };
+
+                        writer.f32(AttrDataWriter::TAG, speed)?;
+                        writer.complete()
+                    }
+                    Attributes::SeekRangeEnd(codec) => {
+                        codec.encode(writer, Self::nullable(playback.duration))
+                    }
+                    Attributes::SeekRangeStart(codec) => {
+                        codec.encode(writer, Self::nullable(playback.duration.map(|_| 0)))
+                    }
+                }
             }
+        } else {
+            Ok(())
         }
     }
 
-    fn _set_state_buffering(&mut self) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-        Err(IMStatusCode::Success)
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        let result = match cmd.cmd_id.try_into()? {
+            Commands::Play => {
+                cmd_enter!("Play");
+                self.play()
+            }
+            Commands::Pause => {
+                cmd_enter!("Pause");
+                self.pause()
+            }
+            Commands::Stop => {
+                cmd_enter!("Stop");
+                self.stop()
+            }
+            Commands::StartOver => {
+                cmd_enter!("StartOver");
+                self.player
+                    .start_over()
+                    .map(|_| self.transition(PlaybackState::Playing, Some(0)))
+            }
+            Commands::Previous => {
+                cmd_enter!("Previous");
+                self.player
+                    .previous()
+                    .map(|_| self.transition(PlaybackState::Playing, Some(0)))
+            }
+            Commands::Next => {
+                cmd_enter!("Next");
+                self.player
+                    .next()
+                    .map(|_| self.transition(PlaybackState::Playing, Some(0)))
+            }
+            Commands::SkipForward => {
+                cmd_enter!("SkipForward");
+                let req = SkipReq::from_tlv(data)?;
+
+                self.skip(|position| position.saturating_add(req.delta_position))
+            }
+            Commands::SkipBackward => {
+                cmd_enter!("SkipBackward");
+                let req = SkipReq::from_tlv(data)?;
+
+                self.skip(|position| position.saturating_sub(req.delta_position))
+            }
+            Commands::Seek => {
+                cmd_enter!("Seek");
+                let req = SeekReq::from_tlv(data)?;
+
+                self.seek(req.position)
+            }
+        };
+
+        let status = match result {
+            Ok(()) => PlaybackStatus::Success,
+            Err(status) => status,
+        };
+
+        encoder
+            .with_command(RespCommands::PlaybackResponse as _)?
+            .set(PlaybackResp {
+                status: status as _,
+            })?;
+
+        Ok(())
+    }
+
+    fn play(&self) -> Result<(), PlaybackStatus> {
+        self.player.play()?;
+        self.transition(PlaybackState::Playing, None);
+
+        Ok(())
+    }
+
+    fn pause(&self) -> Result<(), PlaybackStatus> {
+        self.player.pause()?;
+        self.transition(PlaybackState::Paused, None);
+
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), PlaybackStatus> {
+        self.player.stop()?;
+        self.transition(PlaybackState::NotPlaying, Some(0));
+
+        Ok(())
+    }
+
+    fn seek(&self, position: u64) -> Result<(), PlaybackStatus> {
+        let playback = self.playback.get();
+
+        if playback.state == PlaybackState::NotPlaying {
+            Err(PlaybackStatus::NotActive)?;
+        }
+
+        // The seek range is [0, Duration] for media with a known duration, and unbounded
+        // otherwise
+        if playback
+            .duration
+            .map(|duration| position > duration)
+            .unwrap_or(false)
+        {
+            Err(PlaybackStatus::SeekOutOfRange)?;
+        }
+
+        self.player.seek(position)?;
+        self.transition(playback.state, Some(position));
+
+        Ok(())
+    }
+
+    fn skip(&self, f: impl FnOnce(u64) -> u64) -> Result<(), PlaybackStatus> {
+        let playback = self.playback.get();
+
+        if playback.state == PlaybackState::NotPlaying {
+            Err(PlaybackStatus::NotActive)?;
+        }
+
+        let position = f(self.current_position(&playback).unwrap_or(0));
+
+        // Skipping past the end of the media stops at the end
+        let position = playback
+            .duration
+            .map(|duration| position.min(duration))
+            .unwrap_or(position);
+
+        self.player.seek(position)?;
+        self.transition(playback.state, Some(position));
+
+        Ok(())
+    }
+
+    fn transition(&self, state: PlaybackState, position: Option<u64>) {
+        let mut playback = self.playback.get();
+
+        // Sample the position at the time of the change, as clients extrapolate
+        // the current position from the sample and the playback speed
+        playback.position = position.or_else(|| self.current_position(&playback));
+        playback.updated_at = (self.epoch)();
+
+        if playback.state != state {
+            info!("Media playback state: {:?} -> {:?}", playback.state, state);
+            playback.state = state;
+        }
+
+        self.playback.set(playback);
+        self.data_ver.changed();
     }
 
-    fn _set_duration(&mut self, duration: u64) -> Result<(), IMStatusCode> {
-        self.base
-            .write_attribute_raw(Attributes::Duration as u16, AttrValue::Uint64(duration))?;
-        Err(IMStatusCode::Success)
+    fn current_position(&self, playback: &Playback) -> Option<u64> {
+        playback.position.map(|position| {
+            let position = if playback.state == PlaybackState::Playing {
+                let elapsed = (self.epoch)().saturating_sub(playback.updated_at);
+
+                position + elapsed.as_millis() as u64
+            } else {
+                position
+            };
+
+            playback
+                .duration
+                .map(|duration| position.min(duration))
+                .unwrap_or(position)
+        })
     }
 
-    // When rewinding / changing stream / etc we need to change absolute position and updateAt
-    fn update_position(&mut self, new_pos: u64) -> Result<(), IMStatusCode> {
-        let now = get_epoch_us();
+    fn nullable(value: Option<u64>) -> Nullable<u64> {
+        match value {
+            Some(value) => Nullable::NotNull(value),
+            None => Nullable::Null,
+        }
+    }
+}
 
-        self.sampled_position.position = new_pos;
-        self.sampled_position.updated_at = now;
+impl<'a, T> Handler for MediaPlaybackCluster<'a, T>
+where
+    T: MediaPlayer,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        MediaPlaybackCluster::read(self, attr, encoder)
+    }
 
-        Err(IMStatusCode::Success)
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        MediaPlaybackCluster::invoke(self, exchange, cmd, data, encoder)
     }
 
-    fn enocde_sampled_position(&self, tag: TagType, tw: &mut TLVWriter) {
-        let _ = tw.start_struct(tag);
-        let _ = tw.u64(TagType::Context(0), self.sampled_position.position);
-        let _ = tw.u64(TagType::Context(1), self.sampled_position.updated_at);
-        let _ = tw.end_container();
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
     }
 }
 
-// Commmands
-impl MediaPlaybackCluster {
-    fn handle_play(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-
-        self.run_callback(Commands::Play);
-        self.send_playback_response(CommandStatus::Success, cmd_req);
-        Err(IMStatusCode::Success)
-    }
-    fn handle_pause(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Paused as u8),
-        )?;
-
-        self.run_callback(Commands::Pause);
-        self.send_playback_response(CommandStatus::Success, cmd_req);
-        Err(IMStatusCode::Success)
-    }
-    fn handle_stop(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::NotPlaying as u8),
-        )?;
-
-        self.run_callback(Commands::Stop);
-        self.send_playback_response(CommandStatus::Success, cmd_req);
-        Err(IMStatusCode::Success)
-    }
-
-    // Start current thinbg over
-    fn handle_start_over(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-
-        self.update_position(0)?;
-        self.run_callback(Commands::StartOver);
-        self.send_playback_response(CommandStatus::Success, cmd_req);
-        Err(IMStatusCode::Success)
-    }
-
-    fn handle_next(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-        // self.update_position(0)?;
-        self.send_playback_response(CommandStatus::NotAllowed, cmd_req);
-        Err(IMStatusCode::UnsupportedCommand)
-    }
-
-    fn handle_previous(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-        // self.update_position(0)?;
-        self.send_playback_response(CommandStatus::NotAllowed, cmd_req);
-        Err(IMStatusCode::UnsupportedCommand)
-    }
-
-    fn handle_rewind(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-        self.send_playback_response(CommandStatus::NotAllowed, cmd_req);
-        Err(IMStatusCode::UnsupportedCommand)
-    }
-
-    fn handle_ff(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-        self.send_playback_response(CommandStatus::NotAllowed, cmd_req);
-        Err(IMStatusCode::UnsupportedCommand)
-    }
-
-    fn handle_skip_forward(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-        self.send_playback_response(CommandStatus::NotAllowed, cmd_req);
-        Err(IMStatusCode::UnsupportedCommand)
-    }
-
-    fn handle_skip_backward(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-        self.send_playback_response(CommandStatus::NotAllowed, cmd_req);
-        Err(IMStatusCode::UnsupportedCommand)
-    }
-
-    fn handle_seek(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        self.base.write_attribute_raw(
-            Attributes::CurrentState as u16,
-            AttrValue::Uint8(PlaybackState::Playing as u8),
-        )?;
-        self.send_playback_response(CommandStatus::NotAllowed, cmd_req);
-        Err(IMStatusCode::UnsupportedCommand)
-    }
-
-    // TODO: We send this to client
-    fn send_playback_response(&mut self, status: CommandStatus, cmd_req: &mut CommandReq) {
-        let mut playback_response = cmd_req.cmd;
-        playback_response.path.leaf = Some(Commands::PlaybackResponse as u32);
-
-        let resp = status.u8();
-        let cmd_data = |tag: TagType, t: &mut TLVWriter| {
-            let _ = t.start_struct(tag);
-            let _ = t.u8(TagType::Context(0), resp);
-            let _ = t.end_container();
-        };
+impl<'a, T> NonBlockingHandler for MediaPlaybackCluster<'a, T> where T: MediaPlayer {}
 
-        let invoke_resp = ib::InvResp::Cmd(ib::CmdData::new(
-            playback_response,
-            EncodeValue::Closure(&cmd_data),
-        ));
-        let _ = invoke_resp.to_tlv(cmd_req.resp, TagType::Anonymous);
-        cmd_req.trans.complete();
+impl<'a, T> ChangeNotifier<()> for MediaPlaybackCluster<'a, T> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
     }
 }
 
-impl ClusterType for MediaPlaybackCluster {
-    fn base(&self) -> &Cluster {
-        &self.base
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::{
+        tlv::Nullable,
+        utils::{
+            epoch::{dummy_epoch, UtcClock},
+            rand::dummy_rand,
+        },
+    };
+
+    use super::{MediaPlaybackCluster, MediaPlayer, PlaybackState, PlaybackStatus};
+
+    /// A player which records the last position it was asked to seek to
+    struct Player {
+        position: Cell<Option<u64>>,
     }
-    fn base_mut(&mut self) -> &mut Cluster {
-        &mut self.base
+
+    impl MediaPlayer for Player {
+        fn play(&self) -> Result<(), PlaybackStatus> {
+            Ok(())
+        }
+
+        fn pause(&self) -> Result<(), PlaybackStatus> {
+            Ok(())
+        }
+
+        fn stop(&self) -> Result<(), PlaybackStatus> {
+            Ok(())
+        }
+
+        fn seek(&self, position: u64) -> Result<(), PlaybackStatus> {
+            self.position.set(Some(position));
+            Ok(())
+        }
     }
 
-    fn read_custom_attribute(&self, encoder: &mut dyn Encoder, attr: &AttrDetails) {
-        match num::FromPrimitive::from_u16(attr.attr_id) {
-            Some(Attributes::SampledPosition) => {
-                encoder.encode(EncodeValue::Closure(&|tag, tw| {
-                    log::warn!("Encoding sampled position of self!");
-                    self.enocde_sampled_position(tag, tw)
-                }))
+    impl Player {
+        fn new() -> Self {
+            Self {
+                position: Cell::new(None),
             }
-            _ => log::error!("Attribute not supported!"),
         }
     }
 
-    fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        let cmd = cmd_req
-            .cmd
-            .path
-            .leaf
-            .map(num::FromPrimitive::from_u32)
-            .ok_or(IMStatusCode::UnsupportedCommand)?
-            .ok_or(IMStatusCode::UnsupportedCommand)?;
-
-        match cmd {
-            Commands::Play => self.handle_play(cmd_req),
-            Commands::Pause => self.handle_pause(cmd_req),
-            Commands::Stop => self.handle_stop(cmd_req),
-            Commands::StartOver => self.handle_start_over(cmd_req),
-            Commands::Previous => self.handle_previous(cmd_req),
-            Commands::Next => self.handle_next(cmd_req),
-            Commands::Rewind => self.handle_rewind(cmd_req),
-            Commands::FastForward => self.handle_ff(cmd_req),
-            Commands::SkipForward => self.handle_skip_forward(cmd_req),
-            Commands::SkipBackward => self.handle_skip_backward(cmd_req),
-            Commands::PlaybackResponse => Err(IMStatusCode::InvalidCommand),
-            Commands::Seek => self.handle_seek(cmd_req),
-        }
+    #[test]
+    fn test_play_pause_stop() {
+        let utc = UtcClock::new(dummy_epoch);
+        let playback = MediaPlaybackCluster::new(Player::new(), dummy_epoch, &utc, dummy_rand);
+
+        assert_eq!(playback.state(), PlaybackState::NotPlaying);
+        assert_eq!(playback.position(), None);
+
+        playback.set_media(None, Some(60_000));
+        assert_eq!(playback.play(), Ok(()));
+        assert_eq!(playback.state(), PlaybackState::Playing);
+
+        assert_eq!(playback.pause(), Ok(()));
+        assert_eq!(playback.state(), PlaybackState::Paused);
+
+        playback.seek(30_000).unwrap();
+        assert_eq!(playback.state(), PlaybackState::Paused);
+        assert_eq!(playback.position(), Some(30_000));
+
+        assert_eq!(playback.stop(), Ok(()));
+        assert_eq!(playback.state(), PlaybackState::NotPlaying);
+        assert_eq!(playback.position(), Some(0));
+
+        // Moving within the media needs it to be playing or paused
+        assert_eq!(playback.seek(1000), Err(PlaybackStatus::NotActive));
+        assert_eq!(
+            playback.skip(|position| position + 1000),
+            Err(PlaybackStatus::NotActive)
+        );
+    }
+
+    #[test]
+    fn test_seek() {
+        let utc = UtcClock::new(dummy_epoch);
+        let playback = MediaPlaybackCluster::new(Player::new(), dummy_epoch, &utc, dummy_rand);
+
+        playback.set_media(None, Some(60_000));
+        playback.play().unwrap();
+
+        assert_eq!(playback.seek(60_000), Ok(()));
+        assert_eq!(playback.player().position.get(), Some(60_000));
+        assert_eq!(playback.seek(60_001), Err(PlaybackStatus::SeekOutOfRange));
+        assert_eq!(playback.position(), Some(60_000));
+
+        // Any position is in range when the duration is unknown
+        playback.set_media(None, None);
+        assert_eq!(playback.seek(600_000), Ok(()));
+        assert_eq!(playback.position(), Some(600_000));
+    }
+
+    #[test]
+    fn test_skip() {
+        let utc = UtcClock::new(dummy_epoch);
+        let playback = MediaPlaybackCluster::new(Player::new(), dummy_epoch, &utc, dummy_rand);
+
+        playback.set_media(None, Some(60_000));
+        playback.play().unwrap();
+
+        playback.skip(|position| position + 10_000).unwrap();
+        assert_eq!(playback.position(), Some(10_000));
+
+        playback
+            .skip(|position| position.saturating_sub(20_000))
+            .unwrap();
+        assert_eq!(playback.position(), Some(0));
+
+        // Skipping past the end stops at the end
+        playback.skip(|position| position + 90_000).unwrap();
+        assert_eq!(playback.position(), Some(60_000));
+        assert_eq!(playback.player().position.get(), Some(60_000));
+    }
+
+    #[test]
+    fn test_sampled_position() {
+        let utc = UtcClock::new(dummy_epoch);
+        let playback = MediaPlaybackCluster::new(Player::new(), dummy_epoch, &utc, dummy_rand);
+
+        playback.set_media(None, Some(60_000));
+        playback.play().unwrap();
+
+        // Null until the UTC time is known
+        assert_eq!(playback.sampled_position(), None);
+
+        utc.set(1_000_000);
+        let sampled = playback.sampled_position().unwrap();
+        assert_eq!(sampled.updated_at, 1_000_000);
+        assert_eq!(sampled.position, Nullable::NotNull(0));
     }
 }
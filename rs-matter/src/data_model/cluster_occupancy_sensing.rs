@@ -0,0 +1,149 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::objects::*;
+use crate::{attribute_enum, error::Error, utils::rand::Rand};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0406;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    Occupancy(AttrType<u8>) = 0x0,
+    OccupancySensorType(AttrType<u8>) = 0x1,
+    OccupancySensorTypeBitmap(AttrType<u8>) = 0x2,
+}
+
+attribute_enum!(Attributes);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::Occupancy as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OccupancySensorType as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OccupancySensorTypeBitmap as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[],
+};
+
+/// Bit of the `Occupancy` attribute
+pub const OCCUPIED: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum OccupancySensorType {
+    PIR = 0,
+    Ultrasonic = 1,
+    PIRAndUltrasonic = 2,
+    PhysicalContact = 3,
+}
+
+impl OccupancySensorType {
+    /// The matching `OccupancySensorTypeBitmap`
+    fn bitmap(&self) -> u8 {
+        match self {
+            Self::PIR => 0x01,
+            Self::Ultrasonic => 0x02,
+            Self::PIRAndUltrasonic => 0x03,
+            Self::PhysicalContact => 0x04,
+        }
+    }
+}
+
+pub struct OccupancySensingCluster {
+    data_ver: Dataver,
+    sensor_type: OccupancySensorType,
+    occupied: Cell<bool>,
+}
+
+impl OccupancySensingCluster {
+    pub fn new(sensor_type: OccupancySensorType, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            sensor_type,
+            occupied: Cell::new(false),
+        }
+    }
+
+    pub fn occupied(&self) -> bool {
+        self.occupied.get()
+    }
+
+    pub fn set_occupied(&self, occupied: bool) {
+        if self.occupied.replace(occupied) != occupied {
+            self.data_ver.changed();
+        }
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::Occupancy(codec) => {
+                        codec.encode(writer, if self.occupied.get() { OCCUPIED } else { 0 })
+                    }
+                    Attributes::OccupancySensorType(codec) => {
+                        codec.encode(writer, self.sensor_type as _)
+                    }
+                    Attributes::OccupancySensorTypeBitmap(codec) => {
+                        codec.encode(writer, self.sensor_type.bitmap())
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Handler for OccupancySensingCluster {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        OccupancySensingCluster::read(self, attr, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl NonBlockingHandler for OccupancySensingCluster {}
+
+impl ChangeNotifier<()> for OccupancySensingCluster {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
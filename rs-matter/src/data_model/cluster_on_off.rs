@@ -17,12 +17,19 @@
 
 use core::{cell::Cell, convert::TryInto};
 
-use super::objects::*;
+use super::{
+    cluster_scenes_management::{AttributeValuePair, GlobalScene, SceneParticipant},
+    objects::*,
+};
 use crate::{
-    attribute_enum, cmd_enter, command_enum, error::Error, tlv::TLVElement,
-    transport::exchange::Exchange, utils::rand::Rand,
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, Nullable, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::exchange::Exchange,
+    utils::{rand::Rand, select::Notification},
 };
-use log::info;
+use embassy_time::{Duration, Timer};
+use log::{info, warn};
 use strum::{EnumDiscriminants, FromRepr};
 
 pub const ID: u32 = 0x0006;
@@ -31,6 +38,10 @@ pub const ID: u32 = 0x0006;
 #[repr(u16)]
 pub enum Attributes {
     OnOff(AttrType<bool>) = 0x0,
+    GlobalSceneControl(AttrType<bool>) = 0x4000,
+    OnTime(AttrType<u16>) = 0x4001,
+    OffWaitTime(AttrType<u16>) = 0x4002,
+    StartUpOnOff(AttrType<Nullable<u8>>) = 0x4003,
 }
 
 attribute_enum!(Attributes);
@@ -41,13 +52,22 @@ pub enum Commands {
     Off = 0x0,
     On = 0x01,
     Toggle = 0x02,
+    OffWithEffect = 0x40,
+    OnWithRecallGlobalScene = 0x41,
+    OnWithTimedOff = 0x42,
 }
 
 command_enum!(Commands);
 
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    Lighting = 0x01,
+}
+
 pub const CLUSTER: Cluster<'static> = Cluster {
     id: ID as _,
-    feature_map: 0,
+    feature_map: Feature::Lighting as _,
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
@@ -56,27 +76,106 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             Access::RV,
             Quality::SN,
         ),
+        Attribute::new(
+            AttributesDiscriminants::GlobalSceneControl as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OnTime as u16,
+            Access::RWVO,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OffWaitTime as u16,
+            Access::RWVO,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::StartUpOnOff as u16,
+            Access::RWVM,
+            Quality::N.union(Quality::X),
+        ),
     ],
     commands: &[
         CommandsDiscriminants::Off as _,
         CommandsDiscriminants::On as _,
         CommandsDiscriminants::Toggle as _,
+        CommandsDiscriminants::OffWithEffect as _,
+        CommandsDiscriminants::OnWithRecallGlobalScene as _,
+        CommandsDiscriminants::OnWithTimedOff as _,
     ],
 };
 
-pub struct OnOffCluster {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum StartUpOnOff {
+    Off = 0,
+    On = 1,
+    Toggle = 2,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct OffWithEffectReq {
+    pub effect_identifier: u8,
+    pub effect_variant: u8,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct OnWithTimedOffReq {
+    pub on_off_control: u8,
+    pub on_time: u16,
+    pub off_wait_time: u16,
+}
+
+pub const ACCEPT_ONLY_WHEN_ON: u8 = 0x01;
+
+/// Lets another cluster of the same endpoint take part in switching it on and off
+///
+/// This is how the Level Control cluster implements the On/Off transitions of the
+/// Lighting feature.
+pub trait OnOffCoupling {
+    /// Called after the cluster was switched on by a command or by `OnTime` expiring
+    fn switch_on(&self);
+
+    /// Called when the cluster is about to be switched off by a command
+    ///
+    /// Returning `true` means that the coupled cluster takes over, and will call
+    /// `OnOffCluster::set(false)` itself once done.
+    fn switch_off(&self) -> bool;
+}
+
+pub struct OnOffCluster<'a> {
     data_ver: Dataver,
     on: Cell<bool>,
+    global_scene_control: Cell<bool>,
+    on_time: Cell<u16>,
+    off_wait_time: Cell<u16>,
+    start_up_on_off: Cell<Option<StartUpOnOff>>,
+    coupling: Cell<Option<&'a dyn OnOffCoupling>>,
+    global_scene: Cell<Option<&'a dyn GlobalScene>>,
+    timer_notification: Notification,
 }
 
-impl OnOffCluster {
+impl<'a> OnOffCluster<'a> {
     pub fn new(rand: Rand) -> Self {
         Self {
             data_ver: Dataver::new(rand),
             on: Cell::new(false),
+            global_scene_control: Cell::new(true),
+            on_time: Cell::new(0),
+            off_wait_time: Cell::new(0),
+            start_up_on_off: Cell::new(None),
+            coupling: Cell::new(None),
+            global_scene: Cell::new(None),
+            timer_notification: Notification::new(),
         }
     }
 
+    pub fn get(&self) -> bool {
+        self.on.get()
+    }
+
     pub fn set(&self, on: bool) {
         if self.on.get() != on {
             self.on.set(on);
@@ -84,6 +183,45 @@ impl OnOffCluster {
         }
     }
 
+    pub fn set_coupling(&self, coupling: Option<&'a dyn OnOffCoupling>) {
+        self.coupling.set(coupling);
+    }
+
+    /// Set the global scene stored by `OffWithEffect` and recalled by
+    /// `OnWithRecallGlobalScene`, usually the Scenes Management cluster of the endpoint
+    ///
+    /// Without a global scene, these commands only switch the cluster off and on.
+    pub fn set_global_scene(&self, global_scene: Option<&'a dyn GlobalScene>) {
+        self.global_scene.set(global_scene);
+    }
+
+    /// Apply the `StartUpOnOff` attribute
+    ///
+    /// Should be called once on power up, after the persisted attributes were loaded.
+    pub fn start_up(&self) {
+        match self.start_up_on_off.get() {
+            Some(StartUpOnOff::Off) => self.set(false),
+            Some(StartUpOnOff::On) => self.set(true),
+            Some(StartUpOnOff::Toggle) => self.set(!self.on.get()),
+            None => (),
+        }
+    }
+
+    /// Count down the `OnTime` and `OffWaitTime` attributes
+    ///
+    /// Needs to be running for `OnWithTimedOff` to switch the cluster off.
+    pub async fn run(&self) -> Result<(), Error> {
+        loop {
+            if self.on_time.get() > 0 || self.off_wait_time.get() > 0 {
+                Timer::after(Duration::from_millis(100)).await;
+
+                self.tick();
+            } else {
+                self.timer_notification.wait().await;
+            }
+        }
+    }
+
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
             if attr.is_system() {
@@ -91,6 +229,20 @@ impl OnOffCluster {
             } else {
                 match attr.attr_id.try_into()? {
                     Attributes::OnOff(codec) => codec.encode(writer, self.on.get()),
+                    Attributes::GlobalSceneControl(codec) => {
+                        codec.encode(writer, self.global_scene_control.get())
+                    }
+                    Attributes::OnTime(codec) => codec.encode(writer, self.on_time.get()),
+                    Attributes::OffWaitTime(codec) => {
+                        codec.encode(writer, self.off_wait_time.get())
+                    }
+                    Attributes::StartUpOnOff(codec) => codec.encode(
+                        writer,
+                        match self.start_up_on_off.get() {
+                            Some(start_up) => Nullable::NotNull(start_up as _),
+                            None => Nullable::Null,
+                        },
+                    ),
                 }
             }
         } else {
@@ -103,6 +255,25 @@ impl OnOffCluster {
 
         match attr.attr_id.try_into()? {
             Attributes::OnOff(codec) => self.set(codec.decode(data)?),
+            Attributes::GlobalSceneControl(_) => Err(ErrorCode::InvalidAction)?,
+            Attributes::OnTime(codec) => {
+                self.on_time.set(codec.decode(data)?);
+                self.timer_notification.signal(());
+            }
+            Attributes::OffWaitTime(codec) => {
+                self.off_wait_time.set(codec.decode(data)?);
+                self.timer_notification.signal(());
+            }
+            Attributes::StartUpOnOff(codec) => {
+                let start_up = match codec.decode(data)? {
+                    Nullable::NotNull(start_up) => {
+                        Some(StartUpOnOff::from_repr(start_up).ok_or(ErrorCode::ConstraintError)?)
+                    }
+                    Nullable::Null => None,
+                };
+
+                self.start_up_on_off.set(start_up);
+            }
         }
 
         self.data_ver.changed();
@@ -112,23 +283,75 @@ impl OnOffCluster {
 
     pub fn invoke(
         &self,
-        _exchange: &Exchange,
+        exchange: &Exchange,
         cmd: &CmdDetails,
-        _data: &TLVElement,
+        data: &TLVElement,
         _encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
         match cmd.cmd_id.try_into()? {
             Commands::Off => {
                 cmd_enter!("Off");
-                self.set(false);
+                self.off();
             }
             Commands::On => {
                 cmd_enter!("On");
-                self.set(true);
+                self.on();
             }
             Commands::Toggle => {
                 cmd_enter!("Toggle");
-                self.set(!self.on.get());
+                if self.on.get() {
+                    self.off();
+                } else {
+                    self.on();
+                }
+            }
+            Commands::OffWithEffect => {
+                cmd_enter!("OffWithEffect");
+                let req = OffWithEffectReq::from_tlv(data)?;
+
+                info!(
+                    "Off with effect {}, variant {}",
+                    req.effect_identifier, req.effect_variant
+                );
+
+                if self.global_scene_control.get() {
+                    if let Some(global_scene) = self.global_scene.get() {
+                        let fab_idx = exchange.accessor()?.fab_idx;
+                        let endpoint = cmd
+                            .node
+                            .check_endpoint(cmd.endpoint_id)
+                            .map_err(|_| ErrorCode::EndpointNotFound)?;
+
+                        // Switching off does not depend on the scene table having room
+                        if let Err(e) = global_scene.store_global_scene(endpoint, fab_idx) {
+                            warn!("Failed to store the global scene: {:?}", e);
+                        }
+                    }
+                }
+
+                self.global_scene_control.set(false);
+                self.off();
+            }
+            Commands::OnWithRecallGlobalScene => {
+                cmd_enter!("OnWithRecallGlobalScene");
+
+                if !self.global_scene_control.get() {
+                    self.on();
+
+                    if let Some(global_scene) = self.global_scene.get() {
+                        let fab_idx = exchange.accessor()?.fab_idx;
+
+                        if let Err(e) = global_scene.recall_global_scene(fab_idx) {
+                            warn!("Failed to recall the global scene: {:?}", e);
+                        }
+                    }
+                }
+            }
+            Commands::OnWithTimedOff => {
+                cmd_enter!("OnWithTimedOff");
+                let req = OnWithTimedOffReq::from_tlv(data)?;
+
+                self.on_with_timed_off(&req);
             }
         }
 
@@ -136,9 +359,111 @@ impl OnOffCluster {
 
         Ok(())
     }
+
+    fn on(&self) {
+        if self.on_time.get() == 0 {
+            self.off_wait_time.set(0);
+        }
+
+        self.global_scene_control.set(true);
+        self.switch(true);
+    }
+
+    fn off(&self) {
+        self.on_time.set(0);
+        self.switch(false);
+    }
+
+    fn on_with_timed_off(&self, req: &OnWithTimedOffReq) {
+        let on = self.on.get();
+
+        if req.on_off_control & ACCEPT_ONLY_WHEN_ON != 0 && !on {
+            return;
+        }
+
+        if !on && self.off_wait_time.get() > 0 {
+            self.off_wait_time
+                .set(self.off_wait_time.get().min(req.off_wait_time));
+        } else {
+            self.on_time.set(self.on_time.get().max(req.on_time));
+            self.off_wait_time.set(req.off_wait_time);
+            self.switch(true);
+        }
+
+        self.timer_notification.signal(());
+    }
+
+    fn switch(&self, on: bool) {
+        let coupling = self.coupling.get();
+
+        if on {
+            self.set(true);
+
+            if let Some(coupling) = coupling {
+                coupling.switch_on();
+            }
+        } else if !coupling
+            .map(|coupling| coupling.switch_off())
+            .unwrap_or(false)
+        {
+            self.set(false);
+        }
+    }
+
+    // Called every 1/10th of a second, which is the unit of `OnTime` and `OffWaitTime`
+    //
+    // The countdown is reported once per second only, and when it reaches 0.
+    fn tick(&self) {
+        if self.on.get() {
+            let on_time = self.on_time.get();
+
+            if on_time > 0 {
+                self.on_time.set(on_time - 1);
+
+                if on_time == 1 {
+                    self.off_wait_time.set(0);
+                    self.switch(false);
+                }
+
+                if (on_time - 1) % 10 == 0 {
+                    self.data_ver.changed();
+                }
+            }
+        } else {
+            let off_wait_time = self.off_wait_time.get();
+
+            if off_wait_time > 0 {
+                self.off_wait_time.set(off_wait_time - 1);
+
+                if off_wait_time == 1 {
+                    self.on_time.set(0);
+                }
+
+                if (off_wait_time - 1) % 10 == 0 {
+                    self.data_ver.changed();
+                }
+            }
+        }
+    }
+}
+
+// There are no On/Off transitions, so a Level Control cluster of the same endpoint
+// is the one fading the light in and out when recalling a scene
+impl<'a> SceneParticipant for OnOffCluster<'a> {
+    fn scene_value(&self, attr_id: AttrId) -> Option<u32> {
+        (attr_id == AttributesDiscriminants::OnOff as AttrId).then(|| self.on.get() as _)
+    }
+
+    fn recall_scene(&self, values: &[AttributeValuePair], _transition_time: Duration) {
+        for value in values {
+            if value.attribute_id == AttributesDiscriminants::OnOff as u32 {
+                self.set(value.attribute_value != 0);
+            }
+        }
+    }
 }
 
-impl Handler for OnOffCluster {
+impl<'a> Handler for OnOffCluster<'a> {
     fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         OnOffCluster::read(self, attr, encoder)
     }
@@ -156,12 +481,16 @@ impl Handler for OnOffCluster {
     ) -> Result<(), Error> {
         OnOffCluster::invoke(self, exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 // TODO: Might be removed once the `on` member is externalized
-impl NonBlockingHandler for OnOffCluster {}
+impl<'a> NonBlockingHandler for OnOffCluster<'a> {}
 
-impl ChangeNotifier<()> for OnOffCluster {
+impl<'a> ChangeNotifier<()> for OnOffCluster<'a> {
     fn consume_change(&mut self) -> Option<()> {
         self.data_ver.consume_change(())
     }
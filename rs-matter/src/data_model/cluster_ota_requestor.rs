@@ -0,0 +1,1299 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+};
+
+use super::{cluster_basic_information::BasicInfoConfig, objects::*, persist::StatePersist};
+use crate::{
+    attribute_enum,
+    bdx::receiver::{receive, BdxReceiver, ImageSink},
+    cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    fabric,
+    interaction_model::{
+        client::{InvokeClient, NodeResolver},
+        messages::ib::{attr_list_write, ListOperation},
+    },
+    tlv::{
+        self, FromTLV, Nullable, OctetStr, TLVArray, TLVElement, TLVList, TLVWriter, TagType,
+        ToTLV, UtfStr,
+    },
+    transport::{exchange::Exchange, packet::Packet},
+    utils::{rand::Rand, select::Notification, writebuf::WriteBuf},
+};
+use embassy_time::{Duration, Timer};
+use log::{info, warn};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x002A;
+
+/// The OTA Provider cluster, whose commands the requestor sends
+pub const PROVIDER_ID: u32 = 0x0029;
+
+/// Default OTA Providers are limited to one per fabric
+const MAX_PROVIDERS: usize = fabric::MAX_SUPPORTED_FABRICS;
+
+pub const MAX_UPDATE_TOKEN_LEN: usize = 32;
+pub const MAX_FILE_DESIGNATOR_LEN: usize = 64;
+
+const BDX_SCHEME: &str = "bdx://";
+
+const PROTOCOLS_SUPPORTED: &[u8] = &[DownloadProtocol::BDXSynchronous as u8];
+
+// The provider is not to be asked again sooner than 2 minutes after a delayed action
+const MIN_DELAYED_ACTION_TIME: u32 = 120;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    DefaultOTAProviders(()) = 0x0000,
+    UpdatePossible(AttrType<bool>) = 0x0001,
+    UpdateState(AttrType<u8>) = 0x0002,
+    UpdateStateProgress(AttrType<Nullable<u8>>) = 0x0003,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    AnnounceOTAProvider = 0x00,
+}
+
+command_enum!(Commands);
+
+/// Commands of the OTA Provider cluster
+#[derive(FromRepr)]
+#[repr(u32)]
+pub enum ProviderCommands {
+    QueryImage = 0x00,
+    ApplyUpdateRequest = 0x02,
+    NotifyUpdateApplied = 0x04,
+}
+
+/// Responses of the OTA Provider cluster
+#[derive(FromRepr)]
+#[repr(u16)]
+pub enum ProviderRespCommands {
+    QueryImageResponse = 0x01,
+    ApplyUpdateResponse = 0x03,
+}
+
+/// Events of this cluster
+#[derive(FromRepr)]
+#[repr(u32)]
+pub enum Events {
+    StateTransition = 0x00,
+    VersionApplied = 0x01,
+    DownloadError = 0x02,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::DefaultOTAProviders as u16,
+            Access::RWFVA,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::UpdatePossible as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::UpdateState as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::UpdateStateProgress as u16,
+            Access::RV,
+            Quality::X,
+        ),
+    ],
+    commands: &[CommandsDiscriminants::AnnounceOTAProvider as _],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum UpdateState {
+    Unknown = 0,
+    Idle = 1,
+    Querying = 2,
+    DelayedOnQuery = 3,
+    Downloading = 4,
+    Applying = 5,
+    DelayedOnApply = 6,
+    RollingBack = 7,
+    DelayedOnUserConsent = 8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum AnnouncementReason {
+    SimpleAnnouncement = 0,
+    UpdateAvailable = 1,
+    UrgentUpdateAvailable = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum DownloadProtocol {
+    BDXSynchronous = 0,
+    BDXAsynchronous = 1,
+    HTTPS = 2,
+    VendorSpecific = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum QueryImageStatus {
+    UpdateAvailable = 0,
+    Busy = 1,
+    NotAvailable = 2,
+    DownloadProtocolNotSupported = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum ApplyUpdateAction {
+    Proceed = 0,
+    AwaitNextAction = 1,
+    Discontinue = 2,
+}
+
+/// An entry of the `DefaultOTAProviders` attribute
+#[derive(ToTLV, FromTLV, Clone, Copy, Debug, PartialEq, Eq)]
+#[tlvargs(start = 1)]
+pub struct ProviderLocation {
+    pub provider_node_id: u64,
+    pub endpoint: EndptId,
+    #[tagval(0xFE)]
+    pub fab_idx: Option<u8>,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct AnnounceOTAProviderReq<'a> {
+    pub provider_node_id: u64,
+    pub vendor_id: u16,
+    pub announcement_reason: u8,
+    pub metadata_for_node: Option<OctetStr<'a>>,
+    pub endpoint: EndptId,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct QueryImageReq<'a> {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub software_version: u32,
+    pub protocols_supported: TLVArray<'a, u8>,
+    pub hardware_version: Option<u16>,
+    pub location: Option<UtfStr<'a>>,
+    pub requestor_can_consent: Option<bool>,
+    pub metadata_for_provider: Option<OctetStr<'a>>,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct QueryImageResp<'a> {
+    pub status: u8,
+    pub delayed_action_time: Option<u32>,
+    pub image_uri: Option<UtfStr<'a>>,
+    pub software_version: Option<u32>,
+    pub software_version_string: Option<UtfStr<'a>>,
+    pub update_token: Option<OctetStr<'a>>,
+    pub user_consent_needed: Option<bool>,
+    pub metadata_for_requestor: Option<OctetStr<'a>>,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct ApplyUpdateReq<'a> {
+    pub update_token: OctetStr<'a>,
+    pub new_version: u32,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct ApplyUpdateResp {
+    pub action: u8,
+    pub delayed_action_time: u32,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct NotifyUpdateAppliedReq<'a> {
+    pub update_token: OctetStr<'a>,
+    pub software_version: u32,
+}
+
+/// An image offered by an OTA Provider in its `QueryImageResponse`
+///
+/// Needs to be kept - and persisted by the application across the reboot into the new
+/// image - until the provider was notified that the update was applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateImage {
+    /// The node to download the image from over BDX, usually the provider itself
+    pub node_id: u64,
+    pub file_designator: heapless::String<MAX_FILE_DESIGNATOR_LEN>,
+    pub software_version: u32,
+    pub update_token: heapless::Vec<u8, MAX_UPDATE_TOKEN_LEN>,
+}
+
+impl UpdateImage {
+    pub fn apply_update_req(&self) -> ApplyUpdateReq<'_> {
+        ApplyUpdateReq {
+            update_token: OctetStr(&self.update_token),
+            new_version: self.software_version,
+        }
+    }
+
+    pub fn notify_update_applied_req(&self) -> NotifyUpdateAppliedReq<'_> {
+        NotifyUpdateAppliedReq {
+            update_token: OctetStr(&self.update_token),
+            software_version: self.software_version,
+        }
+    }
+
+    // Parse an image URI of the form bdx://<node ID in hex>/<file designator>
+    fn parse_uri(uri: &str) -> Result<(u64, &str), Error> {
+        let (node_id, file_designator) = uri
+            .strip_prefix(BDX_SCHEME)
+            .and_then(|rest| rest.split_once('/'))
+            .ok_or(ErrorCode::Invalid)?;
+
+        if node_id.len() != 16 || file_designator.is_empty() {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        let node_id = u64::from_str_radix(node_id, 16).map_err(|_| ErrorCode::Invalid)?;
+
+        Ok((node_id, file_designator))
+    }
+}
+
+/// The OTA Requestor
+///
+/// `update` runs an update with the provider from `provider` over an `InvokeClient`:
+/// - `QueryImage`, waiting and querying again while the provider is busy
+/// - the BDX download of the image into an `ImageSink`
+/// - `ApplyUpdateRequest`, waiting and asking again while the provider says so
+///
+/// Once the application installed the image and rebooted into it, `notify_update_applied`
+/// sends `NotifyUpdateApplied`. Each of these steps is also available on its own, with
+/// the requests to send and the handling of the responses separated.
+///
+/// The Default OTA Providers of all fabrics are persisted through `StatePersist`.
+pub struct OtaRequestorCluster {
+    data_ver: Dataver,
+    vendor_id: u16,
+    product_id: u16,
+    hardware_version: u16,
+    software_version: Cell<u32>,
+    providers: RefCell<heapless::Vec<ProviderLocation, MAX_PROVIDERS>>,
+    providers_changed: Cell<bool>,
+    announced: Cell<Option<ProviderLocation>>,
+    announcement: Notification,
+    update_possible: Cell<bool>,
+    update_state: Cell<UpdateState>,
+    update_state_progress: Cell<Option<u8>>,
+}
+
+impl OtaRequestorCluster {
+    pub fn new(dev_det: &BasicInfoConfig, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            vendor_id: dev_det.vid,
+            product_id: dev_det.pid,
+            hardware_version: dev_det.hw_ver,
+            software_version: Cell::new(dev_det.sw_ver),
+            providers: RefCell::new(heapless::Vec::new()),
+            providers_changed: Cell::new(false),
+            announced: Cell::new(None),
+            announcement: Notification::new(),
+            update_possible: Cell::new(true),
+            update_state: Cell::new(UpdateState::Idle),
+            update_state_progress: Cell::new(None),
+        }
+    }
+
+    pub fn update_state(&self) -> UpdateState {
+        self.update_state.get()
+    }
+
+    pub fn update_state_progress(&self) -> Option<u8> {
+        self.update_state_progress.get()
+    }
+
+    /// Whether an update could be applied, e.g. it is not possible when the battery is low
+    pub fn set_update_possible(&self, possible: bool) {
+        if self.update_possible.get() != possible {
+            self.update_possible.set(possible);
+            self.data_ver.changed();
+        }
+    }
+
+    /// The provider to query: the one which announced itself last, otherwise the
+    /// default provider of the first fabric having one
+    pub fn provider(&self) -> Option<ProviderLocation> {
+        self.announced
+            .get()
+            .or_else(|| self.providers.borrow().first().copied())
+    }
+
+    /// Wait until a provider announces itself with `AnnounceOTAProvider`
+    pub async fn wait_announcement(&self) -> ProviderLocation {
+        loop {
+            self.announcement.wait().await;
+
+            if let Some(provider) = self.announced.get() {
+                break provider;
+            }
+        }
+    }
+
+    /// Run an update with the provider from `provider`
+    ///
+    /// Returns the image once the provider allows applying it, for the application to
+    /// install it and reboot into it. Returns `None` if there is no update, or if the
+    /// provider discontinued it.
+    pub async fn update<R, S>(
+        &self,
+        client: &mut InvokeClient<'_, R>,
+        sink: S,
+        max_block_size: u16,
+    ) -> Result<Option<UpdateImage>, Error>
+    where
+        R: NodeResolver,
+        S: ImageSink,
+    {
+        let provider = self.provider().ok_or(ErrorCode::NotFound)?;
+
+        let result = self
+            .run_update(client, &provider, sink, max_block_size)
+            .await;
+
+        if result.is_err() {
+            self.set_update_state(UpdateState::Idle, None);
+        }
+
+        result
+    }
+
+    /// Tell the provider from `provider` that the device runs the software of `image`
+    ///
+    /// To be called after the reboot into the image returned by `update`.
+    pub async fn notify_update_applied<R>(
+        &self,
+        client: &mut InvokeClient<'_, R>,
+        image: &UpdateImage,
+    ) -> Result<(), Error>
+    where
+        R: NodeResolver,
+    {
+        let provider = self.provider().ok_or(ErrorCode::NotFound)?;
+
+        client
+            .invoke(
+                provider.fab_idx.ok_or(ErrorCode::NotFound)?,
+                provider.provider_node_id,
+                provider.endpoint,
+                PROVIDER_ID,
+                ProviderCommands::NotifyUpdateApplied as _,
+                &self.update_applied(image),
+            )
+            .await
+    }
+
+    /// Start querying a provider for an update
+    pub fn query_image(&self) -> Result<QueryImageReq<'static>, Error> {
+        if !self.update_possible.get() {
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        if matches!(
+            self.update_state.get(),
+            UpdateState::Querying | UpdateState::Downloading | UpdateState::Applying
+        ) {
+            Err(ErrorCode::Busy)?;
+        }
+
+        self.set_update_state(UpdateState::Querying, None);
+
+        Ok(QueryImageReq {
+            vendor_id: self.vendor_id,
+            product_id: self.product_id,
+            software_version: self.software_version.get(),
+            protocols_supported: TLVArray::new(PROTOCOLS_SUPPORTED),
+            hardware_version: Some(self.hardware_version),
+            location: None,
+            requestor_can_consent: None,
+            metadata_for_provider: None,
+        })
+    }
+
+    /// Handle the `QueryImageResponse` of the provider
+    ///
+    /// Returns the image to download if there is an update; otherwise, the provider is
+    /// to be queried again later - after `delayed_action_time` if it was busy.
+    pub fn handle_query_image_resp(
+        &self,
+        resp: &QueryImageResp,
+    ) -> Result<Option<UpdateImage>, Error> {
+        if self.update_state.get() != UpdateState::Querying {
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        match QueryImageStatus::from_repr(resp.status) {
+            Some(QueryImageStatus::UpdateAvailable) => match self.update_image(resp) {
+                Ok(Some(image)) => {
+                    info!(
+                        "Update to version {} available at {:x}",
+                        image.software_version, image.node_id
+                    );
+                    self.set_update_state(UpdateState::Downloading, Some(0));
+
+                    Ok(Some(image))
+                }
+                Ok(None) => {
+                    self.set_update_state(UpdateState::Idle, None);
+
+                    Ok(None)
+                }
+                Err(err) => {
+                    warn!("Invalid QueryImageResponse: {:?}", err);
+                    self.set_update_state(UpdateState::Idle, None);
+
+                    Err(err)
+                }
+            },
+            Some(QueryImageStatus::Busy) => {
+                self.set_update_state(UpdateState::DelayedOnQuery, None);
+
+                Ok(None)
+            }
+            _ => {
+                self.set_update_state(UpdateState::Idle, None);
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// Download `image` into `sink` over BDX
+    ///
+    /// The exchange - and the session beneath it - has to be established with the node
+    /// of the image by the caller.
+    pub async fn download<S>(
+        &self,
+        exchange: &mut Exchange<'_>,
+        tx: &mut Packet<'_>,
+        rx: &mut Packet<'_>,
+        image: &UpdateImage,
+        sink: S,
+        max_block_size: u16,
+    ) -> Result<(), Error>
+    where
+        S: ImageSink,
+    {
+        if self.update_state.get() != UpdateState::Downloading {
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        let mut receiver = BdxReceiver::new(
+            ProgressSink {
+                sink,
+                cluster: self,
+                length: None,
+            },
+            max_block_size,
+        );
+
+        match receive(
+            exchange,
+            tx,
+            rx,
+            &mut receiver,
+            image.file_designator.as_bytes(),
+        )
+        .await
+        {
+            Ok(received) => {
+                info!("Downloaded {} bytes of the update", received);
+                self.set_update_state(UpdateState::Downloading, Some(100));
+
+                Ok(())
+            }
+            Err(err) => {
+                warn!("Downloading the update failed: {:?}", err);
+                self.set_update_state(UpdateState::Idle, None);
+
+                Err(err)
+            }
+        }
+    }
+
+    /// Ask the provider whether to apply the downloaded `image`
+    pub fn apply_update<'a>(&self, image: &'a UpdateImage) -> Result<ApplyUpdateReq<'a>, Error> {
+        if !matches!(
+            self.update_state.get(),
+            UpdateState::Downloading | UpdateState::DelayedOnApply
+        ) {
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        self.set_update_state(UpdateState::Applying, None);
+
+        Ok(image.apply_update_req())
+    }
+
+    /// Handle the `ApplyUpdateResponse` of the provider
+    ///
+    /// On `Proceed` the application installs the image and reboots into it; on
+    /// `AwaitNextAction` it asks again after `delayed_action_time`.
+    pub fn handle_apply_update_resp(
+        &self,
+        resp: &ApplyUpdateResp,
+    ) -> Result<ApplyUpdateAction, Error> {
+        if self.update_state.get() != UpdateState::Applying {
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        let action = ApplyUpdateAction::from_repr(resp.action).ok_or(ErrorCode::Invalid)?;
+
+        match action {
+            ApplyUpdateAction::Proceed => (),
+            ApplyUpdateAction::AwaitNextAction => {
+                self.set_update_state(UpdateState::DelayedOnApply, None)
+            }
+            ApplyUpdateAction::Discontinue => self.set_update_state(UpdateState::Idle, None),
+        }
+
+        Ok(action)
+    }
+
+    /// The device runs the software of `image`, which the provider needs to be told about
+    pub fn update_applied<'a>(&self, image: &'a UpdateImage) -> NotifyUpdateAppliedReq<'a> {
+        info!("Running updated version {}", image.software_version);
+
+        self.software_version.set(image.software_version);
+        self.set_update_state(UpdateState::Idle, None);
+
+        image.notify_update_applied_req()
+    }
+
+    /// Remove the provider locations of a fabric which was removed
+    pub fn remove_fabric(&self, fab_idx: u8) {
+        self.providers
+            .borrow_mut()
+            .retain(|provider| provider.fab_idx != Some(fab_idx));
+        self.providers_changed.set(true);
+
+        if matches!(self.announced.get(), Some(provider) if provider.fab_idx == Some(fab_idx)) {
+            self.announced.set(None);
+        }
+
+        self.data_ver.changed();
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::DefaultOTAProviders(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for provider in self.providers.borrow().iter() {
+                            if !attr.fab_filter || Some(attr.fab_idx) == provider.fab_idx {
+                                provider.to_tlv(&mut writer, TagType::Anonymous)?;
+                            }
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                    Attributes::UpdatePossible(codec) => {
+                        codec.encode(writer, self.update_possible.get())
+                    }
+                    Attributes::UpdateState(codec) => {
+                        codec.encode(writer, self.update_state.get() as _)
+                    }
+                    Attributes::UpdateStateProgress(codec) => codec.encode(
+                        writer,
+                        match self.update_state_progress.get() {
+                            Some(progress) => Nullable::NotNull(progress),
+                            None => Nullable::Null,
+                        },
+                    ),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        match attr.attr_id.try_into()? {
+            Attributes::DefaultOTAProviders(_) => {
+                attr_list_write(attr, data.with_dataver(self.data_ver.get())?, |op, data| {
+                    self.write_providers_attr(&op, data, attr.fab_idx)
+                })?;
+            }
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        self.providers_changed.set(true);
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        _encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::AnnounceOTAProvider => {
+                cmd_enter!("AnnounceOTAProvider");
+                let req = AnnounceOTAProviderReq::from_tlv(data)?;
+
+                self.announce(&req, exchange.accessor()?.fab_idx)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn announce(&self, req: &AnnounceOTAProviderReq, fab_idx: u8) -> Result<(), Error> {
+        let reason = AnnouncementReason::from_repr(req.announcement_reason)
+            .ok_or(ErrorCode::ConstraintError)?;
+
+        info!(
+            "OTA Provider {:x}/{} announced itself: {:?}",
+            req.provider_node_id, req.endpoint, reason
+        );
+
+        self.announced.set(Some(ProviderLocation {
+            provider_node_id: req.provider_node_id,
+            endpoint: req.endpoint,
+            fab_idx: Some(fab_idx),
+        }));
+        self.announcement.signal(());
+
+        Ok(())
+    }
+
+    /// Write the DefaultOTAProviders attribute
+    ///
+    /// Indices are relative to the providers of the accessing fabric.
+    fn write_providers_attr(
+        &self,
+        op: &ListOperation,
+        data: &TLVElement,
+        fab_idx: u8,
+    ) -> Result<(), Error> {
+        info!("Performing DefaultOTAProviders operation {:?}", op);
+
+        if fab_idx == 0 {
+            Err(ErrorCode::UnsupportedAccess)?;
+        }
+
+        let mut providers = self.providers.borrow_mut();
+
+        match op {
+            ListOperation::AddItem | ListOperation::EditItem(_) => {
+                let mut provider = ProviderLocation::from_tlv(data)?;
+                provider.fab_idx = Some(fab_idx);
+
+                let position = providers.iter().position(|p| p.fab_idx == Some(fab_idx));
+
+                match (op, position) {
+                    (ListOperation::EditItem(0), Some(position)) => providers[position] = provider,
+                    (ListOperation::EditItem(_), _) => Err(ErrorCode::NotFound)?,
+                    // Only one provider per fabric
+                    (_, Some(_)) => Err(ErrorCode::ConstraintError)?,
+                    (_, None) => providers.push(provider).map_err(|_| ErrorCode::NoSpace)?,
+                }
+            }
+            ListOperation::DeleteItem(index) => {
+                let position = providers
+                    .iter()
+                    .position(|p| p.fab_idx == Some(fab_idx))
+                    .filter(|_| *index == 0)
+                    .ok_or(ErrorCode::NotFound)?;
+
+                providers.remove(position);
+            }
+            ListOperation::DeleteList => {
+                providers.retain(|p| p.fab_idx != Some(fab_idx));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_update<R, S>(
+        &self,
+        client: &mut InvokeClient<'_, R>,
+        provider: &ProviderLocation,
+        sink: S,
+        max_block_size: u16,
+    ) -> Result<Option<UpdateImage>, Error>
+    where
+        R: NodeResolver,
+        S: ImageSink,
+    {
+        let fab_idx = provider.fab_idx.ok_or(ErrorCode::NotFound)?;
+
+        let image = loop {
+            let (image, delayed_action_time) = client
+                .invoke_with(
+                    fab_idx,
+                    provider.provider_node_id,
+                    provider.endpoint,
+                    PROVIDER_ID,
+                    ProviderCommands::QueryImage as _,
+                    &self.query_image()?,
+                    |data| {
+                        let resp = QueryImageResp::from_tlv(data.ok_or(ErrorCode::Invalid)?)?;
+
+                        Ok((
+                            self.handle_query_image_resp(&resp)?,
+                            resp.delayed_action_time,
+                        ))
+                    },
+                )
+                .await?;
+
+            match image {
+                Some(image) => break image,
+                None if self.update_state.get() == UpdateState::DelayedOnQuery => {
+                    Self::delay(delayed_action_time.unwrap_or(0)).await
+                }
+                None => return Ok(None),
+            }
+        };
+
+        {
+            let (mut exchange, mut tx, mut rx) = client.initiate(fab_idx, image.node_id).await?;
+
+            self.download(
+                &mut exchange,
+                &mut tx,
+                &mut rx,
+                &image,
+                sink,
+                max_block_size,
+            )
+            .await?;
+        }
+
+        loop {
+            let (action, delayed_action_time) = client
+                .invoke_with(
+                    fab_idx,
+                    provider.provider_node_id,
+                    provider.endpoint,
+                    PROVIDER_ID,
+                    ProviderCommands::ApplyUpdateRequest as _,
+                    &self.apply_update(&image)?,
+                    |data| {
+                        let resp = ApplyUpdateResp::from_tlv(data.ok_or(ErrorCode::Invalid)?)?;
+
+                        Ok((
+                            self.handle_apply_update_resp(&resp)?,
+                            resp.delayed_action_time,
+                        ))
+                    },
+                )
+                .await?;
+
+            match action {
+                ApplyUpdateAction::Proceed => break Ok(Some(image)),
+                ApplyUpdateAction::AwaitNextAction => Self::delay(delayed_action_time).await,
+                ApplyUpdateAction::Discontinue => break Ok(None),
+            }
+        }
+    }
+
+    async fn delay(delayed_action_time: u32) {
+        let secs = delayed_action_time.max(MIN_DELAYED_ACTION_TIME);
+
+        info!("Asking the OTA Provider again in {} seconds", secs);
+
+        Timer::after(Duration::from_secs(secs as _)).await;
+    }
+
+    fn update_image(&self, resp: &QueryImageResp) -> Result<Option<UpdateImage>, Error> {
+        let (uri, software_version, update_token) =
+            match (&resp.image_uri, resp.software_version, &resp.update_token) {
+                (Some(uri), Some(software_version), Some(update_token)) => {
+                    (uri.as_str()?, software_version, update_token.0)
+                }
+                _ => Err(ErrorCode::Invalid)?,
+            };
+
+        if software_version <= self.software_version.get() {
+            info!("Ignoring update to version {}", software_version);
+            return Ok(None);
+        }
+
+        let (node_id, file_designator) = UpdateImage::parse_uri(uri)?;
+
+        let mut image = UpdateImage {
+            node_id,
+            file_designator: heapless::String::new(),
+            software_version,
+            update_token: heapless::Vec::from_slice(update_token)
+                .map_err(|_| ErrorCode::NoSpace)?,
+        };
+
+        image
+            .file_designator
+            .push_str(file_designator)
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        Ok(Some(image))
+    }
+
+    fn set_update_state(&self, state: UpdateState, progress: Option<u8>) {
+        if self.update_state.get() != state {
+            info!("OTA update state {:?}", state);
+        }
+
+        if self.update_state.get() != state || self.update_state_progress.get() != progress {
+            self.update_state.set(state);
+            self.update_state_progress.set(progress);
+            self.data_ver.changed();
+        }
+    }
+}
+
+impl Handler for OtaRequestorCluster {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        OtaRequestorCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        OtaRequestorCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        OtaRequestorCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        OtaRequestorCluster::remove_fabric(self, fab_idx)
+    }
+}
+
+impl NonBlockingHandler for OtaRequestorCluster {}
+
+impl StatePersist for OtaRequestorCluster {
+    fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        tlv::from_tlv(&mut *self.providers.borrow_mut(), &root)?;
+
+        self.providers_changed.set(false);
+
+        Ok(())
+    }
+
+    fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        if self.providers_changed.get() {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            self.providers
+                .borrow()
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Anonymous)?;
+
+            self.providers_changed.set(false);
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl ChangeNotifier<()> for OtaRequestorCluster {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+// Reports the progress of a download in `UpdateStateProgress`
+struct ProgressSink<'a, S> {
+    sink: S,
+    cluster: &'a OtaRequestorCluster,
+    length: Option<u64>,
+}
+
+impl<'a, S> ImageSink for ProgressSink<'a, S>
+where
+    S: ImageSink,
+{
+    fn begin(&mut self, length: Option<u64>) -> Result<(), Error> {
+        self.length = length;
+        self.sink.begin(length)
+    }
+
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Error> {
+        self.sink.write(offset, data)?;
+
+        if let Some(length) = self.length.filter(|length| *length > 0) {
+            let progress = ((offset + data.len() as u64) * 100 / length).min(100) as u8;
+
+            self.cluster
+                .set_update_state(UpdateState::Downloading, Some(progress));
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.sink.finish()
+    }
+
+    fn abort(&mut self) {
+        self.sink.abort()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bdx::receiver::ImageSink,
+        data_model::{
+            cluster_basic_information::BasicInfoConfig, persist::StatePersist,
+            sdm::general_commissioning::RegLocationType,
+        },
+        error::{Error, ErrorCode},
+        interaction_model::messages::ib::ListOperation,
+        tlv::{get_root_node_struct, FromTLV, OctetStr, TLVWriter, TagType, ToTLV, UtfStr},
+        utils::{rand::dummy_rand, writebuf::WriteBuf},
+    };
+
+    use super::{
+        AnnounceOTAProviderReq, ApplyUpdateAction, ApplyUpdateReq, ApplyUpdateResp,
+        DownloadProtocol, NotifyUpdateAppliedReq, OtaRequestorCluster, ProgressSink,
+        ProviderLocation, QueryImageReq, QueryImageResp, QueryImageStatus, UpdateState,
+    };
+
+    const PROVIDER_NODE_ID: u64 = 0x11;
+    const UPDATE_TOKEN: &[u8] = &[1, 2, 3, 4];
+
+    fn config() -> BasicInfoConfig<'static> {
+        BasicInfoConfig {
+            vid: 0xFFF1,
+            pid: 0x8000,
+            hw_ver: 2,
+            sw_ver: 1,
+            sw_ver_str: "1.0",
+            serial_no: "aabbccdd",
+            device_name: "OTA Test",
+            vendor_name: "Vendor",
+            product_name: "Product",
+            unique_id: "ota-test",
+            manufacturing_date: "20230101",
+            part_number: "",
+            product_url: "",
+            product_label: "",
+            product_appearance: Default::default(),
+            icd: None,
+            location_capability: RegLocationType::IndoorOutdoor,
+        }
+    }
+
+    // Pass a message through its TLV encoding, as it would travel to and from the provider
+    fn transfer<'a, T, R>(value: &T, buf: &'a mut [u8]) -> R
+    where
+        T: ToTLV,
+        R: FromTLV<'a>,
+    {
+        let mut writebuf = WriteBuf::new(&mut *buf);
+        let mut tw = TLVWriter::new(&mut writebuf);
+        value.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        let len = writebuf.as_slice().len();
+
+        let buf: &'a [u8] = buf;
+        R::from_tlv(&get_root_node_struct(&buf[..len]).unwrap()).unwrap()
+    }
+
+    /// An OTA Provider, as the requestor would reach it over a CASE session
+    struct TestProvider {
+        status: QueryImageStatus,
+        software_version: u32,
+    }
+
+    impl TestProvider {
+        fn query_image(&self, req: &QueryImageReq) -> QueryImageResp<'static> {
+            assert_eq!(req.vendor_id, 0xFFF1);
+            assert_eq!(req.product_id, 0x8000);
+            assert_eq!(req.software_version, 1);
+            assert!(req
+                .protocols_supported
+                .iter()
+                .any(|protocol| protocol == DownloadProtocol::BDXSynchronous as u8));
+
+            QueryImageResp {
+                status: self.status as _,
+                delayed_action_time: Some(60),
+                image_uri: Some(UtfStr::new(b"bdx://0000000000000011/image.ota")),
+                software_version: Some(self.software_version),
+                software_version_string: Some(UtfStr::new(b"2.0")),
+                update_token: Some(OctetStr(UPDATE_TOKEN)),
+                user_consent_needed: None,
+                metadata_for_requestor: None,
+            }
+        }
+
+        fn apply_update_request(&self, req: &ApplyUpdateReq) -> ApplyUpdateResp {
+            assert_eq!(req.update_token.0, UPDATE_TOKEN);
+            assert_eq!(req.new_version, self.software_version);
+
+            ApplyUpdateResp {
+                action: ApplyUpdateAction::Proceed as _,
+                delayed_action_time: 0,
+            }
+        }
+    }
+
+    struct NullSink;
+
+    impl ImageSink for NullSink {
+        fn begin(&mut self, _length: Option<u64>) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn write(&mut self, _offset: u64, _data: &[u8]) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn abort(&mut self) {}
+    }
+
+    fn query<'a>(
+        cluster: &OtaRequestorCluster,
+        provider: &TestProvider,
+        buf: &'a mut [u8],
+    ) -> QueryImageResp<'a> {
+        let mut req_buf = [0; 100];
+        let req: QueryImageReq = transfer(&cluster.query_image().unwrap(), &mut req_buf);
+        assert_eq!(cluster.update_state(), UpdateState::Querying);
+
+        transfer(&provider.query_image(&req), buf)
+    }
+
+    #[test]
+    fn test_update() {
+        let cluster = OtaRequestorCluster::new(&config(), dummy_rand);
+        let provider = TestProvider {
+            status: QueryImageStatus::UpdateAvailable,
+            software_version: 2,
+        };
+
+        assert_eq!(cluster.provider(), None);
+
+        cluster
+            .announce(
+                &AnnounceOTAProviderReq {
+                    provider_node_id: PROVIDER_NODE_ID,
+                    vendor_id: 0xFFF1,
+                    announcement_reason: 1,
+                    metadata_for_node: None,
+                    endpoint: 0,
+                },
+                1,
+            )
+            .unwrap();
+        assert_eq!(
+            cluster.provider(),
+            Some(ProviderLocation {
+                provider_node_id: PROVIDER_NODE_ID,
+                endpoint: 0,
+                fab_idx: Some(1),
+            })
+        );
+
+        let mut buf = [0; 100];
+        let resp = query(&cluster, &provider, &mut buf);
+
+        let image = cluster.handle_query_image_resp(&resp).unwrap().unwrap();
+        assert_eq!(image.node_id, PROVIDER_NODE_ID);
+        assert_eq!(image.file_designator.as_str(), "image.ota");
+        assert_eq!(image.software_version, 2);
+        assert_eq!(cluster.update_state(), UpdateState::Downloading);
+        assert_eq!(cluster.update_state_progress(), Some(0));
+
+        // What the BDX receiver does while downloading
+        let mut sink = ProgressSink {
+            sink: NullSink,
+            cluster: &cluster,
+            length: None,
+        };
+        sink.begin(Some(200)).unwrap();
+        sink.write(0, &[0; 100]).unwrap();
+        sink.finish().unwrap();
+        assert_eq!(cluster.update_state_progress(), Some(50));
+
+        let mut req_buf = [0; 100];
+        let req: ApplyUpdateReq = transfer(&cluster.apply_update(&image).unwrap(), &mut req_buf);
+        assert_eq!(cluster.update_state(), UpdateState::Applying);
+        assert_eq!(cluster.update_state_progress(), None);
+
+        let mut resp_buf = [0; 100];
+        let resp: ApplyUpdateResp = transfer(&provider.apply_update_request(&req), &mut resp_buf);
+        assert_eq!(
+            cluster.handle_apply_update_resp(&resp).unwrap(),
+            ApplyUpdateAction::Proceed
+        );
+        assert_eq!(cluster.update_state(), UpdateState::Applying);
+
+        // After the reboot into the new image
+        let mut req_buf = [0; 100];
+        let req: NotifyUpdateAppliedReq = transfer(&cluster.update_applied(&image), &mut req_buf);
+        assert_eq!(req.update_token.0, UPDATE_TOKEN);
+        assert_eq!(req.software_version, 2);
+        assert_eq!(cluster.update_state(), UpdateState::Idle);
+        assert_eq!(cluster.software_version.get(), 2);
+    }
+
+    #[test]
+    fn test_no_update() {
+        let cluster = OtaRequestorCluster::new(&config(), dummy_rand);
+        let mut provider = TestProvider {
+            status: QueryImageStatus::Busy,
+            software_version: 2,
+        };
+
+        let mut buf = [0; 100];
+        let resp = query(&cluster, &provider, &mut buf);
+        assert_eq!(cluster.handle_query_image_resp(&resp).unwrap(), None);
+        assert_eq!(cluster.update_state(), UpdateState::DelayedOnQuery);
+
+        provider.status = QueryImageStatus::NotAvailable;
+        let mut buf = [0; 100];
+        let resp = query(&cluster, &provider, &mut buf);
+        assert_eq!(cluster.handle_query_image_resp(&resp).unwrap(), None);
+        assert_eq!(cluster.update_state(), UpdateState::Idle);
+
+        // Not newer than the running software
+        provider.status = QueryImageStatus::UpdateAvailable;
+        provider.software_version = 1;
+        let mut buf = [0; 100];
+        let resp = query(&cluster, &provider, &mut buf);
+        assert_eq!(cluster.handle_query_image_resp(&resp).unwrap(), None);
+        assert_eq!(cluster.update_state(), UpdateState::Idle);
+
+        cluster.set_update_possible(false);
+        assert_eq!(
+            cluster.query_image().map(|_| ()).map_err(|e| e.code()),
+            Err(ErrorCode::InvalidState)
+        );
+    }
+
+    #[test]
+    fn test_image_uri() {
+        assert_eq!(
+            super::UpdateImage::parse_uri("bdx://00000000000000AB/fw/image.bin").unwrap(),
+            (0xAB, "fw/image.bin")
+        );
+
+        assert!(super::UpdateImage::parse_uri("https://example.com/image.bin").is_err());
+        assert!(super::UpdateImage::parse_uri("bdx://AB/image.bin").is_err());
+        assert!(super::UpdateImage::parse_uri("bdx://00000000000000AB/").is_err());
+    }
+
+    #[test]
+    fn test_default_providers() {
+        let cluster = OtaRequestorCluster::new(&config(), dummy_rand);
+
+        let add = |node: u64, fab_idx: u8| -> Result<(), Error> {
+            let location = ProviderLocation {
+                provider_node_id: node,
+                endpoint: 0,
+                fab_idx: None,
+            };
+
+            let mut buf = [0; 100];
+            let mut writebuf = WriteBuf::new(&mut buf);
+            let mut tw = TLVWriter::new(&mut writebuf);
+            location.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+            let data = get_root_node_struct(writebuf.as_slice()).unwrap();
+
+            cluster.write_providers_attr(&ListOperation::AddItem, &data, fab_idx)
+        };
+
+        add(1, 1).unwrap();
+        add(2, 2).unwrap();
+        assert_eq!(
+            add(3, 1).map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+
+        // Providers can only be written on behalf of a fabric
+        assert_eq!(
+            add(3, 0).map_err(|e| e.code()),
+            Err(ErrorCode::UnsupportedAccess)
+        );
+
+        assert_eq!(cluster.provider().map(|p| p.provider_node_id), Some(1));
+
+        let mut buf = [0; 100];
+        let data = cluster.store(&mut buf).unwrap().unwrap();
+
+        let restored = OtaRequestorCluster::new(&config(), dummy_rand);
+        restored.load(data).unwrap();
+        assert_eq!(*restored.providers.borrow(), *cluster.providers.borrow());
+
+        cluster.remove_fabric(1);
+        assert_eq!(cluster.provider().map(|p| p.provider_node_id), Some(2));
+        assert_eq!(cluster.provider().and_then(|p| p.fab_idx), Some(2));
+    }
+}
@@ -0,0 +1,873 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+};
+
+use super::{
+    objects::*,
+    system_model::descriptor::{PartsMatcher, StandardPartsMatcher},
+};
+use crate::{
+    attribute_enum,
+    error::{Error, ErrorCode},
+    tlv::{Nullable, TagType},
+    utils::{rand::Rand, select::Notification},
+};
+use embassy_futures::select::select;
+use embassy_time::{Duration, Instant, Timer};
+use log::warn;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x002F;
+
+/// The maximum number of active faults of each kind
+pub const MAX_FAULTS: usize = 4;
+
+/// The shortest interval between two reports of the battery voltage and of the
+/// remaining battery, as per the spec
+pub const MIN_REPORT_INTERVAL_SECS: u64 = 10;
+
+enum FeatureMap {
+    Wired = 0x01,
+    Battery = 0x02,
+    Rechargeable = 0x04,
+    Replaceable = 0x08,
+}
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    Status(AttrType<u8>) = 0x00,
+    Order(AttrType<u8>) = 0x01,
+    Description(AttrUtfType) = 0x02,
+    WiredCurrentType(AttrType<u8>) = 0x05,
+    WiredPresent(AttrType<bool>) = 0x09,
+    ActiveWiredFaults(()) = 0x0A,
+    BatVoltage(AttrType<Nullable<u32>>) = 0x0B,
+    BatPercentRemaining(AttrType<Nullable<u8>>) = 0x0C,
+    BatChargeLevel(AttrType<u8>) = 0x0E,
+    BatReplacementNeeded(AttrType<bool>) = 0x0F,
+    BatReplaceability(AttrType<u8>) = 0x10,
+    BatPresent(AttrType<bool>) = 0x11,
+    ActiveBatFaults(()) = 0x12,
+    BatReplacementDescription(AttrUtfType) = 0x13,
+    BatQuantity(AttrType<u8>) = 0x19,
+    BatChargeState(AttrType<u8>) = 0x1A,
+    BatFunctionalWhileCharging(AttrType<bool>) = 0x1C,
+    ActiveBatChargeFaults(()) = 0x1E,
+    EndpointList(()) = 0x1F,
+}
+
+attribute_enum!(Attributes);
+
+/// Events of this cluster
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u32)]
+pub enum Events {
+    WiredFaultChange = 0x00,
+    BatFaultChange = 0x01,
+    BatChargeFaultChange = 0x02,
+}
+
+const STATUS: Attribute = Attribute::new(
+    AttributesDiscriminants::Status as u16,
+    Access::RV,
+    Quality::NONE,
+);
+
+const ORDER: Attribute = Attribute::new(
+    AttributesDiscriminants::Order as u16,
+    Access::RV,
+    Quality::NONE,
+);
+
+const DESCRIPTION: Attribute = Attribute::new(
+    AttributesDiscriminants::Description as u16,
+    Access::RV,
+    Quality::FIXED,
+);
+
+const ENDPOINT_LIST: Attribute = Attribute::new(
+    AttributesDiscriminants::EndpointList as u16,
+    Access::RV,
+    Quality::NONE,
+);
+
+const BAT_VOLTAGE: Attribute = Attribute::new(
+    AttributesDiscriminants::BatVoltage as u16,
+    Access::RV,
+    Quality::X,
+);
+
+const BAT_PERCENT_REMAINING: Attribute = Attribute::new(
+    AttributesDiscriminants::BatPercentRemaining as u16,
+    Access::RV,
+    Quality::X,
+);
+
+const BAT_CHARGE_LEVEL: Attribute = Attribute::new(
+    AttributesDiscriminants::BatChargeLevel as u16,
+    Access::RV,
+    Quality::NONE,
+);
+
+const BAT_REPLACEMENT_NEEDED: Attribute = Attribute::new(
+    AttributesDiscriminants::BatReplacementNeeded as u16,
+    Access::RV,
+    Quality::NONE,
+);
+
+const BAT_REPLACEABILITY: Attribute = Attribute::new(
+    AttributesDiscriminants::BatReplaceability as u16,
+    Access::RV,
+    Quality::FIXED,
+);
+
+const BAT_PRESENT: Attribute = Attribute::new(
+    AttributesDiscriminants::BatPresent as u16,
+    Access::RV,
+    Quality::NONE,
+);
+
+const ACTIVE_BAT_FAULTS: Attribute = Attribute::new(
+    AttributesDiscriminants::ActiveBatFaults as u16,
+    Access::RV,
+    Quality::NONE,
+);
+
+/// Mains power or an external power supply
+pub const WIRED_CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::Wired as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        STATUS,
+        ORDER,
+        DESCRIPTION,
+        Attribute::new(
+            AttributesDiscriminants::WiredCurrentType as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::WiredPresent as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveWiredFaults as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        ENDPOINT_LIST,
+    ],
+    commands: &[],
+};
+
+/// A battery which is replaced once depleted
+pub const BATTERY_CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::Battery as u32 | FeatureMap::Replaceable as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        STATUS,
+        ORDER,
+        DESCRIPTION,
+        BAT_VOLTAGE,
+        BAT_PERCENT_REMAINING,
+        BAT_CHARGE_LEVEL,
+        BAT_REPLACEMENT_NEEDED,
+        BAT_REPLACEABILITY,
+        BAT_PRESENT,
+        ACTIVE_BAT_FAULTS,
+        Attribute::new(
+            AttributesDiscriminants::BatReplacementDescription as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::BatQuantity as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        ENDPOINT_LIST,
+    ],
+    commands: &[],
+};
+
+/// A battery which is recharged, e.g. from a wired power source of the node
+pub const RECHARGEABLE_BATTERY_CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::Battery as u32 | FeatureMap::Rechargeable as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        STATUS,
+        ORDER,
+        DESCRIPTION,
+        BAT_VOLTAGE,
+        BAT_PERCENT_REMAINING,
+        BAT_CHARGE_LEVEL,
+        BAT_REPLACEMENT_NEEDED,
+        BAT_REPLACEABILITY,
+        BAT_PRESENT,
+        ACTIVE_BAT_FAULTS,
+        Attribute::new(
+            AttributesDiscriminants::BatChargeState as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::BatFunctionalWhileCharging as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveBatChargeFaults as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        ENDPOINT_LIST,
+    ],
+    commands: &[],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum PowerSourceStatus {
+    Unspecified = 0,
+    Active = 1,
+    Standby = 2,
+    Unavailable = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum WiredCurrentType {
+    Ac = 0,
+    Dc = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum WiredFault {
+    Unspecified = 0,
+    OverVoltage = 1,
+    UnderVoltage = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum BatChargeLevel {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum BatReplaceability {
+    Unspecified = 0,
+    NotReplaceable = 1,
+    UserReplaceable = 2,
+    FactoryReplaceable = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum BatFault {
+    Unspecified = 0,
+    OverTemp = 1,
+    UnderTemp = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum BatChargeState {
+    Unknown = 0,
+    IsCharging = 1,
+    IsAtFullCharge = 2,
+    IsNotCharging = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum BatChargeFault {
+    Unspecified = 0,
+    AmbientTooHot = 1,
+    AmbientTooCold = 2,
+    BatteryTooHot = 3,
+    BatteryTooCold = 4,
+    BatteryAbsent = 5,
+    BatteryOverVoltage = 6,
+    BatteryUnderVoltage = 7,
+    ChargerOverVoltage = 8,
+    ChargerUnderVoltage = 9,
+    SafetyTimeout = 10,
+}
+
+/// The kind of a power source, which selects the features of its cluster
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSourceKind<'a> {
+    /// `WIRED_CLUSTER`
+    Wired { current_type: WiredCurrentType },
+    /// `BATTERY_CLUSTER`
+    ///
+    /// `replacement_description` is shown to the user, e.g. "2x AA", and `quantity`
+    /// is the number of batteries.
+    ReplaceableBattery {
+        replaceability: BatReplaceability,
+        replacement_description: &'a str,
+        quantity: u8,
+    },
+    /// `RECHARGEABLE_BATTERY_CLUSTER`
+    RechargeableBattery {
+        replaceability: BatReplaceability,
+        functional_while_charging: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerSourceConfig<'a> {
+    /// The order of this source amongst the power sources of the node, the lowest
+    /// being the preferred one
+    pub order: u8,
+    /// A description shown to the user, e.g. "Primary Battery"
+    pub description: &'a str,
+    pub kind: PowerSourceKind<'a>,
+    /// How often the `BatteryMonitor` is read, in seconds
+    pub poll_interval_secs: u16,
+}
+
+impl<'a> PowerSourceConfig<'a> {
+    /// The cluster with the features of this power source, to be used in the `Endpoint`
+    pub const fn cluster(&self) -> Cluster<'static> {
+        match self.kind {
+            PowerSourceKind::Wired { .. } => WIRED_CLUSTER,
+            PowerSourceKind::ReplaceableBattery { .. } => BATTERY_CLUSTER,
+            PowerSourceKind::RechargeableBattery { .. } => RECHARGEABLE_BATTERY_CLUSTER,
+        }
+    }
+}
+
+/// The state of a battery, as read by a `BatteryMonitor`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatteryReading {
+    /// The voltage in mV, `None` if unknown
+    pub voltage: Option<u32>,
+    /// The remaining capacity in half percents, i.e. from 0 to 200, `None` if unknown
+    pub percent_remaining: Option<u8>,
+    pub charge_level: BatChargeLevel,
+    pub replacement_needed: bool,
+    pub present: bool,
+    /// Only reported for rechargeable batteries
+    pub charge_state: BatChargeState,
+    pub faults: heapless::Vec<BatFault, MAX_FAULTS>,
+    /// Only reported for rechargeable batteries
+    pub charge_faults: heapless::Vec<BatChargeFault, MAX_FAULTS>,
+}
+
+impl BatteryReading {
+    pub const fn new() -> Self {
+        Self {
+            voltage: None,
+            percent_remaining: None,
+            charge_level: BatChargeLevel::Ok,
+            replacement_needed: false,
+            present: true,
+            charge_state: BatChargeState::Unknown,
+            faults: heapless::Vec::new(),
+            charge_faults: heapless::Vec::new(),
+        }
+    }
+}
+
+impl Default for BatteryReading {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The platform side of a battery power source
+///
+/// Read by `PowerSourceCluster::run` every `poll_interval_secs` seconds.
+pub trait BatteryMonitor {
+    fn read(&self) -> Result<BatteryReading, Error>;
+}
+
+impl<T> BatteryMonitor for &T
+where
+    T: BatteryMonitor,
+{
+    fn read(&self) -> Result<BatteryReading, Error> {
+        (**self).read()
+    }
+}
+
+/// A power source of the node
+///
+/// The `EndpointList` attribute holds the endpoint of the cluster and the endpoints
+/// which the `PartsMatcher` describes as its parts, just like the `PartsList` of the
+/// Descriptor cluster of that endpoint. A power source on the root endpoint thus
+/// powers the whole node.
+///
+/// The endpoint of the cluster lists `DEV_TYPE_POWER_SOURCE` in its `extra_device_types`.
+pub struct PowerSourceCluster<'a> {
+    data_ver: Dataver,
+    conf: &'a PowerSourceConfig<'a>,
+    matcher: &'a dyn PartsMatcher,
+    status: Cell<PowerSourceStatus>,
+    wired_present: Cell<bool>,
+    wired_faults: RefCell<heapless::Vec<WiredFault, MAX_FAULTS>>,
+    battery: RefCell<BatteryReading>,
+    monitor: Cell<Option<&'a dyn BatteryMonitor>>,
+    // When the voltage and the remaining battery were last reported, and their
+    // latest values if these are not reported yet
+    reported_at: Cell<Option<Instant>>,
+    pending: Cell<Option<(Option<u32>, Option<u8>)>>,
+    pending_notification: Notification,
+}
+
+impl<'a> PowerSourceCluster<'a> {
+    pub fn new(conf: &'a PowerSourceConfig<'a>, rand: Rand) -> Self {
+        Self::new_matching(conf, &StandardPartsMatcher, rand)
+    }
+
+    pub fn new_matching(
+        conf: &'a PowerSourceConfig<'a>,
+        matcher: &'a dyn PartsMatcher,
+        rand: Rand,
+    ) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            conf,
+            matcher,
+            status: Cell::new(PowerSourceStatus::Active),
+            wired_present: Cell::new(true),
+            wired_faults: RefCell::new(heapless::Vec::new()),
+            battery: RefCell::new(BatteryReading::new()),
+            monitor: Cell::new(None),
+            reported_at: Cell::new(None),
+            pending: Cell::new(None),
+            pending_notification: Notification::new(),
+        }
+    }
+
+    pub fn set_monitor(&self, monitor: Option<&'a dyn BatteryMonitor>) {
+        self.monitor.set(monitor);
+    }
+
+    pub fn status(&self) -> PowerSourceStatus {
+        self.status.get()
+    }
+
+    /// Set whether the node is currently powered by this source
+    pub fn set_status(&self, status: PowerSourceStatus) {
+        if self.status.replace(status) != status {
+            self.data_ver.changed();
+        }
+    }
+
+    /// Update whether the wired power source is connected, and its active faults
+    pub fn set_wired(&self, present: bool, faults: &[WiredFault]) -> Result<(), Error> {
+        let faults = heapless::Vec::from_slice(faults).map_err(|_| ErrorCode::ResourceExhausted)?;
+
+        let mut wired_faults = self.wired_faults.borrow_mut();
+
+        if self.wired_present.replace(present) != present || *wired_faults != faults {
+            *wired_faults = faults;
+            self.data_ver.changed();
+        }
+
+        Ok(())
+    }
+
+    /// The latest state of the battery, including a voltage and remaining battery
+    /// which are not reported yet
+    pub fn battery(&self) -> BatteryReading {
+        let mut battery = self.battery.borrow().clone();
+
+        if let Some((voltage, percent_remaining)) = self.pending.get() {
+            battery.voltage = voltage;
+            battery.percent_remaining = percent_remaining;
+        }
+
+        battery
+    }
+
+    /// Update the state of the battery
+    ///
+    /// Changes of the voltage and of the remaining battery are reported at most
+    /// once every `MIN_REPORT_INTERVAL_SECS` seconds, unless they become known or
+    /// unknown; the other changes are reported right away. Until then, the previously
+    /// reported values are served, and `run` reports the latest ones once the interval
+    /// elapsed.
+    pub fn set_battery(&self, reading: BatteryReading) {
+        self.set_battery_at(reading, Instant::now());
+    }
+
+    fn set_battery_at(&self, reading: BatteryReading, now: Instant) {
+        let mut battery = self.battery.borrow_mut();
+
+        let known_changed = battery.voltage.is_some() != reading.voltage.is_some()
+            || battery.percent_remaining.is_some() != reading.percent_remaining.is_some();
+        let quiet_changed = battery.voltage != reading.voltage
+            || battery.percent_remaining != reading.percent_remaining;
+        let changed = battery.charge_level != reading.charge_level
+            || battery.replacement_needed != reading.replacement_needed
+            || battery.present != reading.present
+            || battery.charge_state != reading.charge_state
+            || battery.faults != reading.faults
+            || battery.charge_faults != reading.charge_faults;
+
+        let quiet_reported = known_changed || (quiet_changed && self.is_due(now));
+
+        let (voltage, percent_remaining) = if quiet_changed && !quiet_reported {
+            self.pending
+                .set(Some((reading.voltage, reading.percent_remaining)));
+            self.pending_notification.signal(());
+
+            (battery.voltage, battery.percent_remaining)
+        } else {
+            self.pending.set(None);
+
+            (reading.voltage, reading.percent_remaining)
+        };
+
+        *battery = BatteryReading {
+            voltage,
+            percent_remaining,
+            ..reading
+        };
+
+        if quiet_reported {
+            self.reported_at.set(Some(now));
+        }
+
+        if changed || quiet_reported {
+            self.data_ver.changed();
+        }
+    }
+
+    // Report the latest voltage and remaining battery, if the interval elapsed
+    fn flush_at(&self, now: Instant) {
+        if let Some((voltage, percent_remaining)) = self.pending.get() {
+            if self.is_due(now) {
+                let mut battery = self.battery.borrow_mut();
+
+                battery.voltage = voltage;
+                battery.percent_remaining = percent_remaining;
+
+                self.pending.set(None);
+                self.reported_at.set(Some(now));
+                self.data_ver.changed();
+            }
+        }
+    }
+
+    // When the pending voltage and remaining battery are to be reported
+    fn flush_deadline(&self) -> Option<Instant> {
+        self.pending.get()?;
+
+        Some(self.reported_at.get()? + Duration::from_secs(MIN_REPORT_INTERVAL_SECS))
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.reported_at
+            .get()
+            .map(|reported_at| {
+                now.saturating_duration_since(reported_at)
+                    >= Duration::from_secs(MIN_REPORT_INTERVAL_SECS)
+            })
+            .unwrap_or(true)
+    }
+
+    /// Read the `BatteryMonitor` periodically, and report the throttled changes of the
+    /// voltage and of the remaining battery
+    ///
+    /// Needs to be running for the state of the battery to ever be updated, unless
+    /// the application calls `set_battery` itself.
+    pub async fn run(&self) -> Result<(), Error> {
+        let mut poll_at = Instant::now();
+
+        loop {
+            let now = Instant::now();
+
+            if now >= poll_at {
+                if let Some(monitor) = self.monitor.get() {
+                    match monitor.read() {
+                        Ok(reading) => self.set_battery_at(reading, now),
+                        Err(err) => warn!("Reading the battery failed: {:?}", err),
+                    }
+                }
+
+                poll_at = now + Duration::from_secs(self.conf.poll_interval_secs.max(1) as _);
+            }
+
+            self.flush_at(now);
+
+            let wake_at = self
+                .flush_deadline()
+                .map(|deadline| deadline.min(poll_at))
+                .unwrap_or(poll_at);
+
+            select(Timer::at(wake_at), self.pending_notification.wait()).await;
+        }
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                self.conf.cluster().read(attr.attr_id, writer)
+            } else {
+                let battery = self.battery.borrow();
+
+                match attr.attr_id.try_into()? {
+                    Attributes::Status(codec) => codec.encode(writer, self.status() as _),
+                    Attributes::Order(codec) => codec.encode(writer, self.conf.order),
+                    Attributes::Description(codec) => codec.encode(writer, self.conf.description),
+                    Attributes::WiredCurrentType(codec) => match self.conf.kind {
+                        PowerSourceKind::Wired { current_type } => {
+                            codec.encode(writer, current_type as _)
+                        }
+                        _ => Err(ErrorCode::AttributeNotFound.into()),
+                    },
+                    Attributes::WiredPresent(codec) => {
+                        codec.encode(writer, self.wired_present.get())
+                    }
+                    Attributes::ActiveWiredFaults(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for fault in self.wired_faults.borrow().iter() {
+                            writer.u8(TagType::Anonymous, *fault as _)?;
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                    Attributes::BatVoltage(codec) => {
+                        codec.encode(writer, Self::nullable(battery.voltage))
+                    }
+                    Attributes::BatPercentRemaining(codec) => {
+                        codec.encode(writer, Self::nullable(battery.percent_remaining))
+                    }
+                    Attributes::BatChargeLevel(codec) => {
+                        codec.encode(writer, battery.charge_level as _)
+                    }
+                    Attributes::BatReplacementNeeded(codec) => {
+                        codec.encode(writer, battery.replacement_needed)
+                    }
+                    Attributes::BatReplaceability(codec) => match self.conf.kind {
+                        PowerSourceKind::ReplaceableBattery { replaceability, .. }
+                        | PowerSourceKind::RechargeableBattery { replaceability, .. } => {
+                            codec.encode(writer, replaceability as _)
+                        }
+                        _ => Err(ErrorCode::AttributeNotFound.into()),
+                    },
+                    Attributes::BatPresent(codec) => codec.encode(writer, battery.present),
+                    Attributes::ActiveBatFaults(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for fault in &battery.faults {
+                            writer.u8(TagType::Anonymous, *fault as _)?;
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                    Attributes::BatReplacementDescription(codec) => match self.conf.kind {
+                        PowerSourceKind::ReplaceableBattery {
+                            replacement_description,
+                            ..
+                        } => codec.encode(writer, replacement_description),
+                        _ => Err(ErrorCode::AttributeNotFound.into()),
+                    },
+                    Attributes::BatQuantity(codec) => match self.conf.kind {
+                        PowerSourceKind::ReplaceableBattery { quantity, .. } => {
+                            codec.encode(writer, quantity)
+                        }
+                        _ => Err(ErrorCode::AttributeNotFound.into()),
+                    },
+                    Attributes::BatChargeState(codec) => {
+                        codec.encode(writer, battery.charge_state as _)
+                    }
+                    Attributes::BatFunctionalWhileCharging(codec) => match self.conf.kind {
+                        PowerSourceKind::RechargeableBattery {
+                            functional_while_charging,
+                            ..
+                        } => codec.encode(writer, functional_while_charging),
+                        _ => Err(ErrorCode::AttributeNotFound.into()),
+                    },
+                    Attributes::ActiveBatChargeFaults(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for fault in &battery.charge_faults {
+                            writer.u8(TagType::Anonymous, *fault as _)?;
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                    Attributes::EndpointList(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        writer.u16(TagType::Anonymous, attr.endpoint_id)?;
+                        for endpoint in attr.node.endpoints {
+                            if self.matcher.describe(attr.endpoint_id, endpoint.id) {
+                                writer.u16(TagType::Anonymous, endpoint.id)?;
+                            }
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    fn nullable<T>(value: Option<T>) -> Nullable<T> {
+        match value {
+            Some(value) => Nullable::NotNull(value),
+            None => Nullable::Null,
+        }
+    }
+}
+
+impl<'a> Handler for PowerSourceCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        PowerSourceCluster::read(self, attr, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for PowerSourceCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for PowerSourceCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_time::{Duration, Instant};
+
+    use crate::utils::rand::dummy_rand;
+
+    use super::{
+        BatChargeLevel, BatReplaceability, BatteryReading, PowerSourceCluster, PowerSourceConfig,
+        PowerSourceKind, MIN_REPORT_INTERVAL_SECS,
+    };
+
+    const CONF: PowerSourceConfig<'static> = PowerSourceConfig {
+        order: 0,
+        description: "Battery",
+        kind: PowerSourceKind::ReplaceableBattery {
+            replaceability: BatReplaceability::UserReplaceable,
+            replacement_description: "2x AA",
+            quantity: 2,
+        },
+        poll_interval_secs: 60,
+    };
+
+    fn reading(percent_remaining: Option<u8>) -> BatteryReading {
+        BatteryReading {
+            percent_remaining,
+            ..BatteryReading::new()
+        }
+    }
+
+    #[test]
+    fn test_battery_reporting_rate() {
+        let cluster = PowerSourceCluster::new(&CONF, dummy_rand);
+        let start = Instant::from_secs(100);
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Becoming known is reported right away
+        let ver = cluster.data_ver.get();
+        cluster.set_battery_at(reading(Some(200)), at(0));
+        assert_ne!(cluster.data_ver.get(), ver);
+
+        // Changes of the remaining battery are reported at most every 10 seconds,
+        // serving the reported value in the meantime
+        let ver = cluster.data_ver.get();
+        cluster.set_battery_at(reading(Some(198)), at(1));
+        cluster.set_battery_at(reading(Some(196)), at(5));
+        assert_eq!(cluster.data_ver.get(), ver);
+        assert_eq!(cluster.battery.borrow().percent_remaining, Some(200));
+        assert_eq!(cluster.battery().percent_remaining, Some(196));
+        assert_eq!(cluster.flush_deadline(), Some(at(MIN_REPORT_INTERVAL_SECS)));
+
+        // ... and the pending change is reported once the interval elapsed
+        cluster.flush_at(at(MIN_REPORT_INTERVAL_SECS - 1));
+        assert_eq!(cluster.data_ver.get(), ver);
+
+        cluster.flush_at(at(MIN_REPORT_INTERVAL_SECS));
+        assert_ne!(cluster.data_ver.get(), ver);
+        assert_eq!(cluster.battery.borrow().percent_remaining, Some(196));
+        assert_eq!(cluster.flush_deadline(), None);
+
+        // Other changes are reported right away, without the throttled ones
+        let ver = cluster.data_ver.get();
+        cluster.set_battery_at(
+            BatteryReading {
+                replacement_needed: true,
+                ..reading(Some(194))
+            },
+            at(15),
+        );
+        assert_ne!(cluster.data_ver.get(), ver);
+        assert!(cluster.battery.borrow().replacement_needed);
+        assert_eq!(cluster.battery.borrow().percent_remaining, Some(196));
+
+        // Going back to the reported value drops the pending one
+        cluster.set_battery_at(reading(Some(196)), at(16));
+        assert_eq!(cluster.flush_deadline(), None);
+
+        let ver = cluster.data_ver.get();
+        cluster.set_battery_at(reading(Some(196)), at(30));
+        assert_eq!(cluster.data_ver.get(), ver);
+
+        // So is a change of the charge level
+        cluster.set_battery_at(
+            BatteryReading {
+                charge_level: BatChargeLevel::Warning,
+                ..reading(Some(196))
+            },
+            at(31),
+        );
+        assert_ne!(cluster.data_ver.get(), ver);
+
+        // And so is becoming unknown
+        let ver = cluster.data_ver.get();
+        cluster.set_battery_at(
+            BatteryReading {
+                charge_level: BatChargeLevel::Warning,
+                ..reading(None)
+            },
+            at(32),
+        );
+        assert_ne!(cluster.data_ver.get(), ver);
+    }
+}
@@ -0,0 +1,27 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use super::{cluster_measurement, objects::Cluster};
+
+pub use super::cluster_measurement::{Attributes, AttributesDiscriminants};
+
+pub const ID: u32 = 0x0405;
+
+pub const CLUSTER: Cluster<'static> = cluster_measurement::cluster(ID);
+
+/// Relative humidity in 0.01%, e.g. 4550 for 45.5%
+pub type RelativeHumidityMeasurementCluster = cluster_measurement::MeasurementCluster<u16, ID>;
@@ -0,0 +1,1270 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+};
+
+use super::objects::*;
+use super::persist::StatePersist;
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    fabric,
+    interaction_model::core::IMStatusCode,
+    tlv::{
+        self, FromTLV, Nullable, TLVArray, TLVElement, TLVList, TLVWriter, TagType, ToTLV, UtfStr,
+    },
+    transport::exchange::Exchange,
+    utils::{rand::Rand, writebuf::WriteBuf},
+};
+use embassy_time::Duration;
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0062;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    SceneTableSize(AttrType<u16>) = 0x1,
+    FabricSceneInfo(()) = 0x2,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    AddScene = 0x00,
+    ViewScene = 0x01,
+    RemoveScene = 0x02,
+    RemoveAllScenes = 0x03,
+    StoreScene = 0x04,
+    RecallScene = 0x05,
+    GetSceneMembership = 0x06,
+    CopyScene = 0x40,
+}
+
+command_enum!(Commands);
+
+#[repr(u16)]
+pub enum RespCommands {
+    AddSceneResponse = 0x00,
+    ViewSceneResponse = 0x01,
+    RemoveSceneResponse = 0x02,
+    RemoveAllScenesResponse = 0x03,
+    StoreSceneResponse = 0x04,
+    GetSceneMembershipResponse = 0x06,
+    CopySceneResponse = 0x40,
+}
+
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    SceneNames = 0x01,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: Feature::SceneNames as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::SceneTableSize as u16,
+            Access::RV,
+            Quality::F,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::FabricSceneInfo as u16,
+            Access::RV.union(Access::FAB_SCOPED),
+            Quality::NONE,
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::AddScene as _,
+        CommandsDiscriminants::ViewScene as _,
+        CommandsDiscriminants::RemoveScene as _,
+        CommandsDiscriminants::RemoveAllScenes as _,
+        CommandsDiscriminants::StoreScene as _,
+        CommandsDiscriminants::RecallScene as _,
+        CommandsDiscriminants::GetSceneMembership as _,
+        CommandsDiscriminants::CopyScene as _,
+    ],
+};
+
+/// The number of scenes in the scene table, across all fabrics
+pub const SCENE_TABLE_SIZE: usize = 16;
+pub const SCENES_PER_FABRIC: usize = 8;
+
+/// The maximum number of attribute values in a scene, across all of its clusters
+pub const MAX_SCENE_VALUES: usize = 8;
+pub const MAX_SCENE_NAME_LEN: usize = 16;
+
+const MAX_SCENE_ID: u8 = 0xfe;
+const MAX_TRANSITION_TIME: u32 = 60_000_000;
+
+const COPY_ALL_SCENES: u8 = 0x01;
+
+const GLOBAL_SCENE_GROUP_ID: u16 = 0;
+const GLOBAL_SCENE_ID: u8 = 0;
+
+#[derive(FromTLV, ToTLV, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeValuePair {
+    pub attribute_id: u32,
+    pub attribute_value: u32,
+}
+
+#[derive(FromTLV, ToTLV, Debug, Clone)]
+#[tlvargs(lifetime = "'a")]
+pub struct ExtensionFieldSet<'a> {
+    pub cluster_id: ClusterId,
+    pub attribute_value_list: TLVArray<'a, AttributeValuePair>,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct AddSceneReq<'a> {
+    pub group_id: u16,
+    pub scene_id: u8,
+    /// In milliseconds
+    pub transition_time: u32,
+    pub scene_name: UtfStr<'a>,
+    pub extension_field_sets: TLVArray<'a, ExtensionFieldSet<'a>>,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct SceneReq {
+    pub group_id: u16,
+    pub scene_id: u8,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct GroupReq {
+    pub group_id: u16,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct RecallSceneReq {
+    pub group_id: u16,
+    pub scene_id: u8,
+    pub transition_time: Option<Nullable<u32>>,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct CopySceneReq {
+    pub mode: u8,
+    pub group_identifier_from: u16,
+    pub scene_identifier_from: u8,
+    pub group_identifier_to: u16,
+    pub scene_identifier_to: u8,
+}
+
+#[derive(ToTLV)]
+struct SceneResp {
+    status: u8,
+    group_id: u16,
+    scene_id: u8,
+}
+
+#[derive(ToTLV)]
+struct GroupResp {
+    status: u8,
+    group_id: u16,
+}
+
+#[derive(ToTLV)]
+struct SceneMembershipResp<'a> {
+    status: u8,
+    capacity: Nullable<u8>,
+    group_id: u16,
+    scene_list: Option<&'a [u8]>,
+}
+
+#[derive(ToTLV)]
+struct CopySceneResp {
+    status: u8,
+    group_identifier_from: u16,
+    scene_identifier_from: u8,
+}
+
+// Not derived, as the scene fields are only there on success
+struct ViewSceneResp<'a> {
+    status: u8,
+    group_id: u16,
+    scene_id: u8,
+    scene: Option<&'a Scene>,
+}
+
+impl<'a> ToTLV for ViewSceneResp<'a> {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        tw.u8(TagType::Context(0), self.status)?;
+        tw.u16(TagType::Context(1), self.group_id)?;
+        tw.u8(TagType::Context(2), self.scene_id)?;
+
+        if let Some(scene) = self.scene {
+            scene.write_contents(tw, 3)?;
+        }
+
+        tw.end_container()
+    }
+}
+
+#[derive(ToTLV)]
+struct SceneInfo {
+    scene_count: u8,
+    current_scene: u8,
+    current_group: u16,
+    scene_valid: bool,
+    remaining_capacity: u8,
+    #[tagval(0xFE)]
+    fab_idx: u8,
+}
+
+/// A cluster whose attributes with `Quality::SCENE` are captured in scenes
+///
+/// `StoreScene` reads the values of these attributes from the cluster, and `RecallScene`
+/// hands them back to it, so that it can transition to them like it does for its own
+/// commands.
+pub trait SceneParticipant {
+    /// The current value of a scene attribute, or `None` if not supported
+    fn scene_value(&self, attr_id: AttrId) -> Option<u32>;
+
+    /// Move to the attribute values of a recalled scene within `transition_time`
+    ///
+    /// Attributes missing from `values` keep their current value.
+    fn recall_scene(&self, values: &[AttributeValuePair], transition_time: Duration);
+}
+
+impl<T> SceneParticipant for &T
+where
+    T: SceneParticipant,
+{
+    fn scene_value(&self, attr_id: AttrId) -> Option<u32> {
+        (**self).scene_value(attr_id)
+    }
+
+    fn recall_scene(&self, values: &[AttributeValuePair], transition_time: Duration) {
+        (**self).recall_scene(values, transition_time)
+    }
+}
+
+/// The global scene of an endpoint, i.e. scene 0 of group 0
+///
+/// The On/Off cluster stores it when switched off with `OffWithEffect`, and recalls it
+/// with `OnWithRecallGlobalScene`.
+pub trait GlobalScene {
+    /// Capture the current state of `endpoint` in the global scene of fabric `fab_idx`
+    fn store_global_scene(&self, endpoint: &Endpoint, fab_idx: u8) -> Result<(), Error>;
+
+    /// Restore the global scene of fabric `fab_idx`
+    fn recall_global_scene(&self, fab_idx: u8) -> Result<(), Error>;
+}
+
+impl<T> GlobalScene for &T
+where
+    T: GlobalScene,
+{
+    fn store_global_scene(&self, endpoint: &Endpoint, fab_idx: u8) -> Result<(), Error> {
+        (**self).store_global_scene(endpoint, fab_idx)
+    }
+
+    fn recall_global_scene(&self, fab_idx: u8) -> Result<(), Error> {
+        (**self).recall_global_scene(fab_idx)
+    }
+}
+
+type SceneValues = heapless::Vec<(ClusterId, AttributeValuePair), MAX_SCENE_VALUES>;
+
+#[derive(Debug, Clone)]
+struct Scene {
+    fab_idx: u8,
+    group_id: u16,
+    scene_id: u8,
+    transition_time: u32,
+    name: heapless::String<MAX_SCENE_NAME_LEN>,
+    // The values of each cluster are kept together, in the order of the extension field sets
+    values: SceneValues,
+}
+
+impl Scene {
+    fn new(fab_idx: u8, group_id: u16, scene_id: u8) -> Self {
+        Self {
+            fab_idx,
+            group_id,
+            scene_id,
+            transition_time: 0,
+            name: heapless::String::new(),
+            values: SceneValues::new(),
+        }
+    }
+
+    fn is(&self, fab_idx: u8, group_id: u16, scene_id: u8) -> bool {
+        self.fab_idx == fab_idx && self.group_id == group_id && self.scene_id == scene_id
+    }
+
+    /// Replace the values of a cluster, a later extension field set for the same cluster
+    /// overriding the earlier ones
+    fn set_values<I>(&mut self, cluster_id: ClusterId, values: I) -> Result<(), Error>
+    where
+        I: Iterator<Item = AttributeValuePair>,
+    {
+        self.values.retain(|(cluster, _)| *cluster != cluster_id);
+
+        for value in values {
+            self.values
+                .push((cluster_id, value))
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        Ok(())
+    }
+
+    // The fields shared by the scene table and `ViewSceneResponse`, starting at `tag`
+    fn write_contents(&self, tw: &mut TLVWriter, tag: u8) -> Result<(), Error> {
+        tw.u32(TagType::Context(tag), self.transition_time)?;
+        tw.utf16(TagType::Context(tag + 1), self.name.as_bytes())?;
+
+        tw.start_array(TagType::Context(tag + 2))?;
+
+        let mut values = self.values.as_slice();
+
+        while let Some((cluster_id, _)) = values.first() {
+            let len = values
+                .iter()
+                .take_while(|(cluster, _)| cluster == cluster_id)
+                .count();
+            let (set, rest) = values.split_at(len);
+
+            tw.start_struct(TagType::Anonymous)?;
+            tw.u32(TagType::Context(0), *cluster_id)?;
+            tw.start_array(TagType::Context(1))?;
+            for (_, value) in set {
+                value.to_tlv(tw, TagType::Anonymous)?;
+            }
+            tw.end_container()?;
+            tw.end_container()?;
+
+            values = rest;
+        }
+
+        tw.end_container()
+    }
+}
+
+// Scenes are persisted in the format of `AddScene`, plus the fabric index
+impl ToTLV for Scene {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        tw.u16(TagType::Context(0), self.group_id)?;
+        tw.u8(TagType::Context(1), self.scene_id)?;
+        self.write_contents(tw, 2)?;
+        tw.u8(TagType::Context(0xFE), self.fab_idx)?;
+        tw.end_container()
+    }
+}
+
+impl<'a> FromTLV<'a> for Scene {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<Self, Error> {
+        let req = AddSceneReq::from_tlv(t)?;
+
+        let mut scene = Scene::new(t.find_tag(0xFE)?.u8()?, req.group_id, req.scene_id);
+
+        scene.transition_time = req.transition_time;
+        scene
+            .name
+            .push_str(req.scene_name.as_str()?)
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        for set in req.extension_field_sets.iter() {
+            scene.set_values(set.cluster_id, set.attribute_value_list.iter())?;
+        }
+
+        Ok(scene)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CurrentScene {
+    fab_idx: u8,
+    group_id: u16,
+    scene_id: u8,
+    valid: bool,
+}
+
+pub struct ScenesManagementCluster<'a> {
+    data_ver: Dataver,
+    participants: &'a [(ClusterId, &'a dyn SceneParticipant)],
+    scenes: RefCell<heapless::Vec<Scene, SCENE_TABLE_SIZE>>,
+    current: RefCell<heapless::Vec<CurrentScene, { fabric::MAX_SUPPORTED_FABRICS }>>,
+    changed: Cell<bool>,
+}
+
+impl<'a> ScenesManagementCluster<'a> {
+    /// Create the cluster for the endpoint of the `participants`
+    ///
+    /// Only the attributes of clusters registered as participants are captured by
+    /// `StoreScene` and restored by `RecallScene`.
+    pub fn new(participants: &'a [(ClusterId, &'a dyn SceneParticipant)], rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            participants,
+            scenes: RefCell::new(heapless::Vec::new()),
+            current: RefCell::new(heapless::Vec::new()),
+            changed: Cell::new(false),
+        }
+    }
+
+    /// Remove the scenes of a fabric which was removed
+    pub fn remove_fabric(&self, fab_idx: u8) {
+        self.scenes
+            .borrow_mut()
+            .retain(|scene| scene.fab_idx != fab_idx);
+        self.current
+            .borrow_mut()
+            .retain(|current| current.fab_idx != fab_idx);
+
+        self.table_changed();
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::SceneTableSize(codec) => {
+                        codec.encode(writer, SCENE_TABLE_SIZE as _)
+                    }
+                    Attributes::FabricSceneInfo(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for fab_idx in 1..=fabric::MAX_SUPPORTED_FABRICS as u8 {
+                            if !attr.fab_filter || attr.fab_idx == fab_idx {
+                                if let Some(info) = self.scene_info(fab_idx, attr.fab_filter) {
+                                    info.to_tlv(&mut writer, TagType::Anonymous)?;
+                                }
+                            }
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        let fab_idx = exchange.accessor()?.fab_idx;
+
+        // The scene table is fabric-scoped
+        if fab_idx == 0 {
+            Err(ErrorCode::UnsupportedAccess)?;
+        }
+
+        let endpoint = cmd
+            .node
+            .check_endpoint(cmd.endpoint_id)
+            .map_err(|_| ErrorCode::EndpointNotFound)?;
+
+        match cmd.cmd_id.try_into()? {
+            Commands::AddScene => {
+                cmd_enter!("AddScene");
+                let req = AddSceneReq::from_tlv(data)?;
+
+                let result = self.add_scene(endpoint, fab_idx, &req);
+
+                encoder
+                    .with_command(RespCommands::AddSceneResponse as _)?
+                    .set(SceneResp {
+                        status: Self::status(&result),
+                        group_id: req.group_id,
+                        scene_id: req.scene_id,
+                    })?;
+            }
+            Commands::ViewScene => {
+                cmd_enter!("ViewScene");
+                let req = SceneReq::from_tlv(data)?;
+
+                let result = self.view_scene(fab_idx, req.group_id, req.scene_id);
+
+                encoder
+                    .with_command(RespCommands::ViewSceneResponse as _)?
+                    .set(ViewSceneResp {
+                        status: Self::status(&result),
+                        group_id: req.group_id,
+                        scene_id: req.scene_id,
+                        scene: result.as_ref().ok(),
+                    })?;
+            }
+            Commands::RemoveScene => {
+                cmd_enter!("RemoveScene");
+                let req = SceneReq::from_tlv(data)?;
+
+                let result = self.remove_scene(fab_idx, req.group_id, req.scene_id);
+
+                encoder
+                    .with_command(RespCommands::RemoveSceneResponse as _)?
+                    .set(SceneResp {
+                        status: Self::status(&result),
+                        group_id: req.group_id,
+                        scene_id: req.scene_id,
+                    })?;
+            }
+            Commands::RemoveAllScenes => {
+                cmd_enter!("RemoveAllScenes");
+                let req = GroupReq::from_tlv(data)?;
+
+                let result = self.remove_all_scenes(fab_idx, req.group_id);
+
+                encoder
+                    .with_command(RespCommands::RemoveAllScenesResponse as _)?
+                    .set(GroupResp {
+                        status: Self::status(&result),
+                        group_id: req.group_id,
+                    })?;
+            }
+            Commands::StoreScene => {
+                cmd_enter!("StoreScene");
+                let req = SceneReq::from_tlv(data)?;
+
+                let result = self.store_scene(endpoint, fab_idx, req.group_id, req.scene_id);
+
+                encoder
+                    .with_command(RespCommands::StoreSceneResponse as _)?
+                    .set(SceneResp {
+                        status: Self::status(&result),
+                        group_id: req.group_id,
+                        scene_id: req.scene_id,
+                    })?;
+            }
+            Commands::RecallScene => {
+                cmd_enter!("RecallScene");
+                let req = RecallSceneReq::from_tlv(data)?;
+
+                let transition_time = req.transition_time.and_then(Nullable::notnull);
+
+                self.recall_scene(fab_idx, req.group_id, req.scene_id, transition_time)?;
+            }
+            Commands::GetSceneMembership => {
+                cmd_enter!("GetSceneMembership");
+                let req = GroupReq::from_tlv(data)?;
+
+                let result = self.scene_membership(fab_idx, req.group_id);
+
+                encoder
+                    .with_command(RespCommands::GetSceneMembershipResponse as _)?
+                    .set(SceneMembershipResp {
+                        status: Self::status(&result),
+                        capacity: Nullable::NotNull(
+                            self.remaining_capacity(&self.scenes.borrow(), fab_idx),
+                        ),
+                        group_id: req.group_id,
+                        scene_list: result.as_ref().ok().map(|scenes| scenes.as_slice()),
+                    })?;
+            }
+            Commands::CopyScene => {
+                cmd_enter!("CopyScene");
+                let req = CopySceneReq::from_tlv(data)?;
+
+                let result = self.copy_scene(fab_idx, &req);
+
+                encoder
+                    .with_command(RespCommands::CopySceneResponse as _)?
+                    .set(CopySceneResp {
+                        status: Self::status(&result),
+                        group_identifier_from: req.group_identifier_from,
+                        scene_identifier_from: req.scene_identifier_from,
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_scene(&self, endpoint: &Endpoint, fab_idx: u8, req: &AddSceneReq) -> Result<(), Error> {
+        Self::check_scene(req.group_id, req.scene_id)?;
+
+        if req.transition_time > MAX_TRANSITION_TIME {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let mut scene = Scene::new(fab_idx, req.group_id, req.scene_id);
+
+        scene.transition_time = req.transition_time;
+        scene
+            .name
+            .push_str(req.scene_name.as_str()?)
+            .map_err(|_| ErrorCode::ConstraintError)?;
+
+        // Extension field sets of clusters and attributes which are not part of scenes
+        // on this endpoint are ignored
+        for set in req.extension_field_sets.iter() {
+            if let Ok(cluster) = endpoint.check_cluster(set.cluster_id) {
+                scene.set_values(
+                    set.cluster_id,
+                    set.attribute_value_list.iter().filter(|value| {
+                        cluster.attributes.iter().any(|attr| {
+                            attr.id as u32 == value.attribute_id
+                                && attr.quality.contains(Quality::SCENE)
+                        })
+                    }),
+                )?;
+            }
+        }
+
+        self.insert(scene)?;
+        self.invalidate(fab_idx, req.group_id, Some(req.scene_id));
+
+        Ok(())
+    }
+
+    fn view_scene(&self, fab_idx: u8, group_id: u16, scene_id: u8) -> Result<Scene, Error> {
+        Self::check_scene(group_id, scene_id)?;
+
+        self.find(fab_idx, group_id, scene_id)
+    }
+
+    fn remove_scene(&self, fab_idx: u8, group_id: u16, scene_id: u8) -> Result<(), Error> {
+        Self::check_scene(group_id, scene_id)?;
+
+        let mut scenes = self.scenes.borrow_mut();
+
+        let position = scenes
+            .iter()
+            .position(|scene| scene.is(fab_idx, group_id, scene_id))
+            .ok_or(ErrorCode::NotFound)?;
+
+        scenes.remove(position);
+        drop(scenes);
+
+        self.invalidate(fab_idx, group_id, Some(scene_id));
+        self.table_changed();
+
+        Ok(())
+    }
+
+    fn remove_all_scenes(&self, fab_idx: u8, group_id: u16) -> Result<(), Error> {
+        Self::check_group(group_id)?;
+
+        self.scenes
+            .borrow_mut()
+            .retain(|scene| scene.fab_idx != fab_idx || scene.group_id != group_id);
+
+        self.invalidate(fab_idx, group_id, None);
+        self.table_changed();
+
+        Ok(())
+    }
+
+    // Capture the current values of all scene attributes of the endpoint
+    fn store_scene(
+        &self,
+        endpoint: &Endpoint,
+        fab_idx: u8,
+        group_id: u16,
+        scene_id: u8,
+    ) -> Result<(), Error> {
+        Self::check_scene(group_id, scene_id)?;
+
+        // An existing scene keeps its name and transition time
+        let mut scene = self
+            .find(fab_idx, group_id, scene_id)
+            .unwrap_or_else(|_| Scene::new(fab_idx, group_id, scene_id));
+
+        scene.values.clear();
+
+        for cluster in endpoint.clusters {
+            if let Some(participant) = self.participant(cluster.id) {
+                let values = cluster
+                    .attributes
+                    .iter()
+                    .filter(|attr| attr.quality.contains(Quality::SCENE))
+                    .filter_map(|attr| {
+                        participant
+                            .scene_value(attr.id)
+                            .map(|value| AttributeValuePair {
+                                attribute_id: attr.id as _,
+                                attribute_value: value,
+                            })
+                    });
+
+                scene.set_values(cluster.id, values)?;
+            }
+        }
+
+        self.insert(scene)?;
+        self.set_current(fab_idx, group_id, scene_id);
+
+        Ok(())
+    }
+
+    fn recall_scene(
+        &self,
+        fab_idx: u8,
+        group_id: u16,
+        scene_id: u8,
+        transition_time: Option<u32>,
+    ) -> Result<(), Error> {
+        Self::check_scene(group_id, scene_id)?;
+
+        let scene = self.find(fab_idx, group_id, scene_id)?;
+
+        let transition_time = transition_time.unwrap_or(scene.transition_time);
+        let transition_time = Duration::from_millis(transition_time as u64);
+
+        info!(
+            "Recalling scene {}/{} in {}ms",
+            group_id,
+            scene_id,
+            transition_time.as_millis()
+        );
+
+        for (cluster_id, participant) in self.participants {
+            let values: heapless::Vec<_, MAX_SCENE_VALUES> = scene
+                .values
+                .iter()
+                .filter(|(cluster, _)| cluster == cluster_id)
+                .map(|(_, value)| *value)
+                .collect();
+
+            if !values.is_empty() {
+                participant.recall_scene(&values, transition_time);
+            }
+        }
+
+        self.set_current(fab_idx, group_id, scene_id);
+
+        Ok(())
+    }
+
+    fn scene_membership(
+        &self,
+        fab_idx: u8,
+        group_id: u16,
+    ) -> Result<heapless::Vec<u8, SCENES_PER_FABRIC>, Error> {
+        Self::check_group(group_id)?;
+
+        Ok(self
+            .scenes
+            .borrow()
+            .iter()
+            .filter(|scene| scene.fab_idx == fab_idx && scene.group_id == group_id)
+            .map(|scene| scene.scene_id)
+            .collect())
+    }
+
+    fn copy_scene(&self, fab_idx: u8, req: &CopySceneReq) -> Result<(), Error> {
+        Self::check_group(req.group_identifier_from)?;
+        Self::check_group(req.group_identifier_to)?;
+
+        if req.mode & COPY_ALL_SCENES != 0 {
+            let scenes: heapless::Vec<_, SCENES_PER_FABRIC> = self
+                .scenes
+                .borrow()
+                .iter()
+                .filter(|scene| {
+                    scene.fab_idx == fab_idx && scene.group_id == req.group_identifier_from
+                })
+                .cloned()
+                .collect();
+
+            for mut scene in scenes {
+                scene.group_id = req.group_identifier_to;
+                self.insert(scene)?;
+            }
+        } else {
+            Self::check_scene(req.group_identifier_from, req.scene_identifier_from)?;
+            Self::check_scene(req.group_identifier_to, req.scene_identifier_to)?;
+
+            let mut scene = self.find(
+                fab_idx,
+                req.group_identifier_from,
+                req.scene_identifier_from,
+            )?;
+
+            scene.group_id = req.group_identifier_to;
+            scene.scene_id = req.scene_identifier_to;
+
+            self.insert(scene)?;
+        }
+
+        Ok(())
+    }
+
+    fn find(&self, fab_idx: u8, group_id: u16, scene_id: u8) -> Result<Scene, Error> {
+        self.scenes
+            .borrow()
+            .iter()
+            .find(|scene| scene.is(fab_idx, group_id, scene_id))
+            .cloned()
+            .ok_or_else(|| ErrorCode::NotFound.into())
+    }
+
+    // Add a scene, or replace the existing scene with the same identifiers
+    fn insert(&self, scene: Scene) -> Result<(), Error> {
+        let mut scenes = self.scenes.borrow_mut();
+
+        if let Some(existing) = scenes
+            .iter_mut()
+            .find(|existing| existing.is(scene.fab_idx, scene.group_id, scene.scene_id))
+        {
+            *existing = scene;
+        } else {
+            if self.remaining_capacity(&scenes, scene.fab_idx) == 0 {
+                Err(ErrorCode::ResourceExhausted)?;
+            }
+
+            scenes
+                .push(scene)
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        drop(scenes);
+
+        self.table_changed();
+
+        Ok(())
+    }
+
+    fn remaining_capacity(&self, scenes: &[Scene], fab_idx: u8) -> u8 {
+        let used = scenes
+            .iter()
+            .filter(|scene| scene.fab_idx == fab_idx)
+            .count();
+
+        SCENES_PER_FABRIC
+            .saturating_sub(used)
+            .min(SCENE_TABLE_SIZE - scenes.len()) as _
+    }
+
+    fn set_current(&self, fab_idx: u8, group_id: u16, scene_id: u8) {
+        let current = CurrentScene {
+            fab_idx,
+            group_id,
+            scene_id,
+            valid: true,
+        };
+
+        let mut currents = self.current.borrow_mut();
+
+        if let Some(existing) = currents.iter_mut().find(|c| c.fab_idx == fab_idx) {
+            *existing = current;
+        } else {
+            // There is one entry per fabric at most
+            currents.push(current).unwrap();
+        }
+
+        drop(currents);
+
+        self.data_ver.changed();
+    }
+
+    // The current scene is no longer valid once it is modified or removed
+    fn invalidate(&self, fab_idx: u8, group_id: u16, scene_id: Option<u8>) {
+        for current in self.current.borrow_mut().iter_mut() {
+            if current.fab_idx == fab_idx
+                && current.group_id == group_id
+                && scene_id.map(|id| id == current.scene_id).unwrap_or(true)
+            {
+                current.valid = false;
+            }
+        }
+    }
+
+    fn scene_info(&self, fab_idx: u8, always: bool) -> Option<SceneInfo> {
+        let scenes = self.scenes.borrow();
+        let current = self
+            .current
+            .borrow()
+            .iter()
+            .find(|current| current.fab_idx == fab_idx)
+            .copied();
+
+        let scene_count = scenes
+            .iter()
+            .filter(|scene| scene.fab_idx == fab_idx)
+            .count();
+
+        if !always && scene_count == 0 && current.is_none() {
+            return None;
+        }
+
+        Some(SceneInfo {
+            scene_count: scene_count as _,
+            current_scene: current.map(|current| current.scene_id).unwrap_or(0),
+            current_group: current.map(|current| current.group_id).unwrap_or(0),
+            scene_valid: current.map(|current| current.valid).unwrap_or(false),
+            remaining_capacity: self.remaining_capacity(&scenes, fab_idx),
+            fab_idx,
+        })
+    }
+
+    fn participant(&self, cluster_id: ClusterId) -> Option<&'a dyn SceneParticipant> {
+        self.participants
+            .iter()
+            .find(|(id, _)| *id == cluster_id)
+            .map(|(_, participant)| *participant)
+    }
+
+    fn table_changed(&self) {
+        self.changed.set(true);
+        self.data_ver.changed();
+    }
+
+    // There is no Groups cluster yet, so only scenes outside of groups can be used
+    fn check_group(group_id: u16) -> Result<(), Error> {
+        if group_id != 0 {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_scene(group_id: u16, scene_id: u8) -> Result<(), Error> {
+        if scene_id > MAX_SCENE_ID {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        Self::check_group(group_id)
+    }
+
+    fn status<T>(result: &Result<T, Error>) -> u8 {
+        match result {
+            Ok(_) => IMStatusCode::Success as _,
+            Err(err) => IMStatusCode::from(err.code()) as _,
+        }
+    }
+}
+
+impl<'a> Handler for ScenesManagementCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        ScenesManagementCluster::read(self, attr, encoder)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        ScenesManagementCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        ScenesManagementCluster::remove_fabric(self, fab_idx)
+    }
+}
+
+impl<'a> NonBlockingHandler for ScenesManagementCluster<'a> {}
+
+impl<'a> GlobalScene for ScenesManagementCluster<'a> {
+    fn store_global_scene(&self, endpoint: &Endpoint, fab_idx: u8) -> Result<(), Error> {
+        // The scene table is fabric-scoped
+        if fab_idx == 0 {
+            Err(ErrorCode::UnsupportedAccess)?;
+        }
+
+        self.store_scene(endpoint, fab_idx, GLOBAL_SCENE_GROUP_ID, GLOBAL_SCENE_ID)
+    }
+
+    fn recall_global_scene(&self, fab_idx: u8) -> Result<(), Error> {
+        self.recall_scene(fab_idx, GLOBAL_SCENE_GROUP_ID, GLOBAL_SCENE_ID, None)
+    }
+}
+
+/// Persists the scene table, which is not exposed as attributes
+impl<'a> StatePersist for ScenesManagementCluster<'a> {
+    fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        tlv::from_tlv(&mut *self.scenes.borrow_mut(), &root)?;
+        self.current.borrow_mut().clear();
+
+        self.changed.set(false);
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        if self.changed.get() {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            self.scenes
+                .borrow()
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Anonymous)?;
+
+            self.changed.set(false);
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a> ChangeNotifier<()> for ScenesManagementCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use embassy_time::Duration;
+
+    use crate::{
+        data_model::{
+            cluster_level_control, cluster_on_off,
+            device_types::DEV_TYPE_ON_OFF_LIGHT,
+            objects::{AttrId, Endpoint},
+            persist::StatePersist,
+        },
+        error::ErrorCode,
+        tlv::{TLVArray, UtfStr},
+        utils::rand::dummy_rand,
+    };
+
+    use super::{
+        AddSceneReq, AttributeValuePair, CopySceneReq, ExtensionFieldSet, GlobalScene,
+        SceneParticipant, ScenesManagementCluster, CLUSTER, SCENES_PER_FABRIC,
+    };
+
+    const ENDPOINT: Endpoint<'static> = Endpoint {
+        id: 1,
+        device_type: DEV_TYPE_ON_OFF_LIGHT,
+        extra_device_types: &[],
+        clusters: &[cluster_on_off::CLUSTER, CLUSTER],
+        client_clusters: &[],
+    };
+
+    struct Light {
+        on: Cell<u32>,
+        recalled: Cell<Option<(u32, Duration)>>,
+    }
+
+    impl Light {
+        fn new() -> Self {
+            Self {
+                on: Cell::new(0),
+                recalled: Cell::new(None),
+            }
+        }
+    }
+
+    impl SceneParticipant for Light {
+        fn scene_value(&self, attr_id: AttrId) -> Option<u32> {
+            (attr_id == 0).then(|| self.on.get())
+        }
+
+        fn recall_scene(&self, values: &[AttributeValuePair], transition_time: Duration) {
+            let value = values.first().unwrap().attribute_value;
+
+            self.on.set(value);
+            self.recalled.set(Some((value, transition_time)));
+        }
+    }
+
+    fn pair(attribute_id: u32, attribute_value: u32) -> AttributeValuePair {
+        AttributeValuePair {
+            attribute_id,
+            attribute_value,
+        }
+    }
+
+    #[test]
+    fn test_add_scene() {
+        let scenes = ScenesManagementCluster::new(&[], dummy_rand);
+
+        let on_off = [pair(0, 1), pair(0x4000, 1)];
+        let level = [pair(0, 50)];
+        let sets = [
+            ExtensionFieldSet {
+                cluster_id: cluster_on_off::ID,
+                attribute_value_list: TLVArray::new(&on_off),
+            },
+            ExtensionFieldSet {
+                cluster_id: cluster_level_control::ID,
+                attribute_value_list: TLVArray::new(&level),
+            },
+        ];
+
+        let req = AddSceneReq {
+            group_id: 0,
+            scene_id: 1,
+            transition_time: 1000,
+            scene_name: UtfStr::new(b"Evening"),
+            extension_field_sets: TLVArray::new(&sets),
+        };
+
+        scenes.add_scene(&ENDPOINT, 1, &req).unwrap();
+
+        // Only OnOff is a scene attribute, and Level Control is not on the endpoint
+        let scene = scenes.view_scene(1, 0, 1).unwrap();
+        assert_eq!(scene.name.as_str(), "Evening");
+        assert_eq!(scene.transition_time, 1000);
+        assert_eq!(scene.values.as_slice(), &[(cluster_on_off::ID, pair(0, 1))]);
+
+        // Scenes are fabric-scoped
+        assert_eq!(
+            scenes.view_scene(2, 0, 1).unwrap_err().code(),
+            ErrorCode::NotFound
+        );
+
+        // No group table yet
+        assert_eq!(
+            scenes.view_scene(1, 5, 1).unwrap_err().code(),
+            ErrorCode::InvalidCommand
+        );
+
+        scenes.remove_scene(1, 0, 1).unwrap();
+        assert_eq!(
+            scenes.remove_scene(1, 0, 1).unwrap_err().code(),
+            ErrorCode::NotFound
+        );
+    }
+
+    #[test]
+    fn test_store_recall_scene() {
+        let light = Light::new();
+        let participants: [(u32, &dyn SceneParticipant); 1] = [(cluster_on_off::ID, &light)];
+        let scenes = ScenesManagementCluster::new(&participants, dummy_rand);
+
+        light.on.set(1);
+        scenes.store_scene(&ENDPOINT, 1, 0, 2).unwrap();
+
+        let info = scenes.scene_info(1, false).unwrap();
+        assert_eq!(info.scene_count, 1);
+        assert_eq!(info.current_scene, 2);
+        assert!(info.scene_valid);
+
+        light.on.set(0);
+        scenes.recall_scene(1, 0, 2, Some(500)).unwrap();
+        assert_eq!(light.recalled.get(), Some((1, Duration::from_millis(500))));
+
+        assert_eq!(
+            scenes.recall_scene(1, 0, 3, None).unwrap_err().code(),
+            ErrorCode::NotFound
+        );
+
+        scenes.remove_all_scenes(1, 0).unwrap();
+        assert!(!scenes.scene_info(1, false).unwrap().scene_valid);
+    }
+
+    #[test]
+    fn test_global_scene() {
+        let light = Light::new();
+        let participants: [(u32, &dyn SceneParticipant); 1] = [(cluster_on_off::ID, &light)];
+        let scenes = ScenesManagementCluster::new(&participants, dummy_rand);
+
+        assert_eq!(
+            scenes.recall_global_scene(1).unwrap_err().code(),
+            ErrorCode::NotFound
+        );
+
+        light.on.set(1);
+        scenes.store_global_scene(&ENDPOINT, 1).unwrap();
+        assert_eq!(scenes.scene_membership(1, 0).unwrap().as_slice(), &[0]);
+
+        light.on.set(0);
+        scenes.recall_global_scene(1).unwrap();
+        assert_eq!(light.recalled.get(), Some((1, Duration::from_millis(0))));
+    }
+
+    #[test]
+    fn test_scene_capacity() {
+        let scenes = ScenesManagementCluster::new(&[], dummy_rand);
+
+        for scene_id in 0..SCENES_PER_FABRIC as u8 {
+            scenes.store_scene(&ENDPOINT, 1, 0, scene_id).unwrap();
+        }
+
+        assert_eq!(
+            scenes.store_scene(&ENDPOINT, 1, 0, 100).unwrap_err().code(),
+            ErrorCode::ResourceExhausted
+        );
+
+        // Storing an existing scene again does not need more room
+        scenes.store_scene(&ENDPOINT, 1, 0, 0).unwrap();
+
+        let copy = CopySceneReq {
+            mode: 0,
+            group_identifier_from: 0,
+            scene_identifier_from: 0,
+            group_identifier_to: 0,
+            scene_identifier_to: 100,
+        };
+        assert_eq!(
+            scenes.copy_scene(1, &copy).unwrap_err().code(),
+            ErrorCode::ResourceExhausted
+        );
+
+        // Other fabrics have their own quota
+        scenes.copy_scene(2, &copy).unwrap_err();
+        scenes.store_scene(&ENDPOINT, 2, 0, 0).unwrap();
+        scenes.copy_scene(2, &copy).unwrap();
+
+        assert_eq!(scenes.scene_membership(2, 0).unwrap().as_slice(), &[0, 100]);
+    }
+
+    #[test]
+    fn test_scene_persistence() {
+        let light = Light::new();
+        let participants: [(u32, &dyn SceneParticipant); 1] = [(cluster_on_off::ID, &light)];
+        let scenes = ScenesManagementCluster::new(&participants, dummy_rand);
+
+        light.on.set(1);
+        scenes.store_scene(&ENDPOINT, 1, 0, 1).unwrap();
+        scenes.store_scene(&ENDPOINT, 2, 0, 7).unwrap();
+
+        let mut buf = [0; 512];
+        let data = scenes.store(&mut buf).unwrap().unwrap();
+
+        let restored = ScenesManagementCluster::new(&participants, dummy_rand);
+        restored.load(data).unwrap();
+
+        assert!(restored.store(&mut buf).unwrap().is_none());
+        assert_eq!(
+            restored.view_scene(1, 0, 1).unwrap().values.as_slice(),
+            &[(cluster_on_off::ID, pair(0, 1))]
+        );
+        assert_eq!(restored.scene_membership(2, 0).unwrap().as_slice(), &[7]);
+    }
+}
@@ -22,8 +22,8 @@ use crate::{
 };
 
 use super::objects::{
-    AttrDataEncoder, AttrDetails, ChangeNotifier, Dataver, NonBlockingHandler, ATTRIBUTE_LIST,
-    FEATURE_MAP,
+    AttrDataEncoder, AttrDetails, ChangeNotifier, ClusterId, Dataver, EndptId, NonBlockingHandler,
+    ATTRIBUTE_LIST, FEATURE_MAP,
 };
 
 const CLUSTER_NETWORK_COMMISSIONING_ID: u32 = 0x0031;
@@ -63,6 +63,10 @@ impl Handler for TemplateCluster {
     fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         TemplateCluster::read(self, attr, encoder)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 impl NonBlockingHandler for TemplateCluster {}
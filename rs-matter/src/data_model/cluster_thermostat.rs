@@ -0,0 +1,1136 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+};
+
+use super::{
+    cluster_scenes_management::{AttributeValuePair, SceneParticipant},
+    objects::*,
+};
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, Nullable, TLVArray, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+use embassy_time::Duration;
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0201;
+
+/// Number of transitions of the weekly schedule of each day
+pub const DAILY_TRANSITIONS: u8 = 4;
+
+const DAYS_OF_WEEK: u8 = 7;
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+const MODE_HEAT: u8 = 0x01;
+const MODE_COOL: u8 = 0x02;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    LocalTemperature(AttrType<Nullable<i16>>) = 0x0000,
+    AbsMinHeatSetpointLimit(AttrType<i16>) = 0x0003,
+    AbsMaxHeatSetpointLimit(AttrType<i16>) = 0x0004,
+    AbsMinCoolSetpointLimit(AttrType<i16>) = 0x0005,
+    AbsMaxCoolSetpointLimit(AttrType<i16>) = 0x0006,
+    OccupiedCoolingSetpoint(AttrType<i16>) = 0x0011,
+    OccupiedHeatingSetpoint(AttrType<i16>) = 0x0012,
+    MinHeatSetpointLimit(AttrType<i16>) = 0x0015,
+    MaxHeatSetpointLimit(AttrType<i16>) = 0x0016,
+    MinCoolSetpointLimit(AttrType<i16>) = 0x0017,
+    MaxCoolSetpointLimit(AttrType<i16>) = 0x0018,
+    MinSetpointDeadBand(AttrType<i8>) = 0x0019,
+    ControlSequenceOfOperation(AttrType<u8>) = 0x001B,
+    SystemMode(AttrType<u8>) = 0x001C,
+    ThermostatRunningMode(AttrType<u8>) = 0x001E,
+    StartOfWeek(AttrType<u8>) = 0x0020,
+    NumberOfWeeklyTransitions(AttrType<u8>) = 0x0021,
+    NumberOfDailyTransitions(AttrType<u8>) = 0x0022,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    SetpointRaiseLower = 0x00,
+    SetWeeklySchedule = 0x01,
+    GetWeeklySchedule = 0x02,
+    ClearWeeklySchedule = 0x03,
+}
+
+command_enum!(Commands);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum RespCommands {
+    GetWeeklyScheduleResponse = 0x00,
+}
+
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    Heating = 0x01,
+    Cooling = 0x02,
+    ScheduleConfiguration = 0x08,
+    AutoMode = 0x20,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: Feature::Heating as u32
+        | Feature::Cooling as u32
+        | Feature::ScheduleConfiguration as u32
+        | Feature::AutoMode as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::LocalTemperature as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::AbsMinHeatSetpointLimit as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::AbsMaxHeatSetpointLimit as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::AbsMinCoolSetpointLimit as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::AbsMaxCoolSetpointLimit as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OccupiedCoolingSetpoint as u16,
+            Access::RWVO,
+            Quality::SN,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OccupiedHeatingSetpoint as u16,
+            Access::RWVO,
+            Quality::SN,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MinHeatSetpointLimit as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MaxHeatSetpointLimit as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MinCoolSetpointLimit as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MaxCoolSetpointLimit as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MinSetpointDeadBand as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ControlSequenceOfOperation as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SystemMode as u16,
+            Access::RWVM,
+            Quality::SN,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ThermostatRunningMode as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::StartOfWeek as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NumberOfWeeklyTransitions as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NumberOfDailyTransitions as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::SetpointRaiseLower as _,
+        CommandsDiscriminants::SetWeeklySchedule as _,
+        CommandsDiscriminants::GetWeeklySchedule as _,
+        CommandsDiscriminants::ClearWeeklySchedule as _,
+    ],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum ControlSequenceOfOperation {
+    CoolingOnly = 0,
+    CoolingWithReheat = 1,
+    HeatingOnly = 2,
+    HeatingWithReheat = 3,
+    CoolingAndHeating = 4,
+    CoolingAndHeatingWithReheat = 5,
+}
+
+impl ControlSequenceOfOperation {
+    fn heating(&self) -> bool {
+        !matches!(self, Self::CoolingOnly | Self::CoolingWithReheat)
+    }
+
+    fn cooling(&self) -> bool {
+        !matches!(self, Self::HeatingOnly | Self::HeatingWithReheat)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum SystemMode {
+    Off = 0,
+    Auto = 1,
+    Cool = 3,
+    Heat = 4,
+    EmergencyHeat = 5,
+    Precooling = 6,
+    FanOnly = 7,
+    Dry = 8,
+    Sleep = 9,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum ThermostatRunningMode {
+    Off = 0,
+    Cool = 3,
+    Heat = 4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum SetpointRaiseLowerMode {
+    Heat = 0,
+    Cool = 1,
+    Both = 2,
+}
+
+/// The limits of the setpoints the hardware supports, in 0.01°C
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetpointLimits {
+    pub abs_min_heat: i16,
+    pub abs_max_heat: i16,
+    pub abs_min_cool: i16,
+    pub abs_max_cool: i16,
+}
+
+impl Default for SetpointLimits {
+    fn default() -> Self {
+        Self {
+            abs_min_heat: 700,
+            abs_max_heat: 3000,
+            abs_min_cool: 1600,
+            abs_max_cool: 3200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, FromTLV, ToTLV)]
+pub struct ScheduleTransition {
+    /// Minutes since midnight
+    pub transition_time: u16,
+    pub heat_setpoint: Nullable<i16>,
+    pub cool_setpoint: Nullable<i16>,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct SetpointRaiseLowerReq {
+    pub mode: u8,
+    pub amount: i8,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct SetWeeklyScheduleReq<'a> {
+    pub number_of_transitions_for_sequence: u8,
+    pub day_of_week_for_sequence: u8,
+    pub mode_for_sequence: u8,
+    pub transitions: TLVArray<'a, ScheduleTransition>,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct GetWeeklyScheduleReq {
+    pub days_to_return: u8,
+    pub mode_to_return: u8,
+}
+
+#[derive(ToTLV)]
+#[tlvargs(lifetime = "'a")]
+struct WeeklyScheduleResp<'a> {
+    number_of_transitions_for_sequence: u8,
+    day_of_week_for_sequence: u8,
+    mode_for_sequence: u8,
+    transitions: &'a [ScheduleTransition],
+}
+
+/// The schedule of one day of the week
+#[derive(Debug, Clone)]
+struct DaySchedule {
+    mode: u8,
+    transitions: heapless::Vec<ScheduleTransition, { DAILY_TRANSITIONS as usize }>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetpointLimit {
+    MinHeat,
+    MaxHeat,
+    MinCool,
+    MaxCool,
+}
+
+/// The application side of the Thermostat cluster
+///
+/// Called whenever the system mode or one of the setpoints changes, so that the
+/// application can drive the heating and cooling with them.
+pub trait HvacController {
+    fn apply(&self, system_mode: SystemMode, heating_setpoint: i16, cooling_setpoint: i16);
+}
+
+impl<T> HvacController for &T
+where
+    T: HvacController,
+{
+    fn apply(&self, system_mode: SystemMode, heating_setpoint: i16, cooling_setpoint: i16) {
+        (**self).apply(system_mode, heating_setpoint, cooling_setpoint)
+    }
+}
+
+pub struct ThermostatCluster<T> {
+    data_ver: Dataver,
+    controller: T,
+    limits: SetpointLimits,
+    local_temperature: Cell<Option<i16>>,
+    occupied_cooling_setpoint: Cell<i16>,
+    occupied_heating_setpoint: Cell<i16>,
+    min_heat_setpoint_limit: Cell<i16>,
+    max_heat_setpoint_limit: Cell<i16>,
+    min_cool_setpoint_limit: Cell<i16>,
+    max_cool_setpoint_limit: Cell<i16>,
+    min_setpoint_dead_band: Cell<i8>,
+    control_sequence: Cell<ControlSequenceOfOperation>,
+    system_mode: Cell<SystemMode>,
+    running_mode: Cell<ThermostatRunningMode>,
+    schedule: RefCell<[Option<DaySchedule>; DAYS_OF_WEEK as usize]>,
+}
+
+impl<T> ThermostatCluster<T>
+where
+    T: HvacController,
+{
+    pub fn new(controller: T, limits: SetpointLimits, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            controller,
+            limits,
+            local_temperature: Cell::new(None),
+            occupied_cooling_setpoint: Cell::new(
+                2600.clamp(limits.abs_min_cool, limits.abs_max_cool),
+            ),
+            occupied_heating_setpoint: Cell::new(
+                2000.clamp(limits.abs_min_heat, limits.abs_max_heat),
+            ),
+            min_heat_setpoint_limit: Cell::new(limits.abs_min_heat),
+            max_heat_setpoint_limit: Cell::new(limits.abs_max_heat),
+            min_cool_setpoint_limit: Cell::new(limits.abs_min_cool),
+            max_cool_setpoint_limit: Cell::new(limits.abs_max_cool),
+            min_setpoint_dead_band: Cell::new(25),
+            control_sequence: Cell::new(ControlSequenceOfOperation::CoolingAndHeating),
+            system_mode: Cell::new(SystemMode::Off),
+            running_mode: Cell::new(ThermostatRunningMode::Off),
+            schedule: RefCell::new(Default::default()),
+        }
+    }
+
+    /// Update the measured temperature in 0.01°C, `None` if the measurement is invalid
+    pub fn set_local_temperature(&self, temperature: Option<i16>) {
+        if self.local_temperature.replace(temperature) != temperature {
+            self.data_ver.changed();
+        }
+    }
+
+    /// Update what the system is currently doing, e.g. heating while in `SystemMode::Auto`
+    pub fn set_running_mode(&self, mode: ThermostatRunningMode) {
+        if self.running_mode.replace(mode) != mode {
+            self.data_ver.changed();
+        }
+    }
+
+    pub fn system_mode(&self) -> SystemMode {
+        self.system_mode.get()
+    }
+
+    pub fn heating_setpoint(&self) -> i16 {
+        self.occupied_heating_setpoint.get()
+    }
+
+    pub fn cooling_setpoint(&self) -> i16 {
+        self.occupied_cooling_setpoint.get()
+    }
+
+    pub fn set_system_mode(&self, mode: SystemMode) -> Result<(), Error> {
+        let sequence = self.control_sequence.get();
+
+        let supported = match mode {
+            SystemMode::Heat | SystemMode::EmergencyHeat => sequence.heating(),
+            SystemMode::Cool | SystemMode::Precooling => sequence.cooling(),
+            SystemMode::Auto => sequence.heating() && sequence.cooling(),
+            _ => true,
+        };
+
+        if !supported {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        if self.system_mode.replace(mode) != mode {
+            self.changed();
+        }
+
+        Ok(())
+    }
+
+    /// Set the heating setpoint, raising the cooling setpoint if needed to keep the
+    /// minimum deadband between the two
+    pub fn set_heating_setpoint(&self, setpoint: i16) -> Result<(), Error> {
+        self.set_setpoints(Some(setpoint), None)
+    }
+
+    /// Set the cooling setpoint, lowering the heating setpoint if needed to keep the
+    /// minimum deadband between the two
+    pub fn set_cooling_setpoint(&self, setpoint: i16) -> Result<(), Error> {
+        self.set_setpoints(None, Some(setpoint))
+    }
+
+    /// Apply the weekly schedule of a day, from 0 for Sunday, at `minutes` since midnight
+    ///
+    /// Meant to be called by the application every time its clock reaches a new
+    /// minute; the setpoints of the last transition of the day so far are applied.
+    pub fn apply_schedule(&self, day: u8, minutes: u16) -> Result<(), Error> {
+        let transition = self
+            .schedule
+            .borrow()
+            .get(day as usize)
+            .and_then(Option::as_ref)
+            .and_then(|schedule| {
+                schedule
+                    .transitions
+                    .iter()
+                    .filter(|transition| transition.transition_time <= minutes)
+                    .max_by_key(|transition| transition.transition_time)
+                    .copied()
+            });
+
+        if let Some(transition) = transition {
+            self.set_setpoints(
+                transition.heat_setpoint.notnull(),
+                transition.cool_setpoint.notnull(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::LocalTemperature(codec) => codec.encode(
+                        writer,
+                        match self.local_temperature.get() {
+                            Some(temperature) => Nullable::NotNull(temperature),
+                            None => Nullable::Null,
+                        },
+                    ),
+                    Attributes::AbsMinHeatSetpointLimit(codec) => {
+                        codec.encode(writer, self.limits.abs_min_heat)
+                    }
+                    Attributes::AbsMaxHeatSetpointLimit(codec) => {
+                        codec.encode(writer, self.limits.abs_max_heat)
+                    }
+                    Attributes::AbsMinCoolSetpointLimit(codec) => {
+                        codec.encode(writer, self.limits.abs_min_cool)
+                    }
+                    Attributes::AbsMaxCoolSetpointLimit(codec) => {
+                        codec.encode(writer, self.limits.abs_max_cool)
+                    }
+                    Attributes::OccupiedCoolingSetpoint(codec) => {
+                        codec.encode(writer, self.occupied_cooling_setpoint.get())
+                    }
+                    Attributes::OccupiedHeatingSetpoint(codec) => {
+                        codec.encode(writer, self.occupied_heating_setpoint.get())
+                    }
+                    Attributes::MinHeatSetpointLimit(codec) => {
+                        codec.encode(writer, self.min_heat_setpoint_limit.get())
+                    }
+                    Attributes::MaxHeatSetpointLimit(codec) => {
+                        codec.encode(writer, self.max_heat_setpoint_limit.get())
+                    }
+                    Attributes::MinCoolSetpointLimit(codec) => {
+                        codec.encode(writer, self.min_cool_setpoint_limit.get())
+                    }
+                    Attributes::MaxCoolSetpointLimit(codec) => {
+                        codec.encode(writer, self.max_cool_setpoint_limit.get())
+                    }
+                    Attributes::MinSetpointDeadBand(codec) => {
+                        codec.encode(writer, self.min_setpoint_dead_band.get())
+                    }
+                    Attributes::ControlSequenceOfOperation(codec) => {
+                        codec.encode(writer, self.control_sequence.get() as _)
+                    }
+                    Attributes::SystemMode(codec) => {
+                        codec.encode(writer, self.system_mode.get() as _)
+                    }
+                    Attributes::ThermostatRunningMode(codec) => {
+                        codec.encode(writer, self.running_mode.get() as _)
+                    }
+                    // Sunday
+                    Attributes::StartOfWeek(codec) => codec.encode(writer, 0),
+                    Attributes::NumberOfWeeklyTransitions(codec) => {
+                        codec.encode(writer, DAILY_TRANSITIONS * DAYS_OF_WEEK)
+                    }
+                    Attributes::NumberOfDailyTransitions(codec) => {
+                        codec.encode(writer, DAILY_TRANSITIONS)
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::OccupiedCoolingSetpoint(codec) => {
+                self.set_cooling_setpoint(codec.decode(data)?)?
+            }
+            Attributes::OccupiedHeatingSetpoint(codec) => {
+                self.set_heating_setpoint(codec.decode(data)?)?
+            }
+            Attributes::MinHeatSetpointLimit(codec) => {
+                self.set_limit(SetpointLimit::MinHeat, codec.decode(data)?)?
+            }
+            Attributes::MaxHeatSetpointLimit(codec) => {
+                self.set_limit(SetpointLimit::MaxHeat, codec.decode(data)?)?
+            }
+            Attributes::MinCoolSetpointLimit(codec) => {
+                self.set_limit(SetpointLimit::MinCool, codec.decode(data)?)?
+            }
+            Attributes::MaxCoolSetpointLimit(codec) => {
+                self.set_limit(SetpointLimit::MaxCool, codec.decode(data)?)?
+            }
+            Attributes::MinSetpointDeadBand(codec) => {
+                let dead_band = codec.decode(data)?;
+
+                if !(0..=25).contains(&dead_band) {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                self.min_setpoint_dead_band.set(dead_band);
+                self.data_ver.changed();
+            }
+            Attributes::ControlSequenceOfOperation(codec) => {
+                let sequence = ControlSequenceOfOperation::from_repr(codec.decode(data)?)
+                    .ok_or(ErrorCode::ConstraintError)?;
+
+                self.control_sequence.set(sequence);
+                self.data_ver.changed();
+            }
+            Attributes::SystemMode(codec) => self.set_system_mode(
+                SystemMode::from_repr(codec.decode(data)?).ok_or(ErrorCode::ConstraintError)?,
+            )?,
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::SetpointRaiseLower => {
+                cmd_enter!("SetpointRaiseLower");
+                let req = SetpointRaiseLowerReq::from_tlv(data)?;
+
+                let mode =
+                    SetpointRaiseLowerMode::from_repr(req.mode).ok_or(ErrorCode::InvalidCommand)?;
+
+                self.raise_lower(mode, req.amount)?;
+            }
+            Commands::SetWeeklySchedule => {
+                cmd_enter!("SetWeeklySchedule");
+                let req = SetWeeklyScheduleReq::from_tlv(data)?;
+
+                self.set_weekly_schedule(&req)?;
+            }
+            Commands::GetWeeklySchedule => {
+                cmd_enter!("GetWeeklySchedule");
+                let req = GetWeeklyScheduleReq::from_tlv(data)?;
+
+                let schedule = self.schedule.borrow();
+
+                let (day, day_schedule) = (0..DAYS_OF_WEEK)
+                    .filter(|day| req.days_to_return & (1 << day) != 0)
+                    .find_map(|day| {
+                        schedule[day as usize]
+                            .as_ref()
+                            .filter(|schedule| schedule.mode & req.mode_to_return != 0)
+                            .map(|schedule| (day, Some(schedule)))
+                    })
+                    .or_else(|| {
+                        (0..DAYS_OF_WEEK)
+                            .find(|day| req.days_to_return & (1 << day) != 0)
+                            .map(|day| (day, None))
+                    })
+                    .ok_or(ErrorCode::InvalidCommand)?;
+
+                let transitions = day_schedule
+                    .map(|schedule| schedule.transitions.as_slice())
+                    .unwrap_or(&[]);
+
+                encoder
+                    .with_command(RespCommands::GetWeeklyScheduleResponse as _)?
+                    .set(WeeklyScheduleResp {
+                        number_of_transitions_for_sequence: transitions.len() as _,
+                        day_of_week_for_sequence: 1 << day,
+                        mode_for_sequence: day_schedule
+                            .map(|schedule| schedule.mode)
+                            .unwrap_or(req.mode_to_return),
+                        transitions,
+                    })?;
+            }
+            Commands::ClearWeeklySchedule => {
+                cmd_enter!("ClearWeeklySchedule");
+
+                *self.schedule.borrow_mut() = Default::default();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn raise_lower(&self, mode: SetpointRaiseLowerMode, amount: i8) -> Result<(), Error> {
+        // The amount is in 0.1°C, the setpoints in 0.01°C
+        let delta = amount as i16 * 10;
+
+        let heating = matches!(
+            mode,
+            SetpointRaiseLowerMode::Heat | SetpointRaiseLowerMode::Both
+        )
+        .then(|| {
+            self.occupied_heating_setpoint
+                .get()
+                .saturating_add(delta)
+                .clamp(
+                    self.min_heat_setpoint_limit.get(),
+                    self.max_heat_setpoint_limit.get(),
+                )
+        });
+
+        let cooling = matches!(
+            mode,
+            SetpointRaiseLowerMode::Cool | SetpointRaiseLowerMode::Both
+        )
+        .then(|| {
+            self.occupied_cooling_setpoint
+                .get()
+                .saturating_add(delta)
+                .clamp(
+                    self.min_cool_setpoint_limit.get(),
+                    self.max_cool_setpoint_limit.get(),
+                )
+        });
+
+        self.set_setpoints(heating, cooling)
+    }
+
+    fn set_setpoints(&self, heating: Option<i16>, cooling: Option<i16>) -> Result<(), Error> {
+        let dead_band = self.dead_band();
+
+        let mut heating_setpoint = heating.unwrap_or(self.occupied_heating_setpoint.get());
+        let mut cooling_setpoint = cooling.unwrap_or(self.occupied_cooling_setpoint.get());
+
+        if heating.is_some()
+            && !(self.min_heat_setpoint_limit.get()..=self.max_heat_setpoint_limit.get())
+                .contains(&heating_setpoint)
+            || cooling.is_some()
+                && !(self.min_cool_setpoint_limit.get()..=self.max_cool_setpoint_limit.get())
+                    .contains(&cooling_setpoint)
+        {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        // Keep the deadband by moving the setpoint which was not set
+        if cooling_setpoint - heating_setpoint < dead_band {
+            if cooling.is_none() {
+                cooling_setpoint = heating_setpoint + dead_band;
+
+                if cooling_setpoint > self.max_cool_setpoint_limit.get() {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+            } else if heating.is_none() {
+                heating_setpoint = cooling_setpoint - dead_band;
+
+                if heating_setpoint < self.min_heat_setpoint_limit.get() {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+            } else {
+                Err(ErrorCode::ConstraintError)?;
+            }
+        }
+
+        let previous_heating = self.occupied_heating_setpoint.replace(heating_setpoint);
+        let previous_cooling = self.occupied_cooling_setpoint.replace(cooling_setpoint);
+
+        if previous_heating != heating_setpoint || previous_cooling != cooling_setpoint {
+            self.changed();
+        }
+
+        Ok(())
+    }
+
+    fn set_limit(&self, limit: SetpointLimit, value: i16) -> Result<(), Error> {
+        let dead_band = self.dead_band();
+
+        // The heating limits are kept below the cooling limits by the deadband
+        let (limit, min, max) = match limit {
+            SetpointLimit::MinHeat => (
+                &self.min_heat_setpoint_limit,
+                self.limits.abs_min_heat,
+                self.max_heat_setpoint_limit
+                    .get()
+                    .min(self.min_cool_setpoint_limit.get() - dead_band),
+            ),
+            SetpointLimit::MaxHeat => (
+                &self.max_heat_setpoint_limit,
+                self.min_heat_setpoint_limit.get(),
+                self.limits
+                    .abs_max_heat
+                    .min(self.max_cool_setpoint_limit.get() - dead_band),
+            ),
+            SetpointLimit::MinCool => (
+                &self.min_cool_setpoint_limit,
+                self.limits
+                    .abs_min_cool
+                    .max(self.min_heat_setpoint_limit.get() + dead_band),
+                self.max_cool_setpoint_limit.get(),
+            ),
+            SetpointLimit::MaxCool => (
+                &self.max_cool_setpoint_limit,
+                self.min_cool_setpoint_limit
+                    .get()
+                    .max(self.max_heat_setpoint_limit.get() + dead_band),
+                self.limits.abs_max_cool,
+            ),
+        };
+
+        if !(min..=max).contains(&value) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        limit.set(value);
+        self.data_ver.changed();
+
+        // Keep the setpoints within the new limits, which keeps them apart by the deadband
+        let heating = self.occupied_heating_setpoint.get().clamp(
+            self.min_heat_setpoint_limit.get(),
+            self.max_heat_setpoint_limit.get(),
+        );
+        let cooling = self.occupied_cooling_setpoint.get().clamp(
+            self.min_cool_setpoint_limit.get(),
+            self.max_cool_setpoint_limit.get(),
+        );
+
+        let previous_heating = self.occupied_heating_setpoint.replace(heating);
+        let previous_cooling = self.occupied_cooling_setpoint.replace(cooling);
+
+        if previous_heating != heating || previous_cooling != cooling {
+            self.changed();
+        }
+
+        Ok(())
+    }
+
+    // The minimum deadband, in 0.01°C
+    fn dead_band(&self) -> i16 {
+        self.min_setpoint_dead_band.get() as i16 * 10
+    }
+
+    fn set_weekly_schedule(&self, req: &SetWeeklyScheduleReq) -> Result<(), Error> {
+        let days = req.day_of_week_for_sequence & ((1 << DAYS_OF_WEEK) - 1);
+        let mode = req.mode_for_sequence & (MODE_HEAT | MODE_COOL);
+
+        if days == 0 || mode == 0 {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        let mut transitions = heapless::Vec::new();
+
+        for transition in req.transitions.iter() {
+            if transition.transition_time >= MINUTES_PER_DAY
+                || mode & MODE_HEAT != 0 && transition.heat_setpoint.is_null()
+                || mode & MODE_COOL != 0 && transition.cool_setpoint.is_null()
+            {
+                Err(ErrorCode::InvalidCommand)?;
+            }
+
+            let heat_ok = transition
+                .heat_setpoint
+                .as_ref()
+                .notnull()
+                .map_or(true, |setpoint| {
+                    (self.min_heat_setpoint_limit.get()..=self.max_heat_setpoint_limit.get())
+                        .contains(setpoint)
+                });
+            let cool_ok = transition
+                .cool_setpoint
+                .as_ref()
+                .notnull()
+                .map_or(true, |setpoint| {
+                    (self.min_cool_setpoint_limit.get()..=self.max_cool_setpoint_limit.get())
+                        .contains(setpoint)
+                });
+
+            if !heat_ok || !cool_ok {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            transitions
+                .push(transition)
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        if transitions.len() != req.number_of_transitions_for_sequence as usize {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        info!("Schedule of days {:02x} set, with mode {}", days, mode);
+
+        let mut schedule = self.schedule.borrow_mut();
+
+        for day in 0..DAYS_OF_WEEK {
+            if days & (1 << day) != 0 {
+                schedule[day as usize] = Some(DaySchedule {
+                    mode,
+                    transitions: transitions.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn changed(&self) {
+        self.data_ver.changed();
+
+        self.controller.apply(
+            self.system_mode.get(),
+            self.occupied_heating_setpoint.get(),
+            self.occupied_cooling_setpoint.get(),
+        );
+    }
+}
+
+impl<T> SceneParticipant for ThermostatCluster<T>
+where
+    T: HvacController,
+{
+    fn scene_value(&self, attr_id: AttrId) -> Option<u32> {
+        let attr: Attributes = attr_id.try_into().ok()?;
+
+        match attr {
+            Attributes::OccupiedCoolingSetpoint(_) => {
+                Some(self.occupied_cooling_setpoint.get() as u16 as _)
+            }
+            Attributes::OccupiedHeatingSetpoint(_) => {
+                Some(self.occupied_heating_setpoint.get() as u16 as _)
+            }
+            Attributes::SystemMode(_) => Some(self.system_mode.get() as _),
+            _ => None,
+        }
+    }
+
+    fn recall_scene(&self, values: &[AttributeValuePair], _transition_time: Duration) {
+        let setpoint = |id: AttributesDiscriminants| {
+            values
+                .iter()
+                .find(|value| value.attribute_id == id as u32)
+                .map(|value| value.attribute_value as u16 as i16)
+        };
+
+        let _ = self.set_setpoints(
+            setpoint(AttributesDiscriminants::OccupiedHeatingSetpoint),
+            setpoint(AttributesDiscriminants::OccupiedCoolingSetpoint),
+        );
+
+        if let Some(mode) = values
+            .iter()
+            .find(|value| value.attribute_id == AttributesDiscriminants::SystemMode as u32)
+            .and_then(|value| SystemMode::from_repr(value.attribute_value as _))
+        {
+            let _ = self.set_system_mode(mode);
+        }
+    }
+}
+
+impl<T> Handler for ThermostatCluster<T>
+where
+    T: HvacController,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        ThermostatCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        ThermostatCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        ThermostatCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<T> NonBlockingHandler for ThermostatCluster<T> where T: HvacController {}
+
+impl<T> ChangeNotifier<()> for ThermostatCluster<T> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::{
+        error::ErrorCode,
+        tlv::{Nullable, TLVArray},
+        utils::rand::dummy_rand,
+    };
+
+    use super::{
+        HvacController, ScheduleTransition, SetWeeklyScheduleReq, SetpointLimit, SetpointLimits,
+        SetpointRaiseLowerMode, SystemMode, ThermostatCluster,
+    };
+
+    struct Hvac(Cell<Option<(SystemMode, i16, i16)>>);
+
+    impl HvacController for Hvac {
+        fn apply(&self, system_mode: SystemMode, heating_setpoint: i16, cooling_setpoint: i16) {
+            self.0
+                .set(Some((system_mode, heating_setpoint, cooling_setpoint)));
+        }
+    }
+
+    #[test]
+    fn test_dead_band() {
+        let hvac = Hvac(Cell::new(None));
+        let thermostat = ThermostatCluster::new(&hvac, SetpointLimits::default(), dummy_rand);
+
+        // Raising the heating setpoint pushes the cooling setpoint up by the deadband
+        thermostat.set_heating_setpoint(2500).unwrap();
+        assert_eq!(thermostat.cooling_setpoint(), 2750);
+        assert_eq!(hvac.0.get(), Some((SystemMode::Off, 2500, 2750)));
+
+        thermostat.set_cooling_setpoint(2000).unwrap();
+        assert_eq!(thermostat.heating_setpoint(), 1750);
+
+        // The cooling setpoint cannot go above its limit
+        assert_eq!(
+            thermostat.set_heating_setpoint(3000).map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(thermostat.heating_setpoint(), 1750);
+
+        assert!(thermostat.set_heating_setpoint(600).is_err());
+    }
+
+    #[test]
+    fn test_raise_lower() {
+        let hvac = Hvac(Cell::new(None));
+        let thermostat = ThermostatCluster::new(&hvac, SetpointLimits::default(), dummy_rand);
+
+        thermostat
+            .raise_lower(SetpointRaiseLowerMode::Both, 10)
+            .unwrap();
+        assert_eq!(thermostat.heating_setpoint(), 2100);
+        assert_eq!(thermostat.cooling_setpoint(), 2700);
+
+        thermostat
+            .raise_lower(SetpointRaiseLowerMode::Heat, -127)
+            .unwrap();
+        assert_eq!(thermostat.heating_setpoint(), 830);
+
+        // Clamped to the limit
+        thermostat
+            .raise_lower(SetpointRaiseLowerMode::Heat, -127)
+            .unwrap();
+        assert_eq!(thermostat.heating_setpoint(), 700);
+    }
+
+    #[test]
+    fn test_system_mode() {
+        let hvac = Hvac(Cell::new(None));
+        let thermostat = ThermostatCluster::new(&hvac, SetpointLimits::default(), dummy_rand);
+
+        thermostat.set_system_mode(SystemMode::Auto).unwrap();
+        assert_eq!(thermostat.system_mode(), SystemMode::Auto);
+
+        thermostat
+            .control_sequence
+            .set(super::ControlSequenceOfOperation::HeatingOnly);
+        assert!(thermostat.set_system_mode(SystemMode::Cool).is_err());
+        assert!(thermostat.set_system_mode(SystemMode::Auto).is_err());
+        thermostat.set_system_mode(SystemMode::Heat).unwrap();
+    }
+
+    #[test]
+    fn test_schedule() {
+        let hvac = Hvac(Cell::new(None));
+        let thermostat = ThermostatCluster::new(&hvac, SetpointLimits::default(), dummy_rand);
+
+        let transitions = [
+            ScheduleTransition {
+                transition_time: 6 * 60,
+                heat_setpoint: Nullable::NotNull(2100),
+                cool_setpoint: Nullable::Null,
+            },
+            ScheduleTransition {
+                transition_time: 22 * 60,
+                heat_setpoint: Nullable::NotNull(1700),
+                cool_setpoint: Nullable::Null,
+            },
+        ];
+
+        let mut req = SetWeeklyScheduleReq {
+            number_of_transitions_for_sequence: 2,
+            // Monday and Tuesday
+            day_of_week_for_sequence: 0x06,
+            mode_for_sequence: 0x01,
+            transitions: TLVArray::new(&transitions),
+        };
+
+        thermostat.set_weekly_schedule(&req).unwrap();
+
+        thermostat.apply_schedule(1, 7 * 60).unwrap();
+        assert_eq!(thermostat.heating_setpoint(), 2100);
+
+        thermostat.apply_schedule(2, 23 * 60).unwrap();
+        assert_eq!(thermostat.heating_setpoint(), 1700);
+
+        // Nothing scheduled on Sunday
+        thermostat.apply_schedule(0, 7 * 60).unwrap();
+        assert_eq!(thermostat.heating_setpoint(), 1700);
+
+        // Cooling setpoints are missing
+        req.mode_for_sequence = 0x03;
+        assert!(thermostat.set_weekly_schedule(&req).is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let hvac = Hvac(Cell::new(None));
+        let thermostat = ThermostatCluster::new(&hvac, SetpointLimits::default(), dummy_rand);
+
+        // The heating limits stay below the cooling limits by the deadband
+        assert_eq!(
+            thermostat
+                .set_limit(SetpointLimit::MinHeat, 1400)
+                .map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(
+            thermostat
+                .set_limit(SetpointLimit::MaxCool, 2900)
+                .map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+
+        // Setpoints outside of the new limits are moved within them
+        thermostat.set_limit(SetpointLimit::MaxHeat, 2200).unwrap();
+        thermostat.set_limit(SetpointLimit::MaxCool, 2500).unwrap();
+        assert_eq!(thermostat.cooling_setpoint(), 2500);
+        assert_eq!(thermostat.heating_setpoint(), 2000);
+
+        thermostat.set_limit(SetpointLimit::MinCool, 2450).unwrap();
+        thermostat.set_limit(SetpointLimit::MinHeat, 2200).unwrap();
+        assert_eq!(thermostat.heating_setpoint(), 2200);
+        assert_eq!(thermostat.cooling_setpoint(), 2500);
+        assert_eq!(hvac.0.get(), Some((SystemMode::Off, 2200, 2500)));
+
+        assert_eq!(
+            thermostat
+                .set_limit(SetpointLimit::MaxCool, 2400)
+                .map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+    }
+}
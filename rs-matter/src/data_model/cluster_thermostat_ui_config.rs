@@ -0,0 +1,193 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::objects::*;
+use crate::{
+    attribute_enum,
+    error::{Error, ErrorCode},
+    utils::rand::Rand,
+};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0204;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    TemperatureDisplayMode(AttrType<u8>) = 0x0,
+    KeypadLockout(AttrType<u8>) = 0x1,
+    ScheduleProgrammingVisibility(AttrType<u8>) = 0x2,
+}
+
+attribute_enum!(Attributes);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::TemperatureDisplayMode as u16,
+            Access::RWVO,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::KeypadLockout as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ScheduleProgrammingVisibility as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+    ],
+    commands: &[],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum TemperatureDisplayMode {
+    Celsius = 0,
+    Fahrenheit = 1,
+}
+
+/// How much of the local keypad is locked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum KeypadLockout {
+    NoLockout = 0,
+    Lockout1 = 1,
+    Lockout2 = 2,
+    Lockout3 = 3,
+    Lockout4 = 4,
+    Lockout5 = 5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum ScheduleProgrammingVisibility {
+    LocalProgrammingEnabled = 0,
+    LocalProgrammingDisabled = 1,
+}
+
+/// The settings of the local user interface of a thermostat
+///
+/// The cluster only keeps them; the user interface reads them back whenever the data
+/// version of the cluster changes.
+pub struct ThermostatUserInterfaceConfigurationCluster {
+    data_ver: Dataver,
+    temperature_display_mode: Cell<TemperatureDisplayMode>,
+    keypad_lockout: Cell<KeypadLockout>,
+    schedule_programming_visibility: Cell<ScheduleProgrammingVisibility>,
+}
+
+impl ThermostatUserInterfaceConfigurationCluster {
+    pub fn new(rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            temperature_display_mode: Cell::new(TemperatureDisplayMode::Celsius),
+            keypad_lockout: Cell::new(KeypadLockout::NoLockout),
+            schedule_programming_visibility: Cell::new(
+                ScheduleProgrammingVisibility::LocalProgrammingEnabled,
+            ),
+        }
+    }
+
+    pub fn temperature_display_mode(&self) -> TemperatureDisplayMode {
+        self.temperature_display_mode.get()
+    }
+
+    pub fn keypad_lockout(&self) -> KeypadLockout {
+        self.keypad_lockout.get()
+    }
+
+    pub fn schedule_programming_visibility(&self) -> ScheduleProgrammingVisibility {
+        self.schedule_programming_visibility.get()
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::TemperatureDisplayMode(codec) => {
+                        codec.encode(writer, self.temperature_display_mode.get() as _)
+                    }
+                    Attributes::KeypadLockout(codec) => {
+                        codec.encode(writer, self.keypad_lockout.get() as _)
+                    }
+                    Attributes::ScheduleProgrammingVisibility(codec) => {
+                        codec.encode(writer, self.schedule_programming_visibility.get() as _)
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::TemperatureDisplayMode(codec) => self.temperature_display_mode.set(
+                TemperatureDisplayMode::from_repr(codec.decode(data)?)
+                    .ok_or(ErrorCode::ConstraintError)?,
+            ),
+            Attributes::KeypadLockout(codec) => self.keypad_lockout.set(
+                KeypadLockout::from_repr(codec.decode(data)?).ok_or(ErrorCode::ConstraintError)?,
+            ),
+            Attributes::ScheduleProgrammingVisibility(codec) => {
+                self.schedule_programming_visibility.set(
+                    ScheduleProgrammingVisibility::from_repr(codec.decode(data)?)
+                        .ok_or(ErrorCode::ConstraintError)?,
+                )
+            }
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+}
+
+impl Handler for ThermostatUserInterfaceConfigurationCluster {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        ThermostatUserInterfaceConfigurationCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        ThermostatUserInterfaceConfigurationCluster::write(self, attr, data)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl NonBlockingHandler for ThermostatUserInterfaceConfigurationCluster {}
+
+impl ChangeNotifier<()> for ThermostatUserInterfaceConfigurationCluster {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
@@ -0,0 +1,200 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::{cluster_localization::LocalizationConfig, objects::*};
+use crate::{
+    attribute_enum,
+    error::{Error, ErrorCode},
+    tlv::TagType,
+    utils::rand::Rand,
+};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x002C;
+
+enum FeatureMap {
+    CalendarFormat = 0x01,
+}
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    HourFormat(AttrType<u8>) = 0x0,
+    ActiveCalendarType(AttrType<u8>) = 0x1,
+    SupportedCalendarTypes(()) = 0x2,
+}
+
+attribute_enum!(Attributes);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::CalendarFormat as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::HourFormat as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveCalendarType as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SupportedCalendarTypes as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum HourFormat {
+    TwelveHour = 0,
+    TwentyFourHour = 1,
+    UseActiveLocale = 0xFF,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum CalendarType {
+    Buddhist = 0,
+    Chinese = 1,
+    Coptic = 2,
+    Ethiopian = 3,
+    Gregorian = 4,
+    Hebrew = 5,
+    Indian = 6,
+    Islamic = 7,
+    Japanese = 8,
+    Korean = 9,
+    Persian = 10,
+    Taiwanese = 11,
+    UseActiveLocale = 0xFF,
+}
+
+/// How the user interface of the node displays the time and the date
+pub struct TimeFormatLocalizationCluster<'a> {
+    data_ver: Dataver,
+    conf: &'a LocalizationConfig<'a>,
+    hour_format: Cell<HourFormat>,
+    active_calendar_type: Cell<CalendarType>,
+}
+
+impl<'a> TimeFormatLocalizationCluster<'a> {
+    pub fn new(conf: &'a LocalizationConfig<'a>, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            conf,
+            hour_format: Cell::new(conf.hour_format),
+            active_calendar_type: Cell::new(conf.active_calendar_type),
+        }
+    }
+
+    pub fn hour_format(&self) -> HourFormat {
+        self.hour_format.get()
+    }
+
+    pub fn active_calendar_type(&self) -> CalendarType {
+        self.active_calendar_type.get()
+    }
+
+    /// Set the active calendar type, which needs to be one of the supported calendar types
+    pub fn set_active_calendar_type(&self, calendar_type: CalendarType) -> Result<(), Error> {
+        if !self.conf.supported_calendar_types.contains(&calendar_type) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        self.active_calendar_type.set(calendar_type);
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::HourFormat(codec) => codec.encode(writer, self.hour_format() as _),
+                    Attributes::ActiveCalendarType(codec) => {
+                        codec.encode(writer, self.active_calendar_type() as _)
+                    }
+                    Attributes::SupportedCalendarTypes(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for calendar_type in self.conf.supported_calendar_types {
+                            writer.u8(TagType::Anonymous, *calendar_type as _)?;
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::HourFormat(codec) => {
+                self.hour_format.set(
+                    HourFormat::from_repr(codec.decode(data)?).ok_or(ErrorCode::ConstraintError)?,
+                );
+                self.data_ver.changed();
+            }
+            Attributes::ActiveCalendarType(codec) => self.set_active_calendar_type(
+                CalendarType::from_repr(codec.decode(data)?).ok_or(ErrorCode::ConstraintError)?,
+            )?,
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Handler for TimeFormatLocalizationCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        TimeFormatLocalizationCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        TimeFormatLocalizationCluster::write(self, attr, data)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for TimeFormatLocalizationCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for TimeFormatLocalizationCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
@@ -0,0 +1,134 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::{cluster_localization::LocalizationConfig, objects::*};
+use crate::{
+    attribute_enum,
+    error::{Error, ErrorCode},
+    utils::rand::Rand,
+};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x002D;
+
+enum FeatureMap {
+    TemperatureUnit = 0x01,
+}
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    TemperatureUnit(AttrType<u8>) = 0x0,
+}
+
+attribute_enum!(Attributes);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::TemperatureUnit as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::TemperatureUnit as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+    ],
+    commands: &[],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum TempUnit {
+    Fahrenheit = 0,
+    Celsius = 1,
+    Kelvin = 2,
+}
+
+/// The units in which the user interface of the node displays its values
+pub struct UnitLocalizationCluster {
+    data_ver: Dataver,
+    temperature_unit: Cell<TempUnit>,
+}
+
+impl UnitLocalizationCluster {
+    pub fn new(conf: &LocalizationConfig, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            temperature_unit: Cell::new(conf.temperature_unit),
+        }
+    }
+
+    pub fn temperature_unit(&self) -> TempUnit {
+        self.temperature_unit.get()
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::TemperatureUnit(codec) => {
+                        codec.encode(writer, self.temperature_unit() as _)
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::TemperatureUnit(codec) => self
+                .temperature_unit
+                .set(TempUnit::from_repr(codec.decode(data)?).ok_or(ErrorCode::ConstraintError)?),
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+}
+
+impl Handler for UnitLocalizationCluster {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        UnitLocalizationCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        UnitLocalizationCluster::write(self, attr, data)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl NonBlockingHandler for UnitLocalizationCluster {}
+
+impl ChangeNotifier<()> for UnitLocalizationCluster {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
@@ -0,0 +1,272 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::RefCell, convert::TryInto};
+
+use log::info;
+
+use super::{
+    cluster_fixed_label::{LabelStruct, MAX_LABEL_LEN},
+    objects::*,
+};
+use crate::{
+    attribute_enum,
+    error::{Error, ErrorCode},
+    interaction_model::messages::ib::{attr_list_write, ListOperation},
+    tlv::{FromTLV, TLVElement, TagType, ToTLV},
+    utils::rand::Rand,
+};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0041;
+
+/// The maximum number of labels of an endpoint; the spec requires at least 4
+pub const MAX_USER_LABELS: usize = 4;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    LabelList(()) = 0x0,
+}
+
+attribute_enum!(Attributes);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::LabelList as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+    ],
+    commands: &[],
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UserLabel {
+    label: heapless::String<MAX_LABEL_LEN>,
+    value: heapless::String<MAX_LABEL_LEN>,
+}
+
+impl UserLabel {
+    fn from_tlv(data: &TLVElement) -> Result<Self, Error> {
+        let label = LabelStruct::from_tlv(data)?;
+
+        let mut user_label = Self {
+            label: heapless::String::new(),
+            value: heapless::String::new(),
+        };
+
+        user_label
+            .label
+            .push_str(label.label.as_str()?)
+            .map_err(|_| ErrorCode::ConstraintError)?;
+        user_label
+            .value
+            .push_str(label.value.as_str()?)
+            .map_err(|_| ErrorCode::ConstraintError)?;
+
+        Ok(user_label)
+    }
+}
+
+/// The labels of an endpoint which are set by the user, like the room or the position
+/// of the device
+pub struct UserLabelCluster {
+    data_ver: Dataver,
+    labels: RefCell<heapless::Vec<UserLabel, MAX_USER_LABELS>>,
+}
+
+impl UserLabelCluster {
+    pub fn new(rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            labels: RefCell::new(heapless::Vec::new()),
+        }
+    }
+
+    /// Call `f` with the label and the value of each of the labels
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&str, &str),
+    {
+        for label in self.labels.borrow().iter() {
+            f(&label.label, &label.value);
+        }
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::LabelList(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for label in self.labels.borrow().iter() {
+                            LabelStruct::new(&label.label, &label.value)
+                                .to_tlv(&mut writer, TagType::Anonymous)?;
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        match attr.attr_id.try_into()? {
+            Attributes::LabelList(_) => {
+                // Applied to a copy, so that replacing the list with one which has an
+                // invalid item leaves the labels untouched
+                let mut labels = self.labels.borrow().clone();
+
+                attr_list_write(attr, data.with_dataver(self.data_ver.get())?, |op, data| {
+                    Self::write_labels_attr(&mut labels, &op, data)
+                })?;
+
+                *self.labels.borrow_mut() = labels;
+            }
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    fn write_labels_attr(
+        labels: &mut heapless::Vec<UserLabel, MAX_USER_LABELS>,
+        op: &ListOperation,
+        data: &TLVElement,
+    ) -> Result<(), Error> {
+        info!("Performing LabelList operation {:?}", op);
+
+        match op {
+            ListOperation::AddItem => labels
+                .push(UserLabel::from_tlv(data)?)
+                .map_err(|_| ErrorCode::ResourceExhausted)?,
+            ListOperation::EditItem(index) => {
+                let label = UserLabel::from_tlv(data)?;
+
+                *labels.get_mut(*index as usize).ok_or(ErrorCode::NotFound)? = label;
+            }
+            ListOperation::DeleteItem(index) => {
+                if *index as usize >= labels.len() {
+                    Err(ErrorCode::NotFound)?;
+                }
+
+                labels.remove(*index as usize);
+            }
+            ListOperation::DeleteList => labels.clear(),
+        }
+
+        Ok(())
+    }
+}
+
+impl Handler for UserLabelCluster {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        UserLabelCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        UserLabelCluster::write(self, attr, data)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl NonBlockingHandler for UserLabelCluster {}
+
+impl ChangeNotifier<()> for UserLabelCluster {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data_model::cluster_fixed_label::LabelStruct,
+        error::{Error, ErrorCode},
+        interaction_model::messages::ib::ListOperation,
+        tlv::{get_root_node_struct, TLVWriter, TagType, ToTLV},
+        utils::{rand::dummy_rand, writebuf::WriteBuf},
+    };
+
+    use super::{UserLabelCluster, MAX_USER_LABELS};
+
+    #[test]
+    fn test_label_list() {
+        let cluster = UserLabelCluster::new(dummy_rand);
+
+        let write = |op: ListOperation, label: &str, value: &str| -> Result<(), Error> {
+            let mut buf = [0; 100];
+            let mut writebuf = WriteBuf::new(&mut buf);
+            let mut tw = TLVWriter::new(&mut writebuf);
+            LabelStruct::new(label, value)
+                .to_tlv(&mut tw, TagType::Anonymous)
+                .unwrap();
+            let data = get_root_node_struct(writebuf.as_slice()).unwrap();
+
+            UserLabelCluster::write_labels_attr(&mut cluster.labels.borrow_mut(), &op, &data)
+        };
+
+        write(ListOperation::AddItem, "room", "kitchen").unwrap();
+        write(ListOperation::EditItem(0), "room", "bedroom").unwrap();
+        assert_eq!(
+            write(ListOperation::EditItem(1), "floor", "1").map_err(|e| e.code()),
+            Err(ErrorCode::NotFound)
+        );
+
+        // Labels and values of more than 16 bytes are rejected
+        assert_eq!(
+            write(ListOperation::AddItem, "a label which is too long", "1").map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(
+            write(ListOperation::AddItem, "floor", "a value which is too long")
+                .map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+
+        for _ in 1..MAX_USER_LABELS {
+            write(ListOperation::AddItem, "floor", "1").unwrap();
+        }
+        assert_eq!(
+            write(ListOperation::AddItem, "floor", "2").map_err(|e| e.code()),
+            Err(ErrorCode::ResourceExhausted)
+        );
+
+        let mut labels = heapless::Vec::<_, MAX_USER_LABELS>::new();
+        cluster.for_each(|label, value| {
+            labels.push((label.len(), value.len())).unwrap();
+        });
+        assert_eq!(labels.len(), MAX_USER_LABELS);
+        assert_eq!(labels[0], ("room".len(), "bedroom".len()));
+    }
+}
@@ -0,0 +1,683 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{cell::Cell, convert::TryInto};
+
+use super::objects::*;
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, Nullable, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0102;
+
+/// Fully closed, in percent100ths; 0 is fully open
+pub const CLOSED: u16 = 10000;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    Type(AttrType<u8>) = 0x0000,
+    ConfigStatus(AttrType<u8>) = 0x0007,
+    CurrentPositionLiftPercentage(AttrType<Nullable<u8>>) = 0x0008,
+    CurrentPositionTiltPercentage(AttrType<Nullable<u8>>) = 0x0009,
+    OperationalStatus(AttrType<u8>) = 0x000A,
+    TargetPositionLiftPercent100ths(AttrType<Nullable<u16>>) = 0x000B,
+    TargetPositionTiltPercent100ths(AttrType<Nullable<u16>>) = 0x000C,
+    EndProductType(AttrType<u8>) = 0x000D,
+    CurrentPositionLiftPercent100ths(AttrType<Nullable<u16>>) = 0x000E,
+    CurrentPositionTiltPercent100ths(AttrType<Nullable<u16>>) = 0x000F,
+    Mode(AttrType<u8>) = 0x0017,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    UpOrOpen = 0x00,
+    DownOrClose = 0x01,
+    StopMotion = 0x02,
+    GoToLiftPercentage = 0x05,
+    GoToTiltPercentage = 0x08,
+}
+
+command_enum!(Commands);
+
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    Lift = 0x01,
+    Tilt = 0x02,
+    PositionAwareLift = 0x04,
+    PositionAwareTilt = 0x10,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: Feature::Lift as u32
+        | Feature::Tilt as u32
+        | Feature::PositionAwareLift as u32
+        | Feature::PositionAwareTilt as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::Type as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ConfigStatus as u16,
+            Access::RV,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentPositionLiftPercentage as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentPositionTiltPercentage as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OperationalStatus as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::TargetPositionLiftPercent100ths as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::TargetPositionTiltPercent100ths as u16,
+            Access::RV,
+            Quality::X,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::EndProductType as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentPositionLiftPercent100ths as u16,
+            Access::RV,
+            Quality::X.union(Quality::N),
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentPositionTiltPercent100ths as u16,
+            Access::RV,
+            Quality::X.union(Quality::N),
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Mode as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::UpOrOpen as _,
+        CommandsDiscriminants::DownOrClose as _,
+        CommandsDiscriminants::StopMotion as _,
+        CommandsDiscriminants::GoToLiftPercentage as _,
+        CommandsDiscriminants::GoToTiltPercentage as _,
+    ],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum WindowCoveringType {
+    Rollershade = 0,
+    Rollershade2Motor = 1,
+    RollershadeExterior = 2,
+    RollershadeExterior2Motor = 3,
+    Drapery = 4,
+    Awning = 5,
+    Shutter = 6,
+    TiltBlindTiltOnly = 7,
+    TiltBlindLiftAndTilt = 8,
+    ProjectorScreen = 9,
+    Unknown = 0xFF,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum EndProductType {
+    RollerShade = 0,
+    RomanShade = 1,
+    BalloonShade = 2,
+    WovenWood = 3,
+    PleatedShade = 4,
+    CellularShade = 5,
+    LayeredShade = 6,
+    LayeredShade2D = 7,
+    SheerShade = 8,
+    TiltOnlyInteriorBlind = 9,
+    InteriorBlind = 10,
+    VerticalBlindStripCurtain = 11,
+    InteriorVenetianBlind = 12,
+    ExteriorVenetianBlind = 13,
+    LateralLeftCurtain = 14,
+    LateralRightCurtain = 15,
+    CentralCurtain = 16,
+    RollerShutter = 17,
+    ExteriorVerticalScreen = 18,
+    AwningTerracePatio = 19,
+    AwningVerticalScreen = 20,
+    TiltOnlyPergola = 21,
+    SwingingShutter = 22,
+    SlidingShutter = 23,
+    Unknown = 0xFF,
+}
+
+/// The movement of the covering, or of one of its axes, in `OperationalStatus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum MovementStatus {
+    Stopped = 0,
+    Opening = 1,
+    Closing = 2,
+}
+
+pub const CONFIG_STATUS_OPERATIONAL: u8 = 0x01;
+pub const CONFIG_STATUS_LIFT_MOVEMENT_REVERSED: u8 = 0x04;
+pub const CONFIG_STATUS_LIFT_POSITION_AWARE: u8 = 0x08;
+pub const CONFIG_STATUS_TILT_POSITION_AWARE: u8 = 0x10;
+
+pub const MODE_MOTOR_DIRECTION_REVERSED: u8 = 0x01;
+pub const MODE_CALIBRATION: u8 = 0x02;
+pub const MODE_MAINTENANCE: u8 = 0x04;
+pub const MODE_LED_FEEDBACK: u8 = 0x08;
+
+#[derive(FromTLV, ToTLV)]
+pub struct GoToLiftPercentageReq {
+    pub lift_percent_100ths_value: u16,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct GoToTiltPercentageReq {
+    pub tilt_percent_100ths_value: u16,
+}
+
+/// The application side of the Window Covering cluster
+///
+/// The motor moves the covering towards the requested positions, in percent100ths,
+/// and reports its progress with `WindowCoveringCluster::set_current_position`; the
+/// covering is considered moving until the current positions reach the targets.
+pub trait CoveringMotor {
+    /// Start moving towards `lift` and `tilt`; `None` leaves that axis where it is
+    fn go_to(&self, lift: Option<u16>, tilt: Option<u16>);
+
+    fn stop(&self);
+}
+
+impl<T> CoveringMotor for &T
+where
+    T: CoveringMotor,
+{
+    fn go_to(&self, lift: Option<u16>, tilt: Option<u16>) {
+        (**self).go_to(lift, tilt)
+    }
+
+    fn stop(&self) {
+        (**self).stop()
+    }
+}
+
+pub struct WindowCoveringCluster<T> {
+    data_ver: Dataver,
+    motor: T,
+    covering_type: WindowCoveringType,
+    end_product_type: EndProductType,
+    mode: Cell<u8>,
+    current_lift: Cell<Option<u16>>,
+    current_tilt: Cell<Option<u16>>,
+    target_lift: Cell<Option<u16>>,
+    target_tilt: Cell<Option<u16>>,
+}
+
+impl<T> WindowCoveringCluster<T>
+where
+    T: CoveringMotor,
+{
+    pub fn new(
+        covering_type: WindowCoveringType,
+        end_product_type: EndProductType,
+        motor: T,
+        rand: Rand,
+    ) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            motor,
+            covering_type,
+            end_product_type,
+            mode: Cell::new(0),
+            current_lift: Cell::new(None),
+            current_tilt: Cell::new(None),
+            target_lift: Cell::new(None),
+            target_tilt: Cell::new(None),
+        }
+    }
+
+    /// The current lift and tilt positions, `None` while unknown, e.g. before calibration
+    pub fn current_position(&self) -> (Option<u16>, Option<u16>) {
+        (self.current_lift.get(), self.current_tilt.get())
+    }
+
+    pub fn target_position(&self) -> (Option<u16>, Option<u16>) {
+        (self.target_lift.get(), self.target_tilt.get())
+    }
+
+    /// Update the position of the covering, as the motor moves it
+    pub fn set_current_position(&self, lift: Option<u16>, tilt: Option<u16>) {
+        let lift = lift.map(|lift| lift.min(CLOSED));
+        let tilt = tilt.map(|tilt| tilt.min(CLOSED));
+
+        let previous_lift = self.current_lift.replace(lift);
+        let previous_tilt = self.current_tilt.replace(tilt);
+
+        if previous_lift != lift || previous_tilt != tilt {
+            self.data_ver.changed();
+        }
+    }
+
+    pub fn operational_status(&self) -> u8 {
+        let lift = Self::movement(self.current_lift.get(), self.target_lift.get());
+        let tilt = Self::movement(self.current_tilt.get(), self.target_tilt.get());
+
+        let global = if lift != MovementStatus::Stopped {
+            lift
+        } else {
+            tilt
+        };
+
+        global as u8 | (lift as u8) << 2 | (tilt as u8) << 4
+    }
+
+    /// Move the covering to the given positions; `None` leaves that axis where it is
+    pub fn go_to(&self, lift: Option<u16>, tilt: Option<u16>) -> Result<(), Error> {
+        if self.mode.get() & (MODE_CALIBRATION | MODE_MAINTENANCE) != 0 {
+            Err(ErrorCode::Busy)?;
+        }
+
+        if lift.unwrap_or(0) > CLOSED || tilt.unwrap_or(0) > CLOSED {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        if lift.is_some() {
+            self.target_lift.set(lift);
+        }
+
+        if tilt.is_some() {
+            self.target_tilt.set(tilt);
+        }
+
+        self.data_ver.changed();
+
+        self.motor.go_to(lift, tilt);
+
+        Ok(())
+    }
+
+    pub fn stop_motion(&self) -> Result<(), Error> {
+        if self.mode.get() & MODE_MAINTENANCE != 0 {
+            Err(ErrorCode::Busy)?;
+        }
+
+        self.motor.stop();
+
+        self.target_lift.set(self.current_lift.get());
+        self.target_tilt.set(self.current_tilt.get());
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::Type(codec) => codec.encode(writer, self.covering_type as _),
+                    Attributes::ConfigStatus(codec) => codec.encode(writer, self.config_status()),
+                    Attributes::CurrentPositionLiftPercentage(codec) => codec.encode(
+                        writer,
+                        Self::nullable(self.current_lift.get().map(Self::percentage)),
+                    ),
+                    Attributes::CurrentPositionTiltPercentage(codec) => codec.encode(
+                        writer,
+                        Self::nullable(self.current_tilt.get().map(Self::percentage)),
+                    ),
+                    Attributes::OperationalStatus(codec) => {
+                        codec.encode(writer, self.operational_status())
+                    }
+                    Attributes::TargetPositionLiftPercent100ths(codec) => {
+                        codec.encode(writer, Self::nullable(self.target_lift.get()))
+                    }
+                    Attributes::TargetPositionTiltPercent100ths(codec) => {
+                        codec.encode(writer, Self::nullable(self.target_tilt.get()))
+                    }
+                    Attributes::EndProductType(codec) => {
+                        codec.encode(writer, self.end_product_type as _)
+                    }
+                    Attributes::CurrentPositionLiftPercent100ths(codec) => {
+                        codec.encode(writer, Self::nullable(self.current_lift.get()))
+                    }
+                    Attributes::CurrentPositionTiltPercent100ths(codec) => {
+                        codec.encode(writer, Self::nullable(self.current_tilt.get()))
+                    }
+                    Attributes::Mode(codec) => codec.encode(writer, self.mode.get()),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::Mode(codec) => {
+                let mode = codec.decode(data)?;
+
+                if mode
+                    & !(MODE_MOTOR_DIRECTION_REVERSED
+                        | MODE_CALIBRATION
+                        | MODE_MAINTENANCE
+                        | MODE_LED_FEEDBACK)
+                    != 0
+                {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                self.mode.set(mode);
+            }
+            // Only writable internally, when restoring the persisted position
+            Attributes::CurrentPositionLiftPercent100ths(codec) => {
+                let lift = codec.decode(data)?.notnull();
+
+                self.current_lift.set(lift);
+                self.target_lift.set(lift);
+            }
+            // Only writable internally, when restoring the persisted position
+            Attributes::CurrentPositionTiltPercent100ths(codec) => {
+                let tilt = codec.decode(data)?.notnull();
+
+                self.current_tilt.set(tilt);
+                self.target_tilt.set(tilt);
+            }
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        _encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::UpOrOpen => {
+                cmd_enter!("UpOrOpen");
+
+                self.go_to(Some(0), Some(0))?;
+            }
+            Commands::DownOrClose => {
+                cmd_enter!("DownOrClose");
+
+                self.go_to(Some(CLOSED), Some(CLOSED))?;
+            }
+            Commands::StopMotion => {
+                cmd_enter!("StopMotion");
+
+                self.stop_motion()?;
+            }
+            Commands::GoToLiftPercentage => {
+                cmd_enter!("GoToLiftPercentage");
+                let req = GoToLiftPercentageReq::from_tlv(data)?;
+
+                self.go_to(Some(req.lift_percent_100ths_value), None)?;
+            }
+            Commands::GoToTiltPercentage => {
+                cmd_enter!("GoToTiltPercentage");
+                let req = GoToTiltPercentageReq::from_tlv(data)?;
+
+                self.go_to(None, Some(req.tilt_percent_100ths_value))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn config_status(&self) -> u8 {
+        let mut status = CONFIG_STATUS_LIFT_POSITION_AWARE | CONFIG_STATUS_TILT_POSITION_AWARE;
+
+        // The covering does not move while it is being calibrated or maintained
+        if self.mode.get() & (MODE_CALIBRATION | MODE_MAINTENANCE) == 0 {
+            status |= CONFIG_STATUS_OPERATIONAL;
+        }
+
+        if self.mode.get() & MODE_MOTOR_DIRECTION_REVERSED != 0 {
+            status |= CONFIG_STATUS_LIFT_MOVEMENT_REVERSED;
+        }
+
+        status
+    }
+
+    fn movement(current: Option<u16>, target: Option<u16>) -> MovementStatus {
+        match (current, target) {
+            (Some(current), Some(target)) if target < current => MovementStatus::Opening,
+            (Some(current), Some(target)) if target > current => MovementStatus::Closing,
+            _ => MovementStatus::Stopped,
+        }
+    }
+
+    fn percentage(percent_100ths: u16) -> u8 {
+        (percent_100ths / 100) as _
+    }
+
+    fn nullable<V>(value: Option<V>) -> Nullable<V> {
+        match value {
+            Some(value) => Nullable::NotNull(value),
+            None => Nullable::Null,
+        }
+    }
+}
+
+impl<T> Handler for WindowCoveringCluster<T>
+where
+    T: CoveringMotor,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        WindowCoveringCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        WindowCoveringCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        WindowCoveringCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<T> NonBlockingHandler for WindowCoveringCluster<T> where T: CoveringMotor {}
+
+impl<T> ChangeNotifier<()> for WindowCoveringCluster<T> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::{error::ErrorCode, utils::rand::dummy_rand};
+
+    use super::{
+        CoveringMotor, EndProductType, MovementStatus, WindowCoveringCluster, WindowCoveringType,
+        CLOSED, CONFIG_STATUS_OPERATIONAL, MODE_CALIBRATION, MODE_MAINTENANCE,
+    };
+
+    /// A motor which only records where it was asked to go, so that the tests move the
+    /// covering step by step
+    struct Motor {
+        lift: Cell<Option<u16>>,
+        tilt: Cell<Option<u16>>,
+        stopped: Cell<bool>,
+    }
+
+    impl Motor {
+        fn new() -> Self {
+            Self {
+                lift: Cell::new(None),
+                tilt: Cell::new(None),
+                stopped: Cell::new(false),
+            }
+        }
+    }
+
+    impl CoveringMotor for Motor {
+        fn go_to(&self, lift: Option<u16>, tilt: Option<u16>) {
+            if lift.is_some() {
+                self.lift.set(lift);
+            }
+            if tilt.is_some() {
+                self.tilt.set(tilt);
+            }
+        }
+
+        fn stop(&self) {
+            self.stopped.set(true);
+        }
+    }
+
+    fn covering(motor: &Motor) -> WindowCoveringCluster<&Motor> {
+        let covering = WindowCoveringCluster::new(
+            WindowCoveringType::TiltBlindLiftAndTilt,
+            EndProductType::InteriorVenetianBlind,
+            motor,
+            dummy_rand,
+        );
+
+        covering.set_current_position(Some(0), Some(0));
+
+        covering
+    }
+
+    #[test]
+    fn test_go_to_lift() {
+        let motor = Motor::new();
+        let covering = covering(&motor);
+
+        covering.go_to(Some(5000), None).unwrap();
+        assert_eq!(motor.lift.get(), Some(5000));
+        assert_eq!(motor.tilt.get(), None);
+        assert_eq!(covering.target_position(), (Some(5000), None));
+
+        let closing = MovementStatus::Closing as u8;
+        assert_eq!(covering.operational_status(), closing | closing << 2);
+
+        covering.set_current_position(Some(2500), Some(0));
+        assert_eq!(covering.operational_status(), closing | closing << 2);
+
+        covering.set_current_position(Some(5000), Some(0));
+        assert_eq!(covering.operational_status(), 0);
+
+        assert_eq!(
+            covering.go_to(Some(CLOSED + 1), None).map_err(|e| e.code()),
+            Err(ErrorCode::ConstraintError)
+        );
+    }
+
+    #[test]
+    fn test_open_and_stop() {
+        let motor = Motor::new();
+        let covering = covering(&motor);
+
+        covering.set_current_position(Some(CLOSED), Some(CLOSED));
+        covering.go_to(Some(0), Some(0)).unwrap();
+
+        let opening = MovementStatus::Opening as u8;
+        assert_eq!(
+            covering.operational_status(),
+            opening | opening << 2 | opening << 4
+        );
+
+        covering.set_current_position(Some(0), Some(4000));
+        assert_eq!(covering.operational_status(), opening | opening << 4);
+
+        covering.stop_motion().unwrap();
+        assert!(motor.stopped.get());
+        assert_eq!(covering.target_position(), (Some(0), Some(4000)));
+        assert_eq!(covering.operational_status(), 0);
+    }
+
+    #[test]
+    fn test_maintenance_mode() {
+        let motor = Motor::new();
+        let covering = covering(&motor);
+
+        assert_ne!(covering.config_status() & CONFIG_STATUS_OPERATIONAL, 0);
+
+        covering.mode.set(MODE_MAINTENANCE);
+        assert_eq!(covering.config_status() & CONFIG_STATUS_OPERATIONAL, 0);
+
+        assert_eq!(
+            covering.go_to(Some(CLOSED), None).map_err(|e| e.code()),
+            Err(ErrorCode::Busy)
+        );
+        assert_eq!(motor.lift.get(), None);
+    }
+
+    #[test]
+    fn test_calibration_mode() {
+        let motor = Motor::new();
+        let covering = covering(&motor);
+
+        covering.mode.set(MODE_CALIBRATION);
+        assert_eq!(covering.config_status() & CONFIG_STATUS_OPERATIONAL, 0);
+
+        covering.mode.set(0);
+        assert_ne!(covering.config_status() & CONFIG_STATUS_OPERATIONAL, 0);
+    }
+}
@@ -17,7 +17,7 @@
 
 use portable_atomic::{AtomicU32, Ordering};
 
-use super::objects::*;
+use super::{objects::*, persist::AttrPersist};
 use crate::{
     alloc,
     error::*,
@@ -41,13 +41,18 @@ impl<T> DataModel<T> {
         Self(handler)
     }
 
+    /// Handle an Interaction Model exchange
+    ///
+    /// Returns `true` if a write changed the data version of a cluster with persistent
+    /// attributes, or after an invoke, as commands might also change persistent state
+    /// which is not exposed as attributes (see `StatePersist`).
     pub async fn handle<'r, 'p>(
         &self,
         exchange: &'r mut Exchange<'_>,
         rx: &'r mut Packet<'p>,
         tx: &'r mut Packet<'p>,
         rx_status: &'r mut Packet<'p>,
-    ) -> Result<(), Error>
+    ) -> Result<bool, Error>
     where
         T: DataModelHandler,
     {
@@ -74,6 +79,10 @@ impl<T> DataModel<T> {
         #[cfg(not(feature = "nightly"))]
         let metadata = self.0.lock();
 
+        let dataver = |endpoint: EndptId, cluster: ClusterId| self.0.dataver(endpoint, cluster);
+
+        let mut changed = false;
+
         if interaction.start().await? {
             match interaction {
                 Interaction::Read {
@@ -82,7 +91,7 @@ impl<T> DataModel<T> {
                 } => {
                     let accessor = driver.accessor()?;
 
-                    'outer: for item in metadata.node().read(req, None, &accessor) {
+                    'outer: for item in metadata.node().read(req, None, &accessor, &dataver) {
                         while !AttrDataEncoder::handle_read(&item, &self.0, &mut driver.writer()?)
                             .await?
                         {
@@ -113,11 +122,15 @@ impl<T> DataModel<T> {
                     let write_attrs: heapless::Vec<_, MAX_WRITE_ATTRS_IN_ONE_TRANS> =
                         node.write(req, &accessor).collect();
 
+                    let digest = AttrPersist::digest(&self.0, &node);
+
                     for item in write_attrs {
                         AttrDataEncoder::handle_write(&item, &self.0, &mut driver.writer()?)
                             .await?;
                     }
 
+                    changed = AttrPersist::digest(&self.0, &node) != digest;
+
                     driver.complete(req).await?;
                 }
                 Interaction::Invoke {
@@ -125,13 +138,16 @@ impl<T> DataModel<T> {
                     ref mut driver,
                 } => {
                     let accessor = driver.accessor()?;
+                    let node = metadata.node();
 
-                    for item in metadata.node().invoke(req, &accessor) {
+                    for item in node.invoke(req, &accessor) {
                         let (mut tw, exchange) = driver.writer_exchange()?;
 
                         CmdDataEncoder::handle(&item, &self.0, &mut tw, exchange).await?;
                     }
 
+                    changed = true;
+
                     driver.complete(req).await?;
                 }
                 Interaction::Subscribe {
@@ -140,7 +156,10 @@ impl<T> DataModel<T> {
                 } => {
                     let accessor = driver.accessor()?;
 
-                    'outer: for item in metadata.node().subscribing_read(req, None, &accessor) {
+                    'outer: for item in metadata
+                        .node()
+                        .subscribing_read(req, None, &accessor, &dataver)
+                    {
                         while !AttrDataEncoder::handle_read(&item, &self.0, &mut driver.writer()?)
                             .await?
                         {
@@ -155,6 +174,6 @@ impl<T> DataModel<T> {
             }
         }
 
-        Ok(())
+        Ok(changed)
     }
 }
@@ -27,7 +27,89 @@ pub const DEV_TYPE_ON_OFF_LIGHT: DeviceType = DeviceType {
     drev: 2,
 };
 
+pub const DEV_TYPE_DIMMABLE_LIGHT: DeviceType = DeviceType {
+    dtype: 0x0101,
+    drev: 2,
+};
+
+pub const DEV_TYPE_COLOR_TEMPERATURE_LIGHT: DeviceType = DeviceType {
+    dtype: 0x010C,
+    drev: 2,
+};
+
+pub const DEV_TYPE_EXTENDED_COLOR_LIGHT: DeviceType = DeviceType {
+    dtype: 0x010D,
+    drev: 2,
+};
+
+pub const DEV_TYPE_ON_OFF_LIGHT_SWITCH: DeviceType = DeviceType {
+    dtype: 0x0103,
+    drev: 2,
+};
+
+pub const DEV_TYPE_DOOR_LOCK: DeviceType = DeviceType {
+    dtype: 0x000A,
+    drev: 2,
+};
+
+pub const DEV_TYPE_FAN: DeviceType = DeviceType {
+    dtype: 0x002B,
+    drev: 1,
+};
+
+pub const DEV_TYPE_THERMOSTAT: DeviceType = DeviceType {
+    dtype: 0x0301,
+    drev: 2,
+};
+
+pub const DEV_TYPE_TEMPERATURE_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0302,
+    drev: 2,
+};
+
+pub const DEV_TYPE_PRESSURE_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0305,
+    drev: 2,
+};
+
+pub const DEV_TYPE_HUMIDITY_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0307,
+    drev: 2,
+};
+
+pub const DEV_TYPE_LIGHT_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0106,
+    drev: 3,
+};
+
+pub const DEV_TYPE_OCCUPANCY_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0107,
+    drev: 3,
+};
+
+pub const DEV_TYPE_WINDOW_COVERING: DeviceType = DeviceType {
+    dtype: 0x0202,
+    drev: 2,
+};
+
 pub const DEV_TYPE_ON_SMART_SPEAKER: DeviceType = DeviceType {
     dtype: 0x0022,
     drev: 2,
 };
+
+pub const DEV_TYPE_AGGREGATOR: DeviceType = DeviceType {
+    dtype: 0x000E,
+    drev: 1,
+};
+
+pub const DEV_TYPE_BRIDGED_NODE: DeviceType = DeviceType {
+    dtype: 0x0013,
+    drev: 1,
+};
+
+/// A utility device type, for the `extra_device_types` of an endpoint with a
+/// Power Source cluster
+pub const DEV_TYPE_POWER_SOURCE: DeviceType = DeviceType {
+    dtype: 0x0011,
+    drev: 1,
+};
@@ -0,0 +1,571 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::cell::{Ref, RefCell};
+
+use crate::{
+    error::{Error, ErrorCode},
+    tlv::TLVElement,
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+
+use super::{
+    objects::*,
+    system_model::descriptor::{self, DescriptorCluster, PartsMatcher},
+};
+
+#[cfg(feature = "nightly")]
+pub use asynch::*;
+
+/// A data model whose endpoints and cluster handlers can be added and removed
+/// while the Matter stack is running, as needed by bridges
+///
+/// Requests for clusters without a registered handler are delegated to `next`,
+/// which is typically the handler of the root endpoint.
+///
+/// Unless a handler is registered for it explicitly, the Descriptor cluster of
+/// every endpoint is served by the registry itself, so that its `PartsList`
+/// always reflects the current topology: the root endpoint lists all other
+/// endpoints, while any other endpoint lists its descendants (i.e. an Aggregator
+/// lists its bridged endpoints). The data version of the Descriptor clusters
+/// changes whenever an endpoint is added or removed.
+///
+/// `N` is the maximum number of endpoints and `H` is the maximum number of
+/// registered handlers.
+///
+/// Endpoints cannot be added or removed while an interaction is being processed,
+/// in which case `ErrorCode::Busy` is returned and the operation should be retried.
+pub struct DynamicHandler<'a, T, const N: usize, const H: usize> {
+    node: RefCell<DynamicNode<'a, N>>,
+    parents: RefCell<heapless::Vec<(EndptId, EndptId), N>>,
+    handlers: RefCell<heapless::Vec<(EndptId, ClusterId, &'a dyn NonBlockingHandler), H>>,
+    descriptor: DescriptorCluster<'static>,
+    next: T,
+}
+
+impl<'a, T, const N: usize, const H: usize> DynamicHandler<'a, T, N, H> {
+    pub fn new(node_id: u16, next: T, rand: Rand) -> Self {
+        Self {
+            node: RefCell::new(DynamicNode::new(node_id)),
+            parents: RefCell::new(heapless::Vec::new()),
+            handlers: RefCell::new(heapless::Vec::new()),
+            descriptor: DescriptorCluster::new(rand),
+            next,
+        }
+    }
+
+    /// Add `endpoint` to the data model, as a part of the `parent` endpoint, if any
+    pub fn add_endpoint(
+        &self,
+        endpoint: Endpoint<'a>,
+        parent: Option<EndptId>,
+    ) -> Result<(), Error> {
+        let mut node = self.node.try_borrow_mut().map_err(|_| ErrorCode::Busy)?;
+
+        if node.node().endpoints.iter().any(|ep| ep.id == endpoint.id) {
+            Err(ErrorCode::Duplicate)?;
+        }
+
+        if let Some(parent) = parent {
+            if !node.node().endpoints.iter().any(|ep| ep.id == parent) {
+                Err(ErrorCode::EndpointNotFound)?;
+            }
+        }
+
+        let mut parents = self.parents.borrow_mut();
+
+        if let Some(parent) = parent {
+            parents
+                .push((endpoint.id, parent))
+                .map_err(|_| ErrorCode::NoSpace)?;
+        }
+
+        if node.add(endpoint).is_err() {
+            if parent.is_some() {
+                parents.pop();
+            }
+
+            Err(ErrorCode::NoSpace)?;
+        }
+
+        self.descriptor.parts_changed();
+
+        Ok(())
+    }
+
+    /// Remove the endpoint with `endpoint_id` from the data model, together with
+    /// all handlers registered for it
+    ///
+    /// The parts of the removed endpoint are kept, and are only listed by the
+    /// root endpoint afterwards.
+    pub fn remove_endpoint(&self, endpoint_id: EndptId) -> Result<Endpoint<'a>, Error> {
+        let mut node = self.node.try_borrow_mut().map_err(|_| ErrorCode::Busy)?;
+
+        let endpoint = node
+            .remove(endpoint_id)
+            .ok_or(ErrorCode::EndpointNotFound)?;
+
+        self.parents
+            .borrow_mut()
+            .retain(|(child, parent)| *child != endpoint_id && *parent != endpoint_id);
+        self.handlers
+            .borrow_mut()
+            .retain(|(ep, _, _)| *ep != endpoint_id);
+
+        self.descriptor.parts_changed();
+
+        Ok(endpoint)
+    }
+
+    /// Register `handler` as the handler of cluster `cluster_id` on endpoint `endpoint_id`,
+    /// which must have been added with `add_endpoint` already
+    pub fn add_handler(
+        &self,
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        handler: &'a dyn NonBlockingHandler,
+    ) -> Result<(), Error> {
+        let node = self.node.try_borrow().map_err(|_| ErrorCode::Busy)?;
+
+        if !node.node().endpoints.iter().any(|ep| ep.id == endpoint_id) {
+            Err(ErrorCode::EndpointNotFound)?;
+        }
+
+        if self.handler(endpoint_id, cluster_id).is_some() {
+            Err(ErrorCode::Duplicate)?;
+        }
+
+        self.handlers
+            .borrow_mut()
+            .push((endpoint_id, cluster_id, handler))
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        Ok(())
+    }
+
+    pub fn remove_handler(
+        &self,
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+    ) -> Option<&'a dyn NonBlockingHandler> {
+        let mut handlers = self.handlers.borrow_mut();
+
+        let index = handlers
+            .iter()
+            .position(|(ep, cl, _)| *ep == endpoint_id && *cl == cluster_id)?;
+
+        Some(handlers.swap_remove(index).2)
+    }
+
+    fn handler(
+        &self,
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+    ) -> Option<&'a dyn NonBlockingHandler> {
+        self.handlers
+            .borrow()
+            .iter()
+            .find(|(ep, cl, _)| *ep == endpoint_id && *cl == cluster_id)
+            .map(|(_, _, handler)| *handler)
+    }
+
+    fn read_descriptor(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        let parents = self.parents.borrow();
+
+        self.descriptor
+            .read_matching(&TopologyPartsMatcher(&parents), attr, encoder)
+    }
+}
+
+impl<'a, T, const N: usize, const H: usize> Handler for DynamicHandler<'a, T, N, H>
+where
+    T: Handler,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(handler) = self.handler(attr.endpoint_id, attr.cluster_id) {
+            handler.read(attr, encoder)
+        } else if attr.cluster_id == descriptor::ID {
+            self.read_descriptor(attr, encoder)
+        } else {
+            self.next.read(attr, encoder)
+        }
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        if let Some(handler) = self.handler(attr.endpoint_id, attr.cluster_id) {
+            handler.write(attr, data)
+        } else {
+            self.next.write(attr, data)
+        }
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        if let Some(handler) = self.handler(cmd.endpoint_id, cmd.cluster_id) {
+            handler.invoke(exchange, cmd, data, encoder)
+        } else {
+            self.next.invoke(exchange, cmd, data, encoder)
+        }
+    }
+
+    fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+        if let Some(handler) = self.handler(endpoint, cluster) {
+            handler.dataver(endpoint, cluster)
+        } else if cluster == descriptor::ID {
+            Handler::dataver(&self.descriptor, endpoint, cluster)
+        } else {
+            self.next.dataver(endpoint, cluster)
+        }
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        // Copy the handlers out, so that they can add or remove handlers from the callback
+        let handlers = self.handlers.borrow().clone();
+
+        for (_, _, handler) in handlers.iter() {
+            handler.fabric_removed(fab_idx);
+        }
+
+        self.next.fabric_removed(fab_idx);
+    }
+
+    fn failsafe_expired(&self) {
+        let handlers = self.handlers.borrow().clone();
+
+        for (_, _, handler) in handlers.iter() {
+            handler.failsafe_expired();
+        }
+
+        self.next.failsafe_expired();
+    }
+}
+
+impl<'a, T, const N: usize, const H: usize> NonBlockingHandler for DynamicHandler<'a, T, N, H> where
+    T: NonBlockingHandler
+{
+}
+
+impl<'a, T, const N: usize, const H: usize> Metadata for DynamicHandler<'a, T, N, H> {
+    type MetadataGuard<'g> = Ref<'g, DynamicNode<'a, N>> where Self: 'g;
+
+    fn lock(&self) -> Self::MetadataGuard<'_> {
+        self.node.borrow()
+    }
+}
+
+/// The root endpoint describes all other endpoints, while any other endpoint
+/// describes its descendants
+struct TopologyPartsMatcher<'r>(&'r [(EndptId, EndptId)]);
+
+impl<'r> TopologyPartsMatcher<'r> {
+    fn parent(&self, endpoint: EndptId) -> Option<EndptId> {
+        self.0
+            .iter()
+            .find(|(child, _)| *child == endpoint)
+            .map(|(_, parent)| *parent)
+    }
+}
+
+impl<'r> PartsMatcher for TopologyPartsMatcher<'r> {
+    fn describe(&self, our_endpoint: EndptId, endpoint: EndptId) -> bool {
+        if endpoint == our_endpoint {
+            return false;
+        }
+
+        if our_endpoint == 0 {
+            return true;
+        }
+
+        // Parents are always added before their parts, so the links cannot form a cycle
+        let mut current = endpoint;
+        while let Some(parent) = self.parent(current) {
+            if parent == our_endpoint {
+                return true;
+            }
+
+            current = parent;
+        }
+
+        false
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod asynch {
+    use core::cell::Ref;
+
+    use crate::{
+        data_model::objects::*, data_model::system_model::descriptor, error::Error,
+        tlv::TLVElement, transport::exchange::Exchange,
+    };
+
+    use super::DynamicHandler;
+
+    impl<'a, T, const N: usize, const H: usize> AsyncMetadata for DynamicHandler<'a, T, N, H> {
+        type MetadataGuard<'g> = Ref<'g, DynamicNode<'a, N>> where Self: 'g;
+
+        async fn lock(&self) -> Self::MetadataGuard<'_> {
+            self.node.borrow()
+        }
+    }
+
+    impl<'a, T, const N: usize, const H: usize> AsyncHandler for DynamicHandler<'a, T, N, H>
+    where
+        T: AsyncHandler,
+    {
+        async fn read<'m>(
+            &'m self,
+            attr: &'m AttrDetails<'_>,
+            encoder: AttrDataEncoder<'m, '_, '_>,
+        ) -> Result<(), Error> {
+            if let Some(handler) = self.handler(attr.endpoint_id, attr.cluster_id) {
+                Handler::read(handler, attr, encoder)
+            } else if attr.cluster_id == descriptor::ID {
+                self.read_descriptor(attr, encoder)
+            } else {
+                self.next.read(attr, encoder).await
+            }
+        }
+
+        async fn write<'m>(
+            &'m self,
+            attr: &'m AttrDetails<'_>,
+            data: AttrData<'m>,
+        ) -> Result<(), Error> {
+            if let Some(handler) = self.handler(attr.endpoint_id, attr.cluster_id) {
+                Handler::write(handler, attr, data)
+            } else {
+                self.next.write(attr, data).await
+            }
+        }
+
+        async fn invoke<'m>(
+            &'m self,
+            exchange: &'m Exchange<'_>,
+            cmd: &'m CmdDetails<'_>,
+            data: &'m TLVElement<'_>,
+            encoder: CmdDataEncoder<'m, '_, '_>,
+        ) -> Result<(), Error> {
+            if let Some(handler) = self.handler(cmd.endpoint_id, cmd.cluster_id) {
+                Handler::invoke(handler, exchange, cmd, data, encoder)
+            } else {
+                self.next.invoke(exchange, cmd, data, encoder).await
+            }
+        }
+
+        fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+            if let Some(handler) = self.handler(endpoint, cluster) {
+                Handler::dataver(handler, endpoint, cluster)
+            } else if cluster == descriptor::ID {
+                Handler::dataver(&self.descriptor, endpoint, cluster)
+            } else {
+                self.next.dataver(endpoint, cluster)
+            }
+        }
+
+        fn fabric_removed(&self, fab_idx: u8) {
+            let handlers = self.handlers.borrow().clone();
+
+            for (_, _, handler) in handlers.iter() {
+                Handler::fabric_removed(*handler, fab_idx);
+            }
+
+            self.next.fabric_removed(fab_idx);
+        }
+
+        fn failsafe_expired(&self) {
+            let handlers = self.handlers.borrow().clone();
+
+            for (_, _, handler) in handlers.iter() {
+                Handler::failsafe_expired(*handler);
+            }
+
+            self.next.failsafe_expired();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::{
+        data_model::{
+            device_types::DEV_TYPE_ON_OFF_LIGHT,
+            objects::{
+                AttrDataEncoder, AttrDetails, EmptyHandler, Endpoint, Handler, NonBlockingHandler,
+            },
+            system_model::descriptor::{self, PartsMatcher},
+        },
+        error::{Error, ErrorCode},
+        utils::rand::dummy_rand,
+    };
+
+    use super::{DynamicHandler, TopologyPartsMatcher};
+
+    const fn endpoint(id: u16) -> Endpoint<'static> {
+        Endpoint {
+            id,
+            device_type: DEV_TYPE_ON_OFF_LIGHT,
+            extra_device_types: &[],
+            clusters: &[descriptor::CLUSTER],
+            client_clusters: &[],
+        }
+    }
+
+    #[test]
+    fn test_add_remove_endpoints() {
+        let handler = DynamicHandler::<_, 4, 4>::new(0, EmptyHandler, dummy_rand);
+
+        handler.add_endpoint(endpoint(0), None).unwrap();
+        handler.add_endpoint(endpoint(1), Some(0)).unwrap();
+
+        assert_eq!(
+            handler.add_endpoint(endpoint(1), None).unwrap_err().code(),
+            ErrorCode::Duplicate
+        );
+        assert_eq!(
+            handler
+                .add_endpoint(endpoint(2), Some(5))
+                .unwrap_err()
+                .code(),
+            ErrorCode::EndpointNotFound
+        );
+
+        let dataver = handler.dataver(0, descriptor::ID);
+
+        handler.add_endpoint(endpoint(2), Some(1)).unwrap();
+        assert_ne!(handler.dataver(0, descriptor::ID), dataver);
+        assert_eq!(handler.node.borrow().node().endpoints.len(), 3);
+
+        let dataver = handler.dataver(0, descriptor::ID);
+
+        assert_eq!(handler.remove_endpoint(2).unwrap().id, 2);
+        assert_ne!(handler.dataver(0, descriptor::ID), dataver);
+        assert_eq!(handler.node.borrow().node().endpoints.len(), 2);
+        assert_eq!(
+            handler.remove_endpoint(2).unwrap_err().code(),
+            ErrorCode::EndpointNotFound
+        );
+    }
+
+    #[test]
+    fn test_add_endpoint_no_space() {
+        let handler = DynamicHandler::<_, 2, 4>::new(0, EmptyHandler, dummy_rand);
+
+        handler.add_endpoint(endpoint(0), None).unwrap();
+        handler.add_endpoint(endpoint(1), Some(0)).unwrap();
+
+        assert_eq!(
+            handler
+                .add_endpoint(endpoint(2), Some(1))
+                .unwrap_err()
+                .code(),
+            ErrorCode::NoSpace
+        );
+
+        // No parent link is left behind for the rejected endpoint
+        assert_eq!(handler.parents.borrow().as_slice(), &[(1, 0)]);
+    }
+
+    #[test]
+    fn test_add_handler_unknown_endpoint() {
+        let cluster = SelfRemoving(Cell::new(None));
+        let handler = DynamicHandler::<_, 4, 4>::new(0, EmptyHandler, dummy_rand);
+
+        assert_eq!(
+            handler.add_handler(1, 0x1234, &cluster).unwrap_err().code(),
+            ErrorCode::EndpointNotFound
+        );
+
+        handler.add_endpoint(endpoint(1), None).unwrap();
+        handler.add_handler(1, 0x1234, &cluster).unwrap();
+    }
+
+    #[test]
+    fn test_busy_while_locked() {
+        let handler = DynamicHandler::<_, 4, 4>::new(0, EmptyHandler, dummy_rand);
+
+        let guard = handler.node.borrow();
+        assert_eq!(
+            handler.add_endpoint(endpoint(0), None).unwrap_err().code(),
+            ErrorCode::Busy
+        );
+        drop(guard);
+
+        handler.add_endpoint(endpoint(0), None).unwrap();
+    }
+
+    type TestHandler = DynamicHandler<'static, EmptyHandler, 4, 4>;
+
+    /// A handler which unregisters itself once its fabric is removed
+    struct SelfRemoving(Cell<Option<&'static TestHandler>>);
+
+    impl Handler for SelfRemoving {
+        fn read(&self, _attr: &AttrDetails, _encoder: AttrDataEncoder) -> Result<(), Error> {
+            Err(ErrorCode::AttributeNotFound.into())
+        }
+
+        fn fabric_removed(&self, _fab_idx: u8) {
+            if let Some(dynamic) = self.0.get() {
+                dynamic.remove_handler(1, 0x1234).unwrap();
+            }
+        }
+    }
+
+    impl NonBlockingHandler for SelfRemoving {}
+
+    #[test]
+    fn test_remove_handler_on_fabric_removed() {
+        // Leaked, as the handler and its registry refer to each other
+        let handler: &'static TestHandler =
+            Box::leak(Box::new(DynamicHandler::new(0, EmptyHandler, dummy_rand)));
+        let removing: &'static SelfRemoving = Box::leak(Box::new(SelfRemoving(Cell::new(None))));
+
+        removing.0.set(Some(handler));
+        handler.add_endpoint(endpoint(1), None).unwrap();
+        handler.add_handler(1, 0x1234, removing).unwrap();
+
+        handler.fabric_removed(1);
+
+        assert!(handler.handler(1, 0x1234).is_none());
+    }
+
+    #[test]
+    fn test_parts() {
+        // 0 (root) -> 1 (aggregator) -> 2, 3 (bridged) -> 4 (bridged part of 3)
+        let parents = [(1, 0), (2, 1), (3, 1), (4, 3)];
+        let matcher = TopologyPartsMatcher(&parents);
+
+        assert!((1..=4).all(|ep| matcher.describe(0, ep)));
+        assert!(!matcher.describe(0, 0));
+
+        assert!((2..=4).all(|ep| matcher.describe(1, ep)));
+        assert!(!matcher.describe(1, 0));
+        assert!(!matcher.describe(1, 1));
+
+        assert!(matcher.describe(3, 4));
+        assert!(!matcher.describe(2, 4));
+        assert!(!matcher.describe(4, 3));
+    }
+}
@@ -17,12 +17,41 @@
 
 pub mod core;
 pub mod device_types;
+pub mod dynamic;
 pub mod objects;
+pub mod persist;
+pub mod transition;
 
+pub mod aggregator;
 pub mod cluster_basic_information;
-// TODO pub mod cluster_media_playback;
+pub mod cluster_bridged_device_basic_information;
+pub mod cluster_color_control;
+pub mod cluster_door_lock;
+pub mod cluster_fan_control;
+pub mod cluster_fixed_label;
+pub mod cluster_identify;
+pub mod cluster_illuminance_measurement;
+pub mod cluster_level_control;
+pub mod cluster_localization;
+pub mod cluster_measurement;
+pub mod cluster_media_playback;
+pub mod cluster_occupancy_sensing;
 pub mod cluster_on_off;
+pub mod cluster_ota_requestor;
+pub mod cluster_power_source;
+pub mod cluster_pressure_measurement;
+pub mod cluster_relative_humidity_measurement;
+pub mod cluster_scenes_management;
+pub mod cluster_temperature_measurement;
 pub mod cluster_template;
+pub mod cluster_thermostat;
+pub mod cluster_thermostat_ui_config;
+pub mod cluster_time_format_localization;
+pub mod cluster_unit_localization;
+pub mod cluster_user_label;
+pub mod cluster_window_covering;
 pub mod root_endpoint;
 pub mod sdm;
 pub mod system_model;
+
+pub use rs_matter_macros::import;
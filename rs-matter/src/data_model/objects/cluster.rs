@@ -41,7 +41,7 @@ use core::{
 #[derive(Clone, Copy, Debug, Eq, PartialEq, FromRepr)]
 #[repr(u16)]
 pub enum GlobalElements {
-    _ClusterRevision = 0xFFFD,
+    ClusterRevision = 0xFFFD,
     FeatureMap = 0xFFFC,
     AttributeList = 0xFFFB,
     _EventList = 0xFFFA,
@@ -137,6 +137,8 @@ pub struct CmdDetails<'a> {
     pub cluster_id: ClusterId,
     pub cmd_id: CmdId,
     pub wildcard: bool,
+    /// Whether the command is part of a timed interaction
+    pub timed: bool,
 }
 
 impl<'a> CmdDetails<'a> {
@@ -284,6 +286,12 @@ impl<'a> Cluster<'a> {
             })?;
         }
 
+        // Fabric-scoped data is always written on behalf of a fabric, which a PASE session
+        // does not have yet
+        if write && target_perms.contains(Access::FAB_SCOPED) && accessor.fab_idx == 0 {
+            Err(IMStatusCode::UnsupportedAccess)?;
+        }
+
         access_req.set_target_perms(target_perms);
         if access_req.allow() {
             Ok(())
@@ -404,6 +404,21 @@ impl<'a, 'b, 'c> CmdDataEncoder<'a, 'b, 'c> {
 
         Ok(writer)
     }
+
+    /// Fail the command with the cluster-specific status `cluster_status`, instead of
+    /// responding with a command
+    pub fn cluster_status(self, cluster_status: u16) -> Result<(), Error> {
+        InvResp::Status(CmdStatus::new(
+            self.path,
+            IMStatusCode::Failure,
+            cluster_status,
+        ))
+        .to_tlv(self.tw, TagType::Anonymous)?;
+
+        self.tracker.complete();
+
+        Ok(())
+    }
 }
 
 pub struct CmdDataWriter<'a, 'b, 'c> {
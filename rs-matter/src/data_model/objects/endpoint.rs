@@ -25,7 +25,13 @@ use super::{AttrId, Attribute, Cluster, ClusterId, CmdId, DeviceType, EndptId};
 pub struct Endpoint<'a> {
     pub id: EndptId,
     pub device_type: DeviceType,
+    /// Further device types of the endpoint, i.e. Bridged Node on the endpoint of a
+    /// bridged device, or Power Source on an endpoint with a power source
+    pub extra_device_types: &'a [DeviceType],
     pub clusters: &'a [Cluster<'a>],
+    /// The clusters for which the endpoint is a client, e.g. to control other nodes
+    /// through the Binding cluster
+    pub client_clusters: &'a [ClusterId],
 }
 
 impl<'a> Endpoint<'a> {
@@ -15,13 +15,17 @@
  *    limitations under the License.
  */
 
+use core::marker::PhantomData;
+
 use crate::{
     error::{Error, ErrorCode},
     tlv::TLVElement,
     transport::exchange::Exchange,
 };
 
-use super::{AttrData, AttrDataEncoder, AttrDetails, CmdDataEncoder, CmdDetails};
+use super::{
+    AttrData, AttrDataEncoder, AttrDetails, ClusterId, CmdDataEncoder, CmdDetails, EndptId,
+};
 
 #[cfg(feature = "nightly")]
 pub use asynch::*;
@@ -56,6 +60,25 @@ pub trait Handler {
     ) -> Result<(), Error> {
         Err(ErrorCode::CommandNotFound.into())
     }
+
+    /// Return the current data version of the cluster served by this handler, if known
+    ///
+    /// The data model uses this to skip all attributes of a cluster whose data version
+    /// matches one of the `DataVersionFilter`s of a read or subscribe request.
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        None
+    }
+
+    /// Drop whatever the handler keeps for a fabric which was removed
+    ///
+    /// Called by the transport once the exchange which removed the fabric is over.
+    fn fabric_removed(&self, _fab_idx: u8) {}
+
+    /// Revert whatever the handler changed while the fail-safe was armed, as it expired
+    ///
+    /// Called by the transport once the fail-safe timer elapsed, or once the exchange which
+    /// expired it is over.
+    fn failsafe_expired(&self) {}
 }
 
 impl<T> Handler for &T
@@ -79,6 +102,18 @@ where
     ) -> Result<(), Error> {
         (**self).invoke(exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+        (**self).dataver(endpoint, cluster)
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        (**self).fabric_removed(fab_idx)
+    }
+
+    fn failsafe_expired(&self) {
+        (**self).failsafe_expired()
+    }
 }
 
 impl<T> Handler for &mut T
@@ -102,6 +137,18 @@ where
     ) -> Result<(), Error> {
         (**self).invoke(exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+        (**self).dataver(endpoint, cluster)
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        (**self).fabric_removed(fab_idx)
+    }
+
+    fn failsafe_expired(&self) {
+        (**self).failsafe_expired()
+    }
 }
 
 pub trait NonBlockingHandler: Handler {}
@@ -131,6 +178,18 @@ where
     ) -> Result<(), Error> {
         self.1.invoke(exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+        self.1.dataver(endpoint, cluster)
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        self.1.fabric_removed(fab_idx)
+    }
+
+    fn failsafe_expired(&self) {
+        self.1.failsafe_expired()
+    }
 }
 
 impl<M, H> NonBlockingHandler for (M, H) where H: NonBlockingHandler {}
@@ -190,6 +249,44 @@ impl<H, T> ChainedHandler<H, T> {
     }
 }
 
+impl<H, T> ChainedHandler<H, T> {
+    /// The handler of type `X` in this chain, looked up by its type rather than by its
+    /// position, so the chain must contain exactly one handler of that type
+    pub fn get<X, I>(&self) -> &X
+    where
+        Self: ChainedSelect<X, I>,
+    {
+        self.select()
+    }
+}
+
+/// The position of the selected handler in a `ChainedHandler`: this one
+pub struct Here;
+
+/// The position of the selected handler in a `ChainedHandler`: somewhere in `next`
+pub struct There<I>(PhantomData<I>);
+
+/// Selects the handler of type `X` at position `I` in a `ChainedHandler`,
+/// with `I` inferred by the compiler
+pub trait ChainedSelect<X, I> {
+    fn select(&self) -> &X;
+}
+
+impl<H, T> ChainedSelect<H, Here> for ChainedHandler<H, T> {
+    fn select(&self) -> &H {
+        &self.handler
+    }
+}
+
+impl<X, H, T, I> ChainedSelect<X, There<I>> for ChainedHandler<H, T>
+where
+    T: ChainedSelect<X, I>,
+{
+    fn select(&self) -> &X {
+        self.next.select()
+    }
+}
+
 impl<H, T> Handler for ChainedHandler<H, T>
 where
     H: Handler,
@@ -224,6 +321,24 @@ where
             self.next.invoke(exchange, cmd, data, encoder)
         }
     }
+
+    fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+        if self.handler_endpoint == endpoint && self.handler_cluster == cluster {
+            self.handler.dataver(endpoint, cluster)
+        } else {
+            self.next.dataver(endpoint, cluster)
+        }
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        self.handler.fabric_removed(fab_idx);
+        self.next.fabric_removed(fab_idx);
+    }
+
+    fn failsafe_expired(&self) {
+        self.handler.failsafe_expired();
+        self.next.failsafe_expired();
+    }
 }
 
 impl<H, T> NonBlockingHandler for ChainedHandler<H, T>
@@ -272,10 +387,48 @@ where
     ) -> Result<(), Error> {
         self.0.invoke(exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+        self.0.dataver(endpoint, cluster)
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        self.0.fabric_removed(fab_idx)
+    }
+
+    fn failsafe_expired(&self) {
+        self.0.failsafe_expired()
+    }
 }
 
 impl<T> NonBlockingHandler for HandlerCompat<T> where T: NonBlockingHandler {}
 
+impl<T> HandlerCompat<T> {
+    /// Chains an `AsyncHandler` in front of the wrapped `NonBlockingHandler`
+    pub const fn chain<H>(
+        self,
+        handler_endpoint: u16,
+        handler_cluster: u32,
+        handler: H,
+    ) -> ChainedHandler<H, Self> {
+        ChainedHandler {
+            handler_endpoint,
+            handler_cluster,
+            handler,
+            next: self,
+        }
+    }
+}
+
+impl<X, I, T> ChainedSelect<X, I> for HandlerCompat<T>
+where
+    T: ChainedSelect<X, I>,
+{
+    fn select(&self) -> &X {
+        self.0.select()
+    }
+}
+
 #[allow(unused_macros)]
 #[macro_export]
 macro_rules! handler_chain_type {
@@ -297,7 +450,9 @@ macro_rules! handler_chain_type {
 #[cfg(feature = "nightly")]
 mod asynch {
     use crate::{
-        data_model::objects::{AttrData, AttrDataEncoder, AttrDetails, CmdDataEncoder, CmdDetails},
+        data_model::objects::{
+            AttrData, AttrDataEncoder, AttrDetails, ClusterId, CmdDataEncoder, CmdDetails, EndptId,
+        },
         error::{Error, ErrorCode},
         tlv::TLVElement,
         transport::exchange::Exchange,
@@ -329,6 +484,14 @@ mod asynch {
         ) -> Result<(), Error> {
             Err(ErrorCode::CommandNotFound.into())
         }
+
+        fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+            None
+        }
+
+        fn fabric_removed(&self, _fab_idx: u8) {}
+
+        fn failsafe_expired(&self) {}
     }
 
     impl<T> AsyncHandler for &mut T
@@ -360,6 +523,18 @@ mod asynch {
         ) -> Result<(), Error> {
             (**self).invoke(exchange, cmd, data, encoder).await
         }
+
+        fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+            (**self).dataver(endpoint, cluster)
+        }
+
+        fn fabric_removed(&self, fab_idx: u8) {
+            (**self).fabric_removed(fab_idx)
+        }
+
+        fn failsafe_expired(&self) {
+            (**self).failsafe_expired()
+        }
     }
 
     impl<T> AsyncHandler for &T
@@ -391,6 +566,18 @@ mod asynch {
         ) -> Result<(), Error> {
             (**self).invoke(exchange, cmd, data, encoder).await
         }
+
+        fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+            (**self).dataver(endpoint, cluster)
+        }
+
+        fn fabric_removed(&self, fab_idx: u8) {
+            (**self).fabric_removed(fab_idx)
+        }
+
+        fn failsafe_expired(&self) {
+            (**self).failsafe_expired()
+        }
     }
 
     impl<M, H> AsyncHandler for (M, H)
@@ -422,6 +609,18 @@ mod asynch {
         ) -> Result<(), Error> {
             self.1.invoke(exchange, cmd, data, encoder).await
         }
+
+        fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+            self.1.dataver(endpoint, cluster)
+        }
+
+        fn fabric_removed(&self, fab_idx: u8) {
+            self.1.fabric_removed(fab_idx)
+        }
+
+        fn failsafe_expired(&self) {
+            self.1.failsafe_expired()
+        }
     }
 
     impl<T> AsyncHandler for HandlerCompat<T>
@@ -453,6 +652,18 @@ mod asynch {
         ) -> Result<(), Error> {
             Handler::invoke(&self.0, exchange, cmd, data, encoder)
         }
+
+        fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+            Handler::dataver(&self.0, endpoint, cluster)
+        }
+
+        fn fabric_removed(&self, fab_idx: u8) {
+            Handler::fabric_removed(&self.0, fab_idx)
+        }
+
+        fn failsafe_expired(&self) {
+            Handler::failsafe_expired(&self.0)
+        }
     }
 
     impl AsyncHandler for EmptyHandler {
@@ -509,5 +720,23 @@ mod asynch {
                 self.next.invoke(exchange, cmd, data, encoder).await
             }
         }
+
+        fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+            if self.handler_endpoint == endpoint && self.handler_cluster == cluster {
+                self.handler.dataver(endpoint, cluster)
+            } else {
+                self.next.dataver(endpoint, cluster)
+            }
+        }
+
+        fn fabric_removed(&self, fab_idx: u8) {
+            self.handler.fabric_removed(fab_idx);
+            self.next.fabric_removed(fab_idx);
+        }
+
+        fn failsafe_expired(&self) {
+            self.handler.failsafe_expired();
+            self.next.failsafe_expired();
+        }
     }
 }
@@ -15,7 +15,9 @@
  *    limitations under the License.
  */
 
-use crate::data_model::objects::Node;
+use core::cell::Ref;
+
+use crate::data_model::objects::{DynamicNode, Node};
 
 #[cfg(feature = "nightly")]
 pub use asynch::*;
@@ -74,6 +76,21 @@ where
     }
 }
 
+impl<'r, T> MetadataGuard for Ref<'r, T>
+where
+    T: MetadataGuard,
+{
+    fn node(&self) -> Node<'_> {
+        (**self).node()
+    }
+}
+
+impl<'a, const N: usize> MetadataGuard for DynamicNode<'a, N> {
+    fn node(&self) -> Node<'_> {
+        DynamicNode::node(self)
+    }
+}
+
 impl<'a> MetadataGuard for Node<'a> {
     fn node(&self) -> Node<'_> {
         Node {
@@ -70,6 +70,7 @@ impl<'a> Node<'a> {
         req: &'m ReadReq,
         from: Option<GenericPath>,
         accessor: &'m Accessor<'m>,
+        dataver: &'m dyn Fn(EndptId, ClusterId) -> Option<u32>,
     ) -> impl Iterator<Item = Result<AttrDetails, AttrStatus>> + 'm
     where
         's: 'm,
@@ -82,6 +83,7 @@ impl<'a> Node<'a> {
             req.fabric_filtered,
             accessor,
             from,
+            dataver,
         )
     }
 
@@ -90,6 +92,7 @@ impl<'a> Node<'a> {
         req: &'m SubscribeReq,
         from: Option<GenericPath>,
         accessor: &'m Accessor<'m>,
+        dataver: &'m dyn Fn(EndptId, ClusterId) -> Option<u32>,
     ) -> impl Iterator<Item = Result<AttrDetails, AttrStatus>> + 'm
     where
         's: 'm,
@@ -102,9 +105,11 @@ impl<'a> Node<'a> {
             req.fabric_filtered,
             accessor,
             from,
+            dataver,
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn read_attr_requests<'s, 'm, P>(
         &'s self,
         attr_requests: P,
@@ -112,6 +117,7 @@ impl<'a> Node<'a> {
         fabric_filtered: bool,
         accessor: &'m Accessor<'m>,
         from: Option<GenericPath>,
+        dataver: &'m dyn Fn(EndptId, ClusterId) -> Option<u32>,
     ) -> impl Iterator<Item = Result<AttrDetails, AttrStatus>> + 'm
     where
         's: 'm,
@@ -126,6 +132,9 @@ impl<'a> Node<'a> {
                     .skip_while(move |(ep, cl, attr)| {
                         !Self::matches(from.as_ref(), ep.id, cl.id, attr.id as _)
                     })
+                    .filter(move |(ep, cl, _)| {
+                        !Self::is_unchanged(dataver_filters, dataver, ep.id, cl.id)
+                    })
                     .filter(move |(ep, cl, attr)| {
                         Cluster::check_attr_access(
                             accessor,
@@ -136,15 +145,6 @@ impl<'a> Node<'a> {
                         .is_ok()
                     })
                     .map(move |(ep, cl, attr)| {
-                        let dataver = if let Some(dataver_filters) = dataver_filters {
-                            dataver_filters.iter().find_map(|filter| {
-                                (filter.path.endpoint == ep.id && filter.path.cluster == cl.id)
-                                    .then_some(filter.data_ver)
-                            })
-                        } else {
-                            None
-                        };
-
                         Ok(AttrDetails {
                             node: self,
                             endpoint_id: ep.id,
@@ -153,7 +153,7 @@ impl<'a> Node<'a> {
                             list_index: path.list_index,
                             fab_idx: accessor.fab_idx,
                             fab_filter: fabric_filtered,
-                            dataver,
+                            dataver: Self::dataver_filter(dataver_filters, ep.id, cl.id),
                             wildcard: true,
                         })
                     });
@@ -165,32 +165,26 @@ impl<'a> Node<'a> {
                 let attr = path.attr.unwrap();
 
                 let result = match self.check_attribute(accessor, ep, cl, attr, false) {
-                    Ok(()) => {
-                        let dataver = if let Some(dataver_filters) = dataver_filters {
-                            dataver_filters.iter().find_map(|filter| {
-                                (filter.path.endpoint == ep && filter.path.cluster == cl)
-                                    .then_some(filter.data_ver)
-                            })
-                        } else {
-                            None
-                        };
-
-                        Ok(AttrDetails {
-                            node: self,
-                            endpoint_id: ep,
-                            cluster_id: cl,
-                            attr_id: attr,
-                            list_index: path.list_index,
-                            fab_idx: accessor.fab_idx,
-                            fab_filter: fabric_filtered,
-                            dataver,
-                            wildcard: false,
-                        })
-                    }
-                    Err(err) => Err(AttrStatus::new(&path.to_gp(), err, 0)),
+                    Ok(()) if Self::is_unchanged(dataver_filters, dataver, ep, cl) => None,
+                    Ok(()) => Some(Ok(AttrDetails {
+                        node: self,
+                        endpoint_id: ep,
+                        cluster_id: cl,
+                        attr_id: attr,
+                        list_index: path.list_index,
+                        fab_idx: accessor.fab_idx,
+                        fab_filter: fabric_filtered,
+                        dataver: Self::dataver_filter(dataver_filters, ep, cl),
+                        wildcard: false,
+                    })),
+                    Err(err) => Some(Err(AttrStatus::new(&path.to_gp(), err, 0))),
                 };
 
-                WildcardIter::Single(once(result))
+                if let Some(result) = result {
+                    WildcardIter::Single(once(result))
+                } else {
+                    WildcardIter::None
+                }
             }
         }))
     }
@@ -280,6 +274,9 @@ impl<'a> Node<'a> {
         req: &'m InvReq,
         accessor: &'m Accessor<'m>,
     ) -> impl Iterator<Item = Result<(CmdDetails, TLVElement<'m>), CmdStatus>> + 'm {
+        // The Interaction Model already checked that this matches the preceding Timed Request
+        let timed = req.timed_request.unwrap_or(false);
+
         alloc!(req
             .inv_requests
             .iter()
@@ -307,6 +304,7 @@ impl<'a> Node<'a> {
                                     cluster_id: cl.id,
                                     cmd_id: cmd,
                                     wildcard: true,
+                                    timed,
                                 },
                                 cmd_data.data.clone().unwrap_tlv().unwrap(),
                             ))
@@ -326,6 +324,7 @@ impl<'a> Node<'a> {
                                 cluster_id: cmd_data.path.path.cluster.unwrap(),
                                 cmd_id: cmd_data.path.path.leaf.unwrap(),
                                 wildcard: false,
+                                timed,
                             },
                             cmd_data.data.unwrap_tlv().unwrap(),
                         )),
@@ -337,6 +336,31 @@ impl<'a> Node<'a> {
             }))
     }
 
+    fn dataver_filter(
+        dataver_filters: Option<&TLVArray<DataVersionFilter>>,
+        ep: EndptId,
+        cl: ClusterId,
+    ) -> Option<u32> {
+        dataver_filters.and_then(|dataver_filters| {
+            dataver_filters.iter().find_map(|filter| {
+                (filter.path.endpoint == ep && filter.path.cluster == cl).then_some(filter.data_ver)
+            })
+        })
+    }
+
+    /// Returns true if the requester already has the current data version of the cluster,
+    /// in which case none of its attributes should be reported
+    fn is_unchanged(
+        dataver_filters: Option<&TLVArray<DataVersionFilter>>,
+        dataver: &dyn Fn(EndptId, ClusterId) -> Option<u32>,
+        ep: EndptId,
+        cl: ClusterId,
+    ) -> bool {
+        Self::dataver_filter(dataver_filters, ep, cl)
+            .map(|filter_dataver| dataver(ep, cl) == Some(filter_dataver))
+            .unwrap_or(false)
+    }
+
     fn matches(path: Option<&GenericPath>, ep: EndptId, cl: ClusterId, leaf: u32) -> bool {
         if let Some(path) = path {
             path.endpoint.map(|id| id == ep).unwrap_or(true)
@@ -0,0 +1,305 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use log::warn;
+
+use crate::{
+    error::{Error, ErrorCode},
+    interaction_model::messages::ib::AttrResp,
+    tlv::{FromTLV, TLVList, TLVWriter, TagType},
+    utils::writebuf::WriteBuf,
+};
+
+use super::objects::{
+    Access, AttrData, AttrDataEncoder, AttrDetails, AttrId, Cluster, ClusterId, DataModelHandler,
+    Endpoint, EndptId, MetadataGuard, Node, Quality,
+};
+
+/// Persistent state of a cluster which is not exposed as attributes, i.e. a scene table
+///
+/// As this state can only be changed by commands, the stack notifies the persistence
+/// runner after every invoke (see `Matter::wait_changed`).
+pub trait StatePersist {
+    /// Restore the state from `data`, as previously returned by `store`
+    fn load(&self, data: &[u8]) -> Result<(), Error>;
+
+    /// Store the state into `buf`, if it changed since the last `load` or `store`
+    fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error>;
+}
+
+impl<T> StatePersist for &T
+where
+    T: StatePersist,
+{
+    fn load(&self, data: &[u8]) -> Result<(), Error> {
+        (**self).load(data)
+    }
+
+    fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        (**self).store(buf)
+    }
+}
+
+/// Persists the values of all attributes with `Quality::PERSISTENT` (N) in the data model
+///
+/// The persistent attributes of a cluster are stored again whenever the data version of
+/// that cluster (as reported by `Handler::dataver`) changes. The values are read and
+/// restored through the regular `Handler::read` and `Handler::write` methods, so clusters
+/// only need to support writing their persistent attributes.
+///
+/// Rather than the data version of each cluster, only a digest of the data versions
+/// of all clusters with persistent attributes is kept, so any number of clusters is
+/// supported.
+///
+/// Fabric-scoped attributes (i.e. Binding) are skipped, as they can only be written on
+/// behalf of an accessing fabric. Their clusters persist them through `StatePersist` instead.
+pub struct AttrPersist {
+    digest: Option<u32>,
+}
+
+impl AttrPersist {
+    pub const fn new() -> Self {
+        Self { digest: None }
+    }
+
+    /// Restore the persistent attributes from `data`, as previously returned by `store`
+    ///
+    /// Should be called before the Matter stack is started, so that the first report
+    /// already contains the restored values.
+    pub async fn load<T>(&mut self, handler: &T, data: &[u8]) -> Result<(), Error>
+    where
+        T: DataModelHandler,
+    {
+        #[cfg(feature = "nightly")]
+        let metadata = handler.lock().await;
+
+        #[cfg(not(feature = "nightly"))]
+        let metadata = handler.lock();
+
+        let node = metadata.node();
+
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        for item in root.confirm_array()?.enter().ok_or(ErrorCode::Invalid)? {
+            let attr_data = match AttrResp::from_tlv(&item)? {
+                AttrResp::Data(attr_data) => attr_data,
+                AttrResp::Status(_) => Err(ErrorCode::Invalid)?,
+            };
+
+            let (ep, cl, attr) = attr_data.path.to_gp().not_wildcard()?;
+            let value = attr_data.data.unwrap_tlv().ok_or(ErrorCode::Invalid)?;
+
+            // The data model might have changed since the values were stored
+            if !Self::persistent_attrs(&node).any(|(endpoint, cluster, attribute)| {
+                endpoint.id == ep && cluster.id == cl && attribute == attr as AttrId
+            }) {
+                warn!(
+                    "Skipping stored value of {}/{}/{}: not a persistent attribute",
+                    ep, cl, attr
+                );
+                continue;
+            }
+
+            let details = Self::details(&node, ep, cl, attr as _);
+            let attr_value = AttrData::new(None, &value);
+
+            let result = {
+                #[cfg(not(feature = "nightly"))]
+                {
+                    handler.write(&details, attr_value)
+                }
+
+                #[cfg(feature = "nightly")]
+                {
+                    handler.write(&details, attr_value).await
+                }
+            };
+
+            if let Err(err) = result {
+                warn!("Restoring {}/{}/{} failed: {:?}", ep, cl, attr, err);
+            }
+        }
+
+        self.commit(handler, &node);
+
+        Ok(())
+    }
+
+    /// Store the persistent attributes into `buf`
+    ///
+    /// Returns `None` if the data version of none of the clusters with persistent
+    /// attributes has changed since the last `load` or `store`.
+    pub async fn store<'b, T>(
+        &mut self,
+        handler: &T,
+        buf: &'b mut [u8],
+    ) -> Result<Option<&'b [u8]>, Error>
+    where
+        T: DataModelHandler,
+    {
+        #[cfg(feature = "nightly")]
+        let metadata = handler.lock().await;
+
+        #[cfg(not(feature = "nightly"))]
+        let metadata = handler.lock();
+
+        let node = metadata.node();
+
+        if !self.is_changed(handler, &node) {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+        let mut tw = TLVWriter::new(&mut wb);
+
+        tw.start_array(TagType::Anonymous)?;
+
+        for (endpoint, cluster, attr) in Self::persistent_attrs(&node) {
+            let details = Self::details(&node, endpoint.id, cluster.id, attr);
+            let encoder = AttrDataEncoder::new(&details, &mut tw);
+
+            let result = {
+                #[cfg(not(feature = "nightly"))]
+                {
+                    handler.read(&details, encoder)
+                }
+
+                #[cfg(feature = "nightly")]
+                {
+                    handler.read(&details, encoder).await
+                }
+            };
+
+            match result {
+                Ok(()) => (),
+                Err(err) if err.code() == ErrorCode::NoSpace => return Err(err),
+                Err(err) => warn!(
+                    "Storing {}/{}/{} failed: {:?}",
+                    endpoint.id, cluster.id, attr, err
+                ),
+            }
+        }
+
+        tw.end_container()?;
+
+        self.commit(handler, &node);
+
+        let len = tw.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    fn is_changed<T>(&self, handler: &T, node: &Node) -> bool
+    where
+        T: DataModelHandler,
+    {
+        self.digest != Some(Self::digest(handler, node))
+    }
+
+    fn commit<T>(&mut self, handler: &T, node: &Node)
+    where
+        T: DataModelHandler,
+    {
+        self.digest = Some(Self::digest(handler, node));
+    }
+
+    /// A digest of the data versions of all clusters with persistent attributes
+    ///
+    /// Changes whenever any of these data versions changes.
+    pub(crate) fn digest<T>(handler: &T, node: &Node) -> u32
+    where
+        T: DataModelHandler,
+    {
+        // FNV-1a
+        let mut digest: u32 = 0x811c9dc5;
+
+        for (endpoint, cluster) in Self::persistent_clusters(node) {
+            let dataver = handler.dataver(endpoint.id, cluster.id);
+
+            let bytes = endpoint
+                .id
+                .to_le_bytes()
+                .into_iter()
+                .chain(cluster.id.to_le_bytes())
+                .chain([dataver.is_some() as u8])
+                .chain(dataver.unwrap_or(0).to_le_bytes());
+
+            for byte in bytes {
+                digest = (digest ^ byte as u32).wrapping_mul(0x01000193);
+            }
+        }
+
+        digest
+    }
+
+    fn persistent_clusters<'n>(
+        node: &'n Node<'n>,
+    ) -> impl Iterator<Item = (&'n Endpoint<'n>, &'n Cluster<'n>)> + 'n {
+        node.endpoints.iter().flat_map(|endpoint| {
+            endpoint
+                .clusters
+                .iter()
+                .filter(|cluster| {
+                    cluster
+                        .attributes
+                        .iter()
+                        .any(|attr| attr.quality.contains(Quality::PERSISTENT))
+                })
+                .map(move |cluster| (endpoint, cluster))
+        })
+    }
+
+    fn persistent_attrs<'n>(
+        node: &'n Node<'n>,
+    ) -> impl Iterator<Item = (&'n Endpoint<'n>, &'n Cluster<'n>, AttrId)> + 'n {
+        Self::persistent_clusters(node).flat_map(|(endpoint, cluster)| {
+            cluster
+                .attributes
+                .iter()
+                .filter(|attr| {
+                    attr.quality.contains(Quality::PERSISTENT)
+                        && !attr.access.contains(Access::FAB_SCOPED)
+                })
+                .map(move |attr| (endpoint, cluster, attr.id))
+        })
+    }
+
+    fn details<'n>(
+        node: &'n Node<'n>,
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        attr_id: AttrId,
+    ) -> AttrDetails<'n> {
+        AttrDetails {
+            node,
+            endpoint_id,
+            cluster_id,
+            attr_id,
+            list_index: None,
+            fab_idx: 0,
+            fab_filter: false,
+            dataver: None,
+            wildcard: false,
+        }
+    }
+}
+
+impl Default for AttrPersist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
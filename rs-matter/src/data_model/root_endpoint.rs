@@ -6,11 +6,18 @@ use crate::{
     handler_chain_type,
     mdns::Mdns,
     secure_channel::pake::PaseMgr,
-    utils::{epoch::Epoch, rand::Rand},
+    transport::network::PacketCounters,
+    utils::{
+        epoch::{Epoch, UtcClock},
+        rand::Rand,
+    },
 };
 
 use super::{
     cluster_basic_information::{self, BasicInfoCluster, BasicInfoConfig},
+    cluster_localization::{self, LocalizationCluster, LocalizationConfig},
+    cluster_time_format_localization::{self, TimeFormatLocalizationCluster},
+    cluster_unit_localization::{self, UnitLocalizationCluster},
     objects::{Cluster, EmptyHandler, Endpoint, EndptId},
     sdm::{
         admin_commissioning::{self, AdminCommCluster},
@@ -18,11 +25,16 @@ use super::{
         ethernet_nw_diagnostics::{self, EthNwDiagCluster},
         failsafe::FailSafe,
         general_commissioning::{self, GenCommCluster},
-        general_diagnostics::{self, GenDiagCluster},
+        general_diagnostics::{self, GenDiagCluster, NoDiagnostics},
         group_key_management,
         group_key_management::GrpKeyMgmtCluster,
+        icd_management,
         noc::{self, NocCluster},
         nw_commissioning::{self, NwCommCluster},
+        sw_diagnostics::{self, NoSwDiagnostics, SwDiagCluster},
+        thread_nw_diagnostics::{self, ThreadNwDiagCluster},
+        time_sync::{self, TimeSyncCluster},
+        wifi_nw_diagnostics::{self, WiFiNwDiagCluster},
     },
     system_model::{
         access_control::{self, AccessControlCluster},
@@ -30,20 +42,43 @@ use super::{
     },
 };
 
-pub type RootEndpointHandler<'a> = handler_chain_type!(
+pub type RootEndpointHandler<'a, N = NwCommCluster, D = EthNwDiagCluster<'a>> = handler_chain_type!(
     DescriptorCluster<'static>,
     BasicInfoCluster<'a>,
     GenCommCluster<'a>,
-    NwCommCluster,
+    N,
     AdminCommCluster<'a>,
     NocCluster<'a>,
     AccessControlCluster<'a>,
-    GenDiagCluster,
-    EthNwDiagCluster,
-    GrpKeyMgmtCluster
+    GenDiagCluster<'a>,
+    SwDiagCluster<'a>,
+    D,
+    TimeSyncCluster<'a>,
+    GrpKeyMgmtCluster,
+    LocalizationCluster<'a>,
+    TimeFormatLocalizationCluster<'a>,
+    UnitLocalizationCluster
 );
 
-pub const CLUSTERS: [Cluster<'static>; 10] = [
+/// The Network Diagnostics cluster of the root endpoint, which matches the interface
+/// of its Network Commissioning cluster
+pub trait NwDiagCluster {
+    const ID: u32;
+}
+
+impl<'a> NwDiagCluster for EthNwDiagCluster<'a> {
+    const ID: u32 = ethernet_nw_diagnostics::ID;
+}
+
+impl<'a> NwDiagCluster for WiFiNwDiagCluster<'a> {
+    const ID: u32 = wifi_nw_diagnostics::ID;
+}
+
+impl<'a> NwDiagCluster for ThreadNwDiagCluster<'a> {
+    const ID: u32 = thread_nw_diagnostics::ID;
+}
+
+pub const CLUSTERS: [Cluster<'static>; 15] = [
     descriptor::CLUSTER,
     cluster_basic_information::CLUSTER,
     general_commissioning::CLUSTER,
@@ -52,18 +87,154 @@ pub const CLUSTERS: [Cluster<'static>; 10] = [
     noc::CLUSTER,
     access_control::CLUSTER,
     general_diagnostics::CLUSTER,
+    sw_diagnostics::CLUSTER,
     ethernet_nw_diagnostics::CLUSTER,
+    time_sync::CLUSTER,
+    group_key_management::CLUSTER,
+    cluster_localization::CLUSTER,
+    cluster_time_format_localization::CLUSTER,
+    cluster_unit_localization::CLUSTER,
+];
+
+pub const WIFI_CLUSTERS: [Cluster<'static>; 15] = [
+    descriptor::CLUSTER,
+    cluster_basic_information::CLUSTER,
+    general_commissioning::CLUSTER,
+    nw_commissioning::WIFI_CLUSTER,
+    admin_commissioning::CLUSTER,
+    noc::CLUSTER,
+    access_control::CLUSTER,
+    general_diagnostics::CLUSTER,
+    sw_diagnostics::CLUSTER,
+    wifi_nw_diagnostics::CLUSTER,
+    time_sync::CLUSTER,
     group_key_management::CLUSTER,
+    cluster_localization::CLUSTER,
+    cluster_time_format_localization::CLUSTER,
+    cluster_unit_localization::CLUSTER,
+];
+
+pub const THREAD_CLUSTERS: [Cluster<'static>; 15] = [
+    descriptor::CLUSTER,
+    cluster_basic_information::CLUSTER,
+    general_commissioning::CLUSTER,
+    nw_commissioning::THREAD_CLUSTER,
+    admin_commissioning::CLUSTER,
+    noc::CLUSTER,
+    access_control::CLUSTER,
+    general_diagnostics::CLUSTER,
+    sw_diagnostics::CLUSTER,
+    thread_nw_diagnostics::CLUSTER,
+    time_sync::CLUSTER,
+    group_key_management::CLUSTER,
+    cluster_localization::CLUSTER,
+    cluster_time_format_localization::CLUSTER,
+    cluster_unit_localization::CLUSTER,
+];
+
+pub const WIFI_ICD_CLUSTERS: [Cluster<'static>; 16] = [
+    descriptor::CLUSTER,
+    cluster_basic_information::CLUSTER,
+    general_commissioning::CLUSTER,
+    nw_commissioning::WIFI_CLUSTER,
+    admin_commissioning::CLUSTER,
+    noc::CLUSTER,
+    access_control::CLUSTER,
+    general_diagnostics::CLUSTER,
+    sw_diagnostics::CLUSTER,
+    wifi_nw_diagnostics::CLUSTER,
+    time_sync::CLUSTER,
+    group_key_management::CLUSTER,
+    cluster_localization::CLUSTER,
+    cluster_time_format_localization::CLUSTER,
+    cluster_unit_localization::CLUSTER,
+    icd_management::CLUSTER,
+];
+
+pub const THREAD_ICD_CLUSTERS: [Cluster<'static>; 16] = [
+    descriptor::CLUSTER,
+    cluster_basic_information::CLUSTER,
+    general_commissioning::CLUSTER,
+    nw_commissioning::THREAD_CLUSTER,
+    admin_commissioning::CLUSTER,
+    noc::CLUSTER,
+    access_control::CLUSTER,
+    general_diagnostics::CLUSTER,
+    sw_diagnostics::CLUSTER,
+    thread_nw_diagnostics::CLUSTER,
+    time_sync::CLUSTER,
+    group_key_management::CLUSTER,
+    cluster_localization::CLUSTER,
+    cluster_time_format_localization::CLUSTER,
+    cluster_unit_localization::CLUSTER,
+    icd_management::CLUSTER,
 ];
 
 pub const fn endpoint(id: EndptId) -> Endpoint<'static> {
     Endpoint {
         id,
         device_type: super::device_types::DEV_TYPE_ROOT_NODE,
+        extra_device_types: &[],
         clusters: &CLUSTERS,
+        client_clusters: &[],
+    }
+}
+
+pub const fn wifi_endpoint(id: EndptId) -> Endpoint<'static> {
+    Endpoint {
+        id,
+        device_type: super::device_types::DEV_TYPE_ROOT_NODE,
+        extra_device_types: &[],
+        clusters: &WIFI_CLUSTERS,
+        client_clusters: &[],
+    }
+}
+
+pub const fn thread_endpoint(id: EndptId) -> Endpoint<'static> {
+    Endpoint {
+        id,
+        device_type: super::device_types::DEV_TYPE_ROOT_NODE,
+        extra_device_types: &[],
+        clusters: &THREAD_CLUSTERS,
+        client_clusters: &[],
+    }
+}
+
+/// The root endpoint of a Wi-Fi ICD, whose handler needs an `IcdMgmtCluster` chained to it
+pub const fn wifi_icd_endpoint(id: EndptId) -> Endpoint<'static> {
+    Endpoint {
+        id,
+        device_type: super::device_types::DEV_TYPE_ROOT_NODE,
+        extra_device_types: &[],
+        clusters: &WIFI_ICD_CLUSTERS,
+        client_clusters: &[],
     }
 }
 
+/// The root endpoint of a Thread ICD, whose handler needs an `IcdMgmtCluster` chained to it
+pub const fn thread_icd_endpoint(id: EndptId) -> Endpoint<'static> {
+    Endpoint {
+        id,
+        device_type: super::device_types::DEV_TYPE_ROOT_NODE,
+        extra_device_types: &[],
+        clusters: &THREAD_ICD_CLUSTERS,
+        client_clusters: &[],
+    }
+}
+
+/// The localization clusters of the root endpoint `handler`, with which the application
+/// reads back the locale, the time format and the units chosen by the users of the node
+#[allow(clippy::type_complexity)]
+pub fn localization<'r, 'a, N, D>(
+    handler: &'r RootEndpointHandler<'a, N, D>,
+) -> (
+    &'r LocalizationCluster<'a>,
+    &'r TimeFormatLocalizationCluster<'a>,
+    &'r UnitLocalizationCluster,
+) {
+    (handler.get(), handler.get(), handler.get())
+}
+
 pub fn handler<'a, T>(endpoint_id: u16, matter: &'a T) -> RootEndpointHandler<'a>
 where
     T: Borrow<BasicInfoConfig<'a>>
@@ -73,11 +244,60 @@ where
         + Borrow<RefCell<AclMgr>>
         + Borrow<RefCell<FailSafe>>
         + Borrow<dyn Mdns + 'a>
+        + Borrow<PacketCounters>
+        + Borrow<UtcClock>
         + Borrow<Epoch>
         + Borrow<Rand>
         + 'a,
 {
-    wrap(
+    let counters: &PacketCounters = matter.borrow();
+    let epoch: Epoch = *matter.borrow();
+    let rand: Rand = *matter.borrow();
+
+    handler_with(
+        endpoint_id,
+        matter,
+        NwCommCluster::new(rand),
+        GenDiagCluster::new(&NoDiagnostics, None, epoch, rand),
+        EthNwDiagCluster::new(counters, epoch, rand),
+        SwDiagCluster::new(&NoSwDiagnostics, rand),
+        &LocalizationConfig::DEFAULT,
+    )
+}
+
+/// Like `handler`, but with the given Network Commissioning cluster, with the given
+/// Network Diagnostics cluster of the same interface, with the given General and
+/// Software Diagnostics clusters, and with the given localization
+///
+/// The `WirelessNwCommCluster` of the `wifi_endpoint` or the `thread_endpoint` is an
+/// `AsyncHandler`, so it is chained in front of the `HandlerCompat` of this handler
+/// instead, where it takes over the requests to the Network Commissioning cluster. Its
+/// `run` method needs to be running as well, as it makes the connections requested by
+/// ConnectNetwork
+pub fn handler_with<'a, T, N, D>(
+    endpoint_id: u16,
+    matter: &'a T,
+    nw: N,
+    gen_diag: GenDiagCluster<'a>,
+    nw_diag: D,
+    sw_diag: SwDiagCluster<'a>,
+    localization: &'a LocalizationConfig<'a>,
+) -> RootEndpointHandler<'a, N, D>
+where
+    T: Borrow<BasicInfoConfig<'a>>
+        + Borrow<dyn DevAttDataFetcher + 'a>
+        + Borrow<RefCell<PaseMgr>>
+        + Borrow<RefCell<FabricMgr>>
+        + Borrow<RefCell<AclMgr>>
+        + Borrow<RefCell<FailSafe>>
+        + Borrow<dyn Mdns + 'a>
+        + Borrow<UtcClock>
+        + Borrow<Epoch>
+        + Borrow<Rand>
+        + 'a,
+    D: NwDiagCluster,
+{
+    wrap_with(
         endpoint_id,
         matter.borrow(),
         matter.borrow(),
@@ -86,8 +306,14 @@ where
         matter.borrow(),
         matter.borrow(),
         matter.borrow(),
+        matter.borrow(),
         *matter.borrow(),
         *matter.borrow(),
+        nw,
+        gen_diag,
+        nw_diag,
+        sw_diag,
+        localization,
     )
 }
 
@@ -101,25 +327,78 @@ pub fn wrap<'a>(
     acl: &'a RefCell<AclMgr>,
     failsafe: &'a RefCell<FailSafe>,
     mdns: &'a dyn Mdns,
+    counters: &'a PacketCounters,
+    utc: &'a UtcClock,
     epoch: Epoch,
     rand: Rand,
 ) -> RootEndpointHandler<'a> {
+    wrap_with(
+        endpoint_id,
+        basic_info,
+        dev_att,
+        pase,
+        fabric,
+        acl,
+        failsafe,
+        mdns,
+        utc,
+        epoch,
+        rand,
+        NwCommCluster::new(rand),
+        GenDiagCluster::new(&NoDiagnostics, None, epoch, rand),
+        EthNwDiagCluster::new(counters, epoch, rand),
+        SwDiagCluster::new(&NoSwDiagnostics, rand),
+        &LocalizationConfig::DEFAULT,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn wrap_with<'a, N, D>(
+    endpoint_id: u16,
+    basic_info: &'a BasicInfoConfig<'a>,
+    dev_att: &'a dyn DevAttDataFetcher,
+    pase: &'a RefCell<PaseMgr>,
+    fabric: &'a RefCell<FabricMgr>,
+    acl: &'a RefCell<AclMgr>,
+    failsafe: &'a RefCell<FailSafe>,
+    mdns: &'a dyn Mdns,
+    utc: &'a UtcClock,
+    epoch: Epoch,
+    rand: Rand,
+    nw: N,
+    gen_diag: GenDiagCluster<'a>,
+    nw_diag: D,
+    sw_diag: SwDiagCluster<'a>,
+    localization: &'a LocalizationConfig<'a>,
+) -> RootEndpointHandler<'a, N, D>
+where
+    D: NwDiagCluster,
+{
     EmptyHandler
         .chain(
             endpoint_id,
-            group_key_management::ID,
-            GrpKeyMgmtCluster::new(rand),
+            cluster_unit_localization::ID,
+            UnitLocalizationCluster::new(localization, rand),
         )
         .chain(
             endpoint_id,
-            ethernet_nw_diagnostics::ID,
-            EthNwDiagCluster::new(rand),
+            cluster_time_format_localization::ID,
+            TimeFormatLocalizationCluster::new(localization, rand),
         )
         .chain(
             endpoint_id,
-            general_diagnostics::ID,
-            GenDiagCluster::new(rand),
+            cluster_localization::ID,
+            LocalizationCluster::new(localization, rand),
+        )
+        .chain(
+            endpoint_id,
+            group_key_management::ID,
+            GrpKeyMgmtCluster::new(rand),
         )
+        .chain(endpoint_id, time_sync::ID, TimeSyncCluster::new(utc, rand))
+        .chain(endpoint_id, D::ID, nw_diag)
+        .chain(endpoint_id, sw_diagnostics::ID, sw_diag)
+        .chain(endpoint_id, general_diagnostics::ID, gen_diag)
         .chain(
             endpoint_id,
             access_control::ID,
@@ -128,18 +407,18 @@ pub fn wrap<'a>(
         .chain(
             endpoint_id,
             noc::ID,
-            NocCluster::new(dev_att, fabric, acl, failsafe, mdns, epoch, rand),
+            NocCluster::new(dev_att, fabric, acl, failsafe, mdns, utc, epoch, rand),
         )
         .chain(
             endpoint_id,
             admin_commissioning::ID,
-            AdminCommCluster::new(pase, mdns, rand),
+            AdminCommCluster::new(pase, fabric, failsafe, mdns, epoch, rand),
         )
-        .chain(endpoint_id, nw_commissioning::ID, NwCommCluster::new(rand))
+        .chain(endpoint_id, nw_commissioning::ID, nw)
         .chain(
             endpoint_id,
             general_commissioning::ID,
-            GenCommCluster::new(failsafe, rand),
+            GenCommCluster::new(failsafe, basic_info.location_capability, epoch, rand),
         )
         .chain(
             endpoint_id,
@@ -15,15 +15,19 @@
  *    limitations under the License.
  */
 
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::convert::TryInto;
+use core::time::Duration;
 
 use crate::data_model::objects::*;
+use crate::data_model::sdm::failsafe::FailSafe;
+use crate::fabric::FabricMgr;
 use crate::mdns::Mdns;
-use crate::secure_channel::pake::PaseMgr;
+use crate::secure_channel::pake::{CommWindow, PaseMgr};
 use crate::secure_channel::spake2p::VerifierData;
 use crate::tlv::{FromTLV, Nullable, OctetStr, TLVElement};
 use crate::transport::exchange::Exchange;
+use crate::utils::epoch::Epoch;
 use crate::utils::rand::Rand;
 use crate::{attribute_enum, cmd_enter};
 use crate::{command_enum, error::*};
@@ -33,6 +37,17 @@ use strum::{EnumDiscriminants, FromRepr};
 
 pub const ID: u32 = 0x003C;
 
+/// Bounds of the `CommissioningTimeout` of the commands opening a window, in seconds
+pub const MIN_COMM_TIMEOUT_SECS: u16 = 180;
+pub const MAX_COMM_TIMEOUT_SECS: u16 = 900;
+
+const PAKE_VERIFIER_LEN: usize = 97;
+const MIN_PAKE_ITERATIONS: u32 = 1000;
+const MAX_PAKE_ITERATIONS: u32 = 100000;
+const MIN_PAKE_SALT_LEN: usize = 16;
+const MAX_PAKE_SALT_LEN: usize = 32;
+const MAX_DISCRIMINATOR: u16 = 0xFFF;
+
 #[derive(FromPrimitive, Debug, Copy, Clone, PartialEq)]
 pub enum WindowStatus {
     WindowNotOpen = 0,
@@ -40,12 +55,21 @@ pub enum WindowStatus {
     BasicWindowOpen = 2,
 }
 
+/// The cluster-specific status codes the commands fail with
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u16)]
+pub enum StatusCode {
+    Busy = 2,
+    PAKEParameterError = 3,
+    WindowNotOpen = 4,
+}
+
 #[derive(Copy, Clone, Debug, FromRepr, EnumDiscriminants)]
 #[repr(u16)]
 pub enum Attributes {
     WindowStatus(AttrType<u8>) = 0,
     AdminFabricIndex(AttrType<Nullable<u8>>) = 1,
-    AdminVendorId(AttrType<Nullable<u8>>) = 2,
+    AdminVendorId(AttrType<Nullable<u16>>) = 2,
 }
 
 attribute_enum!(Attributes);
@@ -60,9 +84,15 @@ pub enum Commands {
 
 command_enum!(Commands);
 
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum Feature {
+    Basic = 0x0001,
+}
+
 pub const CLUSTER: Cluster<'static> = Cluster {
     id: ID as _,
-    feature_map: 0,
+    feature_map: Feature::Basic as u32,
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
@@ -84,46 +114,96 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     ],
     commands: &[
         Commands::OpenCommWindow as _,
-        // Commands::OpenBasicCommWindow as _,
-        // Commands::RevokeComm as _,
+        Commands::OpenBasicCommWindow as _,
+        Commands::RevokeComm as _,
     ],
 };
 
 #[derive(FromTLV)]
 #[tlvargs(lifetime = "'a")]
 pub struct OpenCommWindowReq<'a> {
-    _timeout: u16,
+    timeout: u16,
     verifier: OctetStr<'a>,
     discriminator: u16,
     iterations: u32,
     salt: OctetStr<'a>,
 }
 
+#[derive(FromTLV)]
+pub struct OpenBasicCommWindowReq {
+    timeout: u16,
+}
+
 pub struct AdminCommCluster<'a> {
     data_ver: Dataver,
     pase_mgr: &'a RefCell<PaseMgr>,
+    fabric_mgr: &'a RefCell<FabricMgr>,
+    failsafe: &'a RefCell<FailSafe>,
     mdns: &'a dyn Mdns,
+    epoch: Epoch,
+    window: Cell<Option<CommWindow>>,
 }
 
 impl<'a> AdminCommCluster<'a> {
-    pub fn new(pase_mgr: &'a RefCell<PaseMgr>, mdns: &'a dyn Mdns, rand: Rand) -> Self {
+    pub fn new(
+        pase_mgr: &'a RefCell<PaseMgr>,
+        fabric_mgr: &'a RefCell<FabricMgr>,
+        failsafe: &'a RefCell<FailSafe>,
+        mdns: &'a dyn Mdns,
+        epoch: Epoch,
+        rand: Rand,
+    ) -> Self {
         Self {
             data_ver: Dataver::new(rand),
             pase_mgr,
+            fabric_mgr,
+            failsafe,
             mdns,
+            epoch,
+            window: Cell::new(None),
         }
     }
 
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        let window = self.window();
+
         if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
             if attr.is_system() {
                 CLUSTER.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
-                    Attributes::WindowStatus(codec) => codec.encode(writer, 1),
-                    Attributes::AdminVendorId(codec) => codec.encode(writer, Nullable::NotNull(1)),
+                    Attributes::WindowStatus(codec) => {
+                        let status = match window {
+                            Some(window) if window.basic => WindowStatus::BasicWindowOpen,
+                            Some(_) => WindowStatus::EnhancedWindowOpen,
+                            None => WindowStatus::WindowNotOpen,
+                        };
+
+                        codec.encode(writer, status as _)
+                    }
+                    Attributes::AdminVendorId(codec) => codec.encode(
+                        writer,
+                        match window {
+                            Some(window) => Nullable::NotNull(window.vendor_id),
+                            None => Nullable::Null,
+                        },
+                    ),
                     Attributes::AdminFabricIndex(codec) => {
-                        codec.encode(writer, Nullable::NotNull(1))
+                        // Null as well if the fabric of the administrator was removed since
+                        let fab_idx = match window {
+                            Some(window)
+                                if self
+                                    .fabric_mgr
+                                    .borrow()
+                                    .get_fabric(window.fab_idx as usize)?
+                                    .is_some() =>
+                            {
+                                Nullable::NotNull(window.fab_idx)
+                            }
+                            _ => Nullable::Null,
+                        };
+
+                        codec.encode(writer, fab_idx)
                     }
                 }
             }
@@ -134,29 +214,142 @@ impl<'a> AdminCommCluster<'a> {
 
     pub fn invoke(
         &self,
+        exchange: &Exchange,
         cmd: &CmdDetails,
         data: &TLVElement,
-        _encoder: CmdDataEncoder,
+        encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
-        match cmd.cmd_id.try_into()? {
-            Commands::OpenCommWindow => self.handle_command_opencomm_win(data)?,
-            _ => Err(ErrorCode::CommandNotFound)?,
+        if !cmd.timed {
+            Err(ErrorCode::NeedsTimedInteraction)?;
         }
 
-        self.data_ver.changed();
+        let result = match cmd.cmd_id.try_into()? {
+            Commands::OpenCommWindow => self.handle_command_opencomm_win(exchange, data)?,
+            Commands::OpenBasicCommWindow => {
+                self.handle_command_open_basic_comm_win(exchange, data)?
+            }
+            Commands::RevokeComm => self.handle_command_revoke_comm()?,
+        };
+
+        self.window();
+
+        if let Err(status) = result {
+            info!("Command failed with {:?}", status);
+            encoder.cluster_status(status as _)?;
+        }
 
         Ok(())
     }
 
-    fn handle_command_opencomm_win(&self, data: &TLVElement) -> Result<(), Error> {
+    fn handle_command_opencomm_win(
+        &self,
+        exchange: &Exchange,
+        data: &TLVElement,
+    ) -> Result<Result<(), StatusCode>, Error> {
         cmd_enter!("Open Commissioning Window");
         let req = OpenCommWindowReq::from_tlv(data)?;
+
+        if req.discriminator > MAX_DISCRIMINATOR {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        if req.verifier.0.len() != PAKE_VERIFIER_LEN
+            || !(MIN_PAKE_ITERATIONS..=MAX_PAKE_ITERATIONS).contains(&req.iterations)
+            || !(MIN_PAKE_SALT_LEN..=MAX_PAKE_SALT_LEN).contains(&req.salt.0.len())
+        {
+            return Ok(Err(StatusCode::PAKEParameterError));
+        }
+
         let verifier = VerifierData::new(req.verifier.0, req.iterations, req.salt.0);
+
+        self.open_window(exchange, req.timeout, Some((verifier, req.discriminator)))
+    }
+
+    fn handle_command_open_basic_comm_win(
+        &self,
+        exchange: &Exchange,
+        data: &TLVElement,
+    ) -> Result<Result<(), StatusCode>, Error> {
+        cmd_enter!("Open Basic Commissioning Window");
+        let req = OpenBasicCommWindowReq::from_tlv(data)?;
+
+        self.open_window(exchange, req.timeout, None)
+    }
+
+    fn handle_command_revoke_comm(&self) -> Result<Result<(), StatusCode>, Error> {
+        cmd_enter!("Revoke Commissioning");
+
+        // Abort the commissioning in progress, if any
+        if self.failsafe.borrow().is_armed() {
+            self.failsafe.borrow_mut().expire_now();
+        }
+
+        if !self.pase_mgr.borrow().is_pase_session_enabled() {
+            return Ok(Err(StatusCode::WindowNotOpen));
+        }
+
+        self.pase_mgr.borrow_mut().disable_pase_session(self.mdns)?;
+
+        Ok(Ok(()))
+    }
+
+    /// Open a window for `timeout` seconds, on behalf of the administrator of the
+    /// exchange; without a verifier, the window is a basic one
+    fn open_window(
+        &self,
+        exchange: &Exchange,
+        timeout: u16,
+        verifier: Option<(VerifierData, u16)>,
+    ) -> Result<Result<(), StatusCode>, Error> {
+        if !(MIN_COMM_TIMEOUT_SECS..=MAX_COMM_TIMEOUT_SECS).contains(&timeout) {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        // A window is open already, or another commissioning is in progress
+        if self.pase_mgr.borrow().is_pase_session_enabled() || self.failsafe.borrow().is_armed() {
+            return Ok(Err(StatusCode::Busy));
+        }
+
+        let fab_idx = exchange.accessor()?.fab_idx;
+        let vendor_id = self
+            .fabric_mgr
+            .borrow()
+            .get_fabric(fab_idx as usize)?
+            .map(|fabric| fabric.get_vendor_id())
+            .ok_or(ErrorCode::UnsupportedAccess)?;
+
+        let window = CommWindow {
+            basic: verifier.is_none(),
+            fab_idx,
+            vendor_id,
+            expires_at: (self.epoch)() + Duration::from_secs(timeout as u64),
+        };
+
+        info!(
+            "Opening commissioning window for {}s on behalf of fabric {}",
+            timeout, fab_idx
+        );
+
         self.pase_mgr
             .borrow_mut()
-            .enable_pase_session(verifier, req.discriminator, self.mdns)?;
+            .open_comm_window(verifier, window, self.mdns)?;
 
-        Ok(())
+        Ok(Ok(()))
+    }
+
+    /// The window opened by an administrator, if still open
+    ///
+    /// The window also closes on its own, once it expires or a commissioner established
+    /// a PASE session, so the data version is bumped whenever a change is noticed.
+    fn window(&self) -> Option<CommWindow> {
+        let window = self.pase_mgr.borrow().comm_window().copied();
+
+        if self.window.get() != window {
+            self.window.set(window);
+            self.data_ver.changed();
+        }
+
+        window
     }
 }
 
@@ -167,12 +360,18 @@ impl<'a> Handler for AdminCommCluster<'a> {
 
     fn invoke(
         &self,
-        _exchange: &Exchange,
+        exchange: &Exchange,
         cmd: &CmdDetails,
         data: &TLVElement,
         encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
-        AdminCommCluster::invoke(self, cmd, data, encoder)
+        AdminCommCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        self.window();
+
+        Some(self.data_ver.get())
     }
 }
 
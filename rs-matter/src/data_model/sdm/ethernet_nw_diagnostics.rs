@@ -14,23 +14,37 @@
  *    See the License for the specific language governing permissions and
  *    limitations under the License.
  */
-
+use core::cell::Cell;
 use core::convert::TryInto;
+use core::time::Duration;
 
 use crate::{
-    attribute_enum, cmd_enter, command_enum, data_model::objects::AttrType, data_model::objects::*,
-    error::Error, tlv::TLVElement, transport::exchange::Exchange, utils::rand::Rand,
+    attribute_enum, cmd_enter, command_enum,
+    data_model::objects::AttrType,
+    data_model::objects::*,
+    error::Error,
+    tlv::{Nullable, TLVElement},
+    transport::{exchange::Exchange, network::PacketCounters},
+    utils::{epoch::Epoch, rand::Rand},
 };
 use log::info;
 use strum::{EnumDiscriminants, FromRepr};
 
 pub const ID: u32 = 0x0037;
 
+enum FeatureMap {
+    PacketCounts = 0x01,
+}
+
 #[derive(FromRepr, EnumDiscriminants)]
 #[repr(u16)]
 pub enum Attributes {
+    PHYRate(AttrType<Nullable<u8>>) = 0x00,
+    FullDuplex(AttrType<Nullable<bool>>) = 0x01,
     PacketRxCount(AttrType<u64>) = 0x02,
     PacketTxCount(AttrType<u64>) = 0x03,
+    CarrierDetect(AttrType<Nullable<bool>>) = 0x07,
+    TimeSinceReset(AttrType<u64>) = 0x08,
 }
 
 attribute_enum!(Attributes);
@@ -45,10 +59,20 @@ command_enum!(Commands);
 
 pub const CLUSTER: Cluster<'static> = Cluster {
     id: ID as _,
-    feature_map: 0,
+    feature_map: FeatureMap::PacketCounts as _,
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::PHYRate as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::FullDuplex as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
         Attribute::new(
             AttributesDiscriminants::PacketRxCount as u16,
             Access::RV,
@@ -57,31 +81,122 @@ pub const CLUSTER: Cluster<'static> = Cluster {
         Attribute::new(
             AttributesDiscriminants::PacketTxCount as u16,
             Access::RV,
-            Quality::FIXED,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CarrierDetect as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::TimeSinceReset as u16,
+            Access::RV,
+            Quality::NONE,
         ),
     ],
     commands: &[CommandsDiscriminants::ResetCounts as _],
 };
 
-pub struct EthNwDiagCluster {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PHYRate {
+    Rate10M = 0,
+    Rate100M = 1,
+    Rate1G = 2,
+    Rate2_5G = 3,
+    Rate5G = 4,
+    Rate10G = 5,
+    Rate40G = 6,
+    Rate100G = 7,
+    Rate200G = 8,
+    Rate400G = 9,
+}
+
+/// The platform-specific statistics of the Ethernet interface
+pub trait EthNwDiagnostics {
+    /// The number of packets received since the last reset
+    fn packet_rx_count(&self) -> u64;
+
+    /// The number of packets sent since the last reset
+    fn packet_tx_count(&self) -> u64;
+
+    /// Resets the packet counts, on a ResetCounts command
+    fn reset_counts(&self);
+
+    fn phy_rate(&self) -> Option<PHYRate> {
+        None
+    }
+
+    fn full_duplex(&self) -> Option<bool> {
+        None
+    }
+
+    fn carrier_detect(&self) -> Option<bool> {
+        None
+    }
+}
+
+/// Only counts the packets of the Matter transport, which is all that is known
+/// without the statistics of the interface
+impl EthNwDiagnostics for PacketCounters {
+    fn packet_rx_count(&self) -> u64 {
+        self.rx_count()
+    }
+
+    fn packet_tx_count(&self) -> u64 {
+        self.tx_count()
+    }
+
+    fn reset_counts(&self) {
+        self.reset()
+    }
+}
+
+pub struct EthNwDiagCluster<'a> {
     data_ver: Dataver,
+    platform: &'a dyn EthNwDiagnostics,
+    epoch: Epoch,
+    reset_at: Cell<Duration>,
+    served: Cell<(u64, u64, u64)>,
 }
 
-impl EthNwDiagCluster {
-    pub fn new(rand: Rand) -> Self {
+impl<'a> EthNwDiagCluster<'a> {
+    pub fn new(platform: &'a dyn EthNwDiagnostics, epoch: Epoch, rand: Rand) -> Self {
         Self {
             data_ver: Dataver::new(rand),
+            platform,
+            epoch,
+            reset_at: Cell::new(epoch()),
+            served: Cell::new((0, 0, 0)),
         }
     }
 
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        self.update_dataver();
+
         if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
             if attr.is_system() {
                 CLUSTER.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
-                    Attributes::PacketRxCount(codec) => codec.encode(writer, 1),
-                    Attributes::PacketTxCount(codec) => codec.encode(writer, 1),
+                    Attributes::PHYRate(codec) => codec.encode(
+                        writer,
+                        self.platform.phy_rate().map(|rate| rate as _).into(),
+                    ),
+                    Attributes::FullDuplex(codec) => {
+                        codec.encode(writer, self.platform.full_duplex().into())
+                    }
+                    Attributes::PacketRxCount(codec) => {
+                        codec.encode(writer, self.platform.packet_rx_count())
+                    }
+                    Attributes::PacketTxCount(codec) => {
+                        codec.encode(writer, self.platform.packet_tx_count())
+                    }
+                    Attributes::CarrierDetect(codec) => {
+                        codec.encode(writer, self.platform.carrier_detect().into())
+                    }
+                    Attributes::TimeSinceReset(codec) => {
+                        codec.encode(writer, self.time_since_reset())
+                    }
                 }
             }
         } else {
@@ -89,14 +204,6 @@ impl EthNwDiagCluster {
         }
     }
 
-    pub fn write(&self, _attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
-        let _data = data.with_dataver(self.data_ver.get())?;
-
-        self.data_ver.changed();
-
-        Ok(())
-    }
-
     pub fn invoke(
         &self,
         _exchange: &Exchange,
@@ -106,7 +213,10 @@ impl EthNwDiagCluster {
     ) -> Result<(), Error> {
         match cmd.cmd_id.try_into()? {
             Commands::ResetCounts => {
-                cmd_enter!("ResetCounts: Not yet supported");
+                cmd_enter!("ResetCounts");
+
+                self.platform.reset_counts();
+                self.reset_at.set((self.epoch)());
             }
         }
 
@@ -114,17 +224,32 @@ impl EthNwDiagCluster {
 
         Ok(())
     }
+
+    /// The counters change without the cluster knowing, so bump the dataver whenever
+    /// they differ from the ones last served, or the clients would keep their stale
+    /// values on a read with a DataVersionFilter
+    fn update_dataver(&self) {
+        let counters = (
+            self.platform.packet_rx_count(),
+            self.platform.packet_tx_count(),
+            self.time_since_reset(),
+        );
+
+        if self.served.replace(counters) != counters {
+            self.data_ver.changed();
+        }
+    }
+
+    fn time_since_reset(&self) -> u64 {
+        ((self.epoch)() - self.reset_at.get()).as_secs()
+    }
 }
 
-impl Handler for EthNwDiagCluster {
+impl<'a> Handler for EthNwDiagCluster<'a> {
     fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         EthNwDiagCluster::read(self, attr, encoder)
     }
 
-    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
-        EthNwDiagCluster::write(self, attr, data)
-    }
-
     fn invoke(
         &self,
         exchange: &Exchange,
@@ -134,13 +259,41 @@ impl Handler for EthNwDiagCluster {
     ) -> Result<(), Error> {
         EthNwDiagCluster::invoke(self, exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        self.update_dataver();
+
+        Some(self.data_ver.get())
+    }
 }
 
 // TODO: Might be removed once the `on` member is externalized
-impl NonBlockingHandler for EthNwDiagCluster {}
+impl<'a> NonBlockingHandler for EthNwDiagCluster<'a> {}
 
-impl ChangeNotifier<()> for EthNwDiagCluster {
+impl<'a> ChangeNotifier<()> for EthNwDiagCluster<'a> {
     fn consume_change(&mut self) -> Option<()> {
         self.data_ver.consume_change(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::network::PacketCounters;
+
+    use super::EthNwDiagnostics;
+
+    #[test]
+    fn test_packet_counters() {
+        let counters = PacketCounters::new();
+
+        counters.received();
+        counters.received();
+        counters.sent();
+        assert_eq!(counters.packet_rx_count(), 2);
+        assert_eq!(counters.packet_tx_count(), 1);
+
+        counters.reset_counts();
+        assert_eq!(counters.packet_rx_count(), 0);
+        assert_eq!(counters.packet_tx_count(), 0);
+    }
+}
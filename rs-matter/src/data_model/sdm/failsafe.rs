@@ -15,11 +15,13 @@
  *    limitations under the License.
  */
 
+use core::time::Duration;
+
 use crate::{
     error::{Error, ErrorCode},
     transport::session::SessionMode,
 };
-use log::error;
+use log::{error, info};
 
 #[derive(PartialEq)]
 #[allow(dead_code)]
@@ -34,7 +36,7 @@ enum NocState {
 #[derive(PartialEq)]
 pub struct ArmedCtx {
     session_mode: SessionMode,
-    timeout: u8,
+    expires_at: Duration,
     noc_state: NocState,
 }
 
@@ -46,35 +48,124 @@ pub enum State {
 
 pub struct FailSafe {
     state: State,
+    breadcrumb: u64,
+    breadcrumb_ver: u32,
+    generation: u32,
+    expired: bool,
 }
 
 impl FailSafe {
     #[inline(always)]
     pub const fn new() -> Self {
-        Self { state: State::Idle }
+        Self {
+            state: State::Idle,
+            breadcrumb: 0,
+            breadcrumb_ver: 0,
+            generation: 0,
+            expired: false,
+        }
     }
 
-    pub fn arm(&mut self, timeout: u8, session_mode: SessionMode) -> Result<(), Error> {
+    /// Arms the fail-safe for `timeout` seconds from `now`, or re-arms it if it is already
+    /// armed by the same session
+    ///
+    /// A zero `timeout` expires an armed fail-safe immediately. Returns `ErrorCode::Busy`
+    /// if the fail-safe is armed by another session.
+    pub fn arm(
+        &mut self,
+        timeout: u16,
+        session_mode: SessionMode,
+        now: Duration,
+    ) -> Result<(), Error> {
+        let expires_at = now + Duration::from_secs(timeout as _);
+
         match &mut self.state {
             State::Idle => {
-                self.state = State::Armed(ArmedCtx {
-                    session_mode,
-                    timeout,
-                    noc_state: NocState::NocNotRecvd,
-                })
+                if timeout > 0 {
+                    self.generation = self.generation.wrapping_add(1);
+                    self.state = State::Armed(ArmedCtx {
+                        session_mode,
+                        expires_at,
+                        noc_state: NocState::NocNotRecvd,
+                    })
+                }
             }
             State::Armed(c) => {
                 if c.session_mode != session_mode {
                     error!("Received Fail-Safe Arm with different session modes; current {:?}, incoming {:?}", c.session_mode, session_mode);
-                    Err(ErrorCode::Invalid)?;
+                    Err(ErrorCode::Busy)?;
+                }
+
+                if timeout == 0 {
+                    self.expire_now();
+                } else {
+                    // re-arm
+                    c.expires_at = expires_at;
                 }
-                // re-arm
-                c.timeout = timeout;
             }
         }
         Ok(())
     }
 
+    /// Expires the fail-safe if its timer has elapsed by `now`, returning `true` if it did
+    pub fn expire(&mut self, now: Duration) -> bool {
+        match &self.state {
+            State::Armed(c) if c.expires_at <= now => {
+                self.expire_now();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Expires the fail-safe right away, i.e. when an administrator revokes the
+    /// commissioning in progress
+    pub fn expire_now(&mut self) {
+        info!("Fail-Safe expired");
+
+        self.state = State::Idle;
+        self.expired = true;
+        self.set_breadcrumb(0);
+    }
+
+    /// Returns `true` once after the fail-safe expired, so that the transport can have the
+    /// data model revert the changes made while it was armed
+    pub fn take_expired(&mut self) -> bool {
+        core::mem::replace(&mut self.expired, false)
+    }
+
+    /// Incremented (wrapping) whenever the fail-safe is armed while it is not, so that the
+    /// clusters can tell which arming their changes were made under
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// When the fail-safe expires, if it is armed
+    pub fn expires_at(&self) -> Option<Duration> {
+        match &self.state {
+            State::Idle => None,
+            State::Armed(c) => Some(c.expires_at),
+        }
+    }
+
+    pub fn breadcrumb(&self) -> u64 {
+        self.breadcrumb
+    }
+
+    /// Incremented (wrapping) whenever the breadcrumb changes, including when it is
+    /// reset by the expiry or the disarming of the fail-safe, so that the General
+    /// Commissioning cluster can derive its data version from it
+    pub fn breadcrumb_ver(&self) -> u32 {
+        self.breadcrumb_ver
+    }
+
+    pub fn set_breadcrumb(&mut self, breadcrumb: u64) {
+        if self.breadcrumb != breadcrumb {
+            self.breadcrumb = breadcrumb;
+            self.breadcrumb_ver = self.breadcrumb_ver.wrapping_add(1);
+        }
+    }
+
     pub fn disarm(&mut self, session_mode: SessionMode) -> Result<(), Error> {
         match &mut self.state {
             State::Idle => {
@@ -99,6 +190,7 @@ impl FailSafe {
                     }
                 }
                 self.state = State::Idle;
+                self.set_breadcrumb(0);
             }
         }
         Ok(())
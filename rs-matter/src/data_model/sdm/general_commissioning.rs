@@ -15,14 +15,15 @@
  *    limitations under the License.
  */
 
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::convert::TryInto;
 
 use crate::data_model::objects::*;
+use crate::data_model::persist::StatePersist;
 use crate::data_model::sdm::failsafe::FailSafe;
-use crate::tlv::{FromTLV, TLVElement, TLVWriter, TagType, ToTLV, UtfStr};
+use crate::tlv::{FromTLV, TLVElement, TLVList, TLVWriter, TagType, ToTLV, UtfStr};
 use crate::transport::exchange::Exchange;
-use crate::utils::rand::Rand;
+use crate::utils::{epoch::Epoch, rand::Rand, writebuf::WriteBuf};
 use crate::{attribute_enum, cmd_enter};
 use crate::{command_enum, error::*};
 use log::info;
@@ -40,6 +41,9 @@ enum CommissioningError {
 
 pub const ID: u32 = 0x0030;
 
+/// The country code reported when none was configured
+pub const UNKNOWN_COUNTRY_CODE: [u8; 2] = *b"XX";
+
 #[derive(FromRepr, EnumDiscriminants)]
 #[repr(u16)]
 pub enum Attributes {
@@ -75,9 +79,12 @@ struct CommonResponse<'a> {
     debug_txt: UtfStr<'a>,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
 pub enum RegLocationType {
     Indoor = 0,
     Outdoor = 1,
+    #[default]
     IndoorOutdoor = 2,
 }
 
@@ -95,7 +102,7 @@ pub const CLUSTER: Cluster<'static> = Cluster {
         Attribute::new(
             AttributesDiscriminants::RegConfig as u16,
             Access::RV,
-            Quality::NONE,
+            Quality::N,
         ),
         Attribute::new(
             AttributesDiscriminants::LocationCapability as u16,
@@ -117,8 +124,16 @@ pub const CLUSTER: Cluster<'static> = Cluster {
 
 #[derive(FromTLV, ToTLV)]
 struct FailSafeParams {
-    expiry_len: u8,
-    bread_crumb: u8,
+    expiry_len: u16,
+    bread_crumb: u64,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+struct RegulatoryConfigParams<'a> {
+    new_reg_config: u8,
+    country_code: UtfStr<'a>,
+    bread_crumb: u64,
 }
 
 #[derive(ToTLV)]
@@ -131,10 +146,21 @@ pub struct GenCommCluster<'a> {
     data_ver: Dataver,
     basic_comm_info: BasicCommissioningInfo,
     failsafe: &'a RefCell<FailSafe>,
+    location_capability: RegLocationType,
+    reg_config: Cell<RegLocationType>,
+    country_code: Cell<[u8; 2]>,
+    // The country code is not an attribute, so it is persisted as state
+    changed: Cell<bool>,
+    epoch: Epoch,
 }
 
 impl<'a> GenCommCluster<'a> {
-    pub fn new(failsafe: &'a RefCell<FailSafe>, rand: Rand) -> Self {
+    pub fn new(
+        failsafe: &'a RefCell<FailSafe>,
+        location_capability: RegLocationType,
+        epoch: Epoch,
+        rand: Rand,
+    ) -> Self {
         Self {
             data_ver: Dataver::new(rand),
             failsafe,
@@ -143,6 +169,11 @@ impl<'a> GenCommCluster<'a> {
                 expiry_len: 120,
                 max_cmltv_failsafe_secs: 120,
             },
+            location_capability,
+            reg_config: Cell::new(location_capability),
+            country_code: Cell::new(UNKNOWN_COUNTRY_CODE),
+            changed: Cell::new(false),
+            epoch,
         }
     }
 
@@ -150,20 +181,64 @@ impl<'a> GenCommCluster<'a> {
         self.failsafe
     }
 
+    pub fn regulatory_config(&self) -> RegLocationType {
+        self.reg_config.get()
+    }
+
+    /// The ISO 3166-1 alpha-2 code of the country set by the last SetRegulatoryConfig
+    pub fn country_code(&self) -> [u8; 2] {
+        self.country_code.get()
+    }
+
+    /// Validates and applies a new regulatory config
+    ///
+    /// A location the device is not capable of is reported as `ErrorCode::InvalidAction`.
+    fn set_regulatory_config(&self, reg_config: u8, country_code: &[u8]) -> Result<(), Error> {
+        let reg_config =
+            RegLocationType::from_repr(reg_config).ok_or(ErrorCode::ConstraintError)?;
+
+        let country_code: [u8; 2] = country_code
+            .try_into()
+            .map_err(|_| ErrorCode::ConstraintError)?;
+
+        if self.location_capability != RegLocationType::IndoorOutdoor
+            && reg_config != self.location_capability
+        {
+            Err(ErrorCode::InvalidAction)?;
+        }
+
+        self.reg_config.set(reg_config);
+
+        if self.country_code.get() != country_code {
+            self.country_code.set(country_code);
+            self.changed.set(true);
+        }
+
+        Ok(())
+    }
+
+    /// The data version of the cluster, which also changes with the breadcrumb, as the
+    /// breadcrumb lives in the fail-safe and is reset there, i.e. on its expiry
+    fn dataver(&self) -> u32 {
+        self.data_ver
+            .get()
+            .wrapping_add(self.failsafe.borrow().breadcrumb_ver())
+    }
+
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
-        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+        if let Some(mut writer) = encoder.with_dataver(self.dataver())? {
             if attr.is_system() {
                 CLUSTER.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
-                    Attributes::BreadCrumb(codec) => codec.encode(writer, 0),
-                    // TODO: Arch-Specific
+                    Attributes::BreadCrumb(codec) => {
+                        codec.encode(writer, self.failsafe.borrow().breadcrumb())
+                    }
                     Attributes::RegConfig(codec) => {
-                        codec.encode(writer, RegLocationType::IndoorOutdoor as _)
+                        codec.encode(writer, self.reg_config.get() as _)
                     }
-                    // TODO: Arch-Specific
                     Attributes::LocationCapability(codec) => {
-                        codec.encode(writer, RegLocationType::IndoorOutdoor as _)
+                        codec.encode(writer, self.location_capability as _)
                     }
                     Attributes::BasicCommissioningInfo(_) => {
                         self.basic_comm_info
@@ -177,6 +252,33 @@ impl<'a> GenCommCluster<'a> {
         }
     }
 
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.dataver())?;
+
+        match attr.attr_id.try_into()? {
+            // The data version follows the breadcrumb in the fail-safe
+            Attributes::BreadCrumb(codec) => {
+                self.failsafe
+                    .borrow_mut()
+                    .set_breadcrumb(codec.decode(data)?);
+
+                return Ok(());
+            }
+            // Only writable internally, when restoring the persisted config
+            Attributes::RegConfig(codec) => {
+                let reg_config = RegLocationType::from_repr(codec.decode(data)?)
+                    .ok_or(ErrorCode::ConstraintError)?;
+
+                self.reg_config.set(reg_config);
+            }
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
     pub fn invoke(
         &self,
         exchange: &Exchange,
@@ -209,18 +311,21 @@ impl<'a> GenCommCluster<'a> {
 
         let p = FailSafeParams::from_tlv(data)?;
 
-        let status = if self
-            .failsafe
-            .borrow_mut()
-            .arm(
-                p.expiry_len,
-                exchange.with_session(|sess| Ok(sess.get_session_mode().clone()))?,
-            )
-            .is_err()
-        {
-            CommissioningError::ErrBusyWithOtherAdmin as u8
-        } else {
-            CommissioningError::Ok as u8
+        let mut failsafe = self.failsafe.borrow_mut();
+
+        let status = match failsafe.arm(
+            p.expiry_len,
+            exchange.with_session(|sess| Ok(sess.get_session_mode().clone()))?,
+            (self.epoch)(),
+        ) {
+            Ok(()) => {
+                failsafe.set_breadcrumb(p.bread_crumb);
+                CommissioningError::Ok as u8
+            }
+            Err(e) if e.code() == ErrorCode::Busy => {
+                CommissioningError::ErrBusyWithOtherAdmin as u8
+            }
+            Err(e) => Err(e)?,
         };
 
         let cmd_data = CommonResponse {
@@ -242,15 +347,27 @@ impl<'a> GenCommCluster<'a> {
         encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
         cmd_enter!("Set Regulatory Config");
-        let country_code = data
-            .find_tag(1)
-            .map_err(|_| ErrorCode::InvalidCommand)?
-            .slice()
-            .map_err(|_| ErrorCode::InvalidCommand)?;
-        info!("Received country code: {:?}", country_code);
+
+        let p = RegulatoryConfigParams::from_tlv(data)?;
+        info!(
+            "Received regulatory config {} for country code: {}",
+            p.new_reg_config,
+            p.country_code.as_str().unwrap_or_default()
+        );
+
+        let status = match self.set_regulatory_config(p.new_reg_config, p.country_code.0) {
+            Ok(()) => {
+                self.failsafe.borrow_mut().set_breadcrumb(p.bread_crumb);
+                CommissioningError::Ok as u8
+            }
+            Err(e) if e.code() == ErrorCode::InvalidAction => {
+                CommissioningError::ErrValueOutsideRange as u8
+            }
+            Err(e) => Err(e)?,
+        };
 
         let cmd_data = CommonResponse {
-            error_code: 0,
+            error_code: status,
             debug_txt: UtfStr::new(b""),
         };
 
@@ -277,14 +394,16 @@ impl<'a> GenCommCluster<'a> {
             status = CommissioningError::ErrInvalidAuth as u8;
         }
 
-        // AddNOC or UpdateNOC must have happened, and that too for the same fabric
-        // scope that is for this session
-        if self
+        if !self.failsafe.borrow().is_armed() {
+            status = CommissioningError::ErrNotCommissioning as u8;
+        } else if self
             .failsafe
             .borrow_mut()
             .disarm(exchange.with_session(|sess| Ok(sess.get_session_mode().clone()))?)
             .is_err()
         {
+            // AddNOC or UpdateNOC must have happened, and that too for the same fabric
+            // scope that is for this session
             status = CommissioningError::ErrInvalidAuth as u8;
         }
 
@@ -306,6 +425,10 @@ impl<'a> Handler for GenCommCluster<'a> {
         GenCommCluster::read(self, attr, encoder)
     }
 
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        GenCommCluster::write(self, attr, data)
+    }
+
     fn invoke(
         &self,
         exchange: &Exchange,
@@ -315,10 +438,46 @@ impl<'a> Handler for GenCommCluster<'a> {
     ) -> Result<(), Error> {
         GenCommCluster::invoke(self, exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(GenCommCluster::dataver(self))
+    }
 }
 
 impl<'a> NonBlockingHandler for GenCommCluster<'a> {}
 
+/// Persists the country code of the regulatory config, while the location type is
+/// persisted with the `RegConfig` attribute
+impl<'a> StatePersist for GenCommCluster<'a> {
+    fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        let country_code: [u8; 2] = root.slice()?.try_into().map_err(|_| ErrorCode::Invalid)?;
+
+        self.country_code.set(country_code);
+        self.changed.set(false);
+
+        Ok(())
+    }
+
+    fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        if self.changed.get() {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            tw.str8(TagType::Anonymous, &self.country_code.get())?;
+
+            self.changed.set(false);
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
 impl<'a> ChangeNotifier<()> for GenCommCluster<'a> {
     fn consume_change(&mut self) -> Option<()> {
         self.data_ver.consume_change(())
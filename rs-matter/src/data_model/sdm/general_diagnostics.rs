@@ -16,26 +16,35 @@
  */
 
 use core::convert::TryInto;
+use core::time::Duration;
 
 use crate::{
     attribute_enum, cmd_enter, command_enum,
     data_model::objects::AttrType,
     data_model::objects::*,
     error::{Error, ErrorCode},
-    tlv::TLVElement,
+    tlv::{FromTLV, OctetStr, TLVElement, TLVWriter, TagType, ToTLV},
     transport::exchange::Exchange,
-    utils::rand::Rand,
+    utils::{epoch::Epoch, rand::Rand},
 };
 use log::info;
 use strum::{EnumDiscriminants, FromRepr};
 
 pub const ID: u32 = 0x0033;
 
+/// The length of the enable key of the TestEventTrigger command
+pub const TEST_ENABLE_KEY_LEN: usize = 16;
+
 #[derive(FromRepr, EnumDiscriminants)]
 #[repr(u16)]
 pub enum Attributes {
     NetworkInterfaces(()) = 0x00,
     RebootCount(AttrType<u16>) = 0x01,
+    UpTime(AttrType<u64>) = 0x02,
+    BootReason(AttrType<u8>) = 0x04,
+    ActiveHardwareFaults(()) = 0x05,
+    ActiveRadioFaults(()) = 0x06,
+    ActiveNetworkFaults(()) = 0x07,
     TestEventTriggersEnabled(AttrType<bool>) = 0x08,
 }
 
@@ -49,6 +58,15 @@ pub enum Commands {
 
 command_enum!(Commands);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Events {
+    HardwareFaultChange = 0x00,
+    RadioFaultChange = 0x01,
+    NetworkFaultChange = 0x02,
+    BootReason = 0x03,
+}
+
 pub const CLUSTER: Cluster<'static> = Cluster {
     id: ID as _,
     feature_map: 0,
@@ -60,10 +78,36 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             Access::RV,
             Quality::NONE,
         ),
+        // Persisted by the `DiagnosticsPlatform`, as it changes on every boot
         Attribute::new(
             AttributesDiscriminants::RebootCount as u16,
             Access::RV,
-            Quality::PERSISTENT,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::UpTime as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::BootReason as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveHardwareFaults as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveRadioFaults as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveNetworkFaults as u16,
+            Access::RV,
+            Quality::NONE,
         ),
         Attribute::new(
             AttributesDiscriminants::TestEventTriggersEnabled as u16,
@@ -74,25 +118,283 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     commands: &[CommandsDiscriminants::TestEventTrigger as _],
 };
 
-pub struct GenDiagCluster {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterfaceType {
+    Unspecified = 0,
+    WiFi = 1,
+    Ethernet = 2,
+    Cellular = 3,
+    Thread = 4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootReason {
+    Unspecified = 0,
+    PowerOnReboot = 1,
+    BrownOutReset = 2,
+    SoftwareWatchdogReset = 3,
+    HardwareWatchdogReset = 4,
+    SoftwareUpdateCompleted = 5,
+    SoftwareReset = 6,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HardwareFault {
+    Unspecified = 0,
+    Radio = 1,
+    Sensor = 2,
+    ResettableOverTemp = 3,
+    NonResettableOverTemp = 4,
+    PowerSource = 5,
+    VisualDisplayFault = 6,
+    AudioOutputFault = 7,
+    UserInterfaceFault = 8,
+    NonVolatileMemoryError = 9,
+    TamperDetected = 10,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadioFault {
+    Unspecified = 0,
+    WiFiFault = 1,
+    CellularFault = 2,
+    ThreadFault = 3,
+    NFCFault = 4,
+    BLEFault = 5,
+    EthernetFault = 6,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkFault {
+    Unspecified = 0,
+    HardwareFailure = 1,
+    NetworkJammed = 2,
+    ConnectionFailed = 3,
+}
+
+/// An entry of the NetworkInterfaces attribute
+pub struct NetworkInterface<'a> {
+    pub name: &'a str,
+    pub is_operational: bool,
+    pub hw_address: &'a [u8],
+    pub ipv4_addresses: &'a [[u8; 4]],
+    pub ipv6_addresses: &'a [[u8; 16]],
+    pub interface_type: InterfaceType,
+}
+
+impl<'a> ToTLV for NetworkInterface<'a> {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        tw.utf8(TagType::Context(0), self.name.as_bytes())?;
+        tw.bool(TagType::Context(1), self.is_operational)?;
+        // Whether off-premise services are reachable is unknown
+        tw.null(TagType::Context(2))?;
+        tw.null(TagType::Context(3))?;
+        tw.str8(TagType::Context(4), self.hw_address)?;
+
+        tw.start_array(TagType::Context(5))?;
+        for address in self.ipv4_addresses {
+            tw.str8(TagType::Anonymous, address)?;
+        }
+        tw.end_container()?;
+
+        tw.start_array(TagType::Context(6))?;
+        for address in self.ipv6_addresses {
+            tw.str8(TagType::Anonymous, address)?;
+        }
+        tw.end_container()?;
+
+        tw.u8(TagType::Context(7), self.interface_type as _)?;
+        tw.end_container()
+    }
+}
+
+/// The platform-specific diagnostics of the node
+pub trait DiagnosticsPlatform {
+    /// Calls `f` for each network interface of the node
+    fn network_interfaces(
+        &self,
+        f: &mut dyn FnMut(&NetworkInterface) -> Result<(), Error>,
+    ) -> Result<(), Error>;
+
+    /// The number of times the node has rebooted, which the platform keeps persisted
+    fn reboot_count(&self) -> u16;
+
+    /// The seconds since the node booted
+    ///
+    /// If not provided, the seconds since the cluster was created are reported instead.
+    fn uptime(&self) -> Option<u64> {
+        None
+    }
+
+    fn boot_reason(&self) -> BootReason {
+        BootReason::Unspecified
+    }
+
+    fn active_hardware_faults(
+        &self,
+        _f: &mut dyn FnMut(HardwareFault) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn active_radio_faults(
+        &self,
+        _f: &mut dyn FnMut(RadioFault) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn active_network_faults(
+        &self,
+        _f: &mut dyn FnMut(NetworkFault) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// A platform without network interfaces or faults, which never reboots
+pub struct NoDiagnostics;
+
+impl DiagnosticsPlatform for NoDiagnostics {
+    fn network_interfaces(
+        &self,
+        _f: &mut dyn FnMut(&NetworkInterface) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn reboot_count(&self) -> u16 {
+        0
+    }
+}
+
+/// Handles the test event triggers of a specific product feature, e.g. the triggers
+/// used by the certification tests of a cluster
+pub trait TestEventTriggerHandler {
+    /// Handles the trigger, returning `false` if it is not one of this handler
+    fn handle(&self, trigger: u64) -> Result<bool, Error>;
+}
+
+/// The enable key of the TestEventTrigger command, and the handlers it dispatches to
+pub struct TestEventTriggers<'a> {
+    enable_key: [u8; TEST_ENABLE_KEY_LEN],
+    handlers: &'a [&'a dyn TestEventTriggerHandler],
+}
+
+impl<'a> TestEventTriggers<'a> {
+    pub const fn new(
+        enable_key: [u8; TEST_ENABLE_KEY_LEN],
+        handlers: &'a [&'a dyn TestEventTriggerHandler],
+    ) -> Self {
+        Self {
+            enable_key,
+            handlers,
+        }
+    }
+
+    /// An all-zeroes key disables the test event triggers
+    pub fn is_enabled(&self) -> bool {
+        self.enable_key.iter().any(|byte| *byte != 0)
+    }
+
+    pub fn trigger(&self, enable_key: &[u8], trigger: u64) -> Result<(), Error> {
+        if !self.is_enabled() || enable_key != self.enable_key {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        for handler in self.handlers {
+            if handler.handle(trigger)? {
+                return Ok(());
+            }
+        }
+
+        Err(ErrorCode::InvalidCommand.into())
+    }
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct TestEventTriggerReq<'a> {
+    enable_key: OctetStr<'a>,
+    event_trigger: u64,
+}
+
+pub struct GenDiagCluster<'a> {
     data_ver: Dataver,
+    platform: &'a dyn DiagnosticsPlatform,
+    test_event_triggers: Option<&'a TestEventTriggers<'a>>,
+    epoch: Epoch,
+    started: Duration,
 }
 
-impl GenDiagCluster {
-    pub fn new(rand: Rand) -> Self {
+impl<'a> GenDiagCluster<'a> {
+    pub fn new(
+        platform: &'a dyn DiagnosticsPlatform,
+        test_event_triggers: Option<&'a TestEventTriggers<'a>>,
+        epoch: Epoch,
+        rand: Rand,
+    ) -> Self {
         Self {
             data_ver: Dataver::new(rand),
+            platform,
+            test_event_triggers,
+            epoch,
+            started: epoch(),
         }
     }
 
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
-        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
             if attr.is_system() {
                 CLUSTER.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
-                    Attributes::RebootCount(codec) => codec.encode(writer, 1),
-                    _ => Err(ErrorCode::AttributeNotFound.into()),
+                    Attributes::NetworkInterfaces(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        self.platform.network_interfaces(&mut |interface| {
+                            interface.to_tlv(&mut writer, TagType::Anonymous)
+                        })?;
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::RebootCount(codec) => {
+                        codec.encode(writer, self.platform.reboot_count())
+                    }
+                    Attributes::UpTime(codec) => codec.encode(writer, self.uptime()),
+                    Attributes::BootReason(codec) => {
+                        codec.encode(writer, self.platform.boot_reason() as _)
+                    }
+                    Attributes::ActiveHardwareFaults(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        self.platform.active_hardware_faults(&mut |fault| {
+                            writer.u8(TagType::Anonymous, fault as _)
+                        })?;
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::ActiveRadioFaults(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        self.platform.active_radio_faults(&mut |fault| {
+                            writer.u8(TagType::Anonymous, fault as _)
+                        })?;
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::ActiveNetworkFaults(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        self.platform.active_network_faults(&mut |fault| {
+                            writer.u8(TagType::Anonymous, fault as _)
+                        })?;
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::TestEventTriggersEnabled(codec) => codec.encode(
+                        writer,
+                        self.test_event_triggers
+                            .map(TestEventTriggers::is_enabled)
+                            .unwrap_or(false),
+                    ),
                 }
             }
         } else {
@@ -100,42 +402,41 @@ impl GenDiagCluster {
         }
     }
 
-    pub fn write(&self, _attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
-        let _data = data.with_dataver(self.data_ver.get())?;
-
-        self.data_ver.changed();
-
-        Ok(())
-    }
-
     pub fn invoke(
         &self,
         _exchange: &Exchange,
         cmd: &CmdDetails,
-        _data: &TLVElement,
+        data: &TLVElement,
         _encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
         match cmd.cmd_id.try_into()? {
             Commands::TestEventTrigger => {
-                cmd_enter!("TestEventTrigger: Not yet supported");
+                cmd_enter!("TestEventTrigger");
+
+                let req = TestEventTriggerReq::from_tlv(data)?;
+                info!("Received test event trigger {:#x}", req.event_trigger);
+
+                self.test_event_triggers
+                    .ok_or(ErrorCode::ConstraintError)?
+                    .trigger(req.enable_key.0, req.event_trigger)?;
             }
         }
 
-        self.data_ver.changed();
-
         Ok(())
     }
+
+    fn uptime(&self) -> u64 {
+        self.platform
+            .uptime()
+            .unwrap_or_else(|| ((self.epoch)() - self.started).as_secs())
+    }
 }
 
-impl Handler for GenDiagCluster {
+impl<'a> Handler for GenDiagCluster<'a> {
     fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         GenDiagCluster::read(self, attr, encoder)
     }
 
-    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
-        GenDiagCluster::write(self, attr, data)
-    }
-
     fn invoke(
         &self,
         exchange: &Exchange,
@@ -145,13 +446,145 @@ impl Handler for GenDiagCluster {
     ) -> Result<(), Error> {
         GenDiagCluster::invoke(self, exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 // TODO: Might be removed once the `on` member is externalized
-impl NonBlockingHandler for GenDiagCluster {}
+impl<'a> NonBlockingHandler for GenDiagCluster<'a> {}
 
-impl ChangeNotifier<()> for GenDiagCluster {
+impl<'a> ChangeNotifier<()> for GenDiagCluster<'a> {
     fn consume_change(&mut self) -> Option<()> {
         self.data_ver.consume_change(())
     }
 }
+
+#[cfg(all(feature = "nix", not(target_os = "espidf")))]
+pub mod sys {
+    use std::fs;
+    use std::net::Ipv4Addr;
+    use std::path::Path;
+    use std::time::Instant;
+
+    use nix::ifaddrs::{getifaddrs, InterfaceAddress};
+    use nix::net::if_::InterfaceFlags;
+
+    use crate::error::{Error, ErrorCode};
+
+    use super::{BootReason, DiagnosticsPlatform, InterfaceType, NetworkInterface};
+
+    /// The diagnostics of a host with the interfaces reported by `getifaddrs`
+    pub struct SysDiagnostics {
+        reboot_count: u16,
+        boot_reason: BootReason,
+        started: Instant,
+    }
+
+    impl SysDiagnostics {
+        /// Counts this boot in the reboot count kept in `reboot_count_file`
+        pub fn new(boot_reason: BootReason, reboot_count_file: &Path) -> Result<Self, Error> {
+            let reboot_count = match fs::read_to_string(reboot_count_file) {
+                Ok(count) => count
+                    .trim()
+                    .parse::<u16>()
+                    .map_err(|_| ErrorCode::InvalidData)?
+                    .saturating_add(1),
+                Err(_) => 0,
+            };
+
+            fs::write(reboot_count_file, reboot_count.to_string())?;
+
+            Ok(Self {
+                reboot_count,
+                boot_reason,
+                started: Instant::now(),
+            })
+        }
+    }
+
+    impl DiagnosticsPlatform for SysDiagnostics {
+        fn network_interfaces(
+            &self,
+            f: &mut dyn FnMut(&NetworkInterface) -> Result<(), Error>,
+        ) -> Result<(), Error> {
+            let addrs: Vec<InterfaceAddress> = getifaddrs()
+                .map_err(|_| ErrorCode::NoNetworkInterface)?
+                .collect();
+
+            let mut names: Vec<&str> = Vec::new();
+            for addr in &addrs {
+                if !names.contains(&addr.interface_name.as_str()) {
+                    names.push(&addr.interface_name);
+                }
+            }
+
+            for name in names {
+                let mut flags = InterfaceFlags::empty();
+                let mut hw_address = None;
+                let mut ipv4_addresses = Vec::new();
+                let mut ipv6_addresses = Vec::new();
+
+                for addr in addrs.iter().filter(|addr| addr.interface_name == name) {
+                    flags |= addr.flags;
+
+                    if let Some(address) = &addr.address {
+                        if let Some(link) = address.as_link_addr() {
+                            hw_address = hw_address.or(link.addr());
+                        } else if let Some(ipv4) = address.as_sockaddr_in() {
+                            ipv4_addresses.push(Ipv4Addr::from(ipv4.ip()).octets());
+                        } else if let Some(ipv6) = address.as_sockaddr_in6() {
+                            ipv6_addresses.push(ipv6.ip().octets());
+                        }
+                    }
+                }
+
+                if flags.contains(InterfaceFlags::IFF_LOOPBACK) {
+                    continue;
+                }
+
+                let hw_address = hw_address.unwrap_or_default();
+
+                f(&NetworkInterface {
+                    name,
+                    is_operational: flags
+                        .contains(InterfaceFlags::IFF_UP | InterfaceFlags::IFF_RUNNING),
+                    hw_address: &hw_address,
+                    ipv4_addresses: &ipv4_addresses,
+                    ipv6_addresses: &ipv6_addresses,
+                    interface_type: interface_type(name, &hw_address),
+                })?;
+            }
+
+            Ok(())
+        }
+
+        fn reboot_count(&self) -> u16 {
+            self.reboot_count
+        }
+
+        fn uptime(&self) -> Option<u64> {
+            Some(self.started.elapsed().as_secs())
+        }
+
+        fn boot_reason(&self) -> BootReason {
+            self.boot_reason
+        }
+    }
+
+    // Guessed from the usual interface names, as `getifaddrs` does not report the type
+    fn interface_type(name: &str, hw_address: &[u8]) -> InterfaceType {
+        if name.starts_with("wl") {
+            InterfaceType::WiFi
+        } else if name.starts_with("wpan") {
+            InterfaceType::Thread
+        } else if name.starts_with("ww") {
+            InterfaceType::Cellular
+        } else if hw_address.iter().any(|byte| *byte != 0) {
+            InterfaceType::Ethernet
+        } else {
+            InterfaceType::Unspecified
+        }
+    }
+}
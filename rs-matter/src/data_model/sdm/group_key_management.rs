@@ -152,6 +152,10 @@ impl Handler for GrpKeyMgmtCluster {
     ) -> Result<(), Error> {
         GrpKeyMgmtCluster::invoke(self, exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 // TODO: Might be removed once the `on` member is externalized
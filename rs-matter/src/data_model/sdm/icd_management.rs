@@ -0,0 +1,747 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+use core::cell::{Cell, RefCell};
+use core::convert::TryInto;
+
+use crate::{
+    acl::AccessReq,
+    attribute_enum, cmd_enter, command_enum,
+    data_model::objects::AttrType,
+    data_model::objects::*,
+    data_model::persist::StatePersist,
+    error::{Error, ErrorCode},
+    fabric,
+    icd::{IcdConfig, IcdManager, OperatingMode},
+    interaction_model::client::NodeResolver,
+    secure_channel::check_in::{prepare_check_in, CHECK_IN_KEY_LEN},
+    tlv::{FromTLV, OctetStr, TLVArray, TLVElement, TLVList, TLVWriter, TagType, ToTLV},
+    transport::{exchange::Exchange, packet::Packet},
+    utils::{epoch::Epoch, rand::Rand, select::Notification, writebuf::WriteBuf},
+    Matter,
+};
+use embassy_futures::select::select;
+use embassy_time::{Duration, Timer};
+use log::{info, warn};
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0046;
+
+pub const CLIENTS_PER_FABRIC: usize = 2;
+
+const MAX_CLIENTS: usize = CLIENTS_PER_FABRIC * fabric::MAX_SUPPORTED_FABRICS;
+
+enum FeatureMap {
+    CheckInProtocolSupport = 0x01,
+}
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    IdleModeDuration(AttrType<u32>) = 0x00,
+    ActiveModeDuration(AttrType<u32>) = 0x01,
+    ActiveModeThreshold(AttrType<u16>) = 0x02,
+    RegisteredClients(()) = 0x03,
+    IcdCounter(AttrType<u32>) = 0x04,
+    ClientsSupportedPerFabric(AttrType<u16>) = 0x05,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    RegisterClient = 0x00,
+    UnregisterClient = 0x02,
+    StayActiveRequest = 0x03,
+}
+
+command_enum!(Commands);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum RespCommands {
+    RegisterClientResponse = 0x01,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::CheckInProtocolSupport as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::IdleModeDuration as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveModeDuration as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveModeThreshold as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::RegisteredClients as u16,
+            Access::RA.union(Access::FAB_SCOPED),
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::IcdCounter as u16,
+            Access::RA,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ClientsSupportedPerFabric as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::RegisterClient as _,
+        CommandsDiscriminants::UnregisterClient as _,
+        CommandsDiscriminants::StayActiveRequest as _,
+    ],
+};
+
+/// An entry of the RegisteredClients attribute
+///
+/// The key of a registration is never reported.
+#[derive(ToTLV, FromTLV, Debug, Clone)]
+#[tlvargs(start = 1)]
+pub struct MonitoringRegistration {
+    pub check_in_node_id: u64,
+    pub monitored_subject: u64,
+    #[tagval(0xFE)]
+    pub fab_idx: u8,
+}
+
+// A registered client, as persisted along with its key
+#[derive(ToTLV, FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct StoredClient<'a> {
+    fab_idx: u8,
+    check_in_node_id: u64,
+    monitored_subject: u64,
+    key: OctetStr<'a>,
+}
+
+/// A client registered for the Check-In messages of the ICD
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcdClient {
+    pub fab_idx: u8,
+    pub check_in_node_id: u64,
+    pub monitored_subject: u64,
+    key: [u8; CHECK_IN_KEY_LEN],
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct RegisterClientReq<'a> {
+    check_in_node_id: u64,
+    monitored_subject: u64,
+    key: OctetStr<'a>,
+    verification_key: Option<OctetStr<'a>>,
+}
+
+#[derive(ToTLV)]
+struct RegisterClientResp {
+    icd_counter: u32,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct UnregisterClientReq<'a> {
+    check_in_node_id: u64,
+    verification_key: Option<OctetStr<'a>>,
+}
+
+/// The ICD Management cluster, with the fixed parameters of the node-level `IcdManager`
+/// and the clients registered for its Check-In messages
+///
+/// Only for nodes with an ICD configuration in their `BasicInfoConfig`. The registered
+/// clients are persisted through `StatePersist`, as their keys are never readable.
+pub struct IcdMgmtCluster<'a> {
+    data_ver: Dataver,
+    icd: &'a RefCell<IcdManager>,
+    epoch: Epoch,
+    counter: Cell<u32>,
+    clients: RefCell<heapless::Vec<IcdClient, MAX_CLIENTS>>,
+    clients_changed: Cell<bool>,
+    woke: Cell<bool>,
+    wake_notification: Notification,
+}
+
+impl<'a> IcdMgmtCluster<'a> {
+    pub fn new(icd: &'a RefCell<IcdManager>, epoch: Epoch, rand: Rand) -> Self {
+        let mut counter = [0; 4];
+        rand(&mut counter);
+
+        Self {
+            data_ver: Dataver::new(rand),
+            icd,
+            epoch,
+            counter: Cell::new(u32::from_le_bytes(counter)),
+            clients: RefCell::new(heapless::Vec::new()),
+            clients_changed: Cell::new(false),
+            woke: Cell::new(false),
+            wake_notification: Notification::new(),
+        }
+    }
+
+    /// Register a client for Check-In messages, or update its registration
+    ///
+    /// Unless `admin` - i.e. the requester has the Administer privilege - updating a
+    /// registration needs the key it was registered with as the `verification_key`.
+    /// Returns the current ICD counter.
+    pub fn register_client(
+        &self,
+        fab_idx: u8,
+        check_in_node_id: u64,
+        monitored_subject: u64,
+        key: &[u8],
+        verification_key: Option<&[u8]>,
+        admin: bool,
+    ) -> Result<u32, Error> {
+        let client = IcdClient {
+            fab_idx,
+            check_in_node_id,
+            monitored_subject,
+            key: key.try_into().map_err(|_| ErrorCode::ConstraintError)?,
+        };
+
+        let mut clients = self.clients.borrow_mut();
+
+        if let Some(existing) = clients
+            .iter_mut()
+            .find(|c| c.fab_idx == fab_idx && c.check_in_node_id == check_in_node_id)
+        {
+            Self::verify(&existing.key, verification_key, admin)?;
+
+            *existing = client;
+        } else {
+            if clients.iter().filter(|c| c.fab_idx == fab_idx).count() >= CLIENTS_PER_FABRIC {
+                Err(ErrorCode::ResourceExhausted)?;
+            }
+
+            clients
+                .push(client)
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        self.clients_changed.set(true);
+        self.data_ver.changed();
+
+        Ok(self.counter.get())
+    }
+
+    /// Remove the registration of a client
+    ///
+    /// As with `register_client`, this needs the key of the registration as the
+    /// `verification_key`, unless the requester is an `admin`.
+    pub fn unregister_client(
+        &self,
+        fab_idx: u8,
+        check_in_node_id: u64,
+        verification_key: Option<&[u8]>,
+        admin: bool,
+    ) -> Result<(), Error> {
+        let mut clients = self.clients.borrow_mut();
+
+        let index = clients
+            .iter()
+            .position(|c| c.fab_idx == fab_idx && c.check_in_node_id == check_in_node_id)
+            .ok_or(ErrorCode::NotFound)?;
+
+        Self::verify(&clients[index].key, verification_key, admin)?;
+
+        clients.remove(index);
+
+        self.clients_changed.set(true);
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    /// Remove the registered clients of a fabric which was removed
+    pub fn remove_fabric(&self, fab_idx: u8) {
+        self.clients
+            .borrow_mut()
+            .retain(|client| client.fab_idx != fab_idx);
+        self.clients_changed.set(true);
+        self.data_ver.changed();
+    }
+
+    /// Wake the ICD up, e.g. on a user action
+    ///
+    /// If it was idle, `run` checks in the registered clients.
+    pub fn wake(&self) {
+        if self.icd.borrow_mut().wake((self.epoch)()) {
+            self.woke.set(true);
+        }
+
+        self.wake_notification.signal(());
+    }
+
+    /// Drive the operating mode of the ICD, and send the Check-In messages to the registered
+    /// clients whenever it enters the active mode
+    ///
+    /// Needs to be running, along with the transport, for the ICD to ever change its mode.
+    /// `resolver` looks up the operational addresses of the clients, and `buf` holds the
+    /// messages.
+    pub async fn run(
+        &self,
+        matter: &Matter<'_>,
+        resolver: &impl NodeResolver,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        loop {
+            let now = (self.epoch)();
+
+            let entered = self.icd.borrow_mut().update(now);
+            if self.woke.replace(false) || entered == Some(OperatingMode::Active) {
+                self.check_in(matter, resolver, buf).await?;
+            }
+
+            let next = self.icd.borrow().next_transition();
+            if let Some(next) = next {
+                let timeout = next.saturating_sub(now).as_millis() as u64;
+
+                select(
+                    Timer::after(Duration::from_millis(timeout)),
+                    self.wake_notification.wait(),
+                )
+                .await;
+            } else {
+                self.wake_notification.wait().await;
+            }
+        }
+    }
+
+    async fn check_in(
+        &self,
+        matter: &Matter<'_>,
+        resolver: &impl NodeResolver,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        let clients = self.clients.borrow().clone();
+
+        for client in &clients {
+            // A subscribed client already learns about the node's availability from its reports
+            if matter.subscriptions.borrow().is_subscribed(
+                client.fab_idx,
+                client.monitored_subject,
+                (matter.epoch)(),
+            ) {
+                continue;
+            }
+
+            match resolver.resolve(client.fab_idx, client.check_in_node_id) {
+                Some(addr) => {
+                    let mut tx = Packet::new_tx(&mut *buf);
+                    self.prepare_check_in(client, &mut tx)?;
+
+                    matter.send_unsecured(addr, &tx).await?;
+                }
+                None => warn!(
+                    "Skipping the Check-In of node {:x}, as its address is unknown",
+                    client.check_in_node_id
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn prepare_check_in(&self, client: &IcdClient, tx: &mut Packet) -> Result<(), Error> {
+        let counter = self.counter.get().wrapping_add(1);
+        self.counter.set(counter);
+        self.data_ver.changed();
+
+        prepare_check_in(tx, &client.key, counter)
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::IdleModeDuration(codec) => {
+                        codec.encode(writer, self.config()?.idle_mode_duration_secs)
+                    }
+                    Attributes::ActiveModeDuration(codec) => {
+                        codec.encode(writer, self.config()?.active_mode_duration_ms)
+                    }
+                    Attributes::ActiveModeThreshold(codec) => {
+                        codec.encode(writer, self.config()?.active_mode_threshold_ms)
+                    }
+                    Attributes::RegisteredClients(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for client in self.clients.borrow().iter() {
+                            if !attr.fab_filter || attr.fab_idx == client.fab_idx {
+                                MonitoringRegistration {
+                                    check_in_node_id: client.check_in_node_id,
+                                    monitored_subject: client.monitored_subject,
+                                    fab_idx: client.fab_idx,
+                                }
+                                .to_tlv(&mut writer, TagType::Anonymous)?;
+                            }
+                        }
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::IcdCounter(codec) => codec.encode(writer, self.counter.get()),
+                    Attributes::ClientsSupportedPerFabric(codec) => {
+                        codec.encode(writer, CLIENTS_PER_FABRIC as _)
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        // Only writable internally, when restoring the persisted counter
+        match attr.attr_id.try_into()? {
+            Attributes::IcdCounter(codec) => self.counter.set(codec.decode(data)?),
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::RegisterClient => {
+                cmd_enter!("RegisterClient");
+
+                let req = RegisterClientReq::from_tlv(data)?;
+
+                let icd_counter = self.register_client(
+                    Self::fab_idx(exchange)?,
+                    req.check_in_node_id,
+                    req.monitored_subject,
+                    req.key.0,
+                    req.verification_key.map(|key| key.0),
+                    Self::is_admin(exchange, cmd)?,
+                )?;
+
+                encoder
+                    .with_command(RespCommands::RegisterClientResponse as _)?
+                    .set(RegisterClientResp { icd_counter })?;
+            }
+            Commands::UnregisterClient => {
+                cmd_enter!("UnregisterClient");
+
+                let req = UnregisterClientReq::from_tlv(data)?;
+
+                self.unregister_client(
+                    Self::fab_idx(exchange)?,
+                    req.check_in_node_id,
+                    req.verification_key.map(|key| key.0),
+                    Self::is_admin(exchange, cmd)?,
+                )?;
+            }
+            Commands::StayActiveRequest => {
+                cmd_enter!("StayActiveRequest");
+
+                let conf = self.config()?;
+
+                self.icd
+                    .borrow_mut()
+                    .stay_active((self.epoch)(), conf.active_mode_duration_ms);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn config(&self) -> Result<IcdConfig, Error> {
+        Ok(*self.icd.borrow().config().ok_or(ErrorCode::InvalidState)?)
+    }
+
+    fn verify(
+        key: &[u8; CHECK_IN_KEY_LEN],
+        verification_key: Option<&[u8]>,
+        admin: bool,
+    ) -> Result<(), Error> {
+        if admin || verification_key == Some(key.as_slice()) {
+            Ok(())
+        } else {
+            Err(ErrorCode::InvalidAuthKey.into())
+        }
+    }
+
+    fn is_admin(exchange: &Exchange, cmd: &CmdDetails) -> Result<bool, Error> {
+        let accessor = exchange.accessor()?;
+
+        let mut access_req = AccessReq::new(&accessor, cmd.path().path, Access::WRITE);
+        access_req.set_target_perms(Access::WRITE.union(Access::NEED_ADMIN));
+
+        Ok(access_req.allow())
+    }
+
+    fn fab_idx(exchange: &Exchange) -> Result<u8, Error> {
+        exchange
+            .with_session(|sess| Ok(sess.get_local_fabric_idx()))?
+            .ok_or_else(|| ErrorCode::UnsupportedAccess.into())
+    }
+}
+
+impl<'a> Handler for IcdMgmtCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        IcdMgmtCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        IcdMgmtCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        IcdMgmtCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        IcdMgmtCluster::remove_fabric(self, fab_idx)
+    }
+}
+
+impl<'a> NonBlockingHandler for IcdMgmtCluster<'a> {}
+
+impl<'a> StatePersist for IcdMgmtCluster<'a> {
+    fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        let mut clients = self.clients.borrow_mut();
+        clients.clear();
+
+        for entry in TLVArray::<StoredClient>::from_tlv(&root)?.iter() {
+            clients
+                .push(IcdClient {
+                    fab_idx: entry.fab_idx,
+                    check_in_node_id: entry.check_in_node_id,
+                    monitored_subject: entry.monitored_subject,
+                    key: entry.key.0.try_into().map_err(|_| ErrorCode::Invalid)?,
+                })
+                .map_err(|_| ErrorCode::NoSpace)?;
+        }
+
+        self.clients_changed.set(false);
+
+        Ok(())
+    }
+
+    fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        if self.clients_changed.get() {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            tw.start_array(TagType::Anonymous)?;
+            for client in self.clients.borrow().iter() {
+                StoredClient {
+                    fab_idx: client.fab_idx,
+                    check_in_node_id: client.check_in_node_id,
+                    monitored_subject: client.monitored_subject,
+                    key: OctetStr(&client.key),
+                }
+                .to_tlv(&mut tw, TagType::Anonymous)?;
+            }
+            tw.end_container()?;
+
+            self.clients_changed.set(false);
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a> ChangeNotifier<()> for IcdMgmtCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use core::time::Duration;
+
+    use crate::data_model::persist::StatePersist;
+    use crate::error::ErrorCode;
+    use crate::icd::{IcdConfig, IcdManager};
+    use crate::secure_channel::check_in::decode_check_in;
+    use crate::transport::packet::Packet;
+    use crate::utils::rand::dummy_rand;
+
+    use super::{IcdMgmtCluster, CLIENTS_PER_FABRIC};
+
+    const CONF: IcdConfig = IcdConfig {
+        idle_mode_duration_secs: 60,
+        active_mode_duration_ms: 1000,
+        active_mode_threshold_ms: 500,
+        idle_interval_ms: 30000,
+        active_interval_ms: 300,
+    };
+
+    const KEY: [u8; 16] = [0x11; 16];
+    const OTHER_KEY: [u8; 16] = [0x22; 16];
+
+    fn test_epoch() -> Duration {
+        Duration::from_secs(0)
+    }
+
+    #[test]
+    fn test_register_clients() {
+        let icd = RefCell::new(IcdManager::new(Some(CONF)));
+        let cluster = IcdMgmtCluster::new(&icd, test_epoch, dummy_rand);
+
+        for node_id in 0..CLIENTS_PER_FABRIC as u64 {
+            cluster
+                .register_client(1, node_id, 100, &KEY, None, false)
+                .unwrap();
+        }
+        assert_eq!(
+            Err(ErrorCode::ResourceExhausted),
+            cluster
+                .register_client(1, 10, 100, &KEY, None, false)
+                .map_err(|e| e.code())
+        );
+        cluster
+            .register_client(2, 10, 100, &KEY, None, false)
+            .unwrap();
+
+        // Only with the key the client was registered with
+        assert_eq!(
+            Err(ErrorCode::InvalidAuthKey),
+            cluster
+                .register_client(1, 0, 100, &OTHER_KEY, Some(&OTHER_KEY), false)
+                .map_err(|e| e.code())
+        );
+        assert_eq!(
+            Err(ErrorCode::InvalidAuthKey),
+            cluster
+                .register_client(1, 0, 100, &OTHER_KEY, None, false)
+                .map_err(|e| e.code())
+        );
+        cluster
+            .register_client(1, 0, 100, &OTHER_KEY, Some(&KEY), false)
+            .unwrap();
+        assert_eq!(
+            Err(ErrorCode::InvalidAuthKey),
+            cluster
+                .unregister_client(1, 0, Some(&KEY), false)
+                .map_err(|e| e.code())
+        );
+        assert_eq!(
+            Err(ErrorCode::InvalidAuthKey),
+            cluster
+                .unregister_client(1, 0, None, false)
+                .map_err(|e| e.code())
+        );
+        cluster
+            .unregister_client(1, 0, Some(&OTHER_KEY), false)
+            .unwrap();
+        assert_eq!(
+            Err(ErrorCode::NotFound),
+            cluster
+                .unregister_client(1, 0, None, false)
+                .map_err(|e| e.code())
+        );
+
+        // ... or by an administrator
+        cluster
+            .register_client(1, 1, 100, &OTHER_KEY, None, true)
+            .unwrap();
+        cluster.unregister_client(1, 1, None, true).unwrap();
+
+        cluster.remove_fabric(1);
+        assert_eq!(cluster.clients.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_persist_clients() {
+        let icd = RefCell::new(IcdManager::new(Some(CONF)));
+        let cluster = IcdMgmtCluster::new(&icd, test_epoch, dummy_rand);
+
+        cluster
+            .register_client(1, 5, 100, &KEY, None, false)
+            .unwrap();
+        cluster
+            .register_client(2, 6, 200, &OTHER_KEY, None, false)
+            .unwrap();
+
+        let mut buf = [0; 128];
+        let data = cluster.store(&mut buf).unwrap().unwrap();
+
+        let restored = IcdMgmtCluster::new(&icd, test_epoch, dummy_rand);
+        restored.load(data).unwrap();
+
+        assert_eq!(*restored.clients.borrow(), *cluster.clients.borrow());
+
+        // Nothing changed since the last store
+        let mut buf = [0; 128];
+        assert!(cluster.store(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_check_in() {
+        let icd = RefCell::new(IcdManager::new(Some(CONF)));
+        let cluster = IcdMgmtCluster::new(&icd, test_epoch, dummy_rand);
+
+        let counter = cluster
+            .register_client(1, 5, 100, &KEY, None, false)
+            .unwrap();
+
+        let client = cluster.clients.borrow()[0].clone();
+
+        let mut buf = [0; 128];
+        let mut tx = Packet::new_tx(&mut buf);
+        cluster.prepare_check_in(&client, &mut tx).unwrap();
+
+        assert_eq!(decode_check_in(&KEY, tx.as_slice()).unwrap(), counter + 1);
+        assert_eq!(cluster.counter.get(), counter + 1);
+    }
+}
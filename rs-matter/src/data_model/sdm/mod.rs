@@ -22,5 +22,10 @@ pub mod failsafe;
 pub mod general_commissioning;
 pub mod general_diagnostics;
 pub mod group_key_management;
+pub mod icd_management;
 pub mod noc;
 pub mod nw_commissioning;
+pub mod sw_diagnostics;
+pub mod thread_nw_diagnostics;
+pub mod time_sync;
+pub mod wifi_nw_diagnostics;
@@ -28,7 +28,7 @@ use crate::mdns::Mdns;
 use crate::tlv::{FromTLV, OctetStr, TLVElement, TLVWriter, TagType, ToTLV, UtfStr};
 use crate::transport::exchange::Exchange;
 use crate::transport::session::SessionMode;
-use crate::utils::epoch::Epoch;
+use crate::utils::epoch::{Epoch, UtcClock};
 use crate::utils::rand::Rand;
 use crate::utils::writebuf::WriteBuf;
 use crate::{attribute_enum, cmd_enter, command_enum, error::*};
@@ -223,6 +223,7 @@ pub struct NocCluster<'a> {
     acl_mgr: &'a RefCell<AclMgr>,
     failsafe: &'a RefCell<FailSafe>,
     mdns: &'a dyn Mdns,
+    utc: &'a UtcClock,
 }
 
 impl<'a> NocCluster<'a> {
@@ -232,6 +233,7 @@ impl<'a> NocCluster<'a> {
         acl_mgr: &'a RefCell<AclMgr>,
         failsafe: &'a RefCell<FailSafe>,
         mdns: &'a dyn Mdns,
+        utc: &'a UtcClock,
         epoch: Epoch,
         rand: Rand,
     ) -> Self {
@@ -244,6 +246,7 @@ impl<'a> NocCluster<'a> {
             acl_mgr,
             failsafe,
             mdns,
+            utc,
         }
     }
 
@@ -373,6 +376,14 @@ impl<'a> NocCluster<'a> {
             None
         };
 
+        Self::verify_chain(
+            &noc_cert,
+            icac.as_deref(),
+            &noc_data.root_ca,
+            self.utc.now_secs(),
+        )
+        .map_err(|_| NocStatus::InvalidNOC)?;
+
         let fabric = Fabric::new(
             noc_data.key_pair,
             noc_data.root_ca,
@@ -396,6 +407,26 @@ impl<'a> NocCluster<'a> {
         Ok(fab_idx)
     }
 
+    // Verify that the NOC chains up to the trusted root - through the ICAC, if any - and
+    // that all of them are valid at `now`, if the time is known
+    fn verify_chain(
+        noc: &Cert,
+        icac: Option<&[u8]>,
+        root_ca: &[u8],
+        now: Option<u64>,
+    ) -> Result<(), Error> {
+        let icac = icac.map(Cert::new).transpose()?;
+        let root_ca = Cert::new(root_ca)?;
+
+        let mut verifier = noc.verify_chain_start().valid_at(now);
+
+        if let Some(icac) = &icac {
+            verifier = verifier.add_cert(icac)?;
+        }
+
+        verifier.add_cert(&root_ca)?.finalise()
+    }
+
     fn create_nocresponse(
         encoder: CmdDataEncoder,
         status_code: NocStatus,
@@ -605,13 +636,22 @@ impl<'a> NocCluster<'a> {
         Ok(())
     }
 
-    fn add_rca_to_session_noc_data(exchange: &Exchange, data: &TLVElement) -> Result<(), Error> {
+    fn add_rca_to_session_noc_data(
+        exchange: &Exchange,
+        data: &TLVElement,
+        now: Option<u64>,
+    ) -> Result<(), Error> {
         exchange.with_session_mut(|sess| {
             let noc_data = sess.get_noc_data().ok_or(ErrorCode::NoSession)?;
 
             let req = CommonReq::from_tlv(data).map_err(Error::map_invalid_command)?;
             info!("Received Trusted Cert:{:x?}", req.str);
 
+            // A self-signed root, valid now if the time is known
+            Cert::new(req.str.0)
+                .and_then(|root_ca| root_ca.verify_chain_start().valid_at(now).finalise())
+                .map_err(|_| ErrorCode::InvalidCommand)?;
+
             noc_data.root_ca =
                 heapless::Vec::from_slice(req.str.0).map_err(|_| ErrorCode::BufferTooSmall)?;
 
@@ -633,10 +673,10 @@ impl<'a> NocCluster<'a> {
         match exchange.with_session(|sess| Ok(sess.get_session_mode().clone()))? {
             SessionMode::Case(_) => {
                 // TODO - Updating the Trusted RCA of an existing Fabric
-                Self::add_rca_to_session_noc_data(exchange, data)?;
+                Self::add_rca_to_session_noc_data(exchange, data, self.utc.now_secs())?;
             }
             SessionMode::Pase => {
-                Self::add_rca_to_session_noc_data(exchange, data)?;
+                Self::add_rca_to_session_noc_data(exchange, data, self.utc.now_secs())?;
             }
             _ => (),
         }
@@ -659,6 +699,10 @@ impl<'a> Handler for NocCluster<'a> {
     ) -> Result<(), Error> {
         NocCluster::invoke(self, exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 impl<'a> NonBlockingHandler for NocCluster<'a> {}
@@ -18,35 +18,69 @@
 use strum::FromRepr;
 
 use crate::{
-    attribute_enum,
+    attribute_enum, command_enum,
     data_model::objects::{
         Access, AttrDataEncoder, AttrDataWriter, AttrDetails, AttrType, Attribute, ChangeNotifier,
-        Cluster, Dataver, Handler, NonBlockingHandler, Quality, ATTRIBUTE_LIST, FEATURE_MAP,
+        Cluster, ClusterId, Dataver, EndptId, Handler, NonBlockingHandler, Quality, ATTRIBUTE_LIST,
+        FEATURE_MAP,
     },
-    error::Error,
-    tlv::{OctetStr, TLVWriter, TagType, ToTLV},
+    error::{Error, ErrorCode},
+    tlv::{OctetStr, TLVWriter, TagType, ToTLV, UtfStr},
     utils::rand::Rand,
 };
 
+#[cfg(feature = "nightly")]
+pub use asynch::*;
+
 pub const ID: u32 = 0x0031;
 
+/// The maximum length of a network ID, i.e. of a Wi-Fi SSID or a Thread Extended PAN ID
+pub const MAX_NETWORK_ID_LEN: usize = 32;
+
+const MESHCOP_TLV_EXT_PAN_ID: u8 = 0x02;
+const THREAD_EXT_PAN_ID_LEN: usize = 8;
+
 #[derive(FromRepr)]
 #[repr(u16)]
 pub enum Attributes {
     MaxNetworks = 0x00,
     Networks = 0x01,
+    ScanMaxTimeSeconds = 0x02,
     ConnectMaxTimeSecs = 0x03,
     InterfaceEnabled = 0x04,
     LastNetworkingStatus = 0x05,
     LastNetworkID = 0x06,
     LastConnectErrorValue = 0x07,
+    SupportedWiFiBands = 0x08,
+    SupportedThreadFeatures = 0x09,
+    ThreadVersion = 0x0A,
 }
 
 attribute_enum!(Attributes);
 
+#[derive(FromRepr)]
+#[repr(u32)]
+pub enum Commands {
+    ScanNetworks = 0x00,
+    AddOrUpdateWiFiNetwork = 0x02,
+    AddOrUpdateThreadNetwork = 0x03,
+    RemoveNetwork = 0x04,
+    ConnectNetwork = 0x06,
+    ReorderNetwork = 0x08,
+}
+
+command_enum!(Commands);
+
+#[repr(u16)]
+pub enum RespCommands {
+    ScanNetworksResponse = 0x01,
+    NetworkConfigResponse = 0x05,
+    ConnectNetworkResponse = 0x07,
+}
+
 enum FeatureMap {
-    _Wifi = 0x01,
-    _Thread = 0x02,
+    Wifi = 0x01,
+    Thread = 0x02,
     Ethernet = 0x04,
 }
 
@@ -83,6 +117,105 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     commands: &[],
 };
 
+pub const WIFI_CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::Wifi as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(Attributes::MaxNetworks as u16, Access::RA, Quality::F),
+        Attribute::new(Attributes::Networks as u16, Access::RA, Quality::NONE),
+        Attribute::new(
+            Attributes::ScanMaxTimeSeconds as u16,
+            Access::RV,
+            Quality::F,
+        ),
+        Attribute::new(
+            Attributes::ConnectMaxTimeSecs as u16,
+            Access::RV,
+            Quality::F,
+        ),
+        Attribute::new(
+            Attributes::InterfaceEnabled as u16,
+            Access::RWVA,
+            Quality::N,
+        ),
+        Attribute::new(
+            Attributes::LastNetworkingStatus as u16,
+            Access::RA,
+            Quality::X,
+        ),
+        Attribute::new(Attributes::LastNetworkID as u16, Access::RA, Quality::X),
+        Attribute::new(
+            Attributes::LastConnectErrorValue as u16,
+            Access::RA,
+            Quality::X,
+        ),
+        Attribute::new(
+            Attributes::SupportedWiFiBands as u16,
+            Access::RV,
+            Quality::F,
+        ),
+    ],
+    commands: &[
+        Commands::ScanNetworks as _,
+        Commands::AddOrUpdateWiFiNetwork as _,
+        Commands::RemoveNetwork as _,
+        Commands::ConnectNetwork as _,
+        Commands::ReorderNetwork as _,
+    ],
+};
+
+pub const THREAD_CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::Thread as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(Attributes::MaxNetworks as u16, Access::RA, Quality::F),
+        Attribute::new(Attributes::Networks as u16, Access::RA, Quality::NONE),
+        Attribute::new(
+            Attributes::ScanMaxTimeSeconds as u16,
+            Access::RV,
+            Quality::F,
+        ),
+        Attribute::new(
+            Attributes::ConnectMaxTimeSecs as u16,
+            Access::RV,
+            Quality::F,
+        ),
+        Attribute::new(
+            Attributes::InterfaceEnabled as u16,
+            Access::RWVA,
+            Quality::N,
+        ),
+        Attribute::new(
+            Attributes::LastNetworkingStatus as u16,
+            Access::RA,
+            Quality::X,
+        ),
+        Attribute::new(Attributes::LastNetworkID as u16, Access::RA, Quality::X),
+        Attribute::new(
+            Attributes::LastConnectErrorValue as u16,
+            Access::RA,
+            Quality::X,
+        ),
+        Attribute::new(
+            Attributes::SupportedThreadFeatures as u16,
+            Access::RV,
+            Quality::F,
+        ),
+        Attribute::new(Attributes::ThreadVersion as u16, Access::RV, Quality::F),
+    ],
+    commands: &[
+        Commands::ScanNetworks as _,
+        Commands::AddOrUpdateThreadNetwork as _,
+        Commands::RemoveNetwork as _,
+        Commands::ConnectNetwork as _,
+        Commands::ReorderNetwork as _,
+    ],
+};
+
 pub struct NwCommCluster {
     data_ver: Dataver,
 }
@@ -109,10 +242,11 @@ impl NwCommCluster {
     }
 }
 
+/// An entry of the Networks attribute
 #[derive(ToTLV)]
-struct NwInfo<'a> {
-    network_id: OctetStr<'a>,
-    connected: bool,
+pub struct NwInfo<'a> {
+    pub network_id: OctetStr<'a>,
+    pub connected: bool,
 }
 
 struct NwMetaInfo<'a> {
@@ -122,8 +256,8 @@ struct NwMetaInfo<'a> {
     last_nw_status: NetworkCommissioningStatus,
 }
 
-#[allow(dead_code)]
-enum NetworkCommissioningStatus {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkCommissioningStatus {
     Success = 0,
     OutOfRange = 1,
     BoundsExceeded = 2,
@@ -139,6 +273,102 @@ enum NetworkCommissioningStatus {
     UnknownError = 12,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkType {
+    WiFi,
+    Thread,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WiFiBand {
+    Band2G4 = 0,
+    Band3G65 = 1,
+    Band5G = 2,
+    Band6G = 3,
+    Band60G = 4,
+    Band1G = 5,
+}
+
+/// The bits of the `security` bitmap of a Wi-Fi scan result
+pub mod wifi_security {
+    pub const UNENCRYPTED: u8 = 0x01;
+    pub const WEP: u8 = 0x02;
+    pub const WPA_PERSONAL: u8 = 0x04;
+    pub const WPA2_PERSONAL: u8 = 0x08;
+    pub const WPA3_PERSONAL: u8 = 0x10;
+}
+
+#[derive(ToTLV)]
+pub struct WiFiScanResult<'a> {
+    pub security: u8,
+    pub ssid: OctetStr<'a>,
+    pub bssid: OctetStr<'a>,
+    pub channel: u16,
+    pub wifi_band: Option<u8>,
+    pub rssi: Option<i8>,
+}
+
+#[derive(ToTLV)]
+pub struct ThreadScanResult<'a> {
+    pub pan_id: u16,
+    pub extended_pan_id: u64,
+    pub network_name: UtfStr<'a>,
+    pub channel: u16,
+    pub version: u8,
+    pub extended_address: OctetStr<'a>,
+    pub rssi: i8,
+    pub lqi: u8,
+}
+
+pub enum ScanResult<'a> {
+    WiFi(WiFiScanResult<'a>),
+    Thread(ThreadScanResult<'a>),
+}
+
+/// The credentials of a network, as received by the AddOrUpdate commands
+#[derive(Debug, Clone, Copy)]
+pub enum NetworkCredentials<'a> {
+    WiFi { ssid: &'a [u8], password: &'a [u8] },
+    Thread { dataset: &'a [u8] },
+}
+
+impl<'a> NetworkCredentials<'a> {
+    pub fn network_type(&self) -> NetworkType {
+        match self {
+            Self::WiFi { .. } => NetworkType::WiFi,
+            Self::Thread { .. } => NetworkType::Thread,
+        }
+    }
+
+    /// The SSID of a Wi-Fi network, or the Extended PAN ID of a Thread network
+    pub fn network_id(&self) -> Result<&'a [u8], Error> {
+        match self {
+            Self::WiFi { ssid, .. } => Ok(ssid),
+            Self::Thread { dataset } => thread_ext_pan_id(dataset),
+        }
+    }
+}
+
+/// Finds the Extended PAN ID in a Thread Operational Dataset, which is a sequence of
+/// MeshCoP TLVs
+pub fn thread_ext_pan_id(dataset: &[u8]) -> Result<&[u8], Error> {
+    let mut rest = dataset;
+
+    while rest.len() >= 2 {
+        let tlv_type = rest[0];
+        let len = rest[1] as usize;
+        let value = rest.get(2..2 + len).ok_or(ErrorCode::InvalidData)?;
+
+        if tlv_type == MESHCOP_TLV_EXT_PAN_ID && len == THREAD_EXT_PAN_ID_LEN {
+            return Ok(value);
+        }
+
+        rest = &rest[2 + len..];
+    }
+
+    Err(ErrorCode::NotFound.into())
+}
+
 impl Handler for NwCommCluster {
     fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         let info = self.get_network_info();
@@ -176,12 +406,17 @@ impl Handler for NwCommCluster {
                         writer.null(AttrDataWriter::TAG)?;
                         writer.complete()
                     }
+                    _ => Err(ErrorCode::AttributeNotFound.into()),
                 }
             }
         } else {
             Ok(())
         }
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 impl NonBlockingHandler for NwCommCluster {}
@@ -191,3 +426,1424 @@ impl ChangeNotifier<()> for NwCommCluster {
         self.data_ver.consume_change(())
     }
 }
+
+#[cfg(feature = "nightly")]
+mod asynch {
+    use core::cell::{Cell, RefCell};
+    use core::future::pending;
+
+    use embassy_time::{with_timeout, Duration};
+
+    use log::warn;
+
+    use crate::{
+        cmd_enter,
+        data_model::{
+            objects::{
+                AsyncHandler, AttrData, AttrDataEncoder, AttrDataWriter, AttrDetails, AttrType,
+                ChangeNotifier, Cluster, ClusterId, CmdDataEncoder, CmdDataWriter, CmdDetails,
+                Dataver, EndptId,
+            },
+            sdm::failsafe::FailSafe,
+        },
+        error::{Error, ErrorCode},
+        tlv::{FromTLV, Nullable, OctetStr, TLVElement, TLVWriter, TagType, ToTLV, UtfStr},
+        transport::exchange::Exchange,
+        utils::{rand::Rand, select::Notification},
+    };
+
+    use super::{
+        thread_ext_pan_id, wifi_security, Attributes, Commands, NetworkCommissioningStatus,
+        NetworkCredentials, NetworkType, NwInfo, RespCommands, ScanResult, ThreadScanResult,
+        WiFiBand, WiFiScanResult, MAX_NETWORK_ID_LEN, THREAD_CLUSTER, WIFI_CLUSTER,
+    };
+
+    const MAX_WIFI_CREDENTIALS_LEN: usize = 64;
+    const MAX_THREAD_DATASET_LEN: usize = 254;
+
+    /// The platform side of a Wi-Fi or Thread network interface
+    ///
+    /// The driver stores the credentials of the configured networks, and does the actual
+    /// scanning and connecting. Scanning and connecting are asynchronous, and the cluster
+    /// gives up on them after `scan_max_time_secs` and `connect_max_time_secs`.
+    /// Unless the driver supports concurrent connection, connecting is done by
+    /// `WirelessNwCommCluster::run`, after the response to ConnectNetwork was sent.
+    pub trait NetworkDriver {
+        fn network_type(&self) -> NetworkType;
+
+        /// The maximum number of networks that can be configured
+        fn max_networks(&self) -> u8;
+
+        fn scan_max_time_secs(&self) -> u8;
+
+        fn connect_max_time_secs(&self) -> u8;
+
+        /// Only used by Wi-Fi drivers
+        fn supported_wifi_bands(&self) -> &[WiFiBand] {
+            &[WiFiBand::Band2G4]
+        }
+
+        /// Only used by Thread drivers
+        fn thread_features(&self) -> u16 {
+            0
+        }
+
+        /// Only used by Thread drivers
+        fn thread_version(&self) -> u16 {
+            4
+        }
+
+        fn interface_enabled(&self) -> bool;
+
+        fn set_interface_enabled(&self, enabled: bool) -> Result<(), Error>;
+
+        /// Whether the node stays reachable over the commissioning channel (e.g. BLE) while
+        /// connecting, so that ConnectNetwork can be answered with the outcome
+        fn supports_concurrent_connection(&self) -> bool {
+            false
+        }
+
+        /// Saves the configured networks, so that `restore_networks` can revert to them
+        ///
+        /// Called before the first change made while the fail-safe is armed.
+        fn save_networks(&self) -> Result<(), Error>;
+
+        /// Reverts the configured networks to the ones last saved by `save_networks`
+        ///
+        /// Called when the fail-safe expires.
+        fn restore_networks(&self) -> Result<(), Error>;
+
+        /// Calls `f` for each configured network, in priority order
+        fn networks(&self, f: &mut dyn FnMut(&NwInfo) -> Result<(), Error>) -> Result<(), Error>;
+
+        /// Scans for all networks, or only for the network with the given SSID
+        async fn scan(&self, ssid: Option<&[u8]>) -> Result<(), NetworkCommissioningStatus>;
+
+        /// Calls `f` for each network found by the last scan
+        fn scan_results(
+            &self,
+            f: &mut dyn FnMut(&ScanResult) -> Result<(), Error>,
+        ) -> Result<(), Error>;
+
+        /// Adds a network, or updates its credentials if it is already configured,
+        /// returning its index
+        fn add_or_update(
+            &self,
+            credentials: &NetworkCredentials,
+        ) -> Result<u8, NetworkCommissioningStatus>;
+
+        /// Removes a network, returning the index it had
+        fn remove(&self, network_id: &[u8]) -> Result<u8, NetworkCommissioningStatus>;
+
+        fn reorder(&self, network_id: &[u8], index: u8) -> Result<(), NetworkCommissioningStatus>;
+
+        /// Connects to a configured network
+        ///
+        /// On failure, also returns the platform-specific error value, if there is one
+        async fn connect(
+            &self,
+            network_id: &[u8],
+        ) -> Result<(), (NetworkCommissioningStatus, Option<i32>)>;
+    }
+
+    impl<T> NetworkDriver for &T
+    where
+        T: NetworkDriver,
+    {
+        fn network_type(&self) -> NetworkType {
+            (**self).network_type()
+        }
+
+        fn max_networks(&self) -> u8 {
+            (**self).max_networks()
+        }
+
+        fn scan_max_time_secs(&self) -> u8 {
+            (**self).scan_max_time_secs()
+        }
+
+        fn connect_max_time_secs(&self) -> u8 {
+            (**self).connect_max_time_secs()
+        }
+
+        fn supported_wifi_bands(&self) -> &[WiFiBand] {
+            (**self).supported_wifi_bands()
+        }
+
+        fn thread_features(&self) -> u16 {
+            (**self).thread_features()
+        }
+
+        fn thread_version(&self) -> u16 {
+            (**self).thread_version()
+        }
+
+        fn interface_enabled(&self) -> bool {
+            (**self).interface_enabled()
+        }
+
+        fn set_interface_enabled(&self, enabled: bool) -> Result<(), Error> {
+            (**self).set_interface_enabled(enabled)
+        }
+
+        fn supports_concurrent_connection(&self) -> bool {
+            (**self).supports_concurrent_connection()
+        }
+
+        fn save_networks(&self) -> Result<(), Error> {
+            (**self).save_networks()
+        }
+
+        fn restore_networks(&self) -> Result<(), Error> {
+            (**self).restore_networks()
+        }
+
+        fn networks(&self, f: &mut dyn FnMut(&NwInfo) -> Result<(), Error>) -> Result<(), Error> {
+            (**self).networks(f)
+        }
+
+        async fn scan(&self, ssid: Option<&[u8]>) -> Result<(), NetworkCommissioningStatus> {
+            (**self).scan(ssid).await
+        }
+
+        fn scan_results(
+            &self,
+            f: &mut dyn FnMut(&ScanResult) -> Result<(), Error>,
+        ) -> Result<(), Error> {
+            (**self).scan_results(f)
+        }
+
+        fn add_or_update(
+            &self,
+            credentials: &NetworkCredentials,
+        ) -> Result<u8, NetworkCommissioningStatus> {
+            (**self).add_or_update(credentials)
+        }
+
+        fn remove(&self, network_id: &[u8]) -> Result<u8, NetworkCommissioningStatus> {
+            (**self).remove(network_id)
+        }
+
+        fn reorder(&self, network_id: &[u8], index: u8) -> Result<(), NetworkCommissioningStatus> {
+            (**self).reorder(network_id, index)
+        }
+
+        async fn connect(
+            &self,
+            network_id: &[u8],
+        ) -> Result<(), (NetworkCommissioningStatus, Option<i32>)> {
+            (**self).connect(network_id).await
+        }
+    }
+
+    #[derive(FromTLV)]
+    #[tlvargs(lifetime = "'a")]
+    struct ScanNetworksReq<'a> {
+        ssid: Option<Nullable<OctetStr<'a>>>,
+        breadcrumb: Option<u64>,
+    }
+
+    #[derive(FromTLV)]
+    #[tlvargs(lifetime = "'a")]
+    struct AddOrUpdateWiFiNetworkReq<'a> {
+        ssid: OctetStr<'a>,
+        credentials: OctetStr<'a>,
+        breadcrumb: Option<u64>,
+    }
+
+    #[derive(FromTLV)]
+    #[tlvargs(lifetime = "'a")]
+    struct AddOrUpdateThreadNetworkReq<'a> {
+        operational_dataset: OctetStr<'a>,
+        breadcrumb: Option<u64>,
+    }
+
+    /// The RemoveNetwork and ConnectNetwork requests
+    #[derive(FromTLV)]
+    #[tlvargs(lifetime = "'a")]
+    struct NetworkIdReq<'a> {
+        network_id: OctetStr<'a>,
+        breadcrumb: Option<u64>,
+    }
+
+    #[derive(FromTLV)]
+    #[tlvargs(lifetime = "'a")]
+    struct ReorderNetworkReq<'a> {
+        network_id: OctetStr<'a>,
+        network_index: u8,
+        breadcrumb: Option<u64>,
+    }
+
+    #[derive(ToTLV)]
+    struct NetworkConfigResp {
+        networking_status: u8,
+        #[tagval(2)]
+        network_index: Option<u8>,
+    }
+
+    #[derive(ToTLV)]
+    struct ConnectNetworkResp {
+        networking_status: u8,
+        #[tagval(2)]
+        error_value: Nullable<i32>,
+    }
+
+    /// The Network Commissioning cluster of a Wi-Fi or Thread interface, on top of a
+    /// `NetworkDriver`
+    ///
+    /// If the driver supports concurrent connection, ConnectNetwork is answered with the
+    /// outcome of the connection. Otherwise it is answered before connecting, as the
+    /// commissioner might be reachable over the very interface being reconfigured; the
+    /// connection is then made by `run`, and its outcome is only reported by the
+    /// LastNetworkingStatus and LastConnectErrorValue attributes.
+    ///
+    /// The networks configured while the fail-safe is armed are reverted once it expires.
+    pub struct WirelessNwCommCluster<'a, T> {
+        data_ver: Dataver,
+        failsafe: &'a RefCell<FailSafe>,
+        driver: T,
+        saved_networks: Cell<Option<u32>>,
+        last_status: Cell<Option<NetworkCommissioningStatus>>,
+        last_network_id: RefCell<Option<heapless::Vec<u8, MAX_NETWORK_ID_LEN>>>,
+        last_connect_error: Cell<Option<i32>>,
+        scheduled_connect: RefCell<Option<heapless::Vec<u8, MAX_NETWORK_ID_LEN>>>,
+        connect_notification: Notification,
+    }
+
+    impl<'a, T> WirelessNwCommCluster<'a, T>
+    where
+        T: NetworkDriver,
+    {
+        pub fn new(failsafe: &'a RefCell<FailSafe>, driver: T, rand: Rand) -> Self {
+            Self {
+                data_ver: Dataver::new(rand),
+                failsafe,
+                driver,
+                saved_networks: Cell::new(None),
+                last_status: Cell::new(None),
+                last_network_id: RefCell::new(None),
+                last_connect_error: Cell::new(None),
+                scheduled_connect: RefCell::new(None),
+                connect_notification: Notification::new(),
+            }
+        }
+
+        pub fn driver(&self) -> &T {
+            &self.driver
+        }
+
+        pub fn last_networking_status(&self) -> Option<NetworkCommissioningStatus> {
+            self.last_status.get()
+        }
+
+        pub fn last_connect_error_value(&self) -> Option<i32> {
+            self.last_connect_error.get()
+        }
+
+        fn cluster(&self) -> Cluster<'static> {
+            match self.driver.network_type() {
+                NetworkType::WiFi => WIFI_CLUSTER,
+                NetworkType::Thread => THREAD_CLUSTER,
+            }
+        }
+
+        pub async fn scan_networks(
+            &self,
+            ssid: Option<&[u8]>,
+        ) -> Result<NetworkCommissioningStatus, Error> {
+            if ssid
+                .map(|ssid| ssid.len() > MAX_NETWORK_ID_LEN)
+                .unwrap_or(false)
+            {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            // Thread scans are never filtered
+            let ssid = match self.driver.network_type() {
+                NetworkType::WiFi => ssid.filter(|ssid| !ssid.is_empty()),
+                NetworkType::Thread => None,
+            };
+
+            let timeout = Duration::from_secs(self.driver.scan_max_time_secs() as _);
+            let status = match with_timeout(timeout, self.driver.scan(ssid)).await {
+                Ok(Ok(())) => NetworkCommissioningStatus::Success,
+                Ok(Err(status)) => status,
+                // There is no dedicated status for a scan which did not complete in time
+                Err(_) => NetworkCommissioningStatus::UnknownError,
+            };
+
+            self.last_status.set(Some(status));
+            self.data_ver.changed();
+
+            Ok(status)
+        }
+
+        pub fn add_or_update_network(
+            &self,
+            credentials: &NetworkCredentials,
+        ) -> Result<u8, NetworkCommissioningStatus> {
+            let valid = match credentials {
+                NetworkCredentials::WiFi { ssid, password } => {
+                    !ssid.is_empty()
+                        && ssid.len() <= MAX_NETWORK_ID_LEN
+                        && password.len() <= MAX_WIFI_CREDENTIALS_LEN
+                }
+                NetworkCredentials::Thread { dataset } => {
+                    dataset.len() <= MAX_THREAD_DATASET_LEN && thread_ext_pan_id(dataset).is_ok()
+                }
+            };
+
+            if !valid {
+                Err(NetworkCommissioningStatus::OutOfRange)?;
+            }
+
+            self.save_networks()?;
+
+            let index = self.driver.add_or_update(credentials)?;
+            self.data_ver.changed();
+
+            Ok(index)
+        }
+
+        pub fn remove_network(&self, network_id: &[u8]) -> Result<u8, NetworkCommissioningStatus> {
+            Self::check_network_id(network_id)?;
+            self.save_networks()?;
+
+            let index = self.driver.remove(network_id)?;
+            self.data_ver.changed();
+
+            Ok(index)
+        }
+
+        pub fn reorder_network(
+            &self,
+            network_id: &[u8],
+            index: u8,
+        ) -> Result<(), NetworkCommissioningStatus> {
+            Self::check_network_id(network_id)?;
+            self.save_networks()?;
+
+            self.driver.reorder(network_id, index)?;
+            self.data_ver.changed();
+
+            Ok(())
+        }
+
+        /// Connects to a configured network if the driver supports concurrent connection,
+        /// or else schedules the connection, which is made by `run`
+        ///
+        /// On failure, also returns the platform-specific error value, if there is one.
+        pub async fn connect_network(
+            &self,
+            network_id: &[u8],
+        ) -> Result<(), (NetworkCommissioningStatus, Option<i32>)> {
+            Self::check_network_id(network_id).map_err(|status| (status, None))?;
+
+            let mut configured = false;
+            self.driver
+                .networks(&mut |info| {
+                    configured |= info.network_id.0 == network_id;
+                    Ok(())
+                })
+                .map_err(|_| (NetworkCommissioningStatus::UnknownError, None))?;
+
+            if !configured {
+                Err((NetworkCommissioningStatus::NetworkIDNotFound, None))?;
+            }
+
+            if self.driver.supports_concurrent_connection() {
+                return self.connect(network_id).await;
+            }
+
+            *self.scheduled_connect.borrow_mut() = heapless::Vec::from_slice(network_id).ok();
+            self.connect_notification.signal(());
+
+            Ok(())
+        }
+
+        /// Makes the connections scheduled by ConnectNetwork
+        ///
+        /// Needs to be running for the node to ever connect to a configured network.
+        pub async fn run(&self) -> Result<(), Error> {
+            loop {
+                self.connect_notification.wait().await;
+                self.connect_scheduled().await;
+            }
+        }
+
+        async fn connect_scheduled(&self) {
+            let network_id = self.scheduled_connect.borrow_mut().take();
+
+            if let Some(network_id) = network_id {
+                // The outcome is reported by LastNetworkingStatus and LastConnectErrorValue
+                let _ = self.connect(&network_id).await;
+            }
+        }
+
+        async fn connect(
+            &self,
+            network_id: &[u8],
+        ) -> Result<(), (NetworkCommissioningStatus, Option<i32>)> {
+            let timeout = Duration::from_secs(self.driver.connect_max_time_secs() as _);
+            let (status, error_value) =
+                match with_timeout(timeout, self.driver.connect(network_id)).await {
+                    Ok(Ok(())) => (NetworkCommissioningStatus::Success, None),
+                    Ok(Err((status, error_value))) => (status, error_value),
+                    Err(_) => (NetworkCommissioningStatus::OtherConnectionFailure, None),
+                };
+
+            self.last_status.set(Some(status));
+            self.last_connect_error.set(error_value);
+            *self.last_network_id.borrow_mut() = heapless::Vec::from_slice(network_id).ok();
+            self.data_ver.changed();
+
+            if status == NetworkCommissioningStatus::Success {
+                Ok(())
+            } else {
+                Err((status, error_value))
+            }
+        }
+
+        /// Reverts the networks configured while the fail-safe was armed
+        pub fn failsafe_expired(&self) {
+            let generation = self.failsafe.borrow().generation();
+
+            if self.saved_networks.take() == Some(generation) {
+                if let Err(e) = self.driver.restore_networks() {
+                    warn!("Failed to restore the networks: {e:?}");
+                }
+
+                self.data_ver.changed();
+            }
+        }
+
+        /// Has the driver save the networks before the first change made under the current
+        /// arming of the fail-safe
+        fn save_networks(&self) -> Result<(), NetworkCommissioningStatus> {
+            let generation = self.failsafe.borrow().generation();
+
+            if self.saved_networks.get() != Some(generation) {
+                self.driver
+                    .save_networks()
+                    .map_err(|_| NetworkCommissioningStatus::UnknownError)?;
+                self.saved_networks.set(Some(generation));
+            }
+
+            Ok(())
+        }
+
+        fn check_network_id(network_id: &[u8]) -> Result<(), NetworkCommissioningStatus> {
+            if network_id.is_empty() || network_id.len() > MAX_NETWORK_ID_LEN {
+                Err(NetworkCommissioningStatus::OutOfRange)
+            } else {
+                Ok(())
+            }
+        }
+
+        fn check_network_type(&self, network_type: NetworkType) -> Result<(), Error> {
+            if self.driver.network_type() != network_type {
+                Err(ErrorCode::CommandNotFound)?;
+            }
+
+            Ok(())
+        }
+
+        fn set_breadcrumb(&self, breadcrumb: Option<u64>) {
+            if let Some(breadcrumb) = breadcrumb {
+                self.failsafe.borrow_mut().set_breadcrumb(breadcrumb);
+            }
+        }
+
+        fn check_failsafe(&self) -> Result<(), Error> {
+            if !self.failsafe.borrow().is_armed() {
+                Err(ErrorCode::FailSafeRequired)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+            if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+                if attr.is_system() {
+                    self.cluster().read(attr.attr_id, writer)
+                } else {
+                    match attr.attr_id.try_into()? {
+                        Attributes::MaxNetworks => {
+                            AttrType::<u8>::new().encode(writer, self.driver.max_networks())
+                        }
+                        Attributes::Networks => {
+                            writer.start_array(AttrDataWriter::TAG)?;
+                            self.driver.networks(&mut |info| {
+                                info.to_tlv(&mut writer, TagType::Anonymous)
+                            })?;
+                            writer.end_container()?;
+                            writer.complete()
+                        }
+                        Attributes::ScanMaxTimeSeconds => {
+                            AttrType::<u8>::new().encode(writer, self.driver.scan_max_time_secs())
+                        }
+                        Attributes::ConnectMaxTimeSecs => AttrType::<u8>::new()
+                            .encode(writer, self.driver.connect_max_time_secs()),
+                        Attributes::InterfaceEnabled => {
+                            AttrType::<bool>::new().encode(writer, self.driver.interface_enabled())
+                        }
+                        Attributes::LastNetworkingStatus => {
+                            writer.set(nullable(self.last_status.get().map(|status| status as u8)))
+                        }
+                        Attributes::LastNetworkID => writer.set(nullable(
+                            self.last_network_id.borrow().as_deref().map(OctetStr::new),
+                        )),
+                        Attributes::LastConnectErrorValue => {
+                            writer.set(nullable(self.last_connect_error.get()))
+                        }
+                        Attributes::SupportedWiFiBands => {
+                            writer.start_array(AttrDataWriter::TAG)?;
+                            for band in self.driver.supported_wifi_bands() {
+                                writer.u8(TagType::Anonymous, *band as _)?;
+                            }
+                            writer.end_container()?;
+                            writer.complete()
+                        }
+                        Attributes::SupportedThreadFeatures => {
+                            AttrType::<u16>::new().encode(writer, self.driver.thread_features())
+                        }
+                        Attributes::ThreadVersion => {
+                            AttrType::<u16>::new().encode(writer, self.driver.thread_version())
+                        }
+                    }
+                }
+            } else {
+                Ok(())
+            }
+        }
+
+        pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+            let data = data.with_dataver(self.data_ver.get())?;
+
+            match attr.attr_id.try_into()? {
+                Attributes::InterfaceEnabled => self
+                    .driver
+                    .set_interface_enabled(AttrType::<bool>::new().decode(data)?)?,
+                _ => Err(ErrorCode::InvalidAction)?,
+            }
+
+            self.data_ver.changed();
+
+            Ok(())
+        }
+
+        pub async fn invoke(
+            &self,
+            _exchange: &Exchange<'_>,
+            cmd: &CmdDetails<'_>,
+            data: &TLVElement<'_>,
+            encoder: CmdDataEncoder<'_, '_, '_>,
+        ) -> Result<(), Error> {
+            match cmd.cmd_id.try_into()? {
+                Commands::ScanNetworks => {
+                    cmd_enter!("ScanNetworks");
+
+                    let req = ScanNetworksReq::from_tlv(data)?;
+                    let ssid = req.ssid.and_then(Nullable::notnull).map(|ssid| ssid.0);
+
+                    let status = self.scan_networks(ssid).await?;
+                    if status == NetworkCommissioningStatus::Success {
+                        self.set_breadcrumb(req.breadcrumb);
+                    }
+
+                    let mut writer =
+                        encoder.with_command(RespCommands::ScanNetworksResponse as _)?;
+
+                    writer.start_struct(CmdDataWriter::TAG)?;
+                    writer.u8(TagType::Context(0), status as _)?;
+                    if status == NetworkCommissioningStatus::Success {
+                        self.encode_scan_results(&mut writer)?;
+                    }
+                    writer.end_container()?;
+
+                    writer.complete()?;
+                }
+                Commands::AddOrUpdateWiFiNetwork => {
+                    cmd_enter!("AddOrUpdateWiFiNetwork");
+                    self.check_network_type(NetworkType::WiFi)?;
+                    self.check_failsafe()?;
+
+                    let req = AddOrUpdateWiFiNetworkReq::from_tlv(data)?;
+                    let result = self.add_or_update_network(&NetworkCredentials::WiFi {
+                        ssid: req.ssid.0,
+                        password: req.credentials.0,
+                    });
+                    if result.is_ok() {
+                        self.set_breadcrumb(req.breadcrumb);
+                    }
+
+                    Self::encode_network_config(encoder, result)?;
+                }
+                Commands::AddOrUpdateThreadNetwork => {
+                    cmd_enter!("AddOrUpdateThreadNetwork");
+                    self.check_network_type(NetworkType::Thread)?;
+                    self.check_failsafe()?;
+
+                    let req = AddOrUpdateThreadNetworkReq::from_tlv(data)?;
+                    let result = self.add_or_update_network(&NetworkCredentials::Thread {
+                        dataset: req.operational_dataset.0,
+                    });
+                    if result.is_ok() {
+                        self.set_breadcrumb(req.breadcrumb);
+                    }
+
+                    Self::encode_network_config(encoder, result)?;
+                }
+                Commands::RemoveNetwork => {
+                    cmd_enter!("RemoveNetwork");
+                    self.check_failsafe()?;
+
+                    let req = NetworkIdReq::from_tlv(data)?;
+                    let result = self.remove_network(req.network_id.0);
+                    if result.is_ok() {
+                        self.set_breadcrumb(req.breadcrumb);
+                    }
+
+                    Self::encode_network_config(encoder, result)?;
+                }
+                Commands::ConnectNetwork => {
+                    cmd_enter!("ConnectNetwork");
+                    self.check_failsafe()?;
+
+                    let req = NetworkIdReq::from_tlv(data)?;
+                    let (status, error_value) = match self.connect_network(req.network_id.0).await {
+                        Ok(()) => {
+                            self.set_breadcrumb(req.breadcrumb);
+                            (NetworkCommissioningStatus::Success, None)
+                        }
+                        Err(outcome) => outcome,
+                    };
+
+                    encoder
+                        .with_command(RespCommands::ConnectNetworkResponse as _)?
+                        .set(ConnectNetworkResp {
+                            networking_status: status as _,
+                            error_value: nullable(error_value),
+                        })?;
+                }
+                Commands::ReorderNetwork => {
+                    cmd_enter!("ReorderNetwork");
+                    self.check_failsafe()?;
+
+                    let req = ReorderNetworkReq::from_tlv(data)?;
+                    let result = self
+                        .reorder_network(req.network_id.0, req.network_index)
+                        .map(|_| req.network_index);
+                    if result.is_ok() {
+                        self.set_breadcrumb(req.breadcrumb);
+                    }
+
+                    Self::encode_network_config(encoder, result)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        fn encode_scan_results(&self, tw: &mut TLVWriter) -> Result<(), Error> {
+            let tag = match self.driver.network_type() {
+                NetworkType::WiFi => 2,
+                NetworkType::Thread => 3,
+            };
+
+            tw.start_array(TagType::Context(tag))?;
+            self.driver.scan_results(&mut |result| match result {
+                ScanResult::WiFi(result) => result.to_tlv(tw, TagType::Anonymous),
+                ScanResult::Thread(result) => result.to_tlv(tw, TagType::Anonymous),
+            })?;
+            tw.end_container()
+        }
+
+        fn encode_network_config(
+            encoder: CmdDataEncoder,
+            result: Result<u8, NetworkCommissioningStatus>,
+        ) -> Result<(), Error> {
+            let resp = match result {
+                Ok(index) => NetworkConfigResp {
+                    networking_status: NetworkCommissioningStatus::Success as _,
+                    network_index: Some(index),
+                },
+                Err(status) => NetworkConfigResp {
+                    networking_status: status as _,
+                    network_index: None,
+                },
+            };
+
+            encoder
+                .with_command(RespCommands::NetworkConfigResponse as _)?
+                .set(resp)
+        }
+    }
+
+    impl<'a, T> AsyncHandler for WirelessNwCommCluster<'a, T>
+    where
+        T: NetworkDriver,
+    {
+        async fn read<'m>(
+            &'m self,
+            attr: &'m AttrDetails<'_>,
+            encoder: AttrDataEncoder<'m, '_, '_>,
+        ) -> Result<(), Error> {
+            WirelessNwCommCluster::read(self, attr, encoder)
+        }
+
+        async fn write<'m>(
+            &'m self,
+            attr: &'m AttrDetails<'_>,
+            data: AttrData<'m>,
+        ) -> Result<(), Error> {
+            WirelessNwCommCluster::write(self, attr, data)
+        }
+
+        async fn invoke<'m>(
+            &'m self,
+            exchange: &'m Exchange<'_>,
+            cmd: &'m CmdDetails<'_>,
+            data: &'m TLVElement<'_>,
+            encoder: CmdDataEncoder<'m, '_, '_>,
+        ) -> Result<(), Error> {
+            WirelessNwCommCluster::invoke(self, exchange, cmd, data, encoder).await
+        }
+
+        fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+            Some(self.data_ver.get())
+        }
+
+        fn failsafe_expired(&self) {
+            WirelessNwCommCluster::failsafe_expired(self)
+        }
+    }
+
+    impl<'a, T> ChangeNotifier<()> for WirelessNwCommCluster<'a, T> {
+        fn consume_change(&mut self) -> Option<()> {
+            self.data_ver.consume_change(())
+        }
+    }
+
+    fn nullable<T>(value: Option<T>) -> Nullable<T> {
+        match value {
+            Some(value) => Nullable::NotNull(value),
+            None => Nullable::Null,
+        }
+    }
+
+    const MOCK_MAX_NETWORKS: usize = 4;
+
+    #[derive(Clone)]
+    struct MockNetwork {
+        id: heapless::Vec<u8, MAX_NETWORK_ID_LEN>,
+        credentials: heapless::Vec<u8, MAX_THREAD_DATASET_LEN>,
+    }
+
+    impl MockNetwork {
+        fn new(credentials: &NetworkCredentials) -> Option<Self> {
+            let credentials_data = match credentials {
+                NetworkCredentials::WiFi { password, .. } => password,
+                NetworkCredentials::Thread { dataset } => dataset,
+            };
+
+            Some(Self {
+                id: heapless::Vec::from_slice(credentials.network_id().ok()?).ok()?,
+                credentials: heapless::Vec::from_slice(credentials_data).ok()?,
+            })
+        }
+    }
+
+    /// A network driver which only pretends to scan and connect
+    ///
+    /// The networks "in range" are the ones registered with `add_visible`, and connecting
+    /// to one of them only succeeds if the configured credentials match. Useful for tests,
+    /// and for running the Wi-Fi or Thread flavour of the cluster on a host.
+    pub struct MockNetworkDriver {
+        network_type: NetworkType,
+        enabled: Cell<bool>,
+        networks: RefCell<heapless::Vec<MockNetwork, MOCK_MAX_NETWORKS>>,
+        saved: RefCell<Option<heapless::Vec<MockNetwork, MOCK_MAX_NETWORKS>>>,
+        visible: RefCell<heapless::Vec<MockNetwork, MOCK_MAX_NETWORKS>>,
+        scan_filter: RefCell<Option<heapless::Vec<u8, MAX_NETWORK_ID_LEN>>>,
+        connected: RefCell<Option<heapless::Vec<u8, MAX_NETWORK_ID_LEN>>>,
+        connect_max_time_secs: Cell<u8>,
+        unresponsive: Cell<bool>,
+        concurrent: Cell<bool>,
+    }
+
+    impl MockNetworkDriver {
+        pub const fn new(network_type: NetworkType) -> Self {
+            Self {
+                network_type,
+                enabled: Cell::new(true),
+                networks: RefCell::new(heapless::Vec::new()),
+                saved: RefCell::new(None),
+                visible: RefCell::new(heapless::Vec::new()),
+                scan_filter: RefCell::new(None),
+                connected: RefCell::new(None),
+                connect_max_time_secs: Cell::new(20),
+                unresponsive: Cell::new(false),
+                concurrent: Cell::new(false),
+            }
+        }
+
+        /// Makes a network visible to scans, and connectable with the given credentials
+        pub fn add_visible(&self, credentials: &NetworkCredentials) -> Result<(), Error> {
+            let network = MockNetwork::new(credentials).ok_or(ErrorCode::InvalidData)?;
+
+            self.visible
+                .borrow_mut()
+                .push(network)
+                .map_err(|_| ErrorCode::NoSpace.into())
+        }
+
+        pub fn set_connect_max_time_secs(&self, secs: u8) {
+            self.connect_max_time_secs.set(secs);
+        }
+
+        /// Makes scanning and connecting never complete, as with a stuck radio
+        pub fn set_unresponsive(&self, unresponsive: bool) {
+            self.unresponsive.set(unresponsive);
+        }
+
+        /// Makes the driver claim that the node stays reachable while connecting
+        pub fn set_concurrent_connection(&self, concurrent: bool) {
+            self.concurrent.set(concurrent);
+        }
+
+        /// The ID of the network currently connected to, if any
+        pub fn connected(&self, f: impl FnOnce(Option<&[u8]>)) {
+            f(self.connected.borrow().as_deref())
+        }
+
+        fn position(&self, network_id: &[u8]) -> Result<usize, NetworkCommissioningStatus> {
+            self.networks
+                .borrow()
+                .iter()
+                .position(|network| network.id == network_id)
+                .ok_or(NetworkCommissioningStatus::NetworkIDNotFound)
+        }
+    }
+
+    impl NetworkDriver for MockNetworkDriver {
+        fn network_type(&self) -> NetworkType {
+            self.network_type
+        }
+
+        fn max_networks(&self) -> u8 {
+            MOCK_MAX_NETWORKS as _
+        }
+
+        fn scan_max_time_secs(&self) -> u8 {
+            10
+        }
+
+        fn connect_max_time_secs(&self) -> u8 {
+            self.connect_max_time_secs.get()
+        }
+
+        fn interface_enabled(&self) -> bool {
+            self.enabled.get()
+        }
+
+        fn set_interface_enabled(&self, enabled: bool) -> Result<(), Error> {
+            self.enabled.set(enabled);
+
+            if !enabled {
+                *self.connected.borrow_mut() = None;
+            }
+
+            Ok(())
+        }
+
+        fn supports_concurrent_connection(&self) -> bool {
+            self.concurrent.get()
+        }
+
+        fn save_networks(&self) -> Result<(), Error> {
+            *self.saved.borrow_mut() = Some(self.networks.borrow().clone());
+
+            Ok(())
+        }
+
+        fn restore_networks(&self) -> Result<(), Error> {
+            if let Some(saved) = self.saved.borrow_mut().take() {
+                let mut connected = self.connected.borrow_mut();
+                if !saved
+                    .iter()
+                    .any(|network| Some(&network.id) == connected.as_ref())
+                {
+                    *connected = None;
+                }
+
+                *self.networks.borrow_mut() = saved;
+            }
+
+            Ok(())
+        }
+
+        fn networks(&self, f: &mut dyn FnMut(&NwInfo) -> Result<(), Error>) -> Result<(), Error> {
+            let connected = self.connected.borrow();
+
+            for network in self.networks.borrow().iter() {
+                f(&NwInfo {
+                    network_id: OctetStr::new(&network.id),
+                    connected: connected.as_ref() == Some(&network.id),
+                })?;
+            }
+
+            Ok(())
+        }
+
+        async fn scan(&self, ssid: Option<&[u8]>) -> Result<(), NetworkCommissioningStatus> {
+            if self.unresponsive.get() {
+                pending::<()>().await;
+            }
+
+            if !self.enabled.get() {
+                Err(NetworkCommissioningStatus::UnknownError)?;
+            }
+
+            *self.scan_filter.borrow_mut() =
+                ssid.and_then(|ssid| heapless::Vec::from_slice(ssid).ok());
+
+            Ok(())
+        }
+
+        fn scan_results(
+            &self,
+            f: &mut dyn FnMut(&ScanResult) -> Result<(), Error>,
+        ) -> Result<(), Error> {
+            let scan_filter = self.scan_filter.borrow();
+
+            for network in self.visible.borrow().iter() {
+                if scan_filter
+                    .as_ref()
+                    .map(|ssid| *ssid != network.id)
+                    .unwrap_or(false)
+                {
+                    continue;
+                }
+
+                let result = match self.network_type {
+                    NetworkType::WiFi => ScanResult::WiFi(WiFiScanResult {
+                        security: wifi_security::WPA2_PERSONAL,
+                        ssid: OctetStr::new(&network.id),
+                        bssid: OctetStr::new(&[0x02, 0, 0, 0, 0, 0x01]),
+                        channel: 6,
+                        wifi_band: Some(WiFiBand::Band2G4 as _),
+                        rssi: Some(-50),
+                    }),
+                    NetworkType::Thread => ScanResult::Thread(ThreadScanResult {
+                        pan_id: 0x1234,
+                        extended_pan_id: network
+                            .id
+                            .iter()
+                            .fold(0, |id, byte| (id << 8) | *byte as u64),
+                        network_name: UtfStr::new(b"MockThread"),
+                        channel: 15,
+                        version: 4,
+                        extended_address: OctetStr::new(&[0x02, 0, 0, 0, 0, 0, 0, 0x01]),
+                        rssi: -50,
+                        lqi: 255,
+                    }),
+                };
+
+                f(&result)?;
+            }
+
+            Ok(())
+        }
+
+        fn add_or_update(
+            &self,
+            credentials: &NetworkCredentials,
+        ) -> Result<u8, NetworkCommissioningStatus> {
+            if credentials.network_type() != self.network_type {
+                Err(NetworkCommissioningStatus::UnknownError)?;
+            }
+
+            let network =
+                MockNetwork::new(credentials).ok_or(NetworkCommissioningStatus::OutOfRange)?;
+
+            let mut networks = self.networks.borrow_mut();
+
+            if let Some(index) = networks.iter().position(|other| other.id == network.id) {
+                networks[index] = network;
+
+                Ok(index as _)
+            } else {
+                networks
+                    .push(network)
+                    .map_err(|_| NetworkCommissioningStatus::BoundsExceeded)?;
+
+                Ok((networks.len() - 1) as _)
+            }
+        }
+
+        fn remove(&self, network_id: &[u8]) -> Result<u8, NetworkCommissioningStatus> {
+            let index = self.position(network_id)?;
+
+            let mut networks = self.networks.borrow_mut();
+            networks[index..].rotate_left(1);
+            networks.pop();
+
+            let mut connected = self.connected.borrow_mut();
+            if connected.as_deref() == Some(network_id) {
+                *connected = None;
+            }
+
+            Ok(index as _)
+        }
+
+        fn reorder(&self, network_id: &[u8], index: u8) -> Result<(), NetworkCommissioningStatus> {
+            let from = self.position(network_id)?;
+            let to = index as usize;
+
+            let mut networks = self.networks.borrow_mut();
+            if to >= networks.len() {
+                Err(NetworkCommissioningStatus::OutOfRange)?;
+            }
+
+            if from < to {
+                networks[from..=to].rotate_left(1);
+            } else {
+                networks[to..=from].rotate_right(1);
+            }
+
+            Ok(())
+        }
+
+        async fn connect(
+            &self,
+            network_id: &[u8],
+        ) -> Result<(), (NetworkCommissioningStatus, Option<i32>)> {
+            if self.unresponsive.get() {
+                pending::<()>().await;
+            }
+
+            let index = self.position(network_id).map_err(|status| (status, None))?;
+
+            if !self.enabled.get() {
+                return Err((NetworkCommissioningStatus::OtherConnectionFailure, None));
+            }
+
+            let networks = self.networks.borrow();
+            let visible = self.visible.borrow();
+
+            let network = visible
+                .iter()
+                .find(|network| network.id == network_id)
+                .ok_or((NetworkCommissioningStatus::NetworkNotFound, None))?;
+
+            if network.credentials != networks[index].credentials {
+                // Mimics the 802.11 "reason code" of a failed authentication
+                return Err((NetworkCommissioningStatus::AuthFailure, Some(15)));
+            }
+
+            *self.connected.borrow_mut() = Some(network.id.clone());
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use core::cell::RefCell;
+        use core::time::Duration;
+
+        use embassy_futures::block_on;
+
+        use crate::{
+            data_model::sdm::failsafe::FailSafe, transport::session::SessionMode,
+            utils::rand::dummy_rand,
+        };
+
+        use super::super::tests::DATASET;
+        use super::{
+            MockNetworkDriver, NetworkCommissioningStatus, NetworkCredentials, NetworkDriver,
+            NetworkType, WirelessNwCommCluster,
+        };
+
+        const WIFI: NetworkCredentials = NetworkCredentials::WiFi {
+            ssid: b"home",
+            password: b"secret",
+        };
+
+        #[test]
+        fn test_configure_networks() {
+            let failsafe = RefCell::new(FailSafe::new());
+            let driver = MockNetworkDriver::new(NetworkType::WiFi);
+            let nw = WirelessNwCommCluster::new(&failsafe, &driver, dummy_rand);
+
+            assert_eq!(nw.add_or_update_network(&WIFI), Ok(0));
+            assert_eq!(
+                nw.add_or_update_network(&NetworkCredentials::WiFi {
+                    ssid: b"office",
+                    password: b"secret",
+                }),
+                Ok(1)
+            );
+
+            // Updating keeps the index
+            assert_eq!(
+                nw.add_or_update_network(&NetworkCredentials::WiFi {
+                    ssid: b"home",
+                    password: b"other",
+                }),
+                Ok(0)
+            );
+
+            assert_eq!(
+                nw.add_or_update_network(&NetworkCredentials::WiFi {
+                    ssid: b"",
+                    password: b"secret",
+                }),
+                Err(NetworkCommissioningStatus::OutOfRange)
+            );
+
+            assert_eq!(nw.reorder_network(b"office", 0), Ok(()));
+            assert_eq!(
+                nw.reorder_network(b"office", 2),
+                Err(NetworkCommissioningStatus::OutOfRange)
+            );
+            assert_eq!(nw.remove_network(b"home"), Ok(1));
+            assert_eq!(
+                nw.remove_network(b"home"),
+                Err(NetworkCommissioningStatus::NetworkIDNotFound)
+            );
+
+            let mut ids = heapless::Vec::<_, 4>::new();
+            driver
+                .networks(&mut |info| {
+                    ids.push(heapless::Vec::<u8, 32>::from_slice(info.network_id.0).unwrap())
+                        .unwrap();
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!(ids.len(), 1);
+            assert_eq!(ids[0].as_slice(), b"office");
+        }
+
+        #[test]
+        fn test_connect() {
+            let failsafe = RefCell::new(FailSafe::new());
+            let driver = MockNetworkDriver::new(NetworkType::WiFi);
+            let nw = WirelessNwCommCluster::new(&failsafe, &driver, dummy_rand);
+
+            assert_eq!(nw.last_networking_status(), None);
+
+            assert_eq!(
+                block_on(nw.connect_network(b"home")),
+                Err((NetworkCommissioningStatus::NetworkIDNotFound, None))
+            );
+
+            nw.add_or_update_network(&WIFI).unwrap();
+            assert_eq!(block_on(nw.connect_network(b"home")), Ok(()));
+
+            // Only reported once the scheduled connection was made
+            assert_eq!(nw.last_networking_status(), None);
+            block_on(nw.connect_scheduled());
+            assert_eq!(
+                nw.last_networking_status(),
+                Some(NetworkCommissioningStatus::NetworkNotFound)
+            );
+
+            driver
+                .add_visible(&NetworkCredentials::WiFi {
+                    ssid: b"home",
+                    password: b"other",
+                })
+                .unwrap();
+            block_on(nw.connect_network(b"home")).unwrap();
+            block_on(nw.connect_scheduled());
+            assert_eq!(
+                nw.last_networking_status(),
+                Some(NetworkCommissioningStatus::AuthFailure)
+            );
+            assert_eq!(nw.last_connect_error_value(), Some(15));
+
+            nw.add_or_update_network(&NetworkCredentials::WiFi {
+                ssid: b"home",
+                password: b"other",
+            })
+            .unwrap();
+            block_on(nw.connect_network(b"home")).unwrap();
+            block_on(nw.connect_scheduled());
+            assert_eq!(
+                nw.last_networking_status(),
+                Some(NetworkCommissioningStatus::Success)
+            );
+            assert_eq!(nw.last_connect_error_value(), None);
+            driver.connected(|id| assert_eq!(id, Some(&b"home"[..])));
+        }
+
+        #[test]
+        fn test_connect_concurrently() {
+            // The outcome is known when ConnectNetwork is answered
+            let failsafe = RefCell::new(FailSafe::new());
+            let driver = MockNetworkDriver::new(NetworkType::WiFi);
+            let nw = WirelessNwCommCluster::new(&failsafe, &driver, dummy_rand);
+
+            driver.set_concurrent_connection(true);
+            driver
+                .add_visible(&NetworkCredentials::WiFi {
+                    ssid: b"home",
+                    password: b"other",
+                })
+                .unwrap();
+
+            nw.add_or_update_network(&WIFI).unwrap();
+            assert_eq!(
+                block_on(nw.connect_network(b"home")),
+                Err((NetworkCommissioningStatus::AuthFailure, Some(15)))
+            );
+            assert_eq!(
+                nw.last_networking_status(),
+                Some(NetworkCommissioningStatus::AuthFailure)
+            );
+
+            nw.add_or_update_network(&NetworkCredentials::WiFi {
+                ssid: b"home",
+                password: b"other",
+            })
+            .unwrap();
+            assert_eq!(block_on(nw.connect_network(b"home")), Ok(()));
+            driver.connected(|id| assert_eq!(id, Some(&b"home"[..])));
+        }
+
+        #[test]
+        fn test_failsafe_expired() {
+            // The networks configured while the fail-safe was armed are reverted
+            let failsafe = RefCell::new(FailSafe::new());
+            let driver = MockNetworkDriver::new(NetworkType::WiFi);
+            let nw = WirelessNwCommCluster::new(&failsafe, &driver, dummy_rand);
+
+            nw.add_or_update_network(&WIFI).unwrap();
+
+            failsafe
+                .borrow_mut()
+                .arm(60, SessionMode::Pase, Duration::from_secs(0))
+                .unwrap();
+
+            nw.add_or_update_network(&NetworkCredentials::WiFi {
+                ssid: b"office",
+                password: b"secret",
+            })
+            .unwrap();
+            nw.remove_network(b"home").unwrap();
+
+            assert!(failsafe.borrow_mut().expire(Duration::from_secs(60)));
+            assert!(failsafe.borrow_mut().take_expired());
+            nw.failsafe_expired();
+
+            let mut ids = heapless::Vec::<_, 4>::new();
+            driver
+                .networks(&mut |info| {
+                    ids.push(heapless::Vec::<u8, 32>::from_slice(info.network_id.0).unwrap())
+                        .unwrap();
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!(ids.len(), 1);
+            assert_eq!(ids[0].as_slice(), b"home");
+        }
+
+        #[test]
+        fn test_connect_timeout() {
+            // The cluster gives up on a driver which does not connect within ConnectMaxTimeSecs
+            let failsafe = RefCell::new(FailSafe::new());
+            let driver = MockNetworkDriver::new(NetworkType::WiFi);
+            let nw = WirelessNwCommCluster::new(&failsafe, &driver, dummy_rand);
+
+            nw.add_or_update_network(&WIFI).unwrap();
+            driver.add_visible(&WIFI).unwrap();
+
+            driver.set_connect_max_time_secs(1);
+            driver.set_unresponsive(true);
+
+            block_on(nw.connect_network(b"home")).unwrap();
+            block_on(nw.connect_scheduled());
+            assert_eq!(
+                nw.last_networking_status(),
+                Some(NetworkCommissioningStatus::OtherConnectionFailure)
+            );
+            assert_eq!(nw.last_connect_error_value(), None);
+            driver.connected(|id| assert_eq!(id, None));
+
+            driver.set_unresponsive(false);
+            block_on(nw.connect_network(b"home")).unwrap();
+            block_on(nw.connect_scheduled());
+            assert_eq!(
+                nw.last_networking_status(),
+                Some(NetworkCommissioningStatus::Success)
+            );
+        }
+
+        #[test]
+        fn test_thread_network() {
+            let failsafe = RefCell::new(FailSafe::new());
+            let driver = MockNetworkDriver::new(NetworkType::Thread);
+            let nw = WirelessNwCommCluster::new(&failsafe, &driver, dummy_rand);
+
+            let credentials = NetworkCredentials::Thread { dataset: DATASET };
+
+            // No Extended PAN ID
+            assert_eq!(
+                nw.add_or_update_network(&NetworkCredentials::Thread {
+                    dataset: &DATASET[..10]
+                }),
+                Err(NetworkCommissioningStatus::OutOfRange)
+            );
+
+            assert_eq!(nw.add_or_update_network(&credentials), Ok(0));
+            driver.add_visible(&credentials).unwrap();
+
+            assert_eq!(
+                block_on(nw.scan_networks(Some(&b"ignored"[..]))),
+                Ok(NetworkCommissioningStatus::Success)
+            );
+
+            let mut found = 0;
+            driver
+                .scan_results(&mut |_| {
+                    found += 1;
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!(found, 1);
+
+            block_on(nw.connect_network(&[0xde, 0xad, 0x00, 0xbe, 0xef, 0x00, 0xca, 0xfe]))
+                .unwrap();
+            block_on(nw.connect_scheduled());
+            assert_eq!(
+                nw.last_networking_status(),
+                Some(NetworkCommissioningStatus::Success)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::thread_ext_pan_id;
+
+    pub const DATASET: &[u8] = &[
+        0x0e, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, // Active Timestamp
+        0x02, 0x08, 0xde, 0xad, 0x00, 0xbe, 0xef, 0x00, 0xca, 0xfe, // Extended PAN ID
+        0x01, 0x02, 0x12, 0x34, // PAN ID
+    ];
+
+    #[test]
+    fn test_thread_ext_pan_id() {
+        assert_eq!(
+            thread_ext_pan_id(DATASET).unwrap(),
+            &[0xde, 0xad, 0x00, 0xbe, 0xef, 0x00, 0xca, 0xfe]
+        );
+
+        // Truncated in the middle of the Extended PAN ID
+        assert!(thread_ext_pan_id(&DATASET[..14]).is_err());
+        assert!(thread_ext_pan_id(&DATASET[20..]).is_err());
+    }
+}
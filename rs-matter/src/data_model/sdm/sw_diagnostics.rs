@@ -0,0 +1,322 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+use core::convert::TryInto;
+
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    data_model::objects::AttrType,
+    data_model::objects::*,
+    error::Error,
+    tlv::{TLVElement, TLVWriter, TagType, ToTLV, UtfStr},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0034;
+
+/// The maximum length of the name of a thread in the ThreadMetrics attribute
+pub const MAX_THREAD_NAME_LEN: usize = 8;
+
+enum FeatureMap {
+    Watermarks = 0x01,
+}
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    ThreadMetrics(()) = 0x00,
+    CurrentHeapFree(AttrType<u64>) = 0x01,
+    CurrentHeapUsed(AttrType<u64>) = 0x02,
+    CurrentHeapHighWatermark(AttrType<u64>) = 0x03,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    ResetWatermarks = 0x0,
+}
+
+command_enum!(Commands);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Events {
+    SoftwareFault = 0x00,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::Watermarks as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::ThreadMetrics as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentHeapFree as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentHeapUsed as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentHeapHighWatermark as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+    ],
+    commands: &[CommandsDiscriminants::ResetWatermarks as _],
+};
+
+/// An entry of the ThreadMetrics attribute
+///
+/// The stack sizes are in bytes, and left out when the platform cannot tell.
+#[derive(ToTLV)]
+pub struct ThreadMetrics<'a> {
+    pub id: u64,
+    pub name: Option<UtfStr<'a>>,
+    pub stack_free_current: Option<u32>,
+    pub stack_free_minimum: Option<u32>,
+    pub stack_size: Option<u32>,
+}
+
+/// The platform-specific memory and thread statistics of the node
+///
+/// The heap sizes are in bytes.
+pub trait SwDiagnostics {
+    /// Calls `f` for each thread, or task, of the node
+    fn thread_metrics(
+        &self,
+        _f: &mut dyn FnMut(&ThreadMetrics) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn current_heap_free(&self) -> u64 {
+        0
+    }
+
+    fn current_heap_used(&self) -> u64 {
+        0
+    }
+
+    /// The maximum heap used since boot, or since the watermarks were last reset
+    fn current_heap_high_watermark(&self) -> u64 {
+        0
+    }
+
+    /// Resets the heap high watermark and the minimum free stack of the threads
+    /// to their current values
+    fn reset_watermarks(&self) {}
+}
+
+/// A platform without memory or thread statistics
+pub struct NoSwDiagnostics;
+
+impl SwDiagnostics for NoSwDiagnostics {}
+
+pub struct SwDiagCluster<'a> {
+    data_ver: Dataver,
+    platform: &'a dyn SwDiagnostics,
+}
+
+impl<'a> SwDiagCluster<'a> {
+    pub fn new(platform: &'a dyn SwDiagnostics, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            platform,
+        }
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::ThreadMetrics(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        self.platform.thread_metrics(&mut |metrics| {
+                            metrics.to_tlv(&mut writer, TagType::Anonymous)
+                        })?;
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::CurrentHeapFree(codec) => {
+                        codec.encode(writer, self.platform.current_heap_free())
+                    }
+                    Attributes::CurrentHeapUsed(codec) => {
+                        codec.encode(writer, self.platform.current_heap_used())
+                    }
+                    Attributes::CurrentHeapHighWatermark(codec) => {
+                        codec.encode(writer, self.platform.current_heap_high_watermark())
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        _data: &TLVElement,
+        _encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::ResetWatermarks => {
+                cmd_enter!("ResetWatermarks");
+
+                self.platform.reset_watermarks();
+            }
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+}
+
+impl<'a> Handler for SwDiagCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        SwDiagCluster::read(self, attr, encoder)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        SwDiagCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for SwDiagCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for SwDiagCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod sys {
+    use std::fs;
+
+    use crate::error::Error;
+    use crate::tlv::UtfStr;
+
+    use super::{SwDiagnostics, ThreadMetrics, MAX_THREAD_NAME_LEN};
+
+    /// The statistics of the current process, as reported by `procfs`
+    ///
+    /// The heap is approximated by the resident memory of the process, and the stacks
+    /// of the threads are not reported.
+    pub struct SysSwDiagnostics;
+
+    impl SysSwDiagnostics {
+        // The value of a `kB` field of a file like `/proc/self/status`, in bytes
+        fn proc_kb(path: &str, field: &str) -> u64 {
+            fs::read_to_string(path)
+                .ok()
+                .and_then(|status| {
+                    status.lines().find_map(|line| {
+                        line.strip_prefix(field)
+                            .and_then(|value| value.strip_prefix(':'))
+                            .and_then(|value| {
+                                value.trim().trim_end_matches("kB").trim().parse().ok()
+                            })
+                    })
+                })
+                .map(|kb: u64| kb * 1024)
+                .unwrap_or(0)
+        }
+    }
+
+    impl SwDiagnostics for SysSwDiagnostics {
+        fn thread_metrics(
+            &self,
+            f: &mut dyn FnMut(&ThreadMetrics) -> Result<(), Error>,
+        ) -> Result<(), Error> {
+            for task in fs::read_dir("/proc/self/task")? {
+                let task = task?;
+
+                let id = if let Some(id) = task.file_name().to_str().and_then(|id| id.parse().ok())
+                {
+                    id
+                } else {
+                    continue;
+                };
+
+                let comm = fs::read_to_string(task.path().join("comm")).unwrap_or_default();
+                let name = comm.trim();
+
+                let mut end = name.len().min(MAX_THREAD_NAME_LEN);
+                while !name.is_char_boundary(end) {
+                    end -= 1;
+                }
+
+                let name = &name[..end];
+
+                f(&ThreadMetrics {
+                    id,
+                    name: (!name.is_empty()).then(|| UtfStr(name.as_bytes())),
+                    stack_free_current: None,
+                    stack_free_minimum: None,
+                    stack_size: None,
+                })?;
+            }
+
+            Ok(())
+        }
+
+        fn current_heap_free(&self) -> u64 {
+            Self::proc_kb("/proc/meminfo", "MemAvailable")
+        }
+
+        fn current_heap_used(&self) -> u64 {
+            Self::proc_kb("/proc/self/status", "VmRSS")
+        }
+
+        fn current_heap_high_watermark(&self) -> u64 {
+            Self::proc_kb("/proc/self/status", "VmHWM")
+        }
+
+        fn reset_watermarks(&self) {
+            // Resets the peak resident set size, i.e. `VmHWM`
+            let _ = fs::write("/proc/self/clear_refs", "5");
+        }
+    }
+}
@@ -0,0 +1,487 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+use core::convert::TryInto;
+
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    data_model::objects::AttrType,
+    data_model::objects::*,
+    error::Error,
+    tlv::{Nullable, OctetStr, TLVElement, TLVWriter, TagType, ToTLV, UtfStr},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0035;
+
+enum FeatureMap {
+    ErrorCounts = 0x02,
+}
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    Channel(AttrType<Nullable<u16>>) = 0x00,
+    RoutingRole(AttrType<Nullable<u8>>) = 0x01,
+    NetworkName(()) = 0x02,
+    PanId(AttrType<Nullable<u16>>) = 0x03,
+    ExtendedPanId(AttrType<Nullable<u64>>) = 0x04,
+    MeshLocalPrefix(()) = 0x05,
+    OverrunCount(AttrType<u64>) = 0x06,
+    NeighborTable(()) = 0x07,
+    RouteTable(()) = 0x08,
+    PartitionId(AttrType<Nullable<u32>>) = 0x09,
+    Weighting(AttrType<Nullable<u8>>) = 0x0A,
+    DataVersion(AttrType<Nullable<u8>>) = 0x0B,
+    StableDataVersion(AttrType<Nullable<u8>>) = 0x0C,
+    LeaderRouterId(AttrType<Nullable<u8>>) = 0x0D,
+    ActiveTimestamp(AttrType<Nullable<u64>>) = 0x38,
+    PendingTimestamp(AttrType<Nullable<u64>>) = 0x39,
+    Delay(AttrType<Nullable<u32>>) = 0x3A,
+    SecurityPolicy(AttrType<Nullable<SecurityPolicy>>) = 0x3B,
+    ChannelPage0Mask(()) = 0x3C,
+    OperationalDatasetComponents(AttrType<Nullable<OperationalDatasetComponents>>) = 0x3D,
+    ActiveNetworkFaultsList(()) = 0x3E,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    ResetCounts = 0x0,
+}
+
+command_enum!(Commands);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Events {
+    ConnectionStatus = 0x00,
+    NetworkFaultChange = 0x01,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::ErrorCounts as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::Channel as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::RoutingRole as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NetworkName as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PanId as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ExtendedPanId as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::MeshLocalPrefix as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OverrunCount as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NeighborTable as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::RouteTable as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PartitionId as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Weighting as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::DataVersion as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::StableDataVersion as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::LeaderRouterId as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveTimestamp as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PendingTimestamp as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Delay as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SecurityPolicy as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ChannelPage0Mask as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OperationalDatasetComponents as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveNetworkFaultsList as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+    ],
+    commands: &[CommandsDiscriminants::ResetCounts as _],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingRole {
+    Unspecified = 0,
+    Unassigned = 1,
+    SleepyEndDevice = 2,
+    EndDevice = 3,
+    Reed = 4,
+    Router = 5,
+    Leader = 6,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkFault {
+    Unspecified = 0,
+    LinkDown = 1,
+    HardwareFailure = 2,
+    NetworkJammed = 3,
+}
+
+#[derive(ToTLV)]
+pub struct NeighborTable {
+    pub ext_address: u64,
+    pub age: u32,
+    pub rloc16: u16,
+    pub link_frame_counter: u32,
+    pub mle_frame_counter: u32,
+    pub lqi: u8,
+    pub average_rssi: Nullable<i8>,
+    pub last_rssi: Nullable<i8>,
+    pub frame_error_rate: u8,
+    pub message_error_rate: u8,
+    pub rx_on_when_idle: bool,
+    pub full_thread_device: bool,
+    pub full_network_data: bool,
+    pub is_child: bool,
+}
+
+#[derive(ToTLV)]
+pub struct RouteTable {
+    pub ext_address: u64,
+    pub rloc16: u16,
+    pub router_id: u8,
+    pub next_hop: u8,
+    pub path_cost: u8,
+    pub lqi_in: u8,
+    pub lqi_out: u8,
+    pub age: u8,
+    pub allocated: bool,
+    pub link_established: bool,
+}
+
+#[derive(ToTLV, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SecurityPolicy {
+    pub rotation_time: u16,
+    pub flags: u16,
+}
+
+/// Which components the Active Operational Dataset of the node has
+#[derive(ToTLV, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OperationalDatasetComponents {
+    pub active_timestamp_present: bool,
+    pub pending_timestamp_present: bool,
+    pub master_key_present: bool,
+    pub network_name_present: bool,
+    pub extended_pan_id_present: bool,
+    pub mesh_local_prefix_present: bool,
+    pub delay_present: bool,
+    pub pan_id_present: bool,
+    pub channel_present: bool,
+    pub pskc_present: bool,
+    pub security_policy_present: bool,
+    pub channel_mask_present: bool,
+}
+
+/// The Thread network the node is attached to
+pub struct ThreadNwInfo<'a> {
+    pub channel: u16,
+    pub routing_role: RoutingRole,
+    pub network_name: &'a str,
+    pub pan_id: u16,
+    pub extended_pan_id: u64,
+    /// The /64 Mesh-Local Prefix
+    pub mesh_local_prefix: [u8; 8],
+    pub partition_id: u32,
+    pub weighting: u8,
+    pub data_version: u8,
+    pub stable_data_version: u8,
+    pub leader_router_id: u8,
+    pub active_timestamp: Option<u64>,
+    pub pending_timestamp: Option<u64>,
+    pub delay: Option<u32>,
+    pub security_policy: Option<SecurityPolicy>,
+    pub channel_page0_mask: Option<[u8; 4]>,
+    pub dataset_components: Option<OperationalDatasetComponents>,
+}
+
+/// The platform-specific diagnostics of the Thread interface, e.g. on top of
+/// the diagnostics API of OpenThread
+pub trait ThreadNwDiagnostics {
+    /// Calls `f` once with the network the node is attached to, or with `None` if the node
+    /// is not attached, in which case all attributes of the network are reported as null
+    fn network_info(
+        &self,
+        f: &mut dyn FnMut(Option<&ThreadNwInfo>) -> Result<(), Error>,
+    ) -> Result<(), Error>;
+
+    fn neighbors(
+        &self,
+        _f: &mut dyn FnMut(&NeighborTable) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn routes(&self, _f: &mut dyn FnMut(&RouteTable) -> Result<(), Error>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn active_network_faults(
+        &self,
+        _f: &mut dyn FnMut(NetworkFault) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// The number of packets dropped for the lack of buffers since the last reset
+    fn overrun_count(&self) -> u64 {
+        0
+    }
+
+    /// Resets the counts, on a ResetCounts command
+    fn reset_counts(&self) {}
+}
+
+pub struct ThreadNwDiagCluster<'a> {
+    data_ver: Dataver,
+    platform: &'a dyn ThreadNwDiagnostics,
+}
+
+impl<'a> ThreadNwDiagCluster<'a> {
+    pub fn new(platform: &'a dyn ThreadNwDiagnostics, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            platform,
+        }
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::OverrunCount(codec) => {
+                        codec.encode(writer, self.platform.overrun_count())
+                    }
+                    Attributes::NeighborTable(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        self.platform.neighbors(&mut |neighbor| {
+                            neighbor.to_tlv(&mut writer, TagType::Anonymous)
+                        })?;
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::RouteTable(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        self.platform
+                            .routes(&mut |route| route.to_tlv(&mut writer, TagType::Anonymous))?;
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::ActiveNetworkFaultsList(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        self.platform.active_network_faults(&mut |fault| {
+                            writer.u8(TagType::Anonymous, fault as _)
+                        })?;
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    attribute => {
+                        self.platform.network_info(&mut |info| {
+                            Self::encode_network_info(&attribute, info, &mut writer)
+                        })?;
+                        writer.complete()
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    fn encode_network_info(
+        attribute: &Attributes,
+        info: Option<&ThreadNwInfo>,
+        tw: &mut TLVWriter,
+    ) -> Result<(), Error> {
+        let tag = AttrDataWriter::TAG;
+
+        let info = if let Some(info) = info {
+            info
+        } else {
+            return tw.null(tag);
+        };
+
+        match attribute {
+            Attributes::Channel(_) => tw.u16(tag, info.channel),
+            Attributes::RoutingRole(_) => tw.u8(tag, info.routing_role as _),
+            Attributes::NetworkName(_) => UtfStr(info.network_name.as_bytes()).to_tlv(tw, tag),
+            Attributes::PanId(_) => tw.u16(tag, info.pan_id),
+            Attributes::ExtendedPanId(_) => tw.u64(tag, info.extended_pan_id),
+            Attributes::MeshLocalPrefix(_) => {
+                // An IPv6 prefix is encoded as its length in bits, followed by the prefix
+                let mut prefix = [0; 9];
+                prefix[0] = 64;
+                prefix[1..].copy_from_slice(&info.mesh_local_prefix);
+
+                tw.str8(tag, &prefix)
+            }
+            Attributes::PartitionId(_) => tw.u32(tag, info.partition_id),
+            Attributes::Weighting(_) => tw.u8(tag, info.weighting),
+            Attributes::DataVersion(_) => tw.u8(tag, info.data_version),
+            Attributes::StableDataVersion(_) => tw.u8(tag, info.stable_data_version),
+            Attributes::LeaderRouterId(_) => tw.u8(tag, info.leader_router_id),
+            Attributes::ActiveTimestamp(_) => Nullable::from(info.active_timestamp).to_tlv(tw, tag),
+            Attributes::PendingTimestamp(_) => {
+                Nullable::from(info.pending_timestamp).to_tlv(tw, tag)
+            }
+            Attributes::Delay(_) => Nullable::from(info.delay).to_tlv(tw, tag),
+            Attributes::SecurityPolicy(_) => Nullable::from(info.security_policy).to_tlv(tw, tag),
+            Attributes::ChannelPage0Mask(_) => Nullable::from(
+                info.channel_page0_mask
+                    .as_ref()
+                    .map(|mask| OctetStr(&mask[..])),
+            )
+            .to_tlv(tw, tag),
+            Attributes::OperationalDatasetComponents(_) => {
+                Nullable::from(info.dataset_components).to_tlv(tw, tag)
+            }
+            Attributes::OverrunCount(_)
+            | Attributes::NeighborTable(_)
+            | Attributes::RouteTable(_)
+            | Attributes::ActiveNetworkFaultsList(_) => unreachable!(),
+        }
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        _data: &TLVElement,
+        _encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::ResetCounts => {
+                cmd_enter!("ResetCounts");
+
+                self.platform.reset_counts();
+            }
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+}
+
+impl<'a> Handler for ThreadNwDiagCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        ThreadNwDiagCluster::read(self, attr, encoder)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        ThreadNwDiagCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for ThreadNwDiagCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for ThreadNwDiagCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
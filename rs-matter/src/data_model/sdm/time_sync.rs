@@ -0,0 +1,805 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+use core::cell::{Cell, RefCell};
+use core::convert::TryInto;
+
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    data_model::objects::AttrType,
+    data_model::objects::*,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, Nullable, TLVArray, TLVElement, TLVWriter, TagType, ToTLV, UtfStr},
+    transport::{exchange::Exchange, network::Ipv6Addr},
+    utils::{epoch::UtcClock, rand::Rand},
+};
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0038;
+
+pub const MAX_TIME_ZONES: usize = 2;
+pub const MAX_DST_OFFSETS: usize = 2;
+pub const MAX_TIME_ZONE_NAME_LEN: usize = 64;
+pub const MAX_DEFAULT_NTP_LEN: usize = 128;
+
+// The range of the offset of a time zone from UTC, in seconds
+const MIN_TIME_ZONE_OFFSET: i32 = -12 * 60 * 60;
+const MAX_TIME_ZONE_OFFSET: i32 = 14 * 60 * 60;
+
+enum FeatureMap {
+    TimeZone = 0x01,
+    NtpClient = 0x02,
+    TimeSyncClient = 0x08,
+}
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    UtcTime(AttrType<Nullable<u64>>) = 0x00,
+    Granularity(AttrType<u8>) = 0x01,
+    TimeSource(AttrType<u8>) = 0x02,
+    TrustedTimeSource(AttrType<Nullable<TrustedTimeSource>>) = 0x03,
+    DefaultNtp(()) = 0x04,
+    TimeZone(()) = 0x05,
+    DstOffset(()) = 0x06,
+    LocalTime(AttrType<Nullable<u64>>) = 0x07,
+    TimeZoneDatabase(AttrType<u8>) = 0x08,
+    TimeZoneListMaxSize(AttrType<u8>) = 0x0A,
+    DstOffsetListMaxSize(AttrType<u8>) = 0x0B,
+    SupportsDnsResolve(AttrType<bool>) = 0x0C,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    SetUtcTime = 0x00,
+    SetTrustedTimeSource = 0x01,
+    SetTimeZone = 0x02,
+    SetDstOffset = 0x04,
+    SetDefaultNtp = 0x05,
+}
+
+command_enum!(Commands);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum RespCommands {
+    SetTimeZoneResponse = 0x03,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Events {
+    DstTableEmpty = 0x00,
+    DstStatus = 0x01,
+    TimeZoneStatus = 0x02,
+    TimeFailure = 0x03,
+    MissingTrustedTimeSource = 0x04,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::TimeZone as u32
+        | FeatureMap::NtpClient as u32
+        | FeatureMap::TimeSyncClient as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::UtcTime as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Granularity as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::TimeSource as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::TrustedTimeSource as u16,
+            Access::RV,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::DefaultNtp as u16,
+            Access::RV,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::TimeZone as u16,
+            Access::RV,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::DstOffset as u16,
+            Access::RV,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::LocalTime as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::TimeZoneDatabase as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::TimeZoneListMaxSize as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::DstOffsetListMaxSize as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SupportsDnsResolve as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[
+        CommandsDiscriminants::SetUtcTime as _,
+        CommandsDiscriminants::SetTrustedTimeSource as _,
+        CommandsDiscriminants::SetTimeZone as _,
+        CommandsDiscriminants::SetDstOffset as _,
+        CommandsDiscriminants::SetDefaultNtp as _,
+    ],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
+#[repr(u8)]
+pub enum Granularity {
+    NoTimeGranularity = 0,
+    Minutes = 1,
+    Seconds = 2,
+    Milliseconds = 3,
+    Microseconds = 4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum TimeSource {
+    None = 0,
+    Unknown = 1,
+    Admin = 2,
+    NodeTimeCluster = 3,
+    NonMatterSntp = 4,
+    NonMatterNtp = 5,
+    MatterSntp = 6,
+    MatterNtp = 7,
+    MixedNtp = 8,
+    NonMatterSntpNts = 9,
+    NonMatterNtpNts = 10,
+    MatterSntpNts = 11,
+    MatterNtpNts = 12,
+    MixedNtpNts = 13,
+    CloudSource = 14,
+    Ptp = 15,
+    Gnss = 16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeZoneDatabase {
+    Full = 0,
+    Partial = 1,
+    None = 2,
+}
+
+/// The node which the time is synchronized from, as set by an administrator of
+/// the fabric of the node
+#[derive(ToTLV, FromTLV, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrustedTimeSource {
+    pub fabric_index: u8,
+    pub node_id: u64,
+    pub endpoint: u16,
+}
+
+/// An entry of the TimeZone attribute, with the offset from UTC in seconds and
+/// the UTC time, in microseconds since the Matter epoch, from which it applies
+#[derive(ToTLV, FromTLV, Debug, Clone)]
+#[tlvargs(lifetime = "'a")]
+pub struct TimeZone<'a> {
+    pub offset: i32,
+    pub valid_at: u64,
+    pub name: Option<UtfStr<'a>>,
+}
+
+/// An entry of the DSTOffset attribute, with the offset in seconds and the UTC
+/// times, in microseconds since the Matter epoch, during which it applies
+#[derive(ToTLV, FromTLV, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DstOffset {
+    pub offset: i32,
+    pub valid_starting: u64,
+    pub valid_until: Nullable<u64>,
+}
+
+impl DstOffset {
+    fn applies_at(&self, utc_us: u64) -> bool {
+        self.valid_starting <= utc_us
+            && match self.valid_until {
+                Nullable::NotNull(valid_until) => utc_us < valid_until,
+                Nullable::Null => true,
+            }
+    }
+}
+
+struct TimeZoneEntry {
+    offset: i32,
+    valid_at: u64,
+    name: Option<heapless::String<MAX_TIME_ZONE_NAME_LEN>>,
+}
+
+#[derive(FromTLV)]
+struct SetUtcTimeReq {
+    utc_time: u64,
+    granularity: u8,
+    time_source: Option<u8>,
+}
+
+#[derive(FromTLV)]
+struct FabricScopedTrustedTimeSource {
+    node_id: u64,
+    endpoint: u16,
+}
+
+#[derive(FromTLV)]
+struct SetTrustedTimeSourceReq {
+    trusted_time_source: Nullable<FabricScopedTrustedTimeSource>,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct SetTimeZoneReq<'a> {
+    time_zone: TLVArray<'a, TimeZone<'a>>,
+}
+
+#[derive(ToTLV)]
+struct SetTimeZoneResp {
+    dst_offset_required: bool,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct SetDstOffsetReq<'a> {
+    dst_offset: TLVArray<'a, DstOffset>,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct SetDefaultNtpReq<'a> {
+    default_ntp: Nullable<UtfStr<'a>>,
+}
+
+/// The Time Synchronization cluster, which keeps the node-level `UtcClock`
+///
+/// The time is only set by administrators or the application, as the node does
+/// not synchronize with its trusted time source or NTP server itself.
+pub struct TimeSyncCluster<'a> {
+    data_ver: Dataver,
+    utc: &'a UtcClock,
+    granularity: Cell<Granularity>,
+    time_source: Cell<TimeSource>,
+    trusted_time_source: Cell<Option<TrustedTimeSource>>,
+    default_ntp: RefCell<Option<heapless::String<MAX_DEFAULT_NTP_LEN>>>,
+    time_zones: RefCell<heapless::Vec<TimeZoneEntry, MAX_TIME_ZONES>>,
+    dst_offsets: RefCell<heapless::Vec<DstOffset, MAX_DST_OFFSETS>>,
+}
+
+impl<'a> TimeSyncCluster<'a> {
+    pub fn new(utc: &'a UtcClock, rand: Rand) -> Self {
+        // UTC, until an administrator sets the time zone
+        let time_zones = core::iter::once(TimeZoneEntry {
+            offset: 0,
+            valid_at: 0,
+            name: None,
+        })
+        .collect();
+
+        Self {
+            data_ver: Dataver::new(rand),
+            utc,
+            granularity: Cell::new(Granularity::NoTimeGranularity),
+            time_source: Cell::new(TimeSource::None),
+            trusted_time_source: Cell::new(None),
+            default_ntp: RefCell::new(None),
+            time_zones: RefCell::new(time_zones),
+            dst_offsets: RefCell::new(heapless::Vec::new()),
+        }
+    }
+
+    pub fn granularity(&self) -> Granularity {
+        self.granularity.get()
+    }
+
+    pub fn trusted_time_source(&self) -> Option<TrustedTimeSource> {
+        self.trusted_time_source.get()
+    }
+
+    /// Sets the UTC time of the node, in microseconds since the Matter epoch
+    ///
+    /// Fails if the time of the node is already of a finer granularity. Also used by
+    /// applications which synchronize the time themselves.
+    pub fn set_utc_time(
+        &self,
+        utc_us: u64,
+        granularity: Granularity,
+        time_source: TimeSource,
+    ) -> Result<(), Error> {
+        if granularity == Granularity::NoTimeGranularity {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        if self.utc.is_set() && self.granularity.get() > granularity {
+            // Reported as FAILURE, instead of the TimeNotAccepted cluster status
+            Err(ErrorCode::InvalidTime)?;
+        }
+
+        self.utc.set(utc_us);
+        self.granularity.set(granularity);
+        self.time_source.set(time_source);
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    /// The local time, in microseconds since the Matter epoch, as per the current
+    /// time zone and DST offset
+    pub fn local_time(&self) -> Option<u64> {
+        let utc_us = self.utc.now_us()?;
+
+        let dst_offsets = self.dst_offsets.borrow();
+        if dst_offsets.is_empty() {
+            return None;
+        }
+
+        let dst_offset = dst_offsets
+            .iter()
+            .find(|dst| dst.applies_at(utc_us))
+            .map(|dst| dst.offset)
+            .unwrap_or(0);
+
+        let time_zones = self.time_zones.borrow();
+        let time_zone = time_zones.iter().rev().find(|tz| tz.valid_at <= utc_us)?;
+
+        let offset_us = (time_zone.offset as i64 + dst_offset as i64) * 1_000_000;
+
+        (utc_us as i64).checked_add(offset_us)?.try_into().ok()
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::UtcTime(codec) => codec.encode(writer, self.utc.now_us().into()),
+                    Attributes::Granularity(codec) => {
+                        codec.encode(writer, self.granularity.get() as _)
+                    }
+                    Attributes::TimeSource(codec) => {
+                        codec.encode(writer, self.time_source.get() as _)
+                    }
+                    Attributes::TrustedTimeSource(codec) => {
+                        codec.encode(writer, self.trusted_time_source.get().into())
+                    }
+                    Attributes::DefaultNtp(_) => writer.set(Nullable::from(
+                        self.default_ntp
+                            .borrow()
+                            .as_ref()
+                            .map(|ntp| UtfStr(ntp.as_bytes())),
+                    )),
+                    Attributes::TimeZone(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for tz in self.time_zones.borrow().iter() {
+                            TimeZone {
+                                offset: tz.offset,
+                                valid_at: tz.valid_at,
+                                name: tz.name.as_ref().map(|name| UtfStr(name.as_bytes())),
+                            }
+                            .to_tlv(&mut writer, TagType::Anonymous)?;
+                        }
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::DstOffset(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for dst in self.dst_offsets.borrow().iter() {
+                            dst.to_tlv(&mut writer, TagType::Anonymous)?;
+                        }
+                        writer.end_container()?;
+                        writer.complete()
+                    }
+                    Attributes::LocalTime(codec) => codec.encode(writer, self.local_time().into()),
+                    Attributes::TimeZoneDatabase(codec) => {
+                        codec.encode(writer, TimeZoneDatabase::None as _)
+                    }
+                    Attributes::TimeZoneListMaxSize(codec) => {
+                        codec.encode(writer, MAX_TIME_ZONES as _)
+                    }
+                    Attributes::DstOffsetListMaxSize(codec) => {
+                        codec.encode(writer, MAX_DST_OFFSETS as _)
+                    }
+                    Attributes::SupportsDnsResolve(codec) => codec.encode(writer, false),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        // Only writable internally, when restoring the persisted configuration
+        match attr.attr_id.try_into()? {
+            Attributes::TrustedTimeSource(codec) => {
+                self.trusted_time_source.set(codec.decode(data)?.notnull());
+            }
+            Attributes::DefaultNtp(_) => {
+                self.set_default_ntp(Nullable::<UtfStr>::from_tlv(data)?)?;
+            }
+            Attributes::TimeZone(_) => {
+                self.set_time_zones(&TLVArray::from_tlv(data)?)?;
+            }
+            Attributes::DstOffset(_) => {
+                self.set_dst_offsets(&TLVArray::from_tlv(data)?)?;
+            }
+            _ => Err(ErrorCode::InvalidAction)?,
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    pub fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::SetUtcTime => {
+                cmd_enter!("SetUTCTime");
+
+                let req = SetUtcTimeReq::from_tlv(data)?;
+
+                let granularity =
+                    Granularity::from_repr(req.granularity).ok_or(ErrorCode::ConstraintError)?;
+                let time_source = match req.time_source {
+                    Some(time_source) => {
+                        TimeSource::from_repr(time_source).ok_or(ErrorCode::ConstraintError)?
+                    }
+                    None => TimeSource::Admin,
+                };
+
+                self.set_utc_time(req.utc_time, granularity, time_source)?;
+            }
+            Commands::SetTrustedTimeSource => {
+                cmd_enter!("SetTrustedTimeSource");
+
+                let req = SetTrustedTimeSourceReq::from_tlv(data)?;
+
+                let trusted_time_source = match req.trusted_time_source {
+                    Nullable::NotNull(source) => Some(TrustedTimeSource {
+                        fabric_index: exchange
+                            .with_session(|sess| Ok(sess.get_local_fabric_idx()))?
+                            .ok_or(ErrorCode::UnsupportedAccess)?,
+                        node_id: source.node_id,
+                        endpoint: source.endpoint,
+                    }),
+                    Nullable::Null => None,
+                };
+
+                self.trusted_time_source.set(trusted_time_source);
+            }
+            Commands::SetTimeZone => {
+                cmd_enter!("SetTimeZone");
+
+                let req = SetTimeZoneReq::from_tlv(data)?;
+                self.set_time_zones(&req.time_zone)?;
+
+                // The offsets were of the previous time zone
+                self.dst_offsets.borrow_mut().clear();
+
+                // Without a time zone database, the DST offsets are always needed
+                encoder
+                    .with_command(RespCommands::SetTimeZoneResponse as _)?
+                    .set(SetTimeZoneResp {
+                        dst_offset_required: true,
+                    })?;
+            }
+            Commands::SetDstOffset => {
+                cmd_enter!("SetDSTOffset");
+
+                let req = SetDstOffsetReq::from_tlv(data)?;
+                self.set_dst_offsets(&req.dst_offset)?;
+            }
+            Commands::SetDefaultNtp => {
+                cmd_enter!("SetDefaultNTP");
+
+                let req = SetDefaultNtpReq::from_tlv(data)?;
+                self.set_default_ntp(req.default_ntp)?;
+            }
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    fn set_time_zones<'b>(&self, time_zones: &TLVArray<'b, TimeZone<'b>>) -> Result<(), Error> {
+        let mut entries = heapless::Vec::<TimeZoneEntry, MAX_TIME_ZONES>::new();
+
+        for (index, tz) in time_zones.iter().enumerate() {
+            // The first time zone applies from the start, and the others in order
+            let valid_at_ok = if index == 0 {
+                tz.valid_at == 0
+            } else {
+                entries
+                    .last()
+                    .map(|prev| prev.valid_at < tz.valid_at)
+                    .unwrap_or(false)
+            };
+
+            if !valid_at_ok || !(MIN_TIME_ZONE_OFFSET..=MAX_TIME_ZONE_OFFSET).contains(&tz.offset) {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            let name = match tz.name {
+                Some(name) => {
+                    let mut tz_name = heapless::String::new();
+                    tz_name
+                        .push_str(name.as_str()?)
+                        .map_err(|_| ErrorCode::ConstraintError)?;
+
+                    Some(tz_name)
+                }
+                None => None,
+            };
+
+            entries
+                .push(TimeZoneEntry {
+                    offset: tz.offset,
+                    valid_at: tz.valid_at,
+                    name,
+                })
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        if entries.is_empty() {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        *self.time_zones.borrow_mut() = entries;
+
+        Ok(())
+    }
+
+    fn set_dst_offsets(&self, dst_offsets: &TLVArray<'_, DstOffset>) -> Result<(), Error> {
+        let mut entries = heapless::Vec::<DstOffset, MAX_DST_OFFSETS>::new();
+
+        for dst in dst_offsets.iter() {
+            // Only the last offset may apply indefinitely, and the offsets must not overlap
+            let ordered = match dst.valid_until {
+                Nullable::NotNull(valid_until) => dst.valid_starting < valid_until,
+                Nullable::Null => true,
+            };
+
+            let after_previous = entries
+                .last()
+                .map(|prev| match prev.valid_until {
+                    Nullable::NotNull(valid_until) => valid_until <= dst.valid_starting,
+                    Nullable::Null => false,
+                })
+                .unwrap_or(true);
+
+            if !ordered || !after_previous {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            entries
+                .push(dst)
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        *self.dst_offsets.borrow_mut() = entries;
+
+        Ok(())
+    }
+
+    fn set_default_ntp(&self, default_ntp: Nullable<UtfStr>) -> Result<(), Error> {
+        let default_ntp = match default_ntp {
+            Nullable::NotNull(ntp) => {
+                let ntp = ntp.as_str()?;
+
+                // Without DNS resolution, only addresses can be used
+                if ntp.parse::<Ipv6Addr>().is_err() {
+                    Err(ErrorCode::InvalidCommand)?;
+                }
+
+                let mut default_ntp = heapless::String::new();
+                default_ntp
+                    .push_str(ntp)
+                    .map_err(|_| ErrorCode::ConstraintError)?;
+
+                Some(default_ntp)
+            }
+            Nullable::Null => None,
+        };
+
+        *self.default_ntp.borrow_mut() = default_ntp;
+
+        Ok(())
+    }
+}
+
+impl<'a> Handler for TimeSyncCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        TimeSyncCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        TimeSyncCluster::write(self, attr, data)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        TimeSyncCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for TimeSyncCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for TimeSyncCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::error::ErrorCode;
+    use crate::tlv::{Nullable, TLVArray};
+    use crate::utils::{epoch::UtcClock, rand::dummy_rand};
+
+    use super::{DstOffset, Granularity, TimeSource, TimeSyncCluster, TimeZone};
+
+    const HOUR_US: u64 = 60 * 60 * 1_000_000;
+
+    fn test_epoch() -> Duration {
+        Duration::from_secs(0)
+    }
+
+    #[test]
+    fn test_coarser_time_not_accepted() {
+        let utc = UtcClock::new(test_epoch);
+        let time_sync = TimeSyncCluster::new(&utc, dummy_rand);
+
+        time_sync
+            .set_utc_time(10 * HOUR_US, Granularity::Seconds, TimeSource::Admin)
+            .unwrap();
+        assert_eq!(
+            Err(ErrorCode::InvalidTime),
+            time_sync
+                .set_utc_time(20 * HOUR_US, Granularity::Minutes, TimeSource::Admin)
+                .map_err(|e| e.code())
+        );
+        assert_eq!(utc.now_us(), Some(10 * HOUR_US));
+
+        time_sync
+            .set_utc_time(20 * HOUR_US, Granularity::Milliseconds, TimeSource::Admin)
+            .unwrap();
+        assert_eq!(utc.now_us(), Some(20 * HOUR_US));
+    }
+
+    #[test]
+    fn test_local_time() {
+        let utc = UtcClock::new(test_epoch);
+        let time_sync = TimeSyncCluster::new(&utc, dummy_rand);
+
+        time_sync
+            .set_utc_time(10 * HOUR_US, Granularity::Seconds, TimeSource::Admin)
+            .unwrap();
+        // No DST offsets yet
+        assert_eq!(time_sync.local_time(), None);
+
+        time_sync
+            .set_time_zones(&TLVArray::new(&[TimeZone {
+                offset: 2 * 60 * 60,
+                valid_at: 0,
+                name: None,
+            }]))
+            .unwrap();
+        time_sync
+            .set_dst_offsets(&TLVArray::new(&[
+                DstOffset {
+                    offset: 60 * 60,
+                    valid_starting: 0,
+                    valid_until: Nullable::NotNull(5 * HOUR_US),
+                },
+                DstOffset {
+                    offset: 0,
+                    valid_starting: 5 * HOUR_US,
+                    valid_until: Nullable::Null,
+                },
+            ]))
+            .unwrap();
+        assert_eq!(time_sync.local_time(), Some(12 * HOUR_US));
+    }
+
+    #[test]
+    fn test_invalid_offsets() {
+        let utc = UtcClock::new(test_epoch);
+        let time_sync = TimeSyncCluster::new(&utc, dummy_rand);
+
+        // The first time zone has to apply from the start
+        assert_eq!(
+            Err(ErrorCode::ConstraintError),
+            time_sync
+                .set_time_zones(&TLVArray::new(&[TimeZone {
+                    offset: 0,
+                    valid_at: HOUR_US,
+                    name: None,
+                }]))
+                .map_err(|e| e.code())
+        );
+
+        // Overlapping DST offsets
+        assert_eq!(
+            Err(ErrorCode::ConstraintError),
+            time_sync
+                .set_dst_offsets(&TLVArray::new(&[
+                    DstOffset {
+                        offset: 60 * 60,
+                        valid_starting: 0,
+                        valid_until: Nullable::Null,
+                    },
+                    DstOffset {
+                        offset: 0,
+                        valid_starting: 5 * HOUR_US,
+                        valid_until: Nullable::Null,
+                    },
+                ]))
+                .map_err(|e| e.code())
+        );
+    }
+}
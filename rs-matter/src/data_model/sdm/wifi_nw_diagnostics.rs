@@ -0,0 +1,340 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+use core::convert::TryInto;
+
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    data_model::objects::AttrType,
+    data_model::objects::*,
+    error::Error,
+    tlv::{Nullable, OctetStr, TLVElement},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+pub const ID: u32 = 0x0036;
+
+enum FeatureMap {
+    PacketCounts = 0x01,
+    ErrorCounts = 0x02,
+}
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    Bssid(()) = 0x00,
+    SecurityType(AttrType<Nullable<u8>>) = 0x01,
+    WiFiVersion(AttrType<Nullable<u8>>) = 0x02,
+    ChannelNumber(AttrType<Nullable<u16>>) = 0x03,
+    Rssi(AttrType<Nullable<i8>>) = 0x04,
+    BeaconLostCount(AttrType<Nullable<u32>>) = 0x05,
+    BeaconRxCount(AttrType<Nullable<u32>>) = 0x06,
+    PacketMulticastRxCount(AttrType<Nullable<u32>>) = 0x07,
+    PacketMulticastTxCount(AttrType<Nullable<u32>>) = 0x08,
+    PacketUnicastRxCount(AttrType<Nullable<u32>>) = 0x09,
+    PacketUnicastTxCount(AttrType<Nullable<u32>>) = 0x0A,
+    CurrentMaxRate(AttrType<Nullable<u64>>) = 0x0B,
+    OverrunCount(AttrType<Nullable<u64>>) = 0x0C,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u32)]
+pub enum Commands {
+    ResetCounts = 0x0,
+}
+
+command_enum!(Commands);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Events {
+    Disconnection = 0x00,
+    AssociationFailure = 0x01,
+    ConnectionStatus = 0x02,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::PacketCounts as u32 | FeatureMap::ErrorCounts as u32,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::Bssid as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SecurityType as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::WiFiVersion as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ChannelNumber as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Rssi as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::BeaconLostCount as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::BeaconRxCount as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PacketMulticastRxCount as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PacketMulticastTxCount as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PacketUnicastRxCount as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::PacketUnicastTxCount as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::CurrentMaxRate as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::OverrunCount as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+    ],
+    commands: &[CommandsDiscriminants::ResetCounts as _],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityType {
+    Unspecified = 0,
+    None = 1,
+    Wep = 2,
+    Wpa = 3,
+    Wpa2 = 4,
+    Wpa3 = 5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WiFiVersion {
+    A = 0,
+    B = 1,
+    G = 2,
+    N = 3,
+    Ac = 4,
+    Ax = 5,
+    Ah = 6,
+}
+
+/// The platform-specific statistics of the Wi-Fi interface
+///
+/// All values are reported as null while the node is not connected, or when
+/// the platform cannot tell.
+pub trait WiFiNwDiagnostics {
+    /// The BSSID of the access point the node is connected to
+    fn bssid(&self) -> Option<[u8; 6]>;
+
+    fn security_type(&self) -> Option<SecurityType>;
+
+    fn wifi_version(&self) -> Option<WiFiVersion>;
+
+    fn channel_number(&self) -> Option<u16>;
+
+    /// The RSSI of the access point, in dBm
+    fn rssi(&self) -> Option<i8>;
+
+    fn beacon_lost_count(&self) -> Option<u32> {
+        None
+    }
+
+    fn beacon_rx_count(&self) -> Option<u32> {
+        None
+    }
+
+    fn packet_multicast_rx_count(&self) -> Option<u32> {
+        None
+    }
+
+    fn packet_multicast_tx_count(&self) -> Option<u32> {
+        None
+    }
+
+    fn packet_unicast_rx_count(&self) -> Option<u32> {
+        None
+    }
+
+    fn packet_unicast_tx_count(&self) -> Option<u32> {
+        None
+    }
+
+    /// The current maximum PHY rate, in bits per second
+    fn current_max_rate(&self) -> Option<u64> {
+        None
+    }
+
+    fn overrun_count(&self) -> Option<u64> {
+        None
+    }
+
+    /// Resets the counts, on a ResetCounts command
+    fn reset_counts(&self) {}
+}
+
+pub struct WiFiNwDiagCluster<'a> {
+    data_ver: Dataver,
+    platform: &'a dyn WiFiNwDiagnostics,
+}
+
+impl<'a> WiFiNwDiagCluster<'a> {
+    pub fn new(platform: &'a dyn WiFiNwDiagnostics, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            platform,
+        }
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::Bssid(_) => {
+                        let bssid = self.platform.bssid();
+
+                        writer.set(Nullable::from(
+                            bssid.as_ref().map(|bssid| OctetStr(&bssid[..])),
+                        ))
+                    }
+                    Attributes::SecurityType(codec) => codec.encode(
+                        writer,
+                        self.platform.security_type().map(|ty| ty as _).into(),
+                    ),
+                    Attributes::WiFiVersion(codec) => codec.encode(
+                        writer,
+                        self.platform.wifi_version().map(|ver| ver as _).into(),
+                    ),
+                    Attributes::ChannelNumber(codec) => {
+                        codec.encode(writer, self.platform.channel_number().into())
+                    }
+                    Attributes::Rssi(codec) => codec.encode(writer, self.platform.rssi().into()),
+                    Attributes::BeaconLostCount(codec) => {
+                        codec.encode(writer, self.platform.beacon_lost_count().into())
+                    }
+                    Attributes::BeaconRxCount(codec) => {
+                        codec.encode(writer, self.platform.beacon_rx_count().into())
+                    }
+                    Attributes::PacketMulticastRxCount(codec) => {
+                        codec.encode(writer, self.platform.packet_multicast_rx_count().into())
+                    }
+                    Attributes::PacketMulticastTxCount(codec) => {
+                        codec.encode(writer, self.platform.packet_multicast_tx_count().into())
+                    }
+                    Attributes::PacketUnicastRxCount(codec) => {
+                        codec.encode(writer, self.platform.packet_unicast_rx_count().into())
+                    }
+                    Attributes::PacketUnicastTxCount(codec) => {
+                        codec.encode(writer, self.platform.packet_unicast_tx_count().into())
+                    }
+                    Attributes::CurrentMaxRate(codec) => {
+                        codec.encode(writer, self.platform.current_max_rate().into())
+                    }
+                    Attributes::OverrunCount(codec) => {
+                        codec.encode(writer, self.platform.overrun_count().into())
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        _data: &TLVElement,
+        _encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::ResetCounts => {
+                cmd_enter!("ResetCounts");
+
+                self.platform.reset_counts();
+            }
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+}
+
+impl<'a> Handler for WiFiNwDiagCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        WiFiNwDiagCluster::read(self, attr, encoder)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        WiFiNwDiagCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+}
+
+impl<'a> NonBlockingHandler for WiFiNwDiagCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for WiFiNwDiagCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
@@ -188,6 +188,10 @@ impl<'a> Handler for AccessControlCluster<'a> {
     fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
         AccessControlCluster::write(self, attr, data)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 impl<'a> NonBlockingHandler for AccessControlCluster<'a> {}
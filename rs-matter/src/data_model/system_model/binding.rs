@@ -0,0 +1,501 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::cell::{Cell, RefCell};
+use core::convert::TryInto;
+
+use strum::{EnumDiscriminants, FromRepr};
+
+use crate::data_model::objects::*;
+use crate::data_model::persist::StatePersist;
+use crate::fabric;
+use crate::interaction_model::client::{InvokeClient, NodeResolver};
+use crate::interaction_model::messages::ib::{attr_list_write, ListOperation};
+use crate::tlv::{self, FromTLV, TLVElement, TLVList, TLVWriter, TagType, ToTLV};
+use crate::utils::rand::Rand;
+use crate::utils::writebuf::WriteBuf;
+use crate::{attribute_enum, error::*};
+use log::{info, warn};
+
+pub const ID: u32 = 0x001E;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    Binding(()) = 0,
+}
+
+attribute_enum!(Attributes);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        Attribute::new(
+            AttributesDiscriminants::Binding as u16,
+            Access::RWFVM,
+            Quality::N,
+        ),
+    ],
+    commands: &[],
+};
+
+pub const BINDINGS_PER_FABRIC: usize = 4;
+
+const MAX_BINDINGS: usize = BINDINGS_PER_FABRIC * fabric::MAX_SUPPORTED_FABRICS;
+
+/// An entry of the `Binding` attribute
+///
+/// Either `node` and `endpoint`, or `group` are set. Without a `cluster`, the binding
+/// applies to all client clusters of the endpoint.
+#[derive(ToTLV, FromTLV, Clone, Debug, PartialEq, Eq)]
+#[tlvargs(start = 1)]
+pub struct Binding {
+    pub node: Option<u64>,
+    pub group: Option<u16>,
+    pub endpoint: Option<EndptId>,
+    pub cluster: Option<ClusterId>,
+    #[tagval(0xFE)]
+    pub fab_idx: Option<u8>,
+}
+
+impl Binding {
+    pub fn target(&self) -> Option<BindingTarget> {
+        match (self.node, self.group, self.endpoint) {
+            (Some(node_id), None, Some(endpoint)) => {
+                Some(BindingTarget::Node { node_id, endpoint })
+            }
+            (None, Some(group_id), None) => Some(BindingTarget::Group(group_id)),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, cluster: ClusterId) -> bool {
+        self.cluster.is_none() || self.cluster == Some(cluster)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingTarget {
+    /// Unicast to an endpoint of a node, over a CASE session
+    Node { node_id: u64, endpoint: EndptId },
+    /// Groupcast to a group
+    Group(u16),
+}
+
+/// The Binding cluster
+///
+/// The bindings of all fabrics are persisted through `StatePersist`, as the `Binding`
+/// attribute can only be written on behalf of a fabric.
+pub struct BindingCluster {
+    data_ver: Dataver,
+    bindings: RefCell<heapless::Vec<Binding, MAX_BINDINGS>>,
+    changed: Cell<bool>,
+}
+
+impl BindingCluster {
+    pub fn new(rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            bindings: RefCell::new(heapless::Vec::new()),
+            changed: Cell::new(false),
+        }
+    }
+
+    /// The targets bound to `cluster`, along with the fabric index of their binding
+    pub fn targets(&self, cluster: ClusterId) -> heapless::Vec<(u8, BindingTarget), MAX_BINDINGS> {
+        self.bindings
+            .borrow()
+            .iter()
+            .filter(|binding| binding.matches(cluster))
+            .filter_map(|binding| Some((binding.fab_idx?, binding.target()?)))
+            .collect()
+    }
+
+    /// Invoke a command on all targets bound to `cluster`
+    ///
+    /// Returns the number of targets the command was sent to. A failure to reach one
+    /// target does not prevent sending the command to the others.
+    pub async fn dispatch<R>(
+        &self,
+        client: &mut InvokeClient<'_, R>,
+        cluster: ClusterId,
+        cmd: CmdId,
+        data: &dyn ToTLV,
+    ) -> Result<usize, Error>
+    where
+        R: NodeResolver,
+    {
+        let mut sent = 0;
+
+        for (fab_idx, target) in self.targets(cluster) {
+            match target {
+                BindingTarget::Node { node_id, endpoint } => {
+                    match client
+                        .invoke(fab_idx, node_id, endpoint, cluster, cmd, data)
+                        .await
+                    {
+                        Ok(()) => sent += 1,
+                        Err(err) => warn!("Sending to {:?} failed: {:?}", target, err),
+                    }
+                }
+                BindingTarget::Group(_) => {
+                    warn!("Groupcast to {:?} is not supported yet", target)
+                }
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Remove the bindings of a fabric which was removed
+    pub fn remove_fabric(&self, fab_idx: u8) {
+        self.bindings
+            .borrow_mut()
+            .retain(|binding| binding.fab_idx != Some(fab_idx));
+        self.changed.set(true);
+        self.data_ver.changed();
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::Binding(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+                        for binding in self.bindings.borrow().iter() {
+                            if !attr.fab_filter || Some(attr.fab_idx) == binding.fab_idx {
+                                binding.to_tlv(&mut writer, TagType::Anonymous)?;
+                            }
+                        }
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        match attr.attr_id.try_into()? {
+            Attributes::Binding(_) => {
+                attr_list_write(attr, data.with_dataver(self.data_ver.get())?, |op, data| {
+                    self.write_binding_attr(&op, data, attr.fab_idx)
+                })?;
+            }
+        }
+
+        self.changed.set(true);
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    /// Write the Binding attribute
+    ///
+    /// Indices are relative to the bindings of the accessing fabric.
+    fn write_binding_attr(
+        &self,
+        op: &ListOperation,
+        data: &TLVElement,
+        fab_idx: u8,
+    ) -> Result<(), Error> {
+        info!("Performing Binding operation {:?}", op);
+
+        if fab_idx == 0 {
+            Err(ErrorCode::UnsupportedAccess)?;
+        }
+
+        let mut bindings = self.bindings.borrow_mut();
+
+        match op {
+            ListOperation::AddItem | ListOperation::EditItem(_) => {
+                let mut binding = Binding::from_tlv(data)?;
+                binding.fab_idx = Some(fab_idx);
+
+                if binding.target().is_none() {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                if let ListOperation::EditItem(index) = op {
+                    let position = Self::position(&bindings, fab_idx, *index as usize)?;
+                    bindings[position] = binding;
+                } else {
+                    if bindings
+                        .iter()
+                        .filter(|b| b.fab_idx == Some(fab_idx))
+                        .count()
+                        >= BINDINGS_PER_FABRIC
+                    {
+                        Err(ErrorCode::NoSpace)?;
+                    }
+
+                    bindings.push(binding).map_err(|_| ErrorCode::NoSpace)?;
+                }
+            }
+            ListOperation::DeleteItem(index) => {
+                let position = Self::position(&bindings, fab_idx, *index as usize)?;
+                bindings.remove(position);
+            }
+            ListOperation::DeleteList => {
+                bindings.retain(|b| b.fab_idx != Some(fab_idx));
+            }
+        }
+
+        Ok(())
+    }
+
+    // The position of the `index`-th binding of a fabric
+    fn position(bindings: &[Binding], fab_idx: u8, index: usize) -> Result<usize, Error> {
+        bindings
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.fab_idx == Some(fab_idx))
+            .nth(index)
+            .map(|(position, _)| position)
+            .ok_or_else(|| ErrorCode::NotFound.into())
+    }
+}
+
+impl Handler for BindingCluster {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        BindingCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        BindingCluster::write(self, attr, data)
+    }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
+
+    fn fabric_removed(&self, fab_idx: u8) {
+        BindingCluster::remove_fabric(self, fab_idx)
+    }
+}
+
+impl NonBlockingHandler for BindingCluster {}
+
+impl StatePersist for BindingCluster {
+    fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        tlv::from_tlv(&mut *self.bindings.borrow_mut(), &root)?;
+
+        self.changed.set(false);
+
+        Ok(())
+    }
+
+    fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        if self.changed.get() {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            self.bindings
+                .borrow()
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Anonymous)?;
+
+            self.changed.set(false);
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl ChangeNotifier<()> for BindingCluster {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data_model::{
+            objects::{ClusterId, Handler},
+            persist::StatePersist,
+        },
+        error::{Error, ErrorCode},
+        interaction_model::messages::ib::ListOperation,
+        tlv::{get_root_node_struct, TLVWriter, TagType, ToTLV},
+        utils::{rand::dummy_rand, writebuf::WriteBuf},
+    };
+
+    use super::{Binding, BindingCluster, BindingTarget, BINDINGS_PER_FABRIC};
+
+    fn add(cluster: &BindingCluster, binding: &Binding, fab_idx: u8) -> Result<(), Error> {
+        let mut buf: [u8; 100] = [0; 100];
+        let mut writebuf = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        binding.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        let data = get_root_node_struct(writebuf.as_slice()).unwrap();
+
+        cluster.write_binding_attr(&ListOperation::AddItem, &data, fab_idx)
+    }
+
+    fn node(node: u64, cluster: Option<ClusterId>) -> Binding {
+        Binding {
+            node: Some(node),
+            group: None,
+            endpoint: Some(1),
+            cluster,
+            fab_idx: None,
+        }
+    }
+
+    #[test]
+    fn test_binding_add() {
+        let cluster = BindingCluster::new(dummy_rand);
+
+        // The fabric index in the TLV is ignored in favour of the accessing fabric
+        let mut binding = node(10, None);
+        binding.fab_idx = Some(2);
+        add(&cluster, &binding, 1).unwrap();
+
+        assert_eq!(cluster.bindings.borrow()[0].fab_idx, Some(1));
+
+        // Neither a node nor a group
+        let invalid = Binding {
+            node: None,
+            ..node(10, None)
+        };
+        assert_eq!(
+            add(&cluster, &invalid, 1).unwrap_err().code(),
+            ErrorCode::ConstraintError
+        );
+
+        for index in 1..BINDINGS_PER_FABRIC {
+            add(&cluster, &node(10 + index as u64, None), 1).unwrap();
+        }
+
+        assert_eq!(
+            add(&cluster, &node(20, None), 1).unwrap_err().code(),
+            ErrorCode::NoSpace
+        );
+
+        // Other fabrics have their own quota
+        add(&cluster, &node(20, None), 2).unwrap();
+
+        // Bindings can only be written on behalf of a fabric
+        assert_eq!(
+            add(&cluster, &node(30, None), 0).unwrap_err().code(),
+            ErrorCode::UnsupportedAccess
+        );
+    }
+
+    #[test]
+    fn test_binding_delete() {
+        let cluster = BindingCluster::new(dummy_rand);
+
+        add(&cluster, &node(10, None), 1).unwrap();
+        add(&cluster, &node(20, None), 2).unwrap();
+        add(&cluster, &node(30, None), 2).unwrap();
+
+        let data = get_root_node_struct(&[0x15, 0x18]).unwrap();
+
+        // Index 1 of fabric 2 is the third binding
+        cluster
+            .write_binding_attr(&ListOperation::DeleteItem(1), &data, 2)
+            .unwrap();
+
+        assert_eq!(cluster.bindings.borrow().len(), 2);
+        assert_eq!(cluster.bindings.borrow()[1].node, Some(20));
+
+        cluster
+            .write_binding_attr(&ListOperation::DeleteList, &data, 1)
+            .unwrap();
+
+        assert_eq!(cluster.bindings.borrow().len(), 1);
+        assert_eq!(cluster.bindings.borrow()[0].fab_idx, Some(2));
+    }
+
+    #[test]
+    fn test_binding_persist() {
+        let cluster = BindingCluster::new(dummy_rand);
+
+        add(&cluster, &node(10, Some(6)), 1).unwrap();
+        add(&cluster, &node(20, None), 2).unwrap();
+
+        let mut buf = [0; 200];
+        let data = cluster.store(&mut buf).unwrap().unwrap();
+
+        // Nothing changed since the last store
+        let mut buf2 = [0; 200];
+        assert!(cluster.store(&mut buf2).unwrap().is_none());
+
+        let restored = BindingCluster::new(dummy_rand);
+        restored.load(data).unwrap();
+
+        assert_eq!(*restored.bindings.borrow(), *cluster.bindings.borrow());
+        assert_eq!(restored.bindings.borrow()[1].fab_idx, Some(2));
+    }
+
+    #[test]
+    fn test_binding_targets() {
+        let cluster = BindingCluster::new(dummy_rand);
+
+        add(&cluster, &node(10, Some(6)), 1).unwrap();
+        add(&cluster, &node(20, Some(8)), 1).unwrap();
+        add(
+            &cluster,
+            &Binding {
+                node: None,
+                group: Some(5),
+                endpoint: None,
+                cluster: None,
+                fab_idx: None,
+            },
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(
+            cluster.targets(6).as_slice(),
+            &[
+                (
+                    1,
+                    BindingTarget::Node {
+                        node_id: 10,
+                        endpoint: 1
+                    }
+                ),
+                (2, BindingTarget::Group(5)),
+            ]
+        );
+
+        // The bindings of a removed fabric are dropped
+        Handler::fabric_removed(&cluster, 2);
+
+        assert_eq!(cluster.targets(6).len(), 1);
+        assert!(cluster.targets(7).is_empty());
+    }
+}
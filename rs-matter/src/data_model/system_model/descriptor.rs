@@ -53,7 +53,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     commands: &[],
 };
 
-struct StandardPartsMatcher;
+/// The parts of the root endpoint are all the other endpoints of the node
+pub struct StandardPartsMatcher;
 
 impl PartsMatcher for StandardPartsMatcher {
     fn describe(&self, our_endpoint: EndptId, endpoint: EndptId) -> bool {
@@ -115,6 +116,17 @@ impl<'a> DescriptorCluster<'a> {
     }
 
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        self.read_matching(self.matcher, attr, encoder)
+    }
+
+    /// Same as `read`, but uses `matcher` instead of the matcher of this cluster
+    /// to compute the `PartsList` attribute
+    pub fn read_matching(
+        &self,
+        matcher: &dyn PartsMatcher,
+        attr: &AttrDetails,
+        encoder: AttrDataEncoder,
+    ) -> Result<(), Error> {
         if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
             if attr.is_system() {
                 CLUSTER.read(attr.attr_id, writer)
@@ -140,6 +152,7 @@ impl<'a> DescriptorCluster<'a> {
                     }
                     Attributes::PartsList => {
                         self.encode_parts_list(
+                            matcher,
                             attr.node,
                             attr.endpoint_id,
                             AttrDataWriter::TAG,
@@ -173,8 +186,11 @@ impl<'a> DescriptorCluster<'a> {
         tw.start_array(tag)?;
         for endpoint in node.endpoints {
             if endpoint.id == endpoint_id {
-                let dev_type = endpoint.device_type;
-                dev_type.to_tlv(tw, TagType::Anonymous)?;
+                endpoint.device_type.to_tlv(tw, TagType::Anonymous)?;
+
+                for dev_type in endpoint.extra_device_types {
+                    dev_type.to_tlv(tw, TagType::Anonymous)?;
+                }
             }
         }
 
@@ -200,8 +216,14 @@ impl<'a> DescriptorCluster<'a> {
         tw.end_container()
     }
 
+    /// Mark the attributes of this cluster as changed, i.e. after an endpoint was added or removed
+    pub fn parts_changed(&self) {
+        self.data_ver.changed();
+    }
+
     fn encode_parts_list(
         &self,
+        matcher: &dyn PartsMatcher,
         node: &Node,
         endpoint_id: u16,
         tag: TagType,
@@ -210,7 +232,7 @@ impl<'a> DescriptorCluster<'a> {
         tw.start_array(tag)?;
 
         for endpoint in node.endpoints {
-            if self.matcher.describe(endpoint_id, endpoint.id) {
+            if matcher.describe(endpoint_id, endpoint.id) {
                 tw.u16(TagType::Anonymous, endpoint.id)?;
             }
         }
@@ -220,13 +242,20 @@ impl<'a> DescriptorCluster<'a> {
 
     fn encode_client_list(
         &self,
-        _node: &Node,
-        _endpoint_id: u16,
+        node: &Node,
+        endpoint_id: u16,
         tag: TagType,
         tw: &mut TLVWriter,
     ) -> Result<(), Error> {
-        // No Clients supported
         tw.start_array(tag)?;
+        for endpoint in node.endpoints {
+            if endpoint.id == endpoint_id {
+                for cluster in endpoint.client_clusters {
+                    tw.u32(TagType::Anonymous, *cluster)?;
+                }
+            }
+        }
+
         tw.end_container()
     }
 }
@@ -235,6 +264,10 @@ impl<'a> Handler for DescriptorCluster<'a> {
     fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         DescriptorCluster::read(self, attr, encoder)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 impl<'a> NonBlockingHandler for DescriptorCluster<'a> {}
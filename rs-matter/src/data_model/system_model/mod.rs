@@ -16,4 +16,5 @@
  */
 
 pub mod access_control;
+pub mod binding;
 pub mod descriptor;
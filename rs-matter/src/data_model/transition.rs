@@ -0,0 +1,229 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Transitions of attribute values over time, as used by the Level Control and
+//! Color Control clusters
+
+use core::cell::Cell;
+
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::{error::Error, utils::select::Notification};
+
+// How often the values are updated during a transition
+const TICK_MS: u64 = 100;
+
+// How often a change of the values is reported during a transition
+const REPORT_MS: u64 = 1000;
+
+/// A linear change of `N` values over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition<const N: usize> {
+    pub from: [i32; N],
+    pub to: [i32; N],
+    pub start: Instant,
+    pub duration: Duration,
+}
+
+impl<const N: usize> Transition<N> {
+    /// Create a transition starting now
+    pub fn new(from: [i32; N], to: [i32; N], duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            start: Instant::now(),
+            duration,
+        }
+    }
+
+    pub fn values(&self, now: Instant) -> [i32; N] {
+        let elapsed = now.saturating_duration_since(self.start);
+
+        if elapsed >= self.duration {
+            self.to
+        } else {
+            let elapsed = elapsed.as_millis() as i64;
+            let duration = self.duration.as_millis().max(1) as i64;
+
+            let mut values = self.from;
+
+            for (value, to) in values.iter_mut().zip(self.to) {
+                *value = (*value as i64 + (to as i64 - *value as i64) * elapsed / duration) as i32;
+            }
+
+            values
+        }
+    }
+
+    pub fn is_complete(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.start) >= self.duration
+    }
+
+    /// The remaining time in 1/10ths of a second, rounded up
+    pub fn remaining_time(&self, now: Instant) -> u16 {
+        let elapsed = now.saturating_duration_since(self.start);
+
+        if elapsed >= self.duration {
+            0
+        } else {
+            let remaining = (self.duration - elapsed).as_millis();
+
+            ((remaining + 99) / 100).min(u16::MAX as u64) as u16
+        }
+    }
+}
+
+/// Drives the transitions of a cluster and throttles their reporting
+///
+/// Values changing during a transition have the `Q` quality: they are reported
+/// at most once per second, and at the end of the transition.
+pub struct TransitionTimer {
+    notification: Notification,
+    reported: Cell<Instant>,
+}
+
+impl TransitionTimer {
+    pub const fn new() -> Self {
+        Self {
+            notification: Notification::new(),
+            reported: Cell::new(Instant::from_ticks(0)),
+        }
+    }
+
+    /// To be called when a transition starts, which the cluster reports right away
+    pub fn started(&self, now: Instant) {
+        self.reported.set(now);
+        self.notification.signal(());
+    }
+
+    /// Whether a change of the values at `now`, in the middle of a transition,
+    /// is to be reported
+    pub fn report(&self, now: Instant) -> bool {
+        let report =
+            now.saturating_duration_since(self.reported.get()) >= Duration::from_millis(REPORT_MS);
+
+        if report {
+            self.reported.set(now);
+        }
+
+        report
+    }
+
+    /// Call `update` every 1/10th of a second while `in_transition` returns `true`
+    ///
+    /// Transitions with a transition time of 0 complete without this running.
+    /// Any other transition stays at its starting values until stopped.
+    pub async fn run<F, U>(&self, in_transition: F, update: U) -> Result<(), Error>
+    where
+        F: Fn() -> bool,
+        U: Fn(Instant),
+    {
+        loop {
+            if in_transition() {
+                Timer::after(Duration::from_millis(TICK_MS)).await;
+
+                update(Instant::now());
+            } else {
+                self.notification.wait().await;
+            }
+        }
+    }
+}
+
+impl Default for TransitionTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_time::{Duration, Instant};
+
+    use super::{Transition, TransitionTimer};
+
+    fn transition(from: i32, to: i32) -> Transition<1> {
+        Transition {
+            from: [from],
+            to: [to],
+            start: Instant::from_millis(1000),
+            duration: Duration::from_millis(2000),
+        }
+    }
+
+    #[test]
+    fn test_transition_values() {
+        let up = transition(10, 210);
+
+        assert_eq!(up.values(Instant::from_millis(0)), [10]);
+        assert_eq!(up.values(Instant::from_millis(1000)), [10]);
+        assert_eq!(up.values(Instant::from_millis(1500)), [60]);
+        assert_eq!(up.values(Instant::from_millis(2000)), [110]);
+        assert_eq!(up.values(Instant::from_millis(3000)), [210]);
+        assert_eq!(up.values(Instant::from_millis(5000)), [210]);
+
+        let down = transition(210, 10);
+
+        assert_eq!(down.values(Instant::from_millis(2000)), [110]);
+        assert_eq!(down.values(Instant::from_millis(3000)), [10]);
+
+        let xy = Transition {
+            from: [1000, 2000],
+            to: [3000, 0],
+            start: Instant::from_millis(1000),
+            duration: Duration::from_millis(1000),
+        };
+
+        assert_eq!(xy.values(Instant::from_millis(1250)), [1500, 1500]);
+        assert_eq!(xy.values(Instant::from_millis(1500)), [2000, 1000]);
+    }
+
+    #[test]
+    fn test_transition_remaining_time() {
+        let t = transition(1, 254);
+
+        assert_eq!(t.remaining_time(Instant::from_millis(1000)), 20);
+        assert_eq!(t.remaining_time(Instant::from_millis(1950)), 11);
+        assert!(!t.is_complete(Instant::from_millis(2999)));
+        assert_eq!(t.remaining_time(Instant::from_millis(3000)), 0);
+        assert!(t.is_complete(Instant::from_millis(3000)));
+    }
+
+    #[test]
+    fn test_transition_immediate() {
+        let t = Transition {
+            duration: Duration::from_ticks(0),
+            ..transition(1, 254)
+        };
+
+        assert_eq!(t.values(Instant::from_millis(1000)), [254]);
+        assert!(t.is_complete(Instant::from_millis(1000)));
+    }
+
+    #[test]
+    fn test_transition_report() {
+        let timer = TransitionTimer::new();
+
+        timer.started(Instant::from_millis(1000));
+
+        assert!(!timer.report(Instant::from_millis(1100)));
+        assert!(!timer.report(Instant::from_millis(1900)));
+        assert!(timer.report(Instant::from_millis(2000)));
+        assert!(!timer.report(Instant::from_millis(2500)));
+        assert!(timer.report(Instant::from_millis(3100)));
+    }
+}
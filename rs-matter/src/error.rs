@@ -31,8 +31,11 @@ pub enum ErrorCode {
     InvalidDataType,
     UnsupportedAccess,
     ResourceExhausted,
+    ConstraintError,
     Busy,
     DataVersionMismatch,
+    NeedsTimedInteraction,
+    FailSafeRequired,
     Crypto,
     TLSStack,
     MdnsError,
@@ -125,7 +125,9 @@ impl Fabric {
             .map_err(|_| Error::from(ErrorCode::NoSpace))
     }
 
-    pub fn match_dest_id(&self, random: &[u8], target: &[u8]) -> Result<(), Error> {
+    /// Compute the destination identifier of node `node_id` of the fabric, as sent by
+    /// the initiator of a CASE session
+    pub fn get_dest_id(&self, random: &[u8], node_id: u64, out: &mut [u8]) -> Result<(), Error> {
         let mut mac = HmacSha256::new(self.ipk.op_key())?;
 
         mac.update(random)?;
@@ -135,11 +137,16 @@ impl Fabric {
         LittleEndian::write_u64(&mut buf, self.fabric_id);
         mac.update(&buf)?;
 
-        LittleEndian::write_u64(&mut buf, self.node_id);
+        LittleEndian::write_u64(&mut buf, node_id);
         mac.update(&buf)?;
 
+        mac.finish(out)
+    }
+
+    pub fn match_dest_id(&self, random: &[u8], target: &[u8]) -> Result<(), Error> {
         let mut id = [0_u8; crypto::SHA256_HASH_LEN_BYTES];
-        mac.finish(&mut id)?;
+        self.get_dest_id(random, self.node_id, &mut id)?;
+
         if id.as_slice() == target {
             Ok(())
         } else {
@@ -155,6 +162,10 @@ impl Fabric {
         self.node_id
     }
 
+    pub fn get_vendor_id(&self) -> u16 {
+        self.vendor_id
+    }
+
     pub fn get_fabric_id(&self) -> u64 {
         self.fabric_id
     }
@@ -188,6 +199,8 @@ type FabricEntries = Vec<Option<Fabric>, MAX_SUPPORTED_FABRICS>;
 pub struct FabricMgr {
     fabrics: FabricEntries,
     changed: bool,
+    // Bit N is set when the fabric with index N was removed
+    removed: u32,
 }
 
 impl FabricMgr {
@@ -196,6 +209,7 @@ impl FabricMgr {
         Self {
             fabrics: FabricEntries::new(),
             changed: false,
+            removed: 0,
         }
     }
 
@@ -269,6 +283,7 @@ impl FabricMgr {
             if let Some(f) = self.fabrics[(fab_idx - 1) as usize].take() {
                 mdns.remove(&f.mdns_service_name)?;
                 self.changed = true;
+                self.removed |= 1 << fab_idx;
                 Ok(())
             } else {
                 Err(ErrorCode::NotFound.into())
@@ -278,6 +293,18 @@ impl FabricMgr {
         }
     }
 
+    /// Take the index of a fabric removed since the last call, if any
+    pub fn take_removed(&mut self) -> Option<u8> {
+        if self.removed == 0 {
+            None
+        } else {
+            let fab_idx = self.removed.trailing_zeros() as u8;
+            self.removed &= !(1 << fab_idx);
+
+            Some(fab_idx)
+        }
+    }
+
     pub fn match_dest_id(&self, random: &[u8], target: &[u8]) -> Result<usize, Error> {
         for (index, fabric) in self.fabrics.iter().enumerate() {
             if let Some(fabric) = fabric {
@@ -293,7 +320,7 @@ impl FabricMgr {
         if idx == 0 {
             Ok(None)
         } else {
-            Ok(self.fabrics[idx - 1].as_ref())
+            Ok(self.fabrics.get(idx - 1).and_then(Option::as_ref))
         }
     }
 
@@ -0,0 +1,258 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::time::Duration;
+
+use log::info;
+
+use crate::transport::mrp::SessionParams;
+
+/// The fixed parameters of an Intermittently Connected Device (ICD)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IcdConfig {
+    /// How long the device stays in idle mode before waking up on its own, in seconds
+    pub idle_mode_duration_secs: u32,
+    /// How long the device stays in active mode after waking up, in milliseconds
+    pub active_mode_duration_ms: u32,
+    /// How long the device stays in active mode after sending or receiving a message,
+    /// in milliseconds
+    pub active_mode_threshold_ms: u16,
+    /// The interval at which the device polls for messages while idle, in milliseconds
+    pub idle_interval_ms: u32,
+    /// The interval at which the device polls for messages while active, in milliseconds
+    pub active_interval_ms: u32,
+}
+
+impl IcdConfig {
+    pub const fn session_params(&self) -> SessionParams {
+        SessionParams {
+            idle_interval_ms: self.idle_interval_ms,
+            active_interval_ms: self.active_interval_ms,
+            active_threshold_ms: self.active_mode_threshold_ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperatingMode {
+    Idle,
+    Active,
+}
+
+/// The operating mode of the node
+///
+/// Devices which are not ICDs are always active. ICDs alternate between the idle and the
+/// active mode, with the transitions driven by `IcdMgmtCluster::run`, which calls `update`
+/// by `next_transition` and sends the Check-In messages whenever the device enters the
+/// active mode. User actions wake the device up with `IcdMgmtCluster::wake`.
+pub struct IcdManager {
+    conf: Option<IcdConfig>,
+    mode: OperatingMode,
+    // When the current mode ends, as per the epoch
+    mode_until: Option<Duration>,
+}
+
+impl IcdManager {
+    #[inline(always)]
+    pub const fn new(conf: Option<IcdConfig>) -> Self {
+        Self {
+            conf,
+            mode: OperatingMode::Active,
+            mode_until: None,
+        }
+    }
+
+    pub fn config(&self) -> Option<&IcdConfig> {
+        self.conf.as_ref()
+    }
+
+    pub fn is_icd(&self) -> bool {
+        self.conf.is_some()
+    }
+
+    pub fn mode(&self) -> OperatingMode {
+        self.mode
+    }
+
+    /// The interval at which the device polls for messages in its current mode, in
+    /// milliseconds, which delays the acknowledgements it gets
+    ///
+    /// `0` for devices which are not ICDs, as they are always reachable.
+    pub fn poll_interval_ms(&self) -> u32 {
+        match (self.conf, self.mode) {
+            (Some(conf), OperatingMode::Active) => conf.active_interval_ms,
+            (Some(conf), OperatingMode::Idle) => conf.idle_interval_ms,
+            (None, _) => 0,
+        }
+    }
+
+    pub fn session_params(&self) -> SessionParams {
+        self.conf
+            .as_ref()
+            .map(IcdConfig::session_params)
+            .unwrap_or_default()
+    }
+
+    /// When the current mode ends, as per the epoch
+    ///
+    /// `None` for devices which are not ICDs, or before the first `update`.
+    pub fn next_transition(&self) -> Option<Duration> {
+        self.mode_until
+    }
+
+    /// Enter the active mode for the ActiveModeDuration from `now`, e.g. on a user action
+    ///
+    /// Returns `true` if the device was idle, in which case the registered clients should
+    /// be checked in.
+    pub fn wake(&mut self, now: Duration) -> bool {
+        if let Some(conf) = self.conf {
+            self.stay_active(now, conf.active_mode_duration_ms)
+        } else {
+            false
+        }
+    }
+
+    /// Stay in the active mode for at least `duration_ms` milliseconds from `now`
+    ///
+    /// Returns `true` if the device was idle.
+    pub fn stay_active(&mut self, now: Duration, duration_ms: u32) -> bool {
+        if self.conf.is_none() {
+            return false;
+        }
+
+        let until = now + Duration::from_millis(duration_ms as _);
+        let woke = self.mode == OperatingMode::Idle;
+
+        if woke {
+            info!("ICD entering active mode");
+
+            self.mode = OperatingMode::Active;
+            self.mode_until = Some(until);
+        } else {
+            self.mode_until = Some(self.mode_until.map_or(until, |prev| prev.max(until)));
+        }
+
+        woke
+    }
+
+    /// Keep the device active for the ActiveModeThreshold after a message was sent or
+    /// received at `now`
+    pub fn on_activity(&mut self, now: Duration) {
+        if let Some(conf) = self.conf {
+            self.stay_active(now, conf.active_mode_threshold_ms as _);
+        }
+    }
+
+    /// Advance the operating mode to `now`
+    ///
+    /// Returns the new mode if it changed.
+    pub fn update(&mut self, now: Duration) -> Option<OperatingMode> {
+        let conf = self.conf?;
+
+        match self.mode_until {
+            None => {
+                // The device starts in the active mode
+                self.mode_until =
+                    Some(now + Duration::from_millis(conf.active_mode_duration_ms as _));
+                None
+            }
+            Some(until) if now >= until => match self.mode {
+                OperatingMode::Active => {
+                    info!("ICD entering idle mode");
+
+                    self.mode = OperatingMode::Idle;
+                    self.mode_until =
+                        Some(now + Duration::from_secs(conf.idle_mode_duration_secs as _));
+
+                    Some(OperatingMode::Idle)
+                }
+                OperatingMode::Idle => {
+                    self.wake(now);
+
+                    Some(OperatingMode::Active)
+                }
+            },
+            Some(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{IcdConfig, IcdManager, OperatingMode};
+
+    const CONF: IcdConfig = IcdConfig {
+        idle_mode_duration_secs: 60,
+        active_mode_duration_ms: 1000,
+        active_mode_threshold_ms: 500,
+        idle_interval_ms: 30000,
+        active_interval_ms: 300,
+    };
+
+    #[test]
+    fn test_operating_modes() {
+        let mut icd = IcdManager::new(Some(CONF));
+
+        assert_eq!(icd.update(Duration::from_secs(0)), None);
+        assert_eq!(icd.next_transition(), Some(Duration::from_millis(1000)));
+
+        // Activity extends the active mode by the threshold
+        icd.on_activity(Duration::from_millis(800));
+        assert_eq!(icd.update(Duration::from_millis(1000)), None);
+        assert_eq!(
+            icd.update(Duration::from_millis(1300)),
+            Some(OperatingMode::Idle)
+        );
+        assert_eq!(icd.next_transition(), Some(Duration::from_millis(61300)));
+
+        assert_eq!(
+            icd.update(Duration::from_millis(61300)),
+            Some(OperatingMode::Active)
+        );
+        assert_eq!(icd.next_transition(), Some(Duration::from_millis(62300)));
+
+        // Waking up an active device does not check in the clients again
+        assert!(!icd.wake(Duration::from_millis(62000)));
+        assert_eq!(icd.next_transition(), Some(Duration::from_millis(63000)));
+    }
+
+    #[test]
+    fn test_poll_interval() {
+        let mut icd = IcdManager::new(Some(CONF));
+
+        icd.update(Duration::from_secs(0));
+        assert_eq!(icd.poll_interval_ms(), 300);
+
+        icd.update(Duration::from_millis(1000));
+        assert_eq!(icd.mode(), OperatingMode::Idle);
+        assert_eq!(icd.poll_interval_ms(), 30000);
+
+        assert_eq!(IcdManager::new(None).poll_interval_ms(), 0);
+    }
+
+    #[test]
+    fn test_not_icd() {
+        let mut icd = IcdManager::new(None);
+
+        assert_eq!(icd.update(Duration::from_secs(0)), None);
+        assert!(!icd.wake(Duration::from_secs(100)));
+        assert_eq!(icd.mode(), OperatingMode::Active);
+        assert_eq!(icd.next_transition(), None);
+    }
+}
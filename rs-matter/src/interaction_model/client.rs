@@ -0,0 +1,203 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use log::warn;
+
+use crate::{
+    data_model::objects::{ClusterId, CmdId, EncodeValue, EndptId},
+    error::{Error, ErrorCode},
+    tlv::{get_root_node_struct, FromTLV, TLVArray, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::{
+        exchange::Exchange,
+        network::Address,
+        packet::{Packet, MAX_RX_BUF_SIZE, MAX_TX_BUF_SIZE},
+    },
+    Matter,
+};
+
+use super::{
+    core::{IMStatusCode, OpCode, PROTO_ID_INTERACTION_MODEL},
+    messages::{
+        ib::{CmdData, CmdPath, InvResp},
+        msg,
+    },
+};
+
+/// Looks up the operational address of a node
+///
+/// The mDNS responder only advertises our own services, so resolving the operational
+/// instance name of a peer node is left to the application.
+pub trait NodeResolver {
+    fn resolve(&self, fab_idx: u8, node_id: u64) -> Option<Address>;
+}
+
+impl<F> NodeResolver for F
+where
+    F: Fn(u8, u64) -> Option<Address>,
+{
+    fn resolve(&self, fab_idx: u8, node_id: u64) -> Option<Address> {
+        self(fab_idx, node_id)
+    }
+}
+
+/// Invokes commands on other nodes, i.e. on the targets of bindings or on an OTA Provider
+///
+/// Commands are sent over the CASE session with the node, which is established first
+/// if there is none yet. The transport needs to be running for the client to make
+/// progress.
+pub struct InvokeClient<'a, R> {
+    matter: &'a Matter<'a>,
+    resolver: R,
+    tx_buf: [u8; MAX_TX_BUF_SIZE],
+    rx_buf: [u8; MAX_RX_BUF_SIZE],
+}
+
+impl<'a, R> InvokeClient<'a, R>
+where
+    R: NodeResolver,
+{
+    pub const fn new(matter: &'a Matter<'a>, resolver: R) -> Self {
+        Self {
+            matter,
+            resolver,
+            tx_buf: [0; MAX_TX_BUF_SIZE],
+            rx_buf: [0; MAX_RX_BUF_SIZE],
+        }
+    }
+
+    /// Initiate an exchange with node `node_id` of our fabric `fab_idx`, i.e. for
+    /// a protocol other than the Interaction Model
+    ///
+    /// Returns the exchange along with the packets to use for it.
+    pub async fn initiate(
+        &mut self,
+        fab_idx: u8,
+        node_id: u64,
+    ) -> Result<(Exchange<'a>, Packet<'_>, Packet<'_>), Error> {
+        let session_id = match self.matter.get_case_session(fab_idx, node_id) {
+            Some(session_id) => session_id,
+            None => {
+                let peer_addr = self
+                    .resolver
+                    .resolve(fab_idx, node_id)
+                    .ok_or(ErrorCode::NotFound)?;
+
+                let mut tx = Packet::new_tx(&mut self.tx_buf);
+                let mut rx = Packet::new_rx(&mut self.rx_buf);
+
+                self.matter
+                    .establish_case_session(fab_idx, node_id, peer_addr, &mut tx, &mut rx)
+                    .await?
+            }
+        };
+
+        let exchange = self.matter.initiate_exchange(session_id)?;
+
+        Ok((
+            exchange,
+            Packet::new_tx(&mut self.tx_buf),
+            Packet::new_rx(&mut self.rx_buf),
+        ))
+    }
+
+    /// Invoke command `cmd` of `cluster` on `endpoint` of node `node_id` of our fabric
+    /// `fab_idx`
+    ///
+    /// The data of the response command, if any, is ignored.
+    pub async fn invoke(
+        &mut self,
+        fab_idx: u8,
+        node_id: u64,
+        endpoint: EndptId,
+        cluster: ClusterId,
+        cmd: CmdId,
+        data: &dyn ToTLV,
+    ) -> Result<(), Error> {
+        self.invoke_with(fab_idx, node_id, endpoint, cluster, cmd, data, |_| Ok(()))
+            .await
+    }
+
+    /// Same as `invoke`, but hands the data of the response command to `f`
+    ///
+    /// `f` gets `None` if the node responded with a success status instead.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn invoke_with<F, T>(
+        &mut self,
+        fab_idx: u8,
+        node_id: u64,
+        endpoint: EndptId,
+        cluster: ClusterId,
+        cmd: CmdId,
+        data: &dyn ToTLV,
+        f: F,
+    ) -> Result<T, Error>
+    where
+        F: FnOnce(Option<&TLVElement>) -> Result<T, Error>,
+    {
+        let (mut exchange, mut tx, mut rx) = self.initiate(fab_idx, node_id).await?;
+
+        let inv_requests = [CmdData::new(
+            CmdPath::new(Some(endpoint), Some(cluster), Some(cmd)),
+            EncodeValue::Value(data),
+        )];
+
+        let req = msg::InvReq {
+            suppress_response: Some(false),
+            timed_request: Some(false),
+            inv_requests: Some(TLVArray::Slice(&inv_requests)),
+        };
+
+        tx.reset();
+        tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
+        tx.set_proto_opcode(OpCode::InvokeRequest as u8);
+
+        let mut tw = TLVWriter::new(tx.get_writebuf()?);
+        req.to_tlv(&mut tw, TagType::Anonymous)?;
+
+        exchange.exchange(&tx, &mut rx).await?;
+
+        let result = Self::response(&rx).and_then(|data| f(data.as_ref()));
+
+        exchange.acknowledge().await?;
+
+        result
+    }
+
+    // The data of the (single) response command, or `None` for a success status
+    fn response<'r>(rx: &'r Packet) -> Result<Option<TLVElement<'r>>, Error> {
+        if rx.get_proto_id() != PROTO_ID_INTERACTION_MODEL
+            || rx.get_proto_opcode::<OpCode>()? != OpCode::InvokeResponse
+        {
+            Err(ErrorCode::InvalidOpcode)?;
+        }
+
+        let resp = msg::InvResp::from_tlv(&get_root_node_struct(rx.as_slice())?)?;
+        let resp = resp.inv_responses.and_then(|resp| resp.iter().next());
+
+        match resp {
+            Some(InvResp::Cmd(data)) => Ok(Some(data.data.unwrap_tlv().ok_or(ErrorCode::Invalid)?)),
+            Some(InvResp::Status(status)) if status.status.status == IMStatusCode::Success => {
+                Ok(None)
+            }
+            Some(InvResp::Status(status)) => {
+                warn!("Invoking {:?} failed: {:?}", status.path, status.status);
+                Err(ErrorCode::Invalid.into())
+            }
+            None => Err(ErrorCode::Invalid.into()),
+        }
+    }
+}
@@ -20,14 +20,18 @@ use core::time::Duration;
 use crate::{
     acl::Accessor,
     error::*,
-    tlv::{get_root_node_struct, FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+    tlv::{get_root_node_struct, FromTLV, TLVArray, TLVElement, TLVWriter, TagType, ToTLV},
     transport::{exchange::Exchange, packet::Packet},
-    utils::epoch::Epoch,
+    utils::epoch::{Epoch, UtcClock},
 };
 use log::error;
 use num::{self, FromPrimitive};
 use num_derive::FromPrimitive;
 
+use super::events::EventLog;
+use super::subscriptions::SUBSCRIPTION_MAX_INT_SECS;
+
+use super::messages::ib::{EventFilter, EventPath};
 use super::messages::msg::{
     self, InvReq, ReadReq, StatusResp, SubscribeReq, SubscribeResp, TimedReq, WriteReq,
 };
@@ -82,7 +86,11 @@ impl From<ErrorCode> for IMStatusCode {
             ErrorCode::UnsupportedAccess => IMStatusCode::UnsupportedAccess,
             ErrorCode::Busy => IMStatusCode::Busy,
             ErrorCode::DataVersionMismatch => IMStatusCode::DataVersionMismatch,
+            ErrorCode::NeedsTimedInteraction => IMStatusCode::NeedsTimedInteraction,
+            ErrorCode::FailSafeRequired => IMStatusCode::FailSafeRequired,
             ErrorCode::ResourceExhausted => IMStatusCode::ResourceExhausted,
+            ErrorCode::ConstraintError => IMStatusCode::ConstraintError,
+            ErrorCode::NotFound => IMStatusCode::NotFound,
             _ => IMStatusCode::Failure,
         }
     }
@@ -128,6 +136,33 @@ pub const PROTO_ID_INTERACTION_MODEL: u16 = 0x01;
 // the end of long reads.
 const LONG_READS_TLV_RESERVE_SIZE: usize = 24;
 
+// The space needed to close a report after its event reports
+const EVENT_REPORTS_TLV_RESERVE_SIZE: usize = 4;
+
+/// Write the EventReports of the last chunk of a report, with as many of the requested
+/// events as fit
+fn tx_events(
+    tw: &mut TLVWriter,
+    event_requests: Option<&TLVArray<EventPath>>,
+    event_filters: Option<&TLVArray<EventFilter>>,
+    events: &EventLog,
+    utc: &UtcClock,
+    accessor: &Accessor,
+) -> Result<(), Error> {
+    if let Some(event_requests) = event_requests {
+        tw.start_array(TagType::Context(msg::ReportDataTag::EventReports as u8))?;
+
+        tw.get_buf().shrink(EVENT_REPORTS_TLV_RESERVE_SIZE)?;
+        let result = events.report(tw, event_requests, event_filters, utc, accessor);
+        tw.get_buf().expand(EVENT_REPORTS_TLV_RESERVE_SIZE)?;
+        result?;
+
+        tw.end_container()?;
+    }
+
+    Ok(())
+}
+
 impl<'a> ReadReq<'a> {
     pub fn tx_start<'r, 'p>(&self, tx: &'r mut Packet<'p>) -> Result<TLVWriter<'r, 'p>, Error> {
         tx.reset();
@@ -146,20 +181,44 @@ impl<'a> ReadReq<'a> {
     }
 
     pub fn tx_finish_chunk(&self, tx: &mut Packet) -> Result<(), Error> {
-        self.complete(tx, true)
+        self.complete(tx, None)
     }
 
-    pub fn tx_finish(&self, tx: &mut Packet) -> Result<(), Error> {
-        self.complete(tx, false)
+    pub fn tx_finish(
+        &self,
+        tx: &mut Packet,
+        events: &EventLog,
+        utc: &UtcClock,
+        accessor: &Accessor,
+    ) -> Result<(), Error> {
+        self.complete(tx, Some((events, utc, accessor)))
     }
 
-    fn complete(&self, tx: &mut Packet<'_>, more_chunks: bool) -> Result<(), Error> {
+    /// Complete a chunk of the report, which is the last one if it gets the events
+    fn complete(
+        &self,
+        tx: &mut Packet<'_>,
+        events: Option<(&EventLog, &UtcClock, &Accessor)>,
+    ) -> Result<(), Error> {
+        let more_chunks = events.is_none();
+
         let mut tw = Self::restore_long_read_space(tx)?;
 
         if self.attr_requests.is_some() {
             tw.end_container()?;
         }
 
+        if let Some((events, utc, accessor)) = events {
+            tx_events(
+                &mut tw,
+                self.event_requests.as_ref(),
+                self.event_filters.as_ref(),
+                events,
+                utc,
+                accessor,
+            )?;
+        }
+
         if more_chunks {
             tw.bool(
                 TagType::Context(msg::ReportDataTag::MoreChunkedMsgs as u8),
@@ -320,13 +379,31 @@ impl<'a> SubscribeReq<'a> {
         Ok(tw)
     }
 
-    pub fn tx_finish_chunk(&self, tx: &mut Packet<'_>, more_chunks: bool) -> Result<(), Error> {
+    /// Complete a chunk of the priming report, which is the last one if it gets the events
+    pub fn tx_finish_chunk(
+        &self,
+        tx: &mut Packet<'_>,
+        events: Option<(&EventLog, &UtcClock, &Accessor)>,
+    ) -> Result<(), Error> {
+        let more_chunks = events.is_none();
+
         let mut tw = ReadReq::restore_long_read_space(tx)?;
 
         if self.attr_requests.is_some() {
             tw.end_container()?;
         }
 
+        if let Some((events, utc, accessor)) = events {
+            tx_events(
+                &mut tw,
+                self.event_requests.as_ref(),
+                self.event_filters.as_ref(),
+                events,
+                utc,
+                accessor,
+            )?;
+        }
+
         if more_chunks {
             tw.bool(
                 TagType::Context(msg::ReportDataTag::MoreChunkedMsgs as u8),
@@ -349,7 +426,7 @@ impl<'a> SubscribeReq<'a> {
 
         let mut tw = TLVWriter::new(tx.get_writebuf()?);
 
-        let resp = SubscribeResp::new(subscription_id, 40);
+        let resp = SubscribeResp::new(subscription_id, SUBSCRIPTION_MAX_INT_SECS);
         resp.to_tlv(&mut tw, TagType::Anonymous)
     }
 }
@@ -403,7 +480,14 @@ impl<'a, 'r, 'p> ReadDriver<'a, 'r, 'p> {
     }
 
     pub async fn complete(&mut self, req: &ReadReq<'_>) -> Result<(), Error> {
-        req.tx_finish(self.tx)?;
+        let accessor = self.exchange.accessor()?;
+        let matter = self.exchange.matter;
+        req.tx_finish(
+            self.tx,
+            &matter.events.borrow(),
+            &matter.utc_clock,
+            &accessor,
+        )?;
 
         self.exchange.send_complete(self.tx).await
     }
@@ -556,7 +640,7 @@ impl<'a, 'r, 'p> SubscribeDriver<'a, 'r, 'p> {
     }
 
     pub async fn send_chunk(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error> {
-        req.tx_finish_chunk(self.tx, true)?;
+        req.tx_finish_chunk(self.tx, None)?;
 
         if exchange_confirm(self.exchange, self.tx, self.rx).await? != IMStatusCode::Success {
             self.completed = true;
@@ -570,13 +654,37 @@ impl<'a, 'r, 'p> SubscribeDriver<'a, 'r, 'p> {
 
     pub async fn complete(&mut self, req: &SubscribeReq<'_>) -> Result<(), Error> {
         if !self.completed {
-            req.tx_finish_chunk(self.tx, false)?;
+            let accessor = self.exchange.accessor()?;
+            req.tx_finish_chunk(
+                self.tx,
+                Some((
+                    &self.exchange.matter.events.borrow(),
+                    &self.exchange.matter.utc_clock,
+                    &accessor,
+                )),
+            )?;
 
             if exchange_confirm(self.exchange, self.tx, self.rx).await? != IMStatusCode::Success {
                 self.completed = true;
             } else {
+                let subscriber = self.exchange.with_session(|sess| {
+                    Ok(sess.get_local_fabric_idx().zip(sess.get_peer_node_id()))
+                })?;
+
                 req.tx_process_final(self.tx, self.subscription_id)?;
                 self.exchange.send_complete(self.tx).await?;
+
+                // Subscriptions are only tracked for operational (CASE) sessions
+                if let Some((fab_idx, node_id)) = subscriber {
+                    let matter = self.exchange.matter;
+                    let mut subscriptions = matter.subscriptions.borrow_mut();
+
+                    if !req.keep_subs {
+                        subscriptions.remove_subscriber(fab_idx, node_id);
+                    }
+
+                    subscriptions.add(fab_idx, node_id, (matter.epoch)());
+                }
             }
         }
 
@@ -0,0 +1,402 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::time::Duration;
+
+use log::{info, warn};
+
+use crate::{
+    acl::{AccessReq, Accessor},
+    data_model::objects::{Access, ClusterId, EndptId},
+    error::{Error, ErrorCode},
+    tlv::{TLVArray, TLVWriter, TagType, ToTLV},
+    utils::{
+        epoch::{Epoch, UtcClock, MATTER_EPOCH_SECS},
+        writebuf::WriteBuf,
+    },
+};
+
+use super::messages::{
+    ib::{EventFilter, EventPath},
+    GenericPath,
+};
+
+/// The number of events kept by the node; the oldest event is dropped once the log is full
+pub const MAX_EVENTS: usize = 16;
+
+/// The maximum size of the TLV-encoded data of an event
+pub const MAX_EVENT_DATA_LEN: usize = 64;
+
+// The context tag of the data of an EventDataIB
+const EVENT_DATA_TAG: u8 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EventPriority {
+    Debug = 0,
+    Info = 1,
+    Critical = 2,
+}
+
+#[derive(Debug, Clone)]
+struct Event {
+    number: u64,
+    priority: EventPriority,
+    endpoint: EndptId,
+    cluster: ClusterId,
+    event: u32,
+    // The fabric of a fabric-sensitive event, which is only reported to that fabric
+    fab_idx: Option<u8>,
+    // When the event was emitted, as per the epoch
+    at: Duration,
+    data: heapless::Vec<u8, MAX_EVENT_DATA_LEN>,
+}
+
+impl Event {
+    fn matches(&self, path: &EventPath) -> bool {
+        path.endpoint.map(|e| e == self.endpoint).unwrap_or(true)
+            && path.cluster.map(|c| c == self.cluster).unwrap_or(true)
+            && path.event.map(|e| e == self.event).unwrap_or(true)
+    }
+
+    fn allowed(&self, accessor: &Accessor) -> bool {
+        if self
+            .fab_idx
+            .map(|fab_idx| fab_idx != accessor.fab_idx)
+            .unwrap_or(false)
+        {
+            return false;
+        }
+
+        let path = GenericPath::new(Some(self.endpoint), Some(self.cluster), Some(self.event));
+
+        let mut access_req = AccessReq::new(accessor, path, Access::READ);
+        access_req.set_target_perms(Access::RV);
+
+        access_req.allow()
+    }
+
+    /// `utc_us` is the UTC time when the event was emitted, if it is known
+    fn to_tlv(&self, tw: &mut TLVWriter, utc_us: Option<u64>) -> Result<(), Error> {
+        // EventReportIB
+        tw.start_struct(TagType::Anonymous)?;
+
+        // EventDataIB
+        tw.start_struct(TagType::Context(1))?;
+        EventPath {
+            node: None,
+            endpoint: Some(self.endpoint),
+            cluster: Some(self.cluster),
+            event: Some(self.event),
+            is_urgent: None,
+        }
+        .to_tlv(tw, TagType::Context(0))?;
+        tw.u64(TagType::Context(1), self.number)?;
+        tw.u8(TagType::Context(2), self.priority as _)?;
+        if let Some(utc_us) = utc_us {
+            // The epoch timestamp, in milliseconds since the Unix epoch
+            tw.u64(
+                TagType::Context(3),
+                utc_us / 1000 + MATTER_EPOCH_SECS * 1000,
+            )?;
+        } else {
+            // The system timestamp, in milliseconds
+            tw.u64(TagType::Context(4), self.at.as_millis() as _)?;
+        }
+        tw.get_buf().append(&self.data)?;
+        tw.end_container()?;
+
+        tw.end_container()
+    }
+}
+
+/// The events emitted by the clusters of the node, reported by the Read and Subscribe
+/// interactions which request them
+///
+/// Event numbers are only monotonic while the node is running, as they are not persisted.
+pub struct EventLog {
+    epoch: Epoch,
+    next_number: u64,
+    events: heapless::Deque<Event, MAX_EVENTS>,
+}
+
+impl EventLog {
+    #[inline(always)]
+    pub const fn new(epoch: Epoch) -> Self {
+        Self {
+            epoch,
+            next_number: 0,
+            events: heapless::Deque::new(),
+        }
+    }
+
+    /// Record an event of cluster `cluster` on endpoint `endpoint`, whose fields are encoded
+    /// by `data`, returning its event number
+    ///
+    /// `fab_idx` is the fabric of a fabric-sensitive event, which is only reported to the
+    /// accessors of that fabric.
+    pub fn push<T: ToTLV>(
+        &mut self,
+        endpoint: EndptId,
+        cluster: ClusterId,
+        event: u32,
+        priority: EventPriority,
+        fab_idx: Option<u8>,
+        data: &T,
+    ) -> Result<u64, Error> {
+        let mut buf = [0; MAX_EVENT_DATA_LEN];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+
+        data.to_tlv(&mut tw, TagType::Context(EVENT_DATA_TAG))?;
+
+        let number = self.next_number;
+        self.next_number += 1;
+
+        info!(
+            "Event {}: endpoint {}, cluster {:04x}, event {:02x}",
+            number, endpoint, cluster, event
+        );
+
+        let event = Event {
+            number,
+            priority,
+            endpoint,
+            cluster,
+            event,
+            fab_idx,
+            at: (self.epoch)(),
+            data: heapless::Vec::from_slice(wb.as_slice()).map_err(|_| ErrorCode::NoSpace)?,
+        };
+
+        if self.events.is_full() {
+            self.events.pop_front();
+        }
+
+        self.events
+            .push_back(event)
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        Ok(number)
+    }
+
+    /// Write the events matching `paths` and `filters` which `accessor` can read, as
+    /// EventReportIBs
+    ///
+    /// Events carry an epoch timestamp once `utc` is set, and a system timestamp before.
+    ///
+    /// Events are not chunked: they are only reported in the last chunk of a report, and
+    /// the ones which do not fit in `tw` are left out, with a warning. Reporting stops at
+    /// the first such event, so that a later read with an EventMin filter picks up from
+    /// there.
+    pub fn report(
+        &self,
+        tw: &mut TLVWriter,
+        paths: &TLVArray<EventPath>,
+        filters: Option<&TLVArray<EventFilter>>,
+        utc: &UtcClock,
+        accessor: &Accessor,
+    ) -> Result<(), Error> {
+        let now = (self.epoch)();
+        let utc_now_us = utc.now_us();
+
+        let event_min = filters
+            .and_then(|filters| filters.iter().filter_map(|filter| filter.event_min).max())
+            .unwrap_or(0);
+
+        let reportable = |event: &&Event| {
+            event.number >= event_min
+                && paths.iter().any(|path| event.matches(&path))
+                && event.allowed(accessor)
+        };
+
+        for event in self.events.iter().filter(reportable) {
+            let anchor = tw.get_tail();
+
+            // The UTC time of the event, from how long ago it was emitted
+            let utc_us = utc_now_us.map(|utc_now_us| {
+                utc_now_us.saturating_sub(now.saturating_sub(event.at).as_micros() as u64)
+            });
+
+            if let Err(err) = event.to_tlv(tw, utc_us) {
+                if err.code() == ErrorCode::NoSpace {
+                    tw.rewind_to(anchor);
+
+                    let left_out = self
+                        .events
+                        .iter()
+                        .filter(reportable)
+                        .filter(|other| other.number >= event.number)
+                        .count();
+
+                    warn!(
+                        "{} events do not fit in the report, starting with event {}",
+                        left_out, event.number
+                    );
+
+                    break;
+                }
+
+                Err(err)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use core::time::Duration;
+
+    use crate::{
+        acl::{Accessor, AccessorSubjects, AclMgr, AuthMode},
+        interaction_model::messages::ib::EventPath,
+        tlv::{get_root_node_struct, TLVArray, TLVWriter},
+        utils::{
+            epoch::{dummy_epoch, UtcClock, MATTER_EPOCH_SECS},
+            writebuf::WriteBuf,
+        },
+    };
+
+    use super::{EventLog, EventPriority, MAX_EVENTS};
+
+    static NOW_MS: AtomicU64 = AtomicU64::new(100_000);
+
+    fn test_epoch() -> Duration {
+        Duration::from_millis(NOW_MS.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn test_push() {
+        let mut log = EventLog::new(dummy_epoch);
+
+        for number in 0..MAX_EVENTS as u64 + 2 {
+            assert_eq!(
+                log.push(1, 0x39, 3, EventPriority::Info, None, &true)
+                    .unwrap(),
+                number
+            );
+        }
+
+        // The oldest events were dropped
+        assert_eq!(log.events.len(), MAX_EVENTS);
+        assert_eq!(log.events.front().unwrap().number, 2);
+        assert_eq!(log.events.front().unwrap().at, Duration::ZERO);
+        assert_eq!(log.events.front().unwrap().data.as_slice(), &[0x29, 7]);
+    }
+
+    #[test]
+    fn test_report_timestamps() {
+        let mut log = EventLog::new(test_epoch);
+        log.push(1, 0x39, 3, EventPriority::Info, None, &true)
+            .unwrap();
+
+        NOW_MS.fetch_add(2500, Ordering::Relaxed);
+
+        let acl_mgr = RefCell::new(AclMgr::new());
+        let accessor = Accessor::new(0, AccessorSubjects::new(1), AuthMode::Pase, &acl_mgr);
+        let paths = [EventPath {
+            node: None,
+            endpoint: Some(1),
+            cluster: None,
+            event: None,
+            is_urgent: None,
+        }];
+        let utc = UtcClock::new(test_epoch);
+
+        let report = |buf: &mut [u8], utc: &UtcClock| {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            log.report(&mut tw, &TLVArray::new(&paths), None, utc, &accessor)
+                .unwrap();
+
+            wb.get_tail()
+        };
+
+        // Without the UTC time, the system timestamp of when the event was emitted
+        let mut buf = [0; 64];
+        let len = report(&mut buf, &utc);
+        let data = get_root_node_struct(&buf[..len])
+            .unwrap()
+            .find_tag(1)
+            .unwrap();
+        assert!(data.find_tag(3).is_err());
+        assert_eq!(data.find_tag(4).unwrap().u64().unwrap(), 100_000);
+
+        // With the UTC time, the epoch timestamp of when the event was emitted
+        utc.set(5_000_000);
+        let len = report(&mut buf, &utc);
+        let data = get_root_node_struct(&buf[..len])
+            .unwrap()
+            .find_tag(1)
+            .unwrap();
+        assert!(data.find_tag(4).is_err());
+        assert_eq!(
+            data.find_tag(3).unwrap().u64().unwrap(),
+            2500 + MATTER_EPOCH_SECS * 1000
+        );
+    }
+
+    #[test]
+    fn test_report_no_space() {
+        let mut log = EventLog::new(dummy_epoch);
+        for _ in 0..3 {
+            log.push(1, 0x39, 3, EventPriority::Info, None, &true)
+                .unwrap();
+        }
+
+        let acl_mgr = RefCell::new(AclMgr::new());
+        let accessor = Accessor::new(0, AccessorSubjects::new(1), AuthMode::Pase, &acl_mgr);
+        let paths = [EventPath {
+            node: None,
+            endpoint: Some(1),
+            cluster: None,
+            event: None,
+            is_urgent: None,
+        }];
+        let utc = UtcClock::new(dummy_epoch);
+
+        let report = |buf: &mut [u8]| {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            log.report(&mut tw, &TLVArray::new(&paths), None, &utc, &accessor)
+                .unwrap();
+
+            wb.get_tail()
+        };
+
+        let mut buf = [0; 256];
+        let all = report(&mut buf);
+
+        // Only the first event fits, and the ones left out are not an error
+        let len = report(&mut buf[..all / 2]);
+        assert_eq!(len, all / 3);
+
+        let data = get_root_node_struct(&buf[..len])
+            .unwrap()
+            .find_tag(1)
+            .unwrap();
+        assert_eq!(data.find_tag(1).unwrap().u64().unwrap(), 0);
+
+        // Nothing is written if not even the first event fits
+        assert_eq!(report(&mut buf[..all / 3 - 1]), 0);
+    }
+}
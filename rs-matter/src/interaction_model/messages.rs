@@ -74,7 +74,7 @@ pub mod msg {
 
     use super::ib::{
         self, AttrData, AttrPath, AttrResp, AttrStatus, CmdData, DataVersionFilter, EventFilter,
-        EventPath,
+        EventPath, EventResp,
     };
 
     #[derive(Debug, Default, FromTLV, ToTLV)]
@@ -84,8 +84,8 @@ pub mod msg {
         pub min_int_floor: u16,
         pub max_int_ceil: u16,
         pub attr_requests: Option<TLVArray<'a, AttrPath>>,
-        event_requests: Option<TLVArray<'a, EventPath>>,
-        event_filters: Option<TLVArray<'a, EventFilter>>,
+        pub event_requests: Option<TLVArray<'a, EventPath>>,
+        pub event_filters: Option<TLVArray<'a, EventFilter>>,
         // The Context Tags are discontiguous for some reason
         _dummy: Option<bool>,
         pub fabric_filtered: bool,
@@ -168,8 +168,8 @@ pub mod msg {
     #[tlvargs(lifetime = "'a")]
     pub struct ReadReq<'a> {
         pub attr_requests: Option<TLVArray<'a, AttrPath>>,
-        event_requests: Option<TLVArray<'a, EventPath>>,
-        event_filters: Option<TLVArray<'a, EventFilter>>,
+        pub event_requests: Option<TLVArray<'a, EventPath>>,
+        pub event_filters: Option<TLVArray<'a, EventFilter>>,
         pub fabric_filtered: bool,
         pub dataver_filters: Option<TLVArray<'a, DataVersionFilter>>,
     }
@@ -186,6 +186,11 @@ pub mod msg {
             self.attr_requests = Some(TLVArray::new(requests));
             self
         }
+
+        pub fn set_event_requests(mut self, requests: &'a [EventPath]) -> Self {
+            self.event_requests = Some(TLVArray::new(requests));
+            self
+        }
     }
 
     #[derive(FromTLV, ToTLV, Debug)]
@@ -218,8 +223,7 @@ pub mod msg {
     pub struct ReportDataMsg<'a> {
         pub subscription_id: Option<u32>,
         pub attr_reports: Option<TLVArray<'a, AttrResp<'a>>>,
-        // TODO
-        pub event_reports: Option<bool>,
+        pub event_reports: Option<TLVArray<'a, EventResp<'a>>>,
         pub more_chunks: Option<bool>,
         pub suppress_response: Option<bool>,
     }
@@ -227,7 +231,7 @@ pub mod msg {
     pub enum ReportDataTag {
         SubscriptionId = 0,
         AttributeReports = 1,
-        _EventReport = 2,
+        EventReports = 2,
         MoreChunkedMsgs = 3,
         SupressResponse = 4,
     }
@@ -293,8 +297,8 @@ pub mod ib {
 
     #[derive(FromTLV, ToTLV, Clone, PartialEq, Debug)]
     pub struct CmdStatus {
-        path: CmdPath,
-        status: Status,
+        pub path: CmdPath,
+        pub status: Status,
     }
 
     impl CmdStatus {
@@ -570,6 +574,30 @@ pub mod ib {
         pub is_urgent: Option<bool>,
     }
 
+    // Event Response
+    //
+    // Only the events in the event log are reported, so there are no EventStatusIBs
+    #[derive(FromTLV, ToTLV, Clone, Debug)]
+    #[tlvargs(lifetime = "'a")]
+    pub struct EventResp<'a> {
+        #[tagval(1)]
+        pub data: EventData<'a>,
+    }
+
+    // Event Data
+    #[derive(FromTLV, ToTLV, Clone, Debug)]
+    #[tlvargs(lifetime = "'a")]
+    pub struct EventData<'a> {
+        pub path: EventPath,
+        pub event_number: u64,
+        pub priority: u8,
+        pub epoch_timestamp: Option<u64>,
+        pub system_timestamp: Option<u64>,
+        pub delta_epoch_timestamp: Option<u64>,
+        pub delta_system_timestamp: Option<u64>,
+        pub data: TLVElement<'a>,
+    }
+
     #[derive(FromTLV, ToTLV, Clone, Debug)]
     pub struct EventFilter {
         pub node: Option<u64>,
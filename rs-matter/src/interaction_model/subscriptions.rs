@@ -0,0 +1,150 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::time::Duration;
+
+use crate::fabric::MAX_SUPPORTED_FABRICS;
+
+/// The minimum number of subscriptions per fabric, as per the spec
+pub const SUBSCRIPTIONS_PER_FABRIC: usize = 3;
+
+const MAX_SUBSCRIPTIONS: usize = SUBSCRIPTIONS_PER_FABRIC * MAX_SUPPORTED_FABRICS;
+
+/// The MaxInterval granted to every subscription, in seconds
+pub const SUBSCRIPTION_MAX_INT_SECS: u16 = 40;
+
+#[derive(Debug, Clone)]
+struct Subscription {
+    fab_idx: u8,
+    subscriber: u64,
+    // When the subscription lapses, as per the epoch
+    until: Duration,
+}
+
+/// The subscriptions established with the node
+///
+/// Only the priming report of a subscription is sent, so a subscription is active until
+/// its MaxInterval elapses, after which the subscriber considers it lost. The oldest
+/// subscription is replaced once the table is full.
+pub struct Subscriptions {
+    subscriptions: heapless::Vec<Subscription, MAX_SUBSCRIPTIONS>,
+}
+
+impl Subscriptions {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            subscriptions: heapless::Vec::new(),
+        }
+    }
+
+    /// Record a subscription of node `subscriber` on fabric `fab_idx`, established at `now`
+    pub fn add(&mut self, fab_idx: u8, subscriber: u64, now: Duration) {
+        self.purge(now);
+
+        let subscription = Subscription {
+            fab_idx,
+            subscriber,
+            until: now + Duration::from_secs(SUBSCRIPTION_MAX_INT_SECS as _),
+        };
+
+        if let Err(subscription) = self.subscriptions.push(subscription) {
+            self.subscriptions.remove(0);
+            self.subscriptions.push(subscription).unwrap();
+        }
+    }
+
+    /// Whether node `subscriber` on fabric `fab_idx` has an active subscription at `now`
+    pub fn is_subscribed(&self, fab_idx: u8, subscriber: u64, now: Duration) -> bool {
+        self.subscriptions.iter().any(|subscription| {
+            subscription.fab_idx == fab_idx
+                && subscription.subscriber == subscriber
+                && subscription.until > now
+        })
+    }
+
+    /// Forget the subscriptions of a fabric which was removed
+    pub fn remove_fabric(&mut self, fab_idx: u8) {
+        self.subscriptions
+            .retain(|subscription| subscription.fab_idx != fab_idx);
+    }
+
+    /// Forget the subscriptions of node `subscriber` on fabric `fab_idx`, as requested by
+    /// a Subscribe Request with `KeepSubscriptions` unset
+    pub fn remove_subscriber(&mut self, fab_idx: u8, subscriber: u64) {
+        self.subscriptions.retain(|subscription| {
+            subscription.fab_idx != fab_idx || subscription.subscriber != subscriber
+        });
+    }
+
+    fn purge(&mut self, now: Duration) {
+        self.subscriptions
+            .retain(|subscription| subscription.until > now);
+    }
+}
+
+impl Default for Subscriptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{Subscriptions, MAX_SUBSCRIPTIONS, SUBSCRIPTION_MAX_INT_SECS};
+
+    #[test]
+    fn test_subscriptions() {
+        let mut subscriptions = Subscriptions::new();
+
+        subscriptions.add(1, 0x1234, Duration::ZERO);
+
+        assert!(subscriptions.is_subscribed(1, 0x1234, Duration::from_secs(1)));
+        assert!(!subscriptions.is_subscribed(2, 0x1234, Duration::from_secs(1)));
+        assert!(!subscriptions.is_subscribed(1, 0x5678, Duration::from_secs(1)));
+
+        // Lapses after the MaxInterval
+        let lapsed = Duration::from_secs(SUBSCRIPTION_MAX_INT_SECS as _);
+        assert!(!subscriptions.is_subscribed(1, 0x1234, lapsed));
+
+        subscriptions.add(1, 0x1234, lapsed);
+        subscriptions.add(1, 0x5678, lapsed);
+        assert!(subscriptions.is_subscribed(1, 0x1234, lapsed));
+
+        subscriptions.remove_subscriber(1, 0x1234);
+        assert!(!subscriptions.is_subscribed(1, 0x1234, lapsed));
+        assert!(subscriptions.is_subscribed(1, 0x5678, lapsed));
+
+        subscriptions.remove_fabric(1);
+        assert!(!subscriptions.is_subscribed(1, 0x5678, lapsed));
+    }
+
+    #[test]
+    fn test_replace_oldest() {
+        let mut subscriptions = Subscriptions::new();
+
+        for subscriber in 0..=MAX_SUBSCRIPTIONS as u64 {
+            subscriptions.add(1, subscriber, Duration::ZERO);
+        }
+
+        assert!(!subscriptions.is_subscribed(1, 0, Duration::ZERO));
+        assert!(subscriptions.is_subscribed(1, MAX_SUBSCRIPTIONS as _, Duration::ZERO));
+        assert!(subscriptions.is_subscribed(1, 1, Duration::ZERO));
+    }
+}
@@ -73,6 +73,7 @@
 #![cfg_attr(feature = "nightly", allow(incomplete_features))]
 
 pub mod acl;
+pub mod bdx;
 pub mod cert;
 pub mod codec;
 pub mod core;
@@ -81,6 +82,7 @@ pub mod data_model;
 pub mod error;
 pub mod fabric;
 pub mod group_keys;
+pub mod icd;
 pub mod interaction_model;
 pub mod mdns;
 pub mod pairing;
@@ -17,7 +17,7 @@
 
 use core::fmt::Write;
 
-use crate::{data_model::cluster_basic_information::BasicInfoConfig, error::Error};
+use crate::{data_model::cluster_basic_information::BasicInfoConfig, error::Error, icd::IcdConfig};
 
 #[cfg(all(feature = "std", target_os = "macos"))]
 pub mod astro;
@@ -95,6 +95,16 @@ impl ServiceMode {
         name: &str,
         f: F,
     ) -> Result<R, Error> {
+        let session_params = dev_att
+            .icd
+            .as_ref()
+            .map(IcdConfig::session_params)
+            .unwrap_or_default();
+
+        let sii = Self::get_interval_str(session_params.idle_interval_ms);
+        let sai = Self::get_interval_str(session_params.active_interval_ms);
+        let sat = Self::get_interval_str(session_params.active_threshold_ms as _);
+
         match self {
             Self::Commissioned => f(&Service {
                 name,
@@ -102,7 +112,11 @@ impl ServiceMode {
                 protocol: "_tcp",
                 port: matter_port,
                 service_subtypes: &[],
-                txt_kvs: &[],
+                txt_kvs: &[
+                    ("SII", &sii), /* Session Idle Interval */
+                    ("SAI", &sai), /* Session Active Interval */
+                    ("SAT", &sat), /* Session Active Threshold */
+                ],
             }),
             ServiceMode::Commissionable(discriminator) => {
                 let discriminator_str = Self::get_discriminator_str(*discriminator);
@@ -113,10 +127,11 @@ impl ServiceMode {
                     ("CM", "1"),
                     ("DN", dev_att.device_name),
                     ("VP", &vp),
-                    ("SII", "5000"), /* Sleepy Idle Interval */
-                    ("SAI", "300"),  /* Sleepy Active Interval */
-                    ("PH", "33"),    /* Pairing Hint */
-                    ("PI", ""),      /* Pairing Instruction */
+                    ("SII", &sii), /* Session Idle Interval */
+                    ("SAI", &sai), /* Session Active Interval */
+                    ("SAT", &sat), /* Session Active Threshold */
+                    ("PH", "33"),  /* Pairing Hint */
+                    ("PI", ""),    /* Pairing Instruction */
                 ];
 
                 f(&Service {
@@ -154,6 +169,10 @@ impl ServiceMode {
         discriminator.into()
     }
 
+    fn get_interval_str(interval_ms: u32) -> heapless::String<10> {
+        interval_ms.into()
+    }
+
     fn get_vp(vid: u16, pid: u16) -> heapless::String<11> {
         let mut vp = heapless::String::new();
 
@@ -23,8 +23,10 @@ pub mod fileio {
     use std::io::{Read, Write};
     use std::path::{Path, PathBuf};
 
-    use log::info;
+    use log::{info, warn};
 
+    use crate::data_model::objects::DataModelHandler;
+    use crate::data_model::persist::{AttrPersist, StatePersist};
     use crate::error::{Error, ErrorCode};
     use crate::Matter;
 
@@ -54,22 +56,96 @@ pub mod fileio {
             Ok(Self { matter, dir, buf })
         }
 
+        /// Restore the persistent attributes of the data model served by `handler`
+        ///
+        /// Call before running the Matter stack, so that the restored values are the
+        /// ones reported to the controllers.
+        pub async fn load_attrs<T>(
+            &mut self,
+            attrs: &mut AttrPersist,
+            handler: &T,
+        ) -> Result<(), Error>
+        where
+            T: DataModelHandler,
+        {
+            if let Some(data) = Self::load(&self.dir, "attrs", &mut self.buf)? {
+                // Stale or corrupted values should not prevent the stack from starting
+                if let Err(err) = attrs.load(handler, data).await {
+                    warn!("Restoring the persistent attributes failed: {:?}", err);
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Restore the state stored under `key`, i.e. the scene table of a Scenes Management
+        /// cluster
+        pub fn load_state(&mut self, key: &str, state: &dyn StatePersist) -> Result<(), Error> {
+            if let Some(data) = Self::load(&self.dir, key, &mut self.buf)? {
+                if let Err(err) = state.load(data) {
+                    warn!("Restoring {} failed: {:?}", key, err);
+                }
+            }
+
+            Ok(())
+        }
+
         pub async fn run(&mut self) -> Result<(), Error> {
             loop {
                 self.matter.wait_changed().await;
 
-                if self.matter.is_changed() {
-                    if let Some(data) = self.matter.store_acls(&mut self.buf)? {
-                        Self::store(&self.dir, "acls", data)?;
-                    }
+                self.store_matter()?;
+            }
+        }
 
-                    if let Some(data) = self.matter.store_fabrics(&mut self.buf)? {
-                        Self::store(&self.dir, "fabrics", data)?;
+        /// Same as `run`, but also stores the persistent attributes of the data model
+        /// served by `handler`, as well as each of the `states` under its key, whenever
+        /// they change
+        pub async fn run_attrs<T>(
+            &mut self,
+            attrs: &mut AttrPersist,
+            handler: &T,
+            states: &[(&str, &dyn StatePersist)],
+        ) -> Result<(), Error>
+        where
+            T: DataModelHandler,
+        {
+            loop {
+                self.matter.wait_changed().await;
+
+                self.store_matter()?;
+
+                // Keep on storing the ACLs and fabrics even if the attributes do not fit
+                match attrs.store(handler, &mut self.buf).await {
+                    Ok(Some(data)) => Self::store(&self.dir, "attrs", data)?,
+                    Ok(None) => (),
+                    Err(err) => warn!("Storing the persistent attributes failed: {:?}", err),
+                }
+
+                for (key, state) in states {
+                    match state.store(&mut self.buf) {
+                        Ok(Some(data)) => Self::store(&self.dir, key, data)?,
+                        Ok(None) => (),
+                        Err(err) => warn!("Storing {} failed: {:?}", key, err),
                     }
                 }
             }
         }
 
+        fn store_matter(&mut self) -> Result<(), Error> {
+            if self.matter.is_changed() {
+                if let Some(data) = self.matter.store_acls(&mut self.buf)? {
+                    Self::store(&self.dir, "acls", data)?;
+                }
+
+                if let Some(data) = self.matter.store_fabrics(&mut self.buf)? {
+                    Self::store(&self.dir, "fabrics", data)?;
+                }
+            }
+
+            Ok(())
+        }
+
         fn load<'b>(dir: &Path, key: &str, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
             let path = dir.join(key);
 
@@ -26,15 +26,16 @@ use crate::{
     error::{Error, ErrorCode},
     fabric::{Fabric, FabricMgr},
     secure_channel::common::{self, OpCode, PROTO_ID_SECURE_CHANNEL},
-    secure_channel::common::{complete_with_status, SCStatusCodes},
-    tlv::{get_root_node_struct, FromTLV, OctetStr, TLVWriter, TagType},
+    secure_channel::common::{check_sc_status_report, complete_with_status, SCStatusCodes},
+    tlv::{get_root_node_struct, FromTLV, OctetStr, TLVWriter, TagType, ToTLV},
     transport::{
         exchange::Exchange,
+        mrp::SessionParams,
         network::Address,
         packet::Packet,
         session::{CaseDetails, CloneData, NocCatIds, SessionMode},
     },
-    utils::{rand::Rand, writebuf::WriteBuf},
+    utils::{epoch::UtcClock, rand::Rand, writebuf::WriteBuf},
 };
 
 #[derive(Debug, Clone)]
@@ -65,13 +66,25 @@ impl CaseSession {
 
 pub struct Case<'a> {
     fabric_mgr: &'a RefCell<FabricMgr>,
+    utc: &'a UtcClock,
+    session_params: SessionParams,
     rand: Rand,
 }
 
 impl<'a> Case<'a> {
     #[inline(always)]
-    pub fn new(fabric_mgr: &'a RefCell<FabricMgr>, rand: Rand) -> Self {
-        Self { fabric_mgr, rand }
+    pub fn new(
+        fabric_mgr: &'a RefCell<FabricMgr>,
+        utc: &'a UtcClock,
+        session_params: SessionParams,
+        rand: Rand,
+    ) -> Self {
+        Self {
+            fabric_mgr,
+            utc,
+            session_params,
+            rand,
+        }
     }
 
     pub async fn handle(
@@ -87,6 +100,249 @@ impl<'a> Case<'a> {
         self.handle_casesigma3(exchange, rx, tx, &mut session).await
     }
 
+    /// Establish a CASE session with node `peer_nodeid` of our fabric `fab_idx`
+    ///
+    /// `exchange` is an exchange we initiated over an unsecured session with the node.
+    /// Returns the index of the new session in the session manager.
+    pub async fn initiate(
+        &mut self,
+        exchange: &mut Exchange<'_>,
+        rx: &mut Packet<'_>,
+        tx: &mut Packet<'_>,
+        fab_idx: u8,
+        peer_nodeid: u64,
+    ) -> Result<usize, Error> {
+        let mut session = alloc!(CaseSession::new()?);
+
+        // Create an ephemeral Key Pair
+        let key_pair = KeyPair::new(self.rand)?;
+        let _ = key_pair.get_public_key(&mut session.our_pub_key)?;
+
+        self.send_casesigma1(exchange, rx, tx, fab_idx, peer_nodeid, &mut session)
+            .await?;
+
+        let peer_catids = self
+            .handle_casesigma2(exchange, rx, tx, &key_pair, peer_nodeid, &mut session)
+            .await?;
+
+        check_sc_status_report(rx)?;
+        exchange.acknowledge().await?;
+
+        let clone_data = {
+            let fabric_mgr = self.fabric_mgr.borrow();
+            let fabric = fabric_mgr
+                .get_fabric(session.local_fabric_idx)?
+                .ok_or(ErrorCode::NotFound)?;
+
+            Case::get_session_clone_data(
+                fabric.ipk.op_key(),
+                fabric.get_node_id(),
+                peer_nodeid,
+                exchange.with_session(|sess| Ok(sess.get_peer_addr()))?,
+                &session,
+                &peer_catids,
+                true,
+            )?
+        };
+
+        exchange.with_session_mgr_mut(|sess_mgr| sess_mgr.clone_session(&clone_data))
+    }
+
+    async fn send_casesigma1(
+        &mut self,
+        exchange: &mut Exchange<'_>,
+        rx: &mut Packet<'_>,
+        tx: &mut Packet<'_>,
+        fab_idx: u8,
+        peer_nodeid: u64,
+        case_session: &mut CaseSession,
+    ) -> Result<(), Error> {
+        let mut our_random: [u8; 32] = [0; 32];
+        (self.rand)(&mut our_random);
+
+        let mut dest_id = [0_u8; crypto::SHA256_HASH_LEN_BYTES];
+
+        {
+            let fabric_mgr = self.fabric_mgr.borrow();
+            let fabric = fabric_mgr
+                .get_fabric(fab_idx as usize)?
+                .ok_or(ErrorCode::NotFound)?;
+
+            fabric.get_dest_id(&our_random, peer_nodeid, &mut dest_id)?;
+        }
+
+        case_session.local_fabric_idx = fab_idx as usize;
+        case_session.local_sessid =
+            exchange.with_session_mgr_mut(|mgr| Ok(mgr.get_next_sess_id()))?;
+
+        tx.reset();
+        tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
+        tx.set_proto_opcode(OpCode::CASESigma1 as u8);
+
+        let mut tw = TLVWriter::new(tx.get_writebuf()?);
+        tw.start_struct(TagType::Anonymous)?;
+        tw.str8(TagType::Context(1), &our_random)?;
+        tw.u16(TagType::Context(2), case_session.local_sessid)?;
+        tw.str8(TagType::Context(3), &dest_id)?;
+        tw.str8(TagType::Context(4), &case_session.our_pub_key)?;
+        self.session_params.to_tlv(&mut tw, TagType::Context(5))?;
+        tw.end_container()?;
+
+        case_session.tt_hash.update(tx.as_mut_slice())?;
+
+        exchange.exchange(tx, rx).await
+    }
+
+    async fn handle_casesigma2(
+        &mut self,
+        exchange: &mut Exchange<'_>,
+        rx: &mut Packet<'_>,
+        tx: &mut Packet<'_>,
+        key_pair: &KeyPair,
+        peer_nodeid: u64,
+        case_session: &mut CaseSession,
+    ) -> Result<NocCatIds, Error> {
+        if rx.check_proto_opcode(OpCode::CASESigma2 as _).is_err() {
+            check_sc_status_report(rx)?;
+            Err(ErrorCode::Invalid)?;
+        }
+
+        let root = get_root_node_struct(rx.as_slice())?;
+        let r = Sigma2Resp::from_tlv(&root)?;
+
+        if r.responder_pub_key.0.len() != crypto::EC_POINT_LEN_BYTES {
+            error!("Invalid public key length");
+            Err(ErrorCode::Invalid)?;
+        }
+        case_session.peer_sessid = r.responder_sessid;
+        case_session
+            .peer_pub_key
+            .copy_from_slice(r.responder_pub_key.0);
+
+        // Derive the Shared Secret
+        let len = key_pair.derive_secret(r.responder_pub_key.0, &mut case_session.shared_secret)?;
+        if len != 32 {
+            error!("Derived secret length incorrect");
+            Err(ErrorCode::Invalid)?;
+        }
+
+        const MAX_ENCRYPTED_SIZE: usize = 800;
+
+        let mut decrypted = alloc!([0; MAX_ENCRYPTED_SIZE]);
+        if r.encrypted.0.len() > decrypted.len() {
+            error!("Data too large");
+            Err(ErrorCode::NoSpace)?;
+        }
+        let decrypted = &mut decrypted[..r.encrypted.0.len()];
+        decrypted.copy_from_slice(r.encrypted.0);
+
+        let mut encrypted = alloc!([0; MAX_ENCRYPTED_SIZE]);
+        let mut signature = alloc!([0u8; crypto::EC_SIGNATURE_LEN_BYTES]);
+
+        let peer_catids = {
+            let fabric_mgr = self.fabric_mgr.borrow();
+            let fabric = fabric_mgr
+                .get_fabric(case_session.local_fabric_idx)?
+                .ok_or(ErrorCode::NotFound)?;
+
+            let len = Case::get_sigma2_decryption(
+                fabric.ipk.op_key(),
+                r.responder_random.0,
+                case_session,
+                decrypted,
+            )?;
+            let decrypted = &decrypted[..len];
+
+            let root = get_root_node_struct(decrypted)?;
+            let d = Sigma2Decrypt::from_tlv(&root)?;
+
+            let responder_noc = alloc!(Cert::new(d.responder_noc.0)?);
+            let mut responder_icac = None;
+            if let Some(icac) = d.responder_icac {
+                responder_icac = Some(alloc!(Cert::new(icac.0)?));
+            }
+
+            #[cfg(feature = "alloc")]
+            let responder_icac_mut = responder_icac.as_deref();
+
+            #[cfg(not(feature = "alloc"))]
+            let responder_icac_mut = responder_icac.as_ref();
+
+            if let Err(e) = Case::validate_certs(
+                fabric,
+                &responder_noc,
+                responder_icac_mut,
+                self.utc.now_secs(),
+            ) {
+                error!("Certificate Chain doesn't match: {}", e);
+                Err(e)?;
+            }
+
+            if responder_noc.get_node_id()? != peer_nodeid {
+                error!("Responder is not node {:x}", peer_nodeid);
+                Err(ErrorCode::Invalid)?;
+            }
+
+            if let Err(e) = Case::validate_tbs_sign(
+                d.responder_noc.0,
+                d.responder_icac.map(|a| a.0),
+                &responder_noc,
+                d.signature.0,
+                case_session,
+            ) {
+                error!("Sigma2 Signature doesn't match: {}", e);
+                Err(e)?;
+            }
+
+            let mut peer_catids: NocCatIds = Default::default();
+            responder_noc.get_cat_ids(&mut peer_catids);
+
+            // The Sigma2 key was derived, so the message can now be added to the TT Hash
+            case_session.tt_hash.update(rx.as_slice())?;
+
+            #[cfg(feature = "alloc")]
+            let signature_mut = &mut *signature;
+
+            #[cfg(not(feature = "alloc"))]
+            let signature_mut = &mut signature;
+
+            let sign_len = Case::get_tbs_sign(
+                fabric,
+                &case_session.our_pub_key,
+                &case_session.peer_pub_key,
+                signature_mut,
+            )?;
+            let signature = &signature[..sign_len];
+
+            #[cfg(feature = "alloc")]
+            let encrypted_mut = &mut *encrypted;
+
+            #[cfg(not(feature = "alloc"))]
+            let encrypted_mut = &mut encrypted;
+
+            let encrypted_len =
+                Case::get_sigma3_encryption(fabric, case_session, signature, encrypted_mut)?;
+            let encrypted = &encrypted[0..encrypted_len];
+
+            tx.reset();
+            tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
+            tx.set_proto_opcode(OpCode::CASESigma3 as u8);
+
+            let mut tw = TLVWriter::new(tx.get_writebuf()?);
+            tw.start_struct(TagType::Anonymous)?;
+            tw.str16(TagType::Context(1), encrypted)?;
+            tw.end_container()?;
+
+            case_session.tt_hash.update(tx.as_mut_slice())?;
+
+            peer_catids
+        };
+
+        exchange.exchange(tx, rx).await?;
+
+        Ok(peer_catids)
+    }
+
     async fn handle_casesigma3(
         &mut self,
         exchange: &mut Exchange<'_>,
@@ -131,10 +387,15 @@ impl<'a> Case<'a> {
                 #[cfg(not(feature = "alloc"))]
                 let initiator_icac_mut = initiator_icac.as_ref();
 
-                if let Err(e) = Case::validate_certs(fabric, &initiator_noc, initiator_icac_mut) {
+                if let Err(e) = Case::validate_certs(
+                    fabric,
+                    &initiator_noc,
+                    initiator_icac_mut,
+                    self.utc.now_secs(),
+                ) {
                     error!("Certificate Chain doesn't match: {}", e);
                     SCStatusCodes::InvalidParameter
-                } else if let Err(e) = Case::validate_sigma3_sign(
+                } else if let Err(e) = Case::validate_tbs_sign(
                     d.initiator_noc.0,
                     d.initiator_icac.map(|a| a.0),
                     &initiator_noc,
@@ -155,6 +416,7 @@ impl<'a> Case<'a> {
                         exchange.with_session(|sess| Ok(sess.get_peer_addr()))?,
                         case_session,
                         &peer_catids,
+                        false,
                     )?;
 
                     // TODO: Handle NoSpace
@@ -248,7 +510,7 @@ impl<'a> Case<'a> {
                 #[cfg(not(feature = "alloc"))]
                 let signature_mut = &mut signature;
 
-                let sign_len = Case::get_sigma2_sign(
+                let sign_len = Case::get_tbs_sign(
                     fabric,
                     &case_session.our_pub_key,
                     &case_session.peer_pub_key,
@@ -284,6 +546,7 @@ impl<'a> Case<'a> {
                 tw.u16(TagType::Context(2), local_sessid)?;
                 tw.str8(TagType::Context(3), &case_session.our_pub_key)?;
                 tw.str16(TagType::Context(4), encrypted)?;
+                self.session_params.to_tlv(&mut tw, TagType::Context(5))?;
                 tw.end_container()?;
 
                 case_session.tt_hash.update(tx.as_mut_slice())?;
@@ -314,6 +577,7 @@ impl<'a> Case<'a> {
         peer_addr: Address,
         case_session: &CaseSession,
         peer_catids: &NocCatIds,
+        initiator: bool,
     ) -> Result<CloneData, Error> {
         let mut session_keys = [0_u8; 3 * crypto::SYMM_KEY_LEN_BYTES];
         Case::get_session_keys(
@@ -335,18 +599,25 @@ impl<'a> Case<'a> {
             )),
         );
 
-        clone_data.dec_key.copy_from_slice(&session_keys[0..16]);
-        clone_data.enc_key.copy_from_slice(&session_keys[16..32]);
+        // The I2R key comes first, followed by the R2I key
+        if initiator {
+            clone_data.enc_key.copy_from_slice(&session_keys[0..16]);
+            clone_data.dec_key.copy_from_slice(&session_keys[16..32]);
+        } else {
+            clone_data.dec_key.copy_from_slice(&session_keys[0..16]);
+            clone_data.enc_key.copy_from_slice(&session_keys[16..32]);
+        }
         clone_data
             .att_challenge
             .copy_from_slice(&session_keys[32..48]);
         Ok(clone_data)
     }
 
-    fn validate_sigma3_sign(
-        initiator_noc: &[u8],
-        initiator_icac: Option<&[u8]>,
-        initiator_noc_cert: &Cert,
+    // Validate the signature of the peer over its TBSData2 (or TBSData3)
+    fn validate_tbs_sign(
+        peer_noc: &[u8],
+        peer_icac: Option<&[u8]>,
+        peer_noc_cert: &Cert,
         sign: &[u8],
         case_session: &CaseSession,
     ) -> Result<(), Error> {
@@ -355,21 +626,26 @@ impl<'a> Case<'a> {
         let mut write_buf = WriteBuf::new(&mut buf);
         let mut tw = TLVWriter::new(&mut write_buf);
         tw.start_struct(TagType::Anonymous)?;
-        tw.str16(TagType::Context(1), initiator_noc)?;
-        if let Some(icac) = initiator_icac {
+        tw.str16(TagType::Context(1), peer_noc)?;
+        if let Some(icac) = peer_icac {
             tw.str16(TagType::Context(2), icac)?;
         }
         tw.str8(TagType::Context(3), &case_session.peer_pub_key)?;
         tw.str8(TagType::Context(4), &case_session.our_pub_key)?;
         tw.end_container()?;
 
-        let key = KeyPair::new_from_public(initiator_noc_cert.get_pubkey())?;
+        let key = KeyPair::new_from_public(peer_noc_cert.get_pubkey())?;
         key.verify_msg(write_buf.as_slice(), sign)?;
         Ok(())
     }
 
-    fn validate_certs(fabric: &Fabric, noc: &Cert, icac: Option<&Cert>) -> Result<(), Error> {
-        let mut verifier = noc.verify_chain_start();
+    fn validate_certs(
+        fabric: &Fabric,
+        noc: &Cert,
+        icac: Option<&Cert>,
+        now: Option<u64>,
+    ) -> Result<(), Error> {
+        let mut verifier = noc.verify_chain_start().valid_at(now);
 
         if fabric.get_fabric_id() != noc.get_fabric_id()? {
             Err(ErrorCode::Invalid)?;
@@ -441,6 +717,71 @@ impl<'a> Case<'a> {
         Ok(encrypted_len - crypto::AEAD_MIC_LEN_BYTES)
     }
 
+    fn get_sigma2_decryption(
+        ipk: &[u8],
+        responder_random: &[u8],
+        case_session: &CaseSession,
+        encrypted: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut sigma2_key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_sigma2_key(
+            ipk,
+            responder_random,
+            &case_session.peer_pub_key,
+            case_session,
+            &mut sigma2_key,
+        )?;
+
+        let nonce: [u8; crypto::AEAD_NONCE_LEN_BYTES] = [
+            0x4e, 0x43, 0x41, 0x53, 0x45, 0x5f, 0x53, 0x69, 0x67, 0x6d, 0x61, 0x32, 0x4e,
+        ];
+
+        let encrypted_len = encrypted.len();
+        crypto::decrypt_in_place(&sigma2_key, &nonce, &[], encrypted)?;
+        Ok(encrypted_len - crypto::AEAD_MIC_LEN_BYTES)
+    }
+
+    fn get_sigma3_encryption(
+        fabric: &Fabric,
+        case_session: &CaseSession,
+        signature: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut sigma3_key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_sigma3_key(
+            fabric.ipk.op_key(),
+            &case_session.tt_hash,
+            &case_session.shared_secret,
+            &mut sigma3_key,
+        )?;
+
+        let mut write_buf = WriteBuf::new(out);
+        let mut tw = TLVWriter::new(&mut write_buf);
+        tw.start_struct(TagType::Anonymous)?;
+        tw.str16(TagType::Context(1), &fabric.noc)?;
+        if let Some(icac_cert) = fabric.icac.as_ref() {
+            tw.str16(TagType::Context(2), icac_cert)?
+        };
+        tw.str8(TagType::Context(3), signature)?;
+        tw.end_container()?;
+
+        let nonce: [u8; crypto::AEAD_NONCE_LEN_BYTES] = [
+            0x4e, 0x43, 0x41, 0x53, 0x45, 0x5f, 0x53, 0x69, 0x67, 0x6d, 0x61, 0x33, 0x4e,
+        ];
+
+        write_buf.append(&[0u8; crypto::AEAD_MIC_LEN_BYTES])?;
+        let cipher_text = write_buf.as_mut_slice();
+
+        crypto::encrypt_in_place(
+            &sigma3_key,
+            &nonce,
+            &[],
+            cipher_text,
+            cipher_text.len() - crypto::AEAD_MIC_LEN_BYTES,
+        )?;
+        Ok(write_buf.as_slice().len())
+    }
+
     fn get_sigma3_key(
         ipk: &[u8],
         tt: &Sha256,
@@ -470,7 +811,8 @@ impl<'a> Case<'a> {
 
     fn get_sigma2_key(
         ipk: &[u8],
-        our_random: &[u8],
+        responder_random: &[u8],
+        responder_pub_key: &[u8],
         case_session: &CaseSession,
         key: &mut [u8],
     ) -> Result<(), Error> {
@@ -480,8 +822,8 @@ impl<'a> Case<'a> {
         }
         let mut salt = heapless::Vec::<u8, 256>::new();
         salt.extend_from_slice(ipk).unwrap();
-        salt.extend_from_slice(our_random).unwrap();
-        salt.extend_from_slice(&case_session.our_pub_key).unwrap();
+        salt.extend_from_slice(responder_random).unwrap();
+        salt.extend_from_slice(responder_pub_key).unwrap();
 
         let tt = case_session.tt_hash.clone();
 
@@ -512,6 +854,7 @@ impl<'a> Case<'a> {
         Case::get_sigma2_key(
             fabric.ipk.op_key(),
             our_random,
+            &case_session.our_pub_key,
             case_session,
             &mut sigma2_key,
         )?;
@@ -549,7 +892,8 @@ impl<'a> Case<'a> {
         Ok(write_buf.as_slice().len())
     }
 
-    fn get_sigma2_sign(
+    // Sign our TBSData2 (or TBSData3)
+    fn get_tbs_sign(
         fabric: &Fabric,
         our_pub_key: &[u8],
         peer_pub_key: &[u8],
@@ -589,3 +933,20 @@ struct Sigma3Decrypt<'a> {
     initiator_icac: Option<OctetStr<'a>>,
     signature: OctetStr<'a>,
 }
+
+#[derive(FromTLV)]
+#[tlvargs(start = 1, lifetime = "'a")]
+struct Sigma2Resp<'a> {
+    responder_random: OctetStr<'a>,
+    responder_sessid: u16,
+    responder_pub_key: OctetStr<'a>,
+    encrypted: OctetStr<'a>,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(start = 1, lifetime = "'a")]
+struct Sigma2Decrypt<'a> {
+    responder_noc: OctetStr<'a>,
+    responder_icac: Option<OctetStr<'a>>,
+    signature: OctetStr<'a>,
+}
@@ -0,0 +1,147 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use crate::{
+    crypto::{self, HmacSha256},
+    error::{Error, ErrorCode},
+    transport::{network::Address, packet::Packet, session::MATTER_MSG_CTR_RANGE},
+    utils::rand::Rand,
+};
+
+use super::common::{OpCode, PROTO_ID_SECURE_CHANNEL};
+
+/// The length of the key shared by an ICD and a client registered for Check-In messages
+pub const CHECK_IN_KEY_LEN: usize = crypto::SYMM_KEY_LEN_BYTES;
+
+const COUNTER_LEN: usize = 4;
+
+pub const CHECK_IN_PAYLOAD_LEN: usize =
+    crypto::AEAD_NONCE_LEN_BYTES + COUNTER_LEN + crypto::AEAD_MIC_LEN_BYTES;
+
+/// Creates the unsecured Check-In message, with which an ICD tells a registered client
+/// that it is active
+///
+/// The payload is the nonce, followed by the `counter` encrypted with the key of the
+/// client, and the MIC.
+pub fn create_check_in(tx: &mut Packet, key: &[u8], counter: u32, rand: Rand) -> Result<(), Error> {
+    let mut ids = [0; 6];
+    rand(&mut ids);
+
+    tx.reset();
+    prepare_check_in(tx, key, counter)?;
+    tx.proto.set_initiator();
+    tx.proto.exch_id = u16::from_le_bytes([ids[0], ids[1]]);
+    tx.plain.ctr = u32::from_le_bytes([ids[2], ids[3], ids[4], ids[5]]) & MATTER_MSG_CTR_RANGE;
+
+    tx.proto_encode(Address::default(), None, 0, true, None)
+}
+
+/// Writes the payload of the Check-In message to `tx`, leaving the message counter,
+/// the exchange and the encoding to the transport
+pub fn prepare_check_in(tx: &mut Packet, key: &[u8], counter: u32) -> Result<(), Error> {
+    let nonce = check_in_nonce(key, counter)?;
+
+    let mut encrypted = [0; COUNTER_LEN + crypto::AEAD_MIC_LEN_BYTES];
+    encrypted[..COUNTER_LEN].copy_from_slice(&counter.to_le_bytes());
+    crypto::encrypt_in_place(key, &nonce, &[], &mut encrypted, COUNTER_LEN)?;
+
+    tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
+    tx.set_proto_opcode(OpCode::ICDCheckIn as u8);
+    tx.unset_reliable();
+
+    let wb = tx.get_writebuf()?;
+    wb.append(&nonce)?;
+    wb.append(&encrypted)
+}
+
+/// Decrypts the payload of a Check-In message with the key of the client, and returns
+/// the counter of the ICD
+pub fn decode_check_in(key: &[u8], payload: &[u8]) -> Result<u32, Error> {
+    if payload.len() != CHECK_IN_PAYLOAD_LEN {
+        Err(ErrorCode::InvalidData)?;
+    }
+
+    let (nonce, encrypted) = payload.split_at(crypto::AEAD_NONCE_LEN_BYTES);
+
+    let mut decrypted = [0; COUNTER_LEN + crypto::AEAD_MIC_LEN_BYTES];
+    decrypted.copy_from_slice(encrypted);
+    crypto::decrypt_in_place(key, nonce, &[], &mut decrypted)?;
+
+    let counter = u32::from_le_bytes([decrypted[0], decrypted[1], decrypted[2], decrypted[3]]);
+
+    // The nonce is derived from the counter, so that it is never reused with the same key
+    if nonce != check_in_nonce(key, counter)?.as_slice() {
+        Err(ErrorCode::InvalidData)?;
+    }
+
+    Ok(counter)
+}
+
+fn check_in_nonce(key: &[u8], counter: u32) -> Result<[u8; crypto::AEAD_NONCE_LEN_BYTES], Error> {
+    let mut hmac = HmacSha256::new(key)?;
+    hmac.update(&counter.to_le_bytes())?;
+
+    let mut digest = [0; crypto::SHA256_HASH_LEN_BYTES];
+    hmac.finish(&mut digest)?;
+
+    let mut nonce = [0; crypto::AEAD_NONCE_LEN_BYTES];
+    nonce.copy_from_slice(&digest[..crypto::AEAD_NONCE_LEN_BYTES]);
+
+    Ok(nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        secure_channel::common::{OpCode, PROTO_ID_SECURE_CHANNEL},
+        transport::packet::Packet,
+        utils::rand::dummy_rand,
+    };
+
+    use super::{create_check_in, decode_check_in};
+
+    const KEY: [u8; 16] = [
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+        0x10,
+    ];
+
+    #[test]
+    fn test_check_in_round_trip() {
+        let mut tx_buf = [0; 128];
+        let mut tx = Packet::new_tx(&mut tx_buf);
+        create_check_in(&mut tx, &KEY, 0x12345678, dummy_rand).unwrap();
+
+        let mut rx_buf = [0; 128];
+        let len = tx.as_slice().len();
+        rx_buf[..len].copy_from_slice(tx.as_slice());
+
+        let mut rx = Packet::new_rx(&mut rx_buf[..len]);
+        rx.plain_hdr_decode().unwrap();
+        rx.proto_decode(0, None).unwrap();
+
+        assert_eq!(rx.get_proto_id(), PROTO_ID_SECURE_CHANNEL);
+        assert_eq!(rx.get_proto_raw_opcode(), OpCode::ICDCheckIn as u8);
+        assert!(!rx.proto.is_reliable());
+
+        let payload = rx.as_slice();
+        assert_eq!(decode_check_in(&KEY, payload).unwrap(), 0x12345678);
+
+        let mut other_key = KEY;
+        other_key[0] = 0;
+        assert!(decode_check_in(&other_key, payload).is_err());
+    }
+}
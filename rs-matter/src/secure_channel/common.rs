@@ -15,10 +15,11 @@
  *    limitations under the License.
  */
 
+use log::error;
 use num_derive::FromPrimitive;
 
 use crate::{
-    error::Error,
+    error::{Error, ErrorCode},
     transport::{exchange::Exchange, packet::Packet},
 };
 
@@ -42,6 +43,7 @@ pub enum OpCode {
     CASESigma3 = 0x32,
     CASESigma2Resume = 0x33,
     StatusReport = 0x40,
+    ICDCheckIn = 0x50,
 }
 
 #[derive(PartialEq)]
@@ -93,6 +95,36 @@ pub fn create_sc_status_report(
     )
 }
 
+/// Check that the status report a peer sent us reports a successful session establishment
+pub fn check_sc_status_report(rx: &Packet) -> Result<(), Error> {
+    rx.check_proto_opcode(OpCode::StatusReport as _)?;
+
+    let data = rx.as_slice();
+    if data.len() < 8 {
+        Err(ErrorCode::Invalid)?;
+    }
+
+    let general_code = u16::from_le_bytes([data[0], data[1]]);
+    let proto_code = u16::from_le_bytes([data[6], data[7]]);
+
+    if general_code == GeneralCode::Success as u16
+        && proto_code == SCStatusCodes::SessionEstablishmentSuccess as u16
+    {
+        Ok(())
+    } else {
+        error!(
+            "Session establishment failed: general code {}, protocol code {}",
+            general_code, proto_code
+        );
+
+        if proto_code == SCStatusCodes::Busy as u16 {
+            Err(ErrorCode::Busy.into())
+        } else {
+            Err(ErrorCode::Invalid.into())
+        }
+    }
+}
+
 pub fn create_mrp_standalone_ack(proto_tx: &mut Packet) {
     proto_tx.reset();
     proto_tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
@@ -23,10 +23,14 @@ use log::error;
 use crate::{
     error::*,
     fabric::FabricMgr,
+    icd::IcdManager,
     mdns::Mdns,
     secure_channel::{common::*, pake::Pake},
     transport::{exchange::Exchange, packet::Packet},
-    utils::{epoch::Epoch, rand::Rand},
+    utils::{
+        epoch::{Epoch, UtcClock},
+        rand::Rand,
+    },
 };
 
 use super::{case::Case, pake::PaseMgr};
@@ -38,6 +42,8 @@ pub struct SecureChannel<'a> {
     pase: &'a RefCell<PaseMgr>,
     fabric: &'a RefCell<FabricMgr>,
     mdns: &'a dyn Mdns,
+    utc: &'a UtcClock,
+    icd: &'a RefCell<IcdManager>,
     rand: Rand,
 }
 
@@ -47,12 +53,16 @@ impl<'a> SecureChannel<'a> {
         T: Borrow<RefCell<FabricMgr>>
             + Borrow<RefCell<PaseMgr>>
             + Borrow<dyn Mdns + 'a>
+            + Borrow<UtcClock>
+            + Borrow<RefCell<IcdManager>>
             + Borrow<Epoch>
             + Borrow<Rand>,
     >(
         matter: &'a T,
     ) -> Self {
         Self::wrap(
+            matter.borrow(),
+            matter.borrow(),
             matter.borrow(),
             matter.borrow(),
             matter.borrow(),
@@ -65,12 +75,16 @@ impl<'a> SecureChannel<'a> {
         pase: &'a RefCell<PaseMgr>,
         fabric: &'a RefCell<FabricMgr>,
         mdns: &'a dyn Mdns,
+        utc: &'a UtcClock,
+        icd: &'a RefCell<IcdManager>,
         rand: Rand,
     ) -> Self {
         Self {
             fabric,
             pase,
             mdns,
+            utc,
+            icd,
             rand,
         }
     }
@@ -81,14 +95,16 @@ impl<'a> SecureChannel<'a> {
         rx: &mut Packet<'_>,
         tx: &mut Packet<'_>,
     ) -> Result<(), Error> {
+        let session_params = self.icd.borrow().session_params();
+
         match rx.get_proto_opcode()? {
             OpCode::PBKDFParamRequest => {
-                Pake::new(self.pase)
+                Pake::new(self.pase, session_params)
                     .handle(exchange, rx, tx, self.mdns)
                     .await
             }
             OpCode::CASESigma1 => {
-                Case::new(self.fabric, self.rand)
+                Case::new(self.fabric, self.utc, session_params, self.rand)
                     .handle(exchange, rx, tx)
                     .await
             }
@@ -98,4 +114,23 @@ impl<'a> SecureChannel<'a> {
             }
         }
     }
+
+    /// Establish a CASE session with node `peer_nodeid` of our fabric `fab_idx`, over
+    /// `exchange`, which we initiated over an unsecured session with the node
+    ///
+    /// Returns the index of the new session in the session manager.
+    pub async fn initiate_case(
+        &self,
+        exchange: &mut Exchange<'_>,
+        rx: &mut Packet<'_>,
+        tx: &mut Packet<'_>,
+        fab_idx: u8,
+        peer_nodeid: u64,
+    ) -> Result<usize, Error> {
+        let session_params = self.icd.borrow().session_params();
+
+        Case::new(self.fabric, self.utc, session_params, self.rand)
+            .initiate(exchange, rx, tx, fab_idx, peer_nodeid)
+            .await
+    }
 }
@@ -16,6 +16,7 @@
  */
 
 pub mod case;
+pub mod check_in;
 pub mod common;
 #[cfg(not(any(feature = "openssl", feature = "mbedtls", feature = "rustcrypto")))]
 mod crypto_dummy;
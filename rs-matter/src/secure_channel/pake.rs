@@ -29,6 +29,7 @@ use crate::{
     tlv::{self, get_root_node_struct, FromTLV, OctetStr, TLVWriter, TagType, ToTLV},
     transport::{
         exchange::{Exchange, ExchangeId},
+        mrp::SessionParams,
         packet::Packet,
         session::{CloneData, SessionMode},
     },
@@ -36,13 +37,28 @@ use crate::{
 };
 use log::{error, info};
 
+/// A commissioning window opened by an administrator, with the Administrator
+/// Commissioning cluster
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommWindow {
+    /// Whether the window uses the onboarding passcode, rather than a verifier provided
+    /// by the administrator
+    pub basic: bool,
+    pub fab_idx: u8,
+    pub vendor_id: u16,
+    /// When the window closes, as per the epoch
+    pub expires_at: Duration,
+}
+
 struct PaseSession {
     mdns_service_name: heapless::String<16>,
     verifier: VerifierData,
+    window: Option<CommWindow>,
 }
 
 pub struct PaseMgr {
     session: Option<PaseSession>,
+    onboarding: Option<(VerifierData, u16)>,
     timeout: Option<Timeout>,
     epoch: Epoch,
     rand: Rand,
@@ -53,6 +69,7 @@ impl PaseMgr {
     pub fn new(epoch: Epoch, rand: Rand) -> Self {
         Self {
             session: None,
+            onboarding: None,
             timeout: None,
             epoch,
             rand,
@@ -63,11 +80,78 @@ impl PaseMgr {
         self.session.is_some()
     }
 
+    /// The commissioning window opened by an administrator, if it is still open
+    pub fn comm_window(&self) -> Option<&CommWindow> {
+        self.session
+            .as_ref()
+            .and_then(|session| session.window.as_ref())
+    }
+
+    /// Remember the onboarding passcode and discriminator, used by basic commissioning
+    /// windows
+    pub fn set_onboarding_data(&mut self, verifier: VerifierData, discriminator: u16) {
+        self.onboarding = Some((verifier, discriminator));
+    }
+
     pub fn enable_pase_session(
         &mut self,
         verifier: VerifierData,
         discriminator: u16,
         mdns: &dyn Mdns,
+    ) -> Result<(), Error> {
+        self.enable(verifier, discriminator, None, mdns)
+    }
+
+    /// Open a commissioning window on behalf of an administrator
+    ///
+    /// Basic windows use the onboarding data, hence `verifier` and `discriminator` are
+    /// only needed for enhanced windows.
+    pub fn open_comm_window(
+        &mut self,
+        verifier: Option<(VerifierData, u16)>,
+        window: CommWindow,
+        mdns: &dyn Mdns,
+    ) -> Result<(), Error> {
+        if self.session.is_some() {
+            Err(ErrorCode::Busy)?;
+        }
+
+        let (verifier, discriminator) = match verifier {
+            Some(verifier) => verifier,
+            None => self.onboarding.clone().ok_or(ErrorCode::InvalidState)?,
+        };
+
+        self.enable(verifier, discriminator, Some(window), mdns)
+    }
+
+    /// Close the commissioning window opened by an administrator once it expired
+    pub fn expire_comm_window(&mut self, mdns: &dyn Mdns) -> Result<(), Error> {
+        if let Some(window) = self.comm_window() {
+            if window.expires_at <= (self.epoch)() {
+                info!("Commissioning window expired");
+                self.disable_pase_session(mdns)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn disable_pase_session(&mut self, mdns: &dyn Mdns) -> Result<(), Error> {
+        if let Some(session) = self.session.as_ref() {
+            mdns.remove(&session.mdns_service_name)?;
+        }
+
+        self.session = None;
+
+        Ok(())
+    }
+
+    fn enable(
+        &mut self,
+        verifier: VerifierData,
+        discriminator: u16,
+        window: Option<CommWindow>,
+        mdns: &dyn Mdns,
     ) -> Result<(), Error> {
         let mut buf = [0; 8];
         (self.rand)(&mut buf);
@@ -84,20 +168,11 @@ impl PaseMgr {
         self.session = Some(PaseSession {
             mdns_service_name,
             verifier,
+            window,
         });
 
         Ok(())
     }
-
-    pub fn disable_pase_session(&mut self, mdns: &dyn Mdns) -> Result<(), Error> {
-        if let Some(session) = self.session.as_ref() {
-            mdns.remove(&session.mdns_service_name)?;
-        }
-
-        self.session = None;
-
-        Ok(())
-    }
 }
 
 // This file basically deals with the handlers for the PASE secure channel protocol
@@ -129,12 +204,16 @@ impl Timeout {
 
 pub struct Pake<'a> {
     pase: &'a RefCell<PaseMgr>,
+    session_params: SessionParams,
 }
 
 impl<'a> Pake<'a> {
-    pub const fn new(pase: &'a RefCell<PaseMgr>) -> Self {
+    pub const fn new(pase: &'a RefCell<PaseMgr>, session_params: SessionParams) -> Self {
         // TODO: Can any PBKDF2 calculation be pre-computed here
-        Self { pase }
+        Self {
+            pase,
+            session_params,
+        }
     }
 
     pub async fn handle(
@@ -288,6 +367,7 @@ impl<'a> Pake<'a> {
                 our_random: OctetStr(&our_random),
                 local_sessid,
                 params: None,
+                session_params: Some(self.session_params),
             };
             if !a.has_params {
                 let params_resp = PBKDFParamRespParams {
@@ -385,6 +465,7 @@ struct PBKDFParamResp<'a> {
     our_random: OctetStr<'a>,
     local_sessid: u16,
     params: Option<PBKDFParamRespParams<'a>>,
+    session_params: Option<SessionParams>,
 }
 
 #[allow(non_snake_case)]
@@ -294,6 +294,15 @@ impl<T> Nullable<T> {
     }
 }
 
+impl<T> From<Option<T>> for Nullable<T> {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => Nullable::NotNull(value),
+            None => Nullable::Null,
+        }
+    }
+}
+
 impl<'a, T: FromTLV<'a>> FromTLV<'a> for Nullable<T> {
     fn from_tlv(t: &TLVElement<'a>) -> Result<Nullable<T>, Error> {
         match t.get_element_type() {
@@ -435,6 +444,12 @@ impl<'a, T: Debug + ToTLV + FromTLV<'a> + Clone> Debug for TLVArray<'a, T> {
     }
 }
 
+impl<'a> FromTLV<'a> for TLVElement<'a> {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<Self, Error> {
+        Ok(t.clone())
+    }
+}
+
 impl<'a> ToTLV for TLVElement<'a> {
     fn to_tlv(&self, tw: &mut TLVWriter, _tag_type: TagType) -> Result<(), Error> {
         match self.get_element_type() {
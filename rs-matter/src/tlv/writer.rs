@@ -161,6 +161,11 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
         }
     }
 
+    pub fn f32(&mut self, tag_type: TagType, data: f32) -> Result<(), Error> {
+        self.put_control_tag(tag_type, WriteElementType::F32)?;
+        self.buf.le_u32(data.to_bits())
+    }
+
     pub fn str8(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
         if data.len() > 256 {
             error!("use str16() instead");
@@ -313,6 +318,17 @@ mod tests {
         assert_eq!(buf, [4, 12, 36, 1, 13, 4]);
     }
 
+    #[test]
+    fn test_write_f32() {
+        let mut buf = [0; 11];
+        let mut writebuf = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        tw.f32(TagType::Anonymous, 1.0).unwrap();
+        tw.f32(TagType::Context(1), -2.0).unwrap();
+        assert_eq!(buf, [10, 0, 0, 0x80, 0x3f, 42, 1, 0, 0, 0, 0xc0]);
+    }
+
     #[test]
     fn test_put_str8() {
         let mut buf = [0; 20];
@@ -21,7 +21,7 @@ use core::pin::pin;
 
 use embassy_futures::select::{select, select_slice, Either};
 use embassy_sync::{blocking_mutex::raw::NoopRawMutex, channel::Channel};
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 
 use log::{error, info, warn};
 
@@ -29,7 +29,7 @@ use crate::utils::select::Notification;
 use crate::CommissioningData;
 use crate::{
     alloc,
-    data_model::{core::DataModel, objects::DataModelHandler},
+    data_model::{cluster_basic_information, core::DataModel, objects::DataModelHandler},
     error::{Error, ErrorCode},
     interaction_model::core::PROTO_ID_INTERACTION_MODEL,
     secure_channel::{
@@ -43,11 +43,14 @@ use crate::{
 
 use super::{
     exchange::{
-        Exchange, ExchangeCtr, ExchangeCtx, ExchangeId, ExchangeState, Role, MAX_EXCHANGES,
+        Exchange, ExchangeCtr, ExchangeCtx, ExchangeId, ExchangeState, Role, SessionId,
+        MAX_EXCHANGES,
     },
-    mrp::ReliableMessage,
+    mrp::{ReliableMessage, RetransTiming, SessionParams},
+    network::Address,
     packet::{MAX_RX_BUF_SIZE, MAX_RX_STATUS_BUF_SIZE, MAX_TX_BUF_SIZE},
     pipe::{Chunk, Pipe},
+    session::SessionMgr,
 };
 
 #[derive(Debug)]
@@ -160,6 +163,8 @@ impl<'a> Matter<'a> {
                     if let Some(chunk) = data.chunk {
                         udp.send(chunk.addr.unwrap_udp(), &data.buf[chunk.start..chunk.end])
                             .await?;
+                        self.packet_counters.sent();
+                        self.icd.borrow_mut().on_activity((self.epoch)());
                         data.chunk = None;
                         tx_pipe.data_consumed_notification.signal(());
                     }
@@ -176,6 +181,8 @@ impl<'a> Matter<'a> {
 
                     if data.chunk.is_none() {
                         let (len, addr) = udp.recv(data.buf).await?;
+                        self.packet_counters.received();
+                        self.icd.borrow_mut().on_activity((self.epoch)());
 
                         data.chunk = Some(Chunk {
                             start: 0,
@@ -213,6 +220,8 @@ impl<'a> Matter<'a> {
     {
         info!("Running Matter transport");
 
+        cluster_basic_information::start_up(&self.events, self.dev_det().sw_ver)?;
+
         let buf = unsafe { buffers.rx[0].assume_init_mut() };
 
         if self.start_comissioning(dev_comm, buf)? {
@@ -223,8 +232,11 @@ impl<'a> Matter<'a> {
 
         let mut rx = pin!(self.handle_rx(buffers, rx_pipe, &construction_notification, handler));
         let mut tx = pin!(self.handle_tx(tx_pipe));
+        let mut timers = pin!(self.handle_timers(handler));
 
-        select(&mut rx, &mut tx).await.unwrap()
+        embassy_futures::select::select3(&mut rx, &mut tx, &mut timers)
+            .await
+            .unwrap()
     }
 
     #[inline(always)]
@@ -311,6 +323,34 @@ impl<'a> Matter<'a> {
         }
     }
 
+    /// Close the commissioning window opened by an administrator, and disarm the
+    /// fail-safe, once they expire
+    ///
+    /// Sleeps until the next expiry, or until an exchange is over if there is none, as
+    /// the exchanges are what opens the window and arms the fail-safe.
+    pub async fn handle_timers<H>(&self, handler: &H) -> Result<(), Error>
+    where
+        H: DataModelHandler,
+    {
+        loop {
+            self.expire_comm_window()?;
+            self.expire_failsafe();
+
+            if self.take_failsafe_expired() {
+                handler.failsafe_expired();
+            }
+
+            if let Some(expires_at) = self.next_expiry() {
+                let remaining = expires_at.saturating_sub((self.epoch)());
+                let at = Instant::now() + Duration::from_micros(remaining.as_micros() as _);
+
+                select(Timer::at(at), self.wait_timers_changed()).await;
+            } else {
+                self.wait_timers_changed().await;
+            }
+        }
+    }
+
     #[inline(always)]
     pub async fn handle_rx_multiplex<'t, 'e, const N: usize>(
         &'t self,
@@ -426,19 +466,138 @@ impl<'a> Matter<'a> {
 
                 let mut rx_status = alloc!(Packet::new_rx(sx_buf));
 
-                dm.handle(&mut exchange, &mut rx, &mut tx, &mut rx_status)
+                let attrs_changed = dm
+                    .handle(&mut exchange, &mut rx, &mut tx, &mut rx_status)
                     .await?;
 
-                self.notify_changed();
+                if attrs_changed {
+                    self.notify_attrs_changed();
+                } else {
+                    self.notify_changed();
+                }
             }
             other => {
                 error!("Unknown Proto-ID: {}", other);
             }
         }
 
+        while let Some(fab_idx) = self.take_removed_fabric() {
+            self.subscriptions.borrow_mut().remove_fabric(fab_idx);
+            cluster_basic_information::leave(&self.events, fab_idx)?;
+            handler.fabric_removed(fab_idx);
+        }
+
+        if self.take_failsafe_expired() {
+            handler.failsafe_expired();
+        }
+
+        self.notify_timers_changed();
+
         Ok(())
     }
 
+    /// Initiate an exchange over the session `session_id`
+    ///
+    /// Unlike the exchanges initiated by peers, the exchange is driven by the caller, which
+    /// sends the first message. The transport needs to be running for the exchange to proceed.
+    pub fn initiate_exchange(&self, session_id: SessionId) -> Result<Exchange<'_>, Error> {
+        self.purge()?;
+
+        let mut exchanges = self.exchanges.borrow_mut();
+
+        let id = loop {
+            let mut id = [0; 2];
+            (self.rand)(&mut id);
+
+            let id = ExchangeId {
+                id: u16::from_le_bytes(id),
+                session_id: session_id.clone(),
+            };
+
+            if ExchangeCtx::get(&mut exchanges, &id).is_none() {
+                break id;
+            }
+        };
+
+        Self::register(&mut exchanges, id.clone(), Role::Initiator, true)?;
+
+        Ok(Exchange {
+            id,
+            matter: self,
+            notification: Notification::new(),
+        })
+    }
+
+    /// The ID of the CASE session with node `peer_nodeid` of our fabric `fab_idx`, if any
+    pub fn get_case_session(&self, fab_idx: u8, peer_nodeid: u64) -> Option<SessionId> {
+        let mut session_mgr = self.session_mgr.borrow_mut();
+
+        let sess_index = session_mgr.get_case(fab_idx, peer_nodeid)?;
+
+        session_mgr.mut_by_index(sess_index).map(|sess| sess.id())
+    }
+
+    /// Establish a CASE session with node `peer_nodeid` of our fabric `fab_idx`, reachable
+    /// at `peer_addr`
+    ///
+    /// `tx` and `rx` are used for the messages of the session establishment. The transport
+    /// needs to be running for the session establishment to proceed.
+    pub async fn establish_case_session(
+        &self,
+        fab_idx: u8,
+        peer_nodeid: u64,
+        peer_addr: Address,
+        tx: &mut Packet<'_>,
+        rx: &mut Packet<'_>,
+    ) -> Result<SessionId, Error> {
+        let (unsecured_index, unsecured_id) = {
+            let mut session_mgr = self.session_mgr.borrow_mut();
+
+            let sess_index = session_mgr.add_ephemeral(peer_addr)?;
+            let sess_id = session_mgr.mut_by_index(sess_index).unwrap().id();
+
+            (sess_index, sess_id)
+        };
+
+        let result = async {
+            let mut exchange = self.initiate_exchange(unsecured_id)?;
+
+            SecureChannel::new(self)
+                .initiate_case(&mut exchange, rx, tx, fab_idx, peer_nodeid)
+                .await
+        }
+        .await;
+
+        let mut session_mgr = self.session_mgr.borrow_mut();
+
+        session_mgr.remove(unsecured_index);
+
+        let sess_index = result?;
+
+        Ok(session_mgr.mut_by_index(sess_index).unwrap().id())
+    }
+
+    /// Send `tx` to `peer_addr` over a new unsecured session, as with the Check-In
+    /// messages of an ICD
+    ///
+    /// The transport needs to be running for the message to be sent.
+    pub async fn send_unsecured(&self, peer_addr: Address, tx: &Packet<'_>) -> Result<(), Error> {
+        let (unsecured_index, unsecured_id) = {
+            let mut session_mgr = self.session_mgr.borrow_mut();
+
+            let sess_index = session_mgr.add_ephemeral(peer_addr)?;
+            let sess_id = session_mgr.mut_by_index(sess_index).unwrap().id();
+
+            (sess_index, sess_id)
+        };
+
+        let result = async { self.initiate_exchange(unsecured_id)?.complete(tx).await }.await;
+
+        self.session_mgr.borrow_mut().remove(unsecured_index);
+
+        result
+    }
+
     pub fn reset_transport(&self) {
         self.exchanges.borrow_mut().clear();
         self.session_mgr.borrow_mut().reset();
@@ -459,6 +618,11 @@ impl<'a> Matter<'a> {
                     self.send_notification.signal(());
                     return Ok(None);
                 }
+                ErrorCode::NoExchange => {
+                    // E.g. a second acknowledgement of a retransmitted message
+                    warn!("Dropping a message of an unknown exchange");
+                    return Ok(None);
+                }
                 _ => Err(e)?,
             },
         };
@@ -575,6 +739,8 @@ impl<'a> Matter<'a> {
     pub async fn pull_tx(&self, dest_tx: &mut Packet<'_>) -> Result<bool, Error> {
         self.purge()?;
 
+        let now = (self.epoch)();
+
         let mut exchanges = self.exchanges.borrow_mut();
 
         let ctx = exchanges.iter_mut().find(|ctx| {
@@ -582,17 +748,18 @@ impl<'a> Matter<'a> {
                 &ctx.state,
                 ExchangeState::Acknowledge { .. }
                     | ExchangeState::ExchangeSend { .. }
-                    // | ExchangeState::ExchangeRecv {
-                    //     tx_acknowledged: false,
-                    //     ..
-                    // }
-                    | ExchangeState::Complete { .. } // | ExchangeState::CompleteAcknowledge { .. }
-            ) || ctx.mrp.is_ack_ready(*self.borrow())
+                    | ExchangeState::Complete { .. }
+            ) || Self::is_retrans_due(ctx, now)
+                || ctx.mrp.is_ack_ready(*self.borrow())
         });
 
         if let Some(ctx) = ctx {
             self.notify_changed();
 
+            if Self::is_retrans_due(ctx, now) {
+                return self.resend(ctx, dest_tx, now);
+            }
+
             let state = &mut ctx.state;
 
             let send = match state {
@@ -621,25 +788,23 @@ impl<'a> Matter<'a> {
 
                     true
                 }
-                // ExchangeState::ExchangeRecv { .. } => {
-                //     // TODO: Re-send the tx package if due
-                //     false
-                // }
                 ExchangeState::Complete { tx, notification } => {
                     let tx = unsafe { tx.as_ref() }.unwrap();
                     dest_tx.load(tx)?;
 
-                    *state = ExchangeState::CompleteAcknowledge {
-                        _tx: tx as *const _,
-                        notification: *notification,
-                    };
+                    if tx.proto.is_reliable() {
+                        *state = ExchangeState::CompleteAcknowledge {
+                            _tx: tx as *const _,
+                            notification: *notification,
+                        };
+                    } else {
+                        // Nothing to wait for, as no acknowledgement will come
+                        unsafe { notification.as_ref() }.unwrap().signal(());
+                        *state = ExchangeState::Closed;
+                    }
 
                     true
                 }
-                // ExchangeState::CompleteAcknowledge { .. } => {
-                //     // TODO: Re-send the tx package if due
-                //     false
-                // }
                 _ => {
                     ReliableMessage::prepare_ack(ctx.id.id, dest_tx);
                     true
@@ -659,6 +824,59 @@ impl<'a> Matter<'a> {
         Ok(false)
     }
 
+    /// Whether the exchange still waits for the acknowledgement of the message it sent,
+    /// and that message is due for a retransmission
+    fn is_retrans_due(ctx: &ExchangeCtx, now: core::time::Duration) -> bool {
+        matches!(
+            &ctx.state,
+            ExchangeState::ExchangeRecv { .. } | ExchangeState::CompleteAcknowledge { .. }
+        ) && ctx.mrp.is_retrans_due(now)
+    }
+
+    fn resend(
+        &self,
+        ctx: &mut ExchangeCtx,
+        dest_tx: &mut Packet<'_>,
+        now: core::time::Duration,
+    ) -> Result<bool, Error> {
+        let (tx, notification) = match &ctx.state {
+            ExchangeState::ExchangeRecv {
+                _tx, notification, ..
+            } => (*_tx, *notification),
+            ExchangeState::CompleteAcknowledge { _tx, notification } => (*_tx, *notification),
+            _ => unreachable!(),
+        };
+
+        dest_tx.load(unsafe { tx.as_ref() }.unwrap())?;
+
+        let msg_ctr = if let Some(msg_ctr) = ctx.mrp.pre_resend(dest_tx, now, self.rand)? {
+            msg_ctr
+        } else {
+            // The peer is gone: wake up the exchange, which then finds itself closed
+            unsafe { notification.as_ref() }.unwrap().signal(());
+            ctx.state = ExchangeState::Closed;
+
+            return Ok(false);
+        };
+
+        dest_tx.log("Resending packet");
+
+        let mut session_mgr = self.session_mgr.borrow_mut();
+        let sess_index = Self::session_index(&session_mgr, ctx)?;
+
+        Self::set_exchange(ctx, dest_tx);
+
+        session_mgr
+            .mut_by_index(sess_index)
+            .unwrap()
+            .pre_resend(dest_tx, msg_ctr)?;
+        session_mgr.send(sess_index, dest_tx)?;
+
+        self.notify_changed();
+
+        Ok(true)
+    }
+
     fn purge(&self) -> Result<(), Error> {
         loop {
             let mut exchanges = self.exchanges.borrow_mut();
@@ -710,25 +928,57 @@ impl<'a> Matter<'a> {
 
     fn pre_send(&self, ctx: &mut ExchangeCtx, tx: &mut Packet) -> Result<(), Error> {
         let mut session_mgr = self.session_mgr.borrow_mut();
-        let sess_index = session_mgr
+        let sess_index = Self::session_index(&session_mgr, ctx)?;
+
+        let session = session_mgr.mut_by_index(sess_index).unwrap();
+
+        Self::set_exchange(ctx, tx);
+
+        let now = (self.epoch)();
+        let timing = self.retrans_timing(now.saturating_sub(session.last_rx()));
+
+        session.pre_send(tx)?;
+        ctx.mrp.pre_send(tx, timing, now, self.rand)?;
+        session_mgr.send(sess_index, tx)
+    }
+
+    /// The timing of the retransmissions to a peer which last sent us a message `since_rx`
+    /// ago
+    ///
+    /// The session parameters of the peers are not parsed from the PASE and CASE handshakes
+    /// yet, so the default ones are assumed for them. The polling interval of the node, if
+    /// it is an ICD, is added on top.
+    fn retrans_timing(&self, since_rx: core::time::Duration) -> RetransTiming {
+        let peer = SessionParams::DEFAULT;
+
+        let base_interval_ms = if since_rx.as_millis() < peer.active_threshold_ms as u128 {
+            peer.active_interval_ms
+        } else {
+            peer.idle_interval_ms
+        };
+
+        RetransTiming {
+            base_interval_ms,
+            poll_interval_ms: self.icd.borrow().poll_interval_ms(),
+        }
+    }
+
+    fn session_index(session_mgr: &SessionMgr, ctx: &ExchangeCtx) -> Result<usize, Error> {
+        session_mgr
             .get(
                 ctx.id.session_id.id,
                 ctx.id.session_id.peer_addr,
                 ctx.id.session_id.peer_nodeid,
                 ctx.id.session_id.is_encrypted,
             )
-            .ok_or(ErrorCode::NoSession)?;
-
-        let session = session_mgr.mut_by_index(sess_index).unwrap();
+            .ok_or_else(|| ErrorCode::NoSession.into())
+    }
 
+    fn set_exchange(ctx: &ExchangeCtx, tx: &mut Packet) {
         tx.proto.exch_id = ctx.id.id;
         if ctx.role == Role::Initiator {
             tx.proto.set_initiator();
         }
-
-        session.pre_send(tx)?;
-        ctx.mrp.pre_send(tx)?;
-        session_mgr.send(sess_index, tx)
     }
 
     fn register(
@@ -247,7 +247,14 @@ impl<'a> Exchange<'a> {
 
         self.notification.wait().await;
 
-        Ok(())
+        // Closed by the transport if the peer never acknowledged `tx`
+        self.with_ctx(|_, ctx| {
+            if matches!(ctx.state, ExchangeState::Closed) {
+                Err(ErrorCode::NoExchange.into())
+            } else {
+                Ok(())
+            }
+        })
     }
 
     pub async fn complete(mut self, tx: &Packet<'_>) -> Result<(), Error> {
@@ -15,30 +15,128 @@
  *    limitations under the License.
  */
 
-use crate::utils::epoch::Epoch;
+use crate::utils::{epoch::Epoch, rand::Rand};
 use core::time::Duration;
 
-use crate::{error::*, secure_channel, transport::packet::Packet};
-use log::error;
+use crate::{
+    error::*,
+    secure_channel,
+    tlv::{TLVWriter, TagType, ToTLV},
+    transport::packet::Packet,
+};
+use log::{error, warn};
 
 // 200 ms
 const MRP_STANDALONE_ACK_TIMEOUT: u64 = 200;
 
+/// How many times a reliable message is sent, including its first transmission
+const MRP_MAX_TRANSMISSIONS: u8 = 5;
+
+/// The number of retransmissions before the backoff starts growing
+const MRP_BACKOFF_THRESHOLD: u8 = 1;
+
+/// The MRP parameters of this node, which its peers use for timing their retransmissions
+///
+/// Advertised with the SII, SAI and SAT TXT records of mDNS, and in the session parameters
+/// of the PASE and CASE handshakes.
+#[derive(ToTLV, Debug, Clone, Copy, PartialEq, Eq)]
+#[tlvargs(start = 1)]
+pub struct SessionParams {
+    /// The retransmission interval to use while the node is idle, in milliseconds
+    pub idle_interval_ms: u32,
+    /// The retransmission interval to use while the node is active, in milliseconds
+    pub active_interval_ms: u32,
+    /// How long the node stays active after sending or receiving a message, in milliseconds
+    pub active_threshold_ms: u16,
+}
+
+impl SessionParams {
+    /// The parameters of a node which is always reachable, i.e. which is not an ICD
+    pub const DEFAULT: Self = Self {
+        idle_interval_ms: 5000,
+        active_interval_ms: 300,
+        active_threshold_ms: 4000,
+    };
+}
+
+impl Default for SessionParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// How the retransmissions of a reliable message are timed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetransTiming {
+    /// The active or the idle interval of the peer, depending on whether the peer is
+    /// active, in milliseconds
+    pub base_interval_ms: u32,
+    /// The polling interval of this node if it is an ICD, in milliseconds
+    ///
+    /// Added to every backoff, as an ICD only gets the acknowledgement when it polls next.
+    pub poll_interval_ms: u32,
+}
+
+impl RetransTiming {
+    /// The time to wait for an acknowledgement after the `transmissions`-th transmission
+    /// of a message, with `jitter` picking the random part of it
+    ///
+    /// As per the spec, the base interval grows with a margin of 1.1 and a base of 1.6
+    /// per retransmission past the threshold, and is extended by a random jitter of up
+    /// to 25%.
+    pub fn backoff(&self, transmissions: u8, jitter: u8) -> Duration {
+        let mut backoff_ms = self.base_interval_ms as u64 * 11 / 10;
+
+        for _ in MRP_BACKOFF_THRESHOLD..transmissions.max(MRP_BACKOFF_THRESHOLD) {
+            backoff_ms = backoff_ms * 16 / 10;
+        }
+
+        backoff_ms = backoff_ms * (1020 + jitter as u64) / 1020;
+
+        Duration::from_millis(backoff_ms + self.poll_interval_ms as u64)
+    }
+}
+
 #[derive(Debug)]
 pub struct RetransEntry {
     // The msg counter that we are waiting to be acknowledged
     msg_ctr: u32,
-    // This will additionally have retransmission count and periods once we implement it
+    // How many times the message was sent so far
+    transmissions: u8,
+    timing: RetransTiming,
+    // When the message is due for a retransmission, as per the epoch
+    due: Duration,
 }
 
 impl RetransEntry {
-    pub fn new(msg_ctr: u32) -> Self {
-        Self { msg_ctr }
+    pub fn new(msg_ctr: u32, timing: RetransTiming, now: Duration, rand: Rand) -> Self {
+        let mut entry = Self {
+            msg_ctr,
+            transmissions: 0,
+            timing,
+            due: now,
+        };
+
+        entry.sent(now, rand);
+
+        entry
     }
 
     pub fn get_msg_ctr(&self) -> u32 {
         self.msg_ctr
     }
+
+    pub fn is_due(&self, now: Duration) -> bool {
+        now >= self.due
+    }
+
+    fn sent(&mut self, now: Duration, rand: Rand) {
+        let mut jitter = [0];
+        rand(&mut jitter);
+
+        self.transmissions += 1;
+        self.due = now + self.timing.backoff(self.transmissions, jitter[0]);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -103,16 +201,55 @@ impl ReliableMessage {
         secure_channel::common::create_mrp_standalone_ack(proto_tx);
     }
 
-    pub fn pre_send(&mut self, proto_tx: &mut Packet) -> Result<(), Error> {
-        // Check if any acknowledgements are pending for this exchange,
+    /// Whether the reliable message sent last is still not acknowledged, and is due for
+    /// a retransmission at `now`
+    pub fn is_retrans_due(&self, now: Duration) -> bool {
+        self.retrans
+            .as_ref()
+            .map(|entry| entry.is_due(now))
+            .unwrap_or(false)
+    }
 
-        // if so, piggy back in the encoded header here
-        if let Some(ack_entry) = &self.ack {
-            // Ack Entry exists, set ACK bit and remove from table
-            proto_tx.proto.set_ack(ack_entry.get_msg_ctr());
-            self.ack = None;
+    /// Prepare the retransmission of the reliable message sent last
+    ///
+    /// Returns the counter the message has to be sent with, or `None` if the message was
+    /// sent `MRP_MAX_TRANSMISSIONS` times already, in which case it is given up on.
+    pub fn pre_resend(
+        &mut self,
+        proto_tx: &mut Packet,
+        now: Duration,
+        rand: Rand,
+    ) -> Result<Option<u32>, Error> {
+        let entry = self.retrans.as_mut().ok_or(ErrorCode::Invalid)?;
+
+        if entry.transmissions >= MRP_MAX_TRANSMISSIONS {
+            warn!(
+                "Giving up on message {}, which was not acknowledged",
+                entry.get_msg_ctr()
+            );
+
+            self.retrans = None;
+
+            return Ok(None);
         }
 
+        entry.sent(now, rand);
+        let msg_ctr = entry.get_msg_ctr();
+
+        self.piggyback_ack(proto_tx);
+
+        Ok(Some(msg_ctr))
+    }
+
+    pub fn pre_send(
+        &mut self,
+        proto_tx: &mut Packet,
+        timing: RetransTiming,
+        now: Duration,
+        rand: Rand,
+    ) -> Result<(), Error> {
+        self.piggyback_ack(proto_tx);
+
         if !proto_tx.is_reliable() {
             return Ok(());
         }
@@ -123,10 +260,21 @@ impl ReliableMessage {
             Err(ErrorCode::Invalid)?;
         }
 
-        self.retrans = Some(RetransEntry::new(proto_tx.plain.ctr));
+        self.retrans = Some(RetransEntry::new(proto_tx.plain.ctr, timing, now, rand));
         Ok(())
     }
 
+    fn piggyback_ack(&mut self, proto_tx: &mut Packet) {
+        // Check if any acknowledgements are pending for this exchange,
+
+        // if so, piggy back in the encoded header here
+        if let Some(ack_entry) = &self.ack {
+            // Ack Entry exists, set ACK bit and remove from table
+            proto_tx.proto.set_ack(ack_entry.get_msg_ctr());
+            self.ack = None;
+        }
+    }
+
     /* A note about Message ACKs, it is a bit asymmetric in the sense that:
      * -  there can be only one pending ACK per exchange (so this is per-exchange)
      * -  there can be only one pending retransmission per exchange (so this is per-exchange)
@@ -158,3 +306,51 @@ impl ReliableMessage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::utils::rand::dummy_rand;
+
+    use super::{RetransEntry, RetransTiming, MRP_MAX_TRANSMISSIONS};
+
+    const TIMING: RetransTiming = RetransTiming {
+        base_interval_ms: 300,
+        poll_interval_ms: 0,
+    };
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(TIMING.backoff(1, 0), Duration::from_millis(330));
+        assert_eq!(TIMING.backoff(2, 0), Duration::from_millis(528));
+        assert_eq!(TIMING.backoff(3, 0), Duration::from_millis(844));
+
+        // Up to 25% of jitter
+        assert_eq!(TIMING.backoff(1, 255), Duration::from_millis(412));
+
+        // An ICD waits for its next poll on top
+        let icd = RetransTiming {
+            poll_interval_ms: 1000,
+            ..TIMING
+        };
+        assert_eq!(icd.backoff(1, 0), Duration::from_millis(1330));
+    }
+
+    #[test]
+    fn test_retrans_entry() {
+        let mut entry = RetransEntry::new(7, TIMING, Duration::ZERO, dummy_rand);
+
+        assert!(!entry.is_due(Duration::from_millis(329)));
+        assert!(entry.is_due(Duration::from_millis(330)));
+
+        entry.sent(Duration::from_millis(330), dummy_rand);
+        assert!(!entry.is_due(Duration::from_millis(857)));
+        assert!(entry.is_due(Duration::from_millis(858)));
+
+        while entry.transmissions < MRP_MAX_TRANSMISSIONS {
+            entry.sent(Duration::ZERO, dummy_rand);
+        }
+        assert_eq!(entry.get_msg_ctr(), 7);
+    }
+}
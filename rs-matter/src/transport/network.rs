@@ -15,6 +15,7 @@
  *    limitations under the License.
  */
 
+use core::cell::Cell;
 use core::fmt::{Debug, Display};
 #[cfg(not(feature = "std"))]
 pub use no_std_net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
@@ -56,6 +57,47 @@ impl Debug for Address {
     }
 }
 
+/// The number of packets received and sent by the Matter transport, since the
+/// last reset
+///
+/// Reported by the Ethernet Network Diagnostics cluster, when the platform does
+/// not have better statistics of the network interface.
+#[derive(Debug, Default)]
+pub struct PacketCounters {
+    rx: Cell<u64>,
+    tx: Cell<u64>,
+}
+
+impl PacketCounters {
+    pub const fn new() -> Self {
+        Self {
+            rx: Cell::new(0),
+            tx: Cell::new(0),
+        }
+    }
+
+    pub fn rx_count(&self) -> u64 {
+        self.rx.get()
+    }
+
+    pub fn tx_count(&self) -> u64 {
+        self.tx.get()
+    }
+
+    pub fn received(&self) {
+        self.rx.set(self.rx.get().wrapping_add(1));
+    }
+
+    pub fn sent(&self) {
+        self.tx.set(self.tx.get().wrapping_add(1));
+    }
+
+    pub fn reset(&self) {
+        self.rx.set(0);
+        self.tx.set(0);
+    }
+}
+
 #[cfg(all(feature = "std", not(feature = "embassy-net")))]
 pub use std_stack::*;
 
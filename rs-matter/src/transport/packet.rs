@@ -233,6 +233,9 @@ impl<'a> Packet<'a> {
         if plain_text {
             if let Some(d) = peer_nodeid {
                 self.plain.set_dest_u64(d);
+            } else if local_nodeid != 0 {
+                // The ephemeral node ID of the initiator of a CASE session establishment
+                self.plain.set_src_u64(local_nodeid);
             }
         }
 
@@ -53,6 +53,11 @@ impl PlainHdr {
         self.peer_nodeid = Some(id);
     }
 
+    pub fn set_src_u64(&mut self, id: u64) {
+        self.flags |= MsgFlags::SRC_ADDR_PRESENT;
+        self.peer_nodeid = Some(id);
+    }
+
     pub fn get_src_u64(&self) -> Option<u64> {
         if self.flags.contains(MsgFlags::SRC_ADDR_PRESENT) {
             self.peer_nodeid
@@ -79,6 +84,14 @@ impl PlainHdr {
             self.peer_nodeid = Some(msg.le_u64()?);
         }
 
+        // The destination is us, i.e. our ephemeral node ID during a CASE session establishment
+        // we initiated
+        if self.flags.contains(MsgFlags::DSIZ_UNICAST_NODEID) {
+            msg.le_u64()?;
+        } else if self.flags.contains(MsgFlags::DSIZ_GROUPCAST_NODEID) {
+            msg.le_u16()?;
+        }
+
         info!(
             "[decode] flags: {:?}, session type: {:#?}, sess_id: {}, ctr: {}",
             self.flags, self.sess_type, self.sess_id, self.ctr
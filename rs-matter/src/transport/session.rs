@@ -26,7 +26,7 @@ use crate::{error::*, transport::plain_hdr};
 use log::info;
 
 use super::dedup::RxCtrState;
-use super::{network::Address, packet::Packet};
+use super::{exchange::SessionId, network::Address, packet::Packet};
 
 pub const MAX_CAT_IDS_PER_NOC: usize = 3;
 pub type NocCatIds = [u32; MAX_CAT_IDS_PER_NOC];
@@ -73,6 +73,7 @@ pub struct Session {
     mode: SessionMode,
     data: Option<NocData>,
     last_use: Duration,
+    last_rx: Duration,
 }
 
 #[derive(Debug)]
@@ -111,7 +112,7 @@ impl CloneData {
     }
 }
 
-const MATTER_MSG_CTR_RANGE: u32 = 0x0fffffff;
+pub(crate) const MATTER_MSG_CTR_RANGE: u32 = 0x0fffffff;
 
 impl Session {
     pub fn new(peer_addr: Address, peer_nodeid: Option<u64>, epoch: Epoch, rand: Rand) -> Self {
@@ -129,6 +130,7 @@ impl Session {
             mode: SessionMode::PlainText,
             data: None,
             last_use: epoch(),
+            last_rx: epoch(),
         }
     }
 
@@ -148,6 +150,7 @@ impl Session {
             mode: clone_from.mode.clone(),
             data: None,
             last_use: epoch(),
+            last_rx: epoch(),
         }
     }
 
@@ -184,6 +187,18 @@ impl Session {
         self.peer_addr
     }
 
+    /// The ID of the session, as the exchanges we initiate over it are registered with
+    ///
+    /// The peer node ID is left out, as peers do not send it with their unicast messages.
+    pub fn id(&self) -> SessionId {
+        SessionId {
+            id: self.local_sess_id,
+            peer_addr: self.peer_addr,
+            peer_nodeid: None,
+            is_encrypted: self.is_encrypted(),
+        }
+    }
+
     pub fn is_encrypted(&self) -> bool {
         match self.mode {
             SessionMode::Case(_) | SessionMode::Pase => true,
@@ -239,12 +254,26 @@ impl Session {
 
     pub fn recv(&mut self, epoch: Epoch, rx: &mut Packet) -> Result<(), Error> {
         self.last_use = epoch();
+        self.last_rx = self.last_use;
         rx.proto_decode(self.peer_nodeid.unwrap_or_default(), self.get_dec_key())
     }
 
+    /// When the last message was received from the peer, as per the epoch
+    pub fn last_rx(&self) -> Duration {
+        self.last_rx
+    }
+
     pub fn pre_send(&mut self, tx: &mut Packet) -> Result<(), Error> {
+        let ctr = self.get_msg_ctr();
+
+        self.pre_resend(tx, ctr)
+    }
+
+    /// Like `pre_send`, but for the retransmission of the message with counter `ctr`,
+    /// which keeps its counter
+    pub fn pre_resend(&mut self, tx: &mut Packet, ctr: u32) -> Result<(), Error> {
         tx.plain.sess_id = self.get_peer_sess_id();
-        tx.plain.ctr = self.get_msg_ctr();
+        tx.plain.ctr = ctr;
         if self.is_encrypted() {
             tx.plain.sess_type = plain_hdr::SessionType::Encrypted;
         }
@@ -365,6 +394,30 @@ impl SessionMgr {
         self.add_session(session)
     }
 
+    /// Add an unsecured session for establishing a CASE session with a peer
+    ///
+    /// Our messages carry a random ephemeral node ID as their source, which the peer
+    /// sends its messages to.
+    pub fn add_ephemeral(&mut self, peer_addr: Address) -> Result<usize, Error> {
+        let mut nodeid = [0; 8];
+        (self.rand)(&mut nodeid);
+
+        let mut session = Session::new(peer_addr, None, self.epoch, self.rand);
+        // Within the range of operational node IDs
+        session.local_nodeid = u64::from_le_bytes(nodeid) % 0xFFFF_FFEF_FFFF_FFFF + 1;
+
+        self.add_session(session)
+    }
+
+    /// The CASE session with node `peer_nodeid` of our fabric `fab_idx`, if any
+    pub fn get_case(&self, fab_idx: u8, peer_nodeid: u64) -> Option<usize> {
+        self.sessions.iter().position(|x| {
+            x.as_ref().map_or(false, |x| {
+                x.get_local_fabric_idx() == Some(fab_idx) && x.peer_nodeid == Some(peer_nodeid)
+            })
+        })
+    }
+
     /// This assumes that the higher layer has taken care of doing anything required
     /// as per the spec before the session is erased
     pub fn remove(&mut self, idx: usize) {
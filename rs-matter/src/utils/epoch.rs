@@ -1,3 +1,4 @@
+use core::cell::Cell;
 use core::time::Duration;
 
 pub type Epoch = fn() -> Duration;
@@ -9,6 +10,51 @@ pub const MATTER_CERT_DOESNT_EXPIRE: u64 = 252455615999;
 
 pub const MATTER_EPOCH_SECS: u64 = 946684800; // Seconds from 1970/01/01 00:00:00 till 2000/01/01 00:00:00 UTC
 
+/// The UTC time of the node, as last set by an administrator or the application,
+/// and advanced with the `Epoch` since
+///
+/// Times are in microseconds since the Matter epoch, i.e. 2000/01/01 00:00:00 UTC.
+pub struct UtcClock {
+    epoch: Epoch,
+    // The UTC time when it was set, and the epoch at that moment
+    set_at: Cell<Option<(u64, Duration)>>,
+}
+
+impl UtcClock {
+    pub const fn new(epoch: Epoch) -> Self {
+        Self {
+            epoch,
+            set_at: Cell::new(None),
+        }
+    }
+
+    pub fn set(&self, utc_us: u64) {
+        self.set_at.set(Some((utc_us, (self.epoch)())));
+    }
+
+    pub fn clear(&self) {
+        self.set_at.set(None);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.set_at.get().is_some()
+    }
+
+    /// The current UTC time, if it was ever set
+    pub fn now_us(&self) -> Option<u64> {
+        self.set_at.get().map(|(utc_us, at)| {
+            let elapsed = (self.epoch)().saturating_sub(at);
+
+            utc_us.saturating_add(elapsed.as_micros() as u64)
+        })
+    }
+
+    /// The current UTC time in seconds, the resolution of certificate validity periods
+    pub fn now_secs(&self) -> Option<u64> {
+        self.now_us().map(|utc_us| utc_us / 1_000_000)
+    }
+}
+
 pub fn dummy_epoch() -> Duration {
     Duration::from_secs(0)
 }
@@ -19,3 +65,33 @@ pub fn sys_epoch() -> Duration {
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use core::time::Duration;
+
+    use super::UtcClock;
+
+    static NOW_MS: AtomicU64 = AtomicU64::new(100_000);
+
+    fn test_epoch() -> Duration {
+        Duration::from_millis(NOW_MS.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn test_utc_clock() {
+        let clock = UtcClock::new(test_epoch);
+        assert_eq!(clock.now_us(), None);
+
+        clock.set(5_000_000);
+        assert_eq!(clock.now_secs(), Some(5));
+
+        NOW_MS.fetch_add(2500, Ordering::Relaxed);
+        assert_eq!(clock.now_us(), Some(7_500_000));
+        assert_eq!(clock.now_secs(), Some(7));
+
+        clock.clear();
+        assert!(!clock.is_set());
+    }
+}
@@ -24,8 +24,8 @@ use rs_matter::{
     attribute_enum, command_enum,
     data_model::objects::{
         Access, AttrData, AttrDataEncoder, AttrDataWriter, AttrDetails, AttrType, Attribute,
-        Cluster, CmdDataEncoder, CmdDataWriter, CmdDetails, Dataver, Handler, NonBlockingHandler,
-        Quality, ATTRIBUTE_LIST, FEATURE_MAP,
+        Cluster, ClusterId, CmdDataEncoder, CmdDataWriter, CmdDetails, Dataver, EndptId, Handler,
+        NonBlockingHandler, Quality, ATTRIBUTE_LIST, FEATURE_MAP,
     },
     error::{Error, ErrorCode},
     interaction_model::messages::ib::{attr_list_write, ListOperation},
@@ -282,6 +282,10 @@ impl Handler for EchoCluster {
     ) -> Result<(), Error> {
         EchoCluster::invoke(self, exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, _endpoint: EndptId, _cluster: ClusterId) -> Option<u32> {
+        Some(self.data_ver.get())
+    }
 }
 
 impl NonBlockingHandler for EchoCluster {}
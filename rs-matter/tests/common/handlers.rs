@@ -4,7 +4,7 @@ use rs_matter::{
     interaction_model::{
         core::{IMStatusCode, OpCode},
         messages::{
-            ib::{AttrData, AttrPath, AttrResp, AttrStatus, CmdData, DataVersionFilter},
+            ib::{AttrData, AttrPath, AttrResp, AttrStatus, CmdData, DataVersionFilter, EventPath},
             msg::{
                 self, InvReq, ReadReq, ReportDataMsg, StatusResp, TimedReq, WriteReq, WriteResp,
                 WriteRespTag,
@@ -17,7 +17,7 @@ use rs_matter::{
 use super::{
     attributes::assert_attr_report,
     commands::{assert_inv_response, ExpectedInvResp},
-    im_engine::{ImEngine, ImEngineHandler, ImInput, ImOutput},
+    im_engine::{ImEngine, ImHandler, ImInput, ImOutput},
 };
 
 pub enum WriteResponse<'a> {
@@ -41,7 +41,7 @@ impl<'a> ImEngine<'a> {
     // Helper for handling Read Req sequences for this file
     pub fn handle_read_reqs(
         &self,
-        handler: &ImEngineHandler,
+        handler: &impl ImHandler,
         input: &[AttrPath],
         expected: &[AttrResp],
     ) {
@@ -52,7 +52,7 @@ impl<'a> ImEngine<'a> {
 
     pub fn gen_read_reqs_output<'c, const N: usize>(
         &self,
-        handler: &ImEngineHandler,
+        handler: &impl ImHandler,
         input: &[AttrPath],
         dataver_filters: Option<TLVArray<'_, DataVersionFilter>>,
         out: &'c mut heapless::Vec<ImOutput, N>,
@@ -72,6 +72,23 @@ impl<'a> ImEngine<'a> {
         ReportDataMsg::from_tlv(&root).unwrap()
     }
 
+    /// Read the events matching `input`
+    pub fn gen_read_event_reqs_output<'c, const N: usize>(
+        &self,
+        handler: &impl ImHandler,
+        input: &[EventPath],
+        out: &'c mut heapless::Vec<ImOutput, N>,
+    ) -> ReportDataMsg<'c> {
+        let read_req = ReadReq::new(true).set_event_requests(input);
+
+        let input = ImInput::new(OpCode::ReadRequest, &read_req);
+
+        self.process(handler, &[&input], out).unwrap();
+
+        let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+        ReportDataMsg::from_tlv(&root).unwrap()
+    }
+
     pub fn write_reqs(input: &[AttrData], expected: &[AttrStatus]) {
         let im = ImEngine::new_default();
 
@@ -81,7 +98,7 @@ impl<'a> ImEngine<'a> {
 
     pub fn handle_write_reqs(
         &self,
-        handler: &ImEngineHandler,
+        handler: &impl ImHandler,
         input: &[AttrData],
         expected: &[AttrStatus],
     ) {
@@ -126,7 +143,7 @@ impl<'a> ImEngine<'a> {
     // Helper for handling Invoke Command sequences
     pub fn handle_commands(
         &self,
-        handler: &ImEngineHandler,
+        handler: &impl ImHandler,
         input: &[CmdData],
         expected: &[ExpectedInvResp],
     ) {
@@ -152,7 +169,7 @@ impl<'a> ImEngine<'a> {
 
     fn gen_timed_reqs_output<const N: usize>(
         &self,
-        handler: &ImEngineHandler,
+        handler: &impl ImHandler,
         opcode: OpCode,
         request: &dyn ToTLV,
         timeout: u16,
@@ -199,7 +216,7 @@ impl<'a> ImEngine<'a> {
     // Helper for handling Write Attribute sequences
     pub fn handle_timed_write_reqs(
         &self,
-        handler: &ImEngineHandler,
+        handler: &impl ImHandler,
         input: &[AttrData],
         expected: &WriteResponse,
         timeout: u16,
@@ -257,7 +274,7 @@ impl<'a> ImEngine<'a> {
     // Helper for handling Invoke Command sequences
     pub fn handle_timed_commands(
         &self,
-        handler: &ImEngineHandler,
+        handler: &impl ImHandler,
         input: &[CmdData],
         expected: &TimedInvResponse,
         timeout: u16,
@@ -17,13 +17,16 @@
 
 use crate::common::echo_cluster;
 use core::borrow::Borrow;
-use core::future::pending;
+use core::future::{pending, Future};
 use core::time::Duration;
 use embassy_futures::select::select3;
 use rs_matter::{
     acl::{AclEntry, AuthMode},
     data_model::{
-        cluster_basic_information::{self, BasicInfoConfig},
+        cluster_basic_information::{
+            self, BasicInfoConfig, ProductAppearance, ProductColor, ProductFinish,
+        },
+        cluster_identify::{self, IdentifyCluster, IdentifyType},
         cluster_on_off::{self, OnOffCluster},
         device_types::{DEV_TYPE_ON_OFF_LIGHT, DEV_TYPE_ROOT_NODE},
         objects::{
@@ -34,7 +37,8 @@ use rs_matter::{
         sdm::{
             admin_commissioning,
             dev_att::{DataType, DevAttDataFetcher},
-            general_commissioning, noc, nw_commissioning,
+            general_commissioning::{self, RegLocationType},
+            noc, nw_commissioning,
         },
         system_model::{
             access_control,
@@ -72,6 +76,17 @@ const BASIC_INFO: BasicInfoConfig<'static> = BasicInfoConfig {
     device_name: "Test Device",
     product_name: "TestProd",
     vendor_name: "TestVendor",
+    unique_id: "TestUniqueId",
+    manufacturing_date: "20230101",
+    part_number: "Part",
+    product_url: "",
+    product_label: "",
+    product_appearance: ProductAppearance {
+        finish: ProductFinish::Matte,
+        primary_color: Some(ProductColor::White),
+    },
+    icd: None,
+    location_capability: RegLocationType::IndoorOutdoor,
 };
 
 struct DummyDevAtt;
@@ -100,16 +115,21 @@ const NODE: Node<'static> = Node {
                 access_control::CLUSTER,
                 echo_cluster::CLUSTER,
             ],
+            client_clusters: &[],
             device_type: DEV_TYPE_ROOT_NODE,
+            extra_device_types: &[],
         },
         Endpoint {
             id: 1,
             clusters: &[
                 descriptor::CLUSTER,
+                cluster_identify::CLUSTER,
                 cluster_on_off::CLUSTER,
                 echo_cluster::CLUSTER,
             ],
+            client_clusters: &[],
             device_type: DEV_TYPE_ON_OFF_LIGHT,
+            extra_device_types: &[],
         },
     ],
 };
@@ -140,7 +160,7 @@ pub struct ImOutput {
 }
 
 pub struct ImEngineHandler<'a> {
-    handler: handler_chain_type!(OnOffCluster, EchoCluster, DescriptorCluster<'static>, EchoCluster | RootEndpointHandler<'a>),
+    handler: handler_chain_type!(IdentifyCluster<'a>, OnOffCluster<'a>, EchoCluster, DescriptorCluster<'static>, EchoCluster | RootEndpointHandler<'a>),
 }
 
 impl<'a> ImEngineHandler<'a> {
@@ -149,18 +169,27 @@ impl<'a> ImEngineHandler<'a> {
             .chain(0, echo_cluster::ID, EchoCluster::new(2, *matter.borrow()))
             .chain(1, descriptor::ID, DescriptorCluster::new(*matter.borrow()))
             .chain(1, echo_cluster::ID, EchoCluster::new(3, *matter.borrow()))
-            .chain(1, cluster_on_off::ID, OnOffCluster::new(*matter.borrow()));
+            .chain(1, cluster_on_off::ID, OnOffCluster::new(*matter.borrow()))
+            .chain(
+                1,
+                cluster_identify::ID,
+                IdentifyCluster::new(IdentifyType::LightOutput, *matter.borrow()),
+            );
 
         Self { handler }
     }
 
     pub fn echo_cluster(&self, endpoint: u16) -> &EchoCluster {
         match endpoint {
-            0 => &self.handler.next.next.next.handler,
-            1 => &self.handler.next.handler,
+            0 => &self.handler.next.next.next.next.handler,
+            1 => &self.handler.next.next.handler,
             _ => panic!(),
         }
     }
+
+    pub fn identify(&self) -> &IdentifyCluster<'a> {
+        &self.handler.handler
+    }
 }
 
 impl<'a> Handler for ImEngineHandler<'a> {
@@ -181,6 +210,10 @@ impl<'a> Handler for ImEngineHandler<'a> {
     ) -> Result<(), Error> {
         self.handler.invoke(exchange, cmd, data, encoder)
     }
+
+    fn dataver(&self, endpoint: u16, cluster: u32) -> Option<u32> {
+        self.handler.dataver(endpoint, cluster)
+    }
 }
 
 impl<'a> NonBlockingHandler for ImEngineHandler<'a> {}
@@ -193,6 +226,14 @@ impl<'a> Metadata for ImEngineHandler<'a> {
     }
 }
 
+/// A data model handler the `ImEngine` can process requests with
+///
+/// Implemented by `ImEngineHandler`, and by the handlers of the tests which need
+/// a data model other than the default one.
+pub trait ImHandler: NonBlockingHandler + Metadata {}
+
+impl<T> ImHandler for T where T: NonBlockingHandler + Metadata {}
+
 static mut DNS: DummyMdns = DummyMdns;
 
 /// An Interaction Model Engine to facilitate easy testing
@@ -245,27 +286,11 @@ impl<'a> ImEngine<'a> {
 
     pub fn process<const N: usize>(
         &self,
-        handler: &ImEngineHandler,
+        handler: &impl ImHandler,
         input: &[&ImInput],
         out: &mut heapless::Vec<ImOutput, N>,
     ) -> Result<(), Error> {
-        self.matter.reset_transport();
-
-        let clone_data = CloneData::new(
-            IM_ENGINE_REMOTE_PEER_ID,
-            IM_ENGINE_PEER_ID,
-            1,
-            1,
-            Address::default(),
-            SessionMode::Case(CaseDetails::new(1, &self.cat_ids)),
-        );
-
-        let sess_idx = self
-            .matter
-            .session_mgr
-            .borrow_mut()
-            .clone_session(&clone_data)
-            .unwrap();
+        let mut msg_ctr = self.clone_session();
 
         let mut tx_pipe_buf = [0; MAX_RX_BUF_SIZE];
         let mut rx_pipe_buf = [0; MAX_TX_BUF_SIZE];
@@ -283,14 +308,6 @@ impl<'a> ImEngine<'a> {
 
         let handler = &handler;
 
-        let mut msg_ctr = self
-            .matter
-            .session_mgr
-            .borrow_mut()
-            .mut_by_index(sess_idx)
-            .unwrap()
-            .get_msg_ctr();
-
         let resp_notif = Notification::new();
         let resp_notif = &resp_notif;
 
@@ -367,6 +384,129 @@ impl<'a> ImEngine<'a> {
         Ok(())
     }
 
+    /// Run `client`, which initiates exchanges with the peer, with `peer` answering its
+    /// messages in place of the peer node
+    ///
+    /// `peer` fills in the response to a message and returns `true`, or returns `false`
+    /// for a message which is only to be acknowledged.
+    pub fn run_peer<F, P>(&self, handler: &impl ImHandler, client: F, peer: P) -> Result<(), Error>
+    where
+        F: Future<Output = Result<(), Error>>,
+        P: FnMut(&Packet<'_>, &mut Packet<'_>) -> Result<bool, Error>,
+    {
+        let msg_ctr = self.clone_session();
+
+        let mut tx_pipe_buf = [0; MAX_RX_BUF_SIZE];
+        let mut rx_pipe_buf = [0; MAX_TX_BUF_SIZE];
+
+        let mut tx_buf = [0; MAX_RX_BUF_SIZE];
+        let mut rx_buf = [0; MAX_TX_BUF_SIZE];
+
+        let tx_pipe = Pipe::new(&mut tx_buf);
+        let rx_pipe = Pipe::new(&mut rx_buf);
+
+        let handler = &handler;
+
+        let mut buffers = PacketBuffers::new();
+
+        embassy_futures::block_on(select3(
+            self.matter.run_piped(
+                &mut buffers,
+                &tx_pipe,
+                &rx_pipe,
+                CommissioningData {
+                    // TODO: Hard-coded for now
+                    verifier: VerifierData::new_with_pw(123456, *self.matter.borrow()),
+                    discriminator: 250,
+                },
+                &HandlerCompat(handler),
+            ),
+            client,
+            Self::answer(
+                &tx_pipe,
+                &rx_pipe,
+                &mut tx_pipe_buf,
+                &mut rx_pipe_buf,
+                msg_ctr,
+                peer,
+            ),
+        ))
+        .unwrap()
+    }
+
+    // Answer the messages the Matter stack sends to the peer, until failing
+    async fn answer<P>(
+        tx_pipe: &Pipe<'_>,
+        rx_pipe: &Pipe<'_>,
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+        mut msg_ctr: u32,
+        mut peer: P,
+    ) -> Result<(), Error>
+    where
+        P: FnMut(&Packet<'_>, &mut Packet<'_>) -> Result<bool, Error>,
+    {
+        loop {
+            let (len, _) = tx_pipe.recv(rx_buf).await;
+
+            let mut rx = Packet::new_rx(&mut rx_buf[..len]);
+
+            rx.plain_hdr_decode()?;
+            rx.proto_decode(IM_ENGINE_REMOTE_PEER_ID, Some(&[0u8; 16]))?;
+
+            if !rx.proto.is_reliable() {
+                // A standalone ack, which needs no ack of its own
+                continue;
+            }
+
+            let mut tx = Packet::new_tx(tx_buf);
+
+            if !peer(&rx, &mut tx)? {
+                tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
+                tx.set_proto_opcode(secure_channel::common::OpCode::MRPStandAloneAck as u8);
+                tx.proto.unset_reliable();
+            }
+
+            msg_ctr += 1;
+
+            tx.plain.ctr = msg_ctr;
+            tx.plain.sess_id = 1;
+            tx.proto.exch_id = rx.proto.exch_id;
+            tx.proto.set_ack(rx.plain.ctr);
+
+            tx.proto_encode(
+                Address::default(),
+                Some(IM_ENGINE_REMOTE_PEER_ID),
+                IM_ENGINE_PEER_ID,
+                false,
+                Some(&[0u8; 16]),
+            )?;
+
+            rx_pipe.send(Address::default(), tx.as_slice()).await;
+        }
+    }
+
+    // Reset the transport and set up the CASE session with the peer, returning the
+    // message counter of the session
+    fn clone_session(&self) -> u32 {
+        self.matter.reset_transport();
+
+        let clone_data = CloneData::new(
+            IM_ENGINE_REMOTE_PEER_ID,
+            IM_ENGINE_PEER_ID,
+            1,
+            1,
+            Address::default(),
+            SessionMode::Case(CaseDetails::new(1, &self.cat_ids)),
+        );
+
+        let mut session_mgr = self.matter.session_mgr.borrow_mut();
+
+        let sess_idx = session_mgr.clone_session(&clone_data).unwrap();
+
+        session_mgr.mut_by_index(sess_idx).unwrap().get_msg_ctr()
+    }
+
     async fn send(
         input: &ImInput<'_>,
         tx_buf: &mut [u8],
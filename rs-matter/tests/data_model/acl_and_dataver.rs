@@ -552,6 +552,71 @@ fn test_read_data_ver() {
     assert_attr_report(&received, expected_error);
 }
 
+#[test]
+/// Data Version filtering should skip a whole cluster whose data version is current,
+/// while a stale data version should not filter anything
+fn test_read_data_ver_cluster() {
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    // Add ACL to allow our peer with only OPERATE permission
+    let acl = AclEntry::new(1, Privilege::OPERATE, AuthMode::Case);
+    im.matter.acl_mgr.borrow_mut().add(acl).unwrap();
+
+    let data_ver = handler.echo_cluster(0).data_ver.get();
+
+    // Test 1: Wildcard attribute read of a cluster with a current data version
+    let ep0_wc_att = GenericPath::new(Some(0), Some(echo_cluster::ID), None);
+    let input = &[AttrPath::new(&ep0_wc_att)];
+    let dataver_filter = [DataVersionFilter {
+        path: ClusterPath {
+            node: None,
+            endpoint: 0,
+            cluster: echo_cluster::ID,
+        },
+        data_ver,
+    }];
+
+    let mut out = heapless::Vec::new();
+    let received = im.gen_read_reqs_output::<1>(
+        &handler,
+        input,
+        Some(TLVArray::Slice(&dataver_filter)),
+        &mut out,
+    );
+    assert_attr_report(&received, &[]);
+
+    // Test 2: Exact read with a stale data version
+    let ep0_att1 = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::Att1 as u32),
+    );
+    let input = &[AttrPath::new(&ep0_att1)];
+    let stale_filter = [DataVersionFilter {
+        path: ClusterPath {
+            node: None,
+            endpoint: 0,
+            cluster: echo_cluster::ID,
+        },
+        data_ver: data_ver.wrapping_sub(1),
+    }];
+
+    let mut out = heapless::Vec::new();
+    let received = im.gen_read_reqs_output::<1>(
+        &handler,
+        input,
+        Some(TLVArray::Slice(&stale_filter)),
+        &mut out,
+    );
+    assert_attr_report(
+        &received,
+        &[attr_data_path!(ep0_att1, ElementType::U16(0x1234))],
+    );
+}
+
 #[test]
 /// - Write with the correct data version should go through
 /// - Write with incorrect data version should fail with error
@@ -0,0 +1,123 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{borrow::Borrow, cell::RefCell};
+
+use rs_matter::{
+    data_model::{
+        objects::EncodeValue,
+        sdm::{
+            admin_commissioning::{self, Commands, StatusCode},
+            failsafe::FailSafe,
+        },
+    },
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{CmdData, CmdPath, CmdStatus},
+    },
+    tlv::{TLVWriter, TagType},
+    transport::session::SessionMode,
+    utils::epoch::sys_epoch,
+};
+
+use crate::common::{
+    commands::*, handlers::TimedInvResponse, im_engine::ImEngine, init_env_logger,
+};
+
+fn invoke(im: &ImEngine, cmd: Commands, data: EncodeValue, status: Option<StatusCode>) {
+    let path = CmdPath::new(Some(0), Some(admin_commissioning::ID), Some(cmd as u32));
+
+    let input = &[CmdData::new(path.clone(), data)];
+    let expected = &[ExpectedInvResp::Status(match status {
+        Some(status) => CmdStatus::new(path, IMStatusCode::Failure, status as _),
+        None => CmdStatus::new(path, IMStatusCode::Success, 0),
+    })];
+
+    im.handle_timed_commands(
+        &im.handler(),
+        input,
+        &TimedInvResponse::TransactionSuccess(expected),
+        2000,
+        0,
+        true,
+    );
+}
+
+#[test]
+fn test_admin_comm_statuses() {
+    // The commands fail with the cluster-specific statuses
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let empty = |tag, t: &mut TLVWriter| {
+        let _ = t.start_struct(tag);
+        let _ = t.end_container();
+    };
+    let open_basic = |tag, t: &mut TLVWriter| {
+        let _ = t.start_struct(tag);
+        let _ = t.u16(TagType::Context(0), 180);
+        let _ = t.end_container();
+    };
+    let open_short_verifier = |tag, t: &mut TLVWriter| {
+        let _ = t.start_struct(tag);
+        let _ = t.u16(TagType::Context(0), 180);
+        let _ = t.str8(TagType::Context(1), &[0; 10]);
+        let _ = t.u16(TagType::Context(2), 250);
+        let _ = t.u32(TagType::Context(3), 1000);
+        let _ = t.str8(TagType::Context(4), &[0; 16]);
+        let _ = t.end_container();
+    };
+
+    invoke(
+        &im,
+        Commands::RevokeComm,
+        EncodeValue::Closure(&empty),
+        Some(StatusCode::WindowNotOpen),
+    );
+
+    invoke(
+        &im,
+        Commands::OpenCommWindow,
+        EncodeValue::Closure(&open_short_verifier),
+        Some(StatusCode::PAKEParameterError),
+    );
+
+    // A commissioning is in progress
+    let failsafe: &RefCell<FailSafe> = im.matter.borrow();
+    failsafe
+        .borrow_mut()
+        .arm(60, SessionMode::Pase, sys_epoch())
+        .unwrap();
+
+    invoke(
+        &im,
+        Commands::OpenBasicCommWindow,
+        EncodeValue::Closure(&open_basic),
+        Some(StatusCode::Busy),
+    );
+
+    // Revoking aborts the commissioning, even without a window
+    invoke(
+        &im,
+        Commands::RevokeComm,
+        EncodeValue::Closure(&empty),
+        Some(StatusCode::WindowNotOpen),
+    );
+    assert!(!failsafe.borrow().is_armed());
+}
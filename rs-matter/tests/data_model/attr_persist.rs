@@ -0,0 +1,91 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use rs_matter::{
+    data_model::{cluster_basic_information, objects::EncodeValue, persist::AttrPersist},
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrData, AttrPath, AttrResp, AttrStatus},
+        messages::GenericPath,
+    },
+    tlv::{ElementType, TLVElement, TagType, UtfStr},
+};
+
+use crate::{
+    attr_data_path,
+    common::{attributes::*, im_engine::ImEngine, init_env_logger},
+};
+
+#[test]
+fn test_attr_persist_store_load() {
+    // Store the persistent attributes after a write, then restore them
+    // into a freshly created data model
+    init_env_logger();
+
+    let ep0_node_label = GenericPath::new(
+        Some(0),
+        Some(cluster_basic_information::ID),
+        Some(cluster_basic_information::AttributesDiscriminants::NodeLabel as u32),
+    );
+
+    let mut data = [0; 4096];
+    let len = {
+        let im = ImEngine::new_default();
+        let handler = im.handler();
+
+        im.add_default_acl();
+
+        let input = &[AttrData::new(
+            None,
+            AttrPath::new(&ep0_node_label),
+            EncodeValue::Value(&UtfStr::new(b"Kitchen")),
+        )];
+        let expected = &[AttrStatus::new(&ep0_node_label, IMStatusCode::Success, 0)];
+        im.handle_write_reqs(&handler, input, expected);
+
+        let mut attrs = AttrPersist::new();
+        let mut buf = [0; 4096];
+
+        let stored = embassy_futures::block_on(attrs.store(&handler, &mut buf))
+            .unwrap()
+            .unwrap();
+        data[..stored.len()].copy_from_slice(stored);
+        let len = stored.len();
+
+        // Nothing changed since the last store
+        assert!(embassy_futures::block_on(attrs.store(&handler, &mut buf))
+            .unwrap()
+            .is_none());
+
+        len
+    };
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+
+    let mut attrs = AttrPersist::new();
+    embassy_futures::block_on(attrs.load(&handler, &data[..len])).unwrap();
+
+    let input = &[AttrPath::new(&ep0_node_label)];
+    let expected = &[attr_data_path!(
+        ep0_node_label,
+        ElementType::Utf16l(b"Kitchen")
+    )];
+    im.handle_read_reqs(&handler, input, expected);
+}
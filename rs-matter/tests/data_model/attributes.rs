@@ -19,6 +19,7 @@ use rs_matter::{
     data_model::{
         cluster_on_off,
         objects::{EncodeValue, GlobalElements},
+        sdm::general_commissioning as gen_comm,
     },
     interaction_model::{
         core::IMStatusCode,
@@ -478,3 +479,35 @@ fn test_write_unsupported_fields() {
         handler.echo_cluster(0).att_write.get()
     );
 }
+
+#[test]
+fn test_write_breadcrumb() {
+    // The breadcrumb is kept by the fail-safe and read back from it
+    init_env_logger();
+    let attr_data = |tag, t: &mut TLVWriter| {
+        let _ = t.u64(tag, 0x1234);
+    };
+
+    let breadcrumb = GenericPath::new(
+        Some(0),
+        Some(gen_comm::ID),
+        Some(gen_comm::AttributesDiscriminants::BreadCrumb as u32),
+    );
+
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&breadcrumb),
+        EncodeValue::Closure(&attr_data),
+    )];
+    let expected = &[AttrStatus::new(&breadcrumb, IMStatusCode::Success, 0)];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+    im.handle_write_reqs(&handler, input, expected);
+
+    let input = &[AttrPath::new(&breadcrumb)];
+    let expected = &[attr_data_path!(breadcrumb, ElementType::U16(0x1234))];
+    im.handle_read_reqs(&handler, input, expected);
+}
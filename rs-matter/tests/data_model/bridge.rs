@@ -0,0 +1,332 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+
+use rs_matter::{
+    data_model::{
+        aggregator,
+        cluster_bridged_device_basic_information::{
+            self as bridged_info, BridgedDeviceBasicInfoCluster, BridgedDeviceBasicInfoConfig,
+        },
+        cluster_on_off::{self, OnOffCluster},
+        device_types::{DEV_TYPE_BRIDGED_NODE, DEV_TYPE_ON_OFF_LIGHT},
+        dynamic::DynamicHandler,
+        objects::{EncodeValue, Endpoint, Handler, HandlerCompat},
+        root_endpoint,
+        system_model::descriptor,
+    },
+    error::ErrorCode,
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrData, AttrPath, AttrResp, AttrStatus, EventPath},
+        messages::GenericPath,
+    },
+    tlv::{ElementType, TLVElement, TagType, UtfStr},
+};
+
+use crate::{
+    attr_data_path, attr_status,
+    common::{attributes::*, im_engine::ImEngine, init_env_logger},
+};
+
+const AGGREGATOR: u16 = 1;
+
+const LIGHT: Endpoint<'static> = Endpoint {
+    id: 2,
+    device_type: DEV_TYPE_ON_OFF_LIGHT,
+    extra_device_types: &[DEV_TYPE_BRIDGED_NODE],
+    clusters: &[
+        descriptor::CLUSTER,
+        bridged_info::CLUSTER,
+        cluster_on_off::CLUSTER,
+    ],
+    client_clusters: &[],
+};
+
+const LIGHT_CFG: BridgedDeviceBasicInfoConfig<'static> = BridgedDeviceBasicInfoConfig {
+    vendor_name: "Vendor",
+    unique_id: "light-1",
+};
+
+fn attr(endpoint: u16, cluster: u32, attr: u32) -> GenericPath {
+    GenericPath::new(Some(endpoint), Some(cluster), Some(attr))
+}
+
+#[test]
+fn test_bridge_topology() {
+    // The Aggregator lists the bridged light, which has the Bridged Node device type
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let rand = *im.matter.borrow();
+
+    let info = BridgedDeviceBasicInfoCluster::new(LIGHT.id, &LIGHT_CFG, im.matter.borrow(), rand);
+    let on_off = OnOffCluster::new(rand);
+
+    let handler = DynamicHandler::<_, 3, 2>::new(
+        0,
+        HandlerCompat(root_endpoint::handler(0, &im.matter)),
+        rand,
+    );
+
+    handler
+        .add_endpoint(root_endpoint::endpoint(0), None)
+        .unwrap();
+    handler
+        .add_endpoint(aggregator::endpoint(AGGREGATOR), None)
+        .unwrap();
+    aggregator::add_bridged(&handler, AGGREGATOR, LIGHT, &info).unwrap();
+    handler.add_handler(2, cluster_on_off::ID, &on_off).unwrap();
+
+    im.add_default_acl();
+
+    let root_parts = attr(0, descriptor::ID, descriptor::Attributes::PartsList as u32);
+    let aggregator_parts = attr(
+        AGGREGATOR,
+        descriptor::ID,
+        descriptor::Attributes::PartsList as u32,
+    );
+    let light_parts = attr(2, descriptor::ID, descriptor::Attributes::PartsList as u32);
+    let light_dev_types = attr(
+        2,
+        descriptor::ID,
+        descriptor::Attributes::DeviceTypeList as u32,
+    );
+
+    let root_parts_list = TLVHolder::new_array(2, &[AGGREGATOR, 2]);
+    let root_parts_list_tlv = root_parts_list.to_tlv();
+    let aggregator_parts_list = TLVHolder::new_array(2, &[2_u16]);
+    let aggregator_parts_list_tlv = aggregator_parts_list.to_tlv();
+    let light_parts_list = TLVHolder::new_array::<u16, _>(2, &[]);
+    let light_parts_list_tlv = light_parts_list.to_tlv();
+    let light_dev_types_list =
+        TLVHolder::new_array(2, &[DEV_TYPE_ON_OFF_LIGHT, DEV_TYPE_BRIDGED_NODE]);
+    let light_dev_types_list_tlv = light_dev_types_list.to_tlv();
+
+    let input = &[
+        AttrPath::new(&root_parts),
+        AttrPath::new(&aggregator_parts),
+        AttrPath::new(&light_parts),
+        AttrPath::new(&light_dev_types),
+    ];
+    let expected = &[
+        attr_data_path!(root_parts, root_parts_list_tlv.get_element_type().clone()),
+        attr_data_path!(
+            aggregator_parts,
+            aggregator_parts_list_tlv.get_element_type().clone()
+        ),
+        attr_data_path!(light_parts, light_parts_list_tlv.get_element_type().clone()),
+        attr_data_path!(
+            light_dev_types,
+            light_dev_types_list_tlv.get_element_type().clone()
+        ),
+    ];
+    im.handle_read_reqs(&handler, input, expected);
+
+    // Removing the bridged light removes it from the Aggregator as well
+    handler.remove_endpoint(2).unwrap();
+
+    let aggregator_parts_list = TLVHolder::new_array::<u16, _>(2, &[]);
+    let aggregator_parts_list_tlv = aggregator_parts_list.to_tlv();
+
+    let input = &[AttrPath::new(&aggregator_parts)];
+    let expected = &[attr_data_path!(
+        aggregator_parts,
+        aggregator_parts_list_tlv.get_element_type().clone()
+    )];
+    im.handle_read_reqs(&handler, input, expected);
+}
+
+#[test]
+fn test_bridge_add_bridged_rollback() {
+    // Without space for the handler of the Bridged Device Basic Information cluster,
+    // the bridged endpoint is not added either
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let rand = *im.matter.borrow();
+
+    let info = BridgedDeviceBasicInfoCluster::new(LIGHT.id, &LIGHT_CFG, im.matter.borrow(), rand);
+
+    let handler = DynamicHandler::<_, 3, 0>::new(
+        0,
+        HandlerCompat(root_endpoint::handler(0, &im.matter)),
+        rand,
+    );
+
+    handler
+        .add_endpoint(root_endpoint::endpoint(0), None)
+        .unwrap();
+    handler
+        .add_endpoint(aggregator::endpoint(AGGREGATOR), None)
+        .unwrap();
+
+    let err = aggregator::add_bridged(&handler, AGGREGATOR, LIGHT, &info).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::NoSpace);
+
+    // Neither can it be added to an endpoint which does not exist
+    let err = aggregator::add_bridged(&handler, 5, LIGHT, &info).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::EndpointNotFound);
+
+    im.add_default_acl();
+
+    let aggregator_parts = attr(
+        AGGREGATOR,
+        descriptor::ID,
+        descriptor::Attributes::PartsList as u32,
+    );
+    let light_reachable = attr(
+        2,
+        bridged_info::ID,
+        bridged_info::AttributesDiscriminants::Reachable as u32,
+    );
+
+    let aggregator_parts_list = TLVHolder::new_array::<u16, _>(2, &[]);
+    let aggregator_parts_list_tlv = aggregator_parts_list.to_tlv();
+
+    let input = &[
+        AttrPath::new(&aggregator_parts),
+        AttrPath::new(&light_reachable),
+    ];
+    let expected = &[
+        attr_data_path!(
+            aggregator_parts,
+            aggregator_parts_list_tlv.get_element_type().clone()
+        ),
+        attr_status!(&light_reachable, IMStatusCode::UnsupportedEndpoint),
+    ];
+    im.handle_read_reqs(&handler, input, expected);
+}
+
+#[test]
+fn test_bridged_basic_info() {
+    // Reachable follows the bridge, NodeLabel is writable and the rest is read-only
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let rand = *im.matter.borrow();
+
+    let info = BridgedDeviceBasicInfoCluster::new(LIGHT.id, &LIGHT_CFG, im.matter.borrow(), rand);
+
+    let handler = DynamicHandler::<_, 3, 1>::new(
+        0,
+        HandlerCompat(root_endpoint::handler(0, &im.matter)),
+        rand,
+    );
+
+    handler
+        .add_endpoint(root_endpoint::endpoint(0), None)
+        .unwrap();
+    handler
+        .add_endpoint(aggregator::endpoint(AGGREGATOR), None)
+        .unwrap();
+    aggregator::add_bridged(&handler, AGGREGATOR, LIGHT, &info).unwrap();
+
+    im.add_default_acl();
+
+    let vendor_name = attr(
+        2,
+        bridged_info::ID,
+        bridged_info::AttributesDiscriminants::VendorName as u32,
+    );
+    let unique_id = attr(
+        2,
+        bridged_info::ID,
+        bridged_info::AttributesDiscriminants::UniqueId as u32,
+    );
+    let reachable = attr(
+        2,
+        bridged_info::ID,
+        bridged_info::AttributesDiscriminants::Reachable as u32,
+    );
+    let node_label = attr(
+        2,
+        bridged_info::ID,
+        bridged_info::AttributesDiscriminants::NodeLabel as u32,
+    );
+
+    let input = &[
+        AttrPath::new(&vendor_name),
+        AttrPath::new(&unique_id),
+        AttrPath::new(&reachable),
+        AttrPath::new(&node_label),
+    ];
+    let expected = &[
+        attr_data_path!(vendor_name, ElementType::Utf16l(b"Vendor")),
+        attr_data_path!(unique_id, ElementType::Utf16l(b"light-1")),
+        attr_data_path!(reachable, ElementType::True),
+        attr_data_path!(node_label, ElementType::Utf16l(b"")),
+    ];
+    im.handle_read_reqs(&handler, input, expected);
+
+    // The bridge lost the connection to the light
+    let dataver = Handler::dataver(&info, 2, bridged_info::ID);
+    info.set_reachable(false).unwrap();
+    assert_ne!(Handler::dataver(&info, 2, bridged_info::ID), dataver);
+
+    // No change, no new data version and no event
+    let dataver = Handler::dataver(&info, 2, bridged_info::ID);
+    info.set_reachable(false).unwrap();
+    assert_eq!(Handler::dataver(&info, 2, bridged_info::ID), dataver);
+
+    let reachable_changed = EventPath {
+        node: None,
+        endpoint: Some(2),
+        cluster: Some(bridged_info::ID),
+        event: Some(bridged_info::Events::ReachableChanged as _),
+        is_urgent: None,
+    };
+    let mut out = heapless::Vec::<_, 1>::new();
+    let report = im.gen_read_event_reqs_output(&handler, &[reachable_changed], &mut out);
+
+    let mut events = report.event_reports.unwrap().iter();
+    let event = events.next().unwrap().data;
+    assert!(events.next().is_none());
+    assert_eq!(event.path.endpoint, Some(2));
+    assert_eq!(
+        event.path.event,
+        Some(bridged_info::Events::ReachableChanged as _)
+    );
+    // ReachableNewValue
+    assert!(!event.data.find_tag(0).unwrap().bool().unwrap());
+
+    let input = &[AttrPath::new(&reachable)];
+    let expected = &[attr_data_path!(reachable, ElementType::False)];
+    im.handle_read_reqs(&handler, input, expected);
+
+    let input = &[
+        AttrData::new(
+            None,
+            AttrPath::new(&node_label),
+            EncodeValue::Value(&UtfStr::new(b"Desk Lamp")),
+        ),
+        AttrData::new(None, AttrPath::new(&reachable), EncodeValue::Value(&true)),
+    ];
+    let expected = &[
+        AttrStatus::new(&node_label, IMStatusCode::Success, 0),
+        AttrStatus::new(&reachable, IMStatusCode::UnsupportedWrite, 0),
+    ];
+    im.handle_write_reqs(&handler, input, expected);
+
+    let input = &[AttrPath::new(&node_label), AttrPath::new(&reachable)];
+    let expected = &[
+        attr_data_path!(node_label, ElementType::Utf16l(b"Desk Lamp")),
+        attr_data_path!(reachable, ElementType::False),
+    ];
+    im.handle_read_reqs(&handler, input, expected);
+}
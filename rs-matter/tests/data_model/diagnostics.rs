@@ -0,0 +1,716 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+use core::cell::Cell;
+
+use rs_matter::{
+    data_model::{
+        device_types::DEV_TYPE_ROOT_NODE,
+        objects::{EmptyHandler, EncodeValue, Endpoint, Handler, Node},
+        sdm::{
+            ethernet_nw_diagnostics::{self, EthNwDiagCluster, EthNwDiagnostics},
+            general_diagnostics::{
+                self, DiagnosticsPlatform, GenDiagCluster, InterfaceType, NetworkInterface,
+                TestEventTriggerHandler, TestEventTriggers, TEST_ENABLE_KEY_LEN,
+            },
+            sw_diagnostics::{self, SwDiagCluster, SwDiagnostics},
+            thread_nw_diagnostics::{self, ThreadNwDiagCluster, ThreadNwDiagnostics, ThreadNwInfo},
+            wifi_nw_diagnostics::{
+                self, SecurityType, WiFiNwDiagCluster, WiFiNwDiagnostics, WiFiVersion,
+            },
+        },
+        system_model::descriptor::{self, DescriptorCluster},
+    },
+    error::Error,
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrData, AttrPath, AttrResp, ClusterPath, CmdData, CmdPath, CmdStatus},
+        messages::{ib::DataVersionFilter, GenericPath},
+    },
+    tlv::{ElementType, TLVArray, TLVElement, TLVWriter, TagType},
+    utils::{epoch::Epoch, rand::Rand},
+};
+
+use crate::{
+    attr_data_path,
+    common::{
+        attributes::*,
+        commands::*,
+        im_engine::{ImEngine, ImHandler},
+        init_env_logger,
+    },
+};
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[Endpoint {
+        id: 0,
+        device_type: DEV_TYPE_ROOT_NODE,
+        extra_device_types: &[],
+        clusters: &[
+            descriptor::CLUSTER,
+            general_diagnostics::CLUSTER,
+            ethernet_nw_diagnostics::CLUSTER,
+            wifi_nw_diagnostics::CLUSTER,
+            thread_nw_diagnostics::CLUSTER,
+            sw_diagnostics::CLUSTER,
+        ],
+        client_clusters: &[],
+    }],
+};
+
+const ENABLE_KEY: [u8; TEST_ENABLE_KEY_LEN] = [1; TEST_ENABLE_KEY_LEN];
+
+const TRIGGER: u64 = 0x0102_0000_0000_0001;
+
+#[derive(Default)]
+struct FakeDiag;
+
+impl DiagnosticsPlatform for FakeDiag {
+    fn network_interfaces(
+        &self,
+        f: &mut dyn FnMut(&NetworkInterface) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        f(&eth0())
+    }
+
+    fn reboot_count(&self) -> u16 {
+        3
+    }
+
+    fn uptime(&self) -> Option<u64> {
+        Some(42)
+    }
+}
+
+fn eth0() -> NetworkInterface<'static> {
+    NetworkInterface {
+        name: "eth0",
+        is_operational: true,
+        hw_address: &[1, 2, 3, 4, 5, 6],
+        ipv4_addresses: &[[192, 168, 1, 2]],
+        ipv6_addresses: &[],
+        interface_type: InterfaceType::Ethernet,
+    }
+}
+
+/// Handles `TRIGGER` only
+#[derive(Default)]
+struct FakeTriggerHandler {
+    triggered: Cell<bool>,
+}
+
+impl TestEventTriggerHandler for FakeTriggerHandler {
+    fn handle(&self, trigger: u64) -> Result<bool, Error> {
+        let handled = trigger == TRIGGER;
+        if handled {
+            self.triggered.set(true);
+        }
+
+        Ok(handled)
+    }
+}
+
+#[derive(Default)]
+struct FakeEth {
+    rx: Cell<u64>,
+    tx: Cell<u64>,
+}
+
+impl EthNwDiagnostics for FakeEth {
+    fn packet_rx_count(&self) -> u64 {
+        self.rx.get()
+    }
+
+    fn packet_tx_count(&self) -> u64 {
+        self.tx.get()
+    }
+
+    fn reset_counts(&self) {
+        self.rx.set(0);
+        self.tx.set(0);
+    }
+}
+
+#[derive(Default)]
+struct FakeWiFi {
+    connected: Cell<bool>,
+    beacon_lost: Cell<u32>,
+}
+
+impl WiFiNwDiagnostics for FakeWiFi {
+    fn bssid(&self) -> Option<[u8; 6]> {
+        self.connected.get().then_some([1, 2, 3, 4, 5, 6])
+    }
+
+    fn security_type(&self) -> Option<SecurityType> {
+        self.connected.get().then_some(SecurityType::Wpa3)
+    }
+
+    fn wifi_version(&self) -> Option<WiFiVersion> {
+        None
+    }
+
+    fn channel_number(&self) -> Option<u16> {
+        self.connected.get().then_some(6)
+    }
+
+    fn rssi(&self) -> Option<i8> {
+        self.connected.get().then_some(-60)
+    }
+
+    fn beacon_lost_count(&self) -> Option<u32> {
+        Some(self.beacon_lost.get())
+    }
+
+    fn reset_counts(&self) {
+        self.beacon_lost.set(0);
+    }
+}
+
+/// A Thread interface which is not attached to any network
+#[derive(Default)]
+struct FakeThread {
+    overrun: Cell<u64>,
+}
+
+impl ThreadNwDiagnostics for FakeThread {
+    fn network_info(
+        &self,
+        f: &mut dyn FnMut(Option<&ThreadNwInfo>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        f(None)
+    }
+
+    fn overrun_count(&self) -> u64 {
+        self.overrun.get()
+    }
+
+    fn reset_counts(&self) {
+        self.overrun.set(0);
+    }
+}
+
+#[derive(Default)]
+struct FakeSw {
+    used: Cell<u64>,
+    high: Cell<u64>,
+}
+
+impl SwDiagnostics for FakeSw {
+    fn current_heap_used(&self) -> u64 {
+        self.used.get()
+    }
+
+    fn current_heap_high_watermark(&self) -> u64 {
+        self.high.get()
+    }
+
+    fn reset_watermarks(&self) {
+        self.high.set(self.used.get());
+    }
+}
+
+#[derive(Default)]
+struct Platforms {
+    diag: FakeDiag,
+    eth: FakeEth,
+    wifi: FakeWiFi,
+    thread: FakeThread,
+    sw: FakeSw,
+}
+
+fn handler<'a>(
+    im: &'a ImEngine,
+    platforms: &'a Platforms,
+    triggers: Option<&'a TestEventTriggers<'a>>,
+) -> impl ImHandler + 'a {
+    let epoch: Epoch = *im.matter.borrow();
+    let rand: Rand = *im.matter.borrow();
+
+    (
+        NODE,
+        EmptyHandler
+            .chain(0, descriptor::ID, DescriptorCluster::new(rand))
+            .chain(
+                0,
+                general_diagnostics::ID,
+                GenDiagCluster::new(&platforms.diag, triggers, epoch, rand),
+            )
+            .chain(
+                0,
+                ethernet_nw_diagnostics::ID,
+                EthNwDiagCluster::new(&platforms.eth, epoch, rand),
+            )
+            .chain(
+                0,
+                wifi_nw_diagnostics::ID,
+                WiFiNwDiagCluster::new(&platforms.wifi, rand),
+            )
+            .chain(
+                0,
+                thread_nw_diagnostics::ID,
+                ThreadNwDiagCluster::new(&platforms.thread, rand),
+            )
+            .chain(
+                0,
+                sw_diagnostics::ID,
+                SwDiagCluster::new(&platforms.sw, rand),
+            ),
+    )
+}
+
+fn attr(cluster: u32, attr: u16) -> GenericPath {
+    GenericPath::new(Some(0), Some(cluster), Some(attr as u32))
+}
+
+fn read(im: &ImEngine, handler: &impl ImHandler, attrs: &[(GenericPath, ElementType)]) {
+    let input = attrs
+        .iter()
+        .map(|(path, _)| AttrPath::new(path))
+        .collect::<heapless::Vec<_, 4>>();
+    let expected = attrs
+        .iter()
+        .map(|(path, data)| attr_data_path!(path, data.clone()))
+        .collect::<heapless::Vec<_, 4>>();
+
+    im.handle_read_reqs(handler, &input, &expected);
+}
+
+fn invoke(im: &ImEngine, handler: &impl ImHandler, cluster: u32, cmd: u32) {
+    let path = CmdPath::new(Some(0), Some(cluster), Some(cmd));
+
+    let empty = |tag, t: &mut TLVWriter| {
+        let _ = t.start_struct(tag);
+        let _ = t.end_container();
+    };
+
+    let input = &[CmdData::new(path.clone(), EncodeValue::Closure(&empty))];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        path,
+        IMStatusCode::Success,
+        0,
+    ))];
+    im.handle_commands(handler, input, expected);
+}
+
+fn test_event_trigger(
+    im: &ImEngine,
+    handler: &impl ImHandler,
+    enable_key: &[u8],
+    trigger: u64,
+    status: IMStatusCode,
+) {
+    let path = CmdPath::new(
+        Some(0),
+        Some(general_diagnostics::ID),
+        Some(general_diagnostics::CommandsDiscriminants::TestEventTrigger as u32),
+    );
+
+    let req = |tag, t: &mut TLVWriter| {
+        let _ = t.start_struct(tag);
+        let _ = t.str8(TagType::Context(0), enable_key);
+        let _ = t.u64(TagType::Context(1), trigger);
+        let _ = t.end_container();
+    };
+
+    let input = &[CmdData::new(path.clone(), EncodeValue::Closure(&req))];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(path, status, 0))];
+    im.handle_commands(handler, input, expected);
+}
+
+#[test]
+fn test_gen_diag_attributes() {
+    // The attributes are encoded from what the platform reports
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let platforms = Platforms::default();
+    let handler = handler(&im, &platforms, None);
+
+    im.add_default_acl();
+
+    let network_interfaces = attr(
+        general_diagnostics::ID,
+        general_diagnostics::AttributesDiscriminants::NetworkInterfaces as _,
+    );
+    let reboot_count = attr(
+        general_diagnostics::ID,
+        general_diagnostics::AttributesDiscriminants::RebootCount as _,
+    );
+    let uptime = attr(
+        general_diagnostics::ID,
+        general_diagnostics::AttributesDiscriminants::UpTime as _,
+    );
+    let triggers_enabled = attr(
+        general_diagnostics::ID,
+        general_diagnostics::AttributesDiscriminants::TestEventTriggersEnabled as _,
+    );
+
+    let interfaces = TLVHolder::new_array(2, &[eth0()]);
+    let interfaces_tlv = interfaces.to_tlv();
+
+    read(
+        &im,
+        &handler,
+        &[
+            (
+                network_interfaces,
+                interfaces_tlv.get_element_type().clone(),
+            ),
+            (reboot_count, ElementType::U8(3)),
+            (uptime, ElementType::U8(42)),
+            (triggers_enabled, ElementType::False),
+        ],
+    );
+}
+
+#[test]
+fn test_event_trigger_disabled() {
+    // An all-zeroes enable key disables the test event triggers
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let platforms = Platforms::default();
+
+    let trigger_handler = FakeTriggerHandler::default();
+    let trigger_handlers: [&dyn TestEventTriggerHandler; 1] = [&trigger_handler];
+    let triggers = TestEventTriggers::new([0; TEST_ENABLE_KEY_LEN], &trigger_handlers);
+
+    let handler = handler(&im, &platforms, Some(&triggers));
+
+    im.add_default_acl();
+
+    let triggers_enabled = attr(
+        general_diagnostics::ID,
+        general_diagnostics::AttributesDiscriminants::TestEventTriggersEnabled as _,
+    );
+    read(&im, &handler, &[(triggers_enabled, ElementType::False)]);
+
+    test_event_trigger(
+        &im,
+        &handler,
+        &[0; TEST_ENABLE_KEY_LEN],
+        TRIGGER,
+        IMStatusCode::ConstraintError,
+    );
+    assert!(!trigger_handler.triggered.get());
+}
+
+#[test]
+fn test_event_trigger_dispatch() {
+    // With the right enable key, the trigger goes to the handler which knows it
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let platforms = Platforms::default();
+
+    let trigger_handler = FakeTriggerHandler::default();
+    let trigger_handlers: [&dyn TestEventTriggerHandler; 1] = [&trigger_handler];
+    let triggers = TestEventTriggers::new(ENABLE_KEY, &trigger_handlers);
+
+    let handler = handler(&im, &platforms, Some(&triggers));
+
+    im.add_default_acl();
+
+    let triggers_enabled = attr(
+        general_diagnostics::ID,
+        general_diagnostics::AttributesDiscriminants::TestEventTriggersEnabled as _,
+    );
+    read(&im, &handler, &[(triggers_enabled, ElementType::True)]);
+
+    // A wrong key
+    test_event_trigger(
+        &im,
+        &handler,
+        &[2; TEST_ENABLE_KEY_LEN],
+        TRIGGER,
+        IMStatusCode::ConstraintError,
+    );
+    assert!(!trigger_handler.triggered.get());
+
+    // A trigger no handler knows
+    test_event_trigger(
+        &im,
+        &handler,
+        &ENABLE_KEY,
+        TRIGGER + 1,
+        IMStatusCode::InvalidCommand,
+    );
+    assert!(!trigger_handler.triggered.get());
+
+    test_event_trigger(&im, &handler, &ENABLE_KEY, TRIGGER, IMStatusCode::Success);
+    assert!(trigger_handler.triggered.get());
+}
+
+#[test]
+fn test_eth_reset_counts() {
+    // ResetCounts resets the packet counts of the interface
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let platforms = Platforms::default();
+    let handler = handler(&im, &platforms, None);
+
+    im.add_default_acl();
+
+    let rx = attr(
+        ethernet_nw_diagnostics::ID,
+        ethernet_nw_diagnostics::AttributesDiscriminants::PacketRxCount as _,
+    );
+    let tx = attr(
+        ethernet_nw_diagnostics::ID,
+        ethernet_nw_diagnostics::AttributesDiscriminants::PacketTxCount as _,
+    );
+
+    platforms.eth.rx.set(5);
+    platforms.eth.tx.set(3);
+    read(
+        &im,
+        &handler,
+        &[
+            (rx.clone(), ElementType::U8(5)),
+            (tx.clone(), ElementType::U8(3)),
+        ],
+    );
+
+    invoke(
+        &im,
+        &handler,
+        ethernet_nw_diagnostics::ID,
+        ethernet_nw_diagnostics::CommandsDiscriminants::ResetCounts as _,
+    );
+    read(
+        &im,
+        &handler,
+        &[(rx, ElementType::U8(0)), (tx, ElementType::U8(0))],
+    );
+}
+
+#[test]
+fn test_eth_counters_change_dataver() {
+    // A read with the data version of the cluster still reports the counters which changed since
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let platforms = Platforms::default();
+    let handler = handler(&im, &platforms, None);
+
+    im.add_default_acl();
+
+    let rx = attr(
+        ethernet_nw_diagnostics::ID,
+        ethernet_nw_diagnostics::AttributesDiscriminants::PacketRxCount as _,
+    );
+
+    let data_ver = handler.dataver(0, ethernet_nw_diagnostics::ID).unwrap();
+    platforms.eth.rx.set(7);
+
+    let dataver_filter = [DataVersionFilter {
+        path: ClusterPath {
+            node: None,
+            endpoint: 0,
+            cluster: ethernet_nw_diagnostics::ID,
+        },
+        data_ver,
+    }];
+
+    let input = &[AttrPath::new(&rx)];
+    let mut out = heapless::Vec::new();
+    let received = im.gen_read_reqs_output::<1>(
+        &handler,
+        input,
+        Some(TLVArray::Slice(&dataver_filter)),
+        &mut out,
+    );
+    assert_attr_report(&received, &[attr_data_path!(rx, ElementType::U8(7))]);
+}
+
+#[test]
+fn test_wifi_nullable_attributes() {
+    // The attributes of the network are null while the node is not connected
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let platforms = Platforms::default();
+    let handler = handler(&im, &platforms, None);
+
+    im.add_default_acl();
+
+    let bssid = attr(
+        wifi_nw_diagnostics::ID,
+        wifi_nw_diagnostics::AttributesDiscriminants::Bssid as _,
+    );
+    let security_type = attr(
+        wifi_nw_diagnostics::ID,
+        wifi_nw_diagnostics::AttributesDiscriminants::SecurityType as _,
+    );
+    let channel = attr(
+        wifi_nw_diagnostics::ID,
+        wifi_nw_diagnostics::AttributesDiscriminants::ChannelNumber as _,
+    );
+    let rssi = attr(
+        wifi_nw_diagnostics::ID,
+        wifi_nw_diagnostics::AttributesDiscriminants::Rssi as _,
+    );
+
+    read(
+        &im,
+        &handler,
+        &[
+            (bssid.clone(), ElementType::Null),
+            (security_type.clone(), ElementType::Null),
+            (channel.clone(), ElementType::Null),
+            (rssi.clone(), ElementType::Null),
+        ],
+    );
+
+    platforms.wifi.connected.set(true);
+    read(
+        &im,
+        &handler,
+        &[
+            (bssid, ElementType::Str8l(&[1, 2, 3, 4, 5, 6])),
+            (security_type, ElementType::U8(SecurityType::Wpa3 as _)),
+            (channel, ElementType::U8(6)),
+            (rssi, ElementType::S8(-60)),
+        ],
+    );
+}
+
+#[test]
+fn test_wifi_reset_counts() {
+    // ResetCounts resets the counts of the interface
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let platforms = Platforms::default();
+    let handler = handler(&im, &platforms, None);
+
+    im.add_default_acl();
+
+    let beacon_lost = attr(
+        wifi_nw_diagnostics::ID,
+        wifi_nw_diagnostics::AttributesDiscriminants::BeaconLostCount as _,
+    );
+
+    platforms.wifi.beacon_lost.set(7);
+    read(&im, &handler, &[(beacon_lost.clone(), ElementType::U8(7))]);
+
+    invoke(
+        &im,
+        &handler,
+        wifi_nw_diagnostics::ID,
+        wifi_nw_diagnostics::CommandsDiscriminants::ResetCounts as _,
+    );
+    read(&im, &handler, &[(beacon_lost, ElementType::U8(0))]);
+}
+
+#[test]
+fn test_thread_detached_and_reset_counts() {
+    // The attributes of the network are null while the node is not attached,
+    // and ResetCounts resets the overrun count
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let platforms = Platforms::default();
+    let handler = handler(&im, &platforms, None);
+
+    im.add_default_acl();
+
+    let channel = attr(
+        thread_nw_diagnostics::ID,
+        thread_nw_diagnostics::AttributesDiscriminants::Channel as _,
+    );
+    let network_name = attr(
+        thread_nw_diagnostics::ID,
+        thread_nw_diagnostics::AttributesDiscriminants::NetworkName as _,
+    );
+    let pan_id = attr(
+        thread_nw_diagnostics::ID,
+        thread_nw_diagnostics::AttributesDiscriminants::PanId as _,
+    );
+    let overrun = attr(
+        thread_nw_diagnostics::ID,
+        thread_nw_diagnostics::AttributesDiscriminants::OverrunCount as _,
+    );
+
+    platforms.thread.overrun.set(4);
+    read(
+        &im,
+        &handler,
+        &[
+            (channel, ElementType::Null),
+            (network_name, ElementType::Null),
+            (pan_id, ElementType::Null),
+            (overrun.clone(), ElementType::U8(4)),
+        ],
+    );
+
+    invoke(
+        &im,
+        &handler,
+        thread_nw_diagnostics::ID,
+        thread_nw_diagnostics::CommandsDiscriminants::ResetCounts as _,
+    );
+    read(&im, &handler, &[(overrun, ElementType::U8(0))]);
+}
+
+#[test]
+fn test_sw_reset_watermarks() {
+    // ResetWatermarks brings the heap high watermark down to the heap currently used
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let platforms = Platforms::default();
+    let handler = handler(&im, &platforms, None);
+
+    im.add_default_acl();
+
+    let used = attr(
+        sw_diagnostics::ID,
+        sw_diagnostics::AttributesDiscriminants::CurrentHeapUsed as _,
+    );
+    let high = attr(
+        sw_diagnostics::ID,
+        sw_diagnostics::AttributesDiscriminants::CurrentHeapHighWatermark as _,
+    );
+
+    platforms.sw.used.set(100);
+    platforms.sw.high.set(200);
+    read(
+        &im,
+        &handler,
+        &[
+            (used.clone(), ElementType::U8(100)),
+            (high.clone(), ElementType::U8(200)),
+        ],
+    );
+
+    invoke(
+        &im,
+        &handler,
+        sw_diagnostics::ID,
+        sw_diagnostics::CommandsDiscriminants::ResetWatermarks as _,
+    );
+    read(
+        &im,
+        &handler,
+        &[(used, ElementType::U8(100)), (high, ElementType::U8(100))],
+    );
+}
@@ -0,0 +1,361 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::{borrow::Borrow, cell::RefCell, time::Duration};
+
+use rs_matter::{
+    data_model::{
+        objects::{EmptyHandler, EncodeValue, Handler, Node},
+        persist::{AttrPersist, StatePersist},
+        root_endpoint,
+        sdm::{
+            failsafe::FailSafe,
+            general_commissioning::{
+                self, Commands, GenCommCluster, RegLocationType, RespCommands,
+            },
+        },
+    },
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrData, AttrPath, AttrResp, AttrStatus, CmdData, CmdPath},
+        messages::GenericPath,
+    },
+    tlv::{ElementType, TLVElement, TLVWriter, TagType},
+    transport::session::{CaseDetails, SessionMode},
+    utils::{epoch::Epoch, rand::Rand},
+};
+
+use crate::{
+    attr_data_path,
+    common::{
+        attributes::*,
+        commands::*,
+        im_engine::{ImEngine, ImHandler},
+        init_env_logger,
+    },
+};
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[root_endpoint::endpoint(0)],
+};
+
+// The error codes of the responses of the cluster
+const OK: u8 = 0;
+const ERR_VALUE_OUTSIDE_RANGE: u8 = 1;
+const ERR_BUSY_WITH_OTHER_ADMIN: u8 = 4;
+
+fn attr_path(attr: general_commissioning::AttributesDiscriminants) -> GenericPath {
+    GenericPath::new(Some(0), Some(general_commissioning::ID), Some(attr as u32))
+}
+
+fn invoke(
+    im: &ImEngine,
+    handler: &impl ImHandler,
+    cmd: Commands,
+    data: EncodeValue,
+    resp: RespCommands,
+    error_code: u8,
+) {
+    let input = &[CmdData::new(
+        CmdPath::new(Some(0), Some(general_commissioning::ID), Some(cmd as u32)),
+        data,
+    )];
+    let expected = &[ExpectedInvResp::Cmd(
+        CmdPath::new(Some(0), Some(general_commissioning::ID), Some(resp as u32)),
+        error_code,
+    )];
+    im.handle_commands(handler, input, expected);
+}
+
+fn read_breadcrumb(im: &ImEngine, handler: &impl ImHandler, breadcrumb: u8) {
+    let path = attr_path(general_commissioning::AttributesDiscriminants::BreadCrumb);
+
+    let input = &[AttrPath::new(&path)];
+    let expected = &[attr_data_path!(path, ElementType::U8(breadcrumb))];
+    im.handle_read_reqs(handler, input, expected);
+}
+
+#[test]
+fn test_set_reg_config_outside_capability() {
+    // An indoor-only device rejects an outdoor regulatory config
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let epoch: Epoch = *im.matter.borrow();
+    let rand: Rand = *im.matter.borrow();
+    let failsafe: &RefCell<FailSafe> = im.matter.borrow();
+
+    let handler = (
+        NODE,
+        EmptyHandler.chain(
+            0,
+            general_commissioning::ID,
+            GenCommCluster::new(failsafe, RegLocationType::Indoor, epoch, rand),
+        ),
+    );
+
+    im.add_default_acl();
+
+    let set_reg_config = |reg_config: RegLocationType| {
+        move |tag, t: &mut TLVWriter| {
+            let _ = t.start_struct(tag);
+            let _ = t.u8(TagType::Context(0), reg_config as _);
+            let _ = t.utf8(TagType::Context(1), b"DE");
+            let _ = t.u64(TagType::Context(2), 1);
+            let _ = t.end_container();
+        }
+    };
+
+    invoke(
+        &im,
+        &handler,
+        Commands::SetRegulatoryConfig,
+        EncodeValue::Closure(&set_reg_config(RegLocationType::Outdoor)),
+        RespCommands::SetRegulatoryConfigResp,
+        ERR_VALUE_OUTSIDE_RANGE,
+    );
+    assert_eq!(
+        handler.1.handler.regulatory_config(),
+        RegLocationType::Indoor
+    );
+
+    invoke(
+        &im,
+        &handler,
+        Commands::SetRegulatoryConfig,
+        EncodeValue::Closure(&set_reg_config(RegLocationType::Indoor)),
+        RespCommands::SetRegulatoryConfigResp,
+        OK,
+    );
+    assert_eq!(handler.1.handler.country_code(), *b"DE");
+}
+
+#[test]
+fn test_arm_failsafe_busy() {
+    // The fail-safe cannot be armed while another session holds it
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = (NODE, root_endpoint::handler(0, &im.matter));
+
+    im.add_default_acl();
+
+    let epoch: Epoch = *im.matter.borrow();
+    let failsafe: &RefCell<FailSafe> = im.matter.borrow();
+    failsafe
+        .borrow_mut()
+        .arm(60, SessionMode::Pase, epoch())
+        .unwrap();
+
+    let arm = |tag, t: &mut TLVWriter| {
+        let _ = t.start_struct(tag);
+        let _ = t.u16(TagType::Context(0), 60);
+        let _ = t.u64(TagType::Context(1), 2);
+        let _ = t.end_container();
+    };
+
+    invoke(
+        &im,
+        &handler,
+        Commands::ArmFailsafe,
+        EncodeValue::Closure(&arm),
+        RespCommands::ArmFailsafeResp,
+        ERR_BUSY_WITH_OTHER_ADMIN,
+    );
+    read_breadcrumb(&im, &handler, 0);
+}
+
+#[test]
+fn test_breadcrumb_reset_on_expiry() {
+    // The breadcrumb set when arming the fail-safe is reset when it expires,
+    // which also changes the data version of the cluster
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = (NODE, root_endpoint::handler(0, &im.matter));
+
+    im.add_default_acl();
+
+    let arm = |tag, t: &mut TLVWriter| {
+        let _ = t.start_struct(tag);
+        let _ = t.u16(TagType::Context(0), 10);
+        let _ = t.u64(TagType::Context(1), 7);
+        let _ = t.end_container();
+    };
+
+    invoke(
+        &im,
+        &handler,
+        Commands::ArmFailsafe,
+        EncodeValue::Closure(&arm),
+        RespCommands::ArmFailsafeResp,
+        OK,
+    );
+    read_breadcrumb(&im, &handler, 7);
+
+    let dataver = handler.dataver(0, general_commissioning::ID);
+
+    // Reading does not change the data version
+    read_breadcrumb(&im, &handler, 7);
+    assert_eq!(handler.dataver(0, general_commissioning::ID), dataver);
+
+    let epoch: Epoch = *im.matter.borrow();
+    let failsafe: &RefCell<FailSafe> = im.matter.borrow();
+    assert!(failsafe
+        .borrow_mut()
+        .expire(epoch() + Duration::from_secs(20)));
+
+    assert_ne!(handler.dataver(0, general_commissioning::ID), dataver);
+    read_breadcrumb(&im, &handler, 0);
+}
+
+#[test]
+fn test_breadcrumb_reset_on_commissioning_complete() {
+    // Completing the commissioning disarms the fail-safe and resets the breadcrumb
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = (NODE, root_endpoint::handler(0, &im.matter));
+
+    im.add_default_acl();
+
+    // Armed by the CASE session of the IM engine, which added its NOC
+    let epoch: Epoch = *im.matter.borrow();
+    let failsafe: &RefCell<FailSafe> = im.matter.borrow();
+    {
+        let mut failsafe = failsafe.borrow_mut();
+
+        failsafe
+            .arm(
+                60,
+                SessionMode::Case(CaseDetails::new(1, &Default::default())),
+                epoch(),
+            )
+            .unwrap();
+        failsafe.record_add_noc(1).unwrap();
+        failsafe.set_breadcrumb(9);
+    }
+
+    read_breadcrumb(&im, &handler, 9);
+
+    let empty = |tag, t: &mut TLVWriter| {
+        let _ = t.start_struct(tag);
+        let _ = t.end_container();
+    };
+
+    invoke(
+        &im,
+        &handler,
+        Commands::CommissioningComplete,
+        EncodeValue::Closure(&empty),
+        RespCommands::CommissioningCompleteResp,
+        OK,
+    );
+    assert!(!failsafe.borrow().is_armed());
+    read_breadcrumb(&im, &handler, 0);
+}
+
+#[test]
+fn test_reg_config_persist() {
+    // The regulatory config is restored into a freshly created data model, with the
+    // location type as a persistent attribute and the country code as state
+    init_env_logger();
+
+    let reg_config = attr_path(general_commissioning::AttributesDiscriminants::RegConfig);
+
+    let mut attrs_data = [0; 4096];
+    let mut state_data = [0; 16];
+
+    let (attrs_len, state_len) = {
+        let im = ImEngine::new_default();
+        let handler = (NODE, root_endpoint::handler(0, &im.matter));
+
+        im.add_default_acl();
+
+        let set_reg_config = |tag, t: &mut TLVWriter| {
+            let _ = t.start_struct(tag);
+            let _ = t.u8(TagType::Context(0), RegLocationType::Outdoor as _);
+            let _ = t.utf8(TagType::Context(1), b"DE");
+            let _ = t.u64(TagType::Context(2), 1);
+            let _ = t.end_container();
+        };
+
+        invoke(
+            &im,
+            &handler,
+            Commands::SetRegulatoryConfig,
+            EncodeValue::Closure(&set_reg_config),
+            RespCommands::SetRegulatoryConfigResp,
+            OK,
+        );
+
+        let mut attrs = AttrPersist::new();
+        let mut buf = [0; 4096];
+
+        let stored = embassy_futures::block_on(attrs.store(&handler, &mut buf))
+            .unwrap()
+            .unwrap();
+        attrs_data[..stored.len()].copy_from_slice(stored);
+        let attrs_len = stored.len();
+
+        let gen_comm: &GenCommCluster = handler.1.get();
+
+        let stored = gen_comm.store(&mut buf).unwrap().unwrap();
+        state_data[..stored.len()].copy_from_slice(stored);
+        let state_len = stored.len();
+
+        // Nothing changed since the last store
+        assert!(gen_comm.store(&mut buf).unwrap().is_none());
+
+        (attrs_len, state_len)
+    };
+
+    let im = ImEngine::new_default();
+    let handler = (NODE, root_endpoint::handler(0, &im.matter));
+
+    im.add_default_acl();
+
+    let mut attrs = AttrPersist::new();
+    embassy_futures::block_on(attrs.load(&handler, &attrs_data[..attrs_len])).unwrap();
+
+    let gen_comm: &GenCommCluster = handler.1.get();
+    gen_comm.load(&state_data[..state_len]).unwrap();
+
+    let input = &[AttrPath::new(&reg_config)];
+    let expected = &[attr_data_path!(
+        reg_config,
+        ElementType::U8(RegLocationType::Outdoor as _)
+    )];
+    im.handle_read_reqs(&handler, input, expected);
+
+    assert_eq!(gen_comm.regulatory_config(), RegLocationType::Outdoor);
+    assert_eq!(gen_comm.country_code(), *b"DE");
+
+    // Writing the restored config is not allowed to the controllers
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&reg_config),
+        EncodeValue::Value(&(RegLocationType::Indoor as u8)),
+    )];
+    let expected = &[AttrStatus::new(
+        &reg_config,
+        IMStatusCode::UnsupportedWrite,
+        0,
+    )];
+    im.handle_write_reqs(&handler, input, expected);
+}
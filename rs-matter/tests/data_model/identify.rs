@@ -0,0 +1,127 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+use rs_matter::{
+    data_model::{
+        cluster_identify::{self, IdentifyReq, TriggerEffectReq},
+        objects::EncodeValue,
+    },
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrData, AttrPath, AttrStatus, CmdData, CmdPath, CmdStatus},
+        messages::GenericPath,
+    },
+};
+
+use crate::common::{commands::*, im_engine::ImEngine, init_env_logger};
+
+#[test]
+fn test_identify_cmd() {
+    // Identify for 10 seconds, then stop right away
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+
+    let path = CmdPath::new(
+        Some(1),
+        Some(cluster_identify::ID),
+        Some(cluster_identify::CommandsDiscriminants::Identify as u32),
+    );
+
+    let start = IdentifyReq { identify_time: 10 };
+    let input = &[CmdData::new(path.clone(), EncodeValue::Value(&start))];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        path.clone(),
+        IMStatusCode::Success,
+        0,
+    ))];
+    im.handle_commands(&handler, input, expected);
+
+    assert_eq!(handler.identify().identify_time(), 10);
+
+    let stop = IdentifyReq { identify_time: 0 };
+    let input = &[CmdData::new(path.clone(), EncodeValue::Value(&stop))];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        path,
+        IMStatusCode::Success,
+        0,
+    ))];
+    im.handle_commands(&handler, input, expected);
+
+    assert_eq!(handler.identify().identify_time(), 0);
+}
+
+#[test]
+fn test_identify_write_time() {
+    // Writing IdentifyTime is the same as the Identify command
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+
+    let path = GenericPath::new(
+        Some(1),
+        Some(cluster_identify::ID),
+        Some(cluster_identify::AttributesDiscriminants::IdentifyTime as u32),
+    );
+
+    let time = 30u16;
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&path),
+        EncodeValue::Value(&time),
+    )];
+    let expected = &[AttrStatus::new(&path, IMStatusCode::Success, 0)];
+    im.handle_write_reqs(&handler, input, expected);
+
+    assert_eq!(handler.identify().identify_time(), 30);
+}
+
+#[test]
+fn test_trigger_effect() {
+    // A known effect succeeds, an unknown one is rejected
+    init_env_logger();
+
+    let path = CmdPath::new(
+        Some(1),
+        Some(cluster_identify::ID),
+        Some(cluster_identify::CommandsDiscriminants::TriggerEffect as u32),
+    );
+
+    let blink = TriggerEffectReq {
+        effect_identifier: 0x00,
+        effect_variant: 0,
+    };
+    let unknown = TriggerEffectReq {
+        effect_identifier: 0x10,
+        effect_variant: 0,
+    };
+
+    let input = &[
+        CmdData::new(path.clone(), EncodeValue::Value(&blink)),
+        CmdData::new(path.clone(), EncodeValue::Value(&unknown)),
+    ];
+    let expected = &[
+        ExpectedInvResp::Status(CmdStatus::new(path.clone(), IMStatusCode::Success, 0)),
+        ExpectedInvResp::Status(CmdStatus::new(path, IMStatusCode::ConstraintError, 0)),
+    ];
+    ImEngine::commands(input, expected);
+}
@@ -0,0 +1,370 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+
+use rs_matter::{
+    data_model::{
+        cluster_level_control::{
+            self, CommandsDiscriminants, LevelControlCluster, MoveReq, MoveToLevelReq, StepReq,
+            StopReq, MOVE_MODE_DOWN, MOVE_MODE_UP,
+        },
+        cluster_on_off::{self, OnOffCluster},
+        device_types::DEV_TYPE_DIMMABLE_LIGHT,
+        objects::{EncodeValue, Endpoint, Node},
+        root_endpoint,
+        system_model::descriptor::{self, DescriptorCluster},
+    },
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrPath, AttrResp, CmdData, CmdPath, CmdStatus},
+        messages::GenericPath,
+    },
+    tlv::{ElementType, Nullable, TLVElement, TagType, ToTLV},
+};
+
+use crate::{
+    attr_data_path,
+    common::{
+        attributes::*,
+        commands::*,
+        im_engine::{ImEngine, ImHandler},
+        init_env_logger,
+    },
+};
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[
+        root_endpoint::endpoint(0),
+        Endpoint {
+            id: 1,
+            device_type: DEV_TYPE_DIMMABLE_LIGHT,
+            extra_device_types: &[],
+            clusters: &[
+                descriptor::CLUSTER,
+                cluster_on_off::CLUSTER,
+                cluster_level_control::CLUSTER,
+            ],
+            client_clusters: &[],
+        },
+    ],
+};
+
+fn invoke(
+    im: &ImEngine,
+    handler: &impl ImHandler,
+    cmd: CommandsDiscriminants,
+    req: &dyn ToTLV,
+    status: IMStatusCode,
+) {
+    let path = CmdPath::new(Some(1), Some(cluster_level_control::ID), Some(cmd as u32));
+
+    let input = &[CmdData::new(path.clone(), EncodeValue::Value(req))];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(path, status, 0))];
+    im.handle_commands(handler, input, expected);
+}
+
+fn assert_level(im: &ImEngine, handler: &impl ImHandler, level: u8, on: bool) {
+    let current_level = GenericPath::new(
+        Some(1),
+        Some(cluster_level_control::ID),
+        Some(cluster_level_control::AttributesDiscriminants::CurrentLevel as u32),
+    );
+    let remaining_time = GenericPath::new(
+        Some(1),
+        Some(cluster_level_control::ID),
+        Some(cluster_level_control::AttributesDiscriminants::RemainingTime as u32),
+    );
+    let on_off = GenericPath::new(
+        Some(1),
+        Some(cluster_on_off::ID),
+        Some(cluster_on_off::AttributesDiscriminants::OnOff as u32),
+    );
+
+    let input = &[
+        AttrPath::new(&current_level),
+        AttrPath::new(&remaining_time),
+        AttrPath::new(&on_off),
+    ];
+    let expected = &[
+        attr_data_path!(current_level, ElementType::U8(level)),
+        attr_data_path!(remaining_time, ElementType::U8(0)),
+        attr_data_path!(
+            on_off,
+            if on {
+                ElementType::True
+            } else {
+                ElementType::False
+            }
+        ),
+    ];
+    im.handle_read_reqs(handler, input, expected);
+}
+
+fn move_to_level(level: u8) -> MoveToLevelReq {
+    MoveToLevelReq {
+        level,
+        transition_time: Nullable::NotNull(0),
+        options_mask: 0,
+        options_override: 0,
+    }
+}
+
+fn move_(move_mode: u8, rate: Nullable<u8>) -> MoveReq {
+    MoveReq {
+        move_mode,
+        rate,
+        options_mask: 0,
+        options_override: 0,
+    }
+}
+
+fn step(step_mode: u8, step_size: u8) -> StepReq {
+    StepReq {
+        step_mode,
+        step_size,
+        transition_time: Nullable::Null,
+        options_mask: 0,
+        options_override: 0,
+    }
+}
+
+#[test]
+fn test_level_move_to_level() {
+    // MoveToLevel is ignored while off, unlike MoveToLevelWithOnOff
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let rand = *im.matter.borrow();
+
+    let on_off = OnOffCluster::new(rand);
+    let level = LevelControlCluster::new(Some(&on_off), rand);
+    on_off.set_coupling(Some(&level));
+
+    let handler = (
+        NODE,
+        root_endpoint::handler(0, &im.matter)
+            .chain(1, descriptor::ID, DescriptorCluster::new(rand))
+            .chain(1, cluster_on_off::ID, &on_off)
+            .chain(1, cluster_level_control::ID, &level),
+    );
+
+    im.add_default_acl();
+
+    assert_level(&im, &handler, 254, false);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::MoveToLevel,
+        &move_to_level(100),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 254, false);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::MoveToLevelWithOnOff,
+        &move_to_level(100),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 100, true);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::MoveToLevel,
+        &move_to_level(50),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 50, true);
+
+    // Levels are clamped to the ones of the Lighting feature, and 255 is not a level
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::MoveToLevel,
+        &move_to_level(0),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 1, true);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::MoveToLevel,
+        &move_to_level(255),
+        IMStatusCode::ConstraintError,
+    );
+    assert_level(&im, &handler, 1, true);
+}
+
+#[test]
+fn test_level_move() {
+    // Move without a rate completes right away, with a rate it runs until stopped
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let rand = *im.matter.borrow();
+
+    let on_off = OnOffCluster::new(rand);
+    let level = LevelControlCluster::new(Some(&on_off), rand);
+    on_off.set_coupling(Some(&level));
+
+    let handler = (
+        NODE,
+        root_endpoint::handler(0, &im.matter)
+            .chain(1, descriptor::ID, DescriptorCluster::new(rand))
+            .chain(1, cluster_on_off::ID, &on_off)
+            .chain(1, cluster_level_control::ID, &level),
+    );
+
+    im.add_default_acl();
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::MoveWithOnOff,
+        &move_(MOVE_MODE_DOWN, Nullable::Null),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 1, false);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::MoveWithOnOff,
+        &move_(MOVE_MODE_UP, Nullable::Null),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 254, true);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::Move,
+        &move_(MOVE_MODE_UP, Nullable::NotNull(0)),
+        IMStatusCode::InvalidCommand,
+    );
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::Move,
+        &move_(2, Nullable::Null),
+        IMStatusCode::ConstraintError,
+    );
+    assert_level(&im, &handler, 254, true);
+
+    // At 1 unit per second, the level does not change before the move is stopped
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::Move,
+        &move_(MOVE_MODE_DOWN, Nullable::NotNull(1)),
+        IMStatusCode::Success,
+    );
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::Stop,
+        &StopReq {
+            options_mask: 0,
+            options_override: 0,
+        },
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 254, true);
+}
+
+#[test]
+fn test_level_step() {
+    // Steps are clamped, and StepWithOnOff down to the minimum switches off
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let rand = *im.matter.borrow();
+
+    let on_off = OnOffCluster::new(rand);
+    let level = LevelControlCluster::new(Some(&on_off), rand);
+    on_off.set_coupling(Some(&level));
+
+    let handler = (
+        NODE,
+        root_endpoint::handler(0, &im.matter)
+            .chain(1, descriptor::ID, DescriptorCluster::new(rand))
+            .chain(1, cluster_on_off::ID, &on_off)
+            .chain(1, cluster_level_control::ID, &level),
+    );
+
+    im.add_default_acl();
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::MoveToLevelWithOnOff,
+        &move_to_level(100),
+        IMStatusCode::Success,
+    );
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::Step,
+        &step(MOVE_MODE_UP, 20),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 120, true);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::Step,
+        &step(MOVE_MODE_UP, 200),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 254, true);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::Step,
+        &step(2, 10),
+        IMStatusCode::ConstraintError,
+    );
+    assert_level(&im, &handler, 254, true);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::StepWithOnOff,
+        &step(MOVE_MODE_DOWN, 255),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 1, false);
+
+    // Without ExecuteIfOff, Step is ignored while off
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::Step,
+        &step(MOVE_MODE_UP, 10),
+        IMStatusCode::Success,
+    );
+    assert_level(&im, &handler, 1, false);
+}
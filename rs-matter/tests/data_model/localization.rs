@@ -0,0 +1,164 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+
+use rs_matter::{
+    data_model::{
+        cluster_fixed_label::LabelStruct,
+        cluster_localization,
+        cluster_time_format_localization::{self, HourFormat},
+        cluster_unit_localization::{self, TempUnit},
+        cluster_user_label::{self, UserLabelCluster},
+        device_types::{DEV_TYPE_ON_OFF_LIGHT, DEV_TYPE_ROOT_NODE},
+        objects::{EncodeValue, Endpoint, Node},
+        root_endpoint,
+        system_model::descriptor::{self, DescriptorCluster},
+    },
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrData, AttrPath, AttrStatus},
+        messages::GenericPath,
+    },
+};
+
+use crate::common::{im_engine::ImEngine, init_env_logger};
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[
+        Endpoint {
+            id: 0,
+            device_type: DEV_TYPE_ROOT_NODE,
+            extra_device_types: &[],
+            clusters: &[
+                descriptor::CLUSTER,
+                cluster_localization::CLUSTER,
+                cluster_time_format_localization::CLUSTER,
+                cluster_unit_localization::CLUSTER,
+            ],
+            client_clusters: &[],
+        },
+        Endpoint {
+            id: 1,
+            device_type: DEV_TYPE_ON_OFF_LIGHT,
+            extra_device_types: &[],
+            clusters: &[descriptor::CLUSTER, cluster_user_label::CLUSTER],
+            client_clusters: &[],
+        },
+    ],
+};
+
+#[test]
+fn test_read_back_localization() {
+    // The application sees the localization written by the users
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = (NODE, root_endpoint::handler(0, &im.matter));
+
+    im.add_default_acl();
+
+    let hour_format = GenericPath::new(
+        Some(0),
+        Some(cluster_time_format_localization::ID),
+        Some(cluster_time_format_localization::AttributesDiscriminants::HourFormat as u32),
+    );
+    let temperature_unit = GenericPath::new(
+        Some(0),
+        Some(cluster_unit_localization::ID),
+        Some(cluster_unit_localization::AttributesDiscriminants::TemperatureUnit as u32),
+    );
+
+    let input = &[
+        AttrData::new(
+            None,
+            AttrPath::new(&hour_format),
+            EncodeValue::Value(&(HourFormat::TwentyFourHour as u8)),
+        ),
+        AttrData::new(
+            None,
+            AttrPath::new(&temperature_unit),
+            EncodeValue::Value(&(TempUnit::Celsius as u8)),
+        ),
+    ];
+    let expected = &[
+        AttrStatus::new(&hour_format, IMStatusCode::Success, 0),
+        AttrStatus::new(&temperature_unit, IMStatusCode::Success, 0),
+    ];
+    im.handle_write_reqs(&handler, input, expected);
+
+    let (locale, time_format, unit) = root_endpoint::localization(&handler.1);
+    assert_eq!(locale.active_locale(), "en-US");
+    assert_eq!(time_format.hour_format(), HourFormat::TwentyFourHour);
+    assert_eq!(unit.temperature_unit(), TempUnit::Celsius);
+}
+
+#[test]
+fn test_label_list_replace_atomic() {
+    // Replacing the LabelList with a list which has an invalid label keeps the old list
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let rand = *im.matter.borrow();
+
+    let handler = (
+        NODE,
+        root_endpoint::handler(0, &im.matter)
+            .chain(1, descriptor::ID, DescriptorCluster::new(rand))
+            .chain(1, cluster_user_label::ID, UserLabelCluster::new(rand)),
+    );
+
+    im.add_default_acl();
+
+    let label_list = GenericPath::new(
+        Some(1),
+        Some(cluster_user_label::ID),
+        Some(cluster_user_label::AttributesDiscriminants::LabelList as u32),
+    );
+
+    let valid = [LabelStruct::new("room", "kitchen")];
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&label_list),
+        EncodeValue::Value(&valid),
+    )];
+    let expected = &[AttrStatus::new(&label_list, IMStatusCode::Success, 0)];
+    im.handle_write_reqs(&handler, input, expected);
+
+    let invalid = [
+        LabelStruct::new("room", "bedroom"),
+        LabelStruct::new("a label which is too long", "1"),
+    ];
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&label_list),
+        EncodeValue::Value(&invalid),
+    )];
+    let expected = &[AttrStatus::new(
+        &label_list,
+        IMStatusCode::ConstraintError,
+        0,
+    )];
+    im.handle_write_reqs(&handler, input, expected);
+
+    let mut labels = heapless::Vec::<_, 4>::new();
+    handler.1.handler.for_each(|label, value| {
+        labels.push(label == "room" && value == "kitchen").unwrap();
+    });
+    assert_eq!(labels.as_slice(), &[true]);
+}
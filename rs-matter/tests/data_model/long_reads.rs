@@ -17,7 +17,8 @@
 
 use rs_matter::{
     data_model::{
-        cluster_basic_information as basic_info, cluster_on_off as onoff,
+        cluster_basic_information as basic_info, cluster_identify as identify,
+        cluster_on_off as onoff,
         objects::{EncodeValue, GlobalElements},
         sdm::{
             admin_commissioning as adm_comm, general_commissioning as gen_comm, noc,
@@ -63,6 +64,7 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         attr_data!(0, 29, descriptor::Attributes::ClientList, dont_care.clone()),
         attr_data!(0, 40, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 40, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(0, 40, GlobalElements::ClusterRevision, dont_care.clone()),
         attr_data!(
             0,
             40,
@@ -99,6 +101,12 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             basic_info::AttributesDiscriminants::NodeLabel,
             dont_care.clone()
         ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::Location,
+            dont_care.clone()
+        ),
         attr_data!(
             0,
             40,
@@ -117,12 +125,78 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             basic_info::AttributesDiscriminants::SwVerString,
             dont_care.clone()
         ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::ManufacturingDate,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::PartNumber,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::ProductUrl,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::ProductLabel,
+            dont_care.clone()
+        ),
         attr_data!(
             0,
             40,
             basic_info::AttributesDiscriminants::SerialNo,
             dont_care.clone()
         ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::LocalConfigDisabled,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::Reachable,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::UniqueId,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::CapabilityMinima,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::ProductAppearance,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::SpecificationVersion,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            basic_info::AttributesDiscriminants::MaxPathsPerInvoke,
+            dont_care.clone()
+        ),
         attr_data!(0, 48, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 48, GlobalElements::AttributeList, dont_care.clone()),
         attr_data!(
@@ -149,6 +223,9 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             gen_comm::AttributesDiscriminants::BasicCommissioningInfo,
             dont_care.clone()
         ),
+    ];
+
+    let part2 = vec![
         attr_data!(0, 49, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 49, GlobalElements::AttributeList, dont_care.clone()),
         attr_data!(
@@ -213,9 +290,6 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             adm_comm::AttributesDiscriminants::AdminVendorId,
             dont_care.clone()
         ),
-    ];
-
-    let part2 = vec![
         attr_data!(0, 62, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 62, GlobalElements::AttributeList, dont_care.clone()),
         attr_data!(
@@ -305,6 +379,23 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         attr_data!(1, 29, descriptor::Attributes::ServerList, dont_care.clone()),
         attr_data!(1, 29, descriptor::Attributes::PartsList, dont_care.clone()),
         attr_data!(1, 29, descriptor::Attributes::ClientList, dont_care.clone()),
+    ];
+
+    let part3 = vec![
+        attr_data!(1, 3, GlobalElements::FeatureMap, dont_care.clone()),
+        attr_data!(1, 3, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            1,
+            3,
+            identify::AttributesDiscriminants::IdentifyTime,
+            dont_care.clone()
+        ),
+        attr_data!(
+            1,
+            3,
+            identify::AttributesDiscriminants::IdentifyType,
+            dont_care.clone()
+        ),
         attr_data!(1, 6, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(1, 6, GlobalElements::AttributeList, dont_care.clone()),
         attr_data!(
@@ -313,6 +404,30 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             onoff::AttributesDiscriminants::OnOff,
             dont_care.clone()
         ),
+        attr_data!(
+            1,
+            6,
+            onoff::AttributesDiscriminants::GlobalSceneControl,
+            dont_care.clone()
+        ),
+        attr_data!(
+            1,
+            6,
+            onoff::AttributesDiscriminants::OnTime,
+            dont_care.clone()
+        ),
+        attr_data!(
+            1,
+            6,
+            onoff::AttributesDiscriminants::OffWaitTime,
+            dont_care.clone()
+        ),
+        attr_data!(
+            1,
+            6,
+            onoff::AttributesDiscriminants::StartUpOnOff,
+            dont_care.clone()
+        ),
         attr_data!(1, echo::ID, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(
             1,
@@ -340,16 +455,16 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         ),
     ];
 
-    if part == 1 {
-        part1
-    } else {
-        part2
+    match part {
+        1 => part1,
+        2 => part2,
+        _ => part3,
     }
 }
 
 #[test]
 fn test_long_read_success() {
-    // Read the entire attribute database, which requires 2 reads to complete
+    // Read the entire attribute database, which requires 3 reads to complete
     init_env_logger();
 
     let mut out = heapless::Vec::<_, 3>::new();
@@ -368,18 +483,20 @@ fn test_long_read_success() {
         status: IMStatusCode::Success,
     };
     let expected_part2 = wildcard_read_resp(2);
+    let expected_part3 = wildcard_read_resp(3);
 
     im.process(
         &handler,
         &[
             &ImInput::new(OpCode::ReadRequest, &read_req),
             &ImInput::new(OpCode::StatusResponse, &status_report),
+            &ImInput::new(OpCode::StatusResponse, &status_report),
         ],
         &mut out,
     )
     .unwrap();
 
-    assert_eq!(out.len(), 2);
+    assert_eq!(out.len(), 3);
 
     assert_eq!(out[0].action, OpCode::ReportData);
 
@@ -393,15 +510,22 @@ fn test_long_read_success() {
     let root = tlv::get_root_node_struct(&out[1].data).unwrap();
     let report_data = ReportDataMsg::from_tlv(&root).unwrap();
     assert_attr_report_skip_data(&report_data, &expected_part2);
+    assert_eq!(report_data.more_chunks, Some(true));
+
+    assert_eq!(out[2].action, OpCode::ReportData);
+
+    let root = tlv::get_root_node_struct(&out[2].data).unwrap();
+    let report_data = ReportDataMsg::from_tlv(&root).unwrap();
+    assert_attr_report_skip_data(&report_data, &expected_part3);
     assert_eq!(report_data.more_chunks, None);
 }
 
 #[test]
 fn test_long_read_subscription_success() {
-    // Subscribe to the entire attribute database, which requires 2 reads to complete
+    // Subscribe to the entire attribute database, which requires 3 reads to complete
     init_env_logger();
 
-    let mut out = heapless::Vec::<_, 3>::new();
+    let mut out = heapless::Vec::<_, 4>::new();
     let im = ImEngine::new_default();
     let handler = im.handler();
 
@@ -417,6 +541,7 @@ fn test_long_read_subscription_success() {
         status: IMStatusCode::Success,
     };
     let expected_part2 = wildcard_read_resp(2);
+    let expected_part3 = wildcard_read_resp(3);
 
     im.process(
         &handler,
@@ -424,12 +549,13 @@ fn test_long_read_subscription_success() {
             &ImInput::new(OpCode::SubscribeRequest, &subs_req),
             &ImInput::new(OpCode::StatusResponse, &status_report),
             &ImInput::new(OpCode::StatusResponse, &status_report),
+            &ImInput::new(OpCode::StatusResponse, &status_report),
         ],
         &mut out,
     )
     .unwrap();
 
-    assert_eq!(out.len(), 3);
+    assert_eq!(out.len(), 4);
 
     assert_eq!(out[0].action, OpCode::ReportData);
 
@@ -443,11 +569,18 @@ fn test_long_read_subscription_success() {
     let root = tlv::get_root_node_struct(&out[1].data).unwrap();
     let report_data = ReportDataMsg::from_tlv(&root).unwrap();
     assert_attr_report_skip_data(&report_data, &expected_part2);
-    assert_eq!(report_data.more_chunks, None);
+    assert_eq!(report_data.more_chunks, Some(true));
 
-    assert_eq!(out[2].action, OpCode::SubscribeResponse);
+    assert_eq!(out[2].action, OpCode::ReportData);
 
     let root = tlv::get_root_node_struct(&out[2].data).unwrap();
+    let report_data = ReportDataMsg::from_tlv(&root).unwrap();
+    assert_attr_report_skip_data(&report_data, &expected_part3);
+    assert_eq!(report_data.more_chunks, None);
+
+    assert_eq!(out[3].action, OpCode::SubscribeResponse);
+
+    let root = tlv::get_root_node_struct(&out[3].data).unwrap();
     let subs_resp = SubscribeResp::from_tlv(&root).unwrap();
     assert_eq!(subs_resp.subs_id, 1);
 }
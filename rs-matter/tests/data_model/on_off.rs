@@ -0,0 +1,220 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use rs_matter::{
+    data_model::{
+        cluster_on_off::{
+            self, AttributesDiscriminants, CommandsDiscriminants, OffWithEffectReq,
+            OnWithTimedOffReq, ACCEPT_ONLY_WHEN_ON,
+        },
+        objects::EncodeValue,
+    },
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrPath, AttrResp, CmdData, CmdPath, CmdStatus},
+        messages::GenericPath,
+    },
+    tlv::{ElementType, TLVElement, TagType, ToTLV},
+};
+
+use crate::{
+    attr_data_path, cmd_data,
+    common::{
+        attributes::*,
+        commands::*,
+        im_engine::{ImEngine, ImEngineHandler},
+        init_env_logger,
+    },
+};
+
+fn attr(attr: AttributesDiscriminants) -> GenericPath {
+    GenericPath::new(Some(1), Some(cluster_on_off::ID), Some(attr as u32))
+}
+
+fn cmd(cmd: CommandsDiscriminants) -> CmdPath {
+    CmdPath::new(Some(1), Some(cluster_on_off::ID), Some(cmd as u32))
+}
+
+fn invoke(
+    im: &ImEngine,
+    handler: &ImEngineHandler,
+    command: CommandsDiscriminants,
+    req: &dyn ToTLV,
+) {
+    let path = cmd(command);
+
+    let input = &[CmdData::new(path.clone(), EncodeValue::Value(req))];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        path,
+        IMStatusCode::Success,
+        0,
+    ))];
+    im.handle_commands(handler, input, expected);
+}
+
+fn bool_element(value: bool) -> ElementType<'static> {
+    if value {
+        ElementType::True
+    } else {
+        ElementType::False
+    }
+}
+
+fn assert_timed(
+    im: &ImEngine,
+    handler: &ImEngineHandler,
+    on: bool,
+    on_time: u8,
+    off_wait_time: u8,
+) {
+    let on_off = attr(AttributesDiscriminants::OnOff);
+    let on_time_attr = attr(AttributesDiscriminants::OnTime);
+    let off_wait_time_attr = attr(AttributesDiscriminants::OffWaitTime);
+
+    let input = &[
+        AttrPath::new(&on_off),
+        AttrPath::new(&on_time_attr),
+        AttrPath::new(&off_wait_time_attr),
+    ];
+    let expected = &[
+        attr_data_path!(on_off, bool_element(on)),
+        attr_data_path!(on_time_attr, ElementType::U8(on_time)),
+        attr_data_path!(off_wait_time_attr, ElementType::U8(off_wait_time)),
+    ];
+    im.handle_read_reqs(handler, input, expected);
+}
+
+fn assert_global_scene(im: &ImEngine, handler: &ImEngineHandler, on: bool, control: bool) {
+    let on_off = attr(AttributesDiscriminants::OnOff);
+    let global_scene_control = attr(AttributesDiscriminants::GlobalSceneControl);
+
+    let input = &[AttrPath::new(&on_off), AttrPath::new(&global_scene_control)];
+    let expected = &[
+        attr_data_path!(on_off, bool_element(on)),
+        attr_data_path!(global_scene_control, bool_element(control)),
+    ];
+    im.handle_read_reqs(handler, input, expected);
+}
+
+#[test]
+fn test_on_with_timed_off() {
+    // AcceptOnlyWhenOn ignores the command while off, and OnTime only ever grows
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+
+    let only_when_on = OnWithTimedOffReq {
+        on_off_control: ACCEPT_ONLY_WHEN_ON,
+        on_time: 30,
+        off_wait_time: 10,
+    };
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::OnWithTimedOff,
+        &only_when_on,
+    );
+    assert_timed(&im, &handler, false, 0, 0);
+
+    let timed = OnWithTimedOffReq {
+        on_off_control: 0,
+        on_time: 50,
+        off_wait_time: 20,
+    };
+    invoke(&im, &handler, CommandsDiscriminants::OnWithTimedOff, &timed);
+    assert_timed(&im, &handler, true, 50, 20);
+
+    invoke(
+        &im,
+        &handler,
+        CommandsDiscriminants::OnWithTimedOff,
+        &only_when_on,
+    );
+    assert_timed(&im, &handler, true, 50, 10);
+
+    // Off cancels the timed on
+    let input = &[cmd_data!(cmd(CommandsDiscriminants::Off), 1)];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        cmd(CommandsDiscriminants::Off),
+        IMStatusCode::Success,
+        0,
+    ))];
+    im.handle_commands(&handler, input, expected);
+    assert_timed(&im, &handler, false, 0, 10);
+}
+
+#[test]
+fn test_off_with_effect() {
+    // OffWithEffect clears GlobalSceneControl, which allows OnWithRecallGlobalScene
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+
+    let input = &[cmd_data!(cmd(CommandsDiscriminants::On), 1)];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        cmd(CommandsDiscriminants::On),
+        IMStatusCode::Success,
+        0,
+    ))];
+    im.handle_commands(&handler, input, expected);
+    assert_global_scene(&im, &handler, true, true);
+
+    let effect = OffWithEffectReq {
+        effect_identifier: 0,
+        effect_variant: 1,
+    };
+    invoke(&im, &handler, CommandsDiscriminants::OffWithEffect, &effect);
+    assert_global_scene(&im, &handler, false, false);
+
+    let input = &[cmd_data!(
+        cmd(CommandsDiscriminants::OnWithRecallGlobalScene),
+        1
+    )];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        cmd(CommandsDiscriminants::OnWithRecallGlobalScene),
+        IMStatusCode::Success,
+        0,
+    ))];
+    im.handle_commands(&handler, input, expected);
+    assert_global_scene(&im, &handler, true, true);
+
+    // A plain Off keeps GlobalSceneControl set, so there is nothing to recall
+    let input = &[
+        cmd_data!(cmd(CommandsDiscriminants::Off), 1),
+        cmd_data!(cmd(CommandsDiscriminants::OnWithRecallGlobalScene), 1),
+    ];
+    let expected = &[
+        ExpectedInvResp::Status(CmdStatus::new(
+            cmd(CommandsDiscriminants::Off),
+            IMStatusCode::Success,
+            0,
+        )),
+        ExpectedInvResp::Status(CmdStatus::new(
+            cmd(CommandsDiscriminants::OnWithRecallGlobalScene),
+            IMStatusCode::Success,
+            0,
+        )),
+    ];
+    im.handle_commands(&handler, input, expected);
+    assert_global_scene(&im, &handler, false, true);
+}
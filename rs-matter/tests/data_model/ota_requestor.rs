@@ -0,0 +1,361 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+
+use rs_matter::{
+    bdx::{
+        self,
+        messages::{Block, BlockCounter, ReceiveAccept, TransferControl, TransferInit},
+        receiver::ImageSink,
+        PROTO_ID_BDX,
+    },
+    data_model::{
+        cluster_ota_requestor::{
+            self, AnnounceOTAProviderReq, AnnouncementReason, ApplyUpdateAction, ApplyUpdateReq,
+            ApplyUpdateResp, NotifyUpdateAppliedReq, OtaRequestorCluster, ProviderCommands,
+            ProviderRespCommands, QueryImageReq, QueryImageResp, QueryImageStatus, UpdateState,
+        },
+        device_types::DEV_TYPE_ROOT_NODE,
+        objects::{EncodeValue, Endpoint, Node},
+        root_endpoint,
+        system_model::descriptor,
+    },
+    error::Error,
+    interaction_model::{
+        client::InvokeClient,
+        core::{IMStatusCode, OpCode, PROTO_ID_INTERACTION_MODEL},
+        messages::{
+            ib::{CmdData, CmdPath, CmdStatus, InvResp},
+            msg,
+        },
+    },
+    tlv::{
+        get_root_node_struct, FromTLV, OctetStr, TLVArray, TLVElement, TLVWriter, TagType, ToTLV,
+        UtfStr,
+    },
+    transport::{network::Address, packet::Packet},
+};
+
+use crate::common::{
+    commands::*,
+    im_engine::{ImEngine, IM_ENGINE_PEER_ID},
+    init_env_logger,
+};
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[Endpoint {
+        id: 0,
+        device_type: DEV_TYPE_ROOT_NODE,
+        extra_device_types: &[],
+        clusters: &[descriptor::CLUSTER, cluster_ota_requestor::CLUSTER],
+        client_clusters: &[cluster_ota_requestor::PROVIDER_ID],
+    }],
+};
+
+// The provider - the peer of the IM Engine - serves the image itself
+const IMAGE_URI: &[u8] = b"bdx://000000000006CC7E/image.ota";
+const UPDATE_TOKEN: &[u8] = &[1, 2, 3, 4];
+const BLOCK_SIZE: u16 = 16;
+
+/// An OTA Provider, answering the messages of the requestor
+struct TestProvider {
+    image: [u8; 40],
+    block_size: usize,
+    commands: heapless::Vec<u32, 4>,
+    downloaded: bool,
+}
+
+impl TestProvider {
+    fn new() -> Self {
+        Self {
+            image: core::array::from_fn(|i| i as u8),
+            block_size: 0,
+            commands: heapless::Vec::new(),
+            downloaded: false,
+        }
+    }
+
+    fn handle(&mut self, rx: &Packet, tx: &mut Packet) -> Result<bool, Error> {
+        match rx.get_proto_id() {
+            PROTO_ID_INTERACTION_MODEL => {
+                assert_eq!(rx.get_proto_opcode::<OpCode>()?, OpCode::InvokeRequest);
+
+                let req = msg::InvReq::from_tlv(&get_root_node_struct(rx.as_slice())?)?;
+                let cmd = req.inv_requests.unwrap().iter().next().unwrap();
+
+                tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
+                tx.set_proto_opcode(OpCode::InvokeResponse as u8);
+
+                self.invoke(&cmd.path, cmd.data.unwrap_tlv().unwrap(), tx)?;
+
+                Ok(true)
+            }
+            PROTO_ID_BDX => self.transfer(rx, tx),
+            proto_id => panic!("Unexpected Proto-ID {:x}", proto_id),
+        }
+    }
+
+    fn invoke(&mut self, path: &CmdPath, data: TLVElement, tx: &mut Packet) -> Result<(), Error> {
+        assert_eq!(path.path.endpoint, Some(0));
+        assert_eq!(path.path.cluster, Some(cluster_ota_requestor::PROVIDER_ID));
+
+        let cmd = path.path.leaf.unwrap();
+        self.commands.push(cmd).unwrap();
+
+        let query_image_resp;
+        let apply_update_resp;
+
+        let resp = match ProviderCommands::from_repr(cmd).unwrap() {
+            ProviderCommands::QueryImage => {
+                let req = QueryImageReq::from_tlv(&data)?;
+                assert_eq!(req.software_version, 13);
+
+                query_image_resp = QueryImageResp {
+                    status: QueryImageStatus::UpdateAvailable as _,
+                    delayed_action_time: None,
+                    image_uri: Some(UtfStr::new(IMAGE_URI)),
+                    software_version: Some(14),
+                    software_version_string: Some(UtfStr::new(b"14")),
+                    update_token: Some(OctetStr(UPDATE_TOKEN)),
+                    user_consent_needed: None,
+                    metadata_for_requestor: None,
+                };
+
+                Self::response(ProviderRespCommands::QueryImageResponse, &query_image_resp)
+            }
+            ProviderCommands::ApplyUpdateRequest => {
+                let req = ApplyUpdateReq::from_tlv(&data)?;
+                assert_eq!(req.update_token.0, UPDATE_TOKEN);
+                assert_eq!(req.new_version, 14);
+                assert!(self.downloaded);
+
+                apply_update_resp = ApplyUpdateResp {
+                    action: ApplyUpdateAction::Proceed as _,
+                    delayed_action_time: 0,
+                };
+
+                Self::response(
+                    ProviderRespCommands::ApplyUpdateResponse,
+                    &apply_update_resp,
+                )
+            }
+            ProviderCommands::NotifyUpdateApplied => {
+                let req = NotifyUpdateAppliedReq::from_tlv(&data)?;
+                assert_eq!(req.update_token.0, UPDATE_TOKEN);
+                assert_eq!(req.software_version, 14);
+
+                InvResp::status_new(path.clone(), IMStatusCode::Success, 0)
+            }
+        };
+
+        let resps = [resp];
+        let resp = msg::InvResp {
+            suppress_response: Some(false),
+            inv_responses: Some(TLVArray::Slice(&resps)),
+        };
+
+        let mut tw = TLVWriter::new(tx.get_writebuf()?);
+        resp.to_tlv(&mut tw, TagType::Anonymous)
+    }
+
+    fn response<'a>(cmd: ProviderRespCommands, data: &'a dyn ToTLV) -> InvResp<'a> {
+        InvResp::Cmd(CmdData::new(
+            CmdPath::new(
+                Some(0),
+                Some(cluster_ota_requestor::PROVIDER_ID),
+                Some(cmd as u32),
+            ),
+            EncodeValue::Value(data),
+        ))
+    }
+
+    fn transfer(&mut self, rx: &Packet, tx: &mut Packet) -> Result<bool, Error> {
+        tx.set_proto_id(PROTO_ID_BDX);
+
+        let wb = tx.get_writebuf()?;
+
+        let opcode = match rx.get_proto_opcode::<bdx::OpCode>()? {
+            bdx::OpCode::ReceiveInit => {
+                let init = TransferInit::decode(rx.as_slice())?;
+                assert_eq!(init.file_designator, b"image.ota");
+
+                self.block_size = init.max_block_size as usize;
+
+                ReceiveAccept {
+                    version: 0,
+                    transfer_control: TransferControl::RECEIVER_DRIVE,
+                    max_block_size: init.max_block_size,
+                    start_offset: None,
+                    length: Some(self.image.len() as u64),
+                    metadata: &[],
+                }
+                .encode(wb)?;
+
+                bdx::OpCode::ReceiveAccept
+            }
+            bdx::OpCode::BlockQuery => {
+                let counter = BlockCounter::decode(rx.as_slice())?.counter;
+                let start = counter as usize * self.block_size;
+                let end = self.image.len().min(start + self.block_size);
+
+                Block {
+                    counter,
+                    data: &self.image[start..end],
+                }
+                .encode(wb)?;
+
+                if end == self.image.len() {
+                    bdx::OpCode::BlockEOF
+                } else {
+                    bdx::OpCode::Block
+                }
+            }
+            bdx::OpCode::BlockAckEOF => {
+                self.downloaded = true;
+
+                // Only acknowledged, as it ends the transfer
+                return Ok(false);
+            }
+            opcode => panic!("Unexpected BDX opcode {:?}", opcode),
+        };
+
+        tx.set_proto_opcode(opcode as u8);
+
+        Ok(true)
+    }
+}
+
+#[derive(Default)]
+struct TestSink {
+    data: heapless::Vec<u8, 64>,
+    finished: bool,
+}
+
+impl ImageSink for TestSink {
+    fn begin(&mut self, length: Option<u64>) -> Result<(), Error> {
+        assert_eq!(length, Some(40));
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Error> {
+        assert_eq!(offset, self.data.len() as u64);
+        self.data.extend_from_slice(data).unwrap();
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.finished = true;
+        Ok(())
+    }
+
+    fn abort(&mut self) {
+        panic!("Transfer aborted");
+    }
+}
+
+// The CASE session with the provider exists already, so there is nothing to resolve
+fn no_resolver(_fab_idx: u8, _node_id: u64) -> Option<Address> {
+    None
+}
+
+#[test]
+fn test_ota_update() {
+    // A full update: QueryImage, the BDX download, ApplyUpdateRequest and, after the
+    // reboot, NotifyUpdateApplied
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let rand = *im.matter.borrow();
+
+    let ota = OtaRequestorCluster::new(im.matter.dev_det(), rand);
+
+    let handler = (
+        NODE,
+        root_endpoint::handler(0, &im.matter).chain(0, cluster_ota_requestor::ID, &ota),
+    );
+
+    im.add_default_acl();
+
+    // The provider announces itself
+    let announce = AnnounceOTAProviderReq {
+        provider_node_id: IM_ENGINE_PEER_ID,
+        vendor_id: 10,
+        announcement_reason: AnnouncementReason::UpdateAvailable as _,
+        metadata_for_node: None,
+        endpoint: 0,
+    };
+    let path = CmdPath::new(
+        Some(0),
+        Some(cluster_ota_requestor::ID),
+        Some(cluster_ota_requestor::Commands::AnnounceOTAProvider as u32),
+    );
+    let input = &[CmdData::new(path.clone(), EncodeValue::Value(&announce))];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        path,
+        IMStatusCode::Success,
+        0,
+    ))];
+    im.handle_commands(&handler, input, expected);
+
+    let mut provider = TestProvider::new();
+    let mut sink = TestSink::default();
+    let mut image = None;
+
+    im.run_peer(
+        &handler,
+        async {
+            let mut client = InvokeClient::new(&im.matter, no_resolver);
+
+            let update = ota
+                .update(&mut client, &mut sink, BLOCK_SIZE)
+                .await?
+                .unwrap();
+            assert_eq!(ota.update_state(), UpdateState::Applying);
+
+            // After installing the image and rebooting into it
+            ota.notify_update_applied(&mut client, &update).await?;
+
+            image = Some(update);
+
+            Ok::<_, Error>(())
+        },
+        |rx, tx| provider.handle(rx, tx),
+    )
+    .unwrap();
+
+    let image = image.unwrap();
+    assert_eq!(image.node_id, IM_ENGINE_PEER_ID);
+    assert_eq!(image.software_version, 14);
+
+    assert_eq!(
+        provider.commands.as_slice(),
+        &[
+            ProviderCommands::QueryImage as u32,
+            ProviderCommands::ApplyUpdateRequest as u32,
+            ProviderCommands::NotifyUpdateApplied as u32,
+        ]
+    );
+    assert!(provider.downloaded);
+
+    assert!(sink.finished);
+    assert_eq!(sink.data.as_slice(), &provider.image);
+
+    assert_eq!(ota.update_state(), UpdateState::Idle);
+    assert_eq!(ota.update_state_progress(), None);
+}
@@ -0,0 +1,274 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+use core::cell::Cell;
+
+use rs_matter::{
+    data_model::{
+        device_types::DEV_TYPE_ON_OFF_LIGHT,
+        objects::{Access, Dataver, EncodeValue, Endpoint, Node, Quality},
+        root_endpoint,
+        system_model::descriptor::{self, DescriptorCluster},
+    },
+    error::Error,
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrData, AttrPath, AttrResp, AttrStatus, CmdData, CmdPath, CmdStatus},
+        messages::GenericPath,
+    },
+    tlv::{ElementType, TLVElement, TagType},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+
+use crate::{
+    attr_data_path, attr_status, cmd_data,
+    common::{attributes::*, commands::*, im_engine::ImEngine, init_env_logger},
+};
+
+rs_matter::data_model::import!("tests/zcl/onoff-cluster.xml");
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[
+        root_endpoint::endpoint(0),
+        Endpoint {
+            id: 1,
+            device_type: DEV_TYPE_ON_OFF_LIGHT,
+            extra_device_types: &[],
+            clusters: &[descriptor::CLUSTER, on_off::CLUSTER],
+            client_clusters: &[],
+        },
+    ],
+};
+
+/// A light implementing the generated handler trait, with only some of the
+/// optional attributes and commands
+struct Light {
+    data_ver: Dataver,
+    on: Cell<bool>,
+    on_time: Cell<u16>,
+}
+
+impl Light {
+    fn new(rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            on: Cell::new(false),
+            on_time: Cell::new(0),
+        }
+    }
+
+    fn set_on(&self, on: bool) {
+        if self.on.get() != on {
+            self.on.set(on);
+            self.data_ver.changed();
+        }
+    }
+}
+
+impl on_off::ClusterHandler for Light {
+    fn dataver(&self) -> u32 {
+        self.data_ver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.data_ver.changed();
+    }
+
+    fn on_off(&self) -> Result<bool, Error> {
+        Ok(self.on.get())
+    }
+
+    fn on_time(&self) -> Result<u16, Error> {
+        Ok(self.on_time.get())
+    }
+
+    fn set_on_time(&self, value: u16) -> Result<(), Error> {
+        self.on_time.set(value);
+
+        Ok(())
+    }
+
+    fn handle_off(&self, _exchange: &Exchange) -> Result<(), Error> {
+        self.set_on(false);
+
+        Ok(())
+    }
+
+    fn handle_on(&self, _exchange: &Exchange) -> Result<(), Error> {
+        self.set_on(true);
+
+        Ok(())
+    }
+
+    fn handle_toggle(&self, _exchange: &Exchange) -> Result<(), Error> {
+        self.set_on(!self.on.get());
+
+        Ok(())
+    }
+
+    fn handle_on_with_timed_off(
+        &self,
+        _exchange: &Exchange,
+        request: &on_off::OnWithTimedOffRequest,
+    ) -> Result<(), Error> {
+        if request
+            .on_off_control
+            .contains(on_off::OnOffControlBitmap::ACCEPT_ONLY_WHEN_ON)
+            && !self.on.get()
+        {
+            return Ok(());
+        }
+
+        self.on_time.set(request.on_time);
+        self.set_on(true);
+
+        Ok(())
+    }
+}
+
+fn attr(attr: on_off::Attributes) -> GenericPath {
+    GenericPath::new(Some(1), Some(on_off::ID), Some(attr as u32))
+}
+
+fn cmd(cmd: on_off::Commands) -> CmdPath {
+    CmdPath::new(Some(1), Some(on_off::ID), Some(cmd as u32))
+}
+
+#[test]
+fn test_zap_import_metadata() {
+    // The metadata generated from the XML file matches the one of the cluster
+    assert_eq!(on_off::ID, 0x0006);
+    assert_eq!(
+        on_off::Attributes::from_repr(0x4003),
+        Some(on_off::Attributes::StartUpOnOff)
+    );
+    assert_eq!(
+        on_off::Commands::from_repr(0x42),
+        Some(on_off::Commands::OnWithTimedOff)
+    );
+    assert_eq!(on_off::StartUpOnOffEnum::Toggle as u8, 2);
+    assert_eq!(on_off::Feature::LIGHTING.bits(), 1);
+
+    // FeatureMap, AttributeList and the 5 attributes of the cluster
+    assert_eq!(on_off::CLUSTER.attributes.len(), 7);
+    assert_eq!(on_off::CLUSTER.commands.len(), 6);
+
+    let start_up = on_off::Attributes::StartUpOnOff.attribute();
+    assert!(start_up.access.contains(Access::RWVM));
+    assert!(start_up
+        .quality
+        .contains(Quality::NULLABLE | Quality::PERSISTENT));
+
+    let onoff = on_off::Attributes::OnOff.attribute();
+    assert!(!onoff.access.contains(Access::WRITE));
+    assert!(onoff.quality.contains(Quality::SCENE));
+}
+
+#[test]
+fn test_zap_import_handler() {
+    // The generated handler serves the attributes and commands of the light
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let rand = *im.matter.borrow();
+
+    let handler = (
+        NODE,
+        root_endpoint::handler(0, &im.matter)
+            .chain(1, descriptor::ID, DescriptorCluster::new(rand))
+            .chain(1, on_off::ID, on_off::HandlerAdaptor(Light::new(rand))),
+    );
+
+    im.add_default_acl();
+
+    let onoff = attr(on_off::Attributes::OnOff);
+    let on_time = attr(on_off::Attributes::OnTime);
+    let start_up = attr(on_off::Attributes::StartUpOnOff);
+
+    // Optional attributes not implemented by the light are not supported
+    let input = &[
+        AttrPath::new(&onoff),
+        AttrPath::new(&on_time),
+        AttrPath::new(&start_up),
+    ];
+    let expected = &[
+        attr_data_path!(onoff, ElementType::False),
+        attr_data_path!(on_time, ElementType::U8(0)),
+        attr_status!(&start_up, IMStatusCode::UnsupportedAttribute),
+    ];
+    im.handle_read_reqs(&handler, input, expected);
+
+    let input = &[cmd_data!(cmd(on_off::Commands::Toggle), 1)];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        cmd(on_off::Commands::Toggle),
+        IMStatusCode::Success,
+        0,
+    ))];
+    im.handle_commands(&handler, input, expected);
+
+    let input = &[AttrPath::new(&onoff)];
+    let expected = &[attr_data_path!(onoff, ElementType::True)];
+    im.handle_read_reqs(&handler, input, expected);
+
+    // The request struct of a command is decoded, including its bitmap
+    let request = on_off::OnWithTimedOffRequest {
+        on_off_control: on_off::OnOffControlBitmap::ACCEPT_ONLY_WHEN_ON,
+        on_time: 300,
+        off_wait_time: 0,
+    };
+    let input = &[
+        CmdData::new(
+            cmd(on_off::Commands::OnWithTimedOff),
+            EncodeValue::Value(&request),
+        ),
+        cmd_data!(cmd(on_off::Commands::OnWithRecallGlobalScene), 1),
+    ];
+    let expected = &[
+        ExpectedInvResp::Status(CmdStatus::new(
+            cmd(on_off::Commands::OnWithTimedOff),
+            IMStatusCode::Success,
+            0,
+        )),
+        ExpectedInvResp::Status(CmdStatus::new(
+            cmd(on_off::Commands::OnWithRecallGlobalScene),
+            IMStatusCode::UnsupportedCommand,
+            0,
+        )),
+    ];
+    im.handle_commands(&handler, input, expected);
+
+    // Writable attributes go through the generated setters
+    let input = &[
+        AttrData::new(None, AttrPath::new(&on_time), EncodeValue::Value(&600_u16)),
+        AttrData::new(None, AttrPath::new(&onoff), EncodeValue::Value(&false)),
+    ];
+    let expected = &[
+        AttrStatus::new(&on_time, IMStatusCode::Success, 0),
+        AttrStatus::new(&onoff, IMStatusCode::UnsupportedWrite, 0),
+    ];
+    im.handle_write_reqs(&handler, input, expected);
+
+    let input = &[AttrPath::new(&onoff), AttrPath::new(&on_time)];
+    let expected = &[
+        attr_data_path!(onoff, ElementType::True),
+        attr_data_path!(on_time, ElementType::U16(600)),
+    ];
+    im.handle_read_reqs(&handler, input, expected);
+}
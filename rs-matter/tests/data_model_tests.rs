@@ -19,9 +19,20 @@ mod common;
 
 mod data_model {
     mod acl_and_dataver;
+    mod admin_commissioning;
+    mod attr_persist;
     mod attribute_lists;
     mod attributes;
+    mod bridge;
     mod commands;
+    mod diagnostics;
+    mod general_commissioning;
+    mod identify;
+    mod level_control;
+    mod localization;
     mod long_reads;
+    mod on_off;
+    mod ota_requestor;
     mod timed_requests;
+    mod zap_import;
 }